@@ -0,0 +1,286 @@
+/*
+ * MyLobster WebChat widget.
+ *
+ * Embed with:
+ *   <script src="https://<gateway>/webchat/widget.js" async></script>
+ *
+ * Optional attributes on the script tag:
+ *   data-token   visitor token signed by your site (HS256, see docs/channels.md)
+ *   data-open    "true" to open the panel on load
+ *   data-inline  "true" to fill the containing page instead of floating
+ */
+(function () {
+  "use strict";
+
+  var script = document.currentScript;
+  if (!script || window.__mylobsterWebChat) return;
+  window.__mylobsterWebChat = true;
+
+  var base = new URL(script.src, location.href);
+  var wsUrl = (base.protocol === "https:" ? "wss://" : "ws://") + base.host + "/ws/webchat";
+  var storageKey = "mylobster-webchat:" + base.host;
+  var signedToken = script.getAttribute("data-token");
+  var inline = script.getAttribute("data-inline") === "true";
+
+  var socket = null;
+  var retryMs = 1000;
+  var lastId = null;
+  var lastRead = 0;
+  var pending = {};
+  var open = inline || script.getAttribute("data-open") === "true";
+
+  function stored() {
+    try {
+      return localStorage.getItem(storageKey);
+    } catch (e) {
+      return null;
+    }
+  }
+
+  function store(token) {
+    try {
+      localStorage.setItem(storageKey, token);
+    } catch (e) {
+      /* storage unavailable: the visitor starts over on reload */
+    }
+  }
+
+  // --------------------------------------------------------------------------
+  // DOM
+  // --------------------------------------------------------------------------
+
+  var host = document.createElement("div");
+  var root = host.attachShadow ? host.attachShadow({ mode: "open" }) : host;
+  root.innerHTML =
+    "<style>" +
+    ":host{all:initial}" +
+    ".btn{position:fixed;right:20px;bottom:20px;width:56px;height:56px;border-radius:50%;border:0;" +
+    "background:#d9480f;color:#fff;font:24px sans-serif;cursor:pointer;box-shadow:0 4px 12px rgba(0,0,0,.25)}" +
+    ".badge{position:absolute;top:-2px;right:-2px;min-width:18px;height:18px;border-radius:9px;" +
+    "background:#1c7ed6;font:12px/18px sans-serif;display:none}" +
+    ".panel{position:fixed;right:20px;bottom:88px;width:360px;max-width:calc(100vw - 40px);height:520px;" +
+    "max-height:calc(100vh - 120px);display:none;flex-direction:column;background:#fff;border-radius:12px;" +
+    "box-shadow:0 8px 28px rgba(0,0,0,.25);overflow:hidden;font:14px/1.4 system-ui,sans-serif;color:#212529}" +
+    ".panel.inline{position:static;width:100%;max-width:none;height:100vh;max-height:none;border-radius:0}" +
+    ".open .panel{display:flex}" +
+    ".head{padding:12px 16px;background:#d9480f;color:#fff;font-weight:600}" +
+    ".log{flex:1;overflow-y:auto;padding:12px;display:flex;flex-direction:column;gap:8px;background:#f8f9fa}" +
+    ".msg{max-width:80%;padding:8px 12px;border-radius:12px;white-space:pre-wrap;word-wrap:break-word}" +
+    ".visitor{align-self:flex-end;background:#d9480f;color:#fff}" +
+    ".assistant{align-self:flex-start;background:#fff;border:1px solid #dee2e6}" +
+    ".pending{opacity:.6}.failed{background:#fa5252}" +
+    ".meta{align-self:flex-end;font-size:11px;color:#868e96}" +
+    ".typing{padding:0 12px 6px;font-size:12px;color:#868e96;background:#f8f9fa;visibility:hidden}" +
+    "form{display:flex;border-top:1px solid #dee2e6}" +
+    "textarea{flex:1;border:0;padding:12px;resize:none;font:inherit;outline:none}" +
+    "button.send{border:0;background:none;color:#d9480f;font-weight:600;padding:0 16px;cursor:pointer}" +
+    "</style>" +
+    '<div class="wrap">' +
+    (inline ? "" : '<button class="btn" aria-label="Chat">&#128172;<span class="badge"></span></button>') +
+    '<div class="panel' + (inline ? " inline" : "") + '" role="dialog">' +
+    '<div class="head">Chat</div>' +
+    '<div class="log" aria-live="polite"></div>' +
+    '<div class="typing">Typing&hellip;</div>' +
+    '<form><textarea rows="2" placeholder="Type a message" maxlength="4000"></textarea>' +
+    '<button class="send" type="submit">Send</button></form>' +
+    "</div></div>";
+
+  var wrap = root.querySelector(".wrap");
+  var button = root.querySelector(".btn");
+  var badge = root.querySelector(".badge");
+  var head = root.querySelector(".head");
+  var log = root.querySelector(".log");
+  var typing = root.querySelector(".typing");
+  var form = root.querySelector("form");
+  var input = root.querySelector("textarea");
+  var seen = {};
+  var greeting = null;
+
+  function setOpen(value) {
+    open = value;
+    wrap.classList.toggle("open", open);
+    if (open) {
+      markRead();
+      input.focus();
+    }
+  }
+
+  function render(message, pendingEl) {
+    if (seen[message.id]) return;
+    var el = pendingEl || document.createElement("div");
+    el.className = "msg " + message.role;
+    el.textContent = message.text;
+    if (!pendingEl) log.appendChild(el);
+    seen[message.id] = el;
+    if (greeting) {
+      greeting.remove();
+      greeting = null;
+    }
+    if (typeof message.id === "number" && (lastId === null || message.id > lastId)) {
+      lastId = message.id;
+    }
+    log.scrollTop = log.scrollHeight;
+  }
+
+  function unread() {
+    var count = 0;
+    Object.keys(seen).forEach(function (id) {
+      if (Number(id) > lastRead && seen[id].classList.contains("assistant")) count++;
+    });
+    return count;
+  }
+
+  function updateBadge() {
+    if (!badge) return;
+    var count = unread();
+    badge.textContent = count;
+    badge.style.display = count && !open ? "block" : "none";
+  }
+
+  function markRead() {
+    if (lastId !== null && lastId > lastRead) {
+      lastRead = lastId;
+      send({ type: "read", id: lastId });
+    }
+    updateBadge();
+  }
+
+  // --------------------------------------------------------------------------
+  // Connection
+  // --------------------------------------------------------------------------
+
+  function send(frame) {
+    if (socket && socket.readyState === WebSocket.OPEN) {
+      socket.send(JSON.stringify(frame));
+      return true;
+    }
+    return false;
+  }
+
+  function connect() {
+    var params = [];
+    var token = signedToken || stored();
+    if (token) params.push("token=" + encodeURIComponent(token));
+    if (lastId !== null) params.push("since=" + lastId);
+    socket = new WebSocket(wsUrl + (params.length ? "?" + params.join("&") : ""));
+
+    socket.onmessage = function (event) {
+      var frame = JSON.parse(event.data);
+      switch (frame.type) {
+        case "session":
+          retryMs = 1000;
+          if (frame.token) store(frame.token);
+          head.textContent = frame.title;
+          lastRead = Math.max(lastRead, frame.lastRead || 0);
+          if (frame.greeting && lastId === null && !greeting) {
+            greeting = document.createElement("div");
+            greeting.className = "msg assistant";
+            greeting.textContent = frame.greeting;
+            log.appendChild(greeting);
+          }
+          break;
+        case "history":
+          frame.messages.forEach(function (m) {
+            render(m);
+          });
+          break;
+        case "ack":
+          var el = pending[frame.clientId];
+          if (el) {
+            delete pending[frame.clientId];
+            el.classList.remove("pending");
+            render({ id: frame.id, role: "visitor", text: el.textContent }, el);
+          }
+          break;
+        case "message":
+          var waiting = frame.message.clientId && pending[frame.message.clientId];
+          if (!waiting && frame.message.id) render(frame.message);
+          else if (!frame.message.id) render({ id: "b" + Date.now(), role: "assistant", text: frame.message.text });
+          break;
+        case "read":
+          var read = seen[frame.id];
+          if (read && !read.nextSibling) {
+            var meta = document.createElement("div");
+            meta.className = "meta";
+            meta.textContent = "Seen";
+            log.appendChild(meta);
+          }
+          break;
+        case "typing":
+          typing.style.visibility = frame.state ? "visible" : "hidden";
+          break;
+        case "error":
+          var failed = frame.clientId && pending[frame.clientId];
+          if (failed) {
+            delete pending[frame.clientId];
+            failed.classList.add("failed");
+            failed.title = frame.code === "rate_limited" ? "Too many messages, try again shortly" : "Not sent";
+          }
+          break;
+      }
+      if (open) markRead();
+      else updateBadge();
+    };
+
+    socket.onclose = function (event) {
+      typing.style.visibility = "hidden";
+      if (event.code === 1008) return;
+      setTimeout(connect, retryMs);
+      retryMs = Math.min(retryMs * 2, 30000);
+    };
+  }
+
+  form.addEventListener("submit", function (event) {
+    event.preventDefault();
+    var text = input.value.trim();
+    if (!text) return;
+    var clientId = Math.random().toString(36).slice(2);
+    var el = document.createElement("div");
+    el.className = "msg visitor pending";
+    el.textContent = text;
+    log.appendChild(el);
+    log.scrollTop = log.scrollHeight;
+    pending[clientId] = el;
+    if (!send({ type: "message", text: text, clientId: clientId })) {
+      delete pending[clientId];
+      el.classList.add("failed");
+    }
+    input.value = "";
+  });
+
+  input.addEventListener("keydown", function (event) {
+    if (event.key === "Enter" && !event.shiftKey) {
+      event.preventDefault();
+      form.requestSubmit ? form.requestSubmit() : form.dispatchEvent(new Event("submit"));
+    }
+  });
+
+  var typingTimer = null;
+  input.addEventListener("input", function () {
+    if (!typingTimer) send({ type: "typing", state: true });
+    clearTimeout(typingTimer);
+    typingTimer = setTimeout(function () {
+      typingTimer = null;
+      send({ type: "typing", state: false });
+    }, 3000);
+  });
+
+  if (button) {
+    button.addEventListener("click", function () {
+      setOpen(!open);
+    });
+  }
+
+  setInterval(function () {
+    send({ type: "ping" });
+  }, 30000);
+
+  function mount() {
+    document.body.appendChild(host);
+    setOpen(open);
+    connect();
+  }
+
+  if (document.body) mount();
+  else document.addEventListener("DOMContentLoaded", mount);
+})();
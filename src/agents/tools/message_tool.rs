@@ -12,7 +12,7 @@ impl AgentTool for MessageSendTool {
     fn info(&self) -> ToolInfo {
         ToolInfo {
             name: "message_send".to_string(),
-            description: "Send a message to a specific channel (telegram, discord, slack, whatsapp, signal, imessage)".to_string(),
+            description: "Send a message to a specific channel (telegram, discord, slack, whatsapp, signal, imessage, irc, matrix, ...)".to_string(),
             category: "messaging".to_string(),
             hidden: false,
             input_schema: serde_json::json!({
@@ -20,8 +20,11 @@ impl AgentTool for MessageSendTool {
                 "properties": {
                     "channel": {
                         "type": "string",
-                        "description": "Target channel (telegram, discord, slack, whatsapp, signal, imessage)",
-                        "enum": ["telegram", "discord", "slack", "whatsapp", "signal", "imessage", "synology_chat"]
+                        "description": "Target channel id (telegram, discord, slack, whatsapp, signal, imessage, irc, matrix, mattermost, ...)"
+                    },
+                    "accountId": {
+                        "type": "string",
+                        "description": "Channel account to send from (defaults to the channel's default account)"
                     },
                     "to": {
                         "type": "string",
@@ -53,6 +56,8 @@ impl AgentTool for MessageSendTool {
             .and_then(|v| v.as_str())
             .ok_or_else(|| anyhow::anyhow!("Missing channel parameter"))?;
 
+        let account_id = params.get("accountId").and_then(|v| v.as_str());
+
         let to = params
             .get("to")
             .and_then(|v| v.as_str())
@@ -65,9 +70,10 @@ impl AgentTool for MessageSendTool {
 
         tracing::info!(channel, to, chars = text.len(), "sending message via tool");
 
-        match crate::channels::send_message(&context.config, channel, to, text).await {
-            Ok(()) => Ok(ToolResult::json(serde_json::json!({
+        match crate::channels::send_message(&context.config, channel, account_id, to, text).await {
+            Ok(id) => Ok(ToolResult::json(serde_json::json!({
                 "sent": true,
+                "id": id,
                 "channel": channel,
                 "to": to,
                 "chars": text.len()
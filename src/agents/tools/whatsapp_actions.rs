@@ -3,6 +3,7 @@
 //! Supports: react, sendMessage with target auth and allowlist.
 
 use super::{AgentTool, ToolContext, ToolInfo, ToolResult};
+use crate::channels::WhatsAppChannel;
 use anyhow::Result;
 use async_trait::async_trait;
 
@@ -49,14 +50,6 @@ impl AgentTool for WhatsAppActionsTool {
             .and_then(|v| v.as_str())
             .ok_or_else(|| anyhow::anyhow!("Missing 'to' parameter"))?;
 
-        let api_token = std::env::var("WHATSAPP_API_TOKEN")
-            .ok()
-            .ok_or_else(|| anyhow::anyhow!("No WhatsApp API token configured (WHATSAPP_API_TOKEN)"))?;
-
-        let phone_id = std::env::var("WHATSAPP_PHONE_NUMBER_ID")
-            .ok()
-            .ok_or_else(|| anyhow::anyhow!("No WhatsApp phone number ID configured (WHATSAPP_PHONE_NUMBER_ID)"))?;
-
         // Allowlist check
         if let Some(ref allowlist) = context.config.channels.whatsapp.default_account.allow_from {
             if !allowlist.is_empty() && !allowlist.contains(&to.to_string()) {
@@ -67,11 +60,7 @@ impl AgentTool for WhatsAppActionsTool {
             }
         }
 
-        let client = reqwest::Client::new();
-        let base_url = format!(
-            "https://graph.facebook.com/v18.0/{}/messages",
-            phone_id
-        );
+        let channel = WhatsAppChannel::new(&context.config);
 
         match action {
             "sendMessage" => {
@@ -80,20 +69,14 @@ impl AgentTool for WhatsAppActionsTool {
                     .and_then(|v| v.as_str())
                     .ok_or_else(|| anyhow::anyhow!("Missing text parameter"))?;
 
-                let resp = client
-                    .post(&base_url)
-                    .header("Authorization", format!("Bearer {}", api_token))
-                    .json(&serde_json::json!({
-                        "messaging_product": "whatsapp",
-                        "to": to,
-                        "type": "text",
-                        "text": { "body": text }
-                    }))
-                    .send()
-                    .await?;
-
-                let result: serde_json::Value = resp.json().await?;
-                Ok(ToolResult::json(result))
+                match crate::channels::send_message(&context.config, "whatsapp", None, to, text)
+                    .await
+                {
+                    Ok(id) => Ok(ToolResult::json(
+                        serde_json::json!({ "ok": true, "id": id }),
+                    )),
+                    Err(e) => Ok(ToolResult::error(e.to_string())),
+                }
             }
             "react" => {
                 let message_id = params
@@ -105,23 +88,13 @@ impl AgentTool for WhatsAppActionsTool {
                     .and_then(|v| v.as_str())
                     .ok_or_else(|| anyhow::anyhow!("Missing emoji parameter"))?;
 
-                let resp = client
-                    .post(&base_url)
-                    .header("Authorization", format!("Bearer {}", api_token))
-                    .json(&serde_json::json!({
-                        "messaging_product": "whatsapp",
-                        "to": to,
-                        "type": "reaction",
-                        "reaction": {
-                            "message_id": message_id,
-                            "emoji": emoji
-                        }
-                    }))
-                    .send()
-                    .await?;
-
-                let result: serde_json::Value = resp.json().await?;
-                Ok(ToolResult::json(result))
+                match channel
+                    .react(to.trim_start_matches('+'), message_id, emoji)
+                    .await
+                {
+                    Ok(()) => Ok(ToolResult::json(serde_json::json!({ "ok": true }))),
+                    Err(e) => Ok(ToolResult::error(e.to_string())),
+                }
             }
             _ => Ok(ToolResult::error(format!(
                 "Unknown WhatsApp action: {}",
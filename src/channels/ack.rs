@@ -0,0 +1,255 @@
+//! Acknowledgement reactions on inbound messages.
+//!
+//! When an agent run starts for a channel message, the message gets the
+//! `messages.ackReaction` emoji (say 👀) so the sender knows it was seen.
+//! `messages.ackReactionScope` picks the messages that get one:
+//!
+//! - `group-mentions` (default): group messages that mention the bot.
+//! - `group-all`: every group message that starts a run.
+//! - `direct`: direct messages only.
+//! - `all`: both.
+//! - `off` / `none`: never.
+//!
+//! WhatsApp accounts can override this with their own `ackReaction`
+//! (`emoji`, `direct`, `group`: `always` / `mentions` / `never`).
+//!
+//! With `messages.removeAckAfterReply` the reaction is removed once the
+//! reply is ready. Channels that cannot react get a typing indicator
+//! instead, when they support one.
+
+use super::admission::account;
+use super::normalize::{ChatType, NormalizedMessage};
+use super::plugin::UnsupportedCapability;
+use crate::config::{Config, WhatsAppAckReaction};
+use crate::gateway::GatewayState;
+
+use tracing::{debug, warn};
+
+/// Which messages get an ack reaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AckScope {
+    All,
+    Direct,
+    GroupAll,
+    GroupMentions,
+    Off,
+}
+
+impl AckScope {
+    fn parse(scope: Option<&str>) -> Self {
+        match scope.map(str::to_ascii_lowercase).as_deref() {
+            Some("all") => Self::All,
+            Some("direct" | "dm") => Self::Direct,
+            Some("group-all") => Self::GroupAll,
+            Some("off" | "none") => Self::Off,
+            _ => Self::GroupMentions,
+        }
+    }
+
+    fn applies(self, msg: &NormalizedMessage) -> bool {
+        let group = msg.chat_type != ChatType::Dm;
+        match self {
+            Self::All => true,
+            Self::Direct => !group,
+            Self::GroupAll => group,
+            Self::GroupMentions => group && msg.mentioned,
+            Self::Off => false,
+        }
+    }
+}
+
+/// The emoji to acknowledge `msg` with, if any.
+pub fn ack_emoji(config: &Config, msg: &NormalizedMessage) -> Option<String> {
+    let messages = &config.messages;
+    let default_emoji = messages
+        .ack_reaction
+        .as_deref()
+        .map(str::trim)
+        .filter(|e| !e.is_empty());
+
+    if msg.channel == "whatsapp" {
+        let whatsapp = &config.channels.whatsapp;
+        let account = account(
+            &whatsapp.accounts,
+            &whatsapp.default_account,
+            &msg.account_id,
+        );
+        if let Some(ack) = &account.ack_reaction {
+            return whatsapp_ack(ack, msg, default_emoji);
+        }
+    }
+
+    let emoji = default_emoji?;
+    AckScope::parse(messages.ack_reaction_scope.as_deref())
+        .applies(msg)
+        .then(|| emoji.to_string())
+}
+
+fn whatsapp_ack(
+    ack: &WhatsAppAckReaction,
+    msg: &NormalizedMessage,
+    default_emoji: Option<&str>,
+) -> Option<String> {
+    let emoji = ack
+        .emoji
+        .as_deref()
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+        .or(default_emoji)?;
+    let applies = match msg.chat_type {
+        ChatType::Dm => ack.direct != Some(false),
+        ChatType::Group | ChatType::Thread => match ack.group.as_deref() {
+            Some("always") => true,
+            Some("never") => false,
+            _ => msg.mentioned,
+        },
+    };
+    applies.then(|| emoji.to_string())
+}
+
+/// An ack reaction placed on an inbound message.
+pub struct Ack {
+    channel: String,
+    account_id: String,
+    chat_id: String,
+    message_id: String,
+}
+
+/// Acknowledge `msg` as its run starts.
+///
+/// Returns the placed reaction, or `None` when no ack applies or the
+/// channel cannot react (it then gets a typing indicator instead).
+pub async fn acknowledge(
+    state: &GatewayState,
+    config: &Config,
+    msg: &NormalizedMessage,
+) -> Option<Ack> {
+    let emoji = ack_emoji(config, msg)?;
+    // Discord threads are channels of their own.
+    let chat_id = match (&msg.thread_id, msg.channel.as_str()) {
+        (Some(thread), "discord") => thread.clone(),
+        _ => msg.chat_id.clone(),
+    };
+
+    match state
+        .channels
+        .react(&msg.channel, &msg.account_id, &chat_id, &msg.id, &emoji)
+        .await
+    {
+        Ok(()) => Some(Ack {
+            channel: msg.channel.clone(),
+            account_id: msg.account_id.clone(),
+            chat_id,
+            message_id: msg.id.clone(),
+        }),
+        Err(e) if e.downcast_ref::<UnsupportedCapability>().is_some() => {
+            debug!(channel = %msg.channel, "No reactions; acknowledging with typing");
+            let _ = state
+                .channels
+                .send_typing(
+                    &msg.channel,
+                    &msg.account_id,
+                    &msg.chat_id,
+                    msg.thread_id.as_deref(),
+                )
+                .await;
+            None
+        }
+        Err(e) => {
+            warn!(channel = %msg.channel, message = %msg.id, error = %e, "Ack reaction failed");
+            None
+        }
+    }
+}
+
+impl Ack {
+    /// Remove the reaction once the reply is ready, if
+    /// `messages.removeAckAfterReply` is set.
+    pub async fn finish(self, state: &GatewayState, config: &Config) {
+        if config.messages.remove_ack_after_reply != Some(true) {
+            return;
+        }
+        if let Err(e) = state
+            .channels
+            .react(
+                &self.channel,
+                &self.account_id,
+                &self.chat_id,
+                &self.message_id,
+                "",
+            )
+            .await
+        {
+            debug!(channel = %self.channel, error = %e, "Removing ack reaction failed");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::NormalizedSender;
+
+    fn message(channel: &str, chat_type: ChatType, mentioned: bool) -> NormalizedMessage {
+        NormalizedMessage {
+            id: "m1".to_string(),
+            channel: channel.to_string(),
+            account_id: "default".to_string(),
+            chat_id: "c1".to_string(),
+            chat_name: None,
+            chat_type,
+            sender: NormalizedSender {
+                id: "u1".to_string(),
+                name: "Ann".to_string(),
+                is_bot: false,
+                roles: Vec::new(),
+            },
+            text: "hi".to_string(),
+            attachments: Vec::new(),
+            reply_to_id: None,
+            thread_id: None,
+            mentioned,
+            timestamp: String::new(),
+            raw: None,
+        }
+    }
+
+    #[test]
+    fn scope_selects_messages() {
+        let mut config = Config::default();
+        let dm = message("telegram", ChatType::Dm, true);
+        let mention = message("telegram", ChatType::Group, true);
+        let chatter = message("telegram", ChatType::Group, false);
+        assert_eq!(ack_emoji(&config, &mention), None);
+
+        config.messages.ack_reaction = Some("👀".to_string());
+        assert_eq!(ack_emoji(&config, &mention).as_deref(), Some("👀"));
+        assert_eq!(ack_emoji(&config, &chatter), None);
+        assert_eq!(ack_emoji(&config, &dm), None);
+
+        config.messages.ack_reaction_scope = Some("direct".to_string());
+        assert_eq!(ack_emoji(&config, &dm).as_deref(), Some("👀"));
+        assert_eq!(ack_emoji(&config, &mention), None);
+
+        config.messages.ack_reaction_scope = Some("all".to_string());
+        assert!(ack_emoji(&config, &chatter).is_some());
+        config.messages.ack_reaction_scope = Some("off".to_string());
+        assert!(ack_emoji(&config, &dm).is_none());
+    }
+
+    #[test]
+    fn whatsapp_account_overrides() {
+        let mut config = Config::default();
+        config.messages.ack_reaction = Some("👀".to_string());
+        config.channels.whatsapp.default_account.ack_reaction = Some(WhatsAppAckReaction {
+            emoji: Some("✅".to_string()),
+            direct: Some(false),
+            group: Some("always".to_string()),
+        });
+
+        let dm = message("whatsapp", ChatType::Dm, true);
+        let chatter = message("whatsapp", ChatType::Group, false);
+        assert_eq!(ack_emoji(&config, &dm), None);
+        assert_eq!(ack_emoji(&config, &chatter).as_deref(), Some("✅"));
+    }
+}
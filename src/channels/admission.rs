@@ -0,0 +1,741 @@
+//! Admission of inbound direct messages.
+//!
+//! Every message passes through [`Admission::check`] before it reaches a
+//! session (see [`super::inbound::dispatch_inbound_to_session`]). Direct
+//! messages are checked against the channel account's `dmPolicy` and
+//! `allowFrom`:
+//!
+//! - `open`: everyone is admitted.
+//! - `allowlist`: only senders matching `allowFrom`.
+//! - `pairing`: senders matching `allowFrom`, plus senders an operator has
+//!   approved. Anyone else is sent a one-time code, which the operator
+//!   approves with the `channels.pairing.approve` RPC.
+//! - `disabled`: nobody.
+//!
+//! Without an explicit policy, an empty `allowFrom` means open and a
+//! non-empty one means allowlist. Denied senders get a short notice at most
+//! once per [`NOTICE_INTERVAL`], or nothing when
+//! `channels.defaults.denyReply` is false.
+//!
+//! Group and thread messages are gated by the shared mention stage,
+//! [`super::group_history::admit_group_message`], with each channel's
+//! group policy and per-group overrides, since only the channel recognises
+//! mentions of the bot's own identity on its platform.
+
+use super::normalize::{ChatType, NormalizedMessage};
+use crate::config::{Config, DmPolicy};
+use crate::infra::dm_policy::{
+    self, check_dm_access, merge_dm_allow_from_sources, resolve_group_allow_from_sources,
+    AllowFromSource,
+};
+
+use anyhow::{anyhow, Result};
+use parking_lot::Mutex;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// How long a pairing code stays valid.
+pub const PAIRING_CODE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Minimum time between notices to the same denied sender.
+pub const NOTICE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Pairing code alphabet, without look-alikes (0/O, 1/I/L).
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
+const CODE_LEN: usize = 8;
+
+/// Pending requests are capped so a flood of strangers cannot grow memory.
+const MAX_PENDING: usize = 256;
+
+const DENIED_NOTICE: &str = "Sorry, this assistant only talks to approved contacts.";
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// Outcome of admission for one message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// Pass the message on to its session.
+    Admit,
+    /// Do not run the agent; send this text to the sender instead.
+    Reply(String),
+    /// Do not run the agent and say nothing.
+    Drop,
+}
+
+/// A sender waiting for an operator to approve their pairing code.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PairingRequest {
+    pub code: String,
+    pub channel: String,
+    pub account_id: String,
+    pub sender_id: String,
+    pub sender_name: String,
+    /// RFC 3339 time the code was issued.
+    pub requested_at: String,
+    #[serde(skip)]
+    issued: Option<Instant>,
+}
+
+/// A sender approved through pairing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PairedSender {
+    pub channel: String,
+    pub account_id: String,
+    pub sender_id: String,
+    pub sender_name: String,
+    /// RFC 3339 time of approval.
+    pub approved_at: String,
+}
+
+/// DM policy and allowlist for one channel account.
+#[derive(Debug, Clone, Default)]
+struct DmAccess {
+    policy: Option<DmPolicy>,
+    allow_from: Vec<String>,
+}
+
+// ============================================================================
+// Admission
+// ============================================================================
+
+/// Shared admission state: approved pairings, pending codes and the notice
+/// rate limiter.
+pub struct Admission {
+    /// Where approvals are persisted; `None` keeps them in memory.
+    path: Option<PathBuf>,
+    paired: Mutex<Vec<PairedSender>>,
+    pending: Mutex<HashMap<String, PairingRequest>>,
+    notices: Mutex<HashMap<(String, String, String), Instant>>,
+}
+
+impl Admission {
+    /// Admission state kept in memory only.
+    pub fn new() -> Self {
+        Self {
+            path: None,
+            paired: Mutex::new(Vec::new()),
+            pending: Mutex::new(HashMap::new()),
+            notices: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Admission state with approvals persisted at `path`.
+    pub fn load(path: PathBuf) -> Self {
+        let paired = match std::fs::read_to_string(&path) {
+            Ok(raw) => serde_json::from_str::<Value>(&raw)
+                .ok()
+                .and_then(|v| serde_json::from_value(v["paired"].clone()).ok())
+                .unwrap_or_else(|| {
+                    warn!(path = %path.display(), "Ignoring unreadable pairing file");
+                    Vec::new()
+                }),
+            Err(_) => Vec::new(),
+        };
+        Self {
+            path: Some(path),
+            paired: Mutex::new(paired),
+            ..Self::new()
+        }
+    }
+
+    /// Decide whether `msg` may reach its session.
+    pub fn check(&self, config: &Config, msg: &NormalizedMessage) -> Verdict {
+        if msg.chat_type != ChatType::Dm {
+            return Verdict::Admit;
+        }
+
+        let access = dm_access(config, msg);
+        let pairing = access.policy == Some(DmPolicy::Pairing);
+        let mode = match access.policy {
+            Some(DmPolicy::Open) => dm_policy::DmPolicy::Open,
+            Some(DmPolicy::Disabled) => dm_policy::DmPolicy::Block,
+            Some(DmPolicy::Allowlist) | Some(DmPolicy::Pairing) => dm_policy::DmPolicy::Allowlist,
+            None if access.allow_from.is_empty() => dm_policy::DmPolicy::Open,
+            None => dm_policy::DmPolicy::Allowlist,
+        };
+
+        let mut sources = vec![AllowFromSource {
+            account_id: msg.account_id.clone(),
+            entries: access.allow_from,
+            is_group: false,
+        }];
+        if pairing {
+            sources.push(AllowFromSource {
+                account_id: msg.account_id.clone(),
+                entries: self.paired_ids(&msg.channel, &msg.account_id),
+                is_group: false,
+            });
+        }
+        let allow = merge_dm_allow_from_sources(&sources);
+        let admitted = sender_handles(msg).iter().any(|handle| {
+            check_dm_access(handle, mode, &allow, Some(&msg.account_id), false).allowed
+        });
+        if admitted {
+            return Verdict::Admit;
+        }
+
+        debug!(
+            channel = %msg.channel,
+            sender = %msg.sender.id,
+            policy = ?access.policy,
+            "Direct message not admitted"
+        );
+        if pairing {
+            return self.pairing_reply(msg);
+        }
+        let deny_reply = config
+            .channels
+            .defaults
+            .as_ref()
+            .and_then(|d| d.deny_reply)
+            .unwrap_or(true);
+        if deny_reply && self.notice_due(msg) {
+            Verdict::Reply(DENIED_NOTICE.to_string())
+        } else {
+            Verdict::Drop
+        }
+    }
+
+    /// Issue (or repeat) the sender's pairing code.
+    fn pairing_reply(&self, msg: &NormalizedMessage) -> Verdict {
+        let mut pending = self.pending.lock();
+        pending.retain(|_, r| r.issued.is_some_and(|at| at.elapsed() < PAIRING_CODE_TTL));
+
+        let existing = pending
+            .values()
+            .find(|r| {
+                r.channel == msg.channel
+                    && r.account_id == msg.account_id
+                    && r.sender_id == msg.sender.id
+            })
+            .map(|r| r.code.clone());
+        let code = match existing {
+            Some(code) => {
+                drop(pending);
+                if !self.notice_due(msg) {
+                    return Verdict::Drop;
+                }
+                code
+            }
+            None if pending.len() >= MAX_PENDING => {
+                warn!(channel = %msg.channel, "Too many pending pairing requests; ignoring sender");
+                return Verdict::Drop;
+            }
+            None => {
+                let code = new_code();
+                pending.insert(
+                    code.clone(),
+                    PairingRequest {
+                        code: code.clone(),
+                        channel: msg.channel.clone(),
+                        account_id: msg.account_id.clone(),
+                        sender_id: msg.sender.id.clone(),
+                        sender_name: msg.sender.name.clone(),
+                        requested_at: chrono::Utc::now().to_rfc3339(),
+                        issued: Some(Instant::now()),
+                    },
+                );
+                drop(pending);
+                self.notices.lock().insert(notice_key(msg), Instant::now());
+                info!(channel = %msg.channel, sender = %msg.sender.id, code = %code, "Issued pairing code");
+                code
+            }
+        };
+        Verdict::Reply(format!(
+            "This assistant only talks to approved contacts. To request access, send this pairing code to its operator: {code}"
+        ))
+    }
+
+    /// Whether a notice may be sent to the sender now; records it if so.
+    fn notice_due(&self, msg: &NormalizedMessage) -> bool {
+        let mut notices = self.notices.lock();
+        notices.retain(|_, at| at.elapsed() < NOTICE_INTERVAL);
+        let key = notice_key(msg);
+        if notices.contains_key(&key) {
+            return false;
+        }
+        notices.insert(key, Instant::now());
+        true
+    }
+
+    fn paired_ids(&self, channel: &str, account_id: &str) -> Vec<String> {
+        self.paired
+            .lock()
+            .iter()
+            .filter(|p| p.channel == channel && p.account_id == account_id)
+            .map(|p| p.sender_id.clone())
+            .collect()
+    }
+
+    /// Pending pairing requests, oldest first.
+    pub fn pending(&self) -> Vec<PairingRequest> {
+        let mut pending = self.pending.lock();
+        pending.retain(|_, r| r.issued.is_some_and(|at| at.elapsed() < PAIRING_CODE_TTL));
+        let mut requests: Vec<PairingRequest> = pending.values().cloned().collect();
+        requests.sort_by(|a, b| a.requested_at.cmp(&b.requested_at));
+        requests
+    }
+
+    /// Senders approved through pairing.
+    pub fn paired(&self) -> Vec<PairedSender> {
+        self.paired.lock().clone()
+    }
+
+    /// Approve a pending pairing code.
+    pub fn approve(&self, code: &str) -> Result<PairedSender> {
+        let request = self.take_pending(code)?;
+        let sender = PairedSender {
+            channel: request.channel,
+            account_id: request.account_id,
+            sender_id: request.sender_id,
+            sender_name: request.sender_name,
+            approved_at: chrono::Utc::now().to_rfc3339(),
+        };
+        {
+            let mut paired = self.paired.lock();
+            paired.retain(|p| {
+                (&p.channel, &p.account_id, &p.sender_id)
+                    != (&sender.channel, &sender.account_id, &sender.sender_id)
+            });
+            paired.push(sender.clone());
+        }
+        self.save()?;
+        info!(channel = %sender.channel, sender = %sender.sender_id, "Pairing approved");
+        Ok(sender)
+    }
+
+    /// Discard a pending pairing code.
+    pub fn reject(&self, code: &str) -> Result<PairingRequest> {
+        self.take_pending(code)
+    }
+
+    /// Remove an approved sender. Returns whether one was removed.
+    pub fn revoke(&self, channel: &str, account_id: &str, sender_id: &str) -> Result<bool> {
+        let removed = {
+            let mut paired = self.paired.lock();
+            let before = paired.len();
+            paired.retain(|p| {
+                !(p.channel == channel && p.account_id == account_id && p.sender_id == sender_id)
+            });
+            paired.len() < before
+        };
+        if removed {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    fn take_pending(&self, code: &str) -> Result<PairingRequest> {
+        let code = normalize_code(code);
+        self.pending
+            .lock()
+            .remove(&code)
+            .filter(|r| r.issued.is_some_and(|at| at.elapsed() < PAIRING_CODE_TTL))
+            .ok_or_else(|| anyhow!("unknown or expired pairing code: {code}"))
+    }
+
+    /// Persist approvals, replacing the previous file atomically.
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let body = serde_json::json!({ "paired": self.paired() });
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(&body)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+impl Default for Admission {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn notice_key(msg: &NormalizedMessage) -> (String, String, String) {
+    (
+        msg.channel.clone(),
+        msg.account_id.clone(),
+        msg.sender.id.clone(),
+    )
+}
+
+fn new_code() -> String {
+    let mut rng = rand::thread_rng();
+    let code: String = (0..CODE_LEN)
+        .map(|_| CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char)
+        .collect();
+    format!("{}-{}", &code[..CODE_LEN / 2], &code[CODE_LEN / 2..])
+}
+
+/// Accept codes typed in lower case or without the dash.
+fn normalize_code(code: &str) -> String {
+    let code: String = code
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    if code.len() == CODE_LEN {
+        format!("{}-{}", &code[..CODE_LEN / 2], &code[CODE_LEN / 2..])
+    } else {
+        code
+    }
+}
+
+// ============================================================================
+// Per-channel Resolution
+// ============================================================================
+
+/// The account config for `id`, falling back to the default account.
+pub(super) fn account<'a, T>(
+    accounts: &'a Option<HashMap<String, T>>,
+    default: &'a T,
+    id: &str,
+) -> &'a T {
+    accounts.as_ref().and_then(|a| a.get(id)).unwrap_or(default)
+}
+
+/// DM policy and allowlist configured for the message's channel account.
+fn dm_access(config: &Config, msg: &NormalizedMessage) -> DmAccess {
+    let channels = &config.channels;
+    let access = |policy: Option<DmPolicy>, allow_from: &Option<Vec<String>>| DmAccess {
+        policy,
+        allow_from: allow_from.clone().unwrap_or_default(),
+    };
+    let id = msg.account_id.as_str();
+
+    match msg.channel.as_str() {
+        "telegram" => {
+            let a = account(
+                &channels.telegram.accounts,
+                &channels.telegram.default_account,
+                id,
+            );
+            access(a.dm_policy, &a.allow_from)
+        }
+        "discord" => {
+            let a = account(
+                &channels.discord.accounts,
+                &channels.discord.default_account,
+                id,
+            );
+            let dm = a.dm.as_ref().or(a.dms.as_ref());
+            access(
+                dm.and_then(|d| d.policy),
+                &dm.and_then(|d| d.allow_from.clone()),
+            )
+        }
+        "slack" => {
+            let a = account(
+                &channels.slack.accounts,
+                &channels.slack.default_account,
+                id,
+            );
+            let dm = a.dm.as_ref().or(a.dms.as_ref());
+            access(
+                dm.and_then(|d| d.policy),
+                &dm.and_then(|d| d.allow_from.clone()),
+            )
+        }
+        "whatsapp" => {
+            let a = account(
+                &channels.whatsapp.accounts,
+                &channels.whatsapp.default_account,
+                id,
+            );
+            access(a.dm_policy, &a.allow_from)
+        }
+        "matrix" => {
+            let a = account(
+                &channels.matrix.accounts,
+                &channels.matrix.default_account,
+                id,
+            );
+            access(a.dm_policy, &a.allow_from)
+        }
+        "mattermost" => {
+            let a = account(
+                &channels.mattermost.accounts,
+                &channels.mattermost.default_account,
+                id,
+            );
+            access(a.dm_policy, &a.allow_from)
+        }
+        "synology_chat" => channels
+            .synology_chat
+            .as_ref()
+            .map(|c| {
+                let a = account(&c.accounts, &c.default_account, id);
+                access(a.dm_policy, &a.allowed_user_ids)
+            })
+            .unwrap_or_default(),
+        "signal" => access(channels.signal.dm_policy, &channels.signal.allow_from),
+        "imessage" => access(channels.imessage.dm_policy, &channels.imessage.allow_from),
+        "googlechat" => channels
+            .googlechat
+            .as_ref()
+            .map(|c| access(c.dm_policy, &c.allow_from))
+            .unwrap_or_default(),
+        "teams" => channels
+            .msteams
+            .as_ref()
+            .map(|c| access(c.dm_policy, &c.allow_from))
+            .unwrap_or_default(),
+        "irc" => channels
+            .irc
+            .as_ref()
+            .map(|c| access(c.dm_policy, &c.allow_from))
+            .unwrap_or_default(),
+        "email" => channels
+            .email
+            .as_ref()
+            .map(|c| access(c.dm_policy, &c.allow_from))
+            .unwrap_or_default(),
+        "line" => channels
+            .line
+            .as_ref()
+            .map(|c| access(c.dm_policy, &c.allow_from))
+            .unwrap_or_default(),
+        "feishu" => channels
+            .feishu
+            .as_ref()
+            .map(|c| access(c.dm_policy, &c.allow_from))
+            .unwrap_or_default(),
+        "zalo" => channels
+            .zalo
+            .as_ref()
+            .map(|c| access(c.dm_policy, &c.allow_from))
+            .unwrap_or_default(),
+        "zalouser" => channels
+            .zalouser
+            .as_ref()
+            .map(|c| access(c.dm_policy, &c.allow_from))
+            .unwrap_or_default(),
+        "bluebubbles" => channels
+            .bluebubbles
+            .as_ref()
+            .map(|c| access(c.dm_policy, &c.allow_from))
+            .unwrap_or_default(),
+        "nextcloud" => channels
+            .nextcloud
+            .as_ref()
+            .map(|c| access(None, &c.allow_from))
+            .unwrap_or_default(),
+        "nostr" => channels
+            .nostr
+            .as_ref()
+            .map(|c| DmAccess {
+                policy: c.dm_policy,
+                // Allowlists may hold npubs; senders are hex.
+                allow_from: c
+                    .allow_from
+                    .iter()
+                    .flatten()
+                    .map(|e| super::nostr::parse_public_key(e).unwrap_or_else(|_| e.clone()))
+                    .collect(),
+            })
+            .unwrap_or_default(),
+        _ => DmAccess::default(),
+    }
+}
+
+/// The sender allowlist of a group: `group_entries`, the most specific list
+/// configured for it (topic, group or the account's `groupAllowFrom`).
+///
+/// Groups never inherit the account's DM `allowFrom`; `None` when the group
+/// has no entries of its own.
+pub(super) fn group_allowlist(
+    group_entries: Option<&Vec<String>>,
+    allow_from: Option<&Vec<String>>,
+) -> Option<Vec<String>> {
+    let entries = group_entries.filter(|list| !list.is_empty())?;
+    let parent = allow_from.map(Vec::as_slice).unwrap_or_default();
+    Some(resolve_group_allow_from_sources(entries, parent))
+}
+
+/// Identities an allowlist entry may name the sender by: the sender id
+/// plus aliases the platform guarantees to be the sender's own.
+fn sender_handles(msg: &NormalizedMessage) -> Vec<String> {
+    let mut handles = vec![msg.sender.id.clone()];
+    let raw = msg.raw.as_ref();
+    match msg.channel.as_str() {
+        // Phone numbers are often listed in E.164.
+        "whatsapp" => handles.push(format!("+{}", msg.sender.id)),
+        "signal" => {
+            if let Some(uuid) = raw.and_then(|r| r["envelope"]["sourceUuid"].as_str()) {
+                handles.push(uuid.to_string());
+            }
+        }
+        // Telegram, Slack, Discord and Mattermost match on user ids only:
+        // usernames and display names are chosen by the sender.
+        _ => {}
+    }
+    handles.retain(|h| !h.is_empty());
+    handles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::NormalizedSender;
+    use crate::config::{DiscordDmConfig, MatrixAccountConfig};
+
+    fn dm(channel: &str, sender: &str) -> NormalizedMessage {
+        NormalizedMessage {
+            id: "m1".to_string(),
+            channel: channel.to_string(),
+            account_id: "default".to_string(),
+            chat_id: sender.to_string(),
+            chat_name: None,
+            chat_type: ChatType::Dm,
+            sender: NormalizedSender {
+                id: sender.to_string(),
+                name: "Ann".to_string(),
+                is_bot: false,
+                roles: Vec::new(),
+            },
+            text: "hi".to_string(),
+            attachments: Vec::new(),
+            reply_to_id: None,
+            thread_id: None,
+            mentioned: true,
+            timestamp: String::new(),
+            raw: None,
+        }
+    }
+
+    fn matrix_config(policy: Option<DmPolicy>, allow_from: &[&str]) -> Config {
+        let mut config = Config::default();
+        config.channels.matrix.default_account = MatrixAccountConfig {
+            dm_policy: policy,
+            allow_from: Some(allow_from.iter().map(|s| s.to_string()).collect()),
+            ..Default::default()
+        };
+        config
+    }
+
+    fn code_of(verdict: &Verdict) -> String {
+        let Verdict::Reply(text) = verdict else {
+            panic!("expected a reply, got {verdict:?}");
+        };
+        text.rsplit(' ').next().unwrap().to_string()
+    }
+
+    #[test]
+    fn policies_and_rate_limited_notices() {
+        let admission = Admission::new();
+        let ann = dm("matrix", "@ann:x");
+        let bob = dm("matrix", "@bob:x");
+
+        let open = matrix_config(None, &[]);
+        assert_eq!(admission.check(&open, &bob), Verdict::Admit);
+
+        let listed = matrix_config(Some(DmPolicy::Allowlist), &["@ann:x"]);
+        assert_eq!(admission.check(&listed, &ann), Verdict::Admit);
+        assert!(matches!(admission.check(&listed, &bob), Verdict::Reply(_)));
+        assert_eq!(admission.check(&listed, &bob), Verdict::Drop);
+
+        let disabled = matrix_config(Some(DmPolicy::Disabled), &["@ann:x"]);
+        assert!(matches!(
+            admission.check(&disabled, &ann),
+            Verdict::Reply(_)
+        ));
+
+        let mut silent = matrix_config(Some(DmPolicy::Allowlist), &[]);
+        silent.channels.defaults = Some(crate::config::ChannelDefaultsConfig {
+            deny_reply: Some(false),
+            ..Default::default()
+        });
+        assert_eq!(
+            admission.check(&silent, &dm("matrix", "@eve:x")),
+            Verdict::Drop
+        );
+
+        let mut group = dm("matrix", "@eve:x");
+        group.chat_type = ChatType::Group;
+        assert_eq!(admission.check(&disabled, &group), Verdict::Admit);
+    }
+
+    #[test]
+    fn handles_cover_platform_aliases() {
+        let mut config = Config::default();
+        config.channels.whatsapp.default_account.dm_policy = Some(DmPolicy::Allowlist);
+        config.channels.whatsapp.default_account.allow_from = Some(vec!["+15551234".to_string()]);
+        assert_eq!(
+            Admission::new().check(&config, &dm("whatsapp", "15551234")),
+            Verdict::Admit
+        );
+
+        config.channels.telegram.default_account.dm_policy = Some(DmPolicy::Allowlist);
+        config.channels.telegram.default_account.allow_from = Some(vec!["@ann".to_string()]);
+        let mut msg = dm("telegram", "42");
+        msg.raw = Some(serde_json::json!({ "from": { "id": 42, "username": "ann" } }));
+        // Usernames can be changed and then claimed by someone else.
+        assert_ne!(Admission::new().check(&config, &msg), Verdict::Admit);
+        config.channels.telegram.default_account.allow_from = Some(vec!["42".to_string()]);
+        assert_eq!(Admission::new().check(&config, &msg), Verdict::Admit);
+    }
+
+    #[test]
+    fn display_names_do_not_match_allowlists() {
+        let mut config = Config::default();
+        config.channels.discord.default_account.dm = Some(DiscordDmConfig {
+            policy: Some(DmPolicy::Allowlist),
+            allow_from: Some(vec!["Ann".to_string(), "111".to_string()]),
+            ..Default::default()
+        });
+        let admission = Admission::new();
+        // Anyone can rename themselves "Ann".
+        let spoofed = dm("discord", "999");
+        assert_eq!(spoofed.sender.name, "Ann");
+        assert_ne!(admission.check(&config, &spoofed), Verdict::Admit);
+        assert_eq!(admission.check(&config, &dm("discord", "111")), Verdict::Admit);
+    }
+
+    #[test]
+    fn pairing_codes_are_approved_and_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("channels/pairing.json");
+        let admission = Admission::load(path.clone());
+        let config = matrix_config(Some(DmPolicy::Pairing), &[]);
+        let bob = dm("matrix", "@bob:x");
+
+        let code = code_of(&admission.check(&config, &bob));
+        assert_eq!(admission.pending().len(), 1);
+        // The same code is not re-sent within the notice interval.
+        assert_eq!(admission.check(&config, &bob), Verdict::Drop);
+        assert!(admission.approve("nope").is_err());
+
+        let paired = admission
+            .approve(&code.to_lowercase().replace('-', ""))
+            .unwrap();
+        assert_eq!(paired.sender_id, "@bob:x");
+        assert!(admission.pending().is_empty());
+        assert_eq!(admission.check(&config, &bob), Verdict::Admit);
+        assert!(admission.approve(&code).is_err());
+
+        let reloaded = Admission::load(path);
+        assert_eq!(reloaded.check(&config, &bob), Verdict::Admit);
+        // Approvals only apply under the pairing policy.
+        let allowlist = matrix_config(Some(DmPolicy::Allowlist), &[]);
+        assert_ne!(reloaded.check(&allowlist, &bob), Verdict::Admit);
+
+        assert!(reloaded.revoke("matrix", "default", "@bob:x").unwrap());
+        assert!(matches!(reloaded.check(&config, &bob), Verdict::Reply(_)));
+        let rejected = reloaded.reject(&reloaded.pending()[0].code).unwrap();
+        assert_eq!(rejected.sender_id, "@bob:x");
+    }
+}
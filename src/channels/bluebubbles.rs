@@ -1,10 +1,26 @@
-use super::plugin::{ChannelCapability, ChannelMeta, ChannelPlugin};
+use super::format::{render_markdown, FormatTarget};
+use super::inbound::{dispatch_inbound, typing_interval_ms, InboundEvent};
+use super::normalize::{
+    ChatType, NormalizedAttachment, NormalizedMessage, NormalizedOutbound, NormalizedSender,
+};
+use super::plugin::{
+    ChannelCapability, ChannelMeta, ChannelPlugin, WebhookRequest, WebhookResponse,
+};
+use super::webhook::{self, ReplayGuard, WebhookRoute};
+use super::TypingKeepaliveLoop;
+use crate::config::{BlueBubblesConfig, Config, GroupPolicy};
 use crate::gateway::GatewayState;
+use crate::infra::dm_policy;
 
-use anyhow::Result;
+use anyhow::{bail, Context as _, Result};
 use async_trait::async_trait;
-use reqwest::Client;
-use tracing::{info, warn};
+use axum::http::StatusCode;
+use parking_lot::Mutex;
+use reqwest::{Client, Method};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{debug, info, warn};
 
 // ============================================================================
 // BlueBubbles Channel Implementation
@@ -18,54 +34,322 @@ use tracing::{info, warn};
 /// BlueBubbles API docs: <https://documenter.getpostman.com/view/765844/UV5RnfwM>
 ///
 /// The server typically runs on `http://<mac-ip>:1234` and requires a
-/// password for authentication.
+/// password for authentication. Incoming messages arrive through a server
+/// webhook pointed at `/channels/<id>/<webhookPath>?password=<password>`.
+/// `new-message` events become turns, or reactions when they are tapbacks;
+/// replies, attachments, tapbacks, typing indicators and read receipts go
+/// out through the REST API (the last three need the server's Private API).
+///
+/// The `imessage` channel is the same integration configured from
+/// `channels.imessage` (`apiUrl`, `apiPassword`).
 pub struct BlueBubblesChannel {
-    /// BlueBubbles server API URL (e.g. `http://192.168.1.100:1234`).
-    api_url: Option<String>,
-    /// BlueBubbles server password.
-    password: Option<String>,
-    /// Whether this channel is enabled.
-    enabled: Option<bool>,
-    /// HTTP client for API calls.
-    client: Client,
+    enabled: bool,
+    client: Arc<BlueBubblesClient>,
+    /// Message GUIDs already handled.
+    replay: ReplayGuard,
+}
+
+/// REST client and settings, shared with spawned message handlers.
+struct BlueBubblesClient {
+    /// Channel id: `bluebubbles`, or `imessage` for the alias.
+    channel: &'static str,
+    config: BlueBubblesConfig,
+    http: Client,
+    /// Tapback the bot last left on each message, so it can be removed.
+    tapbacks: Mutex<HashMap<String, Tapback>>,
 }
 
+const DEFAULT_WEBHOOK_PATH: &str = "webhook";
+
+/// How often the typing indicator is refreshed while the agent runs.
+const TYPING_INTERVAL_MS: u64 = 10_000;
+
 impl BlueBubblesChannel {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
+        let bluebubbles = config.channels.bluebubbles.clone().unwrap_or_default();
+        Self::with_config("bluebubbles", bluebubbles)
+    }
+
+    /// The `imessage` channel, served by BlueBubbles unless
+    /// `channels.imessage.provider` names another bridge.
+    pub fn imessage(config: &Config) -> Self {
+        let imessage = &config.channels.imessage;
+        let bluebubbles = match imessage.provider.as_deref() {
+            None => true,
+            Some(provider) => provider.eq_ignore_ascii_case("bluebubbles"),
+        };
+        Self::with_config(
+            "imessage",
+            BlueBubblesConfig {
+                enabled: Some(bluebubbles && imessage.enabled.unwrap_or(false)),
+                server_url: imessage.api_url.clone(),
+                password: imessage.api_password.clone(),
+                webhook_path: None,
+                dm_policy: imessage.dm_policy,
+                allow_from: imessage.allow_from.clone(),
+                group_policy: imessage.group_policy,
+                retry: imessage.retry.clone(),
+            },
+        )
+    }
+
+    fn with_config(channel: &'static str, config: BlueBubblesConfig) -> Self {
         Self {
-            api_url: None,
-            password: None,
-            enabled: None,
-            client: Client::new(),
+            enabled: config.enabled.unwrap_or(false),
+            client: Arc::new(BlueBubblesClient {
+                channel,
+                config,
+                http: Client::new(),
+                tapbacks: Mutex::new(HashMap::new()),
+            }),
+            replay: ReplayGuard::default(),
         }
     }
 
-    /// Create a configured BlueBubbles channel.
-    pub fn with_config(api_url: String, password: String) -> Self {
-        Self {
-            api_url: Some(api_url),
-            password: Some(password),
-            enabled: Some(true),
-            client: Client::new(),
+    fn webhook_path(&self) -> &str {
+        webhook::webhook_path(
+            self.client.config.webhook_path.as_deref(),
+            DEFAULT_WEBHOOK_PATH,
+        )
+    }
+
+    /// BlueBubbles webhooks carry the server password as `?password=`.
+    fn password_valid(&self, request: &WebhookRequest) -> bool {
+        let Some(expected) = self.client.config.password.as_deref() else {
+            return false;
+        };
+        webhook::query_params(request)
+            .get("password")
+            .is_some_and(|given| webhook::constant_time_eq(given.as_bytes(), expected.as_bytes()))
+    }
+
+    /// DMs follow `dm_policy`; group chats follow `group_policy`, which
+    /// defaults to disabled since iMessage groups have no mentions.
+    fn admitted(&self, msg: &NormalizedMessage) -> bool {
+        let config = &self.client.config;
+        let allow_from = config.allow_from.as_deref().unwrap_or_default();
+        match msg.chat_type {
+            // `dmPolicy` and `allowFrom` are applied by the admission stage.
+            ChatType::Dm => true,
+            ChatType::Group | ChatType::Thread => {
+                match config.group_policy.unwrap_or(GroupPolicy::Disabled) {
+                    GroupPolicy::Disabled => false,
+                    GroupPolicy::Open => true,
+                    GroupPolicy::Allowlist => {
+                        !allow_from.is_empty()
+                            && dm_policy::is_source_allowed(allow_from, &msg.chat_id)
+                    }
+                }
+            }
         }
     }
+}
+
+impl BlueBubblesClient {
+    fn server_url(&self) -> Result<&str> {
+        self.config
+            .server_url
+            .as_deref()
+            .map(|url| url.trim_end_matches('/'))
+            .ok_or_else(|| anyhow::anyhow!("BlueBubbles server_url not configured"))
+    }
 
-    fn is_enabled(&self) -> bool {
-        self.enabled.unwrap_or(false)
+    fn password(&self) -> Result<&str> {
+        self.config
+            .password
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("BlueBubbles password not configured"))
+    }
+
+    /// Call `/api/v1/<path>`, returning the response's `data`.
+    async fn api(&self, method: Method, path: &str, body: Option<&Value>) -> Result<Value> {
+        let url = format!("{}/api/v1/{path}", self.server_url()?);
+        let mut request = self
+            .http
+            .request(method, &url)
+            .query(&[("password", self.password()?)]);
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+        parse_response(request.send().await?).await
+    }
+
+    /// Send text, as a reply to `reply_to` when given. Returns the new
+    /// message GUID.
+    async fn send_text(
+        &self,
+        chat_guid: &str,
+        text: &str,
+        reply_to: Option<&str>,
+    ) -> Result<Option<String>> {
+        let mut body = json!({
+            "chatGuid": chat_guid,
+            "tempGuid": uuid::Uuid::new_v4().to_string(),
+            "message": render_markdown(text, FormatTarget::Plain),
+            "method": "private-api",
+        });
+        if let Some(reply_to) = reply_to {
+            body["selectedMessageGuid"] = json!(reply_to);
+            body["partIndex"] = json!(0);
+        }
+        let data = self.api(Method::POST, "message/text", Some(&body)).await?;
+        Ok(data["guid"].as_str().map(str::to_string))
+    }
+
+    /// Upload and send a file. Returns the new message GUID.
+    async fn send_attachment(
+        &self,
+        chat_guid: &str,
+        data: Vec<u8>,
+        filename: &str,
+        mime_type: Option<&str>,
+    ) -> Result<Option<String>> {
+        let mut file = reqwest::multipart::Part::bytes(data).file_name(filename.to_string());
+        if let Some(mime_type) = mime_type {
+            file = file.mime_str(mime_type)?;
+        }
+        let form = reqwest::multipart::Form::new()
+            .text("chatGuid", chat_guid.to_string())
+            .text("tempGuid", uuid::Uuid::new_v4().to_string())
+            .text("name", filename.to_string())
+            .text("method", "private-api")
+            .part("attachment", file);
+        let url = format!("{}/api/v1/message/attachment", self.server_url()?);
+        let resp = self
+            .http
+            .post(&url)
+            .query(&[("password", self.password()?)])
+            .multipart(form)
+            .send()
+            .await?;
+        let data = parse_response(resp).await?;
+        Ok(data["guid"].as_str().map(str::to_string))
+    }
+
+    /// Bytes of an outbound attachment: inline data, or fetched from `url`.
+    async fn attachment_bytes(&self, attachment: &NormalizedAttachment) -> Result<Vec<u8>> {
+        match (&attachment.data, attachment.url.as_deref()) {
+            (Some(data), _) => Ok(data.clone()),
+            (None, Some(url)) => {
+                let resp = self.http.get(url).send().await?.error_for_status()?;
+                Ok(resp.bytes().await?.to_vec())
+            }
+            (None, None) => bail!("BlueBubbles attachments need data or a URL"),
+        }
+    }
+
+    /// Leave a tapback on a message, or remove the bot's tapback when
+    /// `emoji` is empty.
+    async fn react(&self, chat_guid: &str, message_guid: &str, emoji: &str) -> Result<()> {
+        let reaction = if emoji.is_empty() {
+            let Some(previous) = self.tapbacks.lock().remove(message_guid) else {
+                return Ok(());
+            };
+            format!("-{}", previous.as_str())
+        } else {
+            let Some(tapback) = Tapback::from_emoji(emoji) else {
+                bail!("iMessage has no tapback for {emoji}");
+            };
+            self.tapbacks
+                .lock()
+                .insert(message_guid.to_string(), tapback);
+            tapback.as_str().to_string()
+        };
+        let body = json!({
+            "chatGuid": chat_guid,
+            "selectedMessageGuid": message_guid,
+            "reaction": reaction,
+            "partIndex": 0,
+        });
+        self.api(Method::POST, "message/react", Some(&body))
+            .await
+            .map(drop)
     }
+
+    async fn set_typing(&self, chat_guid: &str, typing: bool) -> Result<()> {
+        let method = if typing { Method::POST } else { Method::DELETE };
+        let path = format!("chat/{}/typing", urlencoded(chat_guid));
+        self.api(method, &path, None).await.map(drop)
+    }
+
+    fn spawn_typing(self: &Arc<Self>, chat_guid: &str, typing: bool) {
+        let client = self.clone();
+        let chat_guid = chat_guid.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = client.set_typing(&chat_guid, typing).await {
+                debug!(chat = %chat_guid, error = %e, "BlueBubbles typing indicator failed");
+            }
+        });
+    }
+
+    async fn mark_read(&self, chat_guid: &str) -> Result<()> {
+        let path = format!("chat/{}/read", urlencoded(chat_guid));
+        self.api(Method::POST, &path, None).await.map(drop)
+    }
+
+    /// Run the agent for an admitted message and reply, showing a typing
+    /// indicator meanwhile.
+    async fn handle_message(self: Arc<Self>, state: GatewayState, msg: NormalizedMessage) {
+        if let Err(e) = self.mark_read(&msg.chat_id).await {
+            debug!(chat = %msg.chat_id, error = %e, "BlueBubbles read receipt failed");
+        }
+
+        let interval = typing_interval_ms(&*state.config.read().await, TYPING_INTERVAL_MS);
+        self.spawn_typing(&msg.chat_id, true);
+        let typing = TypingKeepaliveLoop::new(interval);
+        let typing_task = {
+            let client = self.clone();
+            let chat_guid = msg.chat_id.clone();
+            typing.start(move || client.spawn_typing(&chat_guid, true))
+        };
+
+        let result = dispatch_inbound(&state, &msg).await;
+        typing.stop();
+        typing_task.abort();
+        self.spawn_typing(&msg.chat_id, false);
+
+        match result {
+            Ok(Some(reply)) => {
+                let outbound = NormalizedOutbound::text(msg.chat_id.clone(), reply);
+                if let Err(e) = state.channels.enqueue(self.channel, outbound).await {
+                    warn!(chat = %msg.chat_id, error = %e, "BlueBubbles reply failed");
+                }
+            }
+            Ok(None) => {}
+            Err(e) => warn!(chat = %msg.chat_id, error = %e, "BlueBubbles agent run failed"),
+        }
+    }
+}
+
+/// Check a BlueBubbles API response and return its `data`.
+async fn parse_response(resp: reqwest::Response) -> Result<Value> {
+    let status = resp.status();
+    let payload: Value = resp.json().await.unwrap_or(Value::Null);
+    if !status.is_success() {
+        let error = payload["error"]["message"]
+            .as_str()
+            .or_else(|| payload["message"].as_str())
+            .unwrap_or("unknown error");
+        bail!("BlueBubbles API error ({status}): {error}");
+    }
+    Ok(payload["data"].clone())
 }
 
 #[async_trait]
 impl ChannelPlugin for BlueBubblesChannel {
     fn id(&self) -> &str {
-        "bluebubbles"
+        self.client.channel
     }
 
     fn meta(&self) -> ChannelMeta {
+        let name = match self.client.channel {
+            "imessage" => "iMessage",
+            _ => "BlueBubbles",
+        };
         ChannelMeta {
-            name: "BlueBubbles".to_string(),
+            name: name.to_string(),
             description: "BlueBubbles iMessage bridge for sending/receiving iMessages".to_string(),
-            enabled: self.is_enabled(),
+            enabled: self.enabled,
             multi_account: false,
         }
     }
@@ -83,103 +367,582 @@ impl ChannelPlugin for BlueBubblesChannel {
         ]
     }
 
+    fn webhook_routes(&self) -> Vec<WebhookRoute> {
+        if !self.enabled {
+            return Vec::new();
+        }
+        vec![WebhookRoute::post(self.webhook_path())]
+    }
+
     async fn start_account(&self, _state: &GatewayState) -> Result<()> {
-        if !self.is_enabled() {
+        if !self.enabled {
             return Ok(());
         }
 
-        let api_url = match &self.api_url {
-            Some(url) => url,
-            None => {
-                warn!("BlueBubbles channel enabled but no api_url configured");
-                return Ok(());
-            }
+        let Some(api_url) = &self.client.config.server_url else {
+            warn!(
+                channel = self.id(),
+                "BlueBubbles channel enabled but no server_url configured"
+            );
+            return Ok(());
         };
 
-        if self.password.is_none() {
-            warn!("BlueBubbles channel enabled but no password configured");
+        if self.client.config.password.is_none() {
+            warn!(
+                channel = self.id(),
+                "BlueBubbles channel enabled but no password configured"
+            );
             return Ok(());
         }
 
-        info!(api_url = %api_url, "BlueBubbles channel starting");
+        info!(channel = self.id(), api_url = %api_url, path = %self.webhook_path(), "BlueBubbles channel starting");
 
         // Verify server connectivity by calling the server info endpoint.
-        let password = self.password.as_deref().unwrap_or_default();
-        let info_url = format!(
-            "{}/api/v1/server/info?password={}",
-            api_url.trim_end_matches('/'),
-            password,
-        );
-
-        match self.client.get(&info_url).send().await {
-            Ok(resp) if resp.status().is_success() => {
-                info!("BlueBubbles: server connectivity verified");
-            }
-            Ok(resp) => {
-                warn!(
-                    "BlueBubbles: server returned status {}",
-                    resp.status()
-                );
-            }
-            Err(e) => {
-                warn!("BlueBubbles: failed to reach server: {}", e);
-            }
+        match self.client.api(Method::GET, "server/info", None).await {
+            Ok(_) => info!("BlueBubbles: server connectivity verified"),
+            Err(e) => warn!("BlueBubbles: failed to reach server: {}", e),
         }
 
-        // TODO: Register a webhook endpoint for incoming messages, or start
-        // polling the messages endpoint for new messages.
-
         Ok(())
     }
 
     async fn stop_account(&self) -> Result<()> {
-        if self.is_enabled() {
-            info!("BlueBubbles channel stopping");
-            // TODO: Deregister webhook if registered.
+        if self.enabled {
+            info!(channel = self.id(), "BlueBubbles channel stopping");
         }
         Ok(())
     }
 
+    async fn probe(&self) -> Result<()> {
+        self.client
+            .api(Method::GET, "server/info", None)
+            .await
+            .context("BlueBubbles server unreachable")
+            .map(drop)
+    }
+
     async fn send_message(&self, to: &str, message: &str) -> Result<()> {
-        let api_url = self
-            .api_url
-            .as_deref()
-            .ok_or_else(|| anyhow::anyhow!("BlueBubbles api_url not configured"))?;
+        info!(to = %to, "BlueBubbles: sending iMessage");
+        self.client
+            .send_text(&chat_guid(to), message, None)
+            .await
+            .map(drop)
+    }
 
-        let password = self
-            .password
-            .as_deref()
-            .ok_or_else(|| anyhow::anyhow!("BlueBubbles password not configured"))?;
+    async fn send(&self, message: &NormalizedOutbound) -> Result<Option<String>> {
+        let chat_guid = chat_guid(&message.chat_id);
+        let mut last = None;
+        for attachment in &message.attachments {
+            let data = self.client.attachment_bytes(attachment).await?;
+            let filename = attachment.filename.as_deref().unwrap_or("attachment");
+            last = self
+                .client
+                .send_attachment(&chat_guid, data, filename, attachment.mime_type.as_deref())
+                .await?;
+        }
+        if message.text.trim().is_empty() {
+            return Ok(last);
+        }
+        Ok(self
+            .client
+            .send_text(&chat_guid, &message.text, message.reply_to_id.as_deref())
+            .await?
+            .or(last))
+    }
 
-        // `to` is a phone number or iMessage email address.
-        let url = format!(
-            "{}/api/v1/message/text?password={}",
-            api_url.trim_end_matches('/'),
-            password,
-        );
+    async fn react(&self, chat_id: &str, message_id: &str, emoji: &str) -> Result<()> {
+        self.client
+            .react(&chat_guid(chat_id), message_id, emoji)
+            .await
+    }
 
-        let body = serde_json::json!({
-            "chatGuid": format!("iMessage;-;{}", to),
-            "tempGuid": uuid::Uuid::new_v4().to_string(),
-            "message": message,
-            "method": "private-api",
-        });
+    async fn send_typing(&self, chat_id: &str, _thread_id: Option<&str>) -> Result<()> {
+        self.client.set_typing(&chat_guid(chat_id), true).await
+    }
 
-        info!(to = %to, "BlueBubbles: sending iMessage");
+    async fn mark_read(&self, chat_id: &str, _message_id: &str) -> Result<()> {
+        self.client.mark_read(&chat_guid(chat_id)).await
+    }
 
+    /// Attachment downloads need the server password.
+    async fn fetch_attachment(&self, attachment: &NormalizedAttachment) -> Result<Vec<u8>> {
+        if let Some(data) = &attachment.data {
+            return Ok(data.clone());
+        }
+        let Some(url) = attachment.url.as_deref() else {
+            bail!("BlueBubbles attachment has no URL");
+        };
         let resp = self
             .client
-            .post(&url)
-            .json(&body)
+            .http
+            .get(url)
+            .query(&[("password", self.client.password()?)])
             .send()
-            .await?;
+            .await?
+            .error_for_status()?;
+        Ok(resp.bytes().await?.to_vec())
+    }
 
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let text = resp.text().await.unwrap_or_default();
-            anyhow::bail!("BlueBubbles send failed ({}): {}", status, text);
+    async fn handle_webhook(
+        &self,
+        state: &GatewayState,
+        request: WebhookRequest,
+    ) -> Result<WebhookResponse> {
+        if !self.enabled {
+            return Ok(WebhookResponse::not_found());
+        }
+        if let Some(response) = webhook::route_check(&self.webhook_routes(), &request) {
+            return Ok(response);
         }
+        if !self.password_valid(&request) {
+            warn!(
+                channel = self.id(),
+                "BlueBubbles webhook request with invalid password"
+            );
+            return Ok(WebhookResponse::status(StatusCode::UNAUTHORIZED));
+        }
+        let Ok(event) = serde_json::from_slice::<Value>(&request.body) else {
+            return Ok(WebhookResponse::status(StatusCode::BAD_REQUEST));
+        };
 
-        Ok(())
+        let Some(event) = parse_event(self.id(), &event, self.client.config.server_url.as_deref())
+        else {
+            return Ok(WebhookResponse::ok());
+        };
+        let msg = match event {
+            Event::Message(msg) => msg,
+            Event::Tapback(reaction) => {
+                if let InboundEvent::Reaction {
+                    message_id, emoji, ..
+                } = &reaction
+                {
+                    debug!(message_id = %message_id, emoji = %emoji, "BlueBubbles tapback received");
+                }
+                state.inbound.push(reaction);
+                return Ok(WebhookResponse::ok());
+            }
+        };
+        if !self.replay.first_delivery(&msg.id) {
+            debug!(guid = %msg.id, "Skipping redelivered BlueBubbles message");
+            return Ok(WebhookResponse::ok());
+        }
+        if !self.admitted(&msg) {
+            debug!(sender = %msg.sender.id, chat = %msg.chat_id, "BlueBubbles message not admitted");
+            return Ok(WebhookResponse::ok());
+        }
+
+        tokio::spawn(self.client.clone().handle_message(state.clone(), msg));
+        Ok(WebhookResponse::ok())
+    }
+}
+
+// ============================================================================
+// Chats and Tapbacks
+// ============================================================================
+
+/// Chat GUID for a send target.
+///
+/// Group chat GUIDs look like `iMessage;+;chat1234` and direct chats
+/// `iMessage;-;+15551234567` (`SMS;-;...` for SMS). A full GUID is used as
+/// is, a bare `chat...` id is a group, anything else an address.
+fn chat_guid(to: &str) -> String {
+    if to.contains(';') {
+        to.to_string()
+    } else if to.starts_with("chat") {
+        format!("iMessage;+;{to}")
+    } else {
+        format!("iMessage;-;{to}")
+    }
+}
+
+/// Percent-encode a chat GUID for a URL path (`;` and `+`).
+fn urlencoded(s: &str) -> String {
+    url::form_urlencoded::byte_serialize(s.as_bytes()).collect()
+}
+
+/// The six iMessage tapbacks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tapback {
+    Love,
+    Like,
+    Dislike,
+    Laugh,
+    Emphasize,
+    Question,
+}
+
+impl Tapback {
+    const ALL: [Self; 6] = [
+        Self::Love,
+        Self::Like,
+        Self::Dislike,
+        Self::Laugh,
+        Self::Emphasize,
+        Self::Question,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Love => "love",
+            Self::Like => "like",
+            Self::Dislike => "dislike",
+            Self::Laugh => "laugh",
+            Self::Emphasize => "emphasize",
+            Self::Question => "question",
+        }
+    }
+
+    fn emoji(self) -> &'static str {
+        match self {
+            Self::Love => "❤️",
+            Self::Like => "👍",
+            Self::Dislike => "👎",
+            Self::Laugh => "😂",
+            Self::Emphasize => "‼️",
+            Self::Question => "❓",
+        }
+    }
+
+    /// The tapback for an emoji, ignoring variation selectors.
+    fn from_emoji(emoji: &str) -> Option<Self> {
+        let bare = |s: &str| s.replace('\u{fe0f}', "");
+        let emoji = bare(emoji);
+        Self::ALL.into_iter().find(|t| bare(t.emoji()) == emoji)
+    }
+
+    /// Parse an `associatedMessageType`: a name (`love`, `-love` to
+    /// remove) or Apple's code (2000-2005 to add, 3000-3005 to remove).
+    /// Returns the tapback and whether it was added.
+    fn parse(kind: &Value) -> Option<(Self, bool)> {
+        let kind = match kind {
+            Value::Number(n) => n.to_string(),
+            Value::String(s) => s.to_ascii_lowercase(),
+            _ => return None,
+        };
+        if let Ok(code) = kind.parse::<usize>() {
+            let (added, index) = match code {
+                2000..=2005 => (true, code - 2000),
+                3000..=3005 => (false, code - 3000),
+                _ => return None,
+            };
+            return Some((Self::ALL[index], added));
+        }
+        let (added, name) = match kind.strip_prefix('-') {
+            Some(name) => (false, name),
+            None => (true, kind.as_str()),
+        };
+        let tapback = Self::ALL.into_iter().find(|t| t.as_str() == name)?;
+        Some((tapback, added))
+    }
+}
+
+// ============================================================================
+// Event Parsing
+// ============================================================================
+
+/// A webhook event that concerns the agent.
+#[derive(Debug)]
+enum Event {
+    Message(NormalizedMessage),
+    /// A tapback, as an [`InboundEvent::Reaction`].
+    Tapback(InboundEvent),
+}
+
+/// Parse a `new-message` webhook event for `channel`.
+///
+/// Tapbacks arrive as messages with an `associatedMessageGuid` (prefixed
+/// `p:<part>/`) and become reactions; an empty emoji removes one.
+fn parse_event(channel: &str, event: &Value, server_url: Option<&str>) -> Option<Event> {
+    if event["type"] != "new-message" {
+        return None;
+    }
+    let data = &event["data"];
+    if data["isFromMe"].as_bool().unwrap_or(false) {
+        return None;
+    }
+    let chat = &data["chats"][0];
+    let chat_guid = chat["guid"].as_str()?.to_string();
+    let sender = data["handle"]["address"].as_str()?.to_string();
+    let sender = NormalizedSender {
+        id: sender.clone(),
+        name: sender,
+        is_bot: false,
+        roles: Vec::new(),
+    };
+
+    if let Some(target) = data["associatedMessageGuid"].as_str() {
+        let (tapback, added) = Tapback::parse(&data["associatedMessageType"])?;
+        let message_id = target.rsplit('/').next().unwrap_or(target);
+        return Some(Event::Tapback(InboundEvent::Reaction {
+            channel: channel.to_string(),
+            chat_id: chat_guid,
+            message_id: message_id.to_string(),
+            sender,
+            emoji: if added { tapback.emoji() } else { "" }.to_string(),
+        }));
+    }
+
+    let attachments: Vec<NormalizedAttachment> = data["attachments"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|attachment| {
+            let guid = attachment["guid"].as_str()?;
+            Some(NormalizedAttachment {
+                mime_type: attachment["mimeType"].as_str().map(str::to_string),
+                url: server_url.map(|url| {
+                    format!(
+                        "{}/api/v1/attachment/{}/download",
+                        url.trim_end_matches('/'),
+                        guid
+                    )
+                }),
+                data: None,
+                filename: attachment["transferName"].as_str().map(str::to_string),
+                size: attachment["totalBytes"].as_u64(),
+            })
+        })
+        .collect();
+    let text = data["text"].as_str().unwrap_or_default().trim().to_string();
+    if text.is_empty() && attachments.is_empty() {
+        return None;
+    }
+
+    Some(Event::Message(NormalizedMessage {
+        id: data["guid"].as_str()?.to_string(),
+        channel: channel.to_string(),
+        account_id: "default".to_string(),
+        chat_type: if chat_guid.contains(";+;") {
+            ChatType::Group
+        } else {
+            ChatType::Dm
+        },
+        chat_id: chat_guid,
+        chat_name: chat["displayName"]
+            .as_str()
+            .filter(|name| !name.is_empty())
+            .map(str::to_string),
+        sender,
+        text,
+        attachments,
+        reply_to_id: data["threadOriginatorGuid"].as_str().map(str::to_string),
+        thread_id: None,
+        mentioned: false,
+        timestamp: webhook::timestamp_from_millis(data["dateCreated"].as_i64().unwrap_or_default()),
+        raw: Some(event.clone()),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::webhook::tests::request;
+    use axum::http::Method;
+    use wiremock::matchers::{body_partial_json, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn channel(configure: impl FnOnce(&mut BlueBubblesConfig)) -> BlueBubblesChannel {
+        let mut bluebubbles = BlueBubblesConfig {
+            enabled: Some(true),
+            server_url: Some("http://mac.local:1234".to_string()),
+            password: Some("hunter2".to_string()),
+            ..Default::default()
+        };
+        configure(&mut bluebubbles);
+        let mut config = Config::default();
+        config.channels.bluebubbles = Some(bluebubbles);
+        BlueBubblesChannel::new(&config)
+    }
+
+    fn event(chat_guid: &str) -> Value {
+        serde_json::json!({
+            "type": "new-message",
+            "data": {
+                "guid": "p:0/ABC",
+                "text": "hello",
+                "isFromMe": false,
+                "dateCreated": 1700000000000i64,
+                "handle": { "address": "+15551234567" },
+                "chats": [{ "guid": chat_guid, "displayName": "" }],
+                "attachments": [
+                    { "guid": "att-1", "mimeType": "image/jpeg", "transferName": "IMG_1.jpg" }
+                ]
+            }
+        })
+    }
+
+    fn normalize_event(event: &Value, server_url: Option<&str>) -> Option<NormalizedMessage> {
+        match parse_event("bluebubbles", event, server_url)? {
+            Event::Message(msg) => Some(msg),
+            Event::Tapback(_) => None,
+        }
+    }
+
+    #[test]
+    fn webhook_password_is_checked() {
+        let channel = channel(|_| {});
+        assert!(channel.password_valid(&request(
+            Method::POST,
+            "webhook?password=hunter2",
+            &[],
+            b""
+        )));
+        assert!(!channel.password_valid(&request(Method::POST, "webhook?password=nope", &[], b"")));
+        assert!(!channel.password_valid(&request(Method::POST, "webhook", &[], b"")));
+    }
+
+    #[test]
+    fn group_guids_and_attachments() {
+        let dm = normalize_event(
+            &event("iMessage;-;+15551234567"),
+            Some("http://mac.local:1234"),
+        )
+        .unwrap();
+        assert_eq!(dm.chat_type, ChatType::Dm);
+        assert_eq!(
+            dm.attachments[0].url.as_deref(),
+            Some("http://mac.local:1234/api/v1/attachment/att-1/download")
+        );
+
+        let group = normalize_event(&event("iMessage;+;chat42"), None).unwrap();
+        assert_eq!(group.chat_type, ChatType::Group);
+        assert!(!channel(|_| {}).admitted(&group));
+        assert!(channel(|c| c.group_policy = Some(GroupPolicy::Open)).admitted(&group));
+
+        let mut own = event("iMessage;-;+15551234567");
+        own["data"]["isFromMe"] = true.into();
+        assert!(normalize_event(&own, None).is_none());
+
+        assert_eq!(chat_guid("iMessage;+;chat42"), "iMessage;+;chat42");
+        assert_eq!(chat_guid("chat42"), "iMessage;+;chat42");
+        assert_eq!(chat_guid("+15551234567"), "iMessage;-;+15551234567");
+    }
+
+    #[test]
+    fn tapbacks_become_reactions() {
+        let mut love = event("iMessage;+;chat42");
+        love["data"]["associatedMessageGuid"] = "p:0/MSG-1".into();
+        love["data"]["associatedMessageType"] = "love".into();
+        let Some(Event::Tapback(InboundEvent::Reaction {
+            channel,
+            chat_id,
+            message_id,
+            sender,
+            emoji,
+        })) = parse_event("imessage", &love, None)
+        else {
+            panic!("expected a tapback");
+        };
+        assert_eq!(channel, "imessage");
+        assert_eq!(chat_id, "iMessage;+;chat42");
+        assert_eq!(message_id, "MSG-1");
+        assert_eq!(sender.id, "+15551234567");
+        assert_eq!(emoji, "❤️");
+
+        love["data"]["associatedMessageType"] = 3001.into();
+        let Some(Event::Tapback(InboundEvent::Reaction { emoji, .. })) =
+            parse_event("imessage", &love, None)
+        else {
+            panic!("expected a tapback");
+        };
+        assert_eq!(emoji, "");
+
+        assert_eq!(Tapback::parse(&json!(2003)), Some((Tapback::Laugh, true)));
+        assert_eq!(
+            Tapback::parse(&json!("-like")),
+            Some((Tapback::Like, false))
+        );
+        assert_eq!(Tapback::parse(&json!("sticker")), None);
+        assert_eq!(Tapback::from_emoji("❤"), Some(Tapback::Love));
+        assert_eq!(Tapback::from_emoji("👀"), None);
+    }
+
+    #[test]
+    fn imessage_is_an_alias_configured_from_its_own_section() {
+        let mut config = Config::default();
+        config.channels.imessage.enabled = Some(true);
+        config.channels.imessage.api_url = Some("http://mac.local:1234".to_string());
+        config.channels.imessage.api_password = Some("pw".to_string());
+        let imessage = BlueBubblesChannel::imessage(&config);
+        assert_eq!(imessage.id(), "imessage");
+        assert_eq!(imessage.meta().name, "iMessage");
+        assert!(imessage.meta().enabled);
+        assert_eq!(imessage.client.password().unwrap(), "pw");
+
+        config.channels.imessage.provider = Some("beeper".to_string());
+        assert!(!BlueBubblesChannel::imessage(&config).meta().enabled);
+    }
+
+    #[tokio::test]
+    async fn sends_text_attachments_tapbacks_and_typing_against_mock_server() {
+        let server = MockServer::start().await;
+        let ok = ResponseTemplate::new(200)
+            .set_body_json(json!({ "status": 200, "data": { "guid": "OUT-1" } }));
+        for (verb, route, body) in [
+            (
+                "POST",
+                "/api/v1/message/text",
+                json!({ "chatGuid": "iMessage;+;chat42", "message": "hi there", "selectedMessageGuid": "MSG-1" }),
+            ),
+            (
+                "POST",
+                "/api/v1/message/react",
+                json!({ "selectedMessageGuid": "MSG-1", "reaction": "like" }),
+            ),
+            (
+                "POST",
+                "/api/v1/message/react",
+                json!({ "selectedMessageGuid": "MSG-1", "reaction": "-like" }),
+            ),
+        ] {
+            Mock::given(method(verb))
+                .and(path(route))
+                .and(query_param("password", "hunter2"))
+                .and(body_partial_json(body))
+                .respond_with(ok.clone())
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("POST"))
+            .and(path("/api/v1/message/attachment"))
+            .respond_with(ok.clone())
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/chat/iMessage%3B%2B%3Bchat42/typing"))
+            .respond_with(ok.clone())
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let channel = channel(|c| c.server_url = Some(server.uri()));
+        let mut reply = NormalizedOutbound::text("chat42", "**hi** there");
+        reply.reply_to_id = Some("MSG-1".to_string());
+        reply.attachments.push(NormalizedAttachment {
+            mime_type: Some("text/plain".to_string()),
+            url: None,
+            data: Some(b"notes".to_vec()),
+            filename: Some("notes.txt".to_string()),
+            size: Some(5),
+        });
+        let id = ChannelPlugin::send(&channel, &reply).await.unwrap();
+        assert_eq!(id.as_deref(), Some("OUT-1"));
+
+        channel.react("chat42", "MSG-1", "👍").await.unwrap();
+        channel.react("chat42", "MSG-1", "").await.unwrap();
+        // Nothing left to remove.
+        channel.react("chat42", "MSG-1", "").await.unwrap();
+        assert!(channel.react("chat42", "MSG-1", "👀").await.is_err());
+
+        channel.send_typing("chat42", None).await.unwrap();
+        let requests = server.received_requests().await.unwrap();
+        let upload = requests
+            .iter()
+            .find(|r| r.url.path() == "/api/v1/message/attachment")
+            .unwrap();
+        let body = String::from_utf8_lossy(&upload.body);
+        assert!(body.contains("iMessage;+;chat42") && body.contains("notes.txt"));
     }
 }
@@ -0,0 +1,473 @@
+//! In-chat commands.
+//!
+//! A message whose text starts with a built-in command (`/new`, `/model`,
+//! ...) is answered here instead of starting an agent turn. Commands act on
+//! the message's session: they reset or compact its history, switch its
+//! model or thinking level, report its status and cancel its runs.
+//!
+//! Text commands are recognised on every channel unless `commands.text` is
+//! false. Telegram and Discord also get the list registered natively
+//! (`commands.native`, or the account's `commands`), and Slack accepts them
+//! as slash commands declared in the app manifest; native invocations go
+//! through [`dispatch`].
+//!
+//! With `commands.ownerAllowFrom` or a `commands.allowFrom` entry for the
+//! channel, only matching senders may run commands; otherwise anyone
+//! admitted to the chat may. Senders are named by id, never by a name they
+//! can change: owners and the `*` list as `<channel>:<id>`, since the same
+//! id names someone else on another platform, and a channel's own list by
+//! bare id as well.
+
+use super::admission::Verdict;
+use super::inbound::session_key_for;
+use super::normalize::NormalizedMessage;
+use crate::config::{AgentModelConfig, Config, ThinkingLevel};
+use crate::gateway::{GatewayState, SessionPatchParams};
+use crate::sessions::COMPACT_KEEP_MESSAGES;
+
+use serde_json::Value;
+use std::collections::HashSet;
+use tracing::{debug, info};
+
+/// A built-in command.
+#[derive(Debug, Clone, Copy)]
+pub struct CommandSpec {
+    /// Name without the leading `/`.
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Name of the optional argument, if the command takes one.
+    pub arg: Option<&'static str>,
+}
+
+/// Built-in commands, in menu order.
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "new",
+        description: "Start a new conversation",
+        arg: None,
+    },
+    CommandSpec {
+        name: "model",
+        description: "Show or switch the model",
+        arg: Some("model"),
+    },
+    CommandSpec {
+        name: "think",
+        description: "Show or set the thinking level",
+        arg: Some("level"),
+    },
+    CommandSpec {
+        name: "status",
+        description: "Show the session's model and activity",
+        arg: None,
+    },
+    CommandSpec {
+        name: "stop",
+        description: "Stop the reply in progress",
+        arg: None,
+    },
+    CommandSpec {
+        name: "compact",
+        description: "Trim the conversation history",
+        arg: None,
+    },
+    CommandSpec {
+        name: "help",
+        description: "List commands",
+        arg: None,
+    },
+];
+
+const THINKING_LEVELS: &str = "off, minimal, low, medium, high, xhigh";
+
+/// Whether an account registers commands natively; the account's
+/// `commands` flag overrides `commands.native`.
+pub fn native_enabled(config: &Config, account_flag: Option<bool>) -> bool {
+    account_flag.unwrap_or_else(|| config.commands.native_enabled())
+}
+
+/// Split a command message into the built-in command and its argument.
+///
+/// `/reset` is accepted for `/new`, and a `@botname` suffix (as Telegram
+/// adds in groups) is ignored.
+pub fn parse(text: &str) -> Option<(&'static CommandSpec, &str)> {
+    let rest = text.trim().strip_prefix('/')?;
+    let (word, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let name = word.split('@').next().unwrap_or(word).to_ascii_lowercase();
+    let name = if name == "reset" {
+        "new"
+    } else {
+        name.as_str()
+    };
+    let spec = COMMANDS.iter().find(|c| c.name == name)?;
+    Some((spec, args.trim()))
+}
+
+/// Run a command received natively (a Discord interaction or a Slack
+/// slash command).
+///
+/// The sender is admitted as for any message, but `commands.text` does
+/// not apply. Returns `None` when the message is not a command or the
+/// sender is dropped silently.
+pub async fn dispatch(state: &GatewayState, msg: &NormalizedMessage) -> Option<String> {
+    let config = state.config.read().await.clone();
+    match state.admission.check(&config, msg) {
+        Verdict::Admit => {}
+        Verdict::Reply(notice) => return Some(notice),
+        Verdict::Drop => return None,
+    }
+    let session_key = session_key_for(state, &config, msg).await;
+    run(state, &config, &session_key, msg)
+}
+
+/// Run `msg` as a command against `session_key`, returning the reply, or
+/// `None` if the text is not a built-in command.
+pub fn run(
+    state: &GatewayState,
+    config: &Config,
+    session_key: &str,
+    msg: &NormalizedMessage,
+) -> Option<String> {
+    let (spec, arg) = parse(&msg.text)?;
+    if !authorized(config, msg) {
+        debug!(channel = %msg.channel, sender = %msg.sender.id, command = spec.name, "Command not allowed");
+        return Some(format!("You are not allowed to use /{}.", spec.name));
+    }
+    info!(
+        channel = %msg.channel,
+        sender = %msg.sender.id,
+        session = %session_key,
+        command = spec.name,
+        "Running chat command"
+    );
+
+    let reply = match spec.name {
+        "new" => {
+            state.runs.cancel(session_key);
+            state.sessions.reset_session(session_key);
+            "Started a new conversation.".to_string()
+        }
+        "model" => model(state, config, session_key, arg),
+        "think" => think(state, config, session_key, arg),
+        "status" => status(state, config, session_key),
+        "stop" => match state.runs.cancel(session_key) {
+            0 => "Nothing to stop.".to_string(),
+            _ => "Stopped.".to_string(),
+        },
+        "compact" => match state
+            .sessions
+            .compact_history(session_key, COMPACT_KEEP_MESSAGES)
+        {
+            Some((before, after)) if after < before => {
+                format!("Compacted the conversation from {before} to {after} messages.")
+            }
+            _ => "Nothing to compact.".to_string(),
+        },
+        _ => help(),
+    };
+    Some(reply)
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+fn model(state: &GatewayState, config: &Config, session_key: &str, arg: &str) -> String {
+    let primary = primary_model(config);
+    if arg.is_empty() {
+        let current = current_model(state, config, session_key);
+        let mut reply = format!("Model: {current}{}", cooldown_note(state, &current));
+        let choices = model_choices(config);
+        if !choices.is_empty() {
+            reply.push_str(&format!("\nAvailable: {}", choices.join(", ")));
+        }
+        return reply;
+    }
+
+    let model = if arg.eq_ignore_ascii_case("default") {
+        primary
+    } else {
+        // Configured aliases resolve to their model id.
+        config
+            .agent
+            .models
+            .iter()
+            .find(|(_, entry)| {
+                entry
+                    .alias
+                    .as_deref()
+                    .is_some_and(|alias| alias.eq_ignore_ascii_case(arg))
+            })
+            .map_or_else(|| arg.to_string(), |(id, _)| id.clone())
+    };
+    if let Err(e) = crate::providers::resolve_provider(config, &model) {
+        return format!("Can't use {model}: {e}");
+    }
+    patch(state, config, session_key, Some(model.clone()), None);
+    format!("Model set to {model}.{}", cooldown_note(state, &model))
+}
+
+fn think(state: &GatewayState, config: &Config, session_key: &str, arg: &str) -> String {
+    if arg.is_empty() {
+        let level = current_thinking(state, config, session_key);
+        return format!("Thinking: {}\nLevels: {THINKING_LEVELS}", level.as_str());
+    }
+    match arg.parse::<ThinkingLevel>() {
+        Ok(level) => {
+            patch(
+                state,
+                config,
+                session_key,
+                None,
+                Some(level.as_str().to_string()),
+            );
+            format!("Thinking set to {}.", level.as_str())
+        }
+        Err(_) => format!("Unknown thinking level \"{arg}\". Levels: {THINKING_LEVELS}"),
+    }
+}
+
+fn status(state: &GatewayState, config: &Config, session_key: &str) -> String {
+    let model = current_model(state, config, session_key);
+    let messages = state
+        .sessions
+        .get_session_handle(session_key)
+        .map_or(0, |session| session.get_history().len());
+    let activity = match state.runs.running(session_key) {
+        0 => "idle".to_string(),
+        1 => "replying".to_string(),
+        n => format!("{n} replies in progress"),
+    };
+    format!(
+        "Session: {session_key}\nModel: {model}{}\nThinking: {}\nMessages: {messages}\nStatus: {activity}",
+        cooldown_note(state, &model),
+        current_thinking(state, config, session_key).as_str(),
+    )
+}
+
+fn help() -> String {
+    let lines: Vec<String> = COMMANDS
+        .iter()
+        .map(|c| match c.arg {
+            Some(arg) => format!("/{} [{arg}] - {}", c.name, c.description),
+            None => format!("/{} - {}", c.name, c.description),
+        })
+        .collect();
+    lines.join("\n")
+}
+
+// ============================================================================
+// Helpers
+// ============================================================================
+
+fn primary_model(config: &Config) -> String {
+    config
+        .agent
+        .model
+        .primary_model()
+        .unwrap_or_else(|| "claude-sonnet-4-6".to_string())
+}
+
+/// The model the session's next turn will use.
+fn current_model(state: &GatewayState, config: &Config, session_key: &str) -> String {
+    state
+        .sessions
+        .get_session(session_key)
+        .and_then(|info| info.model)
+        .unwrap_or_else(|| primary_model(config))
+}
+
+fn current_thinking(state: &GatewayState, config: &Config, session_key: &str) -> ThinkingLevel {
+    state
+        .sessions
+        .get_session(session_key)
+        .and_then(|info| info.thinking)
+        .and_then(|level| level.parse().ok())
+        .or(config.agent.thinking_default)
+        .unwrap_or_default()
+}
+
+/// Primary and fallback models plus the `agent.models` entries.
+fn model_choices(config: &Config) -> Vec<String> {
+    let mut ids = vec![primary_model(config)];
+    if let AgentModelConfig::Detailed(list) = &config.agent.model {
+        ids.extend(list.fallbacks.iter().cloned());
+    }
+    let mut configured: Vec<&String> = config.agent.models.keys().collect();
+    configured.sort();
+    ids.extend(configured.into_iter().cloned());
+
+    let mut seen = HashSet::new();
+    ids.retain(|id| seen.insert(id.clone()));
+    ids.into_iter()
+        .map(|id| {
+            match config
+                .agent
+                .models
+                .get(&id)
+                .and_then(|e| e.alias.as_deref())
+            {
+                Some(alias) => format!("{id} ({alias})"),
+                None => id,
+            }
+        })
+        .collect()
+}
+
+fn cooldown_note(state: &GatewayState, model: &str) -> &'static str {
+    if state.rpc.model_fallback.read().is_on_cooldown(model) {
+        " (cooling down after a failure)"
+    } else {
+        ""
+    }
+}
+
+fn patch(
+    state: &GatewayState,
+    config: &Config,
+    session_key: &str,
+    model: Option<String>,
+    thinking: Option<String>,
+) {
+    state.sessions.get_or_create_session(session_key, config);
+    state.sessions.patch_session(&SessionPatchParams {
+        session_key: session_key.to_string(),
+        title: None,
+        model,
+        thinking,
+    });
+}
+
+/// Whether the sender may run commands in `msg`'s channel.
+fn authorized(config: &Config, msg: &NormalizedMessage) -> bool {
+    let commands = &config.commands;
+    let owners = commands.owner_allow_from.as_deref().unwrap_or_default();
+    let lists = commands.allow_from.as_ref();
+    let (channel_list, scoped) = match lists.and_then(|l| l.get(&msg.channel)) {
+        Some(list) => (Some(list), true),
+        None => (lists.and_then(|l| l.get("*")), false),
+    };
+    if owners.is_empty() && channel_list.is_none() {
+        return true;
+    }
+
+    // A bare id only counts in a list scoped to the message's channel.
+    let id = &msg.sender.id;
+    let qualified = format!("{}:{id}", msg.channel);
+    let matches =
+        |entry: &str, scoped: bool| entry == "*" || entry == qualified || (scoped && entry == id);
+    owners.iter().any(|entry| matches(entry, false))
+        || channel_list.is_some_and(|entries| {
+            entries.iter().any(|entry| match entry {
+                Value::String(s) => matches(s, scoped),
+                Value::Number(n) => matches(&n.to_string(), scoped),
+                _ => false,
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::{ChatType, NormalizedSender};
+    use std::collections::HashMap;
+
+    fn message(channel: &str, sender: &str, text: &str) -> NormalizedMessage {
+        NormalizedMessage {
+            id: "m1".to_string(),
+            channel: channel.to_string(),
+            account_id: "default".to_string(),
+            chat_id: sender.to_string(),
+            chat_name: None,
+            chat_type: ChatType::Dm,
+            sender: NormalizedSender {
+                id: sender.to_string(),
+                name: "Ann".to_string(),
+                is_bot: false,
+                roles: Vec::new(),
+            },
+            text: text.to_string(),
+            attachments: Vec::new(),
+            reply_to_id: None,
+            thread_id: None,
+            mentioned: true,
+            timestamp: String::new(),
+            raw: None,
+        }
+    }
+
+    #[test]
+    fn parses_builtin_commands() {
+        let (spec, arg) = parse("/model  gpt-4o ").unwrap();
+        assert_eq!((spec.name, arg), ("model", "gpt-4o"));
+        assert_eq!(parse("/NEW@my_bot").unwrap().0.name, "new");
+        assert_eq!(parse("/reset").unwrap().0.name, "new");
+        assert!(parse("/unknown").is_none());
+        assert!(parse("hello /new").is_none());
+    }
+
+    #[test]
+    fn everyone_may_run_commands_without_lists() {
+        let config = Config::default();
+        assert!(authorized(&config, &message("telegram", "42", "/new")));
+    }
+
+    #[test]
+    fn lists_restrict_commands() {
+        let mut config = Config::default();
+        config.commands.owner_allow_from = Some(vec!["telegram:1".to_string()]);
+        config.commands.allow_from = Some(HashMap::from([(
+            "discord".to_string(),
+            vec![serde_json::json!(7)],
+        )]));
+
+        assert!(authorized(&config, &message("telegram", "1", "/new")));
+        assert!(!authorized(&config, &message("telegram", "2", "/new")));
+        assert!(authorized(&config, &message("discord", "7", "/new")));
+        assert!(!authorized(&config, &message("discord", "8", "/new")));
+        // Another channel's owner entry does not match.
+        assert!(!authorized(&config, &message("slack", "1", "/new")));
+    }
+
+    #[test]
+    fn bare_ids_only_match_on_their_channel() {
+        let mut config = Config::default();
+        // Telegram user 42 and Discord user 42 are different people.
+        config.commands.owner_allow_from = Some(vec!["42".to_string()]);
+        assert!(!authorized(&config, &message("telegram", "42", "/new")));
+        assert!(!authorized(&config, &message("discord", "42", "/new")));
+
+        config.commands.owner_allow_from = None;
+        config.commands.allow_from = Some(HashMap::from([
+            (
+                "*".to_string(),
+                vec![serde_json::json!(42), serde_json::json!("slack:U1")],
+            ),
+            ("telegram".to_string(), vec![serde_json::json!(42)]),
+        ]));
+        assert!(authorized(&config, &message("telegram", "42", "/new")));
+        assert!(!authorized(&config, &message("discord", "42", "/new")));
+        assert!(authorized(&config, &message("slack", "U1", "/new")));
+    }
+
+    #[test]
+    fn owners_match_sender_ids_only() {
+        let mut config = Config::default();
+        config.commands.owner_allow_from = Some(vec!["Ann".to_string(), "@ann".to_string()]);
+        // The sender's display name and Telegram username are not ids.
+        let mut msg = message("telegram", "42", "/model gpt-4o");
+        msg.raw = Some(serde_json::json!({ "from": { "id": 42, "username": "ann" } }));
+        assert!(!authorized(&config, &msg));
+        assert!(!authorized(&config, &message("discord", "7", "/stop")));
+
+        // Per-channel lists compare ids as well.
+        config.commands.allow_from = Some(HashMap::from([(
+            "telegram".to_string(),
+            vec![serde_json::json!("ann"), serde_json::json!("@ann")],
+        )]));
+        assert!(!authorized(&config, &msg));
+
+        config.commands.owner_allow_from = Some(vec!["telegram:42".to_string()]);
+        assert!(authorized(&config, &msg));
+    }
+}
@@ -1,12 +1,37 @@
-use crate::config::Config;
+use crate::config::{
+    Config, DiscordAccountConfig, DiscordGuildChannelConfig, DiscordGuildEntry, GroupPolicy,
+    ReplyToMode,
+};
 use crate::gateway::GatewayState;
+use crate::infra::dm_policy;
 
-use super::plugin::{ChannelCapability, ChannelMeta, ChannelPlugin};
+use super::admission::account;
+use super::commands::{self, COMMANDS};
+use super::format::{render_markdown_chunks, FormatTarget};
+use super::group_history::admit_group_message;
+use super::inbound::{dispatch_inbound, typing_interval_ms};
+use super::normalize::{
+    split_text, ChatType, NormalizedAttachment, NormalizedMessage, NormalizedOutbound,
+    NormalizedSender,
+};
+use super::plugin::{
+    unsupported, ChannelCapability, ChannelMeta, ChannelPlugin, DEFAULT_ACCOUNT_ID,
+};
+use super::voice;
+use super::TypingKeepaliveLoop;
 
-use anyhow::Result;
+use anyhow::{Context as _, Result};
 use async_trait::async_trait;
+use parking_lot::{Mutex, RwLock};
 use serde::{Deserialize, Serialize};
-use tracing::{info, warn};
+use serenity::all::{
+    Channel, ChannelId, ChannelType, ClientBuilder, CommandInteraction, Context,
+    CreateAllowedMentions, CreateAttachment, CreateInteractionResponse,
+    CreateInteractionResponseMessage, CreateMessage, CreateThread, EventHandler, GatewayIntents,
+    Http, HttpBuilder, Interaction, Message, MessageId, ReactionType, Ready, ShardManager, UserId,
+};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
 
 // ============================================================================
 // v2026.2.26: Slash Command Validation
@@ -86,7 +111,9 @@ pub fn validate_slash_command(cmd: &SlashCommand) -> Vec<SlashCommandValidationE
     {
         errors.push(SlashCommandValidationError {
             field: "name".to_string(),
-            message: "Command name must only contain alphanumeric characters, hyphens, or underscores".to_string(),
+            message:
+                "Command name must only contain alphanumeric characters, hyphens, or underscores"
+                    .to_string(),
         });
     }
 
@@ -134,10 +161,7 @@ pub fn validate_slash_command(cmd: &SlashCommand) -> Vec<SlashCommandValidationE
         if opt.option_type == 0 || opt.option_type > 11 {
             errors.push(SlashCommandValidationError {
                 field: format!("options[{}].type", i),
-                message: format!(
-                    "Option type must be 1-11, got {}",
-                    opt.option_type
-                ),
+                message: format!("Option type must be 1-11, got {}", opt.option_type),
             });
         }
     }
@@ -173,19 +197,370 @@ pub fn filter_valid_commands(commands: Vec<SlashCommand>) -> Vec<SlashCommand> {
 // Discord Channel Implementation
 // ============================================================================
 
+/// Default Discord API origin; `apiUrl` replaces it (e.g. for an HTTP proxy).
+const DISCORD_API_URL: &str = "https://discord.com";
+/// Maximum message length accepted by the Discord API.
+const DISCORD_MAX_MESSAGE_CHARS: usize = 2000;
+/// Discord shows "typing…" for ~10 s; refresh before it lapses.
+const TYPING_INTERVAL_MS: u64 = 8_000;
+/// Maximum length of an auto-created thread name.
+const THREAD_NAME_MAX_CHARS: usize = 80;
+
+/// Per-account state shared between the channel and its gateway handler.
+struct DiscordAccount {
+    account_id: String,
+    config: DiscordAccountConfig,
+    bot_token: Option<String>,
+    /// Bot user id, learned from the READY event.
+    bot_id: RwLock<Option<u64>>,
+}
+
 /// Discord channel implementation using serenity.
+///
+/// Connects to the Discord gateway with a serenity client; guild
+/// messages are admitted according to `guilds` (per-guild and per-channel
+/// allowlists, user lists and `requireMention`), DMs according to `dm`.
 pub struct DiscordChannel {
     enabled: bool,
-    bot_token: Option<String>,
+    account: Arc<DiscordAccount>,
+    /// Shard manager and task of the running gateway client, if any.
+    client: Mutex<Option<(Arc<ShardManager>, tokio::task::JoinHandle<()>)>>,
 }
 
 impl DiscordChannel {
     pub fn new(config: &Config) -> Self {
+        Self::for_account(config, DEFAULT_ACCOUNT_ID)
+    }
+
+    /// The channel for one bot account: an entry of `accounts`, or the
+    /// top-level settings for the default account.
+    pub fn for_account(config: &Config, account_id: &str) -> Self {
         let dc = &config.channels.discord;
-        let bot_token = dc.default_account.token.clone();
-        let enabled = dc.default_account.enabled.unwrap_or(bot_token.is_some());
+        let settings = account(&dc.accounts, &dc.default_account, account_id);
+        let bot_token = settings.token.clone();
+        let enabled = settings.enabled.unwrap_or(bot_token.is_some());
+        Self {
+            enabled,
+            account: Arc::new(DiscordAccount {
+                account_id: account_id.to_string(),
+                config: settings.clone(),
+                bot_token,
+                bot_id: RwLock::new(None),
+            }),
+            client: Mutex::new(None),
+        }
+    }
+}
+
+impl DiscordAccount {
+    /// Build an HTTP client, honouring `apiUrl`.
+    fn http(&self) -> Result<Http> {
+        let token = self
+            .bot_token
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Discord bot token not configured"))?;
+        let mut builder = HttpBuilder::new(token);
+        if let Some(api_url) = self.config.api_url.as_deref() {
+            if api_url.trim_end_matches('/') != DISCORD_API_URL {
+                // Serenity's ratelimiter ignores the proxy, so it is
+                // disabled and rate limiting is left to the proxy.
+                builder = builder.proxy(api_url).ratelimiter_disabled(true);
+            }
+        }
+        Ok(builder.build())
+    }
+
+    fn intents(&self) -> GatewayIntents {
+        let mut intents = GatewayIntents::GUILDS
+            | GatewayIntents::GUILD_MESSAGES
+            | GatewayIntents::DIRECT_MESSAGES
+            | GatewayIntents::MESSAGE_CONTENT;
+        if let Some(extra) = &self.config.intents {
+            if extra.presence == Some(true) {
+                intents |= GatewayIntents::GUILD_PRESENCES;
+            }
+            if extra.guild_members == Some(true) {
+                intents |= GatewayIntents::GUILD_MEMBERS;
+            }
+        }
+        intents
+    }
+
+    fn text_chunk_limit(&self) -> usize {
+        (self.config.text_chunk_limit as usize).clamp(1, DISCORD_MAX_MESSAGE_CHARS)
+    }
+
+    /// Resolve the guild and channel config for a guild message.
+    ///
+    /// Threads inherit the config of their parent channel.
+    fn guild_config(
+        &self,
+        guild_id: &str,
+        channel_id: &str,
+    ) -> (
+        Option<&DiscordGuildEntry>,
+        Option<&DiscordGuildChannelConfig>,
+        bool,
+    ) {
+        let guilds = self.config.guilds.as_ref();
+        let explicit = guilds.and_then(|g| g.get(guild_id));
+        let guild = explicit.or_else(|| guilds.and_then(|g| g.get("*")));
+        let channel = guild
+            .and_then(|g| g.channels.as_ref())
+            .and_then(|c| c.get(channel_id).or_else(|| c.get("*")));
+        (guild, channel, explicit.is_some())
+    }
 
-        Self { enabled, bot_token }
+    /// Decide whether an inbound message should reach the agent.
+    fn admit(&self, msg: &NormalizedMessage, guild_id: Option<&str>) -> bool {
+        if msg.sender.is_bot && self.config.allow_bots != Some(true) {
+            return false;
+        }
+
+        let Some(guild_id) = guild_id else {
+            let dm = self.config.dm.as_ref().or(self.config.dms.as_ref());
+            // `dm.policy` and `dm.allowFrom` are applied by the admission stage.
+            return dm.and_then(|d| d.enabled) != Some(false);
+        };
+
+        let (guild, channel, explicit) = self.guild_config(guild_id, &msg.chat_id);
+
+        if channel.is_some_and(|c| c.enabled == Some(false) || c.allow == Some(false)) {
+            return false;
+        }
+
+        match self.config.group_policy.unwrap_or_default() {
+            GroupPolicy::Disabled => return false,
+            GroupPolicy::Allowlist => {
+                if !explicit {
+                    return false;
+                }
+                // A guild with a channel list only admits listed channels.
+                let has_channel_list = guild
+                    .and_then(|g| g.channels.as_ref())
+                    .is_some_and(|c| !c.is_empty());
+                if has_channel_list && channel.is_none() {
+                    return false;
+                }
+            }
+            GroupPolicy::Open => {}
+        }
+
+        let users = channel
+            .and_then(|c| c.users.as_deref())
+            .or_else(|| guild.and_then(|g| g.users.as_deref()))
+            .filter(|u| !u.is_empty());
+        if let Some(users) = users {
+            if !sender_matches(users, &msg.sender) {
+                return false;
+            }
+        }
+
+        let require_mention = channel
+            .and_then(|c| c.require_mention)
+            .or_else(|| guild.and_then(|g| g.require_mention))
+            .unwrap_or(true);
+        !require_mention || msg.mentioned
+    }
+
+    /// Process one gateway message: normalise, admit, run the agent and reply.
+    async fn handle_message(&self, ctx: &Context, state: &GatewayState, message: &Message) {
+        let bot_id = *self.bot_id.read();
+        let thread_parent = match message.guild_id {
+            Some(_) => thread_parent(ctx, message.channel_id).await,
+            None => None,
+        };
+        let Some(mut normalized) =
+            normalize_message(&self.account_id, message, bot_id, thread_parent)
+        else {
+            return;
+        };
+
+        let guild_id = message.guild_id.map(|g| g.get().to_string());
+        let admitted = admit_group_message(state, &mut normalized, |msg| {
+            self.admit(msg, guild_id.as_deref())
+        })
+        .await;
+        if !admitted {
+            debug!(
+                channel_id = %message.channel_id,
+                sender = %normalized.sender.id,
+                "Discord message not admitted"
+            );
+            return;
+        }
+
+        // Reply inside a new thread when the channel asks for it.
+        let auto_thread = thread_parent.is_none()
+            && guild_id
+                .as_deref()
+                .and_then(|g| self.guild_config(g, &normalized.chat_id).1)
+                .and_then(|c| c.auto_thread)
+                .unwrap_or(false);
+        let mut reply_channel = message.channel_id;
+        let mut reply_to = match self.config.reply_to_mode.unwrap_or_default() {
+            ReplyToMode::Off => None,
+            ReplyToMode::First | ReplyToMode::All => Some(message.id),
+        };
+        if auto_thread {
+            let thread =
+                CreateThread::new(thread_name(&normalized.text)).kind(ChannelType::PublicThread);
+            match message
+                .channel_id
+                .create_thread_from_message(&ctx.http, message.id, thread)
+                .await
+            {
+                Ok(thread) => {
+                    reply_channel = thread.id;
+                    reply_to = None;
+                }
+                Err(e) => warn!(error = %e, "Discord thread creation failed"),
+            }
+        }
+
+        let interval = typing_interval_ms(&*state.config.read().await, TYPING_INTERVAL_MS);
+        let _ = reply_channel.broadcast_typing(&ctx.http).await;
+        let typing = TypingKeepaliveLoop::new(interval);
+        let typing_task = {
+            let http = ctx.http.clone();
+            typing.start(move || {
+                let http = http.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = reply_channel.broadcast_typing(&http).await {
+                        debug!(error = %e, "Discord typing indicator failed");
+                    }
+                });
+            })
+        };
+
+        let result = dispatch_inbound(state, &normalized).await;
+        typing.stop();
+        typing_task.abort();
+
+        match result {
+            Ok(Some(reply)) => {
+                let outbound = NormalizedOutbound {
+                    reply_to_id: reply_to.map(|id| id.get().to_string()),
+                    account_id: Some(self.account_id.clone()),
+                    ..NormalizedOutbound::text(reply_channel.get().to_string(), reply)
+                };
+                let outbound = voice::voice_reply(state, &normalized, outbound).await;
+                if let Err(e) = state.channels.enqueue("discord", outbound).await {
+                    warn!(channel_id = %reply_channel, error = %e, "Discord reply failed");
+                }
+            }
+            Ok(None) => {}
+            Err(e) => {
+                warn!(channel_id = %reply_channel, error = %e, "Discord agent run failed");
+            }
+        }
+    }
+
+    /// Register the built-in commands as global application commands.
+    ///
+    /// Discord replaces the whole global command list on every call.
+    async fn register_commands(&self, http: &Http) {
+        match http.create_global_commands(&builtin_slash_commands()).await {
+            Ok(registered) => info!(count = registered.len(), "Registered Discord commands"),
+            Err(e) => warn!(error = %e, "Discord command registration failed"),
+        }
+    }
+
+    /// Run an application command and answer it with an ephemeral message.
+    async fn handle_command(
+        &self,
+        ctx: &Context,
+        state: &GatewayState,
+        command: &CommandInteraction,
+    ) {
+        let thread_parent = match command.guild_id {
+            Some(_) => thread_parent(ctx, command.channel_id).await,
+            None => None,
+        };
+        let msg = normalize_command(&self.account_id, command, thread_parent);
+
+        let guild_id = command.guild_id.map(|g| g.get().to_string());
+        let reply = if self.admit(&msg, guild_id.as_deref()) {
+            commands::dispatch(state, &msg).await
+        } else {
+            None
+        };
+        let reply = reply.unwrap_or_else(|| "Commands are not available here.".to_string());
+
+        let response = CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(reply)
+                .ephemeral(true),
+        );
+        if let Err(e) = command.create_response(&ctx.http, response).await {
+            warn!(command = %command.data.name, error = %e, "Discord command response failed");
+        }
+    }
+
+    /// Send text as one or more messages of at most `textChunkLimit` chars.
+    async fn send_text(
+        &self,
+        http: &Http,
+        channel_id: ChannelId,
+        reply_to: Option<MessageId>,
+        text: &str,
+    ) -> Result<()> {
+        let reply_all = self.config.reply_to_mode == Some(ReplyToMode::All);
+        for (i, chunk) in split_message(
+            text,
+            self.text_chunk_limit(),
+            self.config.max_lines_per_message,
+            self.config.markdown != Some(false),
+        )
+        .into_iter()
+        .enumerate()
+        {
+            // Never ping @everyone/roles/users from agent output.
+            let mut builder = CreateMessage::new()
+                .content(chunk)
+                .allowed_mentions(CreateAllowedMentions::new());
+            if let Some(id) = reply_to.filter(|_| i == 0 || reply_all) {
+                builder = builder.reference_message((channel_id, id));
+            }
+            channel_id.send_message(http, builder).await?;
+        }
+        Ok(())
+    }
+}
+
+/// serenity gateway event handler for one Discord account.
+struct Handler {
+    account: Arc<DiscordAccount>,
+    state: GatewayState,
+}
+
+#[async_trait]
+impl EventHandler for Handler {
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        info!(user = %ready.user.name, "Discord bot connected");
+        *self.account.bot_id.write() = Some(ready.user.id.get());
+
+        let native = commands::native_enabled(
+            &*self.state.config.read().await,
+            self.account.config.commands,
+        );
+        if native {
+            self.account.register_commands(&ctx.http).await;
+        }
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        if let Interaction::Command(command) = interaction {
+            self.account
+                .handle_command(&ctx, &self.state, &command)
+                .await;
+        }
+    }
+
+    async fn message(&self, ctx: Context, message: Message) {
+        self.account
+            .handle_message(&ctx, &self.state, &message)
+            .await;
     }
 }
 
@@ -195,6 +570,10 @@ impl ChannelPlugin for DiscordChannel {
         "discord"
     }
 
+    fn account_id(&self) -> &str {
+        &self.account.account_id
+    }
+
     fn meta(&self) -> ChannelMeta {
         ChannelMeta {
             name: "Discord".to_string(),
@@ -213,6 +592,7 @@ impl ChannelPlugin for DiscordChannel {
             ChannelCapability::Reactions,
             ChannelCapability::Groups,
             ChannelCapability::Threads,
+            ChannelCapability::TypingIndicators,
             ChannelCapability::EditMessage,
             ChannelCapability::DeleteMessage,
             ChannelCapability::Stickers,
@@ -221,12 +601,12 @@ impl ChannelPlugin for DiscordChannel {
         ]
     }
 
-    async fn start_account(&self, _state: &GatewayState) -> Result<()> {
+    async fn start_account(&self, state: &GatewayState) -> Result<()> {
         if !self.enabled {
             return Ok(());
         }
 
-        let token = match &self.bot_token {
+        let token = match &self.account.bot_token {
             Some(t) => t,
             None => {
                 warn!("Discord channel enabled but no bot token configured");
@@ -239,7 +619,25 @@ impl ChannelPlugin for DiscordChannel {
             &token[token.len().saturating_sub(4)..]
         );
 
-        // TODO: Initialise a serenity::Client with a gateway handler.
+        let handler = Handler {
+            account: self.account.clone(),
+            state: state.clone(),
+        };
+        let mut client = ClientBuilder::new_with_http(self.account.http()?, self.account.intents())
+            .event_handler(handler)
+            .await
+            .context("failed to build Discord client")?;
+
+        let shard_manager = client.shard_manager.clone();
+        let task = tokio::spawn(async move {
+            if let Err(e) = client.start().await {
+                warn!(error = %e, "Discord gateway client exited");
+            }
+        });
+        let previous = self.client.lock().replace((shard_manager, task));
+        if let Some((previous, _)) = previous {
+            previous.shutdown_all().await;
+        }
 
         Ok(())
     }
@@ -247,38 +645,598 @@ impl ChannelPlugin for DiscordChannel {
     async fn stop_account(&self) -> Result<()> {
         if self.enabled {
             info!("Discord channel stopping");
-            // TODO: Shut down the serenity client.
+            let client = self.client.lock().take();
+            if let Some((shard_manager, task)) = client {
+                shard_manager.shutdown_all().await;
+                let _ = task.await;
+            }
         }
         Ok(())
     }
 
-    async fn send_message(&self, to: &str, _message: &str) -> Result<()> {
-        let _token = self
-            .bot_token
-            .as_deref()
-            .ok_or_else(|| anyhow::anyhow!("Discord bot token not configured"))?;
+    async fn probe(&self) -> Result<()> {
+        if self
+            .client
+            .lock()
+            .as_ref()
+            .is_some_and(|(_, task)| task.is_finished())
+        {
+            anyhow::bail!("Discord gateway client exited");
+        }
+        Ok(())
+    }
 
-        let _channel_id: u64 = to
-            .parse()
-            .map_err(|_| anyhow::anyhow!("invalid Discord channel_id: {to}"))?;
+    async fn send_message(&self, to: &str, message: &str) -> Result<()> {
+        self.send(&NormalizedOutbound::text(to, message)).await?;
+        Ok(())
+    }
 
+    async fn send(&self, message: &NormalizedOutbound) -> Result<Option<String>> {
+        // Only inline bytes (voice replies) are uploaded so far.
+        let files = message
+            .attachments
+            .iter()
+            .map(|a| {
+                let filename = a.filename.clone().unwrap_or_else(|| "file".to_string());
+                a.data
+                    .clone()
+                    .map(|data| CreateAttachment::bytes(data, filename))
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| unsupported(self.id(), ChannelCapability::SendMedia))?;
+        let http = self.account.http()?;
+
+        // Threads are channels of their own on Discord.
+        let to = message.thread_id.as_deref().unwrap_or(&message.chat_id);
         info!(channel_id = to, "Discord: sending message");
 
-        // TODO: Use serenity HTTP client to send a message to _channel_id.
+        let channel_id = match to.strip_prefix("user:") {
+            Some(user) => {
+                let user_id: u64 = user
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("invalid Discord user id: {to}"))?;
+                UserId::new(user_id).create_dm_channel(&http).await?.id
+            }
+            None => {
+                let channel_id: u64 = to
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("invalid Discord channel_id: {to}"))?;
+                ChannelId::new(channel_id)
+            }
+        };
+        let reply_to = message
+            .reply_to_id
+            .as_deref()
+            .and_then(|id| id.parse().ok())
+            .filter(|&id| id != 0)
+            .map(MessageId::new);
 
+        if !files.is_empty() {
+            let mut builder = CreateMessage::new().add_files(files);
+            if let Some(id) = reply_to {
+                builder = builder.reference_message((channel_id, id));
+            }
+            channel_id.send_message(&http, builder).await?;
+        }
+        if !message.text.trim().is_empty() {
+            self.account
+                .send_text(&http, channel_id, reply_to, &message.text)
+                .await?;
+        }
+        Ok(None)
+    }
+
+    async fn react(&self, chat_id: &str, message_id: &str, emoji: &str) -> Result<()> {
+        let http = self.account.http()?;
+        let channel_id = chat_id
+            .parse()
+            .ok()
+            .filter(|&id| id != 0)
+            .map(ChannelId::new)
+            .with_context(|| format!("invalid Discord channel_id: {chat_id}"))?;
+        let message_id = message_id
+            .parse()
+            .ok()
+            .filter(|&id| id != 0)
+            .map(MessageId::new)
+            .with_context(|| format!("invalid Discord message id: {message_id}"))?;
+
+        if !emoji.is_empty() {
+            let reaction = ReactionType::Unicode(emoji.to_string());
+            http.create_reaction(channel_id, message_id, &reaction)
+                .await
+                .context("Discord create reaction failed")?;
+            return Ok(());
+        }
+        // Removing: drop every reaction the bot left on the message.
+        let message = http
+            .get_message(channel_id, message_id)
+            .await
+            .context("Discord get message failed")?;
+        for reaction in message.reactions.iter().filter(|r| r.me) {
+            http.delete_reaction_me(channel_id, message_id, &reaction.reaction_type)
+                .await
+                .context("Discord delete reaction failed")?;
+        }
         Ok(())
     }
 }
 
-/// Convenience function called by the top-level `send_message` dispatcher.
-pub(crate) async fn send_message(config: &Config, to: &str, message: &str) -> Result<()> {
-    let channel = DiscordChannel::new(config);
-    channel.send_message(to, message).await
+// ============================================================================
+// Message Normalization
+// ============================================================================
+
+/// Parent channel id if `channel_id` is a thread, using the cache when possible.
+async fn thread_parent(ctx: &Context, channel_id: ChannelId) -> Option<u64> {
+    match channel_id.to_channel(ctx).await {
+        Ok(Channel::Guild(channel))
+            if matches!(
+                channel.kind,
+                ChannelType::PublicThread | ChannelType::PrivateThread | ChannelType::NewsThread
+            ) =>
+        {
+            channel.parent_id.map(|p| p.get())
+        }
+        _ => None,
+    }
+}
+
+/// The built-in chat commands as Discord application commands.
+fn builtin_slash_commands() -> Vec<SlashCommand> {
+    let commands = COMMANDS
+        .iter()
+        .map(|spec| SlashCommand {
+            name: spec.name.to_string(),
+            description: spec.description.to_string(),
+            options: spec
+                .arg
+                .map(|arg| SlashCommandOption {
+                    name: arg.to_string(),
+                    description: format!("New {arg}; leave empty to show the current one"),
+                    option_type: 3,
+                    required: false,
+                })
+                .into_iter()
+                .collect(),
+        })
+        .collect();
+    filter_valid_commands(commands)
+}
+
+/// Convert an application command into a `/name arg` [`NormalizedMessage`].
+///
+/// Invoking a command addresses the bot, so it always counts as a mention.
+fn normalize_command(
+    account_id: &str,
+    command: &CommandInteraction,
+    thread_parent: Option<u64>,
+) -> NormalizedMessage {
+    let arg = command
+        .data
+        .options
+        .iter()
+        .find_map(|o| o.value.as_str())
+        .unwrap_or_default();
+    let text = format!("/{} {arg}", command.data.name)
+        .trim_end()
+        .to_string();
+
+    let channel_id = command.channel_id.get().to_string();
+    let (chat_type, chat_id, thread_id) = match (command.guild_id, thread_parent) {
+        (None, _) => (ChatType::Dm, channel_id, None),
+        (Some(_), Some(parent)) => (ChatType::Thread, parent.to_string(), Some(channel_id)),
+        (Some(_), None) => (ChatType::Group, channel_id, None),
+    };
+
+    let user = &command.user;
+    NormalizedMessage {
+        id: command.id.get().to_string(),
+        channel: "discord".to_string(),
+        account_id: account_id.to_string(),
+        chat_id,
+        chat_name: None,
+        chat_type,
+        sender: NormalizedSender {
+            id: user.id.get().to_string(),
+            name: user
+                .global_name
+                .clone()
+                .unwrap_or_else(|| user.name.clone()),
+            is_bot: user.bot,
+            roles: Vec::new(),
+        },
+        text,
+        attachments: Vec::new(),
+        reply_to_id: None,
+        thread_id,
+        mentioned: true,
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        raw: None,
+    }
+}
+
+/// Convert a Discord message into a [`NormalizedMessage`].
+///
+/// Thread messages are reported against their parent channel with the thread
+/// id in `thread_id`, so channel config and sessions key off the parent.
+/// Returns `None` for the bot's own messages and empty messages.
+fn normalize_message(
+    account_id: &str,
+    message: &Message,
+    bot_id: Option<u64>,
+    thread_parent: Option<u64>,
+) -> Option<NormalizedMessage> {
+    let author_id = message.author.id.get();
+    if bot_id == Some(author_id) {
+        return None;
+    }
+
+    let mentioned = bot_id.is_some_and(|bot| {
+        message.mentions.iter().any(|u| u.id.get() == bot)
+            || message
+                .referenced_message
+                .as_ref()
+                .is_some_and(|m| m.author.id.get() == bot)
+    });
+    let text = match bot_id {
+        Some(bot) => strip_user_mention(&message.content, bot),
+        None => message.content.trim().to_string(),
+    };
+
+    let attachments: Vec<NormalizedAttachment> = message
+        .attachments
+        .iter()
+        .map(|a| NormalizedAttachment {
+            mime_type: a.content_type.clone(),
+            url: Some(a.url.clone()),
+            data: None,
+            filename: Some(a.filename.clone()),
+            size: Some(a.size as u64),
+        })
+        .collect();
+    if text.is_empty() && attachments.is_empty() {
+        return None;
+    }
+
+    let channel_id = message.channel_id.get().to_string();
+    let (chat_type, chat_id, thread_id) = match (message.guild_id, thread_parent) {
+        (None, _) => (ChatType::Dm, channel_id, None),
+        (Some(_), Some(parent)) => (ChatType::Thread, parent.to_string(), Some(channel_id)),
+        (Some(_), None) => (ChatType::Group, channel_id, None),
+    };
+
+    let name = message
+        .member
+        .as_ref()
+        .and_then(|m| m.nick.clone())
+        .or_else(|| message.author.global_name.clone())
+        .unwrap_or_else(|| message.author.name.clone());
+
+    Some(NormalizedMessage {
+        id: message.id.get().to_string(),
+        channel: "discord".to_string(),
+        account_id: account_id.to_string(),
+        chat_id,
+        chat_name: None,
+        chat_type,
+        sender: NormalizedSender {
+            id: author_id.to_string(),
+            name,
+            is_bot: message.author.bot,
+            roles: Vec::new(),
+        },
+        text,
+        attachments,
+        reply_to_id: message
+            .message_reference
+            .as_ref()
+            .and_then(|r| r.message_id)
+            .map(|id| id.get().to_string()),
+        thread_id,
+        mentioned,
+        timestamp: message.timestamp.to_rfc3339().unwrap_or_default(),
+        raw: serde_json::to_value(message).ok(),
+    })
+}
+
+/// Remove `<@id>` / `<@!id>` mentions of the bot from message content.
+fn strip_user_mention(content: &str, bot_id: u64) -> String {
+    content
+        .replace(&format!("<@{bot_id}>"), "")
+        .replace(&format!("<@!{bot_id}>"), "")
+        .split(' ')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim()
+        .to_string()
+}
+
+/// Whether the sender's id or name appears in an allowlist.
+fn sender_matches(allow_from: &[String], sender: &NormalizedSender) -> bool {
+    dm_policy::is_source_allowed(allow_from, &sender.id)
+        || dm_policy::is_source_allowed(allow_from, &sender.name)
+}
+
+/// Thread name derived from the first line of the triggering message.
+fn thread_name(text: &str) -> String {
+    let first_line = text.lines().next().unwrap_or("").trim();
+    let name: String = first_line.chars().take(THREAD_NAME_MAX_CHARS).collect();
+    if name.is_empty() {
+        "Conversation".to_string()
+    } else {
+        name
+    }
+}
+
+/// Split a reply into Discord-sized messages, also honouring
+/// `maxLinesPerMessage` when set.
+///
+/// With `markdown` the reply is rendered first (tables become code blocks,
+/// stray markers are escaped) and split without breaking a span or fence.
+fn split_message(text: &str, limit: usize, max_lines: Option<u32>, markdown: bool) -> Vec<String> {
+    let chunks = if markdown {
+        render_markdown_chunks(text, FormatTarget::Discord, limit)
+    } else {
+        split_text(text, limit)
+    };
+    let Some(max_lines) = max_lines.map(|m| m.max(1) as usize) else {
+        return chunks;
+    };
+    chunks
+        .into_iter()
+        .flat_map(|chunk| {
+            let lines: Vec<&str> = chunk.lines().collect();
+            lines
+                .chunks(max_lines)
+                .map(|group| group.join("\n"))
+                .collect::<Vec<_>>()
+        })
+        .filter(|c| !c.trim().is_empty())
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::channels::{Admission, Verdict};
+    use crate::config::{DiscordDmConfig, DmPolicy};
+    use std::collections::HashMap;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const BOT_ID: u64 = 999;
+
+    fn message_json(content: &str, guild: bool) -> serde_json::Value {
+        let mut json = serde_json::json!({
+            "id": "1001",
+            "channel_id": "10",
+            "author": {
+                "id": "42",
+                "username": "ann",
+                "discriminator": "0",
+                "global_name": "Ann",
+                "avatar": null,
+                "bot": false
+            },
+            "content": content,
+            "timestamp": "2026-01-01T00:00:00.000Z",
+            "edited_timestamp": null,
+            "tts": false,
+            "mention_everyone": false,
+            "mentions": [],
+            "mention_roles": [],
+            "attachments": [],
+            "embeds": [],
+            "pinned": false,
+            "type": 0
+        });
+        if guild {
+            json["guild_id"] = "500".into();
+        }
+        json
+    }
+
+    fn parse(json: serde_json::Value) -> Message {
+        serde_json::from_value(json).expect("valid Discord message")
+    }
+
+    fn channel_with(configure: impl FnOnce(&mut DiscordAccountConfig)) -> DiscordChannel {
+        let mut config = Config::default();
+        config.channels.discord.default_account.token = Some("token".to_string());
+        configure(&mut config.channels.discord.default_account);
+        DiscordChannel::new(&config)
+    }
+
+    fn guild(channels: &[(&str, DiscordGuildChannelConfig)]) -> DiscordGuildEntry {
+        DiscordGuildEntry {
+            channels: Some(
+                channels
+                    .iter()
+                    .map(|(id, c)| (id.to_string(), c.clone()))
+                    .collect::<HashMap<_, _>>(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn normalize_guild_message_with_mention() {
+        let mut json = message_json("<@999> hello there", true);
+        json["mentions"] = serde_json::json!([{
+            "id": "999", "username": "lobster", "discriminator": "0", "avatar": null, "bot": true
+        }]);
+        let msg = normalize_message("default", &parse(json), Some(BOT_ID), None).unwrap();
+        assert_eq!(msg.chat_type, ChatType::Group);
+        assert_eq!(msg.chat_id, "10");
+        assert_eq!(msg.sender.name, "Ann");
+        assert!(msg.mentioned);
+        assert_eq!(msg.text, "hello there");
+    }
+
+    #[test]
+    fn normalize_thread_and_dm_messages() {
+        let thread = normalize_message(
+            "default",
+            &parse(message_json("hi", true)),
+            Some(BOT_ID),
+            Some(7),
+        )
+        .unwrap();
+        assert_eq!(thread.chat_type, ChatType::Thread);
+        assert_eq!(thread.chat_id, "7");
+        assert_eq!(thread.thread_id.as_deref(), Some("10"));
+
+        let dm =
+            normalize_message("default", &parse(message_json("hi", false)), None, None).unwrap();
+        assert_eq!(dm.chat_type, ChatType::Dm);
+        assert!(!dm.mentioned);
+
+        let own = normalize_message("default", &parse(message_json("hi", false)), Some(42), None);
+        assert!(own.is_none());
+    }
+
+    #[test]
+    fn guild_messages_require_mention_by_default() {
+        let channel = channel_with(|_| {});
+        let mut msg = normalize_message(
+            "default",
+            &parse(message_json("hi", true)),
+            Some(BOT_ID),
+            None,
+        )
+        .unwrap();
+        assert!(!channel.account.admit(&msg, Some("500")));
+        msg.mentioned = true;
+        assert!(channel.account.admit(&msg, Some("500")));
+    }
+
+    #[test]
+    fn guild_allowlist_and_channel_rules() {
+        let open_channel = DiscordGuildChannelConfig {
+            require_mention: Some(false),
+            ..Default::default()
+        };
+        let channel = channel_with(|account| {
+            account.group_policy = Some(GroupPolicy::Allowlist);
+            account.guilds = Some(
+                [("500".to_string(), guild(&[("10", open_channel)]))]
+                    .into_iter()
+                    .collect(),
+            );
+        });
+        let msg = normalize_message(
+            "default",
+            &parse(message_json("hi", true)),
+            Some(BOT_ID),
+            None,
+        )
+        .unwrap();
+        assert!(channel.account.admit(&msg, Some("500")));
+
+        // Unlisted channel in an allowlisted guild.
+        let mut other = msg.clone();
+        other.chat_id = "11".to_string();
+        other.mentioned = true;
+        assert!(!channel.account.admit(&other, Some("500")));
+
+        // Unlisted guild.
+        assert!(!channel.account.admit(&other, Some("600")));
+    }
+
+    #[test]
+    fn channel_user_list_and_bots() {
+        let restricted = DiscordGuildChannelConfig {
+            require_mention: Some(false),
+            users: Some(vec!["7".to_string()]),
+            ..Default::default()
+        };
+        let channel = channel_with(|account| {
+            account.guilds = Some(
+                [("500".to_string(), guild(&[("10", restricted)]))]
+                    .into_iter()
+                    .collect(),
+            );
+        });
+        let mut msg = normalize_message(
+            "default",
+            &parse(message_json("hi", true)),
+            Some(BOT_ID),
+            None,
+        )
+        .unwrap();
+        assert!(!channel.account.admit(&msg, Some("500")));
+        msg.sender.id = "7".to_string();
+        assert!(channel.account.admit(&msg, Some("500")));
+        msg.sender.is_bot = true;
+        assert!(!channel.account.admit(&msg, Some("500")));
+    }
+
+    #[test]
+    fn dm_policy_is_enforced() {
+        let dm = DiscordDmConfig {
+            policy: Some(DmPolicy::Allowlist),
+            allow_from: Some(vec!["42".to_string()]),
+            ..Default::default()
+        };
+        let mut config = Config::default();
+        config.channels.discord.default_account.dm = Some(dm.clone());
+        let admission = Admission::new();
+        let mut msg =
+            normalize_message("default", &parse(message_json("hi", false)), None, None).unwrap();
+        assert_eq!(admission.check(&config, &msg), Verdict::Admit);
+        msg.sender.id = "43".to_string();
+        assert_ne!(admission.check(&config, &msg), Verdict::Admit);
+
+        let channel = channel_with(|account| {
+            account.dm = Some(DiscordDmConfig {
+                enabled: Some(false),
+                ..dm
+            })
+        });
+        assert!(!channel.account.admit(&msg, None));
+    }
+
+    #[test]
+    fn split_message_respects_limits() {
+        let text = format!("{}\n\n{}", "a".repeat(1500), "b".repeat(1500));
+        let chunks = split_message(&text, DISCORD_MAX_MESSAGE_CHARS, None, true);
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks
+            .iter()
+            .all(|c| c.chars().count() <= DISCORD_MAX_MESSAGE_CHARS));
+
+        let lines = split_message("1\n2\n3\n4\n5", 2000, Some(2), false);
+        assert_eq!(lines, vec!["1\n2", "3\n4", "5"]);
+
+        let table = split_message("| a | b |\n|---|---|\n| 1 | 2 |", 2000, None, true);
+        assert_eq!(table, vec!["```\na | b\n--+--\n1 | 2\n```"]);
+    }
+
+    #[test]
+    fn thread_name_uses_first_line() {
+        assert_eq!(thread_name("Deploy help\nmore"), "Deploy help");
+        assert_eq!(thread_name(""), "Conversation");
+        assert_eq!(
+            thread_name(&"x".repeat(200)).chars().count(),
+            THREAD_NAME_MAX_CHARS
+        );
+    }
+
+    #[tokio::test]
+    async fn send_message_posts_to_mock_api() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v10/channels/10/messages"))
+            .and(body_partial_json(serde_json::json!({"content": "hello"})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(message_json("hello", false)))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let channel = channel_with(|account| account.api_url = Some(server.uri()));
+        channel.send_message("10", "hello").await.unwrap();
+    }
 
     #[test]
     fn valid_slash_command() {
@@ -360,9 +1318,7 @@ mod tests {
             }],
         };
         let errors = validate_slash_command(&cmd);
-        assert!(errors
-            .iter()
-            .any(|e| e.field.starts_with("options[0]")));
+        assert!(errors.iter().any(|e| e.field.starts_with("options[0]")));
     }
 
     #[test]
@@ -421,4 +1377,13 @@ mod tests {
         let errors = validate_slash_command(&cmd);
         assert!(errors.is_empty());
     }
+
+    #[test]
+    fn builtin_commands_are_valid_slash_commands() {
+        let commands = builtin_slash_commands();
+        assert_eq!(commands.len(), COMMANDS.len());
+        let model = commands.iter().find(|c| c.name == "model").unwrap();
+        assert_eq!(model.options[0].name, "model");
+        assert!(!model.options[0].required);
+    }
 }
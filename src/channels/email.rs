@@ -0,0 +1,1789 @@
+use super::format::{render_markdown, FormatTarget};
+use super::inbound::dispatch_inbound;
+use super::irc::{
+    connect_stream, tls_handshake, IrcStream, RECONNECT_INITIAL_BACKOFF, RECONNECT_MAX_BACKOFF,
+    RECONNECT_RESET_AFTER,
+};
+use super::normalize::{
+    ChatType, NormalizedAttachment, NormalizedMessage, NormalizedOutbound, NormalizedSender,
+};
+use super::plugin::{unsupported, ChannelCapability, ChannelMeta, ChannelPlugin};
+use crate::config::{Config, DmPolicy, EmailConfig, EmailSecurity};
+use crate::gateway::GatewayState;
+use crate::infra::abort_signal::{monitor_with_abort_lifecycle, AbortHandle};
+use crate::infra::dm_policy;
+
+use anyhow::{bail, Context as _, Result};
+use async_trait::async_trait;
+use base64::engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig, STANDARD};
+use base64::engine::DecodePaddingMode;
+use base64::Engine as _;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+// ============================================================================
+// Email Channel Implementation
+// ============================================================================
+
+const DEFAULT_IMAP_TLS_PORT: u16 = 993;
+const DEFAULT_IMAP_PORT: u16 = 143;
+const DEFAULT_SMTP_TLS_PORT: u16 = 465;
+const DEFAULT_SMTP_STARTTLS_PORT: u16 = 587;
+const DEFAULT_SMTP_PORT: u16 = 25;
+const DEFAULT_MAILBOX: &str = "INBOX";
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(60);
+const DEFAULT_MAX_ATTACHMENT_BYTES: u64 = 10 * 1024 * 1024;
+/// Servers may drop an IDLE after 30 minutes; re-issue it before then.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(25 * 60);
+/// How long a server may take to answer a command.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(60);
+/// Threads remembered for reply headers.
+const MAX_THREADS: usize = 1024;
+/// `References` entries kept per thread.
+const MAX_REFERENCES: usize = 20;
+/// Subject of mail that does not continue a known thread.
+const DEFAULT_SUBJECT: &str = "Message from MyLobster";
+
+/// Base64 decoding that tolerates missing padding, as sent by some mailers.
+const LENIENT_BASE64: GeneralPurpose = GeneralPurpose::new(
+    &base64::alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// Subject and message ids of a conversation, for threading replies.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MailThread {
+    subject: String,
+    /// Message ids in the thread, oldest first, without angle brackets.
+    references: Vec<String>,
+}
+
+impl MailThread {
+    fn push(&mut self, id: &str) {
+        if self.references.last().map(String::as_str) != Some(id) {
+            self.references.push(id.to_string());
+        }
+        if self.references.len() > MAX_REFERENCES {
+            // Keep the root so clients can still find the thread start.
+            self.references.remove(1);
+        }
+    }
+}
+
+/// Mailbox connections and thread state for the configured account.
+struct EmailClient {
+    config: EmailConfig,
+    /// Threads by root message id.
+    threads: Mutex<HashMap<String, (Instant, MailThread)>>,
+    /// Set by `stop_account` so the watch loops exit.
+    stopping: AtomicBool,
+}
+
+/// Email channel integration.
+///
+/// Watches IMAP mailboxes for unseen mail, using IDLE where the server
+/// supports it and polling otherwise, and answers over SMTP. Every message
+/// is a direct message from its sender; conversations are threaded by
+/// `Message-ID`, `In-Reply-To` and `References`, and replies carry the same
+/// headers so mail clients keep them together.
+///
+/// Fetched messages are marked `\Seen`, so mail read elsewhere before the
+/// gateway sees it is not answered.
+pub struct EmailChannel {
+    enabled: bool,
+    client: Arc<EmailClient>,
+    /// Abort handle and task of the watch loops, if running.
+    task: Mutex<Option<(AbortHandle, tokio::task::JoinHandle<()>)>>,
+}
+
+impl EmailChannel {
+    pub fn new(config: &Config) -> Self {
+        let config = config.channels.email.clone().unwrap_or_default();
+        Self {
+            enabled: config.enabled.unwrap_or(false),
+            client: Arc::new(EmailClient {
+                config,
+                threads: Mutex::new(HashMap::new()),
+                stopping: AtomicBool::new(false),
+            }),
+            task: Mutex::new(None),
+        }
+    }
+}
+
+impl EmailClient {
+    fn address(&self) -> Option<&str> {
+        self.config.address.as_deref().filter(|a| !a.is_empty())
+    }
+
+    fn mailboxes(&self) -> Vec<String> {
+        let mailboxes: Vec<String> = self
+            .config
+            .imap
+            .as_ref()
+            .and_then(|imap| imap.mailboxes.clone())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|m| !m.trim().is_empty())
+            .collect();
+        if mailboxes.is_empty() {
+            vec![DEFAULT_MAILBOX.to_string()]
+        } else {
+            mailboxes
+        }
+    }
+
+    fn max_attachment_bytes(&self) -> u64 {
+        self.config
+            .max_attachment_bytes
+            .unwrap_or(DEFAULT_MAX_ATTACHMENT_BYTES)
+    }
+
+    /// Open a connection to the IMAP server.
+    async fn connect_imap(&self) -> Result<Box<dyn IrcStream>> {
+        let imap = self.config.imap.as_ref().context("IMAP not configured")?;
+        let host = imap.host.as_deref().context("IMAP host not configured")?;
+        let security = imap.security.unwrap_or(EmailSecurity::Tls);
+        let port = imap.port.unwrap_or(match security {
+            EmailSecurity::Tls => DEFAULT_IMAP_TLS_PORT,
+            EmailSecurity::Starttls | EmailSecurity::None => DEFAULT_IMAP_PORT,
+        });
+        match security {
+            EmailSecurity::Starttls => bail!("IMAP STARTTLS is not supported; use tls"),
+            EmailSecurity::Tls | EmailSecurity::None => {
+                connect_stream(host, port, security == EmailSecurity::Tls).await
+            }
+        }
+    }
+
+    /// Watch `mailbox` until stopped, reconnecting with backoff.
+    async fn watch(
+        self: Arc<Self>,
+        mailbox: String,
+        inbound: mpsc::UnboundedSender<NormalizedMessage>,
+    ) {
+        let mut backoff = RECONNECT_INITIAL_BACKOFF;
+        loop {
+            let started = Instant::now();
+            let result = match self.connect_imap().await {
+                Ok(stream) => self.session(stream, &mailbox, &inbound).await,
+                Err(e) => Err(e),
+            };
+            if self.stopping.load(Ordering::Relaxed) {
+                return;
+            }
+            if started.elapsed() >= RECONNECT_RESET_AFTER {
+                backoff = RECONNECT_INITIAL_BACKOFF;
+            }
+            match result {
+                Ok(()) => {
+                    info!(mailbox = %mailbox, "IMAP connection closed; reconnecting in {:?}", backoff)
+                }
+                Err(e) => {
+                    warn!(mailbox = %mailbox, error = %e, "IMAP connection failed; reconnecting in {:?}", backoff)
+                }
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(RECONNECT_MAX_BACKOFF);
+        }
+    }
+
+    /// Log in, select `mailbox` and relay new mail until the connection closes.
+    async fn session(
+        &self,
+        stream: Box<dyn IrcStream>,
+        mailbox: &str,
+        inbound: &mpsc::UnboundedSender<NormalizedMessage>,
+    ) -> Result<()> {
+        let imap_config = self.config.imap.clone().unwrap_or_default();
+        let username = imap_config
+            .username
+            .as_deref()
+            .or(self.address())
+            .context("IMAP username not configured")?;
+        let password = imap_config
+            .password
+            .as_deref()
+            .context("IMAP password not configured")?;
+
+        let mut imap = Imap::new(stream);
+        imap.greeting().await?;
+        imap.command(&format!("LOGIN {} {}", quote(username), quote(password)))
+            .await
+            .context("IMAP login failed")?;
+        let idle = imap_config.idle.unwrap_or(true) && imap.has_capability("IDLE").await?;
+        imap.command(&format!("SELECT {}", quote(mailbox))).await?;
+        info!(mailbox = %mailbox, idle, "IMAP mailbox selected");
+
+        let poll_interval = imap_config
+            .poll_interval_seconds
+            .map_or(DEFAULT_POLL_INTERVAL, |s| Duration::from_secs(s.max(1)));
+        while !self.stopping.load(Ordering::Relaxed) {
+            for uid in imap.search_unseen().await? {
+                if let Some(raw) = imap.fetch(uid).await? {
+                    match self.normalize(&raw) {
+                        Some(msg) if self.admit(&msg) => {
+                            let _ = inbound.send(msg);
+                        }
+                        Some(msg) => {
+                            debug!(sender = %msg.sender.id, "Email sender not admitted");
+                        }
+                        None => debug!(uid, "Ignoring email"),
+                    }
+                }
+                imap.command(&format!("UID STORE {uid} +FLAGS.SILENT (\\Seen)"))
+                    .await?;
+            }
+            if idle {
+                imap.idle(IDLE_TIMEOUT).await?;
+            } else {
+                tokio::time::sleep(poll_interval).await;
+                imap.command("NOOP").await?;
+            }
+        }
+        let _ = imap.command("LOGOUT").await;
+        Ok(())
+    }
+
+    /// Decide whether a sender may reach the agent.
+    ///
+    /// Allowlisted and disabled policies are enforced here so unknown senders
+    /// never get a reply; pairing goes through the admission stage.
+    fn admit(&self, msg: &NormalizedMessage) -> bool {
+        let allow_from = self.config.allow_from.as_deref().unwrap_or_default();
+        match self.config.dm_policy {
+            Some(DmPolicy::Disabled) => false,
+            Some(DmPolicy::Open) | Some(DmPolicy::Pairing) => true,
+            Some(DmPolicy::Allowlist) => {
+                !allow_from.is_empty() && dm_policy::is_source_allowed(allow_from, &msg.sender.id)
+            }
+            None => dm_policy::is_source_allowed(allow_from, &msg.sender.id),
+        }
+    }
+
+    /// Parse a fetched message and remember its thread for replies.
+    fn normalize(&self, raw: &[u8]) -> Option<NormalizedMessage> {
+        let (msg, thread) = normalize_mail(raw, self.address(), self.max_attachment_bytes())?;
+        if let Some(root) = &msg.thread_id {
+            self.remember_thread(root, thread);
+        }
+        Some(msg)
+    }
+
+    fn remember_thread(&self, root: &str, thread: MailThread) {
+        let mut threads = self.threads.lock();
+        if threads.len() >= MAX_THREADS && !threads.contains_key(root) {
+            let oldest = threads
+                .iter()
+                .min_by_key(|(_, (at, _))| *at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                threads.remove(&oldest);
+            }
+        }
+        match threads.get_mut(root) {
+            Some((at, known)) => {
+                *at = Instant::now();
+                for id in &thread.references {
+                    known.push(id);
+                }
+            }
+            None => {
+                threads.insert(root.to_string(), (Instant::now(), thread));
+            }
+        }
+    }
+
+    /// Send a reply or new message over SMTP; returns its `Message-ID`.
+    async fn send(&self, message: &NormalizedOutbound) -> Result<String> {
+        let from = self.address().context("email address not configured")?;
+        let to = message.chat_id.trim();
+        if !to.contains('@') || to.contains(['<', '>', '\r', '\n', ' ', ',']) {
+            bail!("invalid email recipient: {to:?}");
+        }
+
+        let thread = message.thread_id.as_deref().map(|root| {
+            self.threads
+                .lock()
+                .get(root)
+                .map(|(_, thread)| thread.clone())
+                .unwrap_or_else(|| MailThread {
+                    subject: String::new(),
+                    references: vec![root.to_string()],
+                })
+        });
+        let mut references = thread
+            .as_ref()
+            .map(|t| t.references.clone())
+            .unwrap_or_default();
+        let in_reply_to = message
+            .reply_to_id
+            .clone()
+            .or_else(|| references.last().cloned());
+        if let Some(id) = &in_reply_to {
+            if references.last() != Some(id) {
+                references.push(id.clone());
+            }
+        }
+        let subject = match thread.as_ref().map(|t| t.subject.as_str()) {
+            Some("") | None if in_reply_to.is_some() => "Re: (no subject)".to_string(),
+            Some("") | None => DEFAULT_SUBJECT.to_string(),
+            Some(subject) => reply_subject(subject),
+        };
+
+        let domain = from.rsplit_once('@').map_or("localhost", |(_, d)| d);
+        let message_id = format!("{}@{domain}", uuid::Uuid::new_v4());
+        let mail = OutgoingMail {
+            from,
+            display_name: self.config.display_name.as_deref(),
+            to,
+            subject: &subject,
+            message_id: &message_id,
+            in_reply_to: in_reply_to.as_deref(),
+            references: &references,
+            markdown: &message.text,
+        };
+        let data = build_message(&mail);
+
+        info!(to = %to, "Email: sending message");
+        let mut smtp = self.connect_smtp(domain).await?;
+        smtp.send_mail(from, to, &data).await?;
+
+        if let Some(root) = &message.thread_id {
+            self.remember_thread(
+                root,
+                MailThread {
+                    subject: strip_reply_prefix(&subject).to_string(),
+                    references: vec![message_id.clone()],
+                },
+            );
+        }
+        Ok(message_id)
+    }
+
+    /// Connect, greet, secure and authenticate an SMTP session.
+    async fn connect_smtp(&self, helo_domain: &str) -> Result<Smtp> {
+        let smtp = self.config.smtp.as_ref().context("SMTP not configured")?;
+        let host = smtp.host.as_deref().context("SMTP host not configured")?;
+        let security = smtp.security.unwrap_or(EmailSecurity::Starttls);
+        let port = smtp.port.unwrap_or(match security {
+            EmailSecurity::Tls => DEFAULT_SMTP_TLS_PORT,
+            EmailSecurity::Starttls => DEFAULT_SMTP_STARTTLS_PORT,
+            EmailSecurity::None => DEFAULT_SMTP_PORT,
+        });
+        let imap = self.config.imap.as_ref();
+        let username = smtp
+            .username
+            .as_deref()
+            .or_else(|| imap.and_then(|i| i.username.as_deref()))
+            .or(self.address());
+        let password = smtp
+            .password
+            .as_deref()
+            .or_else(|| imap.and_then(|i| i.password.as_deref()));
+
+        let stream: Box<dyn IrcStream> = match security {
+            EmailSecurity::Tls => connect_stream(host, port, true).await?,
+            EmailSecurity::Starttls | EmailSecurity::None => Box::new(
+                TcpStream::connect((host, port))
+                    .await
+                    .with_context(|| format!("failed to connect to {host}:{port}"))?,
+            ),
+        };
+        let mut session = Smtp::new(stream);
+        session.expect(220).await.context("SMTP greeting")?;
+        let mut extensions = session.command(&format!("EHLO {helo_domain}"), 250).await?;
+        if security == EmailSecurity::Starttls {
+            session.command("STARTTLS", 220).await?;
+            let stream = tls_handshake(host, session.stream.into_inner()).await?;
+            session = Smtp::new(Box::new(stream));
+            extensions = session.command(&format!("EHLO {helo_domain}"), 250).await?;
+        }
+        if let (Some(username), Some(password)) = (username, password) {
+            if !extensions.to_ascii_uppercase().contains("AUTH") {
+                debug!("SMTP server does not advertise AUTH; trying anyway");
+            }
+            let credentials = STANDARD.encode(format!("\0{username}\0{password}"));
+            session
+                .command(&format!("AUTH PLAIN {credentials}"), 235)
+                .await
+                .context("SMTP authentication failed")?;
+        }
+        Ok(session)
+    }
+
+    /// Run the agent for an admitted message and queue the reply.
+    async fn reply(&self, state: &GatewayState, msg: NormalizedMessage) {
+        match dispatch_inbound(state, &msg).await {
+            Ok(Some(reply)) => {
+                let outbound = NormalizedOutbound {
+                    reply_to_id: Some(msg.id.clone()),
+                    thread_id: msg.thread_id.clone(),
+                    ..NormalizedOutbound::text(msg.chat_id.clone(), reply)
+                };
+                if let Err(e) = state.channels.enqueue("email", outbound).await {
+                    warn!(to = %msg.chat_id, error = %e, "Email reply failed");
+                }
+            }
+            Ok(None) => {}
+            Err(e) => warn!(from = %msg.chat_id, error = %e, "Email agent run failed"),
+        }
+    }
+}
+
+#[async_trait]
+impl ChannelPlugin for EmailChannel {
+    fn id(&self) -> &str {
+        "email"
+    }
+
+    fn meta(&self) -> ChannelMeta {
+        ChannelMeta {
+            name: "Email".to_string(),
+            description: "Email via IMAP (IDLE or polling) and SMTP".to_string(),
+            enabled: self.enabled,
+            multi_account: false,
+        }
+    }
+
+    fn capabilities(&self) -> Vec<ChannelCapability> {
+        vec![
+            ChannelCapability::SendText,
+            ChannelCapability::ReceiveText,
+            ChannelCapability::ReceiveMedia,
+            ChannelCapability::Threads,
+        ]
+    }
+
+    async fn start_account(&self, state: &GatewayState) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let Some(host) = self
+            .client
+            .config
+            .imap
+            .as_ref()
+            .and_then(|i| i.host.clone())
+        else {
+            warn!("Email channel enabled but no IMAP host configured");
+            return Ok(());
+        };
+        if self.client.address().is_none() {
+            warn!("Email channel enabled but no address configured");
+            return Ok(());
+        }
+
+        let mailboxes = self.client.mailboxes();
+        info!(host = %host, mailboxes = ?mailboxes, "Email channel starting");
+
+        self.client.stopping.store(false, Ordering::Relaxed);
+        let abort = AbortHandle::new();
+        let task = {
+            let client = self.client.clone();
+            let state = state.clone();
+            let abort = abort.clone();
+            tokio::spawn(async move {
+                let (inbound_tx, mut inbound_rx) = mpsc::unbounded_channel();
+                let watchers = futures::future::join_all(
+                    mailboxes
+                        .into_iter()
+                        .map(|mailbox| client.clone().watch(mailbox, inbound_tx.clone())),
+                );
+                drop(inbound_tx);
+                let dispatch = {
+                    let client = client.clone();
+                    async move {
+                        while let Some(msg) = inbound_rx.recv().await {
+                            let client = client.clone();
+                            let state = state.clone();
+                            tokio::spawn(async move { client.reply(&state, msg).await });
+                        }
+                    }
+                };
+                let watching = async { tokio::join!(watchers, dispatch) };
+                if monitor_with_abort_lifecycle(watching, &abort)
+                    .await
+                    .is_err()
+                {
+                    debug!("Email watch loops stopped");
+                }
+            })
+        };
+        if let Some((previous, _)) = self.task.lock().replace((abort, task)) {
+            previous.abort();
+        }
+
+        Ok(())
+    }
+
+    async fn stop_account(&self) -> Result<()> {
+        if self.enabled {
+            info!("Email channel stopping");
+            self.client.stopping.store(true, Ordering::Relaxed);
+            let task = self.task.lock().take();
+            if let Some((abort, task)) = task {
+                abort.abort();
+                let _ = task.await;
+            }
+        }
+        Ok(())
+    }
+
+    async fn send_message(&self, to: &str, message: &str) -> Result<()> {
+        self.client
+            .send(&NormalizedOutbound::text(to, message))
+            .await?;
+        Ok(())
+    }
+
+    async fn send(&self, message: &NormalizedOutbound) -> Result<Option<String>> {
+        if !message.attachments.is_empty() {
+            return Err(unsupported(self.id(), ChannelCapability::SendMedia));
+        }
+        self.client.send(message).await.map(Some)
+    }
+}
+
+// ============================================================================
+// IMAP Client
+// ============================================================================
+
+/// One IMAP response line, with the literals it carried.
+#[derive(Debug, Default)]
+struct ImapLine {
+    /// The line text; each literal appears as its `{n}` marker.
+    text: String,
+    literals: Vec<Vec<u8>>,
+}
+
+/// A minimal IMAP4rev1 client: just what watching a mailbox needs.
+struct Imap {
+    stream: BufReader<Box<dyn IrcStream>>,
+    next_tag: u32,
+}
+
+impl Imap {
+    fn new(stream: Box<dyn IrcStream>) -> Self {
+        Self {
+            stream: BufReader::new(stream),
+            next_tag: 1,
+        }
+    }
+
+    async fn read_raw_line(&mut self, timeout: Duration) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        let read = tokio::time::timeout(timeout, self.stream.read_until(b'\n', &mut buf))
+            .await
+            .context("IMAP server timed out")??;
+        if read == 0 {
+            bail!("IMAP connection closed");
+        }
+        Ok(buf)
+    }
+
+    /// Read one response line, following any `{n}` literals it announces.
+    async fn read_line(&mut self, timeout: Duration) -> Result<ImapLine> {
+        let mut line = ImapLine::default();
+        loop {
+            let raw = self.read_raw_line(timeout).await?;
+            let text = String::from_utf8_lossy(&raw);
+            let text = text.trim_end_matches(['\r', '\n']);
+            line.text.push_str(text);
+            let Some(size) = literal_size(text) else {
+                return Ok(line);
+            };
+            let mut literal = vec![0; size];
+            tokio::time::timeout(timeout, self.stream.read_exact(&mut literal))
+                .await
+                .context("IMAP server timed out")??;
+            line.literals.push(literal);
+        }
+    }
+
+    async fn write_line(&mut self, line: &str) -> Result<()> {
+        self.stream.write_all(line.as_bytes()).await?;
+        self.stream.write_all(b"\r\n").await?;
+        self.stream.flush().await?;
+        Ok(())
+    }
+
+    async fn greeting(&mut self) -> Result<()> {
+        let line = self.read_line(COMMAND_TIMEOUT).await?;
+        if !(line.text.starts_with("* OK") || line.text.starts_with("* PREAUTH")) {
+            bail!("unexpected IMAP greeting: {}", line.text);
+        }
+        Ok(())
+    }
+
+    fn tag(&mut self) -> String {
+        let tag = format!("A{}", self.next_tag);
+        self.next_tag += 1;
+        tag
+    }
+
+    /// Run a command and collect its untagged responses.
+    async fn command(&mut self, command: &str) -> Result<Vec<ImapLine>> {
+        let tag = self.tag();
+        self.write_line(&format!("{tag} {command}")).await?;
+        self.complete(&tag).await
+    }
+
+    /// Collect untagged responses until `tag` completes.
+    async fn complete(&mut self, tag: &str) -> Result<Vec<ImapLine>> {
+        let mut untagged = Vec::new();
+        loop {
+            let line = self.read_line(COMMAND_TIMEOUT).await?;
+            let Some(status) = line
+                .text
+                .strip_prefix(tag)
+                .and_then(|r| r.strip_prefix(' '))
+            else {
+                untagged.push(line);
+                continue;
+            };
+            if status.starts_with("OK") {
+                return Ok(untagged);
+            }
+            bail!("IMAP command failed: {status}");
+        }
+    }
+
+    async fn has_capability(&mut self, capability: &str) -> Result<bool> {
+        let lines = self.command("CAPABILITY").await?;
+        Ok(lines.iter().any(|line| {
+            line.text.strip_prefix("* CAPABILITY ").is_some_and(|caps| {
+                caps.split_whitespace()
+                    .any(|c| c.eq_ignore_ascii_case(capability))
+            })
+        }))
+    }
+
+    async fn search_unseen(&mut self) -> Result<Vec<u32>> {
+        let lines = self.command("UID SEARCH UNSEEN").await?;
+        Ok(lines
+            .iter()
+            .filter_map(|line| line.text.strip_prefix("* SEARCH"))
+            .flat_map(|uids| uids.split_whitespace().filter_map(|u| u.parse().ok()))
+            .collect())
+    }
+
+    /// Fetch a message without setting `\Seen`.
+    async fn fetch(&mut self, uid: u32) -> Result<Option<Vec<u8>>> {
+        let lines = self
+            .command(&format!("UID FETCH {uid} (UID BODY.PEEK[])"))
+            .await?;
+        Ok(lines
+            .into_iter()
+            .filter(|line| line.text.contains(" FETCH "))
+            .find_map(|line| line.literals.into_iter().next()))
+    }
+
+    /// Wait in IDLE until new mail arrives or `timeout` passes.
+    async fn idle(&mut self, timeout: Duration) -> Result<()> {
+        let tag = self.tag();
+        self.write_line(&format!("{tag} IDLE")).await?;
+        let continuation = self.read_line(COMMAND_TIMEOUT).await?;
+        if !continuation.text.starts_with('+') {
+            bail!("IMAP IDLE rejected: {}", continuation.text);
+        }
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            match self.read_line(remaining).await {
+                Ok(line) if line.text.ends_with(" EXISTS") => break,
+                Ok(_) => {}
+                Err(_) if remaining.is_zero() || tokio::time::Instant::now() >= deadline => break,
+                Err(e) => return Err(e),
+            }
+        }
+        self.write_line("DONE").await?;
+        self.complete(&tag).await?;
+        Ok(())
+    }
+}
+
+/// Size of the literal a response line announces with a trailing `{n}`.
+fn literal_size(line: &str) -> Option<usize> {
+    let open = line.strip_suffix('}')?.rfind('{')?;
+    line[open + 1..line.len() - 1].parse().ok()
+}
+
+/// Quote a string for an IMAP command.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// ============================================================================
+// SMTP Client
+// ============================================================================
+
+/// A minimal SMTP client session.
+struct Smtp {
+    stream: BufReader<Box<dyn IrcStream>>,
+}
+
+impl Smtp {
+    fn new(stream: Box<dyn IrcStream>) -> Self {
+        Self {
+            stream: BufReader::new(stream),
+        }
+    }
+
+    /// Read a (possibly multi-line) reply.
+    async fn reply(&mut self) -> Result<(u16, String)> {
+        let mut text = String::new();
+        loop {
+            let mut line = String::new();
+            let read = tokio::time::timeout(COMMAND_TIMEOUT, self.stream.read_line(&mut line))
+                .await
+                .context("SMTP server timed out")??;
+            if read == 0 {
+                bail!("SMTP connection closed");
+            }
+            let line = line.trim_end();
+            let code = line
+                .get(..3)
+                .and_then(|c| c.parse().ok())
+                .with_context(|| format!("malformed SMTP reply: {line}"))?;
+            text.push_str(line.get(4..).unwrap_or_default());
+            text.push('\n');
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok((code, text));
+            }
+        }
+    }
+
+    async fn expect(&mut self, code: u16) -> Result<String> {
+        let (got, text) = self.reply().await?;
+        if got != code {
+            bail!("SMTP error {got}: {}", text.trim());
+        }
+        Ok(text)
+    }
+
+    async fn command(&mut self, command: &str, code: u16) -> Result<String> {
+        self.stream.write_all(command.as_bytes()).await?;
+        self.stream.write_all(b"\r\n").await?;
+        self.stream.flush().await?;
+        let verb = command.split(' ').next().unwrap_or(command);
+        self.expect(code)
+            .await
+            .with_context(|| format!("SMTP {verb}"))
+    }
+
+    /// Submit one message and close the session.
+    async fn send_mail(&mut self, from: &str, to: &str, data: &str) -> Result<()> {
+        self.command(&format!("MAIL FROM:<{from}>"), 250).await?;
+        let (code, text) = {
+            self.stream
+                .write_all(format!("RCPT TO:<{to}>\r\n").as_bytes())
+                .await?;
+            self.stream.flush().await?;
+            self.reply().await?
+        };
+        if code != 250 && code != 251 {
+            bail!("SMTP recipient {to} rejected ({code}): {}", text.trim());
+        }
+        self.command("DATA", 354).await?;
+        self.stream.write_all(dot_stuff(data).as_bytes()).await?;
+        self.command(".", 250).await?;
+        let _ = self.command("QUIT", 221).await;
+        Ok(())
+    }
+}
+
+/// Escape leading dots and make sure the data ends with CRLF.
+fn dot_stuff(data: &str) -> String {
+    let mut out = String::with_capacity(data.len() + 16);
+    for line in data.split_inclusive("\r\n") {
+        if line.starts_with('.') {
+            out.push('.');
+        }
+        out.push_str(line);
+    }
+    if !out.ends_with("\r\n") {
+        out.push_str("\r\n");
+    }
+    out
+}
+
+// ============================================================================
+// Outgoing Messages
+// ============================================================================
+
+struct OutgoingMail<'a> {
+    from: &'a str,
+    display_name: Option<&'a str>,
+    to: &'a str,
+    subject: &'a str,
+    message_id: &'a str,
+    in_reply_to: Option<&'a str>,
+    references: &'a [String],
+    markdown: &'a str,
+}
+
+/// Build an RFC 5322 message with plain-text and HTML alternatives.
+fn build_message(mail: &OutgoingMail<'_>) -> String {
+    let boundary = format!("=_mylobster_{}", uuid::Uuid::new_v4().simple());
+    let from = match mail.display_name.filter(|n| !n.trim().is_empty()) {
+        Some(name) => format!("{} <{}>", encode_display_name(name), mail.from),
+        None => format!("<{}>", mail.from),
+    };
+
+    let mut headers = vec![
+        format!("From: {from}"),
+        format!("To: <{}>", mail.to),
+        format!("Subject: {}", encode_header(mail.subject)),
+        format!("Date: {}", chrono::Utc::now().to_rfc2822()),
+        format!("Message-ID: <{}>", mail.message_id),
+    ];
+    if let Some(id) = mail.in_reply_to {
+        headers.push(format!("In-Reply-To: <{id}>"));
+    }
+    if !mail.references.is_empty() {
+        let ids: Vec<String> = mail.references.iter().map(|id| format!("<{id}>")).collect();
+        headers.push(format!("References: {}", ids.join("\r\n ")));
+    }
+    headers.push("MIME-Version: 1.0".to_string());
+    // Keeps vacation responders from answering the agent.
+    headers.push("Auto-Submitted: auto-replied".to_string());
+    headers.push(format!(
+        "Content-Type: multipart/alternative; boundary=\"{boundary}\""
+    ));
+
+    let plain = render_markdown(mail.markdown, FormatTarget::Plain);
+    let html = format!(
+        "<!DOCTYPE html>\n<html><body>\n{}</body></html>\n",
+        markdown_to_html(mail.markdown)
+    );
+
+    let mut out = headers.join("\r\n");
+    out.push_str("\r\n\r\n");
+    for (mime, body) in [("text/plain", plain), ("text/html", html)] {
+        out.push_str(&format!(
+            "--{boundary}\r\nContent-Type: {mime}; charset=utf-8\r\n\
+             Content-Transfer-Encoding: base64\r\n\r\n"
+        ));
+        out.push_str(&base64_lines(body.as_bytes()));
+    }
+    out.push_str(&format!("--{boundary}--\r\n"));
+    out
+}
+
+/// Render markdown as HTML, escaping any raw HTML the text contains.
+fn markdown_to_html(markdown: &str) -> String {
+    use pulldown_cmark::{Event, Options, Parser};
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+    let events = Parser::new_ext(markdown, options).map(|event| match event {
+        Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+        other => other,
+    });
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, events);
+    html
+}
+
+/// Base64 in 76-column CRLF lines.
+fn base64_lines(data: &[u8]) -> String {
+    let encoded = STANDARD.encode(data);
+    let mut out = String::with_capacity(encoded.len() + encoded.len() / 38 + 2);
+    for chunk in encoded.as_bytes().chunks(76) {
+        out.push_str(std::str::from_utf8(chunk).unwrap_or_default());
+        out.push_str("\r\n");
+    }
+    out
+}
+
+/// Encode a header value as an RFC 2047 word when it is not plain ASCII.
+fn encode_header(value: &str) -> String {
+    let value: String = value
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    if value.is_ascii() {
+        value
+    } else {
+        format!("=?UTF-8?B?{}?=", STANDARD.encode(value))
+    }
+}
+
+fn encode_display_name(name: &str) -> String {
+    let name = name.trim();
+    if !name.is_ascii() {
+        return encode_header(name);
+    }
+    if name.chars().all(|c| c.is_ascii_alphanumeric() || c == ' ') {
+        name.to_string()
+    } else {
+        quote(name)
+    }
+}
+
+/// `Re: <subject>`, without stacking prefixes.
+fn reply_subject(subject: &str) -> String {
+    format!("Re: {}", strip_reply_prefix(subject))
+}
+
+fn strip_reply_prefix(subject: &str) -> &str {
+    let mut subject = subject.trim();
+    loop {
+        let lower = subject.get(..4).map(str::to_ascii_lowercase);
+        match lower.as_deref() {
+            Some("re: " | "aw: " | "fw: ") => subject = subject[4..].trim_start(),
+            _ => match subject.get(..5).map(str::to_ascii_lowercase).as_deref() {
+                Some("fwd: ") => subject = subject[5..].trim_start(),
+                _ => return subject,
+            },
+        }
+    }
+}
+
+// ============================================================================
+// Message Parsing
+// ============================================================================
+
+/// Header fields of a message or MIME part, unfolded, in order.
+type Headers = Vec<(String, String)>;
+
+/// Text and attachments collected from a MIME tree.
+#[derive(Debug, Default)]
+struct MailContent {
+    plain: Vec<String>,
+    html: Vec<String>,
+    attachments: Vec<NormalizedAttachment>,
+}
+
+/// Split a message or part into its headers and body.
+fn split_headers(raw: &[u8]) -> (Headers, &[u8]) {
+    let (head, body) = match find(raw, b"\r\n\r\n") {
+        Some(i) if find(raw, b"\n\n").map_or(true, |j| i < j) => (&raw[..i], &raw[i + 4..]),
+        _ => match find(raw, b"\n\n") {
+            Some(i) => (&raw[..i], &raw[i + 2..]),
+            None => (raw, &raw[raw.len()..]),
+        },
+    };
+
+    let mut headers: Headers = Vec::new();
+    for line in String::from_utf8_lossy(head).lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    (headers, body)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn header<'a>(headers: &'a Headers, name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, v)| v.as_str())
+}
+
+/// A header value's main token and its `; key=value` parameters.
+fn parse_params(value: &str) -> (String, HashMap<String, String>) {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in value.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => fields.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    fields.push(current);
+
+    let main = fields[0].trim().to_ascii_lowercase();
+    let mut params = HashMap::new();
+    for field in &fields[1..] {
+        let Some((key, value)) = field.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim();
+        // RFC 2231: `filename*=utf-8''na%C3%AFve.txt`.
+        match key.strip_suffix('*') {
+            Some(key) => {
+                let encoded = value.splitn(3, '\'').nth(2).unwrap_or(value);
+                params.insert(key.to_string(), percent_decode(encoded));
+            }
+            None => {
+                params.insert(key, value.to_string());
+            }
+        }
+    }
+    (main, params)
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(byte) = value
+                .get(i + 1..i + 3)
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+            {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Undo a `Content-Transfer-Encoding`.
+fn decode_transfer(body: &[u8], encoding: &str) -> Vec<u8> {
+    match encoding {
+        "base64" => {
+            let compact: Vec<u8> = body
+                .iter()
+                .copied()
+                .filter(|b| !b.is_ascii_whitespace())
+                .collect();
+            LENIENT_BASE64
+                .decode(compact)
+                .unwrap_or_else(|_| body.to_vec())
+        }
+        "quoted-printable" => decode_quoted_printable(body, false),
+        _ => body.to_vec(),
+    }
+}
+
+/// Decode quoted-printable; in headers (`q` words) `_` is a space.
+fn decode_quoted_printable(body: &[u8], header: bool) -> Vec<u8> {
+    let mut out = Vec::with_capacity(body.len());
+    let mut i = 0;
+    while i < body.len() {
+        match body[i] {
+            b'=' => {
+                let rest = &body[i + 1..];
+                if rest.starts_with(b"\r\n") {
+                    i += 3;
+                    continue;
+                }
+                if rest.starts_with(b"\n") {
+                    i += 2;
+                    continue;
+                }
+                let hex = rest
+                    .get(..2)
+                    .and_then(|h| std::str::from_utf8(h).ok())
+                    .and_then(|h| u8::from_str_radix(h, 16).ok());
+                match hex {
+                    Some(byte) => {
+                        out.push(byte);
+                        i += 3;
+                    }
+                    None => {
+                        out.push(b'=');
+                        i += 1;
+                    }
+                }
+            }
+            b'_' if header => {
+                out.push(b' ');
+                i += 1;
+            }
+            byte => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Decode text in `charset`; Latin-1 and Windows-1252 map bytes to chars.
+fn decode_charset(bytes: &[u8], charset: &str) -> String {
+    match charset.to_ascii_lowercase().as_str() {
+        "iso-8859-1" | "latin1" | "latin-1" | "windows-1252" | "cp1252" => {
+            bytes.iter().map(|&b| char::from(b)).collect()
+        }
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+static ENCODED_WORD: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"=\?([^?\s]+)\?([bBqQ])\?([^?\s]*)\?=").expect("valid regex"));
+static ENCODED_WORD_GAP: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(\?=)\s+(=\?)").expect("valid regex"));
+
+/// Decode RFC 2047 encoded words in a header value.
+fn decode_header(value: &str) -> String {
+    let joined = ENCODED_WORD_GAP.replace_all(value, "$1$2");
+    ENCODED_WORD
+        .replace_all(&joined, |caps: &regex::Captures<'_>| {
+            let charset = caps[1].split('*').next().unwrap_or_default();
+            let bytes = if caps[2].eq_ignore_ascii_case("b") {
+                LENIENT_BASE64.decode(&caps[3]).unwrap_or_default()
+            } else {
+                decode_quoted_printable(caps[3].as_bytes(), true)
+            };
+            decode_charset(&bytes, charset)
+        })
+        .into_owned()
+}
+
+/// Walk a MIME part, collecting text bodies and attachments.
+fn collect_parts(raw: &[u8], depth: usize, max_attachment: u64, out: &mut MailContent) {
+    let (headers, body) = split_headers(raw);
+    let (mime, params) = parse_params(header(&headers, "content-type").unwrap_or("text/plain"));
+    let encoding = header(&headers, "content-transfer-encoding")
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let (disposition, disposition_params) =
+        parse_params(header(&headers, "content-disposition").unwrap_or_default());
+
+    if mime.starts_with("multipart/") && depth < 10 {
+        if let Some(boundary) = params.get("boundary") {
+            for part in split_multipart(body, boundary) {
+                collect_parts(part, depth + 1, max_attachment, out);
+            }
+            return;
+        }
+    }
+
+    let filename = disposition_params
+        .get("filename")
+        .or_else(|| params.get("name"))
+        .map(|name| decode_header(name));
+    let is_text = mime == "text/plain" || mime == "text/html";
+    if disposition != "attachment" && is_text && filename.is_none() {
+        let charset = params.get("charset").map_or("utf-8", String::as_str);
+        let text = decode_charset(&decode_transfer(body, &encoding), charset);
+        if mime == "text/html" {
+            out.html.push(text);
+        } else {
+            out.plain.push(text);
+        }
+        return;
+    }
+
+    let data = decode_transfer(body, &encoding);
+    let size = data.len() as u64;
+    out.attachments.push(NormalizedAttachment {
+        mime_type: Some(mime.clone()),
+        url: None,
+        data: (size <= max_attachment).then_some(data),
+        filename: filename.or_else(|| (mime == "message/rfc822").then(|| "message.eml".into())),
+        size: Some(size),
+    });
+}
+
+/// The parts of a multipart body, without their delimiter lines.
+fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{boundary}");
+    let mut parts = Vec::new();
+    let mut start = None;
+    let mut pos = 0;
+    while pos < body.len() {
+        let end = body[pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(body.len(), |i| pos + i + 1);
+        let mut line = &body[pos..end];
+        while let [rest @ .., b'\r' | b'\n' | b' ' | b'\t'] = line {
+            line = rest;
+        }
+        if let Some(rest) = line.strip_prefix(delimiter.as_bytes()) {
+            if rest.is_empty() || rest == b"--" {
+                if let Some(start) = start {
+                    parts.push(strip_line_break(&body[start..pos]));
+                }
+                if rest == b"--" {
+                    return parts;
+                }
+                start = Some(end);
+            }
+        }
+        pos = end;
+    }
+    if let Some(start) = start {
+        parts.push(&body[start..]);
+    }
+    parts
+}
+
+/// Drop the line break that belongs to the following delimiter.
+fn strip_line_break(part: &[u8]) -> &[u8] {
+    let part = part.strip_suffix(b"\n").unwrap_or(part);
+    part.strip_suffix(b"\r").unwrap_or(part)
+}
+
+static HTML_DROP: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?is)<(style|script|head)\b.*?</(style|script|head)\s*>").expect("valid regex")
+});
+static HTML_BREAK: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)<br\s*/?>|</(p|div|li|tr|h[1-6]|blockquote)\s*>").expect("valid regex")
+});
+static HTML_TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"<[^>]*>").expect("valid regex"));
+static BLANK_LINES: Lazy<Regex> = Lazy::new(|| Regex::new(r"\n{3,}").expect("valid regex"));
+
+/// Reduce an HTML body to readable text.
+fn html_to_text(html: &str) -> String {
+    let text = HTML_DROP.replace_all(html, "");
+    let text = HTML_BREAK.replace_all(&text, "\n");
+    let text = HTML_TAG.replace_all(&text, "");
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+    BLANK_LINES
+        .replace_all(lines.join("\n").trim(), "\n\n")
+        .into_owned()
+}
+
+static ATTRIBUTION: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)^on\b.{0,200}\bwrote:\s*$").expect("valid regex"));
+
+/// Cut the quoted history and signature off a reply.
+fn strip_quoted_reply(text: &str) -> String {
+    let mut kept: Vec<&str> = Vec::new();
+    for line in text.lines() {
+        let trimmed = line.trim_end();
+        if trimmed == "--"
+            || trimmed.starts_with("-----Original Message-----")
+            || ATTRIBUTION.is_match(trimmed)
+        {
+            break;
+        }
+        kept.push(trimmed);
+    }
+    while kept
+        .last()
+        .is_some_and(|l| l.is_empty() || l.starts_with('>'))
+    {
+        kept.pop();
+    }
+    kept.join("\n").trim().to_string()
+}
+
+/// Sender display name and lowercased address from a `From` value.
+fn parse_address(value: &str) -> Option<(Option<String>, String)> {
+    let value = value.trim();
+    let (name, address) = match (value.rfind('<'), value.rfind('>')) {
+        (Some(open), Some(close)) if open < close => {
+            (Some(&value[..open]), &value[open + 1..close])
+        }
+        _ => (None, value.split_whitespace().find(|t| t.contains('@'))?),
+    };
+    let address = address.trim().to_ascii_lowercase();
+    if !address.contains('@') {
+        return None;
+    }
+    let name = name
+        .map(|n| decode_header(n.trim().trim_matches('"').trim()))
+        .filter(|n| !n.is_empty());
+    Some((name, address))
+}
+
+static MESSAGE_ID: Lazy<Regex> = Lazy::new(|| Regex::new(r"<([^<>\s]+)>").expect("valid regex"));
+
+/// Message ids in a `Message-ID`, `In-Reply-To` or `References` value.
+fn message_ids(value: Option<&str>) -> Vec<String> {
+    let Some(value) = value else {
+        return Vec::new();
+    };
+    let ids: Vec<String> = MESSAGE_ID
+        .captures_iter(value)
+        .map(|c| c[1].to_string())
+        .collect();
+    if ids.is_empty() {
+        value.split_whitespace().map(str::to_string).collect()
+    } else {
+        ids
+    }
+}
+
+/// Convert a raw message into a [`NormalizedMessage`] and its thread.
+///
+/// Returns `None` for our own mail, automatic mail (`Auto-Submitted`, bulk
+/// `Precedence`) that could start a reply loop, and mail without content.
+fn normalize_mail(
+    raw: &[u8],
+    own_address: Option<&str>,
+    max_attachment: u64,
+) -> Option<(NormalizedMessage, MailThread)> {
+    let (headers, _) = split_headers(raw);
+    let (name, address) = parse_address(&decode_header(header(&headers, "from")?))?;
+    if own_address.is_some_and(|own| own.eq_ignore_ascii_case(&address)) {
+        return None;
+    }
+    if header(&headers, "auto-submitted").is_some_and(|v| !v.eq_ignore_ascii_case("no")) {
+        return None;
+    }
+    if header(&headers, "precedence")
+        .is_some_and(|v| matches!(v.to_ascii_lowercase().as_str(), "bulk" | "junk" | "list"))
+    {
+        return None;
+    }
+
+    let mut content = MailContent::default();
+    collect_parts(raw, 0, max_attachment, &mut content);
+    let body = if content.plain.is_empty() {
+        content
+            .html
+            .iter()
+            .map(|h| html_to_text(h))
+            .collect::<Vec<_>>()
+    } else {
+        content.plain
+    }
+    .join("\n\n");
+    let body = strip_quoted_reply(&body.replace("\r\n", "\n"));
+
+    let subject = decode_header(header(&headers, "subject").unwrap_or_default())
+        .trim()
+        .to_string();
+    let id = message_ids(header(&headers, "message-id"))
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| format!("{}@mylobster.local", uuid::Uuid::new_v4()));
+    let in_reply_to = message_ids(header(&headers, "in-reply-to"));
+    let mut references = message_ids(header(&headers, "references"));
+    if references.is_empty() {
+        references = in_reply_to.clone();
+    }
+    let root = references.first().cloned().unwrap_or_else(|| id.clone());
+
+    let text = if references.is_empty() && !subject.is_empty() {
+        format!("Subject: {subject}\n\n{body}")
+    } else {
+        body
+    };
+    if text.trim().is_empty() && content.attachments.is_empty() {
+        return None;
+    }
+
+    let timestamp = header(&headers, "date")
+        .and_then(|d| chrono::DateTime::parse_from_rfc2822(d.trim()).ok())
+        .map_or_else(|| chrono::Utc::now().to_rfc3339(), |d| d.to_rfc3339());
+
+    let mut thread = MailThread {
+        subject: strip_reply_prefix(&subject).to_string(),
+        references: Vec::new(),
+    };
+    for reference in &references {
+        thread.push(reference);
+    }
+    thread.push(&id);
+
+    let msg = NormalizedMessage {
+        id: id.clone(),
+        channel: "email".to_string(),
+        account_id: "default".to_string(),
+        chat_id: address.clone(),
+        chat_name: (!subject.is_empty()).then(|| subject.clone()),
+        chat_type: ChatType::Dm,
+        sender: NormalizedSender {
+            id: address.clone(),
+            name: name.unwrap_or_else(|| address.clone()),
+            is_bot: false,
+            roles: Vec::new(),
+        },
+        text,
+        attachments: content.attachments,
+        reply_to_id: in_reply_to.last().cloned(),
+        thread_id: Some(root),
+        mentioned: true,
+        timestamp,
+        raw: Some(serde_json::json!({
+            "messageId": id,
+            "inReplyTo": in_reply_to,
+            "references": references,
+            "subject": subject,
+            "from": address,
+            "to": header(&headers, "to"),
+        })),
+    };
+    Some((msg, thread))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{EmailImapConfig, EmailSmtpConfig};
+    use tokio::net::TcpListener;
+
+    const REPLY_MAIL: &str = "From: =?UTF-8?Q?Ann_M=C3=BCller?= <Ann@Example.com>\r\n\
+        To: bot@example.com\r\n\
+        Subject: Re: Invoice\r\n\
+        Date: Tue, 13 Oct 2026 09:30:00 +0000\r\n\
+        Message-ID: <m2@example.com>\r\n\
+        In-Reply-To: <m1@example.com>\r\n\
+        References: <m0@example.com>\r\n <m1@example.com>\r\n\
+        MIME-Version: 1.0\r\n\
+        Content-Type: multipart/mixed; boundary=\"outer\"\r\n\
+        \r\n\
+        preamble\r\n\
+        --outer\r\n\
+        Content-Type: multipart/alternative; boundary=inner\r\n\
+        \r\n\
+        --inner\r\n\
+        Content-Type: text/plain; charset=utf-8\r\n\
+        Content-Transfer-Encoding: quoted-printable\r\n\
+        \r\n\
+        Danke, the total is =E2=82=AC12.\r\n\
+        \r\n\
+        On Mon, 12 Oct 2026, Bot <bot@example.com> wrote:\r\n\
+        > old text\r\n\
+        --inner\r\n\
+        Content-Type: text/html; charset=utf-8\r\n\
+        \r\n\
+        <p>ignored</p>\r\n\
+        --inner--\r\n\
+        --outer\r\n\
+        Content-Type: application/pdf; name=\"invoice.pdf\"\r\n\
+        Content-Disposition: attachment; filename*=utf-8''r%C3%A9sum%C3%A9.pdf\r\n\
+        Content-Transfer-Encoding: base64\r\n\
+        \r\n\
+        JVBERi0x\r\n\
+        LjQ=\r\n\
+        --outer--\r\n";
+
+    fn client_with(configure: impl FnOnce(&mut EmailConfig)) -> Arc<EmailClient> {
+        let mut email = EmailConfig {
+            enabled: Some(true),
+            address: Some("bot@example.com".to_string()),
+            imap: Some(EmailImapConfig {
+                host: Some("127.0.0.1".to_string()),
+                security: Some(EmailSecurity::None),
+                password: Some("pw".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        configure(&mut email);
+        let mut config = Config::default();
+        config.channels.email = Some(email);
+        EmailChannel::new(&config).client
+    }
+
+    #[test]
+    fn parse_multipart_reply() {
+        let (msg, thread) = normalize_mail(REPLY_MAIL.as_bytes(), Some("bot@example.com"), 1024)
+            .expect("normalized");
+        assert_eq!(msg.channel, "email");
+        assert_eq!(msg.chat_type, ChatType::Dm);
+        assert_eq!(msg.chat_id, "ann@example.com");
+        assert_eq!(msg.sender.name, "Ann Müller");
+        assert_eq!(msg.text, "Danke, the total is €12.");
+        assert_eq!(msg.id, "m2@example.com");
+        assert_eq!(msg.thread_id.as_deref(), Some("m0@example.com"));
+        assert_eq!(msg.reply_to_id.as_deref(), Some("m1@example.com"));
+        assert_eq!(msg.timestamp, "2026-10-13T09:30:00+00:00");
+
+        let attachment = &msg.attachments[0];
+        assert_eq!(attachment.filename.as_deref(), Some("résumé.pdf"));
+        assert_eq!(attachment.mime_type.as_deref(), Some("application/pdf"));
+        assert_eq!(attachment.data.as_deref(), Some(&b"%PDF-1.4"[..]));
+
+        assert_eq!(thread.subject, "Invoice");
+        assert_eq!(
+            thread.references,
+            ["m0@example.com", "m1@example.com", "m2@example.com"]
+        );
+
+        // Large attachments are listed without their data.
+        let (msg, _) = normalize_mail(REPLY_MAIL.as_bytes(), None, 4).unwrap();
+        assert!(msg.attachments[0].data.is_none());
+        assert_eq!(msg.attachments[0].size, Some(8));
+    }
+
+    #[test]
+    fn new_thread_html_only_mail() {
+        let raw = "From: carl@example.org\nSubject: Hello\nMessage-ID: <n1@example.org>\n\
+                   Content-Type: text/html\n\n<html><head><style>p{}</style></head>\
+                   <body><p>Hi &amp; welcome</p><p>Line<br>two</p></body></html>\n";
+        let (msg, thread) = normalize_mail(raw.as_bytes(), None, 1024).unwrap();
+        assert_eq!(msg.text, "Subject: Hello\n\nHi & welcome\nLine\ntwo");
+        assert_eq!(msg.thread_id.as_deref(), Some("n1@example.org"));
+        assert_eq!(msg.sender.name, "carl@example.org");
+        assert_eq!(thread.references, ["n1@example.org"]);
+    }
+
+    #[test]
+    fn own_and_automatic_mail_is_ignored() {
+        let own = "From: Bot <BOT@example.com>\nSubject: x\n\nhi\n";
+        assert!(normalize_mail(own.as_bytes(), Some("bot@example.com"), 1024).is_none());
+        let vacation = "From: a@example.com\nAuto-Submitted: auto-replied\n\nAway\n";
+        assert!(normalize_mail(vacation.as_bytes(), None, 1024).is_none());
+        let list = "From: a@example.com\nPrecedence: bulk\n\nNews\n";
+        assert!(normalize_mail(list.as_bytes(), None, 1024).is_none());
+        let manual = "From: a@example.com\nAuto-Submitted: no\n\nHi\n";
+        assert!(normalize_mail(manual.as_bytes(), None, 1024).is_some());
+    }
+
+    #[test]
+    fn header_helpers() {
+        assert_eq!(
+            decode_header("=?utf-8?B?SGVsbG8g?= =?utf-8?q?W=C3=B6rld?= !"),
+            "Hello Wörld !"
+        );
+        assert_eq!(decode_header("=?ISO-8859-1?Q?caf=E9?="), "café");
+        assert_eq!(
+            parse_address("\"Doe, Jane\" <Jane@X.org>"),
+            Some((Some("Doe, Jane".to_string()), "jane@x.org".to_string()))
+        );
+        assert_eq!(
+            parse_address("jane@x.org (Jane)"),
+            Some((None, "jane@x.org".to_string()))
+        );
+        assert_eq!(parse_address("undisclosed"), None);
+        assert_eq!(reply_subject("RE: Fwd: Re: Plans"), "Re: Plans");
+        assert_eq!(encode_header("Grüße"), "=?UTF-8?B?R3LDvMOfZQ==?=");
+        assert_eq!(literal_size("* 1 FETCH (UID 4 BODY[] {120}"), Some(120));
+        assert_eq!(literal_size("* OK {no}"), None);
+        assert_eq!(quote("a\"b\\c"), "\"a\\\"b\\\\c\"");
+        assert_eq!(dot_stuff(".hi\r\nok\r\n..x"), "..hi\r\nok\r\n...x\r\n");
+    }
+
+    #[test]
+    fn quoted_history_and_signature_are_stripped() {
+        let text = "Sounds good.\n\n> earlier\n> more\n";
+        assert_eq!(strip_quoted_reply(text), "Sounds good.");
+        let text = "Yes\n-- \nAnn\nACME Inc.";
+        assert_eq!(strip_quoted_reply(text), "Yes");
+        let text = "First\n> inline quote\nAnswer\n-----Original Message-----\nold";
+        assert_eq!(strip_quoted_reply(text), "First\n> inline quote\nAnswer");
+    }
+
+    #[test]
+    fn allowlist_admission() {
+        let (msg, _) = normalize_mail(REPLY_MAIL.as_bytes(), None, 1024).unwrap();
+        assert!(client_with(|_| {}).admit(&msg));
+
+        let domain = client_with(|e| e.allow_from = Some(vec!["*@example.com".to_string()]));
+        assert!(domain.admit(&msg));
+        let other = client_with(|e| {
+            e.dm_policy = Some(DmPolicy::Allowlist);
+            e.allow_from = Some(vec!["boss@example.org".to_string()]);
+        });
+        assert!(!other.admit(&msg));
+        let empty = client_with(|e| e.dm_policy = Some(DmPolicy::Allowlist));
+        assert!(!empty.admit(&msg));
+        let disabled = client_with(|e| e.dm_policy = Some(DmPolicy::Disabled));
+        assert!(!disabled.admit(&msg));
+    }
+
+    /// Minimal in-process mail server for one client connection.
+    struct TestServer {
+        reader: BufReader<tokio::net::tcp::OwnedReadHalf>,
+        writer: tokio::net::tcp::OwnedWriteHalf,
+    }
+
+    impl TestServer {
+        async fn accept(listener: &TcpListener) -> Self {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (reader, writer) = socket.into_split();
+            Self {
+                reader: BufReader::new(reader),
+                writer,
+            }
+        }
+
+        async fn line(&mut self) -> String {
+            let mut line = String::new();
+            let read =
+                tokio::time::timeout(Duration::from_secs(5), self.reader.read_line(&mut line))
+                    .await
+                    .expect("timed out waiting for client line")
+                    .unwrap();
+            assert!(read > 0, "client closed the connection");
+            line.trim_end().to_string()
+        }
+
+        /// Read a tagged IMAP command; returns its tag.
+        async fn expect_command(&mut self, command: &str) -> String {
+            let line = self.line().await;
+            let (tag, rest) = line.split_once(' ').unwrap();
+            assert_eq!(rest, command);
+            tag.to_string()
+        }
+
+        async fn send(&mut self, data: &str) {
+            self.writer.write_all(data.as_bytes()).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn imap_session_relays_unseen_mail_and_idles() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = client_with(|_| {});
+        let (inbound_tx, mut inbound) = mpsc::unbounded_channel();
+        {
+            let client = client.clone();
+            tokio::spawn(async move {
+                let stream = TcpStream::connect(addr).await.unwrap();
+                let _ = client.session(Box::new(stream), "INBOX", &inbound_tx).await;
+            });
+        }
+        let mut server = TestServer::accept(&listener).await;
+
+        server.send("* OK IMAP4rev1 ready\r\n").await;
+        let tag = server
+            .expect_command("LOGIN \"bot@example.com\" \"pw\"")
+            .await;
+        server.send(&format!("{tag} OK logged in\r\n")).await;
+        let tag = server.expect_command("CAPABILITY").await;
+        server
+            .send(&format!("* CAPABILITY IMAP4rev1 IDLE\r\n{tag} OK\r\n"))
+            .await;
+        let tag = server.expect_command("SELECT \"INBOX\"").await;
+        server
+            .send(&format!(
+                "* 1 EXISTS\r\n* OK [UIDVALIDITY 7] ok\r\n{tag} OK [READ-WRITE] done\r\n"
+            ))
+            .await;
+        let tag = server.expect_command("UID SEARCH UNSEEN").await;
+        server.send(&format!("* SEARCH 42\r\n{tag} OK\r\n")).await;
+        let tag = server
+            .expect_command("UID FETCH 42 (UID BODY.PEEK[])")
+            .await;
+        server
+            .send(&format!(
+                "* 1 FETCH (UID 42 BODY[] {{{}}}\r\n{REPLY_MAIL})\r\n{tag} OK\r\n",
+                REPLY_MAIL.len()
+            ))
+            .await;
+        let tag = server
+            .expect_command("UID STORE 42 +FLAGS.SILENT (\\Seen)")
+            .await;
+        server.send(&format!("{tag} OK\r\n")).await;
+
+        let msg = tokio::time::timeout(Duration::from_secs(5), inbound.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(msg.chat_id, "ann@example.com");
+        assert_eq!(msg.attachments.len(), 1);
+
+        let tag = server.expect_command("IDLE").await;
+        server.send("+ idling\r\n* 2 EXISTS\r\n").await;
+        assert_eq!(server.line().await, "DONE");
+        server.send(&format!("{tag} OK IDLE done\r\n")).await;
+        let tag = server.expect_command("UID SEARCH UNSEEN").await;
+        server.send(&format!("* SEARCH\r\n{tag} OK\r\n")).await;
+        server.expect_command("IDLE").await;
+    }
+
+    #[tokio::test]
+    async fn smtp_reply_is_threaded_with_alternatives() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let client = client_with(|e| {
+            e.display_name = Some("Lobster Bot".to_string());
+            e.smtp = Some(EmailSmtpConfig {
+                host: Some("127.0.0.1".to_string()),
+                port: Some(port),
+                security: Some(EmailSecurity::None),
+                ..Default::default()
+            });
+        });
+        let inbound = client.normalize(REPLY_MAIL.as_bytes()).unwrap();
+
+        let server = tokio::spawn(async move {
+            let mut server = TestServer::accept(&listener).await;
+            server.send("220 localhost ESMTP\r\n").await;
+            assert_eq!(server.line().await, "EHLO example.com");
+            server.send("250-localhost\r\n250 AUTH PLAIN\r\n").await;
+            let auth = server.line().await;
+            let credentials = STANDARD
+                .decode(auth.strip_prefix("AUTH PLAIN ").unwrap())
+                .unwrap();
+            assert_eq!(credentials, b"\0bot@example.com\0pw");
+            server.send("235 ok\r\n").await;
+            assert_eq!(server.line().await, "MAIL FROM:<bot@example.com>");
+            server.send("250 ok\r\n").await;
+            assert_eq!(server.line().await, "RCPT TO:<ann@example.com>");
+            server.send("250 ok\r\n").await;
+            assert_eq!(server.line().await, "DATA");
+            server.send("354 go ahead\r\n").await;
+            let mut data = String::new();
+            loop {
+                let line = server.line().await;
+                if line == "." {
+                    break;
+                }
+                data.push_str(&line);
+                data.push_str("\r\n");
+            }
+            server.send("250 queued\r\n").await;
+            assert_eq!(server.line().await, "QUIT");
+            server.send("221 bye\r\n").await;
+            data
+        });
+
+        let outbound = NormalizedOutbound {
+            reply_to_id: Some(inbound.id.clone()),
+            thread_id: inbound.thread_id.clone(),
+            ..NormalizedOutbound::text(inbound.chat_id.clone(), "**Paid** in full.")
+        };
+        let message_id = client.send(&outbound).await.unwrap();
+        let data = server.await.unwrap();
+
+        let (headers, _) = split_headers(data.as_bytes());
+        assert_eq!(
+            header(&headers, "from"),
+            Some("Lobster Bot <bot@example.com>")
+        );
+        assert_eq!(header(&headers, "subject"), Some("Re: Invoice"));
+        assert_eq!(header(&headers, "in-reply-to"), Some("<m2@example.com>"));
+        assert_eq!(
+            header(&headers, "references"),
+            Some("<m0@example.com> <m1@example.com> <m2@example.com>")
+        );
+        assert_eq!(
+            header(&headers, "message-id"),
+            Some(format!("<{message_id}>").as_str())
+        );
+        let mut content = MailContent::default();
+        collect_parts(data.as_bytes(), 0, 1024, &mut content);
+        assert_eq!(content.plain, ["Paid in full."]);
+        assert!(content.html[0].contains("<strong>Paid</strong> in full."));
+
+        // The reply joins the thread for the next one.
+        let thread = client.threads.lock()["m0@example.com"].1.clone();
+        assert_eq!(thread.references.last(), Some(&message_id));
+    }
+
+    #[tokio::test]
+    async fn send_rejects_bad_recipients() {
+        let client = client_with(|_| {});
+        let err = client
+            .send(&NormalizedOutbound::text("not an address", "hi"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("invalid email recipient"));
+    }
+}
@@ -1,10 +1,26 @@
-use super::plugin::{ChannelCapability, ChannelMeta, ChannelPlugin};
+use super::group_history::admit_group_message;
+use super::normalize::{
+    ChatType, NormalizedAttachment, NormalizedMessage, NormalizedOutbound, NormalizedSender,
+};
+use super::plugin::{
+    unsupported, ChannelCapability, ChannelMeta, ChannelPlugin, WebhookRequest, WebhookResponse,
+};
+use super::split_text;
+use super::webhook::{self, ReplayGuard, WebhookRoute};
+use crate::config::{Config, FeishuConfig};
 use crate::gateway::GatewayState;
 
-use anyhow::Result;
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use anyhow::{Context as _, Result};
 use async_trait::async_trait;
+use axum::http::StatusCode;
+use base64::Engine as _;
+use parking_lot::{Mutex, RwLock};
 use reqwest::Client;
-use tracing::{info, warn};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
 
 // ============================================================================
 // Feishu / Lark Channel Implementation
@@ -13,97 +29,122 @@ use tracing::{info, warn};
 /// Feishu (Lark) channel integration via the Feishu Open Platform API.
 ///
 /// Feishu is the enterprise collaboration platform by ByteDance (known as
-/// Lark internationally). This channel communicates via the Feishu Bot API
-/// to send and receive messages.
+/// Lark internationally). Events arrive on `POST /channels/feishu/<webhookPath>`
+/// (the app's event subscription URL), authenticated with the verification
+/// token or, when an encrypt key is set, decrypted and checked against
+/// `X-Lark-Signature`. Replies go to the inbound message via the reply API.
 ///
 /// API docs: <https://open.feishu.cn/document/server-docs/im-v1/message/create>
 ///
 /// Authentication uses an app_id + app_secret to obtain a `tenant_access_token`
 /// via `POST https://open.feishu.cn/open-apis/auth/v3/tenant_access_token/internal`.
 pub struct FeishuChannel {
-    /// Feishu app ID from the Feishu Open Platform developer console.
-    app_id: Option<String>,
-    /// Feishu app secret.
-    app_secret: Option<String>,
-    /// Whether this channel is enabled.
-    enabled: Option<bool>,
-    /// HTTP client for API calls.
-    client: Client,
+    enabled: bool,
+    config: FeishuConfig,
+    api: FeishuApi,
+    /// The bot's `open_id`, used to recognise mentions.
+    bot_open_id: RwLock<Option<String>>,
+    /// Event ids already handled (Feishu retries unacknowledged events).
+    replay: ReplayGuard,
 }
 
 /// Feishu API base URL.
 const FEISHU_API_BASE: &str = "https://open.feishu.cn/open-apis";
+const DEFAULT_WEBHOOK_PATH: &str = "webhook";
+const SIGNATURE_HEADER: &str = "x-lark-signature";
+const TIMESTAMP_HEADER: &str = "x-lark-request-timestamp";
+const NONCE_HEADER: &str = "x-lark-request-nonce";
+/// Characters per outgoing text message.
+const FEISHU_TEXT_CHUNK_LIMIT: usize = 10_000;
 
-impl FeishuChannel {
-    pub fn new() -> Self {
-        Self {
-            app_id: None,
-            app_secret: None,
-            enabled: None,
-            client: Client::new(),
-        }
-    }
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
 
-    /// Create a configured Feishu channel.
-    pub fn with_config(app_id: String, app_secret: String) -> Self {
+impl FeishuChannel {
+    pub fn new(config: &Config) -> Self {
+        let feishu = config.channels.feishu.clone().unwrap_or_default();
         Self {
-            app_id: Some(app_id),
-            app_secret: Some(app_secret),
-            enabled: Some(true),
-            client: Client::new(),
+            enabled: feishu.enabled.unwrap_or(false),
+            api: FeishuApi {
+                client: Client::new(),
+                api_base: feishu
+                    .api_url
+                    .as_deref()
+                    .unwrap_or(FEISHU_API_BASE)
+                    .trim_end_matches('/')
+                    .to_string(),
+                app_id: feishu.app_id.clone(),
+                app_secret: feishu.app_secret.clone(),
+                token: Arc::new(Mutex::new(None)),
+            },
+            config: feishu,
+            bot_open_id: RwLock::new(None),
+            replay: ReplayGuard::default(),
         }
     }
 
-    fn is_enabled(&self) -> bool {
-        self.enabled.unwrap_or(false)
+    fn webhook_path(&self) -> &str {
+        webhook::webhook_path(self.config.webhook_path.as_deref(), DEFAULT_WEBHOOK_PATH)
     }
 
-    /// Acquire a tenant_access_token from the Feishu Open Platform.
-    async fn acquire_tenant_token(&self) -> Result<String> {
-        let app_id = self
-            .app_id
-            .as_deref()
-            .ok_or_else(|| anyhow::anyhow!("Feishu app_id not configured"))?;
-        let app_secret = self
-            .app_secret
-            .as_deref()
-            .ok_or_else(|| anyhow::anyhow!("Feishu app_secret not configured"))?;
-
-        let url = format!(
-            "{}/auth/v3/tenant_access_token/internal",
-            FEISHU_API_BASE,
-        );
-
-        let body = serde_json::json!({
-            "app_id": app_id,
-            "app_secret": app_secret,
-        });
-
-        let resp = self.client.post(&url).json(&body).send().await?;
-
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let text = resp.text().await.unwrap_or_default();
-            anyhow::bail!(
-                "Feishu tenant_access_token request failed ({}): {}",
-                status,
-                text
-            );
+    /// Decode the request body, decrypting it when an encrypt key is set.
+    ///
+    /// Signed requests carry `X-Lark-Signature`:
+    /// `sha256(timestamp + nonce + encrypt_key + body)` in hex.
+    fn open_payload(&self, request: &WebhookRequest) -> Result<Value, StatusCode> {
+        let body: Value =
+            serde_json::from_slice(&request.body).map_err(|_| StatusCode::BAD_REQUEST)?;
+        let Some(encrypt_key) = self.config.encrypt_key.as_deref() else {
+            return Ok(body);
+        };
+
+        if let Some(signature) = request.header(SIGNATURE_HEADER) {
+            let timestamp = request.header(TIMESTAMP_HEADER).unwrap_or_default();
+            let nonce = request.header(NONCE_HEADER).unwrap_or_default();
+            let digest = webhook::sha256(&[
+                timestamp.as_bytes(),
+                nonce.as_bytes(),
+                encrypt_key.as_bytes(),
+                &request.body,
+            ]);
+            if !webhook::digest_matches_hex(&digest, signature) {
+                return Err(StatusCode::UNAUTHORIZED);
+            }
+            let fresh = timestamp
+                .parse()
+                .is_ok_and(|ts| self.replay.timestamp_fresh(ts));
+            if !fresh {
+                return Err(StatusCode::UNAUTHORIZED);
+            }
         }
 
-        let result: serde_json::Value = resp.json().await?;
-        let code = result["code"].as_i64().unwrap_or(-1);
-        if code != 0 {
-            let msg = result["msg"].as_str().unwrap_or("unknown error");
-            anyhow::bail!("Feishu token error (code {}): {}", code, msg);
+        let encrypted = body["encrypt"].as_str().ok_or(StatusCode::BAD_REQUEST)?;
+        let plaintext = decrypt(encrypt_key, encrypted).map_err(|_| StatusCode::BAD_REQUEST)?;
+        let payload: Value =
+            serde_json::from_str(&plaintext).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+        // Only the URL verification handshake is sent unsigned.
+        let signed = request.header(SIGNATURE_HEADER).is_some();
+        if !signed && payload["type"] != "url_verification" {
+            return Err(StatusCode::UNAUTHORIZED);
         }
+        Ok(payload)
+    }
 
-        let token = result["tenant_access_token"]
+    /// Check the verification token carried in the payload.
+    fn token_valid(&self, payload: &Value) -> bool {
+        let Some(expected) = self.config.verification_token.as_deref() else {
+            // Without a token, only decrypted (hence authenticated) payloads pass.
+            return self.config.encrypt_key.is_some();
+        };
+        let token = payload["header"]["token"]
             .as_str()
-            .ok_or_else(|| anyhow::anyhow!("Feishu: no tenant_access_token in response"))?
-            .to_string();
+            .or_else(|| payload["token"].as_str())
+            .unwrap_or_default();
+        webhook::constant_time_eq(token.as_bytes(), expected.as_bytes())
+    }
 
-        Ok(token)
+    fn admit(&self, msg: &NormalizedMessage) -> bool {
+        webhook::admit(self.config.require_mention.unwrap_or(true), msg)
     }
 }
 
@@ -117,7 +158,7 @@ impl ChannelPlugin for FeishuChannel {
         ChannelMeta {
             name: "Feishu".to_string(),
             description: "Feishu (Lark) channel via Open Platform API".to_string(),
-            enabled: self.is_enabled(),
+            enabled: self.enabled,
             multi_account: false,
         }
     }
@@ -133,78 +174,528 @@ impl ChannelPlugin for FeishuChannel {
         ]
     }
 
+    fn webhook_routes(&self) -> Vec<WebhookRoute> {
+        if !self.enabled {
+            return Vec::new();
+        }
+        vec![WebhookRoute::post(self.webhook_path())]
+    }
+
     async fn start_account(&self, _state: &GatewayState) -> Result<()> {
-        if !self.is_enabled() {
+        if !self.enabled {
             return Ok(());
         }
 
-        if self.app_id.is_none() || self.app_secret.is_none() {
+        if self.api.app_id.is_none() || self.api.app_secret.is_none() {
             warn!("Feishu channel enabled but app_id or app_secret not configured");
             return Ok(());
         }
+        if self.config.verification_token.is_none() && self.config.encrypt_key.is_none() {
+            warn!("Feishu: neither verification_token nor encrypt_key configured; events will be rejected");
+        }
 
-        info!("Feishu channel starting");
+        info!(path = %self.webhook_path(), "Feishu channel starting");
 
-        // Verify credentials by acquiring an initial token.
-        match self.acquire_tenant_token().await {
-            Ok(_) => info!("Feishu: tenant_access_token acquired successfully"),
-            Err(e) => warn!("Feishu: failed to acquire initial token: {}", e),
+        // Verify credentials and learn the bot's open_id for mention detection.
+        match self.api.bot_open_id().await {
+            Ok(open_id) => {
+                info!(open_id = %open_id, "Feishu: bot info retrieved");
+                *self.bot_open_id.write() = Some(open_id);
+            }
+            Err(e) => warn!("Feishu: failed to fetch bot info: {}", e),
         }
 
-        // TODO: Register an event subscription endpoint to receive incoming
-        // messages. Feishu sends events via HTTP POST to the app's event URL.
-
         Ok(())
     }
 
     async fn stop_account(&self) -> Result<()> {
-        if self.is_enabled() {
+        if self.enabled {
             info!("Feishu channel stopping");
         }
         Ok(())
     }
 
     async fn send_message(&self, to: &str, message: &str) -> Result<()> {
-        let token = self.acquire_tenant_token().await?;
-
         // `to` is a Feishu chat_id (group) or open_id (user).
-        // We default to sending to a chat_id. The receive_id_type determines
-        // whether `to` is a chat_id, open_id, user_id, or union_id.
-        let url = format!(
-            "{}/im/v1/messages?receive_id_type=chat_id",
-            FEISHU_API_BASE,
-        );
+        let receive_id_type = if to.starts_with("ou_") {
+            "open_id"
+        } else {
+            "chat_id"
+        };
+        info!(to = %to, "Feishu: sending message");
+        for chunk in split_text(message, FEISHU_TEXT_CHUNK_LIMIT) {
+            let body = json!({
+                "receive_id": to,
+                "msg_type": "text",
+                "content": json!({ "text": chunk }).to_string(),
+            });
+            self.api
+                .post(
+                    &format!("im/v1/messages?receive_id_type={receive_id_type}"),
+                    &body,
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn send(&self, message: &NormalizedOutbound) -> Result<Option<String>> {
+        if !message.attachments.is_empty() {
+            return Err(unsupported(self.id(), ChannelCapability::SendMedia));
+        }
+        // Replies go through the reply endpoint so they stay in the topic.
+        match &message.reply_to_id {
+            Some(message_id) => {
+                let in_thread = message.thread_id.is_some();
+                self.api.reply(message_id, &message.text, in_thread).await?;
+            }
+            None => self.send_message(&message.chat_id, &message.text).await?,
+        }
+        Ok(None)
+    }
+
+    async fn handle_webhook(
+        &self,
+        state: &GatewayState,
+        request: WebhookRequest,
+    ) -> Result<WebhookResponse> {
+        if !self.enabled {
+            return Ok(WebhookResponse::not_found());
+        }
+        if let Some(response) = webhook::route_check(&self.webhook_routes(), &request) {
+            return Ok(response);
+        }
+        let payload = match self.open_payload(&request) {
+            Ok(payload) => payload,
+            Err(status) => {
+                warn!(status = %status, "Feishu event request rejected");
+                return Ok(WebhookResponse::status(status));
+            }
+        };
+        if !self.token_valid(&payload) {
+            warn!("Feishu event with invalid verification token");
+            return Ok(WebhookResponse::status(StatusCode::UNAUTHORIZED));
+        }
+
+        if payload["type"] == "url_verification" {
+            return Ok(WebhookResponse::json(
+                StatusCode::OK,
+                &json!({ "challenge": payload["challenge"] }),
+            ));
+        }
+
+        let header = &payload["header"];
+        if header["event_type"] != "im.message.receive_v1" {
+            return Ok(WebhookResponse::ok());
+        }
+        if let Some(id) = header["event_id"].as_str() {
+            if !self.replay.first_delivery(id) {
+                debug!(event_id = %id, "Skipping redelivered Feishu event");
+                return Ok(WebhookResponse::ok());
+            }
+        }
+
+        let bot_open_id = self.bot_open_id.read().clone();
+        let Some(mut msg) = normalize_event(&payload["event"], bot_open_id.as_deref()) else {
+            return Ok(WebhookResponse::ok());
+        };
+        if !admit_group_message(state, &mut msg, |msg| self.admit(msg)).await {
+            debug!(sender = %msg.sender.id, "Feishu message not admitted");
+            return Ok(WebhookResponse::ok());
+        }
+
+        webhook::spawn_dispatch(state, msg, |msg, text| NormalizedOutbound {
+            reply_to_id: Some(msg.id.clone()),
+            thread_id: msg.thread_id.clone(),
+            ..NormalizedOutbound::text(msg.chat_id.clone(), text)
+        });
+        Ok(WebhookResponse::ok())
+    }
+}
+
+// ============================================================================
+// Open Platform API
+// ============================================================================
+
+/// Outbound half of the channel, cloned into reply tasks.
+#[derive(Clone)]
+struct FeishuApi {
+    client: Client,
+    api_base: String,
+    app_id: Option<String>,
+    app_secret: Option<String>,
+    /// Cached tenant_access_token and its expiry.
+    token: Arc<Mutex<Option<(String, Instant)>>>,
+}
+
+impl FeishuApi {
+    /// Acquire a tenant_access_token from the Feishu Open Platform.
+    async fn acquire_tenant_token(&self) -> Result<String> {
+        if let Some((token, expires)) = self.token.lock().as_ref() {
+            if Instant::now() < *expires {
+                return Ok(token.clone());
+            }
+        }
+
+        let app_id = self
+            .app_id
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Feishu app_id not configured"))?;
+        let app_secret = self
+            .app_secret
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Feishu app_secret not configured"))?;
+
+        let url = format!("{}/auth/v3/tenant_access_token/internal", self.api_base);
 
-        let body = serde_json::json!({
-            "receive_id": to,
-            "msg_type": "text",
-            "content": serde_json::json!({ "text": message }).to_string(),
+        let body = json!({
+            "app_id": app_id,
+            "app_secret": app_secret,
         });
 
-        info!(chat_id = %to, "Feishu: sending message");
+        let resp = self.client.post(&url).json(&body).send().await?;
 
-        let resp = self
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            anyhow::bail!(
+                "Feishu tenant_access_token request failed ({}): {}",
+                status,
+                text
+            );
+        }
+
+        let result: Value = resp.json().await?;
+        let code = result["code"].as_i64().unwrap_or(-1);
+        if code != 0 {
+            let msg = result["msg"].as_str().unwrap_or("unknown error");
+            anyhow::bail!("Feishu token error (code {}): {}", code, msg);
+        }
+
+        let token = result["tenant_access_token"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Feishu: no tenant_access_token in response"))?
+            .to_string();
+
+        // Refresh a few minutes before the advertised expiry.
+        let ttl = result["expire"]
+            .as_u64()
+            .unwrap_or(7200)
+            .saturating_sub(300);
+        *self.token.lock() = Some((token.clone(), Instant::now() + Duration::from_secs(ttl)));
+
+        Ok(token)
+    }
+
+    async fn request(
+        &self,
+        method: reqwest::Method,
+        endpoint: &str,
+        body: Option<&Value>,
+    ) -> Result<Value> {
+        let token = self.acquire_tenant_token().await?;
+        let mut req = self
             .client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", token))
-            .json(&body)
-            .send()
-            .await?;
+            .request(method, format!("{}/{endpoint}", self.api_base))
+            .header("Authorization", format!("Bearer {}", token));
+        if let Some(body) = body {
+            req = req.json(body);
+        }
+        let resp = req.send().await?;
 
         if !resp.status().is_success() {
             let status = resp.status();
             let text = resp.text().await.unwrap_or_default();
-            anyhow::bail!("Feishu send message failed ({}): {}", status, text);
+            anyhow::bail!("Feishu {} failed ({}): {}", endpoint, status, text);
         }
 
         // Check the Feishu API-level error code.
-        let result: serde_json::Value = resp.json().await?;
+        let result: Value = resp.json().await?;
         let code = result["code"].as_i64().unwrap_or(-1);
         if code != 0 {
             let msg = result["msg"].as_str().unwrap_or("unknown error");
-            anyhow::bail!("Feishu send error (code {}): {}", code, msg);
+            anyhow::bail!("Feishu {} error (code {}): {}", endpoint, code, msg);
         }
+        Ok(result)
+    }
+
+    async fn post(&self, endpoint: &str, body: &Value) -> Result<Value> {
+        self.request(reqwest::Method::POST, endpoint, Some(body))
+            .await
+    }
 
+    async fn bot_open_id(&self) -> Result<String> {
+        let result = self
+            .request(reqwest::Method::GET, "bot/v3/info", None)
+            .await?;
+        result["bot"]["open_id"]
+            .as_str()
+            .map(str::to_string)
+            .context("Feishu: no bot.open_id in bot info")
+    }
+
+    /// Reply to a message, staying in its thread when it has one.
+    async fn reply(&self, message_id: &str, text: &str, in_thread: bool) -> Result<()> {
+        for chunk in split_text(text, FEISHU_TEXT_CHUNK_LIMIT) {
+            let body = json!({
+                "msg_type": "text",
+                "content": json!({ "text": chunk }).to_string(),
+                "reply_in_thread": in_thread,
+            });
+            self.post(&format!("im/v1/messages/{message_id}/reply"), &body)
+                .await?;
+        }
         Ok(())
     }
 }
+
+// ============================================================================
+// Event Parsing
+// ============================================================================
+
+/// Decrypt an `encrypt` field: AES-256-CBC keyed with `sha256(encrypt_key)`,
+/// the IV being the first block of the decoded data.
+fn decrypt(encrypt_key: &str, encrypted: &str) -> Result<String> {
+    let data = base64::engine::general_purpose::STANDARD.decode(encrypted)?;
+    if data.len() < 32 {
+        anyhow::bail!("encrypted payload too short");
+    }
+    let key = webhook::sha256(&[encrypt_key.as_bytes()]);
+    let (iv, ciphertext) = data.split_at(16);
+    let plaintext = Aes256CbcDec::new(&key.into(), iv.into())
+        .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+        .map_err(|_| anyhow::anyhow!("invalid padding"))?;
+    Ok(String::from_utf8(plaintext)?)
+}
+
+/// Convert an `im.message.receive_v1` event into a [`NormalizedMessage`].
+fn normalize_event(event: &Value, bot_open_id: Option<&str>) -> Option<NormalizedMessage> {
+    let sender = &event["sender"];
+    if sender["sender_type"] != "user" {
+        return None;
+    }
+    let message = &event["message"];
+    let content: Value = serde_json::from_str(message["content"].as_str()?).ok()?;
+    let mentions = message["mentions"].as_array().cloned().unwrap_or_default();
+
+    let is_bot = |mention: &Value| match bot_open_id {
+        Some(id) => mention["id"]["open_id"] == id,
+        // Before the bot's id is known, any mention counts.
+        None => true,
+    };
+
+    let mut attachments = Vec::new();
+    let mut text = match message["message_type"].as_str()? {
+        "text" => content["text"].as_str().unwrap_or_default().to_string(),
+        kind @ ("image" | "file" | "audio" | "media") => {
+            let key = content["image_key"]
+                .as_str()
+                .or_else(|| content["file_key"].as_str())?;
+            attachments.push(NormalizedAttachment {
+                mime_type: (kind == "image").then(|| "image/*".to_string()),
+                url: Some(format!(
+                    "{FEISHU_API_BASE}/im/v1/messages/{}/resources/{key}?type={}",
+                    message["message_id"].as_str()?,
+                    if kind == "image" { "image" } else { "file" }
+                )),
+                data: None,
+                filename: content["file_name"].as_str().map(str::to_string),
+                size: None,
+            });
+            String::new()
+        }
+        _ => return None,
+    };
+
+    // Mentions appear as `@_user_N` placeholders.
+    let mut mentioned = false;
+    for mention in &mentions {
+        let Some(key) = mention["key"].as_str() else {
+            continue;
+        };
+        if is_bot(mention) {
+            mentioned = true;
+            text = text.replace(key, "");
+        } else {
+            let name = mention["name"].as_str().unwrap_or_default();
+            text = text.replace(key, &format!("@{name}"));
+        }
+    }
+
+    let chat_type = match (
+        message["chat_type"].as_str()?,
+        message["thread_id"].as_str(),
+    ) {
+        ("p2p", _) => ChatType::Dm,
+        (_, Some(_)) => ChatType::Thread,
+        _ => ChatType::Group,
+    };
+    let sender_id = sender["sender_id"]["open_id"].as_str()?.to_string();
+    let non_empty = |v: &Value| v.as_str().filter(|s| !s.is_empty()).map(str::to_string);
+
+    Some(NormalizedMessage {
+        id: message["message_id"].as_str()?.to_string(),
+        channel: "feishu".to_string(),
+        account_id: "default".to_string(),
+        chat_id: message["chat_id"].as_str()?.to_string(),
+        chat_name: None,
+        chat_type,
+        sender: NormalizedSender {
+            id: sender_id.clone(),
+            name: sender_id,
+            is_bot: false,
+            roles: Vec::new(),
+        },
+        text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+        attachments,
+        reply_to_id: non_empty(&message["parent_id"]),
+        thread_id: non_empty(&message["thread_id"]).or_else(|| non_empty(&message["root_id"])),
+        mentioned: mentioned || chat_type == ChatType::Dm,
+        timestamp: webhook::timestamp_from_millis(
+            message["create_time"]
+                .as_str()
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_default(),
+        ),
+        raw: Some(event.clone()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::webhook::tests::request;
+    use aes::cipher::BlockEncryptMut;
+    use axum::http::Method;
+
+    fn channel(configure: impl FnOnce(&mut FeishuConfig)) -> FeishuChannel {
+        let mut config = Config::default();
+        let mut feishu = FeishuConfig {
+            enabled: Some(true),
+            app_id: Some("cli_a".to_string()),
+            app_secret: Some("secret".to_string()),
+            ..Default::default()
+        };
+        configure(&mut feishu);
+        config.channels.feishu = Some(feishu);
+        FeishuChannel::new(&config)
+    }
+
+    fn encrypt(encrypt_key: &str, plaintext: &str) -> String {
+        let key = webhook::sha256(&[encrypt_key.as_bytes()]);
+        let iv = [7u8; 16];
+        let ciphertext = cbc::Encryptor::<aes::Aes256>::new(&key.into(), &iv.into())
+            .encrypt_padded_vec_mut::<Pkcs7>(plaintext.as_bytes());
+        base64::engine::general_purpose::STANDARD.encode([iv.as_slice(), &ciphertext].concat())
+    }
+
+    fn message_event(chat_type: &str, text: &str, mentions: Value) -> Value {
+        json!({
+            "sender": { "sender_id": { "open_id": "ou_user" }, "sender_type": "user" },
+            "message": {
+                "message_id": "om_1", "root_id": "", "parent_id": "", "chat_id": "oc_chat",
+                "chat_type": chat_type, "message_type": "text", "create_time": "1700000000000",
+                "content": json!({ "text": text }).to_string(), "mentions": mentions
+            }
+        })
+    }
+
+    #[test]
+    fn decrypts_documented_example() {
+        assert_eq!(
+            decrypt("test key", "P37w+VZImNgPEO1RBhJ6RtKl7n6zymIbEG1pReEzghk=").unwrap(),
+            "hello world"
+        );
+    }
+
+    #[test]
+    fn url_verification_checks_token() {
+        let channel = channel(|c| c.verification_token = Some("vtoken".to_string()));
+        let ok = json!({ "type": "url_verification", "challenge": "abc", "token": "vtoken" });
+        let payload = channel
+            .open_payload(&request(
+                Method::POST,
+                "webhook",
+                &[],
+                ok.to_string().as_bytes(),
+            ))
+            .unwrap();
+        assert!(channel.token_valid(&payload));
+        let bad = json!({ "type": "url_verification", "challenge": "abc", "token": "nope" });
+        assert!(!channel.token_valid(&bad));
+    }
+
+    #[test]
+    fn encrypted_events_need_a_valid_signature() {
+        let channel = channel(|c| c.encrypt_key = Some("ekey".to_string()));
+        let event = json!({ "schema": "2.0", "header": { "event_id": "e1", "event_type": "im.message.receive_v1" }, "event": {} });
+        let body = json!({ "encrypt": encrypt("ekey", &event.to_string()) }).to_string();
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+        let signature = hex::encode(webhook::sha256(&[
+            timestamp.as_bytes(),
+            b"nonce",
+            b"ekey",
+            body.as_bytes(),
+        ]));
+
+        let signed = request(
+            Method::POST,
+            "webhook",
+            &[
+                (SIGNATURE_HEADER, &signature),
+                (TIMESTAMP_HEADER, &timestamp),
+                (NONCE_HEADER, "nonce"),
+            ],
+            body.as_bytes(),
+        );
+        let payload = channel.open_payload(&signed).unwrap();
+        assert_eq!(payload["header"]["event_id"], "e1");
+        assert!(channel.token_valid(&payload));
+
+        let forged = request(
+            Method::POST,
+            "webhook",
+            &[
+                (SIGNATURE_HEADER, "00"),
+                (TIMESTAMP_HEADER, &timestamp),
+                (NONCE_HEADER, "nonce"),
+            ],
+            body.as_bytes(),
+        );
+        assert_eq!(channel.open_payload(&forged), Err(StatusCode::UNAUTHORIZED));
+        let unsigned = request(Method::POST, "webhook", &[], body.as_bytes());
+        assert_eq!(
+            channel.open_payload(&unsigned),
+            Err(StatusCode::UNAUTHORIZED)
+        );
+    }
+
+    #[test]
+    fn mentions_are_resolved_against_the_bot() {
+        let channel = channel(|_| {});
+        let mentions = json!([
+            { "key": "@_user_1", "id": { "open_id": "ou_bot" }, "name": "Lobster" },
+            { "key": "@_user_2", "id": { "open_id": "ou_other" }, "name": "Alice" }
+        ]);
+        let event = message_event("group", "@_user_1 ask @_user_2 please", mentions);
+        let msg = normalize_event(&event, Some("ou_bot")).unwrap();
+        assert_eq!(msg.chat_type, ChatType::Group);
+        assert_eq!(msg.text, "ask @Alice please");
+        assert!(msg.mentioned);
+        assert!(channel.admit(&msg));
+
+        let other_only =
+            json!([{ "key": "@_user_1", "id": { "open_id": "ou_other" }, "name": "Alice" }]);
+        let msg = normalize_event(
+            &message_event("group", "@_user_1 hi", other_only),
+            Some("ou_bot"),
+        )
+        .unwrap();
+        assert!(!channel.admit(&msg));
+
+        let dm =
+            normalize_event(&message_event("p2p", "hello", json!([])), Some("ou_bot")).unwrap();
+        assert_eq!(dm.chat_type, ChatType::Dm);
+        assert!(channel.admit(&dm));
+    }
+}
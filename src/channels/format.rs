@@ -0,0 +1,994 @@
+//! Markdown rendering for channel targets.
+//!
+//! Models answer in GitHub-flavoured markdown, which every platform renders
+//! differently (or not at all). The reply is parsed with pulldown-cmark and
+//! rendered for a [`FormatTarget`]: Slack mrkdwn, Telegram HTML, Discord
+//! markdown, WhatsApp markup, IRC control codes, or plain text with style
+//! ranges for Signal. Tables have no equivalent on any of them and degrade to
+//! aligned code blocks.
+//!
+//! While rendering, every position where the output may be split is recorded
+//! together with the markup open at that point, so
+//! [`render_markdown_chunks`] can cut a long reply into messages that each
+//! close and reopen their formatting instead of breaking a span or code fence.
+
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+
+/// Markup a reply is rendered into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatTarget {
+    /// Plain text: formatting dropped, links written out.
+    Plain,
+    /// Slack mrkdwn (`*bold*`, `<url|label>`).
+    Slack,
+    /// Telegram `parse_mode=HTML`.
+    TelegramHtml,
+    /// Discord markdown (CommonMark subset without tables).
+    Discord,
+    /// WhatsApp markup (`*bold*`, `_italic_`, `~strike~`).
+    WhatsApp,
+    /// Plain text plus [`StyleRange`]s (see [`render_signal`]).
+    Signal,
+    /// mIRC control codes.
+    Irc,
+}
+
+impl FormatTarget {
+    /// Open and close markers for an inline style.
+    fn markers(self, style: TextStyle) -> (&'static str, &'static str) {
+        use TextStyle::*;
+        match (self, style) {
+            (Self::Slack | Self::WhatsApp, Bold) => ("*", "*"),
+            (Self::Slack | Self::WhatsApp, Italic) => ("_", "_"),
+            (Self::Slack | Self::WhatsApp, Strikethrough) => ("~", "~"),
+            (Self::Slack | Self::WhatsApp | Self::Discord, Monospace) => ("`", "`"),
+            (Self::TelegramHtml, Bold) => ("<b>", "</b>"),
+            (Self::TelegramHtml, Italic) => ("<i>", "</i>"),
+            (Self::TelegramHtml, Strikethrough) => ("<s>", "</s>"),
+            (Self::TelegramHtml, Monospace) => ("<code>", "</code>"),
+            (Self::TelegramHtml, Spoiler) => ("<tg-spoiler>", "</tg-spoiler>"),
+            (Self::Discord, Bold) => ("**", "**"),
+            (Self::Discord, Italic) => ("*", "*"),
+            (Self::Discord, Strikethrough) => ("~~", "~~"),
+            (Self::Discord, Spoiler) => ("||", "||"),
+            (Self::Irc, Bold) => ("\x02", "\x02"),
+            (Self::Irc, Italic) => ("\x1d", "\x1d"),
+            (Self::Irc, Strikethrough) => ("\x1e", "\x1e"),
+            (Self::Irc, Monospace) => ("\x11", "\x11"),
+            _ => ("", ""),
+        }
+    }
+
+    /// Open and close markup for a code block.
+    fn code_fence(self, lang: &str) -> (String, &'static str) {
+        match self {
+            Self::TelegramHtml if lang.is_empty() => ("<pre>".to_string(), "</pre>"),
+            Self::TelegramHtml => (
+                format!("<pre><code class=\"language-{}\">", escape_html(lang)),
+                "</code></pre>",
+            ),
+            Self::Discord => (format!("```{lang}\n"), "\n```"),
+            Self::Slack | Self::WhatsApp => ("```\n".to_string(), "\n```"),
+            Self::Plain | Self::Signal | Self::Irc => (String::new(), ""),
+        }
+    }
+
+    /// Whether links keep their URL out of the visible text.
+    fn has_link_markup(self) -> bool {
+        matches!(self, Self::Slack | Self::TelegramHtml | Self::Discord)
+    }
+}
+
+/// Inline text style.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TextStyle {
+    Bold,
+    Italic,
+    Strikethrough,
+    Monospace,
+    Spoiler,
+}
+
+impl TextStyle {
+    /// Signal's name for the style.
+    pub fn signal_name(self) -> &'static str {
+        match self {
+            Self::Bold => "BOLD",
+            Self::Italic => "ITALIC",
+            Self::Strikethrough => "STRIKETHROUGH",
+            Self::Monospace => "MONOSPACE",
+            Self::Spoiler => "SPOILER",
+        }
+    }
+}
+
+/// A styled range of a [`StyledText`], in UTF-16 code units as Signal
+/// expects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StyleRange {
+    pub start: usize,
+    pub length: usize,
+    pub style: TextStyle,
+}
+
+impl StyleRange {
+    /// `start:length:STYLE`, the signal-cli `textStyle` format.
+    pub fn to_signal(&self) -> String {
+        format!(
+            "{}:{}:{}",
+            self.start,
+            self.length,
+            self.style.signal_name()
+        )
+    }
+}
+
+/// Plain text with style ranges.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StyledText {
+    pub text: String,
+    pub styles: Vec<StyleRange>,
+}
+
+// ============================================================================
+// Public API
+// ============================================================================
+
+/// Render markdown for `target` as a single message.
+pub fn render_markdown(markdown: &str, target: FormatTarget) -> String {
+    Renderer::render(markdown, target).text
+}
+
+/// Render markdown for `target` as messages of at most `limit` characters.
+///
+/// Splits prefer paragraph breaks, then line breaks, then spaces; formatting
+/// open at a split is closed at the end of one message and reopened at the
+/// start of the next, code fences included.
+pub fn render_markdown_chunks(markdown: &str, target: FormatTarget, limit: usize) -> Vec<String> {
+    let rendered = Renderer::render(markdown, target);
+    split(&rendered, limit)
+        .into_iter()
+        .map(|piece| piece.assemble(&rendered))
+        .filter(|chunk| !chunk.trim().is_empty())
+        .collect()
+}
+
+/// Render markdown as plain text with Signal style ranges.
+pub fn render_signal(markdown: &str) -> StyledText {
+    let rendered = Renderer::render(markdown, FormatTarget::Signal);
+    let text = &rendered.text;
+    StyledText {
+        styles: style_ranges(&rendered, 0, text.len()),
+        text: text.clone(),
+    }
+}
+
+/// Render markdown as Signal messages of at most `limit` characters, each
+/// with its own style ranges.
+pub fn render_signal_chunks(markdown: &str, limit: usize) -> Vec<StyledText> {
+    let rendered = Renderer::render(markdown, FormatTarget::Signal);
+    split(&rendered, limit)
+        .into_iter()
+        .filter_map(|piece| {
+            let body = rendered.text[piece.start..piece.end].trim_end();
+            if body.trim().is_empty() {
+                return None;
+            }
+            let end = piece.start + body.len();
+            Some(StyledText {
+                text: body.to_string(),
+                styles: style_ranges(&rendered, piece.start, end),
+            })
+        })
+        .collect()
+}
+
+/// Escape text for Telegram's HTML parse mode.
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        push_escaped(&mut out, c, FormatTarget::TelegramHtml, false);
+    }
+    out
+}
+
+// ============================================================================
+// Renderer
+// ============================================================================
+
+/// Markup open at some point of the output.
+#[derive(Debug, Clone)]
+struct Open {
+    open: String,
+    close: String,
+    style: Option<TextStyle>,
+    /// Byte offset where the element's content starts.
+    start: usize,
+}
+
+/// Markup needed to split the output at a break: `close` ends the open
+/// elements, `reopen` starts them again in the next message.
+#[derive(Debug, Clone, Default)]
+struct OpenState {
+    close: String,
+    reopen: String,
+}
+
+/// A position the output may be split at.
+#[derive(Debug, Clone, Copy)]
+struct Break {
+    /// Byte offset into the output.
+    at: usize,
+    /// 3 = between blocks, 2 = line break, 1 = space, 0 = between characters.
+    weight: u8,
+    /// Index into [`Rendered::states`].
+    state: usize,
+}
+
+/// Rendered output with its split points.
+struct Rendered {
+    text: String,
+    breaks: Vec<Break>,
+    states: Vec<OpenState>,
+    /// Styled byte ranges (`start`, `end`, style), for Signal.
+    spans: Vec<(usize, usize, TextStyle)>,
+}
+
+/// A table being collected; rendered as a code block at its end.
+#[derive(Default)]
+struct Table {
+    rows: Vec<Vec<String>>,
+    header_rows: usize,
+}
+
+struct Renderer {
+    target: FormatTarget,
+    out: String,
+    stack: Vec<Open>,
+    states: Vec<OpenState>,
+    /// Whether `stack` changed since the last state was recorded.
+    state_dirty: bool,
+    breaks: Vec<Break>,
+    spans: Vec<(usize, usize, TextStyle)>,
+    /// Open lists: `Some(next number)` for ordered lists.
+    lists: Vec<Option<u64>>,
+    quote_depth: usize,
+    /// Nothing has been written on the current line yet.
+    line_start: bool,
+    in_code: bool,
+    /// Open links: output offset of the label and destination.
+    links: Vec<(usize, String)>,
+    table: Option<Table>,
+    /// Discord headings use `#` prefixes instead of bold.
+    heading_prefix: bool,
+}
+
+impl Renderer {
+    fn render(markdown: &str, target: FormatTarget) -> Rendered {
+        let mut renderer = Self {
+            target,
+            out: String::with_capacity(markdown.len() + 16),
+            stack: Vec::new(),
+            states: Vec::new(),
+            state_dirty: true,
+            breaks: Vec::new(),
+            spans: Vec::new(),
+            lists: Vec::new(),
+            quote_depth: 0,
+            line_start: true,
+            in_code: false,
+            links: Vec::new(),
+            table: None,
+            heading_prefix: false,
+        };
+        let options =
+            Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TABLES | Options::ENABLE_TASKLISTS;
+        for event in Parser::new_ext(markdown, options) {
+            renderer.event(event);
+        }
+        while !renderer.stack.is_empty() {
+            renderer.pop();
+        }
+        renderer.trim_trailing(|c| c.is_whitespace());
+        Rendered {
+            text: renderer.out,
+            breaks: renderer.breaks,
+            states: renderer.states,
+            spans: renderer.spans,
+        }
+    }
+
+    fn event(&mut self, event: Event<'_>) {
+        if self.table.is_some() {
+            self.table_event(event);
+            return;
+        }
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) | Event::Html(text) | Event::InlineHtml(text) => self.text(&text),
+            Event::Code(code) => {
+                self.push_style(TextStyle::Monospace);
+                let in_code = std::mem::replace(&mut self.in_code, true);
+                self.text(&code);
+                self.in_code = in_code;
+                self.pop();
+            }
+            Event::SoftBreak | Event::HardBreak => self.newline(),
+            Event::Rule => {
+                self.raw("——————");
+                self.end_block();
+            }
+            Event::TaskListMarker(checked) => self.raw(if checked { "☑ " } else { "☐ " }),
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag<'_>) {
+        match tag {
+            Tag::Heading { level, .. } => self.start_heading(level),
+            Tag::Strong => self.push_style(TextStyle::Bold),
+            Tag::Emphasis => self.push_style(TextStyle::Italic),
+            Tag::Strikethrough => self.push_style(TextStyle::Strikethrough),
+            Tag::BlockQuote(_) => {
+                if self.target == FormatTarget::TelegramHtml {
+                    self.push("<blockquote>", "</blockquote>", None);
+                }
+                self.quote_depth += 1;
+            }
+            Tag::CodeBlock(kind) => {
+                let lang = match &kind {
+                    CodeBlockKind::Fenced(lang) => lang.split_whitespace().next().unwrap_or(""),
+                    CodeBlockKind::Indented => "",
+                };
+                self.start_code_block(lang);
+            }
+            Tag::List(start) => {
+                if !self.lists.is_empty() && !self.out.ends_with('\n') {
+                    self.newline();
+                }
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.raw(&"  ".repeat(self.lists.len().saturating_sub(1)));
+                let bullet = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ if self.target == FormatTarget::Discord => "- ".to_string(),
+                    _ => "• ".to_string(),
+                };
+                self.raw(&bullet);
+            }
+            Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. } => {
+                self.line_prefix();
+                if self.target == FormatTarget::TelegramHtml {
+                    let open = format!("<a href=\"{}\">", escape_html(&dest_url));
+                    self.push(&open, "</a>", None);
+                } else {
+                    self.links.push((self.out.len(), dest_url.to_string()));
+                }
+            }
+            Tag::Table(_) => {
+                self.table = Some(Table::default());
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph => {
+                if self.lists.is_empty() {
+                    self.end_block();
+                } else {
+                    self.newline();
+                }
+            }
+            TagEnd::Heading(_) => {
+                if std::mem::take(&mut self.heading_prefix) {
+                    self.end_block();
+                } else {
+                    self.pop();
+                    self.end_block();
+                }
+            }
+            TagEnd::Strong | TagEnd::Emphasis | TagEnd::Strikethrough => self.pop(),
+            TagEnd::BlockQuote(_) => {
+                self.trim_trailing(|c| c == '\n');
+                self.quote_depth = self.quote_depth.saturating_sub(1);
+                if self.target == FormatTarget::TelegramHtml {
+                    self.pop();
+                }
+                self.end_block();
+            }
+            TagEnd::CodeBlock => {
+                if self.out.ends_with('\n') {
+                    self.trim_last_char();
+                }
+                self.in_code = false;
+                if self.target == FormatTarget::Signal || !self.target.code_fence("").1.is_empty() {
+                    self.pop();
+                }
+                self.end_block();
+            }
+            TagEnd::List(_) => {
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.end_block();
+                }
+            }
+            TagEnd::Item if !self.out.ends_with('\n') => self.newline(),
+            TagEnd::Link | TagEnd::Image => {
+                if self.target == FormatTarget::TelegramHtml {
+                    self.pop();
+                } else if let Some((start, url)) = self.links.pop() {
+                    self.end_link(start, &url);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn start_heading(&mut self, level: HeadingLevel) {
+        if self.target == FormatTarget::Discord && level <= HeadingLevel::H3 {
+            let hashes = match level {
+                HeadingLevel::H1 => "# ",
+                HeadingLevel::H2 => "## ",
+                _ => "### ",
+            };
+            self.raw(hashes);
+            self.heading_prefix = true;
+        } else {
+            self.push_style(TextStyle::Bold);
+        }
+    }
+
+    fn start_code_block(&mut self, lang: &str) {
+        if !self.line_start {
+            self.newline();
+        }
+        let (open, close) = self.target.code_fence(lang);
+        if self.target == FormatTarget::Signal {
+            self.push("", "", Some(TextStyle::Monospace));
+        } else if !close.is_empty() {
+            self.push(&open, close, None);
+        }
+        self.in_code = true;
+    }
+
+    /// Write a link whose label has been rendered from `start`.
+    fn end_link(&mut self, start: usize, url: &str) {
+        let label = self.out[start..].to_string();
+        let plain_label = label.trim();
+        let same =
+            plain_label.is_empty() || plain_label == url || plain_label == format!("mailto:{url}");
+        if self.target.has_link_markup() {
+            self.truncate(start);
+            let url = match self.target {
+                FormatTarget::Slack => escape_slack(url),
+                _ => url.to_string(),
+            };
+            let link = match (self.target, same) {
+                (FormatTarget::Slack, true) => format!("<{url}>"),
+                (FormatTarget::Slack, false) => format!("<{url}|{label}>"),
+                (_, true) => url,
+                (_, false) => format!("[{label}]({url})"),
+            };
+            self.raw(&link);
+        } else if plain_label.is_empty() {
+            self.raw(url);
+        } else if !same {
+            self.raw(&format!(" ({url})"));
+        }
+    }
+
+    fn table_event(&mut self, event: Event<'_>) {
+        let Some(table) = self.table.as_mut() else {
+            return;
+        };
+        match event {
+            Event::Start(Tag::TableHead) | Event::Start(Tag::TableRow) => {
+                table.rows.push(Vec::new());
+            }
+            Event::End(TagEnd::TableHead) => table.header_rows = table.rows.len(),
+            Event::Start(Tag::TableCell) => {
+                if let Some(row) = table.rows.last_mut() {
+                    row.push(String::new());
+                }
+            }
+            Event::Text(text) | Event::Code(text) | Event::Html(text) | Event::InlineHtml(text) => {
+                if let Some(cell) = table.rows.last_mut().and_then(|row| row.last_mut()) {
+                    cell.push_str(&text);
+                }
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                if let Some(cell) = table.rows.last_mut().and_then(|row| row.last_mut()) {
+                    cell.push(' ');
+                }
+            }
+            Event::End(TagEnd::Table) => {
+                let table = self.table.take().unwrap_or_default();
+                self.write_table(&table);
+            }
+            _ => {}
+        }
+    }
+
+    /// Write a table as an aligned code block.
+    fn write_table(&mut self, table: &Table) {
+        let columns = table.rows.iter().map(Vec::len).max().unwrap_or(0);
+        let mut widths = vec![0; columns];
+        for row in &table.rows {
+            for (i, cell) in row.iter().enumerate() {
+                widths[i] = widths[i].max(cell.trim().chars().count());
+            }
+        }
+        let line = |row: &[String]| {
+            (0..columns)
+                .map(|i| {
+                    let cell = row.get(i).map_or("", |c| c.trim());
+                    let pad = widths[i] - cell.chars().count();
+                    format!("{cell}{}", " ".repeat(pad))
+                })
+                .collect::<Vec<_>>()
+                .join(" | ")
+                .trim_end()
+                .to_string()
+        };
+        let mut lines = Vec::new();
+        for (i, row) in table.rows.iter().enumerate() {
+            lines.push(line(row));
+            if i + 1 == table.header_rows {
+                let rule: Vec<String> = widths.iter().map(|w| "-".repeat((*w).max(1))).collect();
+                lines.push(rule.join("-+-"));
+            }
+        }
+
+        self.start_code_block("");
+        self.text(&lines.join("\n"));
+        self.end(TagEnd::CodeBlock);
+    }
+
+    // ------------------------------------------------------------------------
+    // Output primitives
+    // ------------------------------------------------------------------------
+
+    /// Write the quote prefix at the start of a line.
+    fn line_prefix(&mut self) {
+        if !self.line_start {
+            return;
+        }
+        self.line_start = false;
+        if self.quote_depth > 0 && self.target != FormatTarget::TelegramHtml {
+            self.out.push_str(&"> ".repeat(self.quote_depth));
+        }
+    }
+
+    /// Write markup (not escaped).
+    fn raw(&mut self, s: &str) {
+        if s.is_empty() {
+            return;
+        }
+        self.line_prefix();
+        self.out.push_str(s);
+    }
+
+    /// Write text, escaped for the target, recording split points.
+    fn text(&mut self, text: &str) {
+        for c in text.chars() {
+            if c == '\n' {
+                self.newline();
+                continue;
+            }
+            self.line_prefix();
+            self.mark(if c == ' ' { 1 } else { 0 });
+            push_escaped(&mut self.out, c, self.target, self.in_code);
+        }
+    }
+
+    fn newline(&mut self) {
+        self.mark(2);
+        self.out.push('\n');
+        self.line_start = true;
+    }
+
+    /// End a block: paragraph break, or line break inside a list.
+    fn end_block(&mut self) {
+        self.mark(3);
+        self.out
+            .push_str(if self.lists.is_empty() { "\n\n" } else { "\n" });
+        self.line_start = true;
+    }
+
+    fn push_style(&mut self, style: TextStyle) {
+        let (open, close) = self.target.markers(style);
+        self.push(open, close, Some(style));
+    }
+
+    fn push(&mut self, open: &str, close: &str, style: Option<TextStyle>) {
+        self.raw(open);
+        self.stack.push(Open {
+            open: open.to_string(),
+            close: close.to_string(),
+            style,
+            start: self.out.len(),
+        });
+        self.state_dirty = true;
+    }
+
+    fn pop(&mut self) {
+        let Some(open) = self.stack.pop() else {
+            return;
+        };
+        if let Some(style) = open.style {
+            if self.out.len() > open.start {
+                self.spans.push((open.start, self.out.len(), style));
+            }
+        }
+        self.out.push_str(&open.close);
+        self.state_dirty = true;
+    }
+
+    /// Record a split point at the current position.
+    fn mark(&mut self, weight: u8) {
+        // Link labels are rewritten when the link ends.
+        if !self.links.is_empty() {
+            return;
+        }
+        if self.state_dirty {
+            self.states.push(OpenState {
+                close: self.stack.iter().rev().map(|o| o.close.as_str()).collect(),
+                reopen: self.stack.iter().map(|o| o.open.as_str()).collect(),
+            });
+            self.state_dirty = false;
+        }
+        self.breaks.push(Break {
+            at: self.out.len(),
+            weight,
+            state: self.states.len() - 1,
+        });
+    }
+
+    fn trim_trailing(&mut self, pred: impl Fn(char) -> bool) {
+        let len = self.out.trim_end_matches(pred).len();
+        self.truncate(len);
+    }
+
+    fn trim_last_char(&mut self) {
+        if let Some(c) = self.out.chars().last() {
+            self.truncate(self.out.len() - c.len_utf8());
+        }
+    }
+
+    /// Cut the output back to `len` bytes, dropping later split points.
+    fn truncate(&mut self, len: usize) {
+        self.out.truncate(len);
+        while self.breaks.last().is_some_and(|b| b.at > len) {
+            self.breaks.pop();
+        }
+        for span in &mut self.spans {
+            span.1 = span.1.min(len);
+        }
+        self.line_start = self.out.is_empty() || self.out.ends_with('\n');
+    }
+}
+
+/// Append `c` escaped for `target`.
+fn push_escaped(out: &mut String, c: char, target: FormatTarget, in_code: bool) {
+    match (target, c) {
+        (FormatTarget::TelegramHtml, '&') | (FormatTarget::Slack, '&') => out.push_str("&amp;"),
+        (FormatTarget::TelegramHtml, '<') | (FormatTarget::Slack, '<') => out.push_str("&lt;"),
+        (FormatTarget::TelegramHtml, '>') | (FormatTarget::Slack, '>') => out.push_str("&gt;"),
+        (FormatTarget::TelegramHtml, '"') => out.push_str("&quot;"),
+        (FormatTarget::Discord, '\\' | '*' | '_' | '~' | '`' | '|') if !in_code => {
+            out.push('\\');
+            out.push(c);
+        }
+        // IRC control codes in the text would restyle the rest of the line.
+        (
+            FormatTarget::Irc,
+            '\x02' | '\x03' | '\x0f' | '\x11' | '\x16' | '\x1d' | '\x1e' | '\x1f',
+        ) => {}
+        _ => out.push(c),
+    }
+}
+
+fn escape_slack(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        push_escaped(&mut out, c, FormatTarget::Slack, false);
+    }
+    out
+}
+
+// ============================================================================
+// Splitting
+// ============================================================================
+
+/// A message cut from [`Rendered::text`].
+struct Piece {
+    start: usize,
+    end: usize,
+    /// State reopened at the start (the previous piece's break).
+    reopen: Option<usize>,
+    /// State closed at the end.
+    close: Option<usize>,
+}
+
+impl Piece {
+    fn assemble(&self, rendered: &Rendered) -> String {
+        let reopen = self
+            .reopen
+            .map_or("", |s| rendered.states[s].reopen.as_str());
+        let close = self.close.map_or("", |s| rendered.states[s].close.as_str());
+        let body = rendered.text[self.start..self.end].trim_end();
+        format!("{reopen}{body}{close}")
+    }
+}
+
+/// Choose split points so each assembled piece fits in `limit` characters.
+fn split(rendered: &Rendered, limit: usize) -> Vec<Piece> {
+    let limit = limit.max(1);
+    let text = &rendered.text;
+    let chars_at = char_offsets(text, &rendered.breaks);
+    let total_chars = text.chars().count();
+    let state_chars: Vec<(usize, usize)> = rendered
+        .states
+        .iter()
+        .map(|s| (s.close.chars().count(), s.reopen.chars().count()))
+        .collect();
+
+    let mut pieces = Vec::new();
+    let (mut start, mut start_char) = (0, 0);
+    let mut reopen: Option<usize> = None;
+    let mut next = 0;
+
+    loop {
+        let prefix = reopen.map_or(0, |s| state_chars[s].1);
+        let budget = limit.saturating_sub(prefix).max(1);
+        if total_chars - start_char <= budget {
+            pieces.push(Piece {
+                start,
+                end: text.len(),
+                reopen,
+                close: None,
+            });
+            return pieces;
+        }
+
+        while next < rendered.breaks.len() && rendered.breaks[next].at <= start {
+            next += 1;
+        }
+        // Best candidate per weight: the last one that fits.
+        let mut best: [Option<usize>; 4] = [None; 4];
+        for (i, b) in rendered.breaks.iter().enumerate().skip(next) {
+            let body = chars_at[i] - start_char;
+            if body > budget {
+                break;
+            }
+            if body + state_chars[b.state].0 <= budget {
+                best[b.weight as usize] = Some(i);
+            }
+        }
+        // Nothing fits (markup longer than the limit): take the nearest
+        // break so the output still makes progress.
+        let pick = best
+            .iter()
+            .rev()
+            .flatten()
+            .next()
+            .copied()
+            .or((next < rendered.breaks.len()).then_some(next));
+        let Some(i) = pick else {
+            pieces.push(Piece {
+                start,
+                end: text.len(),
+                reopen,
+                close: None,
+            });
+            return pieces;
+        };
+
+        let b = rendered.breaks[i];
+        pieces.push(Piece {
+            start,
+            end: b.at,
+            reopen,
+            close: Some(b.state),
+        });
+        let rest = &text[b.at..];
+        let skip = match b.weight {
+            3 => rest.len() - rest.trim_start_matches('\n').len(),
+            1 | 2 => usize::from(rest.starts_with([' ', '\n'])),
+            _ => 0,
+        };
+        reopen = Some(b.state);
+        start = b.at + skip;
+        start_char = chars_at[i] + skip;
+    }
+}
+
+/// Character offset of every break.
+fn char_offsets(text: &str, breaks: &[Break]) -> Vec<usize> {
+    let mut offsets = Vec::with_capacity(breaks.len());
+    let (mut byte, mut chars) = (0, 0);
+    for b in breaks {
+        chars += text[byte..b.at].chars().count();
+        byte = b.at;
+        offsets.push(chars);
+    }
+    offsets
+}
+
+/// Style ranges within `[start, end)` of the output, relative to `start`
+/// and in UTF-16 code units.
+fn style_ranges(rendered: &Rendered, start: usize, end: usize) -> Vec<StyleRange> {
+    let utf16 = |from: usize, to: usize| rendered.text[from..to].encode_utf16().count();
+    let mut ranges: Vec<StyleRange> = rendered
+        .spans
+        .iter()
+        .filter_map(|&(s, e, style)| {
+            let (s, e) = (s.max(start), e.min(end));
+            (s < e).then(|| StyleRange {
+                start: utf16(start, s),
+                length: utf16(s, e),
+                style,
+            })
+        })
+        .collect();
+    ranges.sort_by_key(|r| (r.start, r.length));
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn telegram_html_escapes_and_formats() {
+        let html = |md| render_markdown(md, FormatTarget::TelegramHtml);
+        assert_eq!(
+            html("**bold** & <i>_it_</i> `a<b`"),
+            "<b>bold</b> &amp; &lt;i&gt;<i>it</i>&lt;/i&gt; <code>a&lt;b</code>"
+        );
+        assert_eq!(
+            html("# Title\n\n- one\n- two"),
+            "<b>Title</b>\n\n• one\n• two"
+        );
+        assert_eq!(
+            html("```rust\nfn main() {}\n```"),
+            "<pre><code class=\"language-rust\">fn main() {}</code></pre>"
+        );
+        assert_eq!(
+            html("[docs](https://example.com/?a=1&b=2)"),
+            "<a href=\"https://example.com/?a=1&amp;b=2\">docs</a>"
+        );
+        assert_eq!(html("> quoted"), "<blockquote>quoted</blockquote>");
+    }
+
+    #[test]
+    fn slack_mrkdwn() {
+        let mrkdwn = |md| render_markdown(md, FormatTarget::Slack);
+        assert_eq!(
+            mrkdwn("**bold** _it_ ~~gone~~ `a<b`"),
+            "*bold* _it_ ~gone~ `a&lt;b`"
+        );
+        assert_eq!(mrkdwn("# Title\n\n- one\n- two"), "*Title*\n\n• one\n• two");
+        assert_eq!(
+            mrkdwn("[docs](https://example.com/?a=1&b=2) and <https://x.io>"),
+            "<https://example.com/?a=1&amp;b=2|docs> and <https://x.io>"
+        );
+        assert_eq!(
+            mrkdwn("> quoted\n> text\n\nafter"),
+            "> quoted\n> text\n\nafter"
+        );
+        assert_eq!(
+            mrkdwn("```rust\nfn main() {}\n```"),
+            "```\nfn main() {}\n```"
+        );
+    }
+
+    #[test]
+    fn discord_whatsapp_and_irc() {
+        assert_eq!(
+            render_markdown(
+                "## Plan\n\n**a_b** *c* [x](https://x.io)",
+                FormatTarget::Discord
+            ),
+            "## Plan\n\n**a\\_b** *c* [x](https://x.io)"
+        );
+        assert_eq!(
+            render_markdown(
+                "# Plan\n\n**bold** *it* ~~no~~ [x](https://x.io)",
+                FormatTarget::WhatsApp
+            ),
+            "*Plan*\n\n*bold* _it_ ~no~ x (https://x.io)"
+        );
+        assert_eq!(
+            render_markdown("**bold** and *it*", FormatTarget::Irc),
+            "\x02bold\x02 and \x1dit\x1d"
+        );
+        assert_eq!(
+            render_markdown("1. one\n2. **two**", FormatTarget::Plain),
+            "1. one\n2. two"
+        );
+    }
+
+    #[test]
+    fn tables_degrade_to_code_blocks() {
+        let md = "| Name | Qty |\n|------|-----|\n| apple | 3 |\n| kiwi | 12 |";
+        assert_eq!(
+            render_markdown(md, FormatTarget::Slack),
+            "```\nName  | Qty\n------+----\napple | 3\nkiwi  | 12\n```"
+        );
+        assert!(render_markdown(md, FormatTarget::TelegramHtml).starts_with("<pre>Name"));
+    }
+
+    #[test]
+    fn signal_styles_use_utf16_ranges() {
+        let styled = render_signal("é **bold** and `code` 🦞 *it*");
+        assert_eq!(styled.text, "é bold and code 🦞 it");
+        let ranges: Vec<String> = styled.styles.iter().map(StyleRange::to_signal).collect();
+        assert_eq!(ranges, ["2:4:BOLD", "11:4:MONOSPACE", "19:2:ITALIC"]);
+    }
+
+    #[test]
+    fn chunks_reopen_spans_and_code_fences() {
+        let md = format!("**{}**", "word ".repeat(30).trim());
+        let chunks = render_markdown_chunks(&md, FormatTarget::Slack, 40);
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.chars().count() <= 40, "{chunk:?}");
+            assert!(chunk.starts_with('*') && chunk.ends_with('*'), "{chunk:?}");
+        }
+
+        let code = (1..=20)
+            .map(|i| format!("line {i}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let md = format!("intro\n\n```\n{code}\n```");
+        let chunks = render_markdown_chunks(&md, FormatTarget::Discord, 60);
+        assert_eq!(chunks[0], "intro");
+        for chunk in &chunks[1..] {
+            assert!(chunk.chars().count() <= 60, "{chunk:?}");
+            assert!(
+                chunk.starts_with("```\nline") && chunk.ends_with("\n```"),
+                "{chunk:?}"
+            );
+        }
+        let lines: Vec<&str> = chunks[1..]
+            .iter()
+            .flat_map(|c| c.lines().filter(|l| l.starts_with("line")))
+            .collect();
+        assert_eq!(lines.len(), 20);
+    }
+
+    #[test]
+    fn escaped_text_is_never_split_inside_an_entity() {
+        let chunks = render_markdown_chunks(&"a&b ".repeat(50), FormatTarget::TelegramHtml, 60);
+        assert!(chunks.len() > 4);
+        for chunk in &chunks {
+            assert!(chunk.chars().count() <= 60);
+            assert_eq!(chunk.matches("&amp;").count(), chunk.matches('&').count());
+        }
+    }
+
+    #[test]
+    fn signal_chunks_carry_their_own_ranges() {
+        let md = format!("intro\n\n**{}**", "bold ".repeat(10).trim());
+        let chunks = render_signal_chunks(&md, 30);
+        assert!(chunks.len() >= 2);
+        assert_eq!(chunks[0].text, "intro");
+        assert!(chunks[0].styles.is_empty());
+        for chunk in &chunks[1..] {
+            assert_eq!(chunk.styles.len(), 1);
+            let range = chunk.styles[0];
+            assert_eq!(range.start, 0);
+            assert_eq!(range.length, chunk.text.encode_utf16().count());
+        }
+    }
+}
@@ -1,10 +1,22 @@
-use super::plugin::{ChannelCapability, ChannelMeta, ChannelPlugin};
+use super::group_history::admit_group_message;
+use super::normalize::{ChatType, NormalizedMessage, NormalizedOutbound, NormalizedSender};
+use super::plugin::{
+    unsupported, ChannelCapability, ChannelMeta, ChannelPlugin, WebhookRequest, WebhookResponse,
+};
+use super::webhook::{self, JwtVerifier, ReplayGuard, WebhookRoute};
+use crate::config::{Config, GoogleChatConfig};
 use crate::gateway::GatewayState;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use async_trait::async_trait;
+use axum::http::StatusCode;
+use parking_lot::Mutex;
 use reqwest::Client;
-use tracing::{info, warn};
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
 
 // ============================================================================
 // Google Chat Channel Implementation
@@ -17,49 +29,189 @@ use tracing::{info, warn};
 ///   webhook URL. Simple, no OAuth required, outbound-only.
 /// - **Service account mode**: Uses a Google service account to call the
 ///   Google Chat API for full bidirectional messaging.
+///
+/// In service account mode, the Chat app's HTTP endpoint is
+/// `POST /channels/googlechat/<webhookPath>`. Google signs each event with
+/// a JWT whose audience is the Cloud project number (`audience`); events
+/// are acknowledged immediately and answered through the Chat API.
 pub struct GoogleChatChannel {
-    /// Incoming webhook URL for the Google Chat space.
-    webhook_url: Option<String>,
-    /// Service account JSON key (serialized) for API access.
-    service_account: Option<String>,
-    /// Whether this channel is enabled.
-    enabled: Option<bool>,
+    enabled: bool,
+    config: GoogleChatConfig,
+    /// Chat API client; `None` without a service account key.
+    api: Option<ChatApi>,
+    /// Validates inbound event tokens; `None` without an audience.
+    verifier: Option<JwtVerifier>,
+    /// Message names already handled.
+    replay: ReplayGuard,
     /// HTTP client for API calls.
     client: Client,
 }
 
+const DEFAULT_WEBHOOK_PATH: &str = "webhook";
+const CHAT_API_BASE: &str = "https://chat.googleapis.com/v1";
+const CHAT_BOT_SCOPE: &str = "https://www.googleapis.com/auth/chat.bot";
+
 impl GoogleChatChannel {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
+        let chat = config.channels.googlechat.clone().unwrap_or_default();
+        let client = Client::new();
+        let api =
+            chat.service_account_key.as_deref().and_then(|key| {
+                match ServiceAccountKey::load(key) {
+                    Ok(key) => Some(ChatApi {
+                        client: client.clone(),
+                        key: Arc::new(key),
+                        token: Arc::new(Mutex::new(None)),
+                    }),
+                    Err(e) => {
+                        warn!(error = %e, "Google Chat: invalid service account key");
+                        None
+                    }
+                }
+            });
         Self {
-            webhook_url: None,
-            service_account: None,
-            enabled: None,
-            client: Client::new(),
+            enabled: chat.enabled.unwrap_or(false),
+            verifier: chat.audience.as_deref().map(JwtVerifier::google_chat),
+            api,
+            config: chat,
+            replay: ReplayGuard::default(),
+            client,
         }
     }
 
-    /// Create a webhook-only Google Chat channel.
-    pub fn with_webhook(webhook_url: String) -> Self {
-        Self {
-            webhook_url: Some(webhook_url),
-            service_account: None,
-            enabled: Some(true),
-            client: Client::new(),
+    fn webhook_path(&self) -> &str {
+        webhook::webhook_path(self.config.webhook_path.as_deref(), DEFAULT_WEBHOOK_PATH)
+    }
+
+    /// Validate the bearer token Google attaches to each event.
+    async fn authenticate(&self, request: &WebhookRequest) -> bool {
+        let (Some(verifier), Some(token)) = (&self.verifier, webhook::bearer_token(request)) else {
+            return false;
+        };
+        match verifier.verify(token).await {
+            Ok(_) => true,
+            Err(e) => {
+                warn!(error = %e, "Google Chat: rejected event token");
+                false
+            }
         }
     }
+}
 
-    /// Create a Google Chat channel with service account credentials.
-    pub fn with_service_account(service_account: String) -> Self {
-        Self {
-            webhook_url: None,
-            service_account: Some(service_account),
-            enabled: Some(true),
-            client: Client::new(),
+/// The fields of a service account key file used for token exchange.
+#[derive(Deserialize)]
+struct ServiceAccountKey {
+    client_email: String,
+    private_key: String,
+    #[serde(default = "default_token_uri")]
+    token_uri: String,
+}
+
+fn default_token_uri() -> String {
+    "https://oauth2.googleapis.com/token".to_string()
+}
+
+impl ServiceAccountKey {
+    /// Parse a key given inline as JSON or as a path to the key file.
+    fn load(key: &str) -> Result<Self> {
+        let json = if key.trim_start().starts_with('{') {
+            key.to_string()
+        } else {
+            std::fs::read_to_string(key)
+                .with_context(|| format!("failed to read service account key {key}"))?
+        };
+        serde_json::from_str(&json).context("malformed service account key")
+    }
+}
+
+/// Chat API client authenticated as the service account.
+#[derive(Clone)]
+struct ChatApi {
+    client: Client,
+    key: Arc<ServiceAccountKey>,
+    /// Cached access token and its expiry.
+    token: Arc<Mutex<Option<(String, Instant)>>>,
+}
+
+impl ChatApi {
+    /// Exchange a signed JWT assertion for an access token, with caching.
+    async fn access_token(&self) -> Result<String> {
+        if let Some((token, expires)) = self.token.lock().as_ref() {
+            if Instant::now() < *expires {
+                return Ok(token.clone());
+            }
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let claims = serde_json::json!({
+            "iss": self.key.client_email,
+            "scope": CHAT_BOT_SCOPE,
+            "aud": self.key.token_uri,
+            "iat": now,
+            "exp": now + 3600,
+        });
+        let signing_key = jsonwebtoken::EncodingKey::from_rsa_pem(self.key.private_key.as_bytes())
+            .context("service account private_key is not an RSA PEM key")?;
+        let assertion = jsonwebtoken::encode(
+            &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256),
+            &claims,
+            &signing_key,
+        )?;
+
+        let resp = self
+            .client
+            .post(&self.key.token_uri)
+            .form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", assertion.as_str()),
+            ])
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            anyhow::bail!("Google Chat token exchange failed ({}): {}", status, text);
         }
+
+        let body: Value = resp.json().await?;
+        let token = body["access_token"]
+            .as_str()
+            .context("Google Chat: no access_token in token response")?
+            .to_string();
+        let ttl = body["expires_in"]
+            .as_u64()
+            .unwrap_or(3600)
+            .saturating_sub(60);
+        *self.token.lock() = Some((token.clone(), Instant::now() + Duration::from_secs(ttl)));
+        Ok(token)
     }
 
-    fn is_enabled(&self) -> bool {
-        self.enabled.unwrap_or(false)
+    /// Create a message in `space` (e.g. `spaces/AAAA...`), replying in
+    /// `thread` when given.
+    async fn create_message(&self, space: &str, thread: Option<&str>, text: &str) -> Result<()> {
+        let token = self.access_token().await?;
+        let mut url = format!("{}/{}/messages", CHAT_API_BASE, space);
+        let mut body = serde_json::json!({ "text": text });
+        if let Some(thread) = thread {
+            url.push_str("?messageReplyOption=REPLY_MESSAGE_FALLBACK_TO_NEW_THREAD");
+            body["thread"] = serde_json::json!({ "name": thread });
+        }
+
+        let resp = self
+            .client
+            .post(&url)
+            .bearer_auth(token)
+            .json(&body)
+            .send()
+            .await?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            anyhow::bail!("Google Chat API send failed ({}): {}", status, text);
+        }
+
+        Ok(())
     }
 }
 
@@ -73,7 +225,7 @@ impl ChannelPlugin for GoogleChatChannel {
         ChannelMeta {
             name: "Google Chat".to_string(),
             description: "Google Chat (Workspace) channel via webhook or Chat API".to_string(),
-            enabled: self.is_enabled(),
+            enabled: self.enabled,
             multi_account: false,
         }
     }
@@ -89,16 +241,25 @@ impl ChannelPlugin for GoogleChatChannel {
         ]
     }
 
+    fn webhook_routes(&self) -> Vec<WebhookRoute> {
+        if !self.enabled || self.api.is_none() {
+            return Vec::new();
+        }
+        vec![WebhookRoute::post(self.webhook_path())]
+    }
+
     async fn start_account(&self, _state: &GatewayState) -> Result<()> {
-        if !self.is_enabled() {
+        if !self.enabled {
             return Ok(());
         }
 
-        if self.webhook_url.is_some() {
+        if self.api.is_some() {
+            if self.verifier.is_none() {
+                warn!("Google Chat: no audience configured; inbound events will be rejected");
+            }
+            info!(path = %self.webhook_path(), "Google Chat channel starting (service account mode)");
+        } else if self.config.webhook_url.is_some() {
             info!("Google Chat channel starting (webhook mode)");
-        } else if self.service_account.is_some() {
-            info!("Google Chat channel starting (service account mode)");
-            // TODO: Parse the service account JSON and set up OAuth2 token refresh.
         } else {
             warn!("Google Chat channel enabled but no webhook_url or service_account configured");
         }
@@ -107,25 +268,79 @@ impl ChannelPlugin for GoogleChatChannel {
     }
 
     async fn stop_account(&self) -> Result<()> {
-        if self.is_enabled() {
+        if self.enabled {
             info!("Google Chat channel stopping");
         }
         Ok(())
     }
 
     async fn send_message(&self, to: &str, message: &str) -> Result<()> {
-        // Prefer webhook mode if configured.
-        if let Some(webhook_url) = &self.webhook_url {
-            return self.send_via_webhook(webhook_url, message).await;
+        // Prefer the Chat API, which can address any space the app is in.
+        if let Some(api) = &self.api {
+            info!(space = %to, "Google Chat: sending message via Chat API");
+            return api.create_message(to, None, message).await;
         }
 
-        // Fall back to Chat API with service account.
-        if self.service_account.is_some() {
-            return self.send_via_api(to, message).await;
+        if let Some(webhook_url) = &self.config.webhook_url {
+            return self.send_via_webhook(webhook_url, message).await;
         }
 
         anyhow::bail!("Google Chat: no webhook_url or service_account configured");
     }
+
+    async fn send(&self, message: &NormalizedOutbound) -> Result<Option<String>> {
+        if !message.attachments.is_empty() {
+            return Err(unsupported(self.id(), ChannelCapability::SendMedia));
+        }
+        match (&self.api, &message.thread_id) {
+            (Some(api), Some(thread)) => {
+                api.create_message(&message.chat_id, Some(thread), &message.text)
+                    .await?
+            }
+            _ => self.send_message(&message.chat_id, &message.text).await?,
+        }
+        Ok(None)
+    }
+
+    async fn handle_webhook(
+        &self,
+        state: &GatewayState,
+        request: WebhookRequest,
+    ) -> Result<WebhookResponse> {
+        if !self.enabled || self.api.is_none() {
+            return Ok(WebhookResponse::not_found());
+        }
+        if let Some(response) = webhook::route_check(&self.webhook_routes(), &request) {
+            return Ok(response);
+        }
+        if !self.authenticate(&request).await {
+            return Ok(WebhookResponse::status(StatusCode::UNAUTHORIZED));
+        }
+        let Ok(event) = serde_json::from_slice::<Value>(&request.body) else {
+            return Ok(WebhookResponse::status(StatusCode::BAD_REQUEST));
+        };
+        // An empty JSON object acknowledges the event without a reply.
+        let ack = WebhookResponse::json(StatusCode::OK, &serde_json::json!({}));
+
+        let Some(mut msg) = normalize_event(&event) else {
+            return Ok(ack);
+        };
+        if !self.replay.first_delivery(&msg.id) {
+            debug!(message = %msg.id, "Skipping redelivered Google Chat event");
+            return Ok(ack);
+        }
+        let admitted = admit_group_message(state, &mut msg, |msg| webhook::admit(true, msg)).await;
+        if !admitted {
+            debug!(sender = %msg.sender.id, "Google Chat message not admitted");
+            return Ok(ack);
+        }
+
+        webhook::spawn_dispatch(state, msg, |msg, text| NormalizedOutbound {
+            thread_id: msg.thread_id.clone(),
+            ..NormalizedOutbound::text(msg.chat_id.clone(), text)
+        });
+        Ok(ack)
+    }
 }
 
 impl GoogleChatChannel {
@@ -140,12 +355,7 @@ impl GoogleChatChannel {
 
         info!("Google Chat: sending message via webhook");
 
-        let resp = self
-            .client
-            .post(webhook_url)
-            .json(&body)
-            .send()
-            .await?;
+        let resp = self.client.post(webhook_url).json(&body).send().await?;
 
         if !resp.status().is_success() {
             let status = resp.status();
@@ -155,38 +365,126 @@ impl GoogleChatChannel {
 
         Ok(())
     }
+}
 
-    /// Send a message via the Google Chat API (service account auth).
-    ///
-    /// `to` is a Chat API space name (e.g. `spaces/AAAA...`).
-    async fn send_via_api(&self, to: &str, message: &str) -> Result<()> {
-        // TODO: Implement OAuth2 token acquisition from service account credentials.
-        // Use `https://chat.googleapis.com/v1/{space}/messages` endpoint.
+/// Convert a `MESSAGE` interaction event into a [`NormalizedMessage`].
+///
+/// In spaces, Chat only delivers messages that mention the app, so every
+/// event counts as `mentioned`; `argumentText` is the text without it.
+fn normalize_event(event: &Value) -> Option<NormalizedMessage> {
+    if event["type"] != "MESSAGE" {
+        return None;
+    }
+    let message = &event["message"];
+    let space = &event["space"];
+    let sender = &message["sender"];
+    if sender["type"] == "BOT" {
+        return None;
+    }
 
-        let url = format!(
-            "https://chat.googleapis.com/v1/{}/messages",
-            to
-        );
+    let text = message["argumentText"]
+        .as_str()
+        .or_else(|| message["text"].as_str())
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+    if text.is_empty() {
+        return None;
+    }
 
-        let body = serde_json::json!({
-            "text": message,
-        });
+    let is_dm = space["type"] == "DM" || space["spaceType"] == "DIRECT_MESSAGE";
+    let sender_id = sender["name"].as_str()?.to_string();
 
-        info!(space = %to, "Google Chat: sending message via Chat API");
+    Some(NormalizedMessage {
+        id: message["name"].as_str()?.to_string(),
+        channel: "googlechat".to_string(),
+        account_id: "default".to_string(),
+        chat_id: space["name"].as_str()?.to_string(),
+        chat_name: space["displayName"].as_str().map(str::to_string),
+        chat_type: if is_dm { ChatType::Dm } else { ChatType::Group },
+        sender: NormalizedSender {
+            id: sender_id.clone(),
+            name: sender["displayName"]
+                .as_str()
+                .unwrap_or(&sender_id)
+                .to_string(),
+            is_bot: false,
+            roles: Vec::new(),
+        },
+        text,
+        attachments: Vec::new(),
+        reply_to_id: None,
+        thread_id: message["thread"]["name"].as_str().map(str::to_string),
+        mentioned: true,
+        timestamp: event["eventTime"]
+            .as_str()
+            .map(str::to_string)
+            .unwrap_or_else(|| chrono::Utc::now().to_rfc3339()),
+        raw: Some(event.clone()),
+    })
+}
 
-        let resp = self
-            .client
-            .post(&url)
-            .json(&body)
-            .send()
-            .await?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::webhook::tests::TEST_RSA_PEM;
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
 
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let text = resp.text().await.unwrap_or_default();
-            anyhow::bail!("Google Chat API send failed ({}): {}", status, text);
-        }
+    #[test]
+    fn message_events_use_argument_text() {
+        let event: Value = serde_json::from_str(
+            r#"{
+                "type": "MESSAGE",
+                "eventTime": "2024-01-01T00:00:00Z",
+                "space": { "name": "spaces/AAA", "type": "ROOM", "displayName": "Ops" },
+                "message": {
+                    "name": "spaces/AAA/messages/1",
+                    "sender": { "name": "users/42", "displayName": "Ada", "type": "HUMAN" },
+                    "text": "@Lobster deploy status",
+                    "argumentText": " deploy status",
+                    "thread": { "name": "spaces/AAA/threads/T1" }
+                }
+            }"#,
+        )
+        .unwrap();
+        let msg = normalize_event(&event).unwrap();
+        assert_eq!(msg.text, "deploy status");
+        assert_eq!(msg.chat_id, "spaces/AAA");
+        assert_eq!(msg.chat_type, ChatType::Group);
+        assert_eq!(msg.thread_id.as_deref(), Some("spaces/AAA/threads/T1"));
 
-        Ok(())
+        let mut added = event.clone();
+        added["type"] = "ADDED_TO_SPACE".into();
+        assert!(normalize_event(&added).is_none());
+    }
+
+    #[tokio::test]
+    async fn replies_use_a_service_account_token() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .and(body_string_contains("jwt-bearer"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(
+                    serde_json::json!({ "access_token": "ya29", "expires_in": 3600 }),
+                ),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let api = ChatApi {
+            client: Client::new(),
+            key: Arc::new(ServiceAccountKey {
+                client_email: "bot@example.iam.gserviceaccount.com".to_string(),
+                private_key: TEST_RSA_PEM.to_string(),
+                token_uri: format!("{}/token", server.uri()),
+            }),
+            token: Arc::new(Mutex::new(None)),
+        };
+        assert_eq!(api.access_token().await.unwrap(), "ya29");
+        // The second call is served from the cache.
+        assert_eq!(api.access_token().await.unwrap(), "ya29");
     }
 }
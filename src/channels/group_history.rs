@@ -0,0 +1,369 @@
+//! Mention gating and buffered context for group chats.
+//!
+//! In groups the agent only answers messages addressed to it: a platform
+//! @mention or reply to the bot (reported by the channel as
+//! [`NormalizedMessage::mentioned`]), or text matching a mention pattern.
+//! Patterns come from the default agent's `groupChat.mentionPatterns`, else
+//! `messages.groupChat.mentionPatterns`, plus the agent's identity name as a
+//! whole word. Patterns are case-insensitive regular expressions, compiled
+//! when the config is loaded (see [`MentionPattern`]).
+//!
+//! Channels keep their own admission rules (group policy, per-group
+//! `requireMention`) and run them through [`admit_group_message`]. A message
+//! that would be admitted if it mentioned the bot, but does not, is kept in
+//! a [`GroupHistory`] instead of being dropped. The next addressed turn in
+//! the same chat (or thread) gets the buffered messages, labeled by sender,
+//! ahead of its own text. At most `groupChat.historyLimit` messages are
+//! kept per chat; `0` turns buffering off.
+
+use super::normalize::{ChatType, NormalizedMessage};
+use crate::config::{AgentEntry, Config, MentionPattern};
+use crate::gateway::GatewayState;
+
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
+use tracing::debug;
+
+/// Buffered messages per chat when `historyLimit` is not configured.
+pub const DEFAULT_GROUP_HISTORY_LIMIT: usize = 50;
+
+/// Chats with buffered messages are capped so idle groups cannot grow memory.
+const MAX_CHATS: usize = 1024;
+
+/// An unaddressed group message kept as context.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub sender: String,
+    pub text: String,
+    pub timestamp: String,
+}
+
+/// Unaddressed group messages, per chat, waiting for the next addressed turn.
+#[derive(Default)]
+pub struct GroupHistory {
+    chats: Mutex<HashMap<String, (Instant, VecDeque<HistoryEntry>)>>,
+}
+
+impl GroupHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Buffer `msg`, keeping at most `limit` messages for its chat.
+    pub fn record(&self, msg: &NormalizedMessage, limit: usize) {
+        if limit == 0 {
+            return;
+        }
+        let mut text = msg.text.clone();
+        if !msg.attachments.is_empty() {
+            let note = format!("[{} attachment(s)]", msg.attachments.len());
+            text = if text.is_empty() {
+                note
+            } else {
+                format!("{text} {note}")
+            };
+        }
+
+        let key = chat_key(msg);
+        let mut chats = self.chats.lock();
+        if chats.len() >= MAX_CHATS && !chats.contains_key(&key) {
+            let stalest = chats
+                .iter()
+                .min_by_key(|(_, (at, _))| *at)
+                .map(|(key, _)| key.clone());
+            if let Some(stalest) = stalest {
+                chats.remove(&stalest);
+            }
+        }
+        let (at, entries) = chats
+            .entry(key)
+            .or_insert_with(|| (Instant::now(), VecDeque::new()));
+        *at = Instant::now();
+        entries.push_back(HistoryEntry {
+            sender: msg.sender.name.clone(),
+            text,
+            timestamp: msg.timestamp.clone(),
+        });
+        while entries.len() > limit {
+            entries.pop_front();
+        }
+    }
+
+    /// Remove and return the messages buffered for `msg`'s chat.
+    pub fn take(&self, msg: &NormalizedMessage) -> Vec<HistoryEntry> {
+        self.chats
+            .lock()
+            .remove(&chat_key(msg))
+            .map(|(_, entries)| Vec::from(entries))
+            .unwrap_or_default()
+    }
+
+    /// Number of messages buffered for `msg`'s chat.
+    pub fn buffered(&self, msg: &NormalizedMessage) -> usize {
+        self.chats
+            .lock()
+            .get(&chat_key(msg))
+            .map_or(0, |(_, entries)| entries.len())
+    }
+}
+
+/// Buffer key: threads are buffered apart from their parent chat.
+fn chat_key(msg: &NormalizedMessage) -> String {
+    format!(
+        "{}:{}:{}:{}",
+        msg.channel,
+        msg.account_id,
+        msg.chat_id,
+        msg.thread_id.as_deref().unwrap_or_default()
+    )
+}
+
+/// The agent that answers channel messages: the one marked `default`, else
+/// the first configured.
+pub(super) fn default_agent(config: &Config) -> Option<&AgentEntry> {
+    let agents = &config.agents.list;
+    agents
+        .iter()
+        .find(|a| a.default == Some(true))
+        .or_else(|| agents.first())
+}
+
+/// Messages kept per group chat, from `groupChat.historyLimit`.
+pub fn history_limit(config: &Config) -> usize {
+    default_agent(config)
+        .and_then(|a| a.group_chat.as_ref())
+        .and_then(|g| g.history_limit)
+        .or_else(|| {
+            config
+                .messages
+                .group_chat
+                .as_ref()
+                .and_then(|g| g.history_limit)
+        })
+        .map_or(DEFAULT_GROUP_HISTORY_LIMIT, |limit| limit as usize)
+}
+
+/// Whether the text of `msg` matches a mention pattern or the agent's name.
+pub fn matches_mention_pattern(config: &Config, msg: &NormalizedMessage) -> bool {
+    let agent = default_agent(config);
+    let patterns: &[MentionPattern] = agent
+        .and_then(|a| a.group_chat.as_ref())
+        .filter(|g| !g.mention_patterns.is_empty())
+        .or(config.messages.group_chat.as_ref())
+        .map(|g| g.mention_patterns.as_slice())
+        .unwrap_or_default();
+    if patterns.iter().any(|pattern| pattern.is_match(&msg.text)) {
+        return true;
+    }
+    agent
+        .and_then(|a| a.identity.as_ref())
+        .and_then(|i| i.name.as_deref())
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .is_some_and(|name| contains_word(&msg.text, name))
+}
+
+/// Whether `text` contains `word` (case-insensitively) not joined to other
+/// letters, digits or underscores.
+fn contains_word(text: &str, word: &str) -> bool {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let (text, word) = (text.to_lowercase(), word.to_lowercase());
+    text.match_indices(&word).any(|(at, found)| {
+        let before = text[..at].chars().next_back();
+        let after = text[at + found.len()..].chars().next();
+        !before.is_some_and(is_word) && !after.is_some_and(is_word)
+    })
+}
+
+/// Whether `msg` passes the channel's `admit` check, or would if it
+/// mentioned the bot.
+///
+/// Channels that admit messages before they have the gateway state at hand
+/// forward these, then gate them with [`admit_group_message`].
+pub fn admissible(msg: &NormalizedMessage, admit: impl Fn(&NormalizedMessage) -> bool) -> bool {
+    if admit(msg) {
+        return true;
+    }
+    if msg.mentioned || msg.chat_type == ChatType::Dm {
+        return false;
+    }
+    let mut addressed = msg.clone();
+    addressed.mentioned = true;
+    admit(&addressed)
+}
+
+/// Apply a channel's admission rule to a message, with mention patterns and
+/// group buffering.
+///
+/// `admit` is the channel's own check, which rejects group messages that do
+/// not mention the bot where a mention is required. When that is the only
+/// reason for rejection, a message matching a mention pattern is marked as
+/// mentioned and admitted, and any other message is buffered as context.
+pub async fn admit_group_message(
+    state: &GatewayState,
+    msg: &mut NormalizedMessage,
+    admit: impl Fn(&NormalizedMessage) -> bool,
+) -> bool {
+    if admit(msg) {
+        return true;
+    }
+    if !admissible(msg, &admit) {
+        return false;
+    }
+
+    let config = state.config.read().await;
+    if matches_mention_pattern(&config, msg) {
+        msg.mentioned = true;
+        return true;
+    }
+    debug!(
+        channel = %msg.channel,
+        chat_id = %msg.chat_id,
+        sender = %msg.sender.id,
+        "Buffering unaddressed group message"
+    );
+    state.group_history.record(msg, history_limit(&config));
+    false
+}
+
+/// Prefix the user-turn text with buffered group context, if any.
+pub fn with_context(history: &[HistoryEntry], text: String) -> String {
+    if history.is_empty() {
+        return text;
+    }
+    let mut out = String::from("[Chat messages since your last reply, for context]\n");
+    for entry in history {
+        out.push_str(&format!("{}: {}\n", entry.sender, entry.text));
+    }
+    out.push_str("\n[Current message, respond to this]\n");
+    out.push_str(&text);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::NormalizedSender;
+    use crate::config::{GroupChatConfig, IdentityConfig};
+
+    fn group_message(sender: &str, text: &str) -> NormalizedMessage {
+        NormalizedMessage {
+            id: "m1".to_string(),
+            channel: "telegram".to_string(),
+            account_id: "default".to_string(),
+            chat_id: "-100".to_string(),
+            chat_name: None,
+            chat_type: ChatType::Group,
+            sender: NormalizedSender {
+                id: sender.to_lowercase(),
+                name: sender.to_string(),
+                is_bot: false,
+                roles: Vec::new(),
+            },
+            text: text.to_string(),
+            attachments: Vec::new(),
+            reply_to_id: None,
+            thread_id: None,
+            mentioned: false,
+            timestamp: String::new(),
+            raw: None,
+        }
+    }
+
+    #[test]
+    fn history_is_bounded_and_taken_once() {
+        let history = GroupHistory::new();
+        for i in 0..5 {
+            history.record(&group_message("Ann", &format!("msg {i}")), 3);
+        }
+        let mut thread = group_message("Bob", "in a thread");
+        thread.thread_id = Some("7".to_string());
+        history.record(&thread, 3);
+
+        let taken = history.take(&group_message("Cy", ""));
+        let texts: Vec<&str> = taken.iter().map(|e| e.text.as_str()).collect();
+        assert_eq!(texts, ["msg 2", "msg 3", "msg 4"]);
+        assert!(history.take(&group_message("Cy", "")).is_empty());
+        assert_eq!(history.buffered(&thread), 1);
+
+        history.record(&group_message("Ann", "ignored"), 0);
+        assert_eq!(history.buffered(&group_message("Ann", "")), 0);
+    }
+
+    #[test]
+    fn mention_patterns_and_agent_name() {
+        let mut config = Config::default();
+        let msg = group_message("Ann", "hey Lobster, what's up?");
+        assert!(!matches_mention_pattern(&config, &msg));
+
+        config.messages.group_chat = Some(GroupChatConfig {
+            mention_patterns: vec![MentionPattern::new(r"^hey\s+lob").unwrap()],
+            history_limit: Some(10),
+        });
+        assert!(matches_mention_pattern(&config, &msg));
+        assert!(!matches_mention_pattern(
+            &config,
+            &group_message("Ann", "lobster")
+        ));
+        assert_eq!(history_limit(&config), 10);
+
+        config.agents.list = vec![AgentEntry {
+            id: "main".to_string(),
+            identity: Some(IdentityConfig {
+                name: Some("Lobster".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }];
+        assert!(matches_mention_pattern(
+            &config,
+            &group_message("Ann", "LOBSTER?")
+        ));
+        assert!(!matches_mention_pattern(
+            &config,
+            &group_message("Ann", "lobsters")
+        ));
+    }
+
+    #[test]
+    fn invalid_mention_patterns_fail_the_config_load() {
+        let config = serde_json::json!({
+            "messages": { "groupChat": { "mentionPatterns": ["hey (lob"] } }
+        });
+        let error = serde_json::from_value::<Config>(config).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains(r#"invalid mention pattern "hey (lob""#),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn admissible_when_only_a_mention_is_missing() {
+        let require_mention = |msg: &NormalizedMessage| msg.mentioned;
+        let chatter = group_message("Ann", "hi all");
+        assert!(admissible(&chatter, require_mention));
+        assert!(!admissible(&chatter, |_| false));
+
+        let mut dm = chatter.clone();
+        dm.chat_type = ChatType::Dm;
+        assert!(!admissible(&dm, require_mention));
+    }
+
+    #[test]
+    fn context_is_labeled_by_sender() {
+        let history = vec![HistoryEntry {
+            sender: "Ann".to_string(),
+            text: "lunch?".to_string(),
+            timestamp: String::new(),
+        }];
+        assert_eq!(
+            with_context(&history, "Bob: @bot where?".to_string()),
+            "[Chat messages since your last reply, for context]\nAnn: lunch?\n\n\
+             [Current message, respond to this]\nBob: @bot where?"
+        );
+        assert_eq!(with_context(&[], "hi".to_string()), "hi");
+    }
+}
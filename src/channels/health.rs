@@ -0,0 +1,525 @@
+//! Channel account health supervision.
+//!
+//! [`ChannelManager::start_all`](super::ChannelManager::start_all) runs each
+//! enabled account under a [`Supervisor`]: a task that starts the account,
+//! probes it every minute with [`ChannelPlugin::probe`] and restarts it with
+//! jittered exponential backoff when the start fails or probes keep failing.
+//!
+//! Every account's [`AccountHealth`] is kept in [`ChannelHealth`]:
+//!
+//! - `connecting`: starting, or restarting after a failure.
+//! - `connected`: started, and the last probe passed.
+//! - `degraded`: a probe failed; `reason` says why.
+//! - `error`: the start failed or probes kept failing; a restart is due at
+//!   `nextRetryAt`.
+//! - `stopped`: disabled, logged out or shut down.
+//!
+//! Along with last inbound and outbound message times and the number of
+//! restarts since the account was last healthy. State changes are published
+//! as [`HealthEvent`]s, which the gateway forwards to clients as
+//! `channels.health` events.
+
+use super::plugin::ChannelPlugin;
+use crate::config::OutboundRetryConfig;
+use crate::infra::abort_signal::{monitor_with_abort_lifecycle, AbortHandle};
+use crate::infra::delivery::retry_delay_ms;
+
+use anyhow::Result;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{debug, info, warn};
+
+/// How often a connected account is probed.
+const PROBE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Consecutive failed probes after which an account is restarted.
+const PROBE_FAILURES_BEFORE_RESTART: u32 = 3;
+
+/// Health events buffered per subscriber.
+const HEALTH_EVENT_CAPACITY: usize = 64;
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// Lifecycle state of one channel account.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountState {
+    Connecting,
+    Connected,
+    Degraded,
+    #[default]
+    Stopped,
+    Error,
+}
+
+/// Health of one channel account, as reported by `channels.status`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountHealth {
+    pub state: AccountState,
+    /// Why the account is degraded or failed.
+    pub reason: Option<String>,
+    /// When the account entered its current state (ms since epoch).
+    pub since: Option<u64>,
+    pub last_inbound_at: Option<u64>,
+    pub last_outbound_at: Option<u64>,
+    /// Restarts since the account was last healthy.
+    pub reconnect_attempts: u32,
+    /// When the next restart is due, while in `error` (ms since epoch).
+    pub next_retry_at: Option<u64>,
+}
+
+/// A change of an account's state or reason.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthEvent {
+    pub channel: String,
+    pub account_id: String,
+    #[serde(flatten)]
+    pub health: AccountHealth,
+}
+
+/// Health of every channel account, keyed by `(channel, account id)`.
+pub struct ChannelHealth {
+    accounts: Mutex<HashMap<(String, String), AccountHealth>>,
+    events: broadcast::Sender<HealthEvent>,
+}
+
+impl ChannelHealth {
+    pub fn new() -> Self {
+        Self {
+            accounts: Mutex::new(HashMap::new()),
+            events: broadcast::channel(HEALTH_EVENT_CAPACITY).0,
+        }
+    }
+
+    /// Receive every state change from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<HealthEvent> {
+        self.events.subscribe()
+    }
+
+    /// Current health of an account; accounts never started are `stopped`.
+    pub fn get(&self, channel: &str, account_id: &str) -> AccountHealth {
+        self.accounts
+            .lock()
+            .get(&(channel.to_string(), account_id.to_string()))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Note a message received on an account.
+    pub fn record_inbound(&self, channel: &str, account_id: &str) {
+        self.update(channel, account_id, |h| h.last_inbound_at = Some(now_ms()));
+    }
+
+    /// Note a message delivered from an account.
+    pub fn record_outbound(&self, channel: &str, account_id: &str) {
+        self.update(channel, account_id, |h| h.last_outbound_at = Some(now_ms()));
+    }
+
+    fn connecting(&self, channel: &str, account_id: &str) {
+        self.update(channel, account_id, |h| {
+            h.state = AccountState::Connecting;
+            h.reason = None;
+            h.next_retry_at = None;
+        });
+    }
+
+    fn connected(&self, channel: &str, account_id: &str) {
+        self.update(channel, account_id, |h| {
+            h.state = AccountState::Connected;
+            h.reason = None;
+        });
+    }
+
+    /// Connected and probed successfully: restarts no longer count.
+    fn healthy(&self, channel: &str, account_id: &str) {
+        self.update(channel, account_id, |h| {
+            h.state = AccountState::Connected;
+            h.reason = None;
+            h.reconnect_attempts = 0;
+        });
+    }
+
+    fn degraded(&self, channel: &str, account_id: &str, reason: String) {
+        self.update(channel, account_id, |h| {
+            h.state = AccountState::Degraded;
+            h.reason = Some(reason);
+        });
+    }
+
+    fn failed(
+        &self,
+        channel: &str,
+        account_id: &str,
+        reason: String,
+        attempts: u32,
+        retry_at: u64,
+    ) {
+        self.update(channel, account_id, |h| {
+            h.state = AccountState::Error;
+            h.reason = Some(reason);
+            h.reconnect_attempts = attempts;
+            h.next_retry_at = Some(retry_at);
+        });
+    }
+
+    pub(super) fn stopped(&self, channel: &str, account_id: &str) {
+        self.update(channel, account_id, |h| {
+            h.state = AccountState::Stopped;
+            h.reason = None;
+            h.reconnect_attempts = 0;
+            h.next_retry_at = None;
+        });
+    }
+
+    /// Apply `change` to an account, publishing an event when its state or
+    /// reason changed.
+    fn update(&self, channel: &str, account_id: &str, change: impl FnOnce(&mut AccountHealth)) {
+        let event = {
+            let mut accounts = self.accounts.lock();
+            let health = accounts
+                .entry((channel.to_string(), account_id.to_string()))
+                .or_default();
+            let before = (health.state, health.reason.clone());
+            change(health);
+            if health.state != before.0 {
+                health.since = Some(now_ms());
+            }
+            (before != (health.state, health.reason.clone())).then(|| HealthEvent {
+                channel: channel.to_string(),
+                account_id: account_id.to_string(),
+                health: health.clone(),
+            })
+        };
+        if let Some(event) = event {
+            debug!(channel, account = account_id, state = ?event.health.state, "Channel account health changed");
+            let _ = self.events.send(event);
+        }
+    }
+}
+
+impl Default for ChannelHealth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ============================================================================
+// Supervisor
+// ============================================================================
+
+/// How often a supervisor probes and how it backs off between restarts.
+#[derive(Debug, Clone)]
+pub(super) struct SupervisorTiming {
+    pub probe_every: Duration,
+    pub restart: OutboundRetryConfig,
+}
+
+impl Default for SupervisorTiming {
+    fn default() -> Self {
+        Self {
+            probe_every: PROBE_INTERVAL,
+            restart: OutboundRetryConfig {
+                attempts: u32::MAX,
+                min_delay_ms: 2_000,
+                max_delay_ms: 300_000,
+                jitter: Some(0.1),
+            },
+        }
+    }
+}
+
+/// The task keeping one channel account running.
+pub(super) struct Supervisor {
+    abort: AbortHandle,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Supervisor {
+    /// Supervise `plugin`, starting it with `start`.
+    pub fn spawn<S, F>(
+        plugin: Arc<dyn ChannelPlugin>,
+        health: Arc<ChannelHealth>,
+        timing: SupervisorTiming,
+        start: S,
+    ) -> Self
+    where
+        S: FnMut() -> F + Send + 'static,
+        F: Future<Output = Result<()>> + Send + 'static,
+    {
+        let abort = AbortHandle::new();
+        let task = tokio::spawn(supervise(plugin, health, timing, abort.clone(), start));
+        Self { abort, task }
+    }
+
+    /// Stop supervising. The account itself is left as it is.
+    pub async fn stop(self) {
+        self.abort.abort();
+        let _ = self.task.await;
+    }
+}
+
+async fn supervise<S, F>(
+    plugin: Arc<dyn ChannelPlugin>,
+    health: Arc<ChannelHealth>,
+    timing: SupervisorTiming,
+    abort: AbortHandle,
+    mut start: S,
+) where
+    S: FnMut() -> F,
+    F: Future<Output = Result<()>>,
+{
+    let channel = plugin.id().to_string();
+    let account_id = plugin.account_id().to_string();
+    let mut attempts = 0;
+
+    loop {
+        health.connecting(&channel, &account_id);
+        let failure = match monitor_with_abort_lifecycle(start(), &abort).await {
+            Err(_) => break,
+            Ok(Err(e)) => format!("{e:#}"),
+            Ok(Ok(())) => {
+                health.connected(&channel, &account_id);
+                let watching = watch(&*plugin, &health, timing.probe_every, &mut attempts);
+                match monitor_with_abort_lifecycle(watching, &abort).await {
+                    Err(_) => break,
+                    Ok(reason) => {
+                        if let Err(e) = plugin.stop_account().await {
+                            debug!(channel = %channel, account = %account_id, error = %e, "Stopping failed account");
+                        }
+                        reason
+                    }
+                }
+            }
+        };
+
+        attempts += 1;
+        let delay = retry_delay_ms(&timing.restart, attempts);
+        warn!(
+            channel = %channel,
+            account = %account_id,
+            attempts,
+            delay_ms = delay,
+            error = %failure,
+            "Channel account failed; restarting"
+        );
+        health.failed(&channel, &account_id, failure, attempts, now_ms() + delay);
+        let backoff = tokio::time::sleep(Duration::from_millis(delay));
+        if monitor_with_abort_lifecycle(backoff, &abort).await.is_err() {
+            break;
+        }
+        info!(channel = %channel, account = %account_id, attempts, "Restarting channel account");
+    }
+}
+
+/// Probe a started account until probes keep failing; returns the last
+/// probe error. A passing probe resets `attempts`.
+async fn watch(
+    plugin: &dyn ChannelPlugin,
+    health: &ChannelHealth,
+    every: Duration,
+    attempts: &mut u32,
+) -> String {
+    let (channel, account_id) = (plugin.id(), plugin.account_id());
+    let mut failures = 0;
+    loop {
+        tokio::time::sleep(every).await;
+        match plugin.probe().await {
+            Ok(()) => {
+                failures = 0;
+                *attempts = 0;
+                health.healthy(channel, account_id);
+            }
+            Err(e) => {
+                failures += 1;
+                let reason = format!("{e:#}");
+                if failures >= PROBE_FAILURES_BEFORE_RESTART {
+                    return reason;
+                }
+                health.degraded(channel, account_id, reason);
+            }
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::plugin::{ChannelCapability, ChannelMeta};
+    use crate::gateway::GatewayState;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+    /// Fails to start `failing_starts` times; probes fail while `broken`.
+    #[derive(Default)]
+    struct FakeChannel {
+        failing_starts: AtomicU32,
+        starts: AtomicU32,
+        broken: AtomicBool,
+    }
+
+    impl FakeChannel {
+        fn start(&self) -> Result<()> {
+            self.starts.fetch_add(1, Ordering::SeqCst);
+            let failing = self.failing_starts.load(Ordering::SeqCst);
+            if failing > 0 {
+                self.failing_starts.store(failing - 1, Ordering::SeqCst);
+                anyhow::bail!("token rejected");
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl ChannelPlugin for FakeChannel {
+        fn id(&self) -> &str {
+            "fake"
+        }
+
+        fn meta(&self) -> ChannelMeta {
+            ChannelMeta {
+                name: "Fake".to_string(),
+                description: "Test channel".to_string(),
+                enabled: true,
+                multi_account: false,
+            }
+        }
+
+        fn capabilities(&self) -> Vec<ChannelCapability> {
+            Vec::new()
+        }
+
+        async fn start_account(&self, _state: &GatewayState) -> Result<()> {
+            self.start()
+        }
+
+        async fn stop_account(&self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn probe(&self) -> Result<()> {
+            if self.broken.load(Ordering::SeqCst) {
+                anyhow::bail!("socket closed");
+            }
+            Ok(())
+        }
+
+        async fn send_message(&self, _to: &str, _message: &str) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn fast() -> SupervisorTiming {
+        SupervisorTiming {
+            probe_every: Duration::from_millis(5),
+            restart: OutboundRetryConfig {
+                attempts: u32::MAX,
+                min_delay_ms: 5,
+                max_delay_ms: 5,
+                jitter: Some(0.0),
+            },
+        }
+    }
+
+    fn spawn(fake: &Arc<FakeChannel>, health: &Arc<ChannelHealth>) -> Supervisor {
+        let plugin = fake.clone();
+        Supervisor::spawn(fake.clone(), health.clone(), fast(), move || {
+            let result = plugin.start();
+            async move { result }
+        })
+    }
+
+    async fn wait_for(health: &ChannelHealth, check: impl Fn(&AccountHealth) -> bool) {
+        for _ in 0..200 {
+            if check(&health.get("fake", "default")) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        panic!("health never matched: {:?}", health.get("fake", "default"));
+    }
+
+    #[tokio::test]
+    async fn restarts_after_failed_start() {
+        let fake = Arc::new(FakeChannel::default());
+        fake.failing_starts.store(2, Ordering::SeqCst);
+        let health = Arc::new(ChannelHealth::new());
+        let mut events = health.subscribe();
+
+        let supervisor = spawn(&fake, &health);
+        wait_for(&health, |h| h.state == AccountState::Connected).await;
+        assert_eq!(fake.starts.load(Ordering::SeqCst), 3);
+
+        let first_error = loop {
+            let event = events.recv().await.unwrap();
+            if event.health.state == AccountState::Error {
+                break event;
+            }
+        };
+        assert_eq!(first_error.health.reason.as_deref(), Some("token rejected"));
+        assert_eq!(first_error.health.reconnect_attempts, 1);
+        assert!(first_error.health.next_retry_at.is_some());
+
+        // A passing probe clears the restart count.
+        wait_for(&health, |h| h.reconnect_attempts == 0).await;
+        supervisor.stop().await;
+    }
+
+    #[tokio::test]
+    async fn failing_probes_degrade_then_restart() {
+        let fake = Arc::new(FakeChannel::default());
+        let health = Arc::new(ChannelHealth::new());
+        let supervisor = spawn(&fake, &health);
+        wait_for(&health, |h| h.state == AccountState::Connected).await;
+
+        let mut events = health.subscribe();
+        fake.broken.store(true, Ordering::SeqCst);
+        let states: Vec<AccountState> =
+            [events.recv().await.unwrap(), events.recv().await.unwrap()]
+                .into_iter()
+                .map(|e| e.health.state)
+                .collect();
+        assert_eq!(states, [AccountState::Degraded, AccountState::Error]);
+
+        fake.broken.store(false, Ordering::SeqCst);
+        wait_for(&health, |h| h.state == AccountState::Connected).await;
+        assert!(fake.starts.load(Ordering::SeqCst) >= 2);
+        supervisor.stop().await;
+    }
+
+    #[test]
+    fn activity_does_not_emit_events() {
+        let health = ChannelHealth::new();
+        let mut events = health.subscribe();
+        health.record_inbound("fake", "default");
+        health.record_outbound("fake", "default");
+        assert!(events.try_recv().is_err());
+
+        let status = health.get("fake", "default");
+        assert_eq!(status.state, AccountState::Stopped);
+        assert!(status.last_inbound_at.is_some() && status.last_outbound_at.is_some());
+
+        health.stopped("fake", "default");
+        assert!(events.try_recv().is_err());
+        health.connecting("fake", "default");
+        assert_eq!(
+            events.try_recv().unwrap().health.state,
+            AccountState::Connecting
+        );
+    }
+}
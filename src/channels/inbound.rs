@@ -0,0 +1,628 @@
+//! Inbound dispatch from channels into agent sessions.
+//!
+//! Channel implementations convert platform events into a
+//! [`NormalizedMessage`] and hand it to [`dispatch_inbound`], which admits
+//! the sender (see [`super::admission`]), resolves the session, runs an agent
+//! turn and returns the final reply text. In-chat commands (`/new`,
+//! `/model`, ...) are answered by [`super::commands`] without a turn. Group
+//! turns carry the unaddressed messages buffered since the last one (see
+//! [`super::group_history`]); DM turns send at most `messages.dm.historyLimit`
+//! earlier user turns. Delivery of the reply (formatting, splitting,
+//! threading) stays with the channel.
+//!
+//! `messages.messagePrefix` is put in front of the user-turn text and
+//! `messages.responsePrefix` in front of agent replies (`auto` uses the
+//! agent's identity name). While the run is in progress the triggering
+//! message carries an ack reaction (see [`super::ack`]). Voice notes are
+//! transcribed into the turn text (see [`super::voice`]).
+//!
+//! Every dispatched message, and platform events that do not start a turn
+//! (edits, deletions, reactions), are also published on the gateway's
+//! [`InboundSink`] so tools and streaming can see what arrived where.
+
+use super::ack;
+use super::admission::Verdict;
+use super::commands;
+use super::group_history::{self, default_agent};
+use super::normalize::{ChatType, NormalizedMessage, NormalizedSender};
+use super::voice;
+use crate::config::{AgentBinding, Config, DmScope, SessionScope};
+use crate::gateway::{process_chat, ChatEvent, ChatEventState, ChatSendParams, GatewayState};
+use crate::routing::{resolve_agent_for_session, RoutingContext};
+use crate::sessions::TurnSource;
+
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+use tokio::sync::{broadcast, mpsc};
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+
+/// Default session key for DMs when `session.mainKey` is not configured.
+const DEFAULT_MAIN_KEY: &str = "default";
+
+/// Resolve the session key for an inbound channel message.
+///
+/// Groups and threads always get their own session; DMs follow
+/// `session.dmScope`. A global session scope routes everything to the main key.
+pub fn resolve_session_key(config: &Config, msg: &NormalizedMessage) -> String {
+    let main_key = || {
+        config
+            .session
+            .main_key
+            .clone()
+            .unwrap_or_else(|| DEFAULT_MAIN_KEY.to_string())
+    };
+
+    if config.session.scope == SessionScope::Global {
+        return main_key();
+    }
+
+    match msg.chat_type {
+        ChatType::Dm => match config.session.dm_scope.unwrap_or_default() {
+            DmScope::Main => main_key(),
+            DmScope::PerPeer => format!("dm:{}", msg.sender.id),
+            DmScope::PerChannelPeer => format!("{}:dm:{}", msg.channel, msg.sender.id),
+            DmScope::PerAccountChannelPeer => {
+                format!("{}:{}:dm:{}", msg.channel, msg.account_id, msg.sender.id)
+            }
+        },
+        ChatType::Group => format!("{}:group:{}", msg.channel, msg.chat_id),
+        ChatType::Thread => match &msg.thread_id {
+            Some(thread) => format!("{}:group:{}:thread:{}", msg.channel, msg.chat_id, thread),
+            None => format!("{}:group:{}", msg.channel, msg.chat_id),
+        },
+    }
+}
+
+/// Session key for `msg` with agent routing applied.
+///
+/// When one of `bindings` matches the message's channel, account and chat,
+/// the session belongs to the bound agent and its key is prefixed with
+/// `agent:<id>:`. Unbound messages keep the plain [`resolve_session_key`].
+pub fn routed_session_key(
+    config: &Config,
+    bindings: &[AgentBinding],
+    msg: &NormalizedMessage,
+) -> String {
+    let key = resolve_session_key(config, msg);
+    let context = RoutingContext {
+        channel: Some(msg.channel.clone()),
+        account_id: Some(msg.account_id.clone()),
+        peer: Some(msg.chat_id.clone()),
+        thread_id: msg.thread_id.clone(),
+        session_key: Some(key.clone()),
+    };
+    let default_id = default_agent(config).map_or("default", |a| a.id.as_str());
+    let route = resolve_agent_for_session(bindings, &context, default_id);
+    if route.is_default || route.agent_id == default_id {
+        key
+    } else {
+        format!("agent:{}:{key}", route.agent_id)
+    }
+}
+
+/// Build the user-turn text for an inbound message.
+///
+/// Group messages are prefixed with the sender's name so the agent can tell
+/// participants apart.
+fn inbound_text(msg: &NormalizedMessage) -> String {
+    match msg.chat_type {
+        ChatType::Dm => msg.text.clone(),
+        ChatType::Group | ChatType::Thread => format!("{}: {}", msg.sender.name, msg.text),
+    }
+}
+
+/// Put `prefix` in front of `text`, unless it is already there.
+fn with_prefix(prefix: Option<&str>, text: String) -> String {
+    match prefix.map(str::trim).filter(|p| !p.is_empty()) {
+        Some(prefix) if !text.starts_with(prefix) => format!("{prefix} {text}"),
+        _ => text,
+    }
+}
+
+/// Reply prefix from `messages.responsePrefix`; `auto` becomes the default
+/// agent's identity name in brackets.
+fn response_prefix(config: &Config) -> Option<String> {
+    match config.messages.response_prefix.as_deref()?.trim() {
+        "auto" => default_agent(config)
+            .and_then(|a| a.identity.as_ref())
+            .and_then(|i| i.name.as_deref())
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| format!("[{name}]")),
+        prefix => Some(prefix.to_string()),
+    }
+}
+
+/// Typing indicator interval for channels, from `session.typingIntervalSeconds`.
+pub fn typing_interval_ms(config: &Config, default_ms: u64) -> u64 {
+    config
+        .session
+        .typing_interval_seconds
+        .map(|s| s.max(1) * 1000)
+        .unwrap_or(default_ms)
+}
+
+/// Run an agent turn for an inbound channel message.
+///
+/// Returns the final assistant text, or `None` if the run produced no text
+/// (empty reply or aborted).
+pub async fn dispatch_inbound(
+    state: &GatewayState,
+    msg: &NormalizedMessage,
+) -> Result<Option<String>> {
+    let config = state.config.read().await.clone();
+    let session_key = session_key_for(state, &config, msg).await;
+    dispatch_inbound_to_session(state, session_key, msg).await
+}
+
+/// The session `msg` belongs to, routed through the configured agent
+/// bindings and the routes added at runtime.
+pub async fn session_key_for(
+    state: &GatewayState,
+    config: &Config,
+    msg: &NormalizedMessage,
+) -> String {
+    let mut bindings = config.agents.bindings.clone();
+    bindings.extend(state.rpc.route_manager.read().await.to_bindings().await);
+    routed_session_key(config, &bindings, msg)
+}
+
+/// Run an agent turn for an inbound message in an explicit session.
+///
+/// Used by entry points that key sessions differently from
+/// [`resolve_session_key`], such as slash commands.
+pub async fn dispatch_inbound_to_session(
+    state: &GatewayState,
+    session_key: String,
+    msg: &NormalizedMessage,
+) -> Result<Option<String>> {
+    state
+        .channels
+        .health()
+        .record_inbound(&msg.channel, &msg.account_id);
+    let config = state.config.read().await.clone();
+    match state.admission.check(&config, msg) {
+        Verdict::Admit => {}
+        Verdict::Reply(notice) => return Ok(Some(notice)),
+        Verdict::Drop => return Ok(None),
+    }
+    if config.commands.text_enabled() {
+        if let Some(reply) = commands::run(state, &config, &session_key, msg) {
+            return Ok(Some(reply));
+        }
+    }
+    let transcribed = voice::transcribe_inbound(state, &config, msg).await;
+    let msg = transcribed.as_ref().unwrap_or(msg);
+    state.inbound.push(InboundEvent::Message(msg.clone()));
+
+    let session = state.sessions.get_or_create_session(&session_key, &config);
+    session.set_turn_source(TurnSource {
+        channel: Some(msg.channel.clone()),
+        to: Some(msg.chat_id.clone()),
+        account_id: Some(msg.account_id.clone()),
+        thread_id: msg.thread_id.clone(),
+    });
+
+    debug!(
+        channel = %msg.channel,
+        session = %session_key,
+        "Dispatching inbound channel message"
+    );
+
+    let (context, history_limit) = match msg.chat_type {
+        ChatType::Dm => (
+            Vec::new(),
+            config.messages.dm.as_ref().and_then(|dm| dm.history_limit),
+        ),
+        ChatType::Group | ChatType::Thread => (state.group_history.take(msg), None),
+    };
+
+    let params = ChatSendParams {
+        session_key,
+        message: group_history::with_context(
+            &context,
+            with_prefix(config.messages.message_prefix.as_deref(), inbound_text(msg)),
+        ),
+        thinking: None,
+        deliver: None,
+        attachments: None,
+        timeout_ms: None,
+        idempotency_key: None,
+        best_effort_deliver: None,
+        resume_session_id: None,
+        history_limit,
+    };
+
+    let (tx, mut rx) = mpsc::channel::<ChatEvent>(64);
+    let collect = async move {
+        let mut reply = None;
+        let mut error = None;
+        while let Some(event) = rx.recv().await {
+            match event.state {
+                ChatEventState::Final => {
+                    reply = event
+                        .message
+                        .as_ref()
+                        .and_then(|m| m["content"][0]["text"].as_str())
+                        .map(str::to_string);
+                }
+                ChatEventState::Error => error = event.error_message,
+                ChatEventState::Delta | ChatEventState::Aborted => {}
+            }
+        }
+        (reply, error)
+    };
+
+    let ack = ack::acknowledge(state, &config, msg).await;
+    let run_guard = state.runs.start(&params.session_key);
+    let (run, (reply, error)) = tokio::join!(
+        process_chat(&config, &state.sessions, &params, tx, run_guard.token()),
+        collect
+    );
+    drop(run_guard);
+    if let Some(ack) = ack {
+        ack.finish(state, &config).await;
+    }
+    run?;
+
+    if let Some(error) = error {
+        bail!("agent run failed: {error}");
+    }
+    Ok(reply
+        .filter(|text| !text.trim().is_empty())
+        .map(|text| with_prefix(response_prefix(&config).as_deref(), text)))
+}
+
+// ============================================================================
+// Inbound Sink
+// ============================================================================
+
+/// An event received from a channel.
+#[derive(Debug, Clone)]
+pub enum InboundEvent {
+    /// A new message, published as it is dispatched to the agent.
+    Message(NormalizedMessage),
+    /// A message was edited; carries the new content.
+    Edited(NormalizedMessage),
+    /// A message was deleted.
+    Deleted {
+        channel: String,
+        chat_id: String,
+        message_id: String,
+    },
+    /// A reaction was added, or removed when `emoji` is empty.
+    Reaction {
+        channel: String,
+        chat_id: String,
+        message_id: String,
+        sender: NormalizedSender,
+        emoji: String,
+    },
+}
+
+impl InboundEvent {
+    /// Channel the event came from.
+    pub fn channel(&self) -> &str {
+        match self {
+            Self::Message(msg) | Self::Edited(msg) => &msg.channel,
+            Self::Deleted { channel, .. } | Self::Reaction { channel, .. } => channel,
+        }
+    }
+
+    /// Chat the event belongs to.
+    pub fn chat_id(&self) -> &str {
+        match self {
+            Self::Message(msg) | Self::Edited(msg) => &msg.chat_id,
+            Self::Deleted { chat_id, .. } | Self::Reaction { chat_id, .. } => chat_id,
+        }
+    }
+}
+
+/// Number of events buffered for slow subscribers.
+const INBOUND_EVENT_CAPACITY: usize = 256;
+/// Chats whose latest message is remembered.
+const MAX_RECENT_CHATS: usize = 1024;
+
+/// Gateway-wide stream of inbound channel events.
+///
+/// Channels push events here; subscribers (block streaming, message tools)
+/// receive every event published after they subscribe. The latest message
+/// per chat is kept so a reply can target it (react, quote, mark read)
+/// without threading ids through the agent.
+pub struct InboundSink {
+    tx: broadcast::Sender<InboundEvent>,
+    recent: parking_lot::Mutex<HashMap<(String, String), (Instant, NormalizedMessage)>>,
+}
+
+impl InboundSink {
+    pub fn new() -> Self {
+        Self {
+            tx: broadcast::channel(INBOUND_EVENT_CAPACITY).0,
+            recent: parking_lot::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Publish an event to all current subscribers.
+    pub fn push(&self, event: InboundEvent) {
+        match &event {
+            InboundEvent::Message(msg) | InboundEvent::Edited(msg) => {
+                let mut recent = self.recent.lock();
+                let key = (msg.channel.clone(), msg.chat_id.clone());
+                if recent.len() >= MAX_RECENT_CHATS && !recent.contains_key(&key) {
+                    let oldest = recent
+                        .iter()
+                        .min_by_key(|(_, (at, _))| *at)
+                        .map(|(key, _)| key.clone());
+                    if let Some(oldest) = oldest {
+                        recent.remove(&oldest);
+                    }
+                }
+                recent.insert(key, (Instant::now(), msg.clone()));
+            }
+            InboundEvent::Deleted {
+                channel,
+                chat_id,
+                message_id,
+            } => {
+                let mut recent = self.recent.lock();
+                let key = (channel.clone(), chat_id.clone());
+                if recent
+                    .get(&key)
+                    .is_some_and(|(_, msg)| &msg.id == message_id)
+                {
+                    recent.remove(&key);
+                }
+            }
+            InboundEvent::Reaction { .. } => {}
+        }
+        // No subscribers is fine.
+        let _ = self.tx.send(event);
+    }
+
+    /// Receive events published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<InboundEvent> {
+        self.tx.subscribe()
+    }
+
+    /// The latest message seen in a chat.
+    pub fn last_message(&self, channel: &str, chat_id: &str) -> Option<NormalizedMessage> {
+        self.recent
+            .lock()
+            .get(&(channel.to_string(), chat_id.to_string()))
+            .map(|(_, msg)| msg.clone())
+    }
+}
+
+impl Default for InboundSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ============================================================================
+// Active Runs
+// ============================================================================
+
+/// Agent runs started by channel messages, by session key.
+///
+/// `/stop` cancels every run of a session through [`cancel`](Self::cancel).
+#[derive(Default)]
+pub struct ActiveRuns {
+    runs: parking_lot::Mutex<HashMap<String, HashMap<u64, CancellationToken>>>,
+    next_id: AtomicU64,
+}
+
+impl ActiveRuns {
+    /// Register a run; it stays registered until the guard is dropped.
+    pub fn start(&self, session_key: &str) -> RunGuard<'_> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let token = CancellationToken::new();
+        self.runs
+            .lock()
+            .entry(session_key.to_string())
+            .or_default()
+            .insert(id, token.clone());
+        RunGuard {
+            runs: self,
+            session_key: session_key.to_string(),
+            id,
+            token,
+        }
+    }
+
+    /// Cancel every run of a session; returns how many were running.
+    pub fn cancel(&self, session_key: &str) -> usize {
+        let runs = self.runs.lock().remove(session_key).unwrap_or_default();
+        for token in runs.values() {
+            token.cancel();
+        }
+        runs.len()
+    }
+
+    /// Number of runs in progress for a session.
+    pub fn running(&self, session_key: &str) -> usize {
+        self.runs.lock().get(session_key).map_or(0, HashMap::len)
+    }
+}
+
+/// A registered run; unregisters it when dropped.
+pub struct RunGuard<'a> {
+    runs: &'a ActiveRuns,
+    session_key: String,
+    id: u64,
+    token: CancellationToken,
+}
+
+impl RunGuard<'_> {
+    /// Token cancelled by [`ActiveRuns::cancel`].
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+}
+
+impl Drop for RunGuard<'_> {
+    fn drop(&mut self) {
+        let mut runs = self.runs.runs.lock();
+        if let Some(session) = runs.get_mut(&self.session_key) {
+            session.remove(&self.id);
+            if session.is_empty() {
+                runs.remove(&self.session_key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::NormalizedSender;
+
+    fn message(chat_type: ChatType) -> NormalizedMessage {
+        NormalizedMessage {
+            id: "1".to_string(),
+            channel: "telegram".to_string(),
+            account_id: "default".to_string(),
+            chat_id: "-100".to_string(),
+            chat_name: None,
+            chat_type,
+            sender: NormalizedSender {
+                id: "42".to_string(),
+                name: "Ann".to_string(),
+                is_bot: false,
+                roles: Vec::new(),
+            },
+            text: "hi".to_string(),
+            attachments: Vec::new(),
+            reply_to_id: None,
+            thread_id: Some("7".to_string()),
+            mentioned: false,
+            timestamp: String::new(),
+            raw: None,
+        }
+    }
+
+    #[test]
+    fn bindings_route_accounts_to_agents() {
+        let config = Config::default();
+        let bindings = vec![AgentBinding {
+            agent_id: "support".to_string(),
+            match_rule: crate::config::AgentBindingMatch {
+                channel: Some("telegram".to_string()),
+                account_id: Some("work".to_string()),
+                ..Default::default()
+            },
+        }];
+        let mut msg = message(ChatType::Group);
+        assert_eq!(
+            routed_session_key(&config, &bindings, &msg),
+            "telegram:group:-100"
+        );
+        msg.account_id = "work".to_string();
+        assert_eq!(
+            routed_session_key(&config, &bindings, &msg),
+            "agent:support:telegram:group:-100"
+        );
+    }
+
+    #[test]
+    fn session_key_for_groups_and_threads() {
+        let config = Config::default();
+        assert_eq!(
+            resolve_session_key(&config, &message(ChatType::Group)),
+            "telegram:group:-100"
+        );
+        assert_eq!(
+            resolve_session_key(&config, &message(ChatType::Thread)),
+            "telegram:group:-100:thread:7"
+        );
+    }
+
+    #[test]
+    fn session_key_for_dms_follows_dm_scope() {
+        let mut config = Config::default();
+        let dm = message(ChatType::Dm);
+        assert_eq!(resolve_session_key(&config, &dm), "default");
+
+        config.session.dm_scope = Some(DmScope::PerChannelPeer);
+        assert_eq!(resolve_session_key(&config, &dm), "telegram:dm:42");
+
+        config.session.dm_scope = Some(DmScope::PerAccountChannelPeer);
+        assert_eq!(resolve_session_key(&config, &dm), "telegram:default:dm:42");
+    }
+
+    #[test]
+    fn group_text_is_labelled_with_sender() {
+        assert_eq!(inbound_text(&message(ChatType::Group)), "Ann: hi");
+        assert_eq!(inbound_text(&message(ChatType::Dm)), "hi");
+    }
+
+    #[test]
+    fn prefixes_are_applied_once() {
+        let mut config = Config::default();
+        assert_eq!(with_prefix(Some("[tg]"), "hi".to_string()), "[tg] hi");
+        assert_eq!(with_prefix(Some("[tg]"), "[tg] hi".to_string()), "[tg] hi");
+        assert_eq!(with_prefix(Some(" "), "hi".to_string()), "hi");
+
+        config.messages.response_prefix = Some("auto".to_string());
+        assert_eq!(response_prefix(&config), None);
+        config.agents.list = vec![crate::config::AgentEntry {
+            id: "main".to_string(),
+            identity: Some(crate::config::IdentityConfig {
+                name: Some("Lobster".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }];
+        assert_eq!(response_prefix(&config).as_deref(), Some("[Lobster]"));
+        config.messages.response_prefix = Some("🤖".to_string());
+        assert_eq!(response_prefix(&config).as_deref(), Some("🤖"));
+    }
+
+    #[test]
+    fn sink_publishes_events_and_tracks_the_latest_message() {
+        let sink = InboundSink::new();
+        let mut rx = sink.subscribe();
+
+        sink.push(InboundEvent::Message(message(ChatType::Group)));
+        let mut edited = message(ChatType::Group);
+        edited.text = "hi there".to_string();
+        sink.push(InboundEvent::Edited(edited));
+
+        assert!(matches!(rx.try_recv(), Ok(InboundEvent::Message(_))));
+        let event = rx.try_recv().unwrap();
+        assert_eq!((event.channel(), event.chat_id()), ("telegram", "-100"));
+        assert_eq!(
+            sink.last_message("telegram", "-100").unwrap().text,
+            "hi there"
+        );
+
+        sink.push(InboundEvent::Deleted {
+            channel: "telegram".to_string(),
+            chat_id: "-100".to_string(),
+            message_id: "1".to_string(),
+        });
+        assert!(sink.last_message("telegram", "-100").is_none());
+    }
+
+    #[test]
+    fn active_runs_cancel_and_unregister() {
+        let runs = ActiveRuns::default();
+        let first = runs.start("a");
+        let second = runs.start("a");
+        let other = runs.start("b");
+        assert_eq!(runs.running("a"), 2);
+
+        drop(second);
+        assert_eq!(runs.running("a"), 1);
+
+        assert_eq!(runs.cancel("a"), 1);
+        assert!(first.token().is_cancelled());
+        assert!(!other.token().is_cancelled());
+        assert_eq!(runs.running("a"), 0);
+        drop(first);
+        assert_eq!(runs.cancel("a"), 0);
+    }
+}
@@ -1,69 +1,529 @@
+use super::format::{render_markdown, FormatTarget};
+use super::group_history::{admissible, admit_group_message};
+use super::inbound::dispatch_inbound;
+use super::normalize::{ChatType, NormalizedMessage, NormalizedOutbound, NormalizedSender};
 use super::plugin::{ChannelCapability, ChannelMeta, ChannelPlugin};
+use crate::config::{Config, IrcConfig};
 use crate::gateway::GatewayState;
+use crate::infra::abort_signal::{monitor_with_abort_lifecycle, AbortHandle};
 
-use anyhow::Result;
+use anyhow::{bail, Context as _, Result};
 use async_trait::async_trait;
+use base64::Engine as _;
+use parking_lot::{Mutex, RwLock};
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tracing::{info, warn};
+use std::time::{Duration, Instant};
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
 
 // ============================================================================
 // IRC Channel Implementation
 // ============================================================================
 
+const DEFAULT_PORT: u16 = 6667;
+const DEFAULT_TLS_PORT: u16 = 6697;
+const DEFAULT_NICK: &str = "mylobster";
+/// Maximum IRC line length in bytes, including the trailing CRLF.
+const MAX_LINE_BYTES: usize = 512;
+/// Longest inbound line accepted, IRCv3 message tags included.
+pub(super) const MAX_INBOUND_LINE_BYTES: usize = 8191;
+/// Room left for the `:nick!user@host ` prefix the server adds when relaying
+/// our messages (user up to 10 bytes, host up to 63), excluding the nick.
+const HOSTMASK_RESERVE_BYTES: usize = 77;
+const DEFAULT_FLOOD_BURST: u32 = 4;
+const DEFAULT_FLOOD_INTERVAL_MS: u64 = 1000;
+pub(super) const RECONNECT_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+pub(super) const RECONNECT_MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// A connection that stayed up this long resets the reconnect backoff.
+pub(super) const RECONNECT_RESET_AFTER: Duration = Duration::from_secs(60);
+/// Servers PING every few minutes; silence beyond this means a dead link.
+pub(super) const READ_TIMEOUT: Duration = Duration::from_secs(300);
+/// How long `stop_account` waits for the QUIT to flush.
+pub(super) const QUIT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Byte stream an IRC session runs over (plain TCP or TLS).
+pub(super) trait IrcStream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> IrcStream for T {}
+
+/// A parsed IRC protocol line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct IrcMessage {
+    /// IRCv3 message tags, unescaped.
+    pub(super) tags: HashMap<String, String>,
+    pub(super) prefix: Option<String>,
+    pub(super) command: String,
+    pub(super) params: Vec<String>,
+}
+
+impl IrcMessage {
+    /// Parse a raw line, including any IRCv3 message tags.
+    pub(super) fn parse(line: &str) -> Option<Self> {
+        let mut rest = line.trim_end_matches(['\r', '\n']);
+        let mut tags = HashMap::new();
+        if let Some(tagged) = rest.strip_prefix('@') {
+            let (raw_tags, tail) = tagged.split_once(' ')?;
+            rest = tail;
+            for tag in raw_tags.split(';').filter(|t| !t.is_empty()) {
+                let (key, value) = tag.split_once('=').unwrap_or((tag, ""));
+                tags.insert(key.to_string(), unescape_tag_value(value));
+            }
+        }
+        let prefix = match rest.strip_prefix(':') {
+            Some(prefixed) => {
+                let (prefix, tail) = prefixed.split_once(' ')?;
+                rest = tail;
+                Some(prefix.to_string())
+            }
+            None => None,
+        };
+        let (head, trailing) = match rest.split_once(" :") {
+            Some((head, trailing)) => (head, Some(trailing)),
+            None => (rest, None),
+        };
+        let mut parts = head.split(' ').filter(|p| !p.is_empty());
+        let command = parts.next()?.to_ascii_uppercase();
+        let mut params: Vec<String> = parts.map(str::to_string).collect();
+        if let Some(trailing) = trailing {
+            params.push(trailing.to_string());
+        }
+        Some(Self {
+            tags,
+            prefix,
+            command,
+            params,
+        })
+    }
+
+    /// Nick part of the `nick!user@host` prefix.
+    pub(super) fn nick(&self) -> Option<&str> {
+        self.prefix
+            .as_deref()
+            .map(|p| p.split('!').next().unwrap_or(p))
+    }
+
+    pub(super) fn param(&self, index: usize) -> Option<&str> {
+        self.params.get(index).map(String::as_str)
+    }
+
+    /// Value of a message tag; empty values count as absent.
+    pub(super) fn tag(&self, key: &str) -> Option<&str> {
+        self.tags
+            .get(key)
+            .map(String::as_str)
+            .filter(|v| !v.is_empty())
+    }
+}
+
+/// Undo IRCv3 tag value escaping (`\:` `\s` `\\` `\r` `\n`).
+fn unescape_tag_value(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            out.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some(':') => out.push(';'),
+            Some('s') => out.push(' '),
+            Some('r') => out.push('\r'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+/// Token bucket limiting outbound message rate.
+pub(super) struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    interval: Duration,
+    last: Instant,
+}
+
+impl TokenBucket {
+    pub(super) fn new(burst: u32, interval: Duration, now: Instant) -> Self {
+        let capacity = f64::from(burst.max(1));
+        Self {
+            capacity,
+            tokens: capacity,
+            interval: interval.max(Duration::from_millis(1)),
+            last: now,
+        }
+    }
+
+    /// Take a token, returning how long to wait before sending.
+    pub(super) fn take(&mut self, now: Instant) -> Duration {
+        // Time still owed from earlier waits that have not elapsed yet.
+        let pending = self.last.saturating_duration_since(now);
+        if pending.is_zero() {
+            let elapsed = now.duration_since(self.last);
+            self.tokens = (self.tokens + elapsed.as_secs_f64() / self.interval.as_secs_f64())
+                .min(self.capacity);
+            self.last = now;
+        }
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Duration::ZERO;
+        }
+        let wait = pending + self.interval.mul_f64(1.0 - self.tokens);
+        self.tokens = 0.0;
+        self.last = now + wait;
+        wait
+    }
+}
+
+/// Connection state for the configured IRC network.
+struct IrcClient {
+    config: IrcConfig,
+    /// Current nick; changes on collisions and server-side renames.
+    nick: RwLock<String>,
+    /// Queue into the writer of the active connection.
+    outbound: Mutex<Option<mpsc::UnboundedSender<String>>>,
+    /// Whether the connection is registered (RPL_WELCOME received).
+    connected: AtomicBool,
+    /// Set by `stop_account` so the reconnect loop exits.
+    stopping: AtomicBool,
+}
+
 /// IRC channel integration.
 ///
-/// Connects to an IRC server via raw TCP (optionally TLS) and joins the
-/// configured channels. Uses a simple line-based IRC protocol implementation.
+/// Connects to an IRC server via raw TCP (optionally TLS), registers with
+/// optional SASL PLAIN and NickServ identification, and joins the configured
+/// channels, reconnecting with backoff when the link drops. Channel messages
+/// are only answered when they mention the bot's nick.
 ///
 /// This is a non-REST channel — it requires a persistent TCP connection.
 /// `send_message` will return an error if the connection is not active.
 pub struct IrcChannel {
-    /// IRC server hostname (e.g. `irc.libera.chat`).
-    server: Option<String>,
-    /// IRC server port (default: 6667, or 6697 for TLS).
-    port: Option<u16>,
-    /// Bot nickname.
-    nick: Option<String>,
-    /// List of IRC channels to join (e.g. `["#mylobster", "#general"]`).
-    channels: Option<Vec<String>>,
-    /// Whether this channel is enabled.
-    enabled: Option<bool>,
-    /// Whether the IRC connection is currently active.
-    connected: Arc<AtomicBool>,
+    enabled: bool,
+    client: Arc<IrcClient>,
+    /// Abort handle and task of the connection loop, if running.
+    task: Mutex<Option<(AbortHandle, tokio::task::JoinHandle<()>)>>,
 }
 
 impl IrcChannel {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
+        let config = config.channels.irc.clone().unwrap_or_default();
+        let enabled = config.enabled.unwrap_or(false);
+        let nick = config
+            .nickname
+            .clone()
+            .unwrap_or_else(|| DEFAULT_NICK.to_string());
         Self {
-            server: None,
-            port: None,
-            nick: None,
-            channels: None,
-            enabled: None,
-            connected: Arc::new(AtomicBool::new(false)),
-        }
-    }
-
-    /// Create a configured IRC channel.
-    pub fn with_config(
-        server: String,
-        port: u16,
-        nick: String,
-        channels: Vec<String>,
-    ) -> Self {
-        Self {
-            server: Some(server),
-            port: Some(port),
-            nick: Some(nick),
-            channels: Some(channels),
-            enabled: Some(true),
-            connected: Arc::new(AtomicBool::new(false)),
+            enabled,
+            client: Arc::new(IrcClient {
+                config,
+                nick: RwLock::new(nick),
+                outbound: Mutex::new(None),
+                connected: AtomicBool::new(false),
+                stopping: AtomicBool::new(false),
+            }),
+            task: Mutex::new(None),
         }
     }
+}
+
+impl IrcClient {
+    fn use_tls(&self) -> bool {
+        self.config.tls.unwrap_or(false)
+    }
 
-    fn is_enabled(&self) -> bool {
-        self.enabled.unwrap_or(false)
+    fn port(&self) -> u16 {
+        self.config.port.unwrap_or(if self.use_tls() {
+            DEFAULT_TLS_PORT
+        } else {
+            DEFAULT_PORT
+        })
+    }
+
+    /// Open a TCP or TLS connection to the configured server.
+    async fn connect(&self) -> Result<Box<dyn IrcStream>> {
+        let server = self
+            .config
+            .server
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("IRC server not configured"))?;
+        connect_stream(server, self.port(), self.use_tls()).await
+    }
+
+    /// Connect and run sessions until stopped, reconnecting with backoff.
+    async fn run(self: Arc<Self>, inbound: mpsc::UnboundedSender<NormalizedMessage>) {
+        let mut backoff = RECONNECT_INITIAL_BACKOFF;
+        loop {
+            let started = Instant::now();
+            let result = match self.connect().await {
+                Ok(stream) => self.session(stream, &inbound).await,
+                Err(e) => Err(e),
+            };
+            self.connected.store(false, Ordering::Relaxed);
+            self.outbound.lock().take();
+
+            if self.stopping.load(Ordering::Relaxed) {
+                return;
+            }
+            if started.elapsed() >= RECONNECT_RESET_AFTER {
+                backoff = RECONNECT_INITIAL_BACKOFF;
+            }
+            match result {
+                Ok(()) => info!("IRC connection closed; reconnecting in {:?}", backoff),
+                Err(e) => warn!(error = %e, "IRC connection failed; reconnecting in {:?}", backoff),
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(RECONNECT_MAX_BACKOFF);
+        }
+    }
+
+    /// Register and process one connection until it closes.
+    async fn session(
+        &self,
+        stream: impl IrcStream,
+        inbound: &mpsc::UnboundedSender<NormalizedMessage>,
+    ) -> Result<()> {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+        *self.outbound.lock() = Some(tx.clone());
+
+        let burst = self.config.flood_burst.unwrap_or(DEFAULT_FLOOD_BURST);
+        let interval = Duration::from_millis(
+            self.config
+                .flood_interval_ms
+                .unwrap_or(DEFAULT_FLOOD_INTERVAL_MS),
+        );
+        let write_loop = async move {
+            let mut bucket = TokenBucket::new(burst, interval, Instant::now());
+            while let Some(line) = rx.recv().await {
+                // Protocol replies (PONG, registration) are never delayed.
+                if line.starts_with("PRIVMSG ") || line.starts_with("NOTICE ") {
+                    let wait = bucket.take(Instant::now());
+                    if !wait.is_zero() {
+                        tokio::time::sleep(wait).await;
+                    }
+                }
+                writer.write_all(line.as_bytes()).await?;
+                writer.write_all(b"\r\n").await?;
+                writer.flush().await?;
+            }
+            Ok::<_, std::io::Error>(())
+        };
+
+        self.register(&tx);
+        let read_loop = self.read_loop(reader, &tx, inbound);
+
+        tokio::select! {
+            result = write_loop => result.context("IRC write failed"),
+            result = read_loop => result,
+        }
+    }
+
+    /// Send the registration burst.
+    fn register(&self, tx: &mpsc::UnboundedSender<String>) {
+        let nick = self.nick.read().clone();
+        // Sender identities come from the `account` tag where available.
+        let _ = tx.send("CAP REQ :account-tag".to_string());
+        if self.sasl_credentials().is_some() {
+            let _ = tx.send("CAP REQ :sasl".to_string());
+        }
+        if let Some(password) = &self.config.password {
+            let _ = tx.send(format!("PASS {password}"));
+        }
+        let username = self.config.username.as_deref().unwrap_or(&nick);
+        let realname = self.config.realname.as_deref().unwrap_or("MyLobster");
+        let _ = tx.send(format!("NICK {nick}"));
+        let _ = tx.send(format!("USER {username} 0 * :{realname}"));
+    }
+
+    fn sasl_credentials(&self) -> Option<(String, &str)> {
+        let sasl = self.config.sasl.as_ref()?;
+        let password = sasl.password.as_deref()?;
+        let username = sasl
+            .username
+            .clone()
+            .unwrap_or_else(|| self.nick.read().clone());
+        Some((username, password))
+    }
+
+    async fn read_loop(
+        &self,
+        reader: impl AsyncRead + Unpin,
+        tx: &mpsc::UnboundedSender<String>,
+        inbound: &mpsc::UnboundedSender<NormalizedMessage>,
+    ) -> Result<()> {
+        let mut reader = BufReader::new(reader);
+        let mut buf = Vec::new();
+        loop {
+            let read = tokio::time::timeout(READ_TIMEOUT, read_line(&mut reader, &mut buf))
+                .await
+                .context("IRC ping timeout")??;
+            if read == 0 {
+                return Ok(());
+            }
+            let line = String::from_utf8_lossy(&buf);
+            let Some(message) = IrcMessage::parse(&line) else {
+                continue;
+            };
+            self.handle_line(&message, tx, inbound)?;
+        }
+    }
+
+    /// React to one server line.
+    fn handle_line(
+        &self,
+        message: &IrcMessage,
+        tx: &mpsc::UnboundedSender<String>,
+        inbound: &mpsc::UnboundedSender<NormalizedMessage>,
+    ) -> Result<()> {
+        let send = |line: String| {
+            let _ = tx.send(line);
+        };
+        match message.command.as_str() {
+            "PING" => send(format!("PONG :{}", message.param(0).unwrap_or_default())),
+            "CAP" => {
+                let sasl = message.param(2).is_some_and(|caps| caps.contains("sasl"));
+                match message.param(1) {
+                    Some("ACK") if sasl => send("AUTHENTICATE PLAIN".to_string()),
+                    Some("NAK") if sasl => {
+                        warn!("IRC server does not support SASL");
+                        send("CAP END".to_string());
+                    }
+                    // Without SASL, negotiation ends once `account-tag` is answered.
+                    Some("ACK" | "NAK") if self.sasl_credentials().is_none() => {
+                        send("CAP END".to_string());
+                    }
+                    _ => {}
+                }
+            }
+            "AUTHENTICATE" if message.param(0) == Some("+") => {
+                if let Some((username, password)) = self.sasl_credentials() {
+                    let payload = format!("{username}\0{username}\0{password}");
+                    send(format!(
+                        "AUTHENTICATE {}",
+                        base64::engine::general_purpose::STANDARD.encode(payload)
+                    ));
+                }
+            }
+            // RPL_SASLSUCCESS
+            "903" => send("CAP END".to_string()),
+            // ERR_NICKLOCKED, ERR_SASLFAIL, ERR_SASLTOOLONG, ERR_SASLABORTED, ERR_SASLALREADY
+            "902" | "904" | "905" | "906" | "907" => {
+                warn!(code = %message.command, "IRC SASL authentication failed");
+                send("CAP END".to_string());
+            }
+            // RPL_WELCOME
+            "001" => {
+                if let Some(nick) = message.param(0) {
+                    *self.nick.write() = nick.to_string();
+                }
+                self.connected.store(true, Ordering::Relaxed);
+                info!(nick = %self.nick.read(), "IRC registered");
+                if let Some(password) = &self.config.nickserv_password {
+                    send(format!("PRIVMSG NickServ :IDENTIFY {password}"));
+                }
+                for channel in self.config.channels.iter().flatten() {
+                    send(format!("JOIN {}", channel.trim()));
+                }
+            }
+            // ERR_NICKNAMEINUSE
+            "433" if !self.connected.load(Ordering::Relaxed) => {
+                let nick = format!("{}_", self.nick.read());
+                warn!(nick = %nick, "IRC nick in use; retrying");
+                *self.nick.write() = nick.clone();
+                send(format!("NICK {nick}"));
+            }
+            "NICK" => {
+                let own = self.nick.read().clone();
+                if message.nick().is_some_and(|n| n.eq_ignore_ascii_case(&own)) {
+                    if let Some(nick) = message.param(0) {
+                        *self.nick.write() = nick.to_string();
+                    }
+                }
+            }
+            "KICK" => {
+                let own = self.nick.read().clone();
+                if message
+                    .param(1)
+                    .is_some_and(|n| n.eq_ignore_ascii_case(&own))
+                {
+                    warn!(channel = ?message.param(0), "Kicked from IRC channel");
+                }
+            }
+            "PRIVMSG" => {
+                let own = self.nick.read().clone();
+                if let Some(msg) = normalize_privmsg(message, &own) {
+                    if admissible(&msg, |msg| self.admit(msg)) {
+                        let _ = inbound.send(msg);
+                    } else {
+                        debug!(chat = %msg.chat_id, sender = %msg.sender.id, "IRC message not admitted");
+                    }
+                }
+            }
+            "ERROR" => bail!(
+                "IRC server closed the link: {}",
+                message.param(0).unwrap_or_default()
+            ),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Decide whether an inbound message should reach the agent.
+    fn admit(&self, msg: &NormalizedMessage) -> bool {
+        match msg.chat_type {
+            // `dmPolicy` and `allowFrom` are applied by the admission stage.
+            ChatType::Dm => true,
+            ChatType::Group | ChatType::Thread => {
+                !self.config.require_mention.unwrap_or(true) || msg.mentioned
+            }
+        }
+    }
+
+    /// Queue `text` as PRIVMSG lines to `target`.
+    fn send_text(&self, target: &str, text: &str) -> Result<()> {
+        if target.is_empty() || target.contains([' ', '\r', '\n', ',']) {
+            bail!("invalid IRC target: {target:?}");
+        }
+        let tx = self
+            .outbound
+            .lock()
+            .clone()
+            .filter(|_| self.connected.load(Ordering::Relaxed));
+        let Some(tx) = tx else {
+            bail!("IRC: not connected — cannot send message to {}", target);
+        };
+        let limit = max_payload_bytes(&self.nick.read(), target);
+        // Clients reset formatting at the end of each line, so spans never
+        // leak from one PRIVMSG into the next.
+        let text = render_markdown(text, FormatTarget::Irc);
+        for line in split_for_irc(&text, limit) {
+            tx.send(format!("PRIVMSG {target} :{line}"))
+                .map_err(|_| anyhow::anyhow!("IRC connection closed"))?;
+        }
+        Ok(())
+    }
+
+    /// Run the agent for an admitted message and send the reply.
+    async fn reply(&self, state: &GatewayState, mut msg: NormalizedMessage) {
+        if !admit_group_message(state, &mut msg, |msg| self.admit(msg)).await {
+            return;
+        }
+        match dispatch_inbound(state, &msg).await {
+            Ok(Some(reply)) => {
+                let outbound = NormalizedOutbound::text(msg.chat_id.clone(), reply);
+                if let Err(e) = state.channels.enqueue("irc", outbound).await {
+                    warn!(target = %msg.chat_id, error = %e, "IRC reply failed");
+                }
+            }
+            Ok(None) => {}
+            Err(e) => warn!(target = %msg.chat_id, error = %e, "IRC agent run failed"),
+        }
     }
 }
 
@@ -77,7 +537,7 @@ impl ChannelPlugin for IrcChannel {
         ChannelMeta {
             name: "IRC".to_string(),
             description: "Internet Relay Chat channel via raw TCP connection".to_string(),
-            enabled: self.is_enabled(),
+            enabled: self.enabled,
             multi_account: false,
         }
     }
@@ -90,65 +550,651 @@ impl ChannelPlugin for IrcChannel {
         ]
     }
 
-    async fn start_account(&self, _state: &GatewayState) -> Result<()> {
-        if !self.is_enabled() {
+    async fn start_account(&self, state: &GatewayState) -> Result<()> {
+        if !self.enabled {
             return Ok(());
         }
 
-        let server = match &self.server {
-            Some(s) => s,
-            None => {
-                warn!("IRC channel enabled but no server configured");
-                return Ok(());
-            }
+        let Some(server) = &self.client.config.server else {
+            warn!("IRC channel enabled but no server configured");
+            return Ok(());
         };
 
-        let port = self.port.unwrap_or(6667);
-        let nick = self.nick.as_deref().unwrap_or("mylobster");
-        let channels = self.channels.as_deref().unwrap_or(&[]);
-
         info!(
             server = %server,
-            port = %port,
-            nick = %nick,
-            channels = ?channels,
-            "IRC channel starting — would connect to server"
+            port = %self.client.port(),
+            tls = self.client.use_tls(),
+            nick = %self.client.nick.read(),
+            channels = ?self.client.config.channels,
+            "IRC channel starting"
         );
 
-        // TODO: Establish a TCP (or TLS) connection to the IRC server.
-        // 1. Send NICK and USER commands.
-        // 2. Join configured channels.
-        // 3. Start a read loop to parse incoming IRC messages.
-        //
-        // The connection lifecycle would be managed in a spawned task,
-        // setting `self.connected` to true once registered.
+        self.client.stopping.store(false, Ordering::Relaxed);
+        let abort = AbortHandle::new();
+        let task = {
+            let client = self.client.clone();
+            let state = state.clone();
+            let abort = abort.clone();
+            tokio::spawn(async move {
+                let (inbound_tx, mut inbound_rx) = mpsc::unbounded_channel();
+                let dispatch = {
+                    let client = client.clone();
+                    async move {
+                        while let Some(msg) = inbound_rx.recv().await {
+                            let client = client.clone();
+                            let state = state.clone();
+                            tokio::spawn(async move { client.reply(&state, msg).await });
+                        }
+                    }
+                };
+                let connection = async { tokio::join!(client.run(inbound_tx), dispatch) };
+                if monitor_with_abort_lifecycle(connection, &abort)
+                    .await
+                    .is_err()
+                {
+                    debug!("IRC connection loop stopped");
+                }
+            })
+        };
+        if let Some((previous, _)) = self.task.lock().replace((abort, task)) {
+            previous.abort();
+        }
 
         Ok(())
     }
 
     async fn stop_account(&self) -> Result<()> {
-        if self.is_enabled() {
+        if self.enabled {
             info!("IRC channel stopping");
-            self.connected.store(false, Ordering::Relaxed);
-            // TODO: Send QUIT command and close the TCP connection.
+            self.client.stopping.store(true, Ordering::Relaxed);
+            if let Some(tx) = self.client.outbound.lock().clone() {
+                let _ = tx.send("QUIT :Shutting down".to_string());
+            }
+            let task = self.task.lock().take();
+            if let Some((abort, mut task)) = task {
+                if tokio::time::timeout(QUIT_TIMEOUT, &mut task).await.is_err() {
+                    abort.abort();
+                    let _ = task.await;
+                }
+            }
+            self.client.connected.store(false, Ordering::Relaxed);
         }
         Ok(())
     }
 
     async fn send_message(&self, to: &str, message: &str) -> Result<()> {
-        if !self.connected.load(Ordering::Relaxed) {
-            anyhow::bail!("IRC: not connected — cannot send message to {}", to);
+        debug!(target_channel = %to, "IRC: sending PRIVMSG");
+
+        // `to` is an IRC channel name (e.g. "#mylobster") or a nick for DMs.
+        self.client.send_text(to, message)
+    }
+}
+
+/// Read one line into `buf`, returning 0 at end of stream.
+///
+/// Lines longer than [`MAX_INBOUND_LINE_BYTES`] fail the read, so a
+/// misbehaving server cannot grow the buffer without bound.
+pub(super) async fn read_line(
+    reader: &mut (impl AsyncBufRead + Unpin),
+    buf: &mut Vec<u8>,
+) -> std::io::Result<usize> {
+    buf.clear();
+    let read = (&mut *reader)
+        .take(MAX_INBOUND_LINE_BYTES as u64)
+        .read_until(b'\n', buf)
+        .await?;
+    if read == MAX_INBOUND_LINE_BYTES && buf.last() != Some(&b'\n') {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("IRC line longer than {MAX_INBOUND_LINE_BYTES} bytes"),
+        ));
+    }
+    Ok(read)
+}
+
+/// Open a TCP connection to `host:port`, wrapped in TLS when `tls` is set.
+pub(super) async fn connect_stream(host: &str, port: u16, tls: bool) -> Result<Box<dyn IrcStream>> {
+    let tcp = TcpStream::connect((host, port))
+        .await
+        .with_context(|| format!("failed to connect to {host}:{port}"))?;
+    if !tls {
+        return Ok(Box::new(tcp));
+    }
+    Ok(Box::new(tls_handshake(host, tcp).await?))
+}
+
+/// Run a TLS client handshake over `stream`, verifying `host` against the
+/// web PKI roots.
+pub(super) async fn tls_handshake<S>(
+    host: &str,
+    stream: S,
+) -> Result<tokio_rustls::client::TlsStream<S>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let roots = tokio_rustls::rustls::RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let tls_config = tokio_rustls::rustls::ClientConfig::builder_with_provider(Arc::new(
+        tokio_rustls::rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()?
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    let server_name = tokio_rustls::rustls::pki_types::ServerName::try_from(host.to_string())
+        .with_context(|| format!("invalid server name: {host}"))?;
+    tokio_rustls::TlsConnector::from(Arc::new(tls_config))
+        .connect(server_name, stream)
+        .await
+        .context("TLS handshake failed")
+}
+
+// ============================================================================
+// Message Handling
+// ============================================================================
+
+/// Whether an IRC target names a channel rather than a nick.
+fn is_channel_name(target: &str) -> bool {
+    target.starts_with(['#', '&', '+', '!'])
+}
+
+/// Convert a PRIVMSG into a [`NormalizedMessage`].
+///
+/// Channel messages are reported against the channel and count as mentions
+/// when they name the bot's nick; DMs are reported against the sender.
+/// Returns `None` for our own messages and CTCP requests other than ACTION.
+fn normalize_privmsg(message: &IrcMessage, own_nick: &str) -> Option<NormalizedMessage> {
+    if message.command != "PRIVMSG" {
+        return None;
+    }
+    let sender = message.nick()?;
+    if sender.eq_ignore_ascii_case(own_nick) {
+        return None;
+    }
+    let target = message.param(0)?;
+    let body = message.param(1)?;
+
+    let body = match body.strip_prefix('\x01') {
+        Some(ctcp) => {
+            let action = ctcp.trim_end_matches('\x01').strip_prefix("ACTION ")?;
+            format!("* {sender} {action}")
         }
+        None => body.to_string(),
+    };
+    let body = strip_formatting(&body);
 
-        info!(target_channel = %to, "IRC: sending PRIVMSG");
+    let is_channel = is_channel_name(target);
+    let (mentioned, text) = if is_channel {
+        strip_nick_mention(&body, own_nick)
+    } else {
+        (true, body.trim().to_string())
+    };
+    if text.is_empty() {
+        return None;
+    }
 
-        // `to` is an IRC channel name (e.g. "#mylobster") or a nick for DMs.
-        // The message would be sent as: PRIVMSG <to> :<message>
-        //
-        // TODO: Write the PRIVMSG line to the active TCP stream.
-        // For long messages, split into multiple lines (IRC has a ~512 byte limit).
-        let _ = message;
+    Some(NormalizedMessage {
+        id: uuid::Uuid::new_v4().to_string(),
+        channel: "irc".to_string(),
+        account_id: "default".to_string(),
+        chat_id: if is_channel { target } else { sender }.to_string(),
+        chat_name: is_channel.then(|| target.to_string()),
+        chat_type: if is_channel {
+            ChatType::Group
+        } else {
+            ChatType::Dm
+        },
+        sender: NormalizedSender {
+            id: sender_id(message)?,
+            name: sender.to_string(),
+            is_bot: false,
+            roles: Vec::new(),
+        },
+        text,
+        attachments: Vec::new(),
+        reply_to_id: None,
+        thread_id: None,
+        mentioned,
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        raw: Some(serde_json::json!({
+            "prefix": message.prefix,
+            "command": message.command,
+            "params": message.params,
+        })),
+    })
+}
 
-        Ok(())
+/// Allowlist identity of a message's sender: the services account from the
+/// IRCv3 `account` tag when the server sends one, else the full
+/// `nick!user@host` mask. A bare nick can be taken by anyone.
+fn sender_id(message: &IrcMessage) -> Option<String> {
+    match message.tag("account") {
+        Some(account) if account != "*" => Some(account.to_string()),
+        _ => message.prefix.clone(),
+    }
+}
+
+/// Detect and strip an address to `nick` (`nick: hi`, `nick, hi`, `@nick hi`).
+///
+/// A nick mentioned elsewhere in the text counts as a mention but is kept.
+pub(super) fn strip_nick_mention(text: &str, nick: &str) -> (bool, String) {
+    let trimmed = text.trim();
+    let (at_sign, addressed) = match trimmed.strip_prefix('@') {
+        Some(rest) => (true, rest),
+        None => (false, trimmed),
+    };
+    if let Some(head) = addressed.get(..nick.len()) {
+        if head.eq_ignore_ascii_case(nick) {
+            let rest = &addressed[nick.len()..];
+            if rest.is_empty()
+                || rest.starts_with([':', ','])
+                || (at_sign && rest.starts_with(char::is_whitespace))
+            {
+                return (true, rest.trim_start_matches([':', ',']).trim().to_string());
+            }
+        }
+    }
+    let mentioned = trimmed.split_whitespace().any(|word| {
+        word.trim_matches(|c: char| matches!(c, ':' | ',' | '.' | '!' | '?' | '@'))
+            .eq_ignore_ascii_case(nick)
+    });
+    (mentioned, trimmed.to_string())
+}
+
+/// Remove mIRC formatting codes (bold, colours, etc.).
+fn strip_formatting(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\x02' | '\x0f' | '\x11' | '\x16' | '\x1d' | '\x1e' | '\x1f' => {}
+            '\x03' => {
+                // Up to two foreground digits, optionally `,` and two background digits.
+                for _ in 0..2 {
+                    if chars.next_if(|c| c.is_ascii_digit()).is_none() {
+                        break;
+                    }
+                }
+                let mut lookahead = chars.clone();
+                if lookahead.next() == Some(',')
+                    && lookahead.peek().is_some_and(|c| c.is_ascii_digit())
+                {
+                    chars.next();
+                    for _ in 0..2 {
+                        if chars.next_if(|c| c.is_ascii_digit()).is_none() {
+                            break;
+                        }
+                    }
+                }
+            }
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+/// Payload bytes available in a `PRIVMSG <target> :` line once the server
+/// has prefixed it with our hostmask.
+fn max_payload_bytes(nick: &str, target: &str) -> usize {
+    let overhead = 2 // CRLF
+        + "PRIVMSG ".len()
+        + target.len()
+        + " :".len()
+        + 1 // leading ':'
+        + nick.len()
+        + HOSTMASK_RESERVE_BYTES;
+    MAX_LINE_BYTES.saturating_sub(overhead).max(32)
+}
+
+/// Split text into IRC lines of at most `max_bytes` bytes.
+///
+/// Each input line becomes at least one message (IRC has no multi-line
+/// messages); long lines break at the last space that fits, or at a UTF-8
+/// character boundary.
+pub(super) fn split_for_irc(text: &str, max_bytes: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    for line in text.lines() {
+        let line = line.replace(['\r', '\0'], "");
+        let mut rest = line.trim_end();
+        if rest.trim().is_empty() {
+            continue;
+        }
+        while rest.len() > max_bytes {
+            let mut cut = max_bytes;
+            while !rest.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            if let Some(space) = rest[..cut].rfind(' ').filter(|&i| i > 0) {
+                cut = space;
+            }
+            out.push(rest[..cut].to_string());
+            rest = rest[cut..].trim_start();
+        }
+        if !rest.is_empty() {
+            out.push(rest.to_string());
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::{Admission, Verdict};
+    use crate::config::{DmPolicy, IrcSaslConfig};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    fn client_with(configure: impl FnOnce(&mut IrcConfig)) -> Arc<IrcClient> {
+        let mut config = Config::default();
+        let mut irc = IrcConfig {
+            enabled: Some(true),
+            server: Some("127.0.0.1".to_string()),
+            nickname: Some("lobster".to_string()),
+            channels: Some(vec!["#test".to_string()]),
+            flood_burst: Some(100),
+            ..Default::default()
+        };
+        configure(&mut irc);
+        config.channels.irc = Some(irc);
+        IrcChannel::new(&config).client
+    }
+
+    #[test]
+    fn parse_lines() {
+        let msg = IrcMessage::parse(":alice!a@host PRIVMSG #test :hello there\r\n").unwrap();
+        assert_eq!(msg.prefix.as_deref(), Some("alice!a@host"));
+        assert_eq!(msg.nick(), Some("alice"));
+        assert_eq!(msg.command, "PRIVMSG");
+        assert_eq!(msg.params, vec!["#test", "hello there"]);
+
+        let ping = IrcMessage::parse("PING :irc.example.net").unwrap();
+        assert_eq!(ping.command, "PING");
+        assert_eq!(ping.params, vec!["irc.example.net"]);
+
+        let tagged =
+            IrcMessage::parse("@time=2026-01-01T00:00:00Z :srv 001 lobster :Welcome").unwrap();
+        assert_eq!(tagged.command, "001");
+        assert_eq!(tagged.params, vec!["lobster", "Welcome"]);
+        assert_eq!(tagged.tag("time"), Some("2026-01-01T00:00:00Z"));
+
+        let escaped = IrcMessage::parse(r"@msg=a\sb\:c;empty= PING :x").unwrap();
+        assert_eq!(escaped.tag("msg"), Some("a b;c"));
+        assert_eq!(escaped.tag("empty"), None);
+
+        assert!(IrcMessage::parse("").is_none());
+    }
+
+    #[test]
+    fn normalize_channel_and_dm_messages() {
+        let msg = IrcMessage::parse(":alice!a@h PRIVMSG #test :Lobster: what's up?").unwrap();
+        let normalized = normalize_privmsg(&msg, "lobster").unwrap();
+        assert_eq!(normalized.chat_type, ChatType::Group);
+        assert_eq!(normalized.chat_id, "#test");
+        assert_eq!(normalized.sender.id, "alice!a@h");
+        assert!(normalized.mentioned);
+        assert_eq!(normalized.text, "what's up?");
+
+        let msg = IrcMessage::parse(":alice!a@h PRIVMSG #test :just chatting").unwrap();
+        assert!(!normalize_privmsg(&msg, "lobster").unwrap().mentioned);
+
+        let msg = IrcMessage::parse("@account=alice :al!a@h PRIVMSG #test :hi").unwrap();
+        assert_eq!(
+            normalize_privmsg(&msg, "lobster").unwrap().sender.id,
+            "alice"
+        );
+        let msg = IrcMessage::parse("@account=* :al!a@h PRIVMSG #test :hi").unwrap();
+        assert_eq!(
+            normalize_privmsg(&msg, "lobster").unwrap().sender.id,
+            "al!a@h"
+        );
+
+        let msg = IrcMessage::parse(":alice!a@h PRIVMSG lobster :\x02hi\x02 \x0304,01red").unwrap();
+        let dm = normalize_privmsg(&msg, "lobster").unwrap();
+        assert_eq!(dm.chat_type, ChatType::Dm);
+        assert_eq!(dm.chat_id, "alice");
+        assert_eq!(dm.text, "hi red");
+    }
+
+    #[test]
+    fn normalize_ctcp_and_own_messages() {
+        let action = IrcMessage::parse(":alice!a@h PRIVMSG lobster :\x01ACTION waves\x01").unwrap();
+        assert_eq!(
+            normalize_privmsg(&action, "lobster").unwrap().text,
+            "* alice waves"
+        );
+
+        let version = IrcMessage::parse(":alice!a@h PRIVMSG lobster :\x01VERSION\x01").unwrap();
+        assert!(normalize_privmsg(&version, "lobster").is_none());
+
+        let own = IrcMessage::parse(":lobster!l@h PRIVMSG #test :echo").unwrap();
+        assert!(normalize_privmsg(&own, "lobster").is_none());
+    }
+
+    #[test]
+    fn admission_rules() {
+        let client = client_with(|irc| {
+            irc.dm_policy = Some(DmPolicy::Allowlist);
+            irc.allow_from = Some(vec!["alice".to_string()]);
+        });
+        let mut config = Config::default();
+        config.channels.irc = Some(client.config.clone());
+        let admission = Admission::new();
+        let dm = normalize_privmsg(
+            &IrcMessage::parse("@account=alice :alice!a@h PRIVMSG lobster :hi").unwrap(),
+            "lobster",
+        )
+        .unwrap();
+        assert!(client.admit(&dm));
+        assert_eq!(admission.check(&config, &dm), Verdict::Admit);
+        // Anyone can take the nick; without the account it is not admitted.
+        let impostor = normalize_privmsg(
+            &IrcMessage::parse(":alice!x@elsewhere PRIVMSG lobster :hi").unwrap(),
+            "lobster",
+        )
+        .unwrap();
+        assert_ne!(admission.check(&config, &impostor), Verdict::Admit);
+        let other = normalize_privmsg(
+            &IrcMessage::parse(":bob!b@h PRIVMSG lobster :hi").unwrap(),
+            "lobster",
+        )
+        .unwrap();
+        assert!(client.admit(&other));
+        assert_ne!(admission.check(&config, &other), Verdict::Admit);
+
+        let unaddressed = normalize_privmsg(
+            &IrcMessage::parse(":bob!b@h PRIVMSG #test :hi all").unwrap(),
+            "lobster",
+        )
+        .unwrap();
+        assert!(!client.admit(&unaddressed));
+    }
+
+    #[tokio::test]
+    async fn read_line_rejects_oversized_lines() {
+        let long = format!(
+            "PING :{}\r\nPING :ok\r\n",
+            "x".repeat(MAX_INBOUND_LINE_BYTES)
+        );
+        let mut reader = BufReader::new(long.as_bytes());
+        let mut buf = Vec::new();
+        let err = read_line(&mut reader, &mut buf).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let mut reader = BufReader::new(&b"PING :a\r\nPING :b"[..]);
+        assert_eq!(read_line(&mut reader, &mut buf).await.unwrap(), 9);
+        assert_eq!(buf, b"PING :a\r\n");
+        assert_eq!(read_line(&mut reader, &mut buf).await.unwrap(), 7);
+        assert_eq!(read_line(&mut reader, &mut buf).await.unwrap(), 0);
+    }
+
+    #[test]
+    fn split_respects_byte_limit() {
+        let limit = max_payload_bytes("lobster", "#test");
+        assert!(limit < MAX_LINE_BYTES);
+
+        let text = format!("{}\n\nsecond line", "word ".repeat(300));
+        let lines = split_for_irc(&text, limit);
+        assert!(lines.len() > 3);
+        assert!(lines.iter().all(|l| l.len() <= limit));
+        assert_eq!(lines.last().unwrap(), "second line");
+
+        // Multi-byte characters are never split.
+        let lines = split_for_irc(&"é".repeat(100), 51);
+        assert!(lines
+            .iter()
+            .all(|l| l.len() <= 51 && l.chars().all(|c| c == 'é')));
+        assert_eq!(lines.concat().chars().count(), 100);
+    }
+
+    #[test]
+    fn token_bucket_throttles_after_burst() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(2, Duration::from_secs(1), start);
+        assert_eq!(bucket.take(start), Duration::ZERO);
+        assert_eq!(bucket.take(start), Duration::ZERO);
+        assert_eq!(bucket.take(start), Duration::from_secs(1));
+        assert_eq!(bucket.take(start), Duration::from_secs(2));
+        // Tokens refill once the owed time has passed.
+        let later = start + Duration::from_secs(5);
+        assert_eq!(bucket.take(later), Duration::ZERO);
+    }
+
+    /// Minimal in-process IRC server for one client connection.
+    struct TestServer {
+        reader: BufReader<tokio::net::tcp::OwnedReadHalf>,
+        writer: tokio::net::tcp::OwnedWriteHalf,
+    }
+
+    impl TestServer {
+        async fn expect(&mut self, prefix: &str) -> String {
+            loop {
+                let mut line = String::new();
+                let read =
+                    tokio::time::timeout(Duration::from_secs(5), self.reader.read_line(&mut line))
+                        .await
+                        .expect("timed out waiting for client line")
+                        .unwrap();
+                assert!(read > 0, "client closed while waiting for {prefix}");
+                let line = line.trim_end().to_string();
+                if line.starts_with(prefix) {
+                    return line;
+                }
+            }
+        }
+
+        async fn send(&mut self, line: &str) {
+            self.writer
+                .write_all(format!("{line}\r\n").as_bytes())
+                .await
+                .unwrap();
+        }
+    }
+
+    async fn start_session(
+        client: Arc<IrcClient>,
+    ) -> (TestServer, mpsc::UnboundedReceiver<NormalizedMessage>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (inbound_tx, inbound_rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let _ = client.session(stream, &inbound_tx).await;
+        });
+        let (socket, _) = listener.accept().await.unwrap();
+        let (reader, writer) = socket.into_split();
+        (
+            TestServer {
+                reader: BufReader::new(reader),
+                writer,
+            },
+            inbound_rx,
+        )
+    }
+
+    #[tokio::test]
+    async fn session_registers_joins_and_relays_messages() {
+        let client = client_with(|irc| irc.nickserv_password = Some("pw".to_string()));
+        let (mut server, mut inbound) = start_session(client.clone()).await;
+
+        server.expect("CAP REQ :account-tag").await;
+        server.expect("NICK lobster").await;
+        server.expect("USER lobster 0 *").await;
+        server.send(":srv CAP * ACK :account-tag").await;
+        server.expect("CAP END").await;
+        server
+            .send(":srv 433 * lobster :Nickname is already in use")
+            .await;
+        server.expect("NICK lobster_").await;
+        server.send(":srv 001 lobster_ :Welcome").await;
+        server.expect("PRIVMSG NickServ :IDENTIFY pw").await;
+        server.expect("JOIN #test").await;
+
+        server.send("PING :token123").await;
+        assert_eq!(server.expect("PONG").await, "PONG :token123");
+
+        server
+            .send(":alice!a@h PRIVMSG #test :lobster_: hello bot")
+            .await;
+        let msg = tokio::time::timeout(Duration::from_secs(5), inbound.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(msg.text, "hello bot");
+        assert_eq!(msg.chat_id, "#test");
+
+        // Unaddressed channel chatter is relayed unmentioned, to be buffered
+        // as group context.
+        server.send(":alice!a@h PRIVMSG #test :hello all").await;
+        let chatter = tokio::time::timeout(Duration::from_secs(5), inbound.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(chatter.text, "hello all");
+        assert!(!chatter.mentioned);
+
+        client.send_text("#test", &"word ".repeat(200)).unwrap();
+        let first = server.expect("PRIVMSG #test :").await;
+        let second = server.expect("PRIVMSG #test :").await;
+        let relayed = format!(":lobster_!lobster@{} {first}\r\n", "h".repeat(63));
+        assert!(relayed.len() <= MAX_LINE_BYTES);
+        assert!(second.len() > "PRIVMSG #test :".len());
+    }
+
+    #[tokio::test]
+    async fn session_authenticates_with_sasl() {
+        let client = client_with(|irc| {
+            irc.sasl = Some(IrcSaslConfig {
+                username: Some("acct".to_string()),
+                password: Some("secret".to_string()),
+            });
+        });
+        let (mut server, _inbound) = start_session(client.clone()).await;
+
+        server.expect("CAP REQ :sasl").await;
+        server.expect("NICK lobster").await;
+        server.send(":srv CAP * ACK :sasl").await;
+        server.expect("AUTHENTICATE PLAIN").await;
+        server.send("AUTHENTICATE +").await;
+        let auth = server.expect("AUTHENTICATE ").await;
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(auth.trim_start_matches("AUTHENTICATE "))
+            .unwrap();
+        assert_eq!(decoded, b"acct\0acct\0secret");
+        server
+            .send(":srv 903 lobster :SASL authentication successful")
+            .await;
+        server.expect("CAP END").await;
+        server.send(":srv 001 lobster :Welcome").await;
+        server.expect("JOIN #test").await;
+        assert!(client.connected.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn send_fails_when_not_connected() {
+        let client = client_with(|_| {});
+        let err = client.send_text("#test", "hi").unwrap_err();
+        assert!(err.to_string().contains("not connected"));
+        assert!(client.send_text("#a b", "hi").is_err());
     }
 }
@@ -1,10 +1,21 @@
-use super::plugin::{ChannelCapability, ChannelMeta, ChannelPlugin};
+use super::group_history::admit_group_message;
+use super::normalize::{
+    ChatType, NormalizedAttachment, NormalizedMessage, NormalizedOutbound, NormalizedSender,
+};
+use super::plugin::{
+    unsupported, ChannelCapability, ChannelMeta, ChannelPlugin, WebhookRequest, WebhookResponse,
+};
+use super::split_text;
+use super::webhook::{self, ReplayGuard, WebhookRoute};
+use crate::config::{Config, LineConfig};
 use crate::gateway::GatewayState;
 
 use anyhow::Result;
 use async_trait::async_trait;
+use axum::http::StatusCode;
 use reqwest::Client;
-use tracing::{info, warn};
+use serde_json::{json, Value};
+use tracing::{debug, info, warn};
 
 // ============================================================================
 // LINE Channel Implementation
@@ -12,48 +23,66 @@ use tracing::{info, warn};
 
 /// LINE Messaging API channel integration.
 ///
-/// Sends and receives messages via the LINE Messaging API.
-/// Push messages use `POST https://api.line.me/v2/bot/message/push`.
-/// Reply messages use `POST https://api.line.me/v2/bot/message/reply`.
+/// Receives events on `POST /channels/line/<webhookPath>` (the URL set in
+/// the LINE Developer Console), verified with `X-Line-Signature`.
+/// Reply messages use `POST https://api.line.me/v2/bot/message/reply`,
+/// falling back to `POST https://api.line.me/v2/bot/message/push` once the
+/// reply token has expired.
 ///
 /// Requires a LINE Messaging API channel access token and channel secret
 /// (for webhook signature verification).
 pub struct LineChannel {
-    /// Long-lived channel access token from the LINE Developer Console.
-    channel_access_token: Option<String>,
-    /// Channel secret for webhook signature verification (HMAC-SHA256).
-    channel_secret: Option<String>,
-    /// Whether this channel is enabled.
-    enabled: Option<bool>,
-    /// HTTP client for LINE API calls.
-    client: Client,
+    enabled: bool,
+    config: LineConfig,
+    api: LineApi,
+    /// Webhook event ids already handled (LINE redelivers failed events).
+    replay: ReplayGuard,
 }
 
 /// LINE Messaging API base URL.
 const LINE_API_BASE: &str = "https://api.line.me/v2/bot";
+/// LINE content API base URL (message media).
+const LINE_DATA_API_BASE: &str = "https://api-data.line.me/v2/bot";
+const DEFAULT_WEBHOOK_PATH: &str = "webhook";
+const SIGNATURE_HEADER: &str = "x-line-signature";
+/// Maximum characters in one text message.
+const LINE_MAX_TEXT_CHARS: usize = 5000;
+/// Maximum message objects per reply or push request.
+const LINE_MAX_MESSAGES_PER_REQUEST: usize = 5;
 
 impl LineChannel {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
+        let line = config.channels.line.clone().unwrap_or_default();
         Self {
-            channel_access_token: None,
-            channel_secret: None,
-            enabled: None,
-            client: Client::new(),
+            enabled: line.enabled.unwrap_or(false),
+            api: LineApi {
+                client: Client::new(),
+                token: line.channel_access_token.clone(),
+                api_base: LINE_API_BASE.to_string(),
+            },
+            config: line,
+            replay: ReplayGuard::default(),
         }
     }
 
-    /// Create a configured LINE channel.
-    pub fn with_config(channel_access_token: String, channel_secret: String) -> Self {
-        Self {
-            channel_access_token: Some(channel_access_token),
-            channel_secret: Some(channel_secret),
-            enabled: Some(true),
-            client: Client::new(),
-        }
+    fn webhook_path(&self) -> &str {
+        webhook::webhook_path(self.config.webhook_path.as_deref(), DEFAULT_WEBHOOK_PATH)
     }
 
-    fn is_enabled(&self) -> bool {
-        self.enabled.unwrap_or(false)
+    /// Validate `X-Line-Signature` (base64 HMAC-SHA256 of the body).
+    fn signature_valid(&self, request: &WebhookRequest) -> bool {
+        let (Some(secret), Some(signature)) = (
+            self.config.channel_secret.as_deref(),
+            request.header(SIGNATURE_HEADER),
+        ) else {
+            return false;
+        };
+        let digest = webhook::hmac_sha256(secret.as_bytes(), &[&request.body]);
+        webhook::digest_matches_base64(&digest, signature)
+    }
+
+    fn admit(&self, msg: &NormalizedMessage) -> bool {
+        webhook::admit(self.config.require_mention.unwrap_or(true), msg)
     }
 }
 
@@ -67,7 +96,7 @@ impl ChannelPlugin for LineChannel {
         ChannelMeta {
             name: "LINE".to_string(),
             description: "LINE Messaging API channel".to_string(),
-            enabled: self.is_enabled(),
+            enabled: self.enabled,
             multi_account: false,
         }
     }
@@ -83,88 +112,428 @@ impl ChannelPlugin for LineChannel {
         ]
     }
 
+    fn webhook_routes(&self) -> Vec<WebhookRoute> {
+        if !self.enabled {
+            return Vec::new();
+        }
+        vec![WebhookRoute::post(self.webhook_path())]
+    }
+
     async fn start_account(&self, _state: &GatewayState) -> Result<()> {
-        if !self.is_enabled() {
+        if !self.enabled {
             return Ok(());
         }
 
-        if self.channel_access_token.is_none() {
+        let Some(token) = &self.api.token else {
             warn!("LINE channel enabled but no channel_access_token configured");
             return Ok(());
+        };
+        if self.config.channel_secret.is_none() {
+            warn!(
+                "LINE channel enabled but no channel_secret configured; webhooks will be rejected"
+            );
         }
 
-        info!("LINE channel starting");
+        info!(path = %self.webhook_path(), "LINE channel starting");
 
         // Verify the token by calling the bot info endpoint.
-        if let Some(token) = &self.channel_access_token {
-            let url = format!("{}/info", LINE_API_BASE);
-            match self
-                .client
-                .get(&url)
-                .header("Authorization", format!("Bearer {}", token))
-                .send()
-                .await
-            {
-                Ok(resp) if resp.status().is_success() => {
-                    info!("LINE: bot info endpoint reachable, token valid");
-                }
-                Ok(resp) => {
-                    warn!("LINE: bot info returned status {}", resp.status());
-                }
-                Err(e) => {
-                    warn!("LINE: failed to verify token: {}", e);
-                }
+        let url = format!("{}/info", self.api.api_base);
+        match self
+            .api
+            .client
+            .get(&url)
+            .header("Authorization", format!("Bearer {}", token))
+            .send()
+            .await
+        {
+            Ok(resp) if resp.status().is_success() => {
+                info!("LINE: bot info endpoint reachable, token valid");
+            }
+            Ok(resp) => {
+                warn!("LINE: bot info returned status {}", resp.status());
+            }
+            Err(e) => {
+                warn!("LINE: failed to verify token: {}", e);
             }
         }
 
-        // TODO: Register a webhook endpoint for incoming LINE messages.
-        // LINE sends events to the webhook URL configured in the LINE Developer Console.
-
         Ok(())
     }
 
     async fn stop_account(&self) -> Result<()> {
-        if self.is_enabled() {
+        if self.enabled {
             info!("LINE channel stopping");
         }
         Ok(())
     }
 
     async fn send_message(&self, to: &str, message: &str) -> Result<()> {
-        let token = self
-            .channel_access_token
-            .as_deref()
-            .ok_or_else(|| anyhow::anyhow!("LINE channel_access_token not configured"))?;
-
         // `to` is a LINE user ID, group ID, or room ID.
-        let url = format!("{}/message/push", LINE_API_BASE);
-
-        let body = serde_json::json!({
-            "to": to,
-            "messages": [
-                {
-                    "type": "text",
-                    "text": message,
+        info!(to = %to, "LINE: sending push message");
+        self.api.push(to, message).await
+    }
+
+    async fn send(&self, message: &NormalizedOutbound) -> Result<Option<String>> {
+        if !message.attachments.is_empty() {
+            return Err(unsupported(self.id(), ChannelCapability::SendMedia));
+        }
+        // `reply_to_id` carries the event's reply token, which is free to
+        // use; `reply` falls back to a push once it has expired.
+        self.api
+            .reply(
+                message.reply_to_id.as_deref(),
+                &message.chat_id,
+                &message.text,
+            )
+            .await?;
+        Ok(None)
+    }
+
+    async fn handle_webhook(
+        &self,
+        state: &GatewayState,
+        request: WebhookRequest,
+    ) -> Result<WebhookResponse> {
+        if !self.enabled {
+            return Ok(WebhookResponse::not_found());
+        }
+        if let Some(response) = webhook::route_check(&self.webhook_routes(), &request) {
+            return Ok(response);
+        }
+        if !self.signature_valid(&request) {
+            warn!("LINE webhook request with invalid signature");
+            return Ok(WebhookResponse::status(StatusCode::UNAUTHORIZED));
+        }
+
+        let payload: Value = match serde_json::from_slice(&request.body) {
+            Ok(payload) => payload,
+            Err(e) => {
+                return Ok(WebhookResponse::text(
+                    StatusCode::BAD_REQUEST,
+                    format!("invalid payload: {e}"),
+                ));
+            }
+        };
+
+        // The console's "Verify" button sends an empty event list.
+        for event in payload["events"].as_array().into_iter().flatten() {
+            if let Some(id) = event["webhookEventId"].as_str() {
+                if !self.replay.first_delivery(id) {
+                    debug!(event_id = %id, "Skipping redelivered LINE event");
+                    continue;
                 }
-            ],
-        });
+            }
+            let Some(mut msg) = normalize_event(event) else {
+                continue;
+            };
+            if !admit_group_message(state, &mut msg, |msg| self.admit(msg)).await {
+                debug!(sender = %msg.sender.id, "LINE message not admitted");
+                continue;
+            }
+            let reply_token = event["replyToken"].as_str().map(str::to_string);
+            webhook::spawn_dispatch(state, msg, move |msg, text| NormalizedOutbound {
+                reply_to_id: reply_token,
+                ..NormalizedOutbound::text(msg.chat_id.clone(), text)
+            });
+        }
+        Ok(WebhookResponse::ok())
+    }
+}
 
-        info!(to = %to, "LINE: sending push message");
+// ============================================================================
+// Messaging API
+// ============================================================================
+
+/// Outbound half of the channel, cloned into reply tasks.
+#[derive(Clone)]
+struct LineApi {
+    client: Client,
+    token: Option<String>,
+    api_base: String,
+}
+
+impl LineApi {
+    async fn post(&self, endpoint: &str, body: &Value) -> Result<()> {
+        let token = self
+            .token
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("LINE channel_access_token not configured"))?;
 
         let resp = self
             .client
-            .post(&url)
+            .post(format!("{}/message/{endpoint}", self.api_base))
             .header("Authorization", format!("Bearer {}", token))
-            .json(&body)
+            .json(body)
             .send()
             .await?;
 
         if !resp.status().is_success() {
             let status = resp.status();
             let text = resp.text().await.unwrap_or_default();
-            anyhow::bail!("LINE push message failed ({}): {}", status, text);
+            anyhow::bail!("LINE {} message failed ({}): {}", endpoint, status, text);
         }
 
         Ok(())
     }
+
+    /// Push `text` to a user, group or room, split into request batches.
+    async fn push(&self, to: &str, text: &str) -> Result<()> {
+        for batch in text_batches(text) {
+            self.post("push", &json!({ "to": to, "messages": batch }))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Answer with the reply token when possible; reply tokens are single
+    /// use and expire, so the remainder (or everything) is pushed.
+    async fn reply(&self, reply_token: Option<&str>, to: &str, text: &str) -> Result<()> {
+        let mut batches = text_batches(text).into_iter();
+        if let Some((token, first)) = reply_token.and_then(|t| Some((t, batches.next()?))) {
+            let body = json!({ "replyToken": token, "messages": first });
+            if let Err(e) = self.post("reply", &body).await {
+                debug!(error = %e, "LINE reply token rejected; pushing instead");
+                self.post("push", &json!({ "to": to, "messages": body["messages"] }))
+                    .await?;
+            }
+        }
+        for batch in batches {
+            self.post("push", &json!({ "to": to, "messages": batch }))
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Split text into message objects, grouped per request.
+fn text_batches(text: &str) -> Vec<Vec<Value>> {
+    let messages: Vec<Value> = split_text(text, LINE_MAX_TEXT_CHARS)
+        .into_iter()
+        .map(|chunk| json!({ "type": "text", "text": chunk }))
+        .collect();
+    messages
+        .chunks(LINE_MAX_MESSAGES_PER_REQUEST)
+        .map(<[Value]>::to_vec)
+        .collect()
+}
+
+// ============================================================================
+// Event Parsing
+// ============================================================================
+
+/// Convert a `message` webhook event into a [`NormalizedMessage`].
+fn normalize_event(event: &Value) -> Option<NormalizedMessage> {
+    if event["type"] != "message" || event["mode"] == "standby" {
+        return None;
+    }
+    let message = &event["message"];
+    let id = message["id"].as_str()?.to_string();
+    let source = &event["source"];
+    let sender_id = source["userId"].as_str().unwrap_or_default().to_string();
+
+    let (chat_type, chat_id) = match source["type"].as_str()? {
+        "user" => (ChatType::Dm, sender_id.clone()),
+        "group" => (ChatType::Group, source["groupId"].as_str()?.to_string()),
+        "room" => (ChatType::Group, source["roomId"].as_str()?.to_string()),
+        _ => return None,
+    };
+
+    let mut mentioned = chat_type == ChatType::Dm;
+    let mut text = String::new();
+    let mut attachments = Vec::new();
+    match message["type"].as_str()? {
+        "text" => {
+            text = message["text"].as_str().unwrap_or_default().to_string();
+            // Mentionee offsets count UTF-16 code units.
+            let mut units: Vec<u16> = text.encode_utf16().collect();
+            let mut own: Vec<(usize, usize)> = message["mention"]["mentionees"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|m| m["isSelf"] == true)
+                .filter_map(|m| {
+                    Some((
+                        m["index"].as_u64()? as usize,
+                        m["length"].as_u64()? as usize,
+                    ))
+                })
+                .collect();
+            if !own.is_empty() {
+                mentioned = true;
+                own.sort_unstable_by(|a, b| b.cmp(a));
+                for (index, length) in own {
+                    if index + length <= units.len() {
+                        units.drain(index..index + length);
+                    }
+                }
+                text = String::from_utf16_lossy(&units).trim().to_string();
+            }
+        }
+        kind @ ("image" | "video" | "audio" | "file") => {
+            let mime_type = match kind {
+                "image" => Some("image/jpeg".to_string()),
+                "video" => Some("video/mp4".to_string()),
+                "audio" => Some("audio/m4a".to_string()),
+                _ => None,
+            };
+            attachments.push(NormalizedAttachment {
+                mime_type,
+                url: Some(format!("{LINE_DATA_API_BASE}/message/{id}/content")),
+                data: None,
+                filename: message["fileName"].as_str().map(str::to_string),
+                size: message["fileSize"].as_u64(),
+            });
+        }
+        _ => return None,
+    }
+
+    Some(NormalizedMessage {
+        id,
+        channel: "line".to_string(),
+        account_id: "default".to_string(),
+        chat_id,
+        chat_name: None,
+        chat_type,
+        sender: NormalizedSender {
+            id: sender_id.clone(),
+            name: sender_id,
+            is_bot: false,
+            roles: Vec::new(),
+        },
+        text,
+        attachments,
+        reply_to_id: message["quotedMessageId"].as_str().map(str::to_string),
+        thread_id: None,
+        mentioned,
+        timestamp: webhook::timestamp_from_millis(event["timestamp"].as_i64().unwrap_or_default()),
+        raw: Some(event.clone()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::webhook::tests::request;
+    use axum::http::Method;
+    use base64::Engine as _;
+    use wiremock::matchers::{body_partial_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn channel(configure: impl FnOnce(&mut LineConfig)) -> LineChannel {
+        let mut config = Config::default();
+        let mut line = LineConfig {
+            enabled: Some(true),
+            channel_access_token: Some("token".to_string()),
+            channel_secret: Some("secret".to_string()),
+            ..Default::default()
+        };
+        configure(&mut line);
+        config.channels.line = Some(line);
+        LineChannel::new(&config)
+    }
+
+    fn text_event(source: Value, text: &str, mentionees: Value) -> Value {
+        json!({
+            "type": "message",
+            "mode": "active",
+            "timestamp": 1_700_000_000_000i64,
+            "webhookEventId": "01HEVENT",
+            "replyToken": "reply-token",
+            "source": source,
+            "message": { "type": "text", "id": "468789", "text": text, "mention": { "mentionees": mentionees } }
+        })
+    }
+
+    #[test]
+    fn signature_is_base64_hmac_of_body() {
+        let channel = channel(|_| {});
+        let body = br#"{"destination":"U1","events":[]}"#;
+        let signature = base64::engine::general_purpose::STANDARD
+            .encode(webhook::hmac_sha256(b"secret", &[body]));
+        let signed = request(
+            Method::POST,
+            "webhook",
+            &[(SIGNATURE_HEADER, &signature)],
+            body,
+        );
+        assert!(channel.signature_valid(&signed));
+        let tampered = request(
+            Method::POST,
+            "webhook",
+            &[(SIGNATURE_HEADER, &signature)],
+            b"{}",
+        );
+        assert!(!channel.signature_valid(&tampered));
+    }
+
+    #[test]
+    fn group_mentions_are_stripped_and_admitted() {
+        let channel = channel(|_| {});
+        let event = text_event(
+            json!({ "type": "group", "groupId": "C123", "userId": "U456" }),
+            "@Bot 😀 hello",
+            json!([{ "index": 0, "length": 4, "userId": "U999", "isSelf": true }]),
+        );
+        let msg = normalize_event(&event).unwrap();
+        assert_eq!(msg.chat_type, ChatType::Group);
+        assert_eq!(msg.chat_id, "C123");
+        assert_eq!(msg.text, "😀 hello");
+        assert!(msg.mentioned);
+        assert!(channel.admit(&msg));
+
+        let unmentioned = text_event(
+            json!({ "type": "group", "groupId": "C123", "userId": "U456" }),
+            "chatter",
+            json!([]),
+        );
+        assert!(!channel.admit(&normalize_event(&unmentioned).unwrap()));
+
+        let image = json!({
+            "type": "message", "timestamp": 0, "source": { "type": "user", "userId": "U1" },
+            "message": { "type": "image", "id": "77" }
+        });
+        let msg = normalize_event(&image).unwrap();
+        assert_eq!(msg.chat_type, ChatType::Dm);
+        assert_eq!(
+            msg.attachments[0].url.as_deref(),
+            Some("https://api-data.line.me/v2/bot/message/77/content")
+        );
+    }
+
+    #[tokio::test]
+    async fn expired_reply_token_falls_back_to_push() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/message/reply"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("Invalid reply token"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/message/push"))
+            .and(header("authorization", "Bearer token"))
+            .and(body_partial_json(
+                json!({ "to": "U1", "messages": [{ "type": "text", "text": "hi" }] }),
+            ))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let api = LineApi {
+            client: Client::new(),
+            token: Some("token".to_string()),
+            api_base: server.uri(),
+        };
+        api.reply(Some("expired"), "U1", "hi").await.unwrap();
+    }
+
+    #[test]
+    fn long_replies_are_batched() {
+        let text = "x".repeat(LINE_MAX_TEXT_CHARS * 6);
+        let batches = text_batches(&text);
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].len(), LINE_MAX_MESSAGES_PER_REQUEST);
+        assert_eq!(batches[1].len(), 1);
+    }
 }
@@ -1,61 +1,605 @@
-use super::plugin::{ChannelCapability, ChannelMeta, ChannelPlugin};
+use super::admission;
+use super::group_history::{admissible, admit_group_message};
+use super::inbound::{dispatch_inbound, typing_interval_ms};
+use super::normalize::{
+    split_text, ChatType, NormalizedAttachment, NormalizedMessage, NormalizedOutbound,
+    NormalizedSender,
+};
+use super::plugin::{
+    unsupported, ChannelCapability, ChannelMeta, ChannelPlugin, DEFAULT_ACCOUNT_ID,
+};
+use super::TypingKeepaliveLoop;
+use crate::config::{Config, MatrixAccountConfig, MatrixAutoJoin};
 use crate::gateway::GatewayState;
+use crate::infra::abort_signal::{monitor_with_abort_lifecycle, AbortHandle};
+use crate::infra::dm_policy;
 
-use anyhow::Result;
+use anyhow::{bail, Context as _, Result};
 use async_trait::async_trait;
-use reqwest::Client;
-use tracing::{info, warn};
+use parking_lot::{Mutex, RwLock};
+use reqwest::{Client, Method, StatusCode};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
 
 // ============================================================================
 // Matrix Channel Implementation
 // ============================================================================
 
+const CLIENT_API_PREFIX: &str = "/_matrix/client/v3";
+/// Long-poll timeout for `/sync`.
+const SYNC_TIMEOUT_MS: u64 = 30_000;
+/// Extra time on top of the long-poll timeout before a sync request is abandoned.
+const SYNC_REQUEST_GRACE: Duration = Duration::from_secs(30);
+const SYNC_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const SYNC_MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// Limits timeline size per room and drops presence, which the bot ignores.
+const SYNC_FILTER: &str = r#"{"room":{"timeline":{"limit":50},"state":{"lazy_load_members":true}},"presence":{"not_types":["*"]}}"#;
+/// How long the homeserver shows "typing…" per notification.
+const TYPING_TIMEOUT_MS: u64 = 30_000;
+const TYPING_INTERVAL_MS: u64 = 20_000;
+const DEFAULT_TEXT_CHUNK_LIMIT: usize = 16_000;
+/// Attempts for requests answered with `M_LIMIT_EXCEEDED`.
+const RATE_LIMIT_ATTEMPTS: u32 = 3;
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(10);
+
+/// The bot's own Matrix identity.
+#[derive(Debug, Clone)]
+struct MatrixIdentity {
+    user_id: String,
+    display_name: Option<String>,
+}
+
+/// Result of one `/sync` round-trip.
+struct SyncBatch {
+    next_batch: String,
+    /// Admitted inbound messages, in timeline order.
+    messages: Vec<NormalizedMessage>,
+}
+
+/// Per-account state shared between the channel and its sync task.
+struct MatrixAccount {
+    account_id: String,
+    config: MatrixAccountConfig,
+    /// Homeserver base URL without a trailing slash.
+    homeserver: Option<String>,
+    http: Client,
+    identity: RwLock<Option<MatrixIdentity>>,
+    /// File holding the last `next_batch` sync token.
+    sync_state_path: PathBuf,
+    /// Joined member counts from sync room summaries; two members is a DM.
+    member_counts: Mutex<HashMap<String, u64>>,
+    /// Rooms with end-to-end encryption enabled, reported once each.
+    encrypted_rooms: Mutex<HashSet<String>>,
+}
+
 /// Matrix channel integration using the Client-Server API.
 ///
 /// Communicates with a Matrix homeserver via the Matrix Client-Server API
-/// (`/_matrix/client/v3/`). Sends messages using the `/rooms/{roomId}/send`
-/// endpoint with `m.room.message` events.
+/// (`/_matrix/client/v3/`). Receives events through a `/sync` long-poll loop
+/// whose `next_batch` token is persisted under `state_dir/matrix/`, and sends
+/// messages using the `/rooms/{roomId}/send` endpoint with `m.room.message`
+/// events. End-to-end encrypted rooms are not supported: their messages are
+/// reported and skipped.
 pub struct MatrixChannel {
-    /// Whether this channel is enabled.
-    enabled: Option<bool>,
-    /// Matrix homeserver URL (e.g. `https://matrix.org`).
-    homeserver_url: Option<String>,
-    /// Access token for the Matrix account.
-    access_token: Option<String>,
-    /// Matrix user ID (e.g. `@bot:matrix.org`).
-    user_id: Option<String>,
-    /// HTTP client for API calls.
-    client: Client,
+    enabled: bool,
+    account: Arc<MatrixAccount>,
+    /// Abort handle and task of the running sync loop, if any.
+    poller: Mutex<Option<(AbortHandle, tokio::task::JoinHandle<()>)>>,
 }
 
 impl MatrixChannel {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
+        Self::for_account(config, DEFAULT_ACCOUNT_ID)
+    }
+
+    /// The channel for one Matrix user: an entry of `accounts`, or the
+    /// top-level settings for the default account.
+    pub fn for_account(config: &Config, account_id: &str) -> Self {
+        let channel = &config.channels.matrix;
+        let account = admission::account(&channel.accounts, &channel.default_account, account_id);
+        let enabled = account
+            .enabled
+            .unwrap_or(account.homeserver_url.is_some() && account.access_token.is_some());
+        let sync_state_path = config
+            .state_dir
+            .join("matrix")
+            .join(account_id)
+            .join("sync.json");
+
         Self {
-            enabled: None,
-            homeserver_url: None,
-            access_token: None,
-            user_id: None,
-            client: Client::new(),
+            enabled,
+            account: Arc::new(MatrixAccount {
+                account_id: account_id.to_string(),
+                config: account.clone(),
+                homeserver: account
+                    .homeserver_url
+                    .as_deref()
+                    .map(|url| url.trim_end_matches('/').to_string()),
+                http: Client::new(),
+                identity: RwLock::new(None),
+                sync_state_path,
+                member_counts: Mutex::new(HashMap::new()),
+                encrypted_rooms: Mutex::new(HashSet::new()),
+            }),
+            poller: Mutex::new(None),
         }
     }
+}
 
-    /// Create a configured Matrix channel.
-    pub fn with_config(
-        homeserver_url: String,
-        access_token: String,
-        user_id: String,
-    ) -> Self {
-        Self {
-            enabled: Some(true),
-            homeserver_url: Some(homeserver_url),
-            access_token: Some(access_token),
-            user_id: Some(user_id),
-            client: Client::new(),
+impl MatrixAccount {
+    fn homeserver(&self) -> Result<&str> {
+        self.homeserver
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Matrix homeserver_url not configured"))
+    }
+
+    fn token(&self) -> Result<&str> {
+        self.config
+            .access_token
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Matrix access_token not configured"))
+    }
+
+    fn text_chunk_limit(&self) -> usize {
+        self.config
+            .text_chunk_limit
+            .unwrap_or(DEFAULT_TEXT_CHUNK_LIMIT)
+    }
+
+    /// Call a client-server API endpoint; `path` is relative to `/v3` and
+    /// must already be percent-encoded.
+    async fn api(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&Value>,
+        timeout: Option<Duration>,
+    ) -> Result<Value> {
+        let url = format!("{}{CLIENT_API_PREFIX}{path}", self.homeserver()?);
+        let token = self.token()?;
+
+        let mut attempt = 1;
+        loop {
+            let mut request = self.http.request(method.clone(), &url).bearer_auth(token);
+            if let Some(body) = body {
+                request = request.json(body);
+            }
+            if let Some(timeout) = timeout {
+                request = request.timeout(timeout);
+            }
+            let resp = request.send().await?;
+            let status = resp.status();
+            let value: Value = resp.json().await.unwrap_or(Value::Null);
+            if status.is_success() {
+                return Ok(value);
+            }
+            if status == StatusCode::TOO_MANY_REQUESTS && attempt < RATE_LIMIT_ATTEMPTS {
+                let retry_after = value["retry_after_ms"]
+                    .as_u64()
+                    .map(Duration::from_millis)
+                    .unwrap_or(SYNC_INITIAL_BACKOFF)
+                    .min(MAX_RETRY_AFTER);
+                debug!(path, "Matrix rate limited; retrying in {:?}", retry_after);
+                tokio::time::sleep(retry_after).await;
+                attempt += 1;
+                continue;
+            }
+            bail!(
+                "Matrix {} {} failed ({}): {} {}",
+                method,
+                path,
+                status,
+                value["errcode"].as_str().unwrap_or_default(),
+                value["error"].as_str().unwrap_or_default()
+            );
+        }
+    }
+
+    /// Resolve the bot's user id (via `whoami` unless configured) and
+    /// display name.
+    async fn resolve_identity(&self) -> Result<MatrixIdentity> {
+        let user_id = match &self.config.user_id {
+            Some(user_id) => user_id.clone(),
+            None => self.api(Method::GET, "/account/whoami", None, None).await?["user_id"]
+                .as_str()
+                .map(str::to_string)
+                .context("Matrix whoami returned no user_id")?,
+        };
+        let display_name = match self
+            .api(
+                Method::GET,
+                &format!("/profile/{}/displayname", urlencoded(&user_id)),
+                None,
+                None,
+            )
+            .await
+        {
+            Ok(profile) => profile["displayname"].as_str().map(str::to_string),
+            Err(e) => {
+                debug!(error = %e, "Matrix display name lookup failed");
+                None
+            }
+        };
+        let identity = MatrixIdentity {
+            user_id,
+            display_name,
+        };
+        *self.identity.write() = Some(identity.clone());
+        Ok(identity)
+    }
+
+    fn load_next_batch(&self) -> Option<String> {
+        let raw = std::fs::read_to_string(&self.sync_state_path).ok()?;
+        let state: Value = serde_json::from_str(&raw).ok()?;
+        state["nextBatch"].as_str().map(str::to_string)
+    }
+
+    /// Persist the sync token, replacing the previous file atomically.
+    fn save_next_batch(&self, next_batch: &str) -> Result<()> {
+        if let Some(dir) = self.sync_state_path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp = self.sync_state_path.with_extension("json.tmp");
+        std::fs::write(&tmp, json!({ "nextBatch": next_batch }).to_string())?;
+        std::fs::rename(&tmp, &self.sync_state_path)?;
+        Ok(())
+    }
+
+    /// Run one `/sync` request, handle invites and return admitted messages.
+    async fn sync_once(&self, since: Option<&str>, timeout_ms: u64) -> Result<SyncBatch> {
+        let mut path = format!(
+            "/sync?timeout={timeout_ms}&filter={}",
+            urlencoded(SYNC_FILTER)
+        );
+        if let Some(since) = since {
+            path.push_str(&format!("&since={}", urlencoded(since)));
+        }
+        let body = self
+            .api(
+                Method::GET,
+                &path,
+                None,
+                Some(Duration::from_millis(timeout_ms) + SYNC_REQUEST_GRACE),
+            )
+            .await?;
+        let next_batch = body["next_batch"]
+            .as_str()
+            .context("Matrix sync response missing next_batch")?
+            .to_string();
+
+        self.handle_invites(&body["rooms"]["invite"]).await;
+        let messages = self.process_joined_rooms(&body["rooms"]["join"]);
+        Ok(SyncBatch {
+            next_batch,
+            messages,
+        })
+    }
+
+    /// Join invited rooms permitted by `autoJoin`.
+    async fn handle_invites(&self, invites: &Value) {
+        let Some(invites) = invites.as_object() else {
+            return;
+        };
+        let own = self.identity.read().as_ref().map(|i| i.user_id.clone());
+        for (room_id, room) in invites {
+            let inviter = room["invite_state"]["events"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .find(|event| {
+                    event["type"] == "m.room.member"
+                        && event["content"]["membership"] == "invite"
+                        && own.as_deref().map_or(true, |own| event["state_key"] == own)
+                })
+                .and_then(|event| event["sender"].as_str())
+                .unwrap_or_default();
+
+            if !self.should_join(inviter) {
+                info!(room_id = %room_id, inviter = %inviter, "Ignoring Matrix invite (autoJoin policy)");
+                continue;
+            }
+            match self
+                .api(
+                    Method::POST,
+                    &format!("/join/{}", urlencoded(room_id)),
+                    Some(&json!({})),
+                    None,
+                )
+                .await
+            {
+                Ok(_) => info!(room_id = %room_id, inviter = %inviter, "Joined Matrix room"),
+                Err(e) => warn!(room_id = %room_id, error = %e, "Matrix room join failed"),
+            }
+        }
+    }
+
+    fn should_join(&self, inviter: &str) -> bool {
+        match self.config.auto_join.unwrap_or_default() {
+            MatrixAutoJoin::Always => true,
+            MatrixAutoJoin::Allowlist => {
+                let allow = self
+                    .config
+                    .auto_join_allow_from
+                    .as_deref()
+                    .unwrap_or_default();
+                !allow.is_empty() && dm_policy::is_source_allowed(allow, inviter)
+            }
+            MatrixAutoJoin::Off => false,
+        }
+    }
+
+    /// Normalize and admit the timeline events of joined rooms.
+    fn process_joined_rooms(&self, rooms: &Value) -> Vec<NormalizedMessage> {
+        let Some(rooms) = rooms.as_object() else {
+            return Vec::new();
+        };
+        let Some(identity) = self.identity.read().clone() else {
+            return Vec::new();
+        };
+
+        let mut messages = Vec::new();
+        for (room_id, room) in rooms {
+            if let Some(count) = room["summary"]["m.joined_member_count"].as_u64() {
+                self.member_counts.lock().insert(room_id.clone(), count);
+            }
+            let member_count = self.member_counts.lock().get(room_id).copied();
+
+            let state = room["state"]["events"].as_array().into_iter().flatten();
+            let timeline = room["timeline"]["events"].as_array().into_iter().flatten();
+            for event in state.chain(timeline) {
+                match event["type"].as_str() {
+                    Some("m.room.encryption") => self.report_encrypted(room_id),
+                    Some("m.room.encrypted") => {
+                        self.report_encrypted(room_id);
+                        debug!(
+                            room_id = %room_id,
+                            event_id = ?event["event_id"].as_str(),
+                            "Skipping undecryptable Matrix event"
+                        );
+                    }
+                    Some("m.room.message") => {
+                        let Some(msg) = normalize_event(
+                            &self.account_id,
+                            self.homeserver.as_deref().unwrap_or_default(),
+                            room_id,
+                            event,
+                            &identity,
+                            member_count,
+                        ) else {
+                            continue;
+                        };
+                        if admissible(&msg, |msg| self.admit(msg)) {
+                            messages.push(msg);
+                        } else {
+                            debug!(room_id = %room_id, sender = %msg.sender.id, "Matrix message not admitted");
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        messages
+    }
+
+    /// Report a room the bot cannot read because it is end-to-end encrypted.
+    fn report_encrypted(&self, room_id: &str) {
+        if self.encrypted_rooms.lock().insert(room_id.to_string()) {
+            warn!(
+                room_id = %room_id,
+                "Matrix room is end-to-end encrypted; its messages cannot be decrypted and will be ignored"
+            );
+        }
+    }
+
+    /// Decide whether an inbound message should reach the agent.
+    fn admit(&self, msg: &NormalizedMessage) -> bool {
+        match msg.chat_type {
+            // `dmPolicy` and `allowFrom` are applied by the admission stage.
+            ChatType::Dm => true,
+            ChatType::Group | ChatType::Thread => {
+                !self.config.require_mention.unwrap_or(true) || msg.mentioned
+            }
+        }
+    }
+
+    /// Long-poll `/sync` until aborted, dispatching admitted messages.
+    async fn sync_loop(self: Arc<Self>, state: GatewayState) {
+        let mut since = self.load_next_batch();
+        let mut backoff = SYNC_INITIAL_BACKOFF;
+
+        loop {
+            // Without a stored token, catch up instantly and skip the backlog
+            // rather than answering old messages.
+            let initial = since.is_none();
+            let timeout_ms = if initial { 0 } else { SYNC_TIMEOUT_MS };
+            match self.sync_once(since.as_deref(), timeout_ms).await {
+                Ok(batch) => {
+                    backoff = SYNC_INITIAL_BACKOFF;
+                    if initial {
+                        debug!(
+                            skipped = batch.messages.len(),
+                            "Matrix initial sync; skipping backlog"
+                        );
+                    } else {
+                        for msg in batch.messages {
+                            tokio::spawn(self.clone().handle_message(state.clone(), msg));
+                        }
+                    }
+                    if let Err(e) = self.save_next_batch(&batch.next_batch) {
+                        warn!(error = %e, "Failed to persist Matrix sync token");
+                    }
+                    since = Some(batch.next_batch);
+                }
+                Err(e) => {
+                    warn!(error = %e, "Matrix sync failed; retrying in {:?}", backoff);
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(SYNC_MAX_BACKOFF);
+                }
+            }
+        }
+    }
+
+    async fn handle_message(self: Arc<Self>, state: GatewayState, mut msg: NormalizedMessage) {
+        if !admit_group_message(&state, &mut msg, |msg| self.admit(msg)).await {
+            return;
+        }
+        let room_id = msg.chat_id.clone();
+        if self.config.read_receipts.unwrap_or(true) {
+            if let Err(e) = self.send_read_receipt(&room_id, &msg.id).await {
+                debug!(room_id = %room_id, error = %e, "Matrix read receipt failed");
+            }
+        }
+
+        let interval = typing_interval_ms(&*state.config.read().await, TYPING_INTERVAL_MS);
+        self.spawn_typing(&room_id, true);
+        let typing = TypingKeepaliveLoop::new(interval);
+        let typing_task = {
+            let account = self.clone();
+            let room_id = room_id.clone();
+            typing.start(move || account.spawn_typing(&room_id, true))
+        };
+
+        let result = dispatch_inbound(&state, &msg).await;
+        typing.stop();
+        typing_task.abort();
+        self.spawn_typing(&room_id, false);
+
+        // Group replies quote the triggering message; threads stay in-thread.
+        let reply_to = (msg.chat_type != ChatType::Dm).then(|| msg.id.clone());
+        match result {
+            Ok(Some(reply)) => {
+                let outbound = NormalizedOutbound {
+                    reply_to_id: reply_to,
+                    thread_id: msg.thread_id.clone(),
+                    account_id: Some(self.account_id.clone()),
+                    ..NormalizedOutbound::text(room_id.clone(), reply)
+                };
+                if let Err(e) = state.channels.enqueue("matrix", outbound).await {
+                    warn!(room_id = %room_id, error = %e, "Matrix reply failed");
+                }
+            }
+            Ok(None) => {}
+            Err(e) => warn!(room_id = %room_id, error = %e, "Matrix agent run failed"),
+        }
+    }
+
+    async fn send_read_receipt(&self, room_id: &str, event_id: &str) -> Result<()> {
+        self.api(
+            Method::POST,
+            &format!(
+                "/rooms/{}/receipt/m.read/{}",
+                urlencoded(room_id),
+                urlencoded(event_id)
+            ),
+            Some(&json!({})),
+            None,
+        )
+        .await
+        .map(drop)
+    }
+
+    async fn set_typing(&self, room_id: &str, typing: bool) -> Result<()> {
+        let user_id = self
+            .identity
+            .read()
+            .as_ref()
+            .map(|i| i.user_id.clone())
+            .context("Matrix identity not resolved")?;
+        let mut body = json!({ "typing": typing });
+        if typing {
+            body["timeout"] = json!(TYPING_TIMEOUT_MS);
+        }
+        self.api(
+            Method::PUT,
+            &format!(
+                "/rooms/{}/typing/{}",
+                urlencoded(room_id),
+                urlencoded(&user_id)
+            ),
+            Some(&body),
+            None,
+        )
+        .await
+        .map(drop)
+    }
+
+    fn spawn_typing(self: &Arc<Self>, room_id: &str, typing: bool) {
+        let account = self.clone();
+        let room_id = room_id.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = account.set_typing(&room_id, typing).await {
+                debug!(room_id = %room_id, error = %e, "Matrix typing notification failed");
+            }
+        });
+    }
+
+    /// Resolve a `#alias:server` to a room id; room ids pass through.
+    async fn resolve_room(&self, room: &str) -> Result<String> {
+        if !room.starts_with('#') {
+            return Ok(room.to_string());
         }
+        self.api(
+            Method::GET,
+            &format!("/directory/room/{}", urlencoded(room)),
+            None,
+            None,
+        )
+        .await?["room_id"]
+            .as_str()
+            .map(str::to_string)
+            .with_context(|| format!("Matrix alias {room} did not resolve to a room"))
     }
 
-    fn is_enabled(&self) -> bool {
-        self.enabled.unwrap_or(false)
+    /// Send text as one or more `m.text` events.
+    ///
+    /// With `thread`, every chunk goes into that thread; `reply_to` quotes
+    /// the first chunk's parent.
+    async fn send_text(
+        &self,
+        room_id: &str,
+        text: &str,
+        thread: Option<&str>,
+        reply_to: Option<&str>,
+    ) -> Result<()> {
+        for (i, chunk) in split_text(text, self.text_chunk_limit())
+            .into_iter()
+            .enumerate()
+        {
+            let reply_to = reply_to.filter(|_| i == 0);
+            let mut content = json!({ "msgtype": "m.text", "body": chunk });
+            match (thread, reply_to) {
+                (Some(root), _) => {
+                    content["m.relates_to"] = json!({
+                        "rel_type": "m.thread",
+                        "event_id": root,
+                        "is_falling_back": reply_to.is_none(),
+                        "m.in_reply_to": { "event_id": reply_to.unwrap_or(root) },
+                    });
+                }
+                (None, Some(parent)) => {
+                    content["m.relates_to"] = json!({ "m.in_reply_to": { "event_id": parent } });
+                }
+                (None, None) => {}
+            }
+
+            let txn_id = uuid::Uuid::new_v4().to_string();
+            self.api(
+                Method::PUT,
+                &format!(
+                    "/rooms/{}/send/m.room.message/{}",
+                    urlencoded(room_id),
+                    txn_id
+                ),
+                Some(&content),
+                None,
+            )
+            .await?;
+        }
+        Ok(())
     }
 }
 
@@ -65,11 +609,15 @@ impl ChannelPlugin for MatrixChannel {
         "matrix"
     }
 
+    fn account_id(&self) -> &str {
+        &self.account.account_id
+    }
+
     fn meta(&self) -> ChannelMeta {
         ChannelMeta {
             name: "Matrix".to_string(),
             description: "Matrix protocol channel via Client-Server API".to_string(),
-            enabled: self.is_enabled(),
+            enabled: self.enabled,
             multi_account: true,
         }
     }
@@ -90,105 +638,565 @@ impl ChannelPlugin for MatrixChannel {
         ]
     }
 
-    async fn start_account(&self, _state: &GatewayState) -> Result<()> {
-        if !self.is_enabled() {
+    async fn start_account(&self, state: &GatewayState) -> Result<()> {
+        if !self.enabled {
             return Ok(());
         }
 
-        let homeserver = match &self.homeserver_url {
-            Some(url) => url,
-            None => {
-                warn!("Matrix channel enabled but no homeserver_url configured");
-                return Ok(());
-            }
+        let Some(homeserver) = &self.account.homeserver else {
+            warn!("Matrix channel enabled but no homeserver_url configured");
+            return Ok(());
         };
 
-        if self.access_token.is_none() {
+        if self.account.config.access_token.is_none() {
             warn!("Matrix channel enabled but no access_token configured");
             return Ok(());
         }
 
-        let user_id = self.user_id.as_deref().unwrap_or("(unknown)");
+        let identity = self
+            .account
+            .resolve_identity()
+            .await
+            .context("Matrix identity lookup failed")?;
         info!(
             homeserver = %homeserver,
-            user_id = %user_id,
+            user_id = %identity.user_id,
             "Matrix channel starting"
         );
 
-        // TODO: Start a /sync long-poll loop to receive incoming messages.
-        // The sync loop would call `/_matrix/client/v3/sync` with a `since`
-        // token and dispatch incoming `m.room.message` events.
+        let abort = AbortHandle::new();
+        let task = {
+            let account = self.account.clone();
+            let state = state.clone();
+            let abort = abort.clone();
+            tokio::spawn(async move {
+                let syncing = account.sync_loop(state);
+                if monitor_with_abort_lifecycle(syncing, &abort).await.is_err() {
+                    debug!("Matrix sync loop stopped");
+                }
+            })
+        };
+        if let Some((previous, _)) = self.poller.lock().replace((abort, task)) {
+            previous.abort();
+        }
 
         Ok(())
     }
 
     async fn stop_account(&self) -> Result<()> {
-        if self.is_enabled() {
+        if self.enabled {
             info!("Matrix channel stopping");
-            // TODO: Cancel the sync loop task.
+            let poller = self.poller.lock().take();
+            if let Some((abort, task)) = poller {
+                abort.abort();
+                let _ = task.await;
+            }
         }
         Ok(())
     }
 
     async fn send_message(&self, to: &str, message: &str) -> Result<()> {
-        let homeserver = self
-            .homeserver_url
-            .as_deref()
-            .ok_or_else(|| anyhow::anyhow!("Matrix homeserver_url not configured"))?;
+        self.send(&NormalizedOutbound::text(to, message)).await?;
+        Ok(())
+    }
 
-        let access_token = self
-            .access_token
-            .as_deref()
-            .ok_or_else(|| anyhow::anyhow!("Matrix access_token not configured"))?;
+    async fn send(&self, message: &NormalizedOutbound) -> Result<Option<String>> {
+        if !message.attachments.is_empty() {
+            return Err(unsupported(self.id(), ChannelCapability::SendMedia));
+        }
+        // `chat_id` is a Matrix room ID (e.g. "!abc123:matrix.org") or alias,
+        // optionally followed by `/<threadRootEventId>`.
+        let to = message.chat_id.as_str();
+        let (room, thread) = match to.split_once('/') {
+            Some((room, thread)) if !thread.is_empty() => (room, Some(thread)),
+            _ => (to, None),
+        };
+        let thread = message.thread_id.as_deref().or(thread);
+        let room_id = self.account.resolve_room(room).await?;
 
-        // `to` is a Matrix room ID (e.g. "!abc123:matrix.org").
-        let room_id = to;
-        let txn_id = uuid::Uuid::new_v4().to_string();
+        info!(room_id = %room_id, "Matrix: sending message");
 
-        let url = format!(
-            "{}/_matrix/client/v3/rooms/{}/send/m.room.message/{}",
-            homeserver.trim_end_matches('/'),
-            urlencoded(room_id),
-            txn_id,
-        );
+        self.account
+            .send_text(
+                &room_id,
+                &message.text,
+                thread,
+                message.reply_to_id.as_deref(),
+            )
+            .await?;
+        Ok(None)
+    }
+}
 
-        let body = serde_json::json!({
-            "msgtype": "m.text",
-            "body": message,
-        });
+// ============================================================================
+// Event Handling
+// ============================================================================
 
-        info!(room_id = %room_id, "Matrix: sending message");
+/// Localpart of a Matrix user id (`@alice:example.org` → `alice`).
+fn localpart(user_id: &str) -> &str {
+    let id = user_id.strip_prefix('@').unwrap_or(user_id);
+    id.split(':').next().unwrap_or(id)
+}
 
-        let resp = self
-            .client
-            .put(&url)
-            .bearer_token(access_token)
-            .json(&body)
-            .send()
-            .await?;
+/// Drop the `> quoted` fallback that clients prepend to reply bodies.
+fn strip_reply_fallback(body: &str) -> &str {
+    if !body.starts_with("> ") {
+        return body;
+    }
+    let mut rest = body;
+    while rest.starts_with('>') {
+        rest = rest.split_once('\n').map_or("", |(_, tail)| tail);
+    }
+    rest.trim_start_matches('\n')
+}
 
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let text = resp.text().await.unwrap_or_default();
-            anyhow::bail!("Matrix send failed ({}): {}", status, text);
+/// Detect and strip an address to the bot (`Bot: hi`).
+fn strip_mention(content: &Value, body: &str, identity: &MatrixIdentity) -> (bool, String) {
+    let explicit = content["m.mentions"]["user_ids"]
+        .as_array()
+        .is_some_and(|ids| ids.iter().any(|id| *id == identity.user_id.as_str()));
+
+    let names = [
+        Some(identity.user_id.as_str()),
+        identity.display_name.as_deref(),
+        Some(localpart(&identity.user_id)),
+    ];
+    let trimmed = body.trim();
+    for name in names.into_iter().flatten().filter(|n| !n.is_empty()) {
+        let Some(head) = trimmed.get(..name.len()) else {
+            continue;
+        };
+        if head.eq_ignore_ascii_case(name) {
+            if let Some(rest) = trimmed[name.len()..].strip_prefix([':', ',']) {
+                return (true, rest.trim().to_string());
+            }
         }
+    }
+    (
+        explicit || trimmed.contains(&identity.user_id),
+        trimmed.to_string(),
+    )
+}
 
-        Ok(())
+/// Convert an `mxc://server/id` URI into an authenticated download URL.
+fn mxc_download_url(homeserver: &str, mxc: &str) -> Option<String> {
+    let (server, media_id) = mxc.strip_prefix("mxc://")?.split_once('/')?;
+    Some(format!(
+        "{homeserver}/_matrix/client/v1/media/download/{server}/{media_id}"
+    ))
+}
+
+/// Convert an `m.room.message` event into a [`NormalizedMessage`].
+///
+/// Returns `None` for our own events, notices (the bot convention for
+/// automated messages), edits and unsupported message types. Thread events
+/// carry the root event id as `thread_id`; replies carry the parent as
+/// `reply_to_id`.
+fn normalize_event(
+    account_id: &str,
+    homeserver: &str,
+    room_id: &str,
+    event: &Value,
+    identity: &MatrixIdentity,
+    member_count: Option<u64>,
+) -> Option<NormalizedMessage> {
+    let sender = event["sender"].as_str()?;
+    if sender == identity.user_id {
+        return None;
+    }
+    let event_id = event["event_id"].as_str()?;
+    let content = &event["content"];
+    let relates = &content["m.relates_to"];
+    if relates["rel_type"] == "m.replace" {
+        return None;
+    }
+
+    let sender_name = localpart(sender);
+    let body = content["body"].as_str().unwrap_or_default();
+    let in_reply_to = relates["m.in_reply_to"]["event_id"].as_str();
+    let body = if in_reply_to.is_some() {
+        strip_reply_fallback(body)
+    } else {
+        body
+    };
+
+    let mut attachments = Vec::new();
+    let body = match content["msgtype"].as_str()? {
+        "m.text" => body.to_string(),
+        "m.emote" => format!("* {sender_name} {body}"),
+        "m.image" | "m.file" | "m.audio" | "m.video" => {
+            // A separate `filename` means `body` is a caption.
+            let filename = content["filename"].as_str();
+            attachments.push(NormalizedAttachment {
+                mime_type: content["info"]["mimetype"].as_str().map(str::to_string),
+                url: content["url"]
+                    .as_str()
+                    .and_then(|mxc| mxc_download_url(homeserver, mxc)),
+                data: None,
+                filename: Some(filename.unwrap_or(body).to_string()),
+                size: content["info"]["size"].as_u64(),
+            });
+            match filename {
+                Some(name) if name != body => body.to_string(),
+                _ => String::new(),
+            }
+        }
+        _ => return None,
+    };
+
+    let (mentioned, text) = strip_mention(content, &body, identity);
+    if text.is_empty() && attachments.is_empty() {
+        return None;
     }
+
+    let thread_id = (relates["rel_type"] == "m.thread")
+        .then(|| relates["event_id"].as_str().map(str::to_string))
+        .flatten();
+    let reply_to_id = match &thread_id {
+        Some(_) if relates["is_falling_back"] == true => None,
+        _ => in_reply_to.map(str::to_string),
+    };
+    let chat_type = if thread_id.is_some() {
+        ChatType::Thread
+    } else if member_count.is_some_and(|count| count <= 2) {
+        ChatType::Dm
+    } else {
+        ChatType::Group
+    };
+
+    let timestamp = event["origin_server_ts"]
+        .as_i64()
+        .and_then(chrono::DateTime::from_timestamp_millis)
+        .unwrap_or_else(chrono::Utc::now)
+        .to_rfc3339();
+
+    Some(NormalizedMessage {
+        id: event_id.to_string(),
+        channel: "matrix".to_string(),
+        account_id: account_id.to_string(),
+        chat_id: room_id.to_string(),
+        chat_name: None,
+        chat_type,
+        sender: NormalizedSender {
+            id: sender.to_string(),
+            name: sender_name.to_string(),
+            is_bot: false,
+            roles: Vec::new(),
+        },
+        text,
+        attachments,
+        reply_to_id,
+        thread_id,
+        mentioned,
+        timestamp,
+        raw: Some(event.clone()),
+    })
 }
 
-/// Percent-encode a Matrix room ID for use in URL paths.
+/// Percent-encode a Matrix identifier for use in URL paths.
 fn urlencoded(s: &str) -> String {
     url::form_urlencoded::byte_serialize(s.as_bytes()).collect()
 }
 
-/// Helper trait to add bearer_token to reqwest::RequestBuilder.
-trait BearerToken {
-    fn bearer_token(self, token: &str) -> Self;
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_partial_json, method, path, path_regex, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn identity() -> MatrixIdentity {
+        MatrixIdentity {
+            user_id: "@lobster:example.org".to_string(),
+            display_name: Some("Lobster Bot".to_string()),
+        }
+    }
+
+    fn text_event(body: &str, extra: Value) -> Value {
+        let mut event = json!({
+            "type": "m.room.message",
+            "event_id": "$ev1",
+            "sender": "@alice:example.org",
+            "origin_server_ts": 1_700_000_000_000i64,
+            "content": { "msgtype": "m.text", "body": body }
+        });
+        if let Some(extra) = extra.as_object() {
+            for (key, value) in extra {
+                event["content"][key] = value.clone();
+            }
+        }
+        event
+    }
+
+    fn normalize(event: &Value, member_count: Option<u64>) -> Option<NormalizedMessage> {
+        normalize_event(
+            "default",
+            "https://hs",
+            "!room:example.org",
+            event,
+            &identity(),
+            member_count,
+        )
+    }
+
+    fn channel_with(
+        homeserver: &str,
+        configure: impl FnOnce(&mut MatrixAccountConfig),
+    ) -> MatrixChannel {
+        let mut config = Config::default();
+        config.state_dir =
+            std::env::temp_dir().join(format!("mylobster-matrix-{}", uuid::Uuid::new_v4()));
+        let account = &mut config.channels.matrix.default_account;
+        account.homeserver_url = Some(homeserver.to_string());
+        account.access_token = Some("tok".to_string());
+        configure(account);
+        let channel = MatrixChannel::new(&config);
+        *channel.account.identity.write() = Some(identity());
+        channel
+    }
+
+    #[test]
+    fn normalize_group_message_with_mention() {
+        let event = text_event(
+            "Lobster Bot: what's new?",
+            json!({ "m.mentions": { "user_ids": ["@lobster:example.org"] } }),
+        );
+        let msg = normalize(&event, Some(5)).unwrap();
+        assert_eq!(msg.chat_type, ChatType::Group);
+        assert_eq!(msg.chat_id, "!room:example.org");
+        assert_eq!(msg.sender.id, "@alice:example.org");
+        assert_eq!(msg.sender.name, "alice");
+        assert!(msg.mentioned);
+        assert_eq!(msg.text, "what's new?");
+        assert_eq!(msg.timestamp, "2023-11-14T22:13:20+00:00");
+
+        let chatter = normalize(&text_event("hi all", json!({})), Some(5)).unwrap();
+        assert!(!chatter.mentioned);
+
+        let dm = normalize(&text_event("hi", json!({})), Some(2)).unwrap();
+        assert_eq!(dm.chat_type, ChatType::Dm);
+    }
+
+    #[test]
+    fn normalize_replies_and_threads() {
+        let reply = text_event(
+            "> <@bob:example.org> earlier\n\nlobster: agreed?",
+            json!({ "m.relates_to": { "m.in_reply_to": { "event_id": "$parent" } } }),
+        );
+        let msg = normalize(&reply, None).unwrap();
+        assert_eq!(msg.reply_to_id.as_deref(), Some("$parent"));
+        assert_eq!(msg.text, "agreed?");
+        assert!(msg.mentioned);
+
+        let threaded = text_event(
+            "in thread",
+            json!({ "m.relates_to": {
+                "rel_type": "m.thread",
+                "event_id": "$root",
+                "is_falling_back": true,
+                "m.in_reply_to": { "event_id": "$last" }
+            } }),
+        );
+        let msg = normalize(&threaded, None).unwrap();
+        assert_eq!(msg.chat_type, ChatType::Thread);
+        assert_eq!(msg.thread_id.as_deref(), Some("$root"));
+        assert!(msg.reply_to_id.is_none());
+    }
+
+    #[test]
+    fn normalize_skips_notices_edits_and_own_events() {
+        let mut notice = text_event("beep", json!({}));
+        notice["content"]["msgtype"] = json!("m.notice");
+        assert!(normalize(&notice, None).is_none());
+
+        let edit = text_event(
+            "* fixed",
+            json!({ "m.relates_to": { "rel_type": "m.replace", "event_id": "$ev0" } }),
+        );
+        assert!(normalize(&edit, None).is_none());
+
+        let mut own = text_event("echo", json!({}));
+        own["sender"] = json!("@lobster:example.org");
+        assert!(normalize(&own, None).is_none());
+    }
+
+    #[test]
+    fn normalize_media_attachment() {
+        let mut image = text_event("cat.png", json!({}));
+        image["content"] = json!({
+            "msgtype": "m.image",
+            "body": "cat.png",
+            "url": "mxc://example.org/abc",
+            "info": { "mimetype": "image/png", "size": 42 }
+        });
+        let msg = normalize(&image, Some(2)).unwrap();
+        assert_eq!(msg.text, "");
+        let attachment = &msg.attachments[0];
+        assert_eq!(
+            attachment.url.as_deref(),
+            Some("https://hs/_matrix/client/v1/media/download/example.org/abc")
+        );
+        assert_eq!(attachment.mime_type.as_deref(), Some("image/png"));
+        assert_eq!(attachment.filename.as_deref(), Some("cat.png"));
+    }
+
+    #[test]
+    fn auto_join_policy() {
+        let channel = channel_with("http://localhost", |_| {});
+        assert!(!channel.account.should_join("@alice:example.org"));
+
+        let channel = channel_with("http://localhost", |account| {
+            account.auto_join = Some(MatrixAutoJoin::Allowlist);
+            account.auto_join_allow_from = Some(vec!["@alice:example.org".to_string()]);
+        });
+        assert!(channel.account.should_join("@alice:example.org"));
+        assert!(!channel.account.should_join("@mallory:example.org"));
+
+        let channel = channel_with("http://localhost", |account| {
+            account.auto_join = Some(MatrixAutoJoin::Always);
+        });
+        assert!(channel.account.should_join("@mallory:example.org"));
+    }
+
+    #[tokio::test]
+    async fn sync_against_homeserver_stub() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/_matrix/client/v3/sync"))
+            .and(query_param("since", "s1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "next_batch": "s2",
+                "rooms": {
+                    "join": {
+                        "!room:example.org": {
+                            "summary": { "m.joined_member_count": 4 },
+                            "timeline": { "events": [
+                                text_event("lobster: ping", json!({})),
+                                text_event("unaddressed", json!({})),
+                            ] }
+                        },
+                        "!secret:example.org": {
+                            "timeline": { "events": [{
+                                "type": "m.room.encrypted",
+                                "event_id": "$enc",
+                                "sender": "@alice:example.org",
+                                "content": { "algorithm": "m.megolm.v1.aes-sha2" }
+                            }] }
+                        }
+                    },
+                    "invite": {
+                        "!new:example.org": {
+                            "invite_state": { "events": [{
+                                "type": "m.room.member",
+                                "sender": "@alice:example.org",
+                                "state_key": "@lobster:example.org",
+                                "content": { "membership": "invite" }
+                            }] }
+                        }
+                    }
+                }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path_regex(r"^/_matrix/client/v3/join/.+new.+$"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({ "room_id": "!new:example.org" })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let channel = channel_with(&server.uri(), |account| {
+            account.auto_join = Some(MatrixAutoJoin::Always);
+        });
+        let account = &channel.account;
+        let batch = account.sync_once(Some("s1"), 0).await.unwrap();
+        assert_eq!(batch.next_batch, "s2");
+        // Unaddressed messages come through unmentioned, for group context.
+        assert_eq!(batch.messages.len(), 2);
+        assert_eq!(batch.messages[0].text, "ping");
+        assert!(batch.messages[0].mentioned);
+        assert!(!batch.messages[1].mentioned);
+        assert!(account
+            .encrypted_rooms
+            .lock()
+            .contains("!secret:example.org"));
+
+        assert!(account.load_next_batch().is_none());
+        account.save_next_batch(&batch.next_batch).unwrap();
+        assert_eq!(account.load_next_batch().as_deref(), Some("s2"));
+        let _ = std::fs::remove_dir_all(account.sync_state_path.parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn thread_reply_receipt_and_typing_against_stub() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path_regex(
+                r"^/_matrix/client/v3/rooms/[^/]+/send/m\.room\.message/[^/]+$",
+            ))
+            .and(body_partial_json(json!({
+                "msgtype": "m.text",
+                "body": "hello",
+                "m.relates_to": {
+                    "rel_type": "m.thread",
+                    "event_id": "$root",
+                    "is_falling_back": false,
+                    "m.in_reply_to": { "event_id": "$ev1" }
+                }
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "event_id": "$out" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path_regex(
+                r"^/_matrix/client/v3/rooms/[^/]+/receipt/m\.read/[^/]+$",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path_regex(r"^/_matrix/client/v3/rooms/[^/]+/typing/[^/]+$"))
+            .and(body_partial_json(
+                json!({ "typing": true, "timeout": 30000 }),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let channel = channel_with(&server.uri(), |_| {});
+        let account = &channel.account;
+        account
+            .send_text("!room:example.org", "hello", Some("$root"), Some("$ev1"))
+            .await
+            .unwrap();
+        account
+            .send_read_receipt("!room:example.org", "$ev1")
+            .await
+            .unwrap();
+        account.set_typing("!room:example.org", true).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn send_message_reports_api_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(403).set_body_json(json!({
+                "errcode": "M_FORBIDDEN",
+                "error": "not in room"
+            })))
+            .mount(&server)
+            .await;
 
-impl BearerToken for reqwest::RequestBuilder {
-    fn bearer_token(self, token: &str) -> Self {
-        self.header("Authorization", format!("Bearer {}", token))
+        let channel = channel_with(&server.uri(), |_| {});
+        let err = channel
+            .send_message("!room:example.org", "hi")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("M_FORBIDDEN"));
     }
 }
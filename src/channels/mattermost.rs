@@ -1,55 +1,416 @@
-use super::plugin::{ChannelCapability, ChannelMeta, ChannelPlugin};
+use super::admission;
+use super::group_history::{admissible, admit_group_message};
+use super::inbound::{dispatch_inbound, typing_interval_ms};
+use super::normalize::{
+    split_text, ChatType, NormalizedAttachment, NormalizedMessage, NormalizedOutbound,
+    NormalizedSender,
+};
+use super::plugin::{
+    unsupported, ChannelCapability, ChannelMeta, ChannelPlugin, DEFAULT_ACCOUNT_ID,
+};
+use super::TypingKeepaliveLoop;
+use crate::config::{Config, MattermostAccountConfig};
 use crate::gateway::GatewayState;
+use crate::infra::abort_signal::{monitor_with_abort_lifecycle, AbortHandle};
 
-use anyhow::Result;
+use anyhow::{bail, Context as _, Result};
 use async_trait::async_trait;
+use futures::{SinkExt, StreamExt};
+use parking_lot::{Mutex, RwLock};
 use reqwest::Client;
-use tracing::{info, warn};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio_tungstenite::{connect_async, tungstenite};
+use tracing::{debug, error, info, warn};
 
 // ============================================================================
 // Mattermost Channel Implementation
 // ============================================================================
 
+/// Mattermost rejects posts longer than 16383 characters.
+const DEFAULT_TEXT_CHUNK_LIMIT: usize = 16_383;
+/// Clients clear "typing…" after ~5 s, so refresh slightly sooner.
+const TYPING_INTERVAL_MS: u64 = 4_000;
+/// Interval between client pings on the event stream.
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+/// Silence beyond this (no events or pongs) means a dead connection.
+const READ_TIMEOUT: Duration = Duration::from_secs(90);
+/// A connection that stayed up this long resets the reconnect backoff.
+const STABLE_CONNECTION: Duration = Duration::from_secs(60);
+
+/// The bot's own Mattermost user.
+#[derive(Debug, Clone)]
+struct MattermostIdentity {
+    user_id: String,
+    username: String,
+}
+
+/// Per-account state shared between the channel and its event stream task.
+struct MattermostAccount {
+    account_id: String,
+    config: MattermostAccountConfig,
+    /// Server base URL without a trailing slash.
+    server_url: Option<String>,
+    http: Client,
+    identity: RwLock<Option<MattermostIdentity>>,
+    /// Queue into the writer of the active WebSocket connection.
+    outbound: Mutex<Option<mpsc::UnboundedSender<String>>>,
+    /// Sequence number for WebSocket actions.
+    seq: AtomicU64,
+}
+
 /// Mattermost channel integration via the Mattermost REST API v4.
 ///
 /// Communicates with a Mattermost server using a bot access token or
-/// personal access token. Messages are sent via
-/// `POST /api/v4/posts`.
+/// personal access token. Incoming `posted` events arrive over the
+/// authenticated `/api/v4/websocket` event stream, which reconnects with
+/// the `reconnect` backoff; messages are sent via `POST /api/v4/posts`.
 ///
 /// Mattermost API docs: <https://api.mattermost.com/>
 pub struct MattermostChannel {
-    /// Mattermost server URL (e.g. `https://mattermost.example.com`).
-    server_url: Option<String>,
-    /// Bot access token or personal access token.
-    token: Option<String>,
-    /// Whether this channel is enabled.
-    enabled: Option<bool>,
-    /// HTTP client for API calls.
-    client: Client,
+    enabled: bool,
+    account: Arc<MattermostAccount>,
+    /// Abort handle and task of the running event stream, if any.
+    poller: Mutex<Option<(AbortHandle, tokio::task::JoinHandle<()>)>>,
 }
 
 impl MattermostChannel {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
+        Self::for_account(config, DEFAULT_ACCOUNT_ID)
+    }
+
+    /// The channel for one bot account: an entry of `accounts`, or the
+    /// top-level settings for the default account.
+    pub fn for_account(config: &Config, account_id: &str) -> Self {
+        let channel = &config.channels.mattermost;
+        let account = admission::account(&channel.accounts, &channel.default_account, account_id);
+        let enabled = account
+            .enabled
+            .unwrap_or(account.server_url.is_some() && account.token.is_some());
+
         Self {
-            server_url: None,
-            token: None,
-            enabled: None,
-            client: Client::new(),
+            enabled,
+            account: Arc::new(MattermostAccount {
+                account_id: account_id.to_string(),
+                config: account.clone(),
+                server_url: account
+                    .server_url
+                    .as_deref()
+                    .map(|url| url.trim_end_matches('/').to_string()),
+                http: Client::new(),
+                identity: RwLock::new(None),
+                outbound: Mutex::new(None),
+                seq: AtomicU64::new(1),
+            }),
+            poller: Mutex::new(None),
         }
     }
+}
 
-    /// Create a configured Mattermost channel.
-    pub fn with_config(server_url: String, token: String) -> Self {
-        Self {
-            server_url: Some(server_url),
-            token: Some(token),
-            enabled: Some(true),
-            client: Client::new(),
+impl MattermostAccount {
+    fn server_url(&self) -> Result<&str> {
+        self.server_url
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Mattermost server_url not configured"))
+    }
+
+    fn token(&self) -> Result<&str> {
+        self.config
+            .token
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Mattermost token not configured"))
+    }
+
+    fn text_chunk_limit(&self) -> usize {
+        self.config
+            .text_chunk_limit
+            .unwrap_or(DEFAULT_TEXT_CHUNK_LIMIT)
+    }
+
+    /// Event stream URL: the server URL with a `ws`/`wss` scheme.
+    fn websocket_url(&self) -> Result<String> {
+        let server = self.server_url()?;
+        let ws = if let Some(rest) = server.strip_prefix("https://") {
+            format!("wss://{rest}")
+        } else if let Some(rest) = server.strip_prefix("http://") {
+            format!("ws://{rest}")
+        } else {
+            bail!("Mattermost server_url must be http(s): {server}");
+        };
+        Ok(format!("{ws}/api/v4/websocket"))
+    }
+
+    /// Look up the bot user behind the token.
+    async fn resolve_identity(&self) -> Result<MattermostIdentity> {
+        let resp = self
+            .http
+            .get(format!("{}/api/v4/users/me", self.server_url()?))
+            .bearer_auth(self.token()?)
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            bail!("Mattermost auth check returned status {}", resp.status());
+        }
+        let body: Value = resp.json().await?;
+        let identity = MattermostIdentity {
+            user_id: body["id"]
+                .as_str()
+                .context("Mattermost /users/me returned no id")?
+                .to_string(),
+            username: body["username"].as_str().unwrap_or_default().to_string(),
+        };
+        *self.identity.write() = Some(identity.clone());
+        Ok(identity)
+    }
+
+    /// Queue a WebSocket action on the active connection.
+    fn send_action(&self, action: &str, data: Value) -> Result<()> {
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        let frame = json!({ "seq": seq, "action": action, "data": data });
+        let tx = self.outbound.lock().clone();
+        let Some(tx) = tx else {
+            bail!("Mattermost event stream not connected");
+        };
+        tx.send(frame.to_string())
+            .map_err(|_| anyhow::anyhow!("Mattermost event stream closed"))
+    }
+
+    fn send_typing(&self, channel_id: &str, parent_id: Option<&str>) {
+        let data = json!({ "channel_id": channel_id, "parent_id": parent_id.unwrap_or_default() });
+        if let Err(e) = self.send_action("user_typing", data) {
+            debug!(channel_id = %channel_id, error = %e, "Mattermost typing event failed");
+        }
+    }
+
+    /// Stream events until the reconnect policy gives up.
+    async fn run(self: Arc<Self>, inbound: mpsc::UnboundedSender<NormalizedMessage>) {
+        let reconnect = self.config.reconnect.clone().unwrap_or_default();
+        let mut attempt = 0;
+        loop {
+            let started = Instant::now();
+            let result = self.session(&inbound).await;
+            self.outbound.lock().take();
+
+            if started.elapsed() >= STABLE_CONNECTION {
+                attempt = 0;
+            }
+            let Some(delay) = reconnect.delay(attempt) else {
+                error!(
+                    attempts = attempt,
+                    "Mattermost event stream: giving up after repeated failures"
+                );
+                return;
+            };
+            attempt += 1;
+            match result {
+                Ok(()) => info!(
+                    "Mattermost event stream closed; reconnecting in {:?}",
+                    delay
+                ),
+                Err(e) => warn!(
+                    error = %e,
+                    "Mattermost event stream failed; reconnecting in {:?}",
+                    delay
+                ),
+            }
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Authenticate and process one event stream connection until it closes.
+    async fn session(&self, inbound: &mpsc::UnboundedSender<NormalizedMessage>) -> Result<()> {
+        let (ws, _) = connect_async(self.websocket_url()?)
+            .await
+            .context("Mattermost WebSocket connect failed")?;
+        let (mut sink, mut stream) = ws.split();
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+        *self.outbound.lock() = Some(tx);
+        self.send_action(
+            "authentication_challenge",
+            json!({ "token": self.token()? }),
+        )?;
+
+        let write_loop = async move {
+            let mut ping = tokio::time::interval(PING_INTERVAL);
+            ping.tick().await;
+            loop {
+                tokio::select! {
+                    frame = rx.recv() => match frame {
+                        Some(text) => sink.send(tungstenite::Message::Text(text.into())).await?,
+                        None => return Ok::<_, tungstenite::Error>(()),
+                    },
+                    _ = ping.tick() => sink.send(tungstenite::Message::Ping(Vec::new().into())).await?,
+                }
+            }
+        };
+
+        let read_loop = async {
+            loop {
+                let frame = tokio::time::timeout(READ_TIMEOUT, stream.next())
+                    .await
+                    .context("Mattermost event stream timed out")?;
+                match frame {
+                    None | Some(Ok(tungstenite::Message::Close(_))) => return Ok(()),
+                    Some(Err(e)) => return Err(e).context("Mattermost event stream error"),
+                    Some(Ok(tungstenite::Message::Text(text))) => {
+                        self.handle_frame(text.as_str(), inbound)?;
+                    }
+                    Some(Ok(_)) => {}
+                }
+            }
+        };
+
+        tokio::select! {
+            result = write_loop => result.context("Mattermost WebSocket write failed"),
+            result = read_loop => result,
+        }
+    }
+
+    /// Handle one event stream frame.
+    fn handle_frame(
+        &self,
+        text: &str,
+        inbound: &mpsc::UnboundedSender<NormalizedMessage>,
+    ) -> Result<()> {
+        let Ok(frame) = serde_json::from_str::<Value>(text) else {
+            debug!("Ignoring non-JSON Mattermost frame");
+            return Ok(());
+        };
+
+        // Replies to our own actions carry `seq_reply`.
+        if frame.get("seq_reply").is_some() {
+            if frame["status"] == "FAIL" {
+                let message = frame["error"]["message"]
+                    .as_str()
+                    .unwrap_or("unknown error");
+                if frame["seq_reply"] == 1 {
+                    bail!("Mattermost WebSocket authentication failed: {message}");
+                }
+                debug!(error = %message, "Mattermost WebSocket action failed");
+            }
+            return Ok(());
+        }
+
+        match frame["event"].as_str() {
+            Some("hello") => info!(
+                server_version = ?frame["data"]["server_version"].as_str(),
+                "Mattermost event stream connected"
+            ),
+            Some("posted") => {
+                let Some(identity) = self.identity.read().clone() else {
+                    return Ok(());
+                };
+                let Some(msg) = normalize_posted(
+                    &self.account_id,
+                    self.server_url.as_deref().unwrap_or_default(),
+                    &frame["data"],
+                    &identity,
+                ) else {
+                    return Ok(());
+                };
+                if admissible(&msg, |msg| self.admit(msg)) {
+                    let _ = inbound.send(msg);
+                } else {
+                    debug!(channel_id = %msg.chat_id, sender = %msg.sender.id, "Mattermost post not admitted");
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Decide whether an inbound post should reach the agent.
+    fn admit(&self, msg: &NormalizedMessage) -> bool {
+        if msg.sender.is_bot {
+            return false;
+        }
+        match msg.chat_type {
+            // `dmPolicy` and `allowFrom` are applied by the admission stage.
+            ChatType::Dm => true,
+            ChatType::Group | ChatType::Thread => {
+                !self.config.require_mention.unwrap_or(true) || msg.mentioned
+            }
         }
     }
 
-    fn is_enabled(&self) -> bool {
-        self.enabled.unwrap_or(false)
+    /// Run the agent for an admitted post and reply.
+    async fn handle_message(self: Arc<Self>, state: GatewayState, mut msg: NormalizedMessage) {
+        if !admit_group_message(&state, &mut msg, |msg| self.admit(msg)).await {
+            return;
+        }
+        // Threads continue in place; channel posts start a thread unless
+        // `threadReplies` is off. DMs reply at the top level.
+        let root_id = match (&msg.thread_id, msg.chat_type) {
+            (Some(root), _) => Some(root.clone()),
+            (None, ChatType::Group) if self.config.thread_replies.unwrap_or(true) => {
+                Some(msg.id.clone())
+            }
+            _ => None,
+        };
+
+        let interval = typing_interval_ms(&*state.config.read().await, TYPING_INTERVAL_MS);
+        self.send_typing(&msg.chat_id, root_id.as_deref());
+        let typing = TypingKeepaliveLoop::new(interval);
+        let typing_task = {
+            let account = self.clone();
+            let channel_id = msg.chat_id.clone();
+            let root_id = root_id.clone();
+            typing.start(move || account.send_typing(&channel_id, root_id.as_deref()))
+        };
+
+        let result = dispatch_inbound(&state, &msg).await;
+        typing.stop();
+        typing_task.abort();
+
+        match result {
+            Ok(Some(reply)) => {
+                let outbound = NormalizedOutbound {
+                    thread_id: root_id,
+                    account_id: Some(self.account_id.clone()),
+                    ..NormalizedOutbound::text(msg.chat_id.clone(), reply)
+                };
+                if let Err(e) = state.channels.enqueue("mattermost", outbound).await {
+                    warn!(channel_id = %msg.chat_id, error = %e, "Mattermost reply failed");
+                }
+            }
+            Ok(None) => {}
+            Err(e) => warn!(channel_id = %msg.chat_id, error = %e, "Mattermost agent run failed"),
+        }
+    }
+
+    /// Create one post per chunk, optionally inside the `root_id` thread.
+    async fn send_text(&self, channel_id: &str, text: &str, root_id: Option<&str>) -> Result<()> {
+        let url = format!("{}/api/v4/posts", self.server_url()?);
+        let token = self.token()?;
+
+        for chunk in split_text(text, self.text_chunk_limit()) {
+            let mut body = json!({
+                "channel_id": channel_id,
+                "message": chunk,
+            });
+            if let Some(root_id) = root_id {
+                body["root_id"] = json!(root_id);
+            }
+
+            let resp = self
+                .http
+                .post(&url)
+                .bearer_auth(token)
+                .json(&body)
+                .send()
+                .await?;
+
+            if !resp.status().is_success() {
+                let status = resp.status();
+                let text = resp.text().await.unwrap_or_default();
+                bail!("Mattermost post creation failed ({}): {}", status, text);
+            }
+        }
+        Ok(())
     }
 }
 
@@ -59,11 +420,15 @@ impl ChannelPlugin for MattermostChannel {
         "mattermost"
     }
 
+    fn account_id(&self) -> &str {
+        &self.account.account_id
+    }
+
     fn meta(&self) -> ChannelMeta {
         ChannelMeta {
             name: "Mattermost".to_string(),
             description: "Mattermost channel via REST API v4".to_string(),
-            enabled: self.is_enabled(),
+            enabled: self.enabled,
             multi_account: true,
         }
     }
@@ -77,112 +442,458 @@ impl ChannelPlugin for MattermostChannel {
             ChannelCapability::Reactions,
             ChannelCapability::Groups,
             ChannelCapability::Threads,
+            ChannelCapability::TypingIndicators,
             ChannelCapability::EditMessage,
             ChannelCapability::DeleteMessage,
         ]
     }
 
-    async fn start_account(&self, _state: &GatewayState) -> Result<()> {
-        if !self.is_enabled() {
+    async fn start_account(&self, state: &GatewayState) -> Result<()> {
+        if !self.enabled {
             return Ok(());
         }
 
-        let server_url = match &self.server_url {
-            Some(url) => url,
-            None => {
-                warn!("Mattermost channel enabled but no server_url configured");
-                return Ok(());
-            }
+        let Some(server_url) = &self.account.server_url else {
+            warn!("Mattermost channel enabled but no server_url configured");
+            return Ok(());
         };
 
-        let token = match &self.token {
-            Some(t) => t,
-            None => {
-                warn!("Mattermost channel enabled but no token configured");
-                return Ok(());
-            }
-        };
+        if self.account.config.token.is_none() {
+            warn!("Mattermost channel enabled but no token configured");
+            return Ok(());
+        }
 
         info!(server_url = %server_url, "Mattermost channel starting");
 
-        // Verify credentials by calling the /users/me endpoint.
-        let me_url = format!(
-            "{}/api/v4/users/me",
-            server_url.trim_end_matches('/'),
-        );
-
-        match self
-            .client
-            .get(&me_url)
-            .header("Authorization", format!("Bearer {}", token))
-            .send()
+        let identity = self
+            .account
+            .resolve_identity()
             .await
-        {
-            Ok(resp) if resp.status().is_success() => {
-                let body: serde_json::Value = resp.json().await.unwrap_or_default();
-                let username = body["username"].as_str().unwrap_or("unknown");
-                info!(username = %username, "Mattermost: authenticated successfully");
-            }
-            Ok(resp) => {
-                warn!("Mattermost: auth check returned status {}", resp.status());
-            }
-            Err(e) => {
-                warn!("Mattermost: failed to verify credentials: {}", e);
-            }
-        }
+            .context("Mattermost credential check failed")?;
+        info!(username = %identity.username, "Mattermost: authenticated successfully");
 
-        // TODO: Set up a WebSocket connection to the Mattermost event stream
-        // at `wss://<server>/api/v4/websocket` to receive incoming messages.
+        let abort = AbortHandle::new();
+        let task = {
+            let account = self.account.clone();
+            let state = state.clone();
+            let abort = abort.clone();
+            tokio::spawn(async move {
+                let (inbound_tx, mut inbound_rx) = mpsc::unbounded_channel();
+                let dispatch = {
+                    let account = account.clone();
+                    async move {
+                        while let Some(msg) = inbound_rx.recv().await {
+                            tokio::spawn(account.clone().handle_message(state.clone(), msg));
+                        }
+                    }
+                };
+                let streaming = async { tokio::join!(account.run(inbound_tx), dispatch) };
+                if monitor_with_abort_lifecycle(streaming, &abort)
+                    .await
+                    .is_err()
+                {
+                    debug!("Mattermost event stream stopped");
+                }
+            })
+        };
+        if let Some((previous, _)) = self.poller.lock().replace((abort, task)) {
+            previous.abort();
+        }
 
         Ok(())
     }
 
     async fn stop_account(&self) -> Result<()> {
-        if self.is_enabled() {
+        if self.enabled {
             info!("Mattermost channel stopping");
-            // TODO: Close the WebSocket event stream connection.
+            let poller = self.poller.lock().take();
+            if let Some((abort, task)) = poller {
+                abort.abort();
+                let _ = task.await;
+            }
+            self.account.outbound.lock().take();
         }
         Ok(())
     }
 
     async fn send_message(&self, to: &str, message: &str) -> Result<()> {
-        let server_url = self
-            .server_url
-            .as_deref()
-            .ok_or_else(|| anyhow::anyhow!("Mattermost server_url not configured"))?;
+        self.send(&NormalizedOutbound::text(to, message)).await?;
+        Ok(())
+    }
 
-        let token = self
-            .token
-            .as_deref()
-            .ok_or_else(|| anyhow::anyhow!("Mattermost token not configured"))?;
+    async fn send(&self, message: &NormalizedOutbound) -> Result<Option<String>> {
+        if !message.attachments.is_empty() {
+            return Err(unsupported(self.id(), ChannelCapability::SendMedia));
+        }
+        // `chat_id` is a Mattermost channel ID (26-char alphanumeric string),
+        // optionally `channel_id:root_id` to post into a thread.
+        let to = message.chat_id.as_str();
+        let (channel_id, root_id) = match to.split_once(':') {
+            Some((channel_id, root_id)) if !root_id.is_empty() => (channel_id, Some(root_id)),
+            _ => (to, None),
+        };
+        let root_id = message.thread_id.as_deref().or(root_id);
 
-        // `to` is a Mattermost channel ID (26-char alphanumeric string).
-        let url = format!(
-            "{}/api/v4/posts",
-            server_url.trim_end_matches('/'),
-        );
+        info!(channel_id = %channel_id, "Mattermost: creating post");
+
+        self.account
+            .send_text(channel_id, &message.text, root_id)
+            .await?;
+        Ok(None)
+    }
+}
+
+// ============================================================================
+// Event Handling
+// ============================================================================
+
+/// Parse a JSON value that Mattermost sends as an encoded string.
+fn nested_json(value: &Value) -> Option<Value> {
+    match value {
+        Value::String(raw) => serde_json::from_str(raw).ok(),
+        Value::Null => None,
+        other => Some(other.clone()),
+    }
+}
+
+/// Strip a leading `@username` address from a post.
+fn strip_mention(text: &str, username: &str) -> String {
+    let trimmed = text.trim();
+    if let Some(rest) = trimmed.strip_prefix('@') {
+        if let Some(head) = rest.get(..username.len()) {
+            let tail = &rest[username.len()..];
+            let boundary =
+                !tail.starts_with(|c: char| c.is_alphanumeric() || matches!(c, '.' | '-' | '_'));
+            if head.eq_ignore_ascii_case(username) && boundary {
+                return tail.trim_start_matches([':', ',']).trim().to_string();
+            }
+        }
+    }
+    trimmed.to_string()
+}
+
+/// Convert the data of a `posted` event into a [`NormalizedMessage`].
+///
+/// The post itself is a JSON-encoded string inside the event. Returns `None`
+/// for our own posts, system posts and empty posts. `channel_type` `D` is a
+/// DM; replies (`root_id`) are threads.
+fn normalize_posted(
+    account_id: &str,
+    server_url: &str,
+    data: &Value,
+    identity: &MattermostIdentity,
+) -> Option<NormalizedMessage> {
+    let post = nested_json(&data["post"])?;
+    let user_id = post["user_id"].as_str()?;
+    if user_id == identity.user_id {
+        return None;
+    }
+    if post["type"]
+        .as_str()
+        .is_some_and(|kind| kind.starts_with("system_"))
+    {
+        return None;
+    }
+    let post_id = post["id"].as_str()?;
+    let channel_id = post["channel_id"].as_str()?;
+
+    let mentioned = nested_json(&data["mentions"])
+        .and_then(|m| m.as_array().cloned())
+        .is_some_and(|ids| ids.iter().any(|id| *id == identity.user_id.as_str()));
+    let text = strip_mention(
+        post["message"].as_str().unwrap_or_default(),
+        &identity.username,
+    );
+
+    let attachments: Vec<NormalizedAttachment> = post["metadata"]["files"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|file| {
+            let id = file["id"].as_str()?;
+            Some(NormalizedAttachment {
+                mime_type: file["mime_type"].as_str().map(str::to_string),
+                url: Some(format!("{server_url}/api/v4/files/{id}")),
+                data: None,
+                filename: file["name"].as_str().map(str::to_string),
+                size: file["size"].as_u64(),
+            })
+        })
+        .collect();
+    if text.is_empty() && attachments.is_empty() {
+        return None;
+    }
+
+    let thread_id = post["root_id"]
+        .as_str()
+        .filter(|root| !root.is_empty())
+        .map(str::to_string);
+    let chat_type = match (data["channel_type"].as_str(), &thread_id) {
+        (Some("D"), _) => ChatType::Dm,
+        (_, Some(_)) => ChatType::Thread,
+        _ => ChatType::Group,
+    };
+
+    let sender_name = data["sender_name"]
+        .as_str()
+        .map(|name| name.trim_start_matches('@'))
+        .filter(|name| !name.is_empty())
+        .unwrap_or(user_id);
+    let is_bot = post["props"]["from_bot"] == "true" || post["props"]["from_bot"] == true;
+    let timestamp = post["create_at"]
+        .as_i64()
+        .and_then(chrono::DateTime::from_timestamp_millis)
+        .unwrap_or_else(chrono::Utc::now)
+        .to_rfc3339();
 
-        let body = serde_json::json!({
-            "channel_id": to,
+    Some(NormalizedMessage {
+        id: post_id.to_string(),
+        channel: "mattermost".to_string(),
+        account_id: account_id.to_string(),
+        chat_id: channel_id.to_string(),
+        chat_name: data["channel_display_name"].as_str().map(str::to_string),
+        chat_type,
+        sender: NormalizedSender {
+            id: user_id.to_string(),
+            name: sender_name.to_string(),
+            is_bot,
+            roles: Vec::new(),
+        },
+        text,
+        attachments,
+        reply_to_id: None,
+        thread_id,
+        mentioned: mentioned || chat_type == ChatType::Dm,
+        timestamp,
+        raw: Some(post),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DmPolicy;
+    use tokio::net::TcpListener;
+    use wiremock::matchers::{body_partial_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn identity() -> MattermostIdentity {
+        MattermostIdentity {
+            user_id: "botid".to_string(),
+            username: "lobster".to_string(),
+        }
+    }
+
+    fn posted(channel_type: &str, message: &str, root_id: &str, mentions: &[&str]) -> Value {
+        let post = json!({
+            "id": "post1",
+            "create_at": 1_700_000_000_000i64,
+            "user_id": "userid",
+            "channel_id": "chan1",
+            "root_id": root_id,
             "message": message,
+            "type": "",
+            "props": {}
         });
+        json!({
+            "channel_type": channel_type,
+            "channel_display_name": "Town Square",
+            "sender_name": "@alice",
+            "post": post.to_string(),
+            "mentions": serde_json::to_string(mentions).unwrap(),
+        })
+    }
 
-        info!(channel_id = %to, "Mattermost: creating post");
+    fn channel_with(
+        server_url: &str,
+        configure: impl FnOnce(&mut MattermostAccountConfig),
+    ) -> MattermostChannel {
+        let mut config = Config::default();
+        let account = &mut config.channels.mattermost.default_account;
+        account.server_url = Some(server_url.to_string());
+        account.token = Some("tok".to_string());
+        configure(account);
+        let channel = MattermostChannel::new(&config);
+        *channel.account.identity.write() = Some(identity());
+        channel
+    }
 
-        let resp = self
-            .client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", token))
-            .json(&body)
-            .send()
-            .await?;
+    #[test]
+    fn normalize_channel_post_with_mention() {
+        let data = posted("O", "@lobster: status?", "", &["botid"]);
+        let msg = normalize_posted("default", "https://mm", &data, &identity()).unwrap();
+        assert_eq!(msg.chat_type, ChatType::Group);
+        assert_eq!(msg.chat_id, "chan1");
+        assert_eq!(msg.chat_name.as_deref(), Some("Town Square"));
+        assert_eq!(msg.sender.id, "userid");
+        assert_eq!(msg.sender.name, "alice");
+        assert!(msg.mentioned);
+        assert_eq!(msg.text, "status?");
 
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let text = resp.text().await.unwrap_or_default();
-            anyhow::bail!("Mattermost post creation failed ({}): {}", status, text);
-        }
+        let other = posted("O", "@lobsterfan hi", "", &[]);
+        let msg = normalize_posted("default", "https://mm", &other, &identity()).unwrap();
+        assert!(!msg.mentioned);
+        assert_eq!(msg.text, "@lobsterfan hi");
+    }
 
-        Ok(())
+    #[test]
+    fn normalize_dm_thread_and_skips() {
+        let dm = posted("D", "hello", "", &[]);
+        let msg = normalize_posted("default", "https://mm", &dm, &identity()).unwrap();
+        assert_eq!(msg.chat_type, ChatType::Dm);
+        assert!(msg.mentioned);
+
+        let reply = posted("O", "more", "root1", &[]);
+        let msg = normalize_posted("default", "https://mm", &reply, &identity()).unwrap();
+        assert_eq!(msg.chat_type, ChatType::Thread);
+        assert_eq!(msg.thread_id.as_deref(), Some("root1"));
+
+        let mut own = posted("O", "echo", "", &[]);
+        own["post"] = json!(
+            json!({"id": "p", "user_id": "botid", "channel_id": "c", "message": "x"}).to_string()
+        );
+        assert!(normalize_posted("default", "https://mm", &own, &identity()).is_none());
+
+        let mut system = posted("O", "joined", "", &[]);
+        system["post"] = json!(json!({
+            "id": "p", "user_id": "u", "channel_id": "c", "message": "joined", "type": "system_join_channel"
+        })
+        .to_string());
+        assert!(normalize_posted("default", "https://mm", &system, &identity()).is_none());
+    }
+
+    #[test]
+    fn admission_rules() {
+        let channel = channel_with("http://localhost", |account| {
+            account.dm_policy = Some(DmPolicy::Allowlist);
+            account.allow_from = Some(vec!["alice".to_string()]);
+        });
+        let dm = normalize_posted("default", "", &posted("D", "hi", "", &[]), &identity()).unwrap();
+        assert!(channel.account.admit(&dm));
+
+        let chatter =
+            normalize_posted("default", "", &posted("O", "hi all", "", &[]), &identity()).unwrap();
+        assert!(!channel.account.admit(&chatter));
+
+        let mut bot = dm.clone();
+        bot.sender.is_bot = true;
+        assert!(!channel.account.admit(&bot));
+    }
+
+    #[test]
+    fn websocket_url_from_server_url() {
+        let channel = channel_with("https://mm.example.com/", |_| {});
+        assert_eq!(
+            channel.account.websocket_url().unwrap(),
+            "wss://mm.example.com/api/v4/websocket"
+        );
+    }
+
+    #[tokio::test]
+    async fn event_stream_against_local_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let channel = channel_with(&format!("http://{addr}"), |_| {});
+        let account = channel.account.clone();
+
+        let (inbound_tx, mut inbound) = mpsc::unbounded_channel();
+        let session = {
+            let account = account.clone();
+            tokio::spawn(async move { account.session(&inbound_tx).await })
+        };
+
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        let next_json = |frame: Option<Result<tungstenite::Message, tungstenite::Error>>| {
+            let frame = frame.unwrap().unwrap();
+            serde_json::from_str::<Value>(frame.to_text().unwrap()).unwrap()
+        };
+
+        let auth = next_json(ws.next().await);
+        assert_eq!(auth["action"], "authentication_challenge");
+        assert_eq!(auth["data"]["token"], "tok");
+        ws.send(tungstenite::Message::Text(
+            json!({"status": "OK", "seq_reply": auth["seq"]})
+                .to_string()
+                .into(),
+        ))
+        .await
+        .unwrap();
+        ws.send(tungstenite::Message::Text(
+            json!({"event": "hello", "data": {"server_version": "9"}})
+                .to_string()
+                .into(),
+        ))
+        .await
+        .unwrap();
+        ws.send(tungstenite::Message::Text(
+            json!({"event": "posted", "data": posted("O", "@lobster ping", "", &["botid"])})
+                .to_string()
+                .into(),
+        ))
+        .await
+        .unwrap();
+
+        let msg = tokio::time::timeout(Duration::from_secs(5), inbound.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(msg.text, "ping");
+
+        account.send_typing("chan1", Some("post1"));
+        let typing = next_json(ws.next().await);
+        assert_eq!(typing["action"], "user_typing");
+        assert_eq!(typing["data"]["parent_id"], "post1");
+
+        ws.close(None).await.unwrap();
+        let result = tokio::time::timeout(Duration::from_secs(5), session)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn authentication_failure_ends_session() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let channel = channel_with(&format!("http://{addr}"), |_| {});
+        let account = channel.account.clone();
+
+        let (inbound_tx, _inbound) = mpsc::unbounded_channel();
+        let session = tokio::spawn(async move { account.session(&inbound_tx).await });
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        let _ = ws.next().await;
+        ws.send(tungstenite::Message::Text(
+            json!({"status": "FAIL", "seq_reply": 1, "error": {"message": "invalid token"}})
+                .to_string()
+                .into(),
+        ))
+        .await
+        .unwrap();
+
+        let err = session.await.unwrap().unwrap_err();
+        assert!(err.to_string().contains("authentication failed"));
+    }
+
+    #[tokio::test]
+    async fn send_message_posts_into_thread() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v4/posts"))
+            .and(header("authorization", "Bearer tok"))
+            .and(body_partial_json(json!({
+                "channel_id": "chan1",
+                "message": "hi",
+                "root_id": "root1"
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({"id": "p2"})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let channel = channel_with(&server.uri(), |_| {});
+        channel.send_message("chan1:root1", "hi").await.unwrap();
     }
 }
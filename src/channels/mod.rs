@@ -1,8 +1,15 @@
+mod ack;
+mod admission;
 mod bluebubbles;
+mod commands;
 mod discord;
+mod email;
 mod feishu;
+mod format;
 mod googlechat;
-mod imessage;
+mod group_history;
+mod health;
+mod inbound;
 mod irc;
 mod line;
 mod matrix;
@@ -10,6 +17,7 @@ mod mattermost;
 mod nextcloud;
 mod normalize;
 mod nostr;
+mod outbox;
 mod plugin;
 mod signal;
 mod slack;
@@ -18,18 +26,45 @@ mod teams;
 mod telegram;
 mod tlon;
 mod twitch;
+mod voice;
 mod webchat;
+pub mod webhook;
 mod whatsapp;
 mod zalo;
 mod zalouser;
 
-pub use plugin::{ChannelCapability, ChannelMeta, ChannelPlugin};
-
+pub use admission::{Admission, PairedSender, PairingRequest, Verdict};
+pub use format::{
+    render_markdown, render_markdown_chunks, render_signal, render_signal_chunks, FormatTarget,
+    StyleRange, StyledText, TextStyle,
+};
+pub use group_history::{GroupHistory, HistoryEntry};
+pub use health::{AccountHealth, AccountState, ChannelHealth, HealthEvent};
+pub use inbound::{
+    dispatch_inbound, resolve_session_key, routed_session_key, ActiveRuns, InboundEvent,
+    InboundSink, RunGuard,
+};
+pub use normalize::{
+    split_text, ChatType, NormalizedAttachment, NormalizedMessage, NormalizedOutbound,
+    NormalizedSender, OutboundButton,
+};
+pub use outbox::{Outbox, OutboxEntry};
+pub use plugin::{
+    unsupported, ChannelCapability, ChannelMeta, ChannelPlugin, UnsupportedCapability,
+    WebhookRequest, WebhookResponse, DEFAULT_ACCOUNT_ID,
+};
+pub use signal::SignalChannel;
+pub use webchat::{
+    WebChatChannel, WIDGET_HTML as WEBCHAT_WIDGET_HTML, WIDGET_JS as WEBCHAT_WIDGET_JS,
+};
+pub use whatsapp::{WhatsAppApiError, WhatsAppChannel, WhatsAppMediaKind};
+
+use self::health::{Supervisor, SupervisorTiming};
 use crate::config::Config;
 use crate::gateway::GatewayState;
 
 use anyhow::Result;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -177,39 +212,143 @@ pub fn resolve_active_run_queue_action(
 }
 
 // Re-export the send_message convenience function.
-pub use self::send::send_message;
+pub use self::send::{send_approval_request, send_message};
 
 mod send {
+    use super::{ChannelManager, NormalizedOutbound};
     use crate::config::Config;
     use anyhow::{bail, Result};
 
-    /// Send a message through a specific channel.
+    /// Send a message through a specific channel and wait for delivery.
     ///
-    /// This is a convenience wrapper that dispatches to the appropriate channel
-    /// implementation based on the `channel` argument (e.g. "telegram", "discord").
+    /// This is a convenience wrapper for callers without a running gateway
+    /// (CLI, FFI, agent tools): it builds the configured channels, queues the
+    /// message with [`ChannelManager::enqueue`] and delivers it through the
+    /// outbox, with the account's retries and send timeout. Returns the
+    /// outbox entry id once the platform accepts the message, or the last
+    /// error once the outbox gives up. `account_id` picks one of the
+    /// channel's configured accounts; `None` sends from the default account.
+    /// Channels that need a live connection (IRC, Twitch) fail unless it is
+    /// up.
     pub async fn send_message(
         config: &Config,
         channel: &str,
+        account_id: Option<&str>,
         to: &str,
         message: &str,
+    ) -> Result<String> {
+        let mut outbound = NormalizedOutbound::text(to, message);
+        outbound.account_id = account_id.map(str::to_string);
+        let channels = ChannelManager::detached(config);
+        let id = channels.enqueue(channel, outbound).await?;
+        channels.outbox().deliver(&channels, &id).await?;
+        Ok(id)
+    }
+
+    /// Ask for an exec approval in a chat, with buttons that resolve the
+    /// request (`request` as stored by `exec.approval.request`).
+    ///
+    /// Only Teams renders approval prompts (as an adaptive card).
+    pub async fn send_approval_request(
+        config: &Config,
+        channel: &str,
+        to: &str,
+        request: &serde_json::Value,
     ) -> Result<()> {
         match channel {
-            "telegram" => super::telegram::send_message(config, to, message).await,
-            "discord" => super::discord::send_message(config, to, message).await,
-            "slack" => super::slack::send_message(config, to, message).await,
-            "whatsapp" => super::whatsapp::send_message(config, to, message).await,
-            "signal" => super::signal::send_message(config, to, message).await,
-            "imessage" => super::imessage::send_message(config, to, message).await,
-            "synology_chat" => super::synology_chat::send_message(config, to, message).await,
-            other => bail!("unknown channel: {other}"),
+            "teams" => super::teams::send_approval(config, to, request).await,
+            other => bail!("approval prompts are not supported on {other}"),
         }
     }
 }
 
+/// Account ids configured for `channel`: `default` first, then the named
+/// entries of its `accounts` map for channels with multi-account support.
+fn configured_accounts(config: &Config, channel: &str) -> Vec<String> {
+    fn names<T>(accounts: &Option<HashMap<String, T>>) -> Vec<String> {
+        let mut names: Vec<String> = accounts
+            .iter()
+            .flat_map(|accounts| accounts.keys())
+            .filter(|id| id.as_str() != DEFAULT_ACCOUNT_ID)
+            .cloned()
+            .collect();
+        names.sort();
+        names
+    }
+
+    let channels = &config.channels;
+    let named = match channel {
+        "telegram" => names(&channels.telegram.accounts),
+        "discord" => names(&channels.discord.accounts),
+        "slack" => names(&channels.slack.accounts),
+        "whatsapp" => names(&channels.whatsapp.accounts),
+        "matrix" => names(&channels.matrix.accounts),
+        "mattermost" => names(&channels.mattermost.accounts),
+        "synology_chat" => channels
+            .synology_chat
+            .as_ref()
+            .map(|c| names(&c.accounts))
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    std::iter::once(DEFAULT_ACCOUNT_ID.to_string())
+        .chain(named)
+        .collect()
+}
+
+/// Builds the plugin instance for a named account of a multi-account channel.
+type AccountFactory = fn(&Config, &str) -> Arc<dyn ChannelPlugin>;
+
+/// Multi-account channels and how to build their per-account instances.
+const ACCOUNT_FACTORIES: [(&str, AccountFactory); 7] = [
+    ("telegram", |c, id| {
+        Arc::new(telegram::TelegramChannel::for_account(c, id))
+    }),
+    ("discord", |c, id| {
+        Arc::new(discord::DiscordChannel::for_account(c, id))
+    }),
+    ("slack", |c, id| {
+        Arc::new(slack::SlackChannel::for_account(c, id))
+    }),
+    ("whatsapp", |c, id| {
+        Arc::new(whatsapp::WhatsAppChannel::for_account(c, id))
+    }),
+    ("synology_chat", |c, id| {
+        Arc::new(synology_chat::SynologyChatChannel::for_account(c, id))
+    }),
+    ("matrix", |c, id| {
+        Arc::new(matrix::MatrixChannel::for_account(c, id))
+    }),
+    ("mattermost", |c, id| {
+        Arc::new(mattermost::MattermostChannel::for_account(c, id))
+    }),
+];
+
+/// Plugin instances of one channel keyed by account id.
+type ChannelAccounts = BTreeMap<String, Arc<dyn ChannelPlugin>>;
+
+/// The default account of a channel, or its first one if it has none.
+fn default_instance(accounts: &ChannelAccounts) -> Option<&Arc<dyn ChannelPlugin>> {
+    accounts
+        .get(DEFAULT_ACCOUNT_ID)
+        .or_else(|| accounts.values().next())
+}
+
 /// Manages all channel instances and their lifecycle.
 pub struct ChannelManager {
-    /// Registered channel plugins keyed by channel id (e.g. "telegram", "discord").
-    plugins: RwLock<HashMap<String, Arc<dyn ChannelPlugin>>>,
+    /// Registered channel plugins keyed by channel id (e.g. "telegram",
+    /// "discord"), then by account id. Single-account channels only have
+    /// the `default` account.
+    plugins: RwLock<HashMap<String, ChannelAccounts>>,
+    /// The WebChat channel, also registered in `plugins`; the gateway
+    /// serves its WebSocket endpoint directly.
+    webchat: Arc<WebChatChannel>,
+    /// Durable queue that outbound messages are delivered from.
+    outbox: Arc<Outbox>,
+    /// Health of every account, updated by the supervisors.
+    health: Arc<ChannelHealth>,
+    /// Supervisors of running accounts, keyed by `(channel, account id)`.
+    supervisors: parking_lot::Mutex<HashMap<(String, String), Supervisor>>,
     /// Snapshot of channel configuration at construction time.
     config: Config,
 }
@@ -244,7 +383,7 @@ impl ChannelManager {
         );
         plugins.insert(
             "imessage".to_string(),
-            Arc::new(imessage::IMessageChannel::new(config)),
+            Arc::new(bluebubbles::BlueBubblesChannel::imessage(config)),
         );
         plugins.insert(
             "synology_chat".to_string(),
@@ -254,44 +393,48 @@ impl ChannelManager {
         // New channel plugins (v2026.3.3).
         plugins.insert(
             "matrix".to_string(),
-            Arc::new(matrix::MatrixChannel::new()),
+            Arc::new(matrix::MatrixChannel::new(config)),
+        );
+        plugins.insert("irc".to_string(), Arc::new(irc::IrcChannel::new(config)));
+        plugins.insert(
+            "email".to_string(),
+            Arc::new(email::EmailChannel::new(config)),
         );
-        plugins.insert("irc".to_string(), Arc::new(irc::IrcChannel::new()));
         plugins.insert(
             "googlechat".to_string(),
-            Arc::new(googlechat::GoogleChatChannel::new()),
+            Arc::new(googlechat::GoogleChatChannel::new(config)),
         );
         plugins.insert(
             "teams".to_string(),
-            Arc::new(teams::TeamsChannel::new()),
+            Arc::new(teams::TeamsChannel::new(config)),
         );
         plugins.insert(
             "bluebubbles".to_string(),
-            Arc::new(bluebubbles::BlueBubblesChannel::new()),
+            Arc::new(bluebubbles::BlueBubblesChannel::new(config)),
         );
         plugins.insert(
             "line".to_string(),
-            Arc::new(line::LineChannel::new()),
+            Arc::new(line::LineChannel::new(config)),
         );
         plugins.insert(
             "mattermost".to_string(),
-            Arc::new(mattermost::MattermostChannel::new()),
+            Arc::new(mattermost::MattermostChannel::new(config)),
         );
         plugins.insert(
             "twitch".to_string(),
-            Arc::new(twitch::TwitchChannel::new()),
+            Arc::new(twitch::TwitchChannel::new(config)),
         );
         plugins.insert(
             "nostr".to_string(),
-            Arc::new(nostr::NostrChannel::new()),
+            Arc::new(nostr::NostrChannel::new(config)),
         );
         plugins.insert(
             "feishu".to_string(),
-            Arc::new(feishu::FeishuChannel::new()),
+            Arc::new(feishu::FeishuChannel::new(config)),
         );
         plugins.insert(
             "nextcloud".to_string(),
-            Arc::new(nextcloud::NextcloudChannel::new()),
+            Arc::new(nextcloud::NextcloudChannel::new(config)),
         );
         plugins.insert(
             "tlon".to_string(),
@@ -299,74 +442,211 @@ impl ChannelManager {
         );
         plugins.insert(
             "zalo".to_string(),
-            Arc::new(zalo::ZaloChannel::new()),
+            Arc::new(zalo::ZaloChannel::new(config)),
         );
         plugins.insert(
             "zalouser".to_string(),
-            Arc::new(zalouser::ZaloUserChannel::new()),
+            Arc::new(zalouser::ZaloUserChannel::new(config)),
         );
-        plugins.insert(
-            "webchat".to_string(),
-            Arc::new(webchat::WebChatChannel::new()),
+        let webchat = Arc::new(WebChatChannel::new(config));
+        plugins.insert("webchat".to_string(), webchat.clone());
+
+        // Every channel runs its default account; multi-account channels also
+        // get one instance per named account.
+        let mut plugins: HashMap<String, ChannelAccounts> = plugins
+            .into_iter()
+            .map(|(id, plugin)| {
+                (
+                    id,
+                    BTreeMap::from([(DEFAULT_ACCOUNT_ID.to_string(), plugin)]),
+                )
+            })
+            .collect();
+        for (channel, build) in ACCOUNT_FACTORIES {
+            let accounts = plugins.entry(channel.to_string()).or_default();
+            for account_id in configured_accounts(config, channel).into_iter().skip(1) {
+                let plugin = build(config, &account_id);
+                accounts.insert(account_id, plugin);
+            }
+        }
+
+        let outbox = Outbox::new(
+            Some(config.state_dir.join("channels").join("outbox.json")),
+            config,
         );
 
         Self {
             plugins: RwLock::new(plugins),
+            webchat,
+            outbox: Arc::new(outbox),
+            health: Arc::new(ChannelHealth::new()),
+            supervisors: parking_lot::Mutex::new(HashMap::new()),
             config: config.clone(),
         }
     }
 
+    /// A manager for one-off sends outside the gateway.
+    ///
+    /// Its outbox is kept in memory: `outbox.json` belongs to the gateway,
+    /// which rewrites the file from its own queue.
+    pub(crate) fn detached(config: &Config) -> Self {
+        Self {
+            outbox: Arc::new(Outbox::new(None, config)),
+            ..Self::new(config)
+        }
+    }
+
     /// Start all registered channel plugins that are enabled.
     ///
-    /// Each plugin's `start_account` method is invoked. Plugins that fail to
-    /// start are logged but do not prevent other channels from starting.
+    /// Each account runs under a health supervisor that starts it, probes it
+    /// and restarts it when it fails (see [`ChannelHealth`]). Accounts start in the
+    /// background; failures show up in [`get_status`](Self::get_status).
     pub async fn start_all(&self, state: &GatewayState) -> Result<()> {
-        let plugins = self.plugins.read().await;
-        for (id, plugin) in plugins.iter() {
-            let meta = plugin.meta();
-            if !meta.enabled {
-                info!(channel = %id, "Channel disabled, skipping");
+        // Recover queued messages before channels start queueing replies.
+        self.outbox
+            .start(state.channels.clone(), state.shutdown_tx.subscribe());
+
+        let plugins: Vec<Arc<dyn ChannelPlugin>> = {
+            let plugins = self.plugins.read().await;
+            plugins.values().flat_map(|a| a.values().cloned()).collect()
+        };
+        for plugin in plugins {
+            let (id, account_id) = (plugin.id(), plugin.account_id());
+            if !plugin.meta().enabled {
+                info!(channel = %id, account = %account_id, "Channel disabled, skipping");
                 continue;
             }
-            info!(channel = %id, "Starting channel");
-            if let Err(e) = plugin.start_account(state).await {
-                warn!(channel = %id, error = %e, "Failed to start channel");
-            }
+            info!(channel = %id, account = %account_id, "Starting channel");
+            self.supervise(state, plugin.clone()).await;
         }
         Ok(())
     }
 
     /// Stop all running channel plugins.
     pub async fn stop_all(&self) -> Result<()> {
-        let plugins = self.plugins.read().await;
-        for (id, plugin) in plugins.iter() {
-            info!(channel = %id, "Stopping channel");
-            if let Err(e) = plugin.stop_account().await {
-                warn!(channel = %id, error = %e, "Failed to stop channel");
-            }
+        self.outbox.stop();
+        let plugins: Vec<Arc<dyn ChannelPlugin>> = {
+            let plugins = self.plugins.read().await;
+            plugins.values().flat_map(|a| a.values().cloned()).collect()
+        };
+        for plugin in plugins {
+            self.stop_plugin(&plugin).await;
         }
         Ok(())
     }
 
+    /// Stop one account and its supervisor; it stays stopped until
+    /// restarted.
+    pub async fn stop_account(&self, channel: &str, account_id: &str) -> Result<()> {
+        let Some(plugin) = self.get_account(channel, account_id).await else {
+            anyhow::bail!("unknown account {account_id} on channel {channel}");
+        };
+        self.stop_plugin(&plugin).await;
+        Ok(())
+    }
+
+    /// Stop an account and start it again under a fresh supervisor.
+    pub async fn restart_account(
+        &self,
+        state: &GatewayState,
+        channel: &str,
+        account_id: &str,
+    ) -> Result<()> {
+        let Some(plugin) = self.get_account(channel, account_id).await else {
+            anyhow::bail!("unknown account {account_id} on channel {channel}");
+        };
+        if !plugin.meta().enabled {
+            anyhow::bail!("channel {channel} is not enabled");
+        }
+        self.stop_plugin(&plugin).await;
+        info!(channel = %channel, account = %account_id, "Restarting channel");
+        self.supervise(state, plugin).await;
+        Ok(())
+    }
+
+    /// Run `plugin` under a new supervisor, replacing any running one.
+    async fn supervise(&self, state: &GatewayState, plugin: Arc<dyn ChannelPlugin>) {
+        let key = (plugin.id().to_string(), plugin.account_id().to_string());
+        let start = {
+            let (plugin, state) = (plugin.clone(), state.clone());
+            move || {
+                let (plugin, state) = (plugin.clone(), state.clone());
+                async move { plugin.start_account(&state).await }
+            }
+        };
+        let supervisor = Supervisor::spawn(
+            plugin,
+            self.health.clone(),
+            SupervisorTiming::default(),
+            start,
+        );
+        let previous = self.supervisors.lock().insert(key, supervisor);
+        if let Some(previous) = previous {
+            previous.stop().await;
+        }
+    }
+
+    async fn stop_plugin(&self, plugin: &Arc<dyn ChannelPlugin>) {
+        let (id, account_id) = (plugin.id(), plugin.account_id());
+        let supervisor = self
+            .supervisors
+            .lock()
+            .remove(&(id.to_string(), account_id.to_string()));
+        if let Some(supervisor) = supervisor {
+            supervisor.stop().await;
+        }
+        info!(channel = %id, account = %account_id, "Stopping channel");
+        if let Err(e) = plugin.stop_account().await {
+            warn!(channel = %id, account = %account_id, error = %e, "Failed to stop channel");
+        }
+        self.health.stopped(id, account_id);
+    }
+
     /// Return a JSON status summary of all channels.
+    ///
+    /// A channel is enabled when any of its accounts is; `accounts` reports
+    /// each account's [`AccountHealth`] on its own.
     pub async fn get_status(&self) -> serde_json::Value {
         let plugins = self.plugins.read().await;
         let mut status = serde_json::Map::new();
 
-        for (id, plugin) in plugins.iter() {
+        for (id, accounts) in plugins.iter() {
+            let Some(plugin) = default_instance(accounts) else {
+                continue;
+            };
             let meta = plugin.meta();
             let capabilities: Vec<String> = plugin
                 .capabilities()
                 .iter()
                 .map(|c| format!("{c:?}"))
                 .collect();
+            let webhooks: Vec<String> = accounts
+                .values()
+                .flat_map(|p| p.webhook_routes())
+                .map(|r| format!("{} /channels/{id}/{}", r.method, r.path))
+                .collect();
+            let account_status: serde_json::Map<String, serde_json::Value> = accounts
+                .iter()
+                .map(|(account_id, p)| {
+                    let mut account = serde_json::json!({ "enabled": p.meta().enabled });
+                    if let Ok(serde_json::Value::Object(health)) =
+                        serde_json::to_value(self.health.get(id, account_id))
+                    {
+                        account.as_object_mut().unwrap().extend(health);
+                    }
+                    (account_id.clone(), account)
+                })
+                .collect();
 
             status.insert(
                 id.clone(),
                 serde_json::json!({
                     "name": meta.name,
-                    "enabled": meta.enabled,
+                    "enabled": accounts.values().any(|p| p.meta().enabled),
                     "capabilities": capabilities,
+                    "webhooks": webhooks,
+                    "accounts": account_status,
+                    "outbox": self.outbox.status(id),
                 }),
             );
         }
@@ -374,8 +654,302 @@ impl ChannelManager {
         serde_json::Value::Object(status)
     }
 
-    /// Look up a channel plugin by id.
+    /// Register a channel plugin under its id and account, replacing any
+    /// existing instance of that account.
+    pub async fn register(&self, plugin: Arc<dyn ChannelPlugin>) {
+        self.plugins
+            .write()
+            .await
+            .entry(plugin.id().to_string())
+            .or_default()
+            .insert(plugin.account_id().to_string(), plugin);
+    }
+
+    /// Look up a channel plugin by id, returning its default account.
     pub async fn get_plugin(&self, id: &str) -> Option<Arc<dyn ChannelPlugin>> {
-        self.plugins.read().await.get(id).cloned()
+        self.plugins
+            .read()
+            .await
+            .get(id)
+            .and_then(default_instance)
+            .cloned()
+    }
+
+    /// Look up one account of a channel.
+    pub async fn get_account(
+        &self,
+        channel: &str,
+        account_id: &str,
+    ) -> Option<Arc<dyn ChannelPlugin>> {
+        self.plugins
+            .read()
+            .await
+            .get(channel)?
+            .get(account_id)
+            .cloned()
+    }
+
+    /// All accounts of a channel, ordered by account id.
+    pub async fn accounts(&self, channel: &str) -> Vec<Arc<dyn ChannelPlugin>> {
+        self.plugins
+            .read()
+            .await
+            .get(channel)
+            .map(|accounts| accounts.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// The account of `channel` serving a webhook at `path`: the one whose
+    /// routes include the path, else the default account.
+    pub async fn webhook_plugin(
+        &self,
+        channel: &str,
+        path: &str,
+    ) -> Option<Arc<dyn ChannelPlugin>> {
+        let plugins = self.plugins.read().await;
+        let accounts = plugins.get(channel)?;
+        let path = path.trim_matches('/');
+        accounts
+            .values()
+            .find(|p| p.webhook_routes().iter().any(|r| r.path == path))
+            .or_else(|| default_instance(accounts))
+            .cloned()
+    }
+
+    /// Health of every channel account.
+    pub fn health(&self) -> Arc<ChannelHealth> {
+        self.health.clone()
+    }
+
+    /// The built-in WebChat channel.
+    pub fn webchat(&self) -> Arc<WebChatChannel> {
+        self.webchat.clone()
+    }
+
+    // ------------------------------------------------------------------------
+    // Outbound operations
+    //
+    // These check the plugin's advertised capabilities first, so callers get
+    // an `UnsupportedCapability` error without a platform round trip.
+    // ------------------------------------------------------------------------
+
+    /// Look up an enabled channel account that advertises `capability`.
+    async fn plugin_supporting(
+        &self,
+        channel: &str,
+        account_id: &str,
+        capability: ChannelCapability,
+    ) -> Result<Arc<dyn ChannelPlugin>> {
+        let Some(plugin) = self.get_account(channel, account_id).await else {
+            if self.get_plugin(channel).await.is_some() {
+                anyhow::bail!("unknown account {account_id} on channel {channel}");
+            }
+            anyhow::bail!("unknown channel: {channel}");
+        };
+        if !plugin.meta().enabled {
+            anyhow::bail!("channel {channel} is not enabled");
+        }
+        if !plugin.supports(capability) {
+            return Err(unsupported(channel, capability));
+        }
+        Ok(plugin)
+    }
+
+    /// Queue a message for delivery on `channel` through the outbox.
+    ///
+    /// The message goes out from its `account_id`. Returns the outbox entry
+    /// id once the message is persisted; delivery, retries and failures are
+    /// reported in [`get_status`](Self::get_status).
+    pub async fn enqueue(&self, channel: &str, message: NormalizedOutbound) -> Result<String> {
+        self.plugin_supporting(channel, message.account(), ChannelCapability::SendText)
+            .await?;
+        self.outbox.enqueue(channel, message).await
+    }
+
+    /// The outbound delivery queue.
+    pub fn outbox(&self) -> Arc<Outbox> {
+        self.outbox.clone()
+    }
+
+    /// Send a structured message from its `account_id`, returning the
+    /// platform message id if known.
+    pub async fn send(
+        &self,
+        channel: &str,
+        message: &NormalizedOutbound,
+    ) -> Result<Option<String>> {
+        let capability = if message.attachments.is_empty() {
+            ChannelCapability::SendText
+        } else {
+            ChannelCapability::SendMedia
+        };
+        let plugin = self
+            .plugin_supporting(channel, message.account(), capability)
+            .await?;
+        let id = plugin.send(message).await?;
+        self.health.record_outbound(channel, message.account());
+        Ok(id)
+    }
+
+    /// Edit a message sent by the bot.
+    pub async fn edit_message(
+        &self,
+        channel: &str,
+        account_id: &str,
+        chat_id: &str,
+        message_id: &str,
+        text: &str,
+    ) -> Result<()> {
+        self.plugin_supporting(channel, account_id, ChannelCapability::EditMessage)
+            .await?
+            .edit_message(chat_id, message_id, text)
+            .await
+    }
+
+    /// Delete a message.
+    pub async fn delete_message(
+        &self,
+        channel: &str,
+        account_id: &str,
+        chat_id: &str,
+        message_id: &str,
+    ) -> Result<()> {
+        self.plugin_supporting(channel, account_id, ChannelCapability::DeleteMessage)
+            .await?
+            .delete_message(chat_id, message_id)
+            .await
+    }
+
+    /// React to a message; an empty `emoji` removes the reaction.
+    pub async fn react(
+        &self,
+        channel: &str,
+        account_id: &str,
+        chat_id: &str,
+        message_id: &str,
+        emoji: &str,
+    ) -> Result<()> {
+        self.plugin_supporting(channel, account_id, ChannelCapability::Reactions)
+            .await?
+            .react(chat_id, message_id, emoji)
+            .await
+    }
+
+    /// Show a typing indicator.
+    pub async fn send_typing(
+        &self,
+        channel: &str,
+        account_id: &str,
+        chat_id: &str,
+        thread_id: Option<&str>,
+    ) -> Result<()> {
+        self.plugin_supporting(channel, account_id, ChannelCapability::TypingIndicators)
+            .await?
+            .send_typing(chat_id, thread_id)
+            .await
+    }
+
+    /// Mark a message as read.
+    pub async fn mark_read(
+        &self,
+        channel: &str,
+        account_id: &str,
+        chat_id: &str,
+        message_id: &str,
+    ) -> Result<()> {
+        self.plugin_supporting(channel, account_id, ChannelCapability::ReadReceipts)
+            .await?
+            .mark_read(chat_id, message_id)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TelegramAccountConfig;
+
+    fn telegram_accounts() -> Config {
+        let mut config = Config::default();
+        let telegram = &mut config.channels.telegram;
+        telegram.default_account.bot_token = Some("1:default".to_string());
+        telegram.default_account.webhook_url = Some("https://bot.example.com/default".to_string());
+        let work = TelegramAccountConfig {
+            bot_token: Some("2:work".to_string()),
+            webhook_url: Some("https://bot.example.com/work".to_string()),
+            ..Default::default()
+        };
+        telegram.accounts = Some(HashMap::from([("work".to_string(), work)]));
+        config
+    }
+
+    #[tokio::test]
+    async fn named_accounts_get_their_own_instances() {
+        let manager = ChannelManager::new(&telegram_accounts());
+        let ids: Vec<String> = manager
+            .accounts("telegram")
+            .await
+            .iter()
+            .map(|p| p.account_id().to_string())
+            .collect();
+        assert_eq!(ids, ["default", "work"]);
+        assert_eq!(
+            manager.get_plugin("telegram").await.unwrap().account_id(),
+            DEFAULT_ACCOUNT_ID
+        );
+        assert!(manager.get_account("telegram", "home").await.is_none());
+
+        let status = manager.get_status().await;
+        assert_eq!(status["telegram"]["accounts"]["work"]["enabled"], true);
+        assert_eq!(status["telegram"]["accounts"]["work"]["state"], "stopped");
+        assert_eq!(manager.accounts("signal").await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn webhooks_route_to_the_matching_account() {
+        let manager = ChannelManager::new(&telegram_accounts());
+        let work = manager
+            .webhook_plugin("telegram", "/webhook/work")
+            .await
+            .unwrap();
+        assert_eq!(work.account_id(), "work");
+        let default = manager.webhook_plugin("telegram", "webhook").await.unwrap();
+        assert_eq!(default.account_id(), DEFAULT_ACCOUNT_ID);
+    }
+
+    #[tokio::test]
+    async fn sends_reject_unknown_accounts() {
+        let config = telegram_accounts();
+        let error = send_message(&config, "telegram", Some("home"), "1", "hi")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unknown account home on channel telegram"
+        );
+        assert!(send_message(&config, "signal", Some("work"), "1", "hi")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn sends_reach_every_registered_channel() {
+        let mut config = Config::default();
+        config.channels.irc = Some(crate::config::IrcConfig {
+            enabled: Some(true),
+            ..Default::default()
+        });
+        let error = send_message(&config, "irc", None, "#lobster", "hi")
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("not connected"), "{error}");
+        let error = send_message(&config, "nostr", None, "npub1x", "hi")
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "channel nostr is not enabled");
+        let error = send_message(&config, "carrier-pigeon", None, "x", "hi")
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "unknown channel: carrier-pigeon");
     }
 }
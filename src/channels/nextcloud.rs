@@ -1,62 +1,140 @@
-use super::plugin::{ChannelCapability, ChannelMeta, ChannelPlugin};
+use super::normalize::{ChatType, NormalizedMessage, NormalizedOutbound, NormalizedSender};
+use super::plugin::{
+    unsupported, ChannelCapability, ChannelMeta, ChannelPlugin, WebhookRequest, WebhookResponse,
+};
+use super::webhook::{self, ReplayGuard, WebhookRoute};
+use crate::config::{Config, NextcloudTalkConfig};
 use crate::gateway::GatewayState;
+use crate::infra::dm_policy;
 
 use anyhow::Result;
 use async_trait::async_trait;
+use axum::http::StatusCode;
+use rand::Rng;
 use reqwest::Client;
-use tracing::{info, warn};
+use serde_json::Value;
+use tracing::{debug, info, warn};
 
 // ============================================================================
 // Nextcloud Talk Channel Implementation
 // ============================================================================
 
-/// Nextcloud Talk channel integration via the OCS API.
+/// Nextcloud Talk channel integration via the Talk bot API.
 ///
-/// Communicates with a Nextcloud instance using the Talk API (OCS format).
-/// Messages are sent via
-/// `POST /ocs/v2.php/apps/spreed/api/v1/chat/{token}`.
+/// The bot is installed on the Nextcloud instance with
+/// `occ talk:bot:install <name> <secret> <url>`, where `<url>` is
+/// `https://<gateway>/channels/nextcloud/<webhookPath>`. Talk then posts
+/// ActivityStreams events for every message in rooms the bot is added to,
+/// signed with `X-Nextcloud-Talk-Signature` (hex `HMAC-SHA256(secret,
+/// random + body)`), and the bot replies via
+/// `POST /ocs/v2.php/apps/spreed/api/v1/bot/{token}/message` signed the same
+/// way over the message text.
 ///
-/// Authentication uses either a Nextcloud app password or a bot-specific
-/// token. All API calls require the `OCS-APIRequest: true` header.
-///
-/// API docs: <https://nextcloud-talk.readthedocs.io/en/latest/>
+/// API docs: <https://nextcloud-talk.readthedocs.io/en/latest/bots/>
 pub struct NextcloudChannel {
-    /// Nextcloud server URL (e.g. `https://cloud.example.com`).
-    server_url: Option<String>,
-    /// Authentication token (app password or bot token).
-    token: Option<String>,
-    /// Nextcloud username for basic auth.
-    username: Option<String>,
-    /// Whether this channel is enabled.
-    enabled: Option<bool>,
-    /// HTTP client for API calls.
-    client: Client,
+    enabled: bool,
+    config: NextcloudTalkConfig,
+    /// Bot API client.
+    api: TalkBotApi,
+    /// Message ids already handled.
+    replay: ReplayGuard,
 }
 
+const DEFAULT_WEBHOOK_PATH: &str = "webhook";
+const SIGNATURE_HEADER: &str = "x-nextcloud-talk-signature";
+const RANDOM_HEADER: &str = "x-nextcloud-talk-random";
+
 impl NextcloudChannel {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
+        let nextcloud = config.channels.nextcloud.clone().unwrap_or_default();
         Self {
-            server_url: None,
-            token: None,
-            username: None,
-            enabled: None,
-            client: Client::new(),
+            enabled: nextcloud.enabled.unwrap_or(false),
+            api: TalkBotApi {
+                client: Client::new(),
+                server_url: nextcloud.server_url.clone(),
+                secret: nextcloud.bot_secret.clone(),
+            },
+            config: nextcloud,
+            replay: ReplayGuard::default(),
         }
     }
 
-    /// Create a configured Nextcloud Talk channel.
-    pub fn with_config(server_url: String, username: String, token: String) -> Self {
-        Self {
-            server_url: Some(server_url),
-            token: Some(token),
-            username: Some(username),
-            enabled: Some(true),
-            client: Client::new(),
-        }
+    fn webhook_path(&self) -> &str {
+        webhook::webhook_path(self.config.webhook_path.as_deref(), DEFAULT_WEBHOOK_PATH)
     }
 
-    fn is_enabled(&self) -> bool {
-        self.enabled.unwrap_or(false)
+    /// Validate `X-Nextcloud-Talk-Signature` against the shared secret.
+    fn signature_valid(&self, request: &WebhookRequest) -> bool {
+        let (Some(secret), Some(signature), Some(random)) = (
+            self.config.bot_secret.as_deref(),
+            request.header(SIGNATURE_HEADER),
+            request.header(RANDOM_HEADER),
+        ) else {
+            return false;
+        };
+        let digest = webhook::hmac_sha256(secret.as_bytes(), &[random.as_bytes(), &request.body]);
+        webhook::digest_matches_hex(&digest, signature)
+    }
+}
+
+/// Talk bot API client; requests are signed with the bot secret.
+#[derive(Clone)]
+struct TalkBotApi {
+    client: Client,
+    server_url: Option<String>,
+    secret: Option<String>,
+}
+
+impl TalkBotApi {
+    /// Post `message` to the room with `token`, optionally replying to a
+    /// message id.
+    async fn send(&self, token: &str, message: &str, reply_to: Option<&str>) -> Result<()> {
+        let server_url = self
+            .server_url
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Nextcloud Talk server_url not configured"))?;
+        let secret = self
+            .secret
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Nextcloud Talk bot_secret not configured"))?;
+
+        let url = format!(
+            "{}/ocs/v2.php/apps/spreed/api/v1/bot/{}/message",
+            server_url.trim_end_matches('/'),
+            token,
+        );
+
+        let random = hex::encode(rand::thread_rng().gen::<[u8; 32]>());
+        let signature = hex::encode(webhook::hmac_sha256(
+            secret.as_bytes(),
+            &[random.as_bytes(), message.as_bytes()],
+        ));
+        let mut body = serde_json::json!({
+            "message": message,
+            "referenceId": hex::encode(webhook::sha256(&[random.as_bytes()])),
+        });
+        if let Some(reply_to) = reply_to.and_then(|id| id.parse::<i64>().ok()) {
+            body["replyTo"] = reply_to.into();
+        }
+
+        let resp = self
+            .client
+            .post(&url)
+            .header("OCS-APIRequest", "true")
+            .header("Accept", "application/json")
+            .header("X-Nextcloud-Talk-Bot-Random", &random)
+            .header("X-Nextcloud-Talk-Bot-Signature", &signature)
+            .json(&body)
+            .send()
+            .await?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            anyhow::bail!("Nextcloud Talk send message failed ({}): {}", status, text);
+        }
+
+        Ok(())
     }
 }
 
@@ -69,8 +147,8 @@ impl ChannelPlugin for NextcloudChannel {
     fn meta(&self) -> ChannelMeta {
         ChannelMeta {
             name: "Nextcloud Talk".to_string(),
-            description: "Nextcloud Talk channel via OCS API".to_string(),
-            enabled: self.is_enabled(),
+            description: "Nextcloud Talk channel via the Talk bot API".to_string(),
+            enabled: self.enabled,
             multi_account: false,
         }
     }
@@ -86,119 +164,208 @@ impl ChannelPlugin for NextcloudChannel {
         ]
     }
 
+    fn webhook_routes(&self) -> Vec<WebhookRoute> {
+        if !self.enabled {
+            return Vec::new();
+        }
+        vec![WebhookRoute::post(self.webhook_path())]
+    }
+
     async fn start_account(&self, _state: &GatewayState) -> Result<()> {
-        if !self.is_enabled() {
+        if !self.enabled {
             return Ok(());
         }
 
-        let server_url = match &self.server_url {
-            Some(url) => url,
-            None => {
-                warn!("Nextcloud Talk channel enabled but no server_url configured");
-                return Ok(());
-            }
+        let Some(server_url) = &self.config.server_url else {
+            warn!("Nextcloud Talk channel enabled but no server_url configured");
+            return Ok(());
         };
 
-        if self.token.is_none() {
-            warn!("Nextcloud Talk channel enabled but no token configured");
+        if self.config.bot_secret.is_none() {
+            warn!("Nextcloud Talk channel enabled but no bot_secret configured");
             return Ok(());
         }
 
-        info!(server_url = %server_url, "Nextcloud Talk channel starting");
-
-        // Verify connectivity by calling the capabilities endpoint.
-        let caps_url = format!(
-            "{}/ocs/v2.php/cloud/capabilities",
-            server_url.trim_end_matches('/'),
+        info!(
+            server_url = %server_url,
+            path = %self.webhook_path(),
+            "Nextcloud Talk channel starting"
         );
 
-        let username = self.username.as_deref().unwrap_or("bot");
-        let token = self.token.as_deref().unwrap_or_default();
-
-        match self
-            .client
-            .get(&caps_url)
-            .basic_auth(username, Some(token))
-            .header("OCS-APIRequest", "true")
-            .header("Accept", "application/json")
-            .send()
-            .await
-        {
-            Ok(resp) if resp.status().is_success() => {
-                info!("Nextcloud Talk: server capabilities endpoint reachable");
-            }
-            Ok(resp) => {
-                warn!(
-                    "Nextcloud Talk: capabilities returned status {}",
-                    resp.status()
-                );
-            }
-            Err(e) => {
-                warn!("Nextcloud Talk: failed to reach server: {}", e);
-            }
-        }
-
-        // TODO: Set up long-polling or SSE endpoint to receive incoming messages.
-
         Ok(())
     }
 
     async fn stop_account(&self) -> Result<()> {
-        if self.is_enabled() {
+        if self.enabled {
             info!("Nextcloud Talk channel stopping");
         }
         Ok(())
     }
 
     async fn send_message(&self, to: &str, message: &str) -> Result<()> {
-        let server_url = self
-            .server_url
-            .as_deref()
-            .ok_or_else(|| anyhow::anyhow!("Nextcloud Talk server_url not configured"))?;
+        // `to` is a Nextcloud Talk conversation token (e.g. "abc123xy").
+        info!(conversation = %to, "Nextcloud Talk: sending message");
+        self.api.send(to, message, None).await
+    }
 
-        let username = self
-            .username
-            .as_deref()
-            .ok_or_else(|| anyhow::anyhow!("Nextcloud Talk username not configured"))?;
+    async fn send(&self, message: &NormalizedOutbound) -> Result<Option<String>> {
+        if !message.attachments.is_empty() {
+            return Err(unsupported(self.id(), ChannelCapability::SendMedia));
+        }
+        self.api
+            .send(
+                &message.chat_id,
+                &message.text,
+                message.reply_to_id.as_deref(),
+            )
+            .await?;
+        Ok(None)
+    }
 
-        let token = self
-            .token
-            .as_deref()
-            .ok_or_else(|| anyhow::anyhow!("Nextcloud Talk token not configured"))?;
+    async fn handle_webhook(
+        &self,
+        state: &GatewayState,
+        request: WebhookRequest,
+    ) -> Result<WebhookResponse> {
+        if !self.enabled {
+            return Ok(WebhookResponse::not_found());
+        }
+        if let Some(response) = webhook::route_check(&self.webhook_routes(), &request) {
+            return Ok(response);
+        }
+        if !self.signature_valid(&request) {
+            warn!("Nextcloud Talk webhook request with invalid signature");
+            return Ok(WebhookResponse::status(StatusCode::UNAUTHORIZED));
+        }
+        let Ok(event) = serde_json::from_slice::<Value>(&request.body) else {
+            return Ok(WebhookResponse::status(StatusCode::BAD_REQUEST));
+        };
 
-        // `to` is a Nextcloud Talk conversation token (e.g. "abc123xy").
-        let url = format!(
-            "{}/ocs/v2.php/apps/spreed/api/v1/chat/{}",
-            server_url.trim_end_matches('/'),
-            to,
-        );
+        let Some(msg) = normalize_event(&event) else {
+            return Ok(WebhookResponse::ok());
+        };
+        if !self
+            .replay
+            .first_delivery(&format!("{}/{}", msg.chat_id, msg.id))
+        {
+            debug!(message = %msg.id, "Skipping redelivered Nextcloud Talk message");
+            return Ok(WebhookResponse::ok());
+        }
+        let allow_from = self.config.allow_from.as_deref().unwrap_or_default();
+        if !dm_policy::is_source_allowed(allow_from, &msg.sender.id) {
+            debug!(actor = %msg.sender.id, "Nextcloud Talk message not admitted");
+            return Ok(WebhookResponse::ok());
+        }
 
-        let body = serde_json::json!({
-            "message": message,
+        webhook::spawn_dispatch(state, msg, |msg, text| NormalizedOutbound {
+            reply_to_id: Some(msg.id.clone()),
+            ..NormalizedOutbound::text(msg.chat_id.clone(), text)
         });
+        Ok(WebhookResponse::ok())
+    }
+}
 
-        info!(conversation = %to, "Nextcloud Talk: sending message");
-
-        let resp = self
-            .client
-            .post(&url)
-            .basic_auth(username, Some(token))
-            .header("OCS-APIRequest", "true")
-            .header("Accept", "application/json")
-            .json(&body)
-            .send()
-            .await?;
+/// Convert a Talk bot `Create` activity into a [`NormalizedMessage`].
+///
+/// `object.content` is itself JSON: a message with `{placeholder}`s that
+/// are filled in from its rich-object `parameters`.
+fn normalize_event(event: &Value) -> Option<NormalizedMessage> {
+    if event["type"] != "Create" || event["actor"]["type"] == "Application" {
+        return None;
+    }
+    let actor = &event["actor"];
+    let object = &event["object"];
+    let target = &event["target"];
 
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let text = resp.text().await.unwrap_or_default();
-            anyhow::bail!(
-                "Nextcloud Talk send message failed ({}): {}",
-                status,
-                text
-            );
+    let content: Value = serde_json::from_str(object["content"].as_str()?).ok()?;
+    let mut text = content["message"].as_str().unwrap_or_default().to_string();
+    if let Some(parameters) = content["parameters"].as_object() {
+        for (key, parameter) in parameters {
+            let name = parameter["name"].as_str().unwrap_or_default();
+            let replacement = match parameter["type"].as_str() {
+                Some("user") | Some("guest") => format!("@{}", name),
+                _ => name.to_string(),
+            };
+            text = text.replace(&format!("{{{}}}", key), &replacement);
         }
+    }
+    let text = text.trim().to_string();
+    if text.is_empty() {
+        return None;
+    }
 
-        Ok(())
+    let sender_id = actor["id"].as_str()?.to_string();
+    Some(NormalizedMessage {
+        id: object["id"].as_str()?.to_string(),
+        channel: "nextcloud".to_string(),
+        account_id: "default".to_string(),
+        chat_id: target["id"].as_str()?.to_string(),
+        chat_name: target["name"].as_str().map(str::to_string),
+        chat_type: ChatType::Group,
+        sender: NormalizedSender {
+            id: sender_id.clone(),
+            name: actor["name"].as_str().unwrap_or(&sender_id).to_string(),
+            is_bot: false,
+            roles: Vec::new(),
+        },
+        text,
+        attachments: Vec::new(),
+        reply_to_id: None,
+        thread_id: None,
+        mentioned: true,
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        raw: Some(event.clone()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::webhook::tests::request;
+    use axum::http::Method;
+
+    #[test]
+    fn signatures_cover_random_and_body() {
+        let mut config = Config::default();
+        config.channels.nextcloud = Some(NextcloudTalkConfig {
+            enabled: Some(true),
+            bot_secret: Some("talk-secret".to_string()),
+            ..Default::default()
+        });
+        let channel = NextcloudChannel::new(&config);
+
+        let body = br#"{"type":"Create"}"#;
+        let signature = hex::encode(webhook::hmac_sha256(b"talk-secret", &[b"r4nd0m", body]));
+        let signed = |random: &str| {
+            request(
+                Method::POST,
+                "webhook",
+                &[(SIGNATURE_HEADER, &signature), (RANDOM_HEADER, random)],
+                body,
+            )
+        };
+        assert!(channel.signature_valid(&signed("r4nd0m")));
+        assert!(!channel.signature_valid(&signed("other")));
+    }
+
+    #[test]
+    fn rich_object_parameters_are_substituted() {
+        let content = serde_json::json!({
+            "message": "{mention-user1} can you check {file}?",
+            "parameters": {
+                "mention-user1": { "type": "user", "id": "bob", "name": "Bob" },
+                "file": { "type": "file", "id": "12", "name": "report.pdf" }
+            }
+        });
+        let event = serde_json::json!({
+            "type": "Create",
+            "actor": { "type": "Person", "id": "users/alice", "name": "Alice" },
+            "object": { "type": "Note", "id": "1567", "name": "message", "content": content.to_string() },
+            "target": { "type": "Collection", "id": "n3xtc10ud", "name": "Ops" }
+        });
+        let msg = normalize_event(&event).unwrap();
+        assert_eq!(msg.text, "@Bob can you check report.pdf?");
+        assert_eq!(msg.chat_id, "n3xtc10ud");
+        assert_eq!(msg.sender.id, "users/alice");
     }
 }
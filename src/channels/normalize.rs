@@ -1,3 +1,5 @@
+use super::plugin::DEFAULT_ACCOUNT_ID;
+
 use serde::{Deserialize, Serialize};
 
 /// A normalized inbound message from any channel.
@@ -27,6 +29,13 @@ pub struct NormalizedMessage {
     pub attachments: Vec<NormalizedAttachment>,
     /// If this message is a reply, the id of the message it replies to.
     pub reply_to_id: Option<String>,
+    /// Thread / topic identifier within the chat, if any.
+    #[serde(default)]
+    pub thread_id: Option<String>,
+    /// Whether the bot was explicitly addressed (platform @mention or a
+    /// reply to one of the bot's own messages).
+    #[serde(default)]
+    pub mentioned: bool,
     /// ISO 8601 timestamp of when the message was sent.
     pub timestamp: String,
     /// Raw platform-specific payload, preserved for channel-specific tooling.
@@ -52,6 +61,9 @@ pub struct NormalizedSender {
     /// Whether this sender is a bot.
     #[serde(default)]
     pub is_bot: bool,
+    /// Platform roles or badges held in the chat (e.g. "moderator").
+    #[serde(default)]
+    pub roles: Vec<String>,
 }
 
 /// A media attachment (image, file, audio, video, etc.).
@@ -82,6 +94,44 @@ pub struct NormalizedOutbound {
     /// Optional media attachments to include.
     #[serde(default)]
     pub attachments: Vec<NormalizedAttachment>,
+    /// Thread / topic to post in, if the platform has threads.
+    #[serde(default)]
+    pub thread_id: Option<String>,
+    /// Quick-reply buttons shown with the message.
+    #[serde(default)]
+    pub buttons: Vec<OutboundButton>,
+    /// Channel account to send from; `None` uses the default account.
+    #[serde(default)]
+    pub account_id: Option<String>,
+}
+
+impl NormalizedOutbound {
+    /// A plain text message to `chat_id`.
+    pub fn text(chat_id: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            chat_id: chat_id.into(),
+            text: text.into(),
+            reply_to_id: None,
+            attachments: Vec::new(),
+            thread_id: None,
+            buttons: Vec::new(),
+            account_id: None,
+        }
+    }
+
+    /// The account to send from.
+    pub fn account(&self) -> &str {
+        self.account_id.as_deref().unwrap_or(DEFAULT_ACCOUNT_ID)
+    }
+}
+
+/// A button attached to an outbound message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutboundButton {
+    /// Text shown on the button.
+    pub label: String,
+    /// Sent back as the user's reply when the button is pressed.
+    pub value: String,
 }
 
 /// Strip markdown formatting that is not supported by a target platform.
@@ -130,15 +180,51 @@ pub fn strip_markdown(text: &str) -> String {
     result
 }
 
-/// Convert markdown to a simplified representation suitable for platforms that
-/// support basic formatting (Telegram MarkdownV2, Slack mrkdwn, etc.).
+/// Split `text` into chunks of at most `limit` characters.
 ///
-/// This is a placeholder — a full implementation would parse the markdown AST
-/// and emit the platform-specific markup.
-pub fn markdown_to_platform(text: &str, _platform: &str) -> String {
-    // For now, return the text unchanged.  Individual channel implementations
-    // can override with platform-specific conversion.
-    text.to_string()
+/// Prefers breaking at paragraph boundaries, then line breaks, then spaces,
+/// and only hard-splits a run of text with no whitespace. The separator at a
+/// break is dropped; everything else (including indentation) is preserved.
+pub fn split_text(text: &str, limit: usize) -> Vec<String> {
+    let limit = limit.max(1);
+    let mut chunks = Vec::new();
+    let mut rest = text;
+
+    while rest.chars().count() > limit {
+        let window_end = rest
+            .char_indices()
+            .nth(limit)
+            .map(|(i, _)| i)
+            .unwrap_or(rest.len());
+        // Include the next character so a separator right at the limit counts.
+        let search_end = rest[window_end..]
+            .chars()
+            .next()
+            .map_or(window_end, |c| window_end + c.len_utf8());
+        let window = &rest[..search_end];
+
+        let (cut, skip) = [("\n\n", 2), ("\n", 1), (" ", 1)]
+            .iter()
+            .find_map(|(sep, skip)| {
+                window
+                    .rfind(sep)
+                    .filter(|&i| i > 0 && i <= window_end)
+                    .map(|i| (i, *skip))
+            })
+            .unwrap_or((window_end, 0));
+
+        let head = rest[..cut].trim_end();
+        if !head.is_empty() {
+            chunks.push(head.to_string());
+        }
+        rest = &rest[cut + skip..];
+    }
+
+    let tail = rest.trim_end();
+    if !tail.trim_start().is_empty() {
+        chunks.push(tail.to_string());
+    }
+    chunks
 }
 
 #[cfg(test)]
@@ -160,4 +246,27 @@ mod tests {
         let plain = "Hello, world!";
         assert_eq!(strip_markdown(plain), plain);
     }
+
+    #[test]
+    fn split_text_short_is_single_chunk() {
+        assert_eq!(split_text("hello", 10), vec!["hello"]);
+        assert!(split_text("", 10).is_empty());
+    }
+
+    #[test]
+    fn split_text_prefers_paragraphs_then_words() {
+        let text = "first paragraph\n\nsecond one here";
+        assert_eq!(
+            split_text(text, 20),
+            vec!["first paragraph", "second one here"]
+        );
+        assert_eq!(split_text("aaa bbb ccc", 7), vec!["aaa bbb", "ccc"]);
+    }
+
+    #[test]
+    fn split_text_hard_splits_long_words() {
+        let chunks = split_text(&"é".repeat(25), 10);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|c| c.chars().count() <= 10));
+    }
 }
@@ -1,60 +1,86 @@
-use super::plugin::{ChannelCapability, ChannelMeta, ChannelPlugin};
+use super::inbound::dispatch_inbound;
+use super::normalize::{ChatType, NormalizedMessage, NormalizedOutbound, NormalizedSender};
+use super::plugin::{unsupported, ChannelCapability, ChannelMeta, ChannelPlugin};
+use crate::config::{Config, NostrConfig, NostrDmProtocol};
 use crate::gateway::GatewayState;
+use crate::infra::abort_signal::{monitor_with_abort_lifecycle, AbortHandle};
 
-use anyhow::Result;
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut};
+use anyhow::{bail, Context as _, Result};
 use async_trait::async_trait;
-use std::sync::atomic::{AtomicBool, Ordering};
+use base64::Engine as _;
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use futures::{SinkExt, StreamExt};
+use hmac::{Hmac, Mac};
+use k256::schnorr::{Signature, SigningKey, VerifyingKey};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicI64, Ordering};
 use std::sync::Arc;
-use tracing::{info, warn};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio_tungstenite::{connect_async, tungstenite};
+use tracing::{debug, info, warn};
 
 // ============================================================================
 // Nostr Channel Implementation
 // ============================================================================
 
+const KIND_TEXT_NOTE: u64 = 1;
+const KIND_ENCRYPTED_DM: u64 = 4;
+const KIND_SEAL: u64 = 13;
+const KIND_PRIVATE_DM: u64 = 14;
+const KIND_GIFT_WRAP: u64 = 1059;
+const SUBSCRIPTION_ID: &str = "mylobster";
+/// NIP-59 randomizes seal and gift wrap timestamps up to two days back.
+const GIFT_WRAP_TIME_WINDOW_SECS: i64 = 2 * 24 * 60 * 60;
+const RECONNECT_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// A connection that stayed up this long resets the reconnect backoff.
+const RECONNECT_RESET_AFTER: Duration = Duration::from_secs(60);
+/// Event ids remembered for cross-relay deduplication.
+const SEEN_EVENTS_CAPACITY: usize = 10_000;
+
 /// Nostr relay channel integration.
 ///
-/// Connects to one or more Nostr relays via WebSocket and publishes/receives
-/// NIP-01 text note events (kind 1) and NIP-04 encrypted DMs (kind 4).
-///
-/// This is a non-REST channel — it requires persistent WebSocket connections
-/// to relays. `send_message` will return an error if not connected.
+/// Connects to every configured relay via WebSocket and subscribes (NIP-01
+/// `REQ`) to direct messages and mentions of our public key: NIP-04 kind 4
+/// DMs, NIP-17 gift-wrapped DMs (kind 1059) and kind 1 notes tagging us.
+/// Events are verified (id and BIP-340 signature) and deduplicated across
+/// relays. DM replies use the sender's protocol; mention replies are
+/// threaded kind 1 notes. Published events go to every connected relay.
 ///
 /// Protocol reference: <https://github.com/nostr-protocol/nips>
 pub struct NostrChannel {
-    /// Nostr private key (hex-encoded, 32 bytes / 64 hex chars).
-    /// Used to sign events and derive the public key (npub).
-    private_key: Option<String>,
-    /// List of relay WebSocket URLs (e.g. `["wss://relay.damus.io"]`).
-    relays: Option<Vec<String>>,
-    /// Whether this channel is enabled.
-    enabled: Option<bool>,
-    /// Whether relay connections are currently active.
-    connected: Arc<AtomicBool>,
+    enabled: bool,
+    /// `None` when no valid private key is configured.
+    client: Option<Arc<NostrClient>>,
+    /// Abort handle and task of the running relay connections, if any.
+    poller: Mutex<Option<(AbortHandle, tokio::task::JoinHandle<()>)>>,
 }
 
 impl NostrChannel {
-    pub fn new() -> Self {
-        Self {
-            private_key: None,
-            relays: None,
-            enabled: None,
-            connected: Arc::new(AtomicBool::new(false)),
-        }
-    }
+    pub fn new(config: &Config) -> Self {
+        let nostr = config.channels.nostr.clone().unwrap_or_default();
+        let enabled = nostr.enabled.unwrap_or(nostr.private_key.is_some());
+        let client = match nostr.private_key.as_deref().map(NostrKeys::parse) {
+            Some(Ok(keys)) => Some(Arc::new(NostrClient::new(nostr, keys))),
+            Some(Err(e)) => {
+                warn!(error = %e, "Invalid Nostr private_key");
+                None
+            }
+            None => None,
+        };
 
-    /// Create a configured Nostr channel.
-    pub fn with_config(private_key: String, relays: Vec<String>) -> Self {
         Self {
-            private_key: Some(private_key),
-            relays: Some(relays),
-            enabled: Some(true),
-            connected: Arc::new(AtomicBool::new(false)),
+            enabled,
+            client,
+            poller: Mutex::new(None),
         }
     }
-
-    fn is_enabled(&self) -> bool {
-        self.enabled.unwrap_or(false)
-    }
 }
 
 #[async_trait]
@@ -67,7 +93,7 @@ impl ChannelPlugin for NostrChannel {
         ChannelMeta {
             name: "Nostr".to_string(),
             description: "Nostr protocol channel via relay WebSocket connections".to_string(),
-            enabled: self.is_enabled(),
+            enabled: self.enabled,
             multi_account: false,
         }
     }
@@ -77,67 +103,1268 @@ impl ChannelPlugin for NostrChannel {
             ChannelCapability::SendText,
             ChannelCapability::ReceiveText,
             ChannelCapability::Groups,
+            ChannelCapability::Threads,
         ]
     }
 
-    async fn start_account(&self, _state: &GatewayState) -> Result<()> {
-        if !self.is_enabled() {
+    async fn start_account(&self, state: &GatewayState) -> Result<()> {
+        if !self.enabled {
             return Ok(());
         }
 
-        if self.private_key.is_none() {
-            warn!("Nostr channel enabled but no private_key configured");
+        let Some(client) = &self.client else {
+            warn!("Nostr channel enabled but no valid private_key configured");
             return Ok(());
-        }
+        };
 
-        let relays = self.relays.as_deref().unwrap_or(&[]);
-        if relays.is_empty() {
+        if client.relays.is_empty() {
             warn!("Nostr channel enabled but no relays configured");
             return Ok(());
         }
 
         info!(
-            relay_count = relays.len(),
-            relays = ?relays,
-            "Nostr channel starting — would connect to relays"
+            pubkey = %client.keys.public_key,
+            relay_count = client.relays.len(),
+            "Nostr channel starting"
         );
+        client
+            .started_at
+            .store(chrono::Utc::now().timestamp(), Ordering::Relaxed);
 
-        // TODO: For each relay:
-        // 1. Open a WebSocket connection (tokio-tungstenite).
-        // 2. Subscribe to events mentioning our pubkey (REQ filter).
-        // 3. Handle incoming EVENT, EOSE, NOTICE messages.
-        // 4. Implement NIP-04 decryption for encrypted DMs.
-        //
-        // Event signing uses secp256k1 Schnorr signatures (BIP-340).
-        // The private key is used to derive the public key and sign events.
+        let abort = AbortHandle::new();
+        let task = {
+            let client = client.clone();
+            let state = state.clone();
+            let abort = abort.clone();
+            tokio::spawn(async move {
+                let (inbound_tx, mut inbound_rx) = mpsc::unbounded_channel();
+                let dispatch = {
+                    let client = client.clone();
+                    async move {
+                        while let Some(msg) = inbound_rx.recv().await {
+                            tokio::spawn(client.clone().handle_message(state.clone(), msg));
+                        }
+                    }
+                };
+                let relays = async { tokio::join!(client.run(inbound_tx), dispatch) };
+                if monitor_with_abort_lifecycle(relays, &abort).await.is_err() {
+                    debug!("Nostr relay connections stopped");
+                }
+            })
+        };
+        if let Some((previous, _)) = self.poller.lock().replace((abort, task)) {
+            previous.abort();
+        }
 
         Ok(())
     }
 
     async fn stop_account(&self) -> Result<()> {
-        if self.is_enabled() {
+        if self.enabled {
             info!("Nostr channel stopping");
-            self.connected.store(false, Ordering::Relaxed);
-            // TODO: Send CLOSE to all subscriptions and disconnect from relays.
+            if let Some(client) = &self.client {
+                client.broadcast(&json!(["CLOSE", SUBSCRIPTION_ID]).to_string());
+            }
+            let poller = self.poller.lock().take();
+            if let Some((abort, task)) = poller {
+                abort.abort();
+                let _ = task.await;
+            }
+            if let Some(client) = &self.client {
+                client.connections.lock().clear();
+            }
         }
         Ok(())
     }
 
     async fn send_message(&self, to: &str, message: &str) -> Result<()> {
-        if !self.connected.load(Ordering::Relaxed) {
-            anyhow::bail!("Nostr: not connected to any relay — cannot send message");
+        self.send(&NormalizedOutbound::text(to, message)).await?;
+        Ok(())
+    }
+
+    async fn send(&self, message: &NormalizedOutbound) -> Result<Option<String>> {
+        if !message.attachments.is_empty() {
+            return Err(unsupported(self.id(), ChannelCapability::SendMedia));
+        }
+        let Some(client) = &self.client else {
+            bail!("Nostr: no valid private_key configured");
+        };
+
+        // With a reply id, `chat_id` is the author of the note replied to
+        // and the reply is a public note in its thread.
+        if let Some(parent) = &message.reply_to_id {
+            let author = parse_public_key(&message.chat_id)?;
+            let root = message.thread_id.as_deref().unwrap_or(parent);
+            return client
+                .publish_reply(&message.text, root, parent, &author)
+                .map(|_| None);
         }
 
-        info!(to = %to, "Nostr: publishing event");
+        // Otherwise `chat_id` is the recipient's public key (`npub1…` or
+        // hex), optionally prefixed `nip04:` or `nip17:`; the message is sent
+        // as an encrypted DM using that protocol or `dmProtocol`.
+        let (protocol, to) = match message.chat_id.split_once(':') {
+            Some(("nip04", key)) => (NostrDmProtocol::Nip04, key),
+            Some(("nip17", key)) => (NostrDmProtocol::Nip17, key),
+            _ => (
+                client.config.dm_protocol.unwrap_or_default(),
+                message.chat_id.as_str(),
+            ),
+        };
+        let recipient = parse_public_key(to)?;
 
-        // `to` interpretation depends on the event kind:
-        // - For kind 1 (text note): `to` is ignored (broadcast to relays)
-        // - For kind 4 (encrypted DM): `to` is the recipient's pubkey (hex)
-        //
-        // TODO: Construct a NIP-01 event, sign it with the private key,
-        // and publish via ["EVENT", <event>] to all connected relays.
-        let _ = message;
+        info!(to = %recipient, "Nostr: sending direct message");
 
+        client.send_dm(&recipient, &message.text, protocol)?;
+        Ok(None)
+    }
+}
+
+// ============================================================================
+// Relay Client
+// ============================================================================
+
+/// Bounded set of recently seen event ids.
+#[derive(Default)]
+struct SeenEvents {
+    ids: HashSet<String>,
+    order: VecDeque<String>,
+}
+
+impl SeenEvents {
+    /// Record `id`, returning `false` if it was already seen.
+    fn insert(&mut self, id: &str) -> bool {
+        if !self.ids.insert(id.to_string()) {
+            return false;
+        }
+        self.order.push_back(id.to_string());
+        if self.order.len() > SEEN_EVENTS_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+/// State shared between the channel and its relay connections.
+struct NostrClient {
+    config: NostrConfig,
+    keys: NostrKeys,
+    relays: Vec<String>,
+    /// Outbound queues of connected relays, keyed by URL.
+    connections: Mutex<HashMap<String, mpsc::UnboundedSender<String>>>,
+    seen: Mutex<SeenEvents>,
+    /// Unix time the channel started; older messages are not answered.
+    started_at: AtomicI64,
+}
+
+impl NostrClient {
+    fn new(config: NostrConfig, keys: NostrKeys) -> Self {
+        let relays = config.relays.clone().unwrap_or_default();
+        Self {
+            config,
+            keys,
+            relays,
+            connections: Mutex::new(HashMap::new()),
+            seen: Mutex::new(SeenEvents::default()),
+            started_at: AtomicI64::new(chrono::Utc::now().timestamp()),
+        }
+    }
+
+    /// Maintain a connection to every relay until aborted.
+    async fn run(self: Arc<Self>, inbound: mpsc::UnboundedSender<NormalizedMessage>) {
+        let relays = self.relays.iter().map(|url| self.relay_loop(url, &inbound));
+        futures::future::join_all(relays).await;
+    }
+
+    /// Reconnect to one relay with exponential backoff.
+    async fn relay_loop(&self, url: &str, inbound: &mpsc::UnboundedSender<NormalizedMessage>) {
+        let mut backoff = RECONNECT_INITIAL_BACKOFF;
+        loop {
+            let started = Instant::now();
+            let result = self.relay_session(url, inbound).await;
+            self.connections.lock().remove(url);
+
+            if started.elapsed() >= RECONNECT_RESET_AFTER {
+                backoff = RECONNECT_INITIAL_BACKOFF;
+            }
+            match result {
+                Ok(()) => info!(relay = %url, "Nostr relay closed; reconnecting in {:?}", backoff),
+                Err(e) => warn!(
+                    relay = %url,
+                    error = %e,
+                    "Nostr relay connection failed; reconnecting in {:?}",
+                    backoff
+                ),
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(RECONNECT_MAX_BACKOFF);
+        }
+    }
+
+    /// Subscription filters: DMs (NIP-04 and gift wraps) and mentions.
+    fn filters(&self) -> Vec<Value> {
+        let pubkey = &self.keys.public_key;
+        let since = self.started_at.load(Ordering::Relaxed);
+        let mut filters = vec![
+            json!({ "kinds": [KIND_ENCRYPTED_DM], "#p": [pubkey], "since": since }),
+            json!({
+                "kinds": [KIND_GIFT_WRAP],
+                "#p": [pubkey],
+                "since": since - GIFT_WRAP_TIME_WINDOW_SECS,
+            }),
+        ];
+        if self.config.mentions.unwrap_or(true) {
+            filters.push(json!({ "kinds": [KIND_TEXT_NOTE], "#p": [pubkey], "since": since }));
+        }
+        filters
+    }
+
+    /// Subscribe on one relay and process its messages until it closes.
+    async fn relay_session(
+        &self,
+        url: &str,
+        inbound: &mpsc::UnboundedSender<NormalizedMessage>,
+    ) -> Result<()> {
+        let (ws, _) = connect_async(url)
+            .await
+            .with_context(|| format!("Nostr relay {url} connect failed"))?;
+        let (mut sink, mut stream) = ws.split();
+
+        let mut req = vec![json!("REQ"), json!(SUBSCRIPTION_ID)];
+        req.extend(self.filters());
+        sink.send(tungstenite::Message::Text(
+            Value::from(req).to_string().into(),
+        ))
+        .await?;
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+        self.connections.lock().insert(url.to_string(), tx);
+        debug!(relay = %url, "Nostr relay connected");
+
+        let write_loop = async move {
+            while let Some(frame) = rx.recv().await {
+                sink.send(tungstenite::Message::Text(frame.into())).await?;
+            }
+            Ok::<_, tungstenite::Error>(())
+        };
+
+        let read_loop = async {
+            while let Some(frame) = stream.next().await {
+                match frame.context("Nostr relay read failed")? {
+                    tungstenite::Message::Text(text) => {
+                        self.handle_relay_message(url, text.as_str(), inbound)?
+                    }
+                    tungstenite::Message::Close(_) => break,
+                    _ => {}
+                }
+            }
+            Ok(())
+        };
+
+        tokio::select! {
+            result = write_loop => result.context("Nostr relay write failed"),
+            result = read_loop => result,
+        }
+    }
+
+    /// Handle one relay-to-client message (`EVENT`, `OK`, `NOTICE`, …).
+    fn handle_relay_message(
+        &self,
+        url: &str,
+        text: &str,
+        inbound: &mpsc::UnboundedSender<NormalizedMessage>,
+    ) -> Result<()> {
+        let Ok(Value::Array(message)) = serde_json::from_str::<Value>(text) else {
+            debug!(relay = %url, "Ignoring malformed Nostr relay message");
+            return Ok(());
+        };
+        match message.first().and_then(Value::as_str) {
+            Some("EVENT") => {
+                let Some(event) = message
+                    .get(2)
+                    .and_then(|e| serde_json::from_value::<NostrEvent>(e.clone()).ok())
+                else {
+                    return Ok(());
+                };
+                self.handle_event(event, inbound);
+            }
+            Some("OK") if message.get(2) == Some(&Value::Bool(false)) => warn!(
+                relay = %url,
+                event_id = ?message.get(1).and_then(|v| v.as_str()),
+                reason = ?message.get(3).and_then(|v| v.as_str()),
+                "Nostr relay rejected event"
+            ),
+            Some("NOTICE") => info!(
+                relay = %url,
+                notice = ?message.get(1).and_then(|v| v.as_str()),
+                "Nostr relay notice"
+            ),
+            Some("CLOSED") => bail!(
+                "subscription closed by relay: {}",
+                message.get(2).and_then(Value::as_str).unwrap_or_default()
+            ),
+            Some("EOSE") => debug!(relay = %url, "Nostr relay end of stored events"),
+            _ => {}
+        }
         Ok(())
     }
+
+    /// Verify, deduplicate, decrypt and queue one event.
+    fn handle_event(&self, event: NostrEvent, inbound: &mpsc::UnboundedSender<NormalizedMessage>) {
+        if !self.seen.lock().insert(&event.id) {
+            return;
+        }
+        if !event.verify() {
+            debug!(event_id = %event.id, "Dropping Nostr event with invalid signature");
+            return;
+        }
+        let Some(msg) = self.normalize(&event) else {
+            return;
+        };
+        // DMs are checked against `dmPolicy`/`allowFrom` by the admission
+        // stage; mentions are always addressed to us.
+        let _ = inbound.send(msg);
+    }
+
+    /// Convert a verified event addressed to us into a [`NormalizedMessage`].
+    fn normalize(&self, event: &NostrEvent) -> Option<NormalizedMessage> {
+        let our_key = &self.keys.public_key;
+        if event.pubkey == *our_key || !event.tags_p().any(|p| p == our_key) {
+            return None;
+        }
+        match event.kind {
+            KIND_ENCRYPTED_DM => {
+                let text = match nip04::decrypt(&self.keys, &event.pubkey, &event.content) {
+                    Ok(text) => text,
+                    Err(e) => {
+                        debug!(event_id = %event.id, error = %e, "Nostr NIP-04 decrypt failed");
+                        return None;
+                    }
+                };
+                Some(dm_message(
+                    event,
+                    &event.pubkey,
+                    text,
+                    NostrDmProtocol::Nip04,
+                ))
+            }
+            KIND_GIFT_WRAP => {
+                let rumor = match nip17::unwrap(&self.keys, event) {
+                    Ok(rumor) => rumor,
+                    Err(e) => {
+                        debug!(event_id = %event.id, error = %e, "Nostr gift wrap unwrap failed");
+                        return None;
+                    }
+                };
+                // Gift wraps are backdated, so the window re-delivers old
+                // messages; only answer those sent since we started.
+                if rumor.kind != KIND_PRIVATE_DM
+                    || rumor.created_at < self.started_at.load(Ordering::Relaxed)
+                    || !self.seen.lock().insert(&rumor.id)
+                {
+                    return None;
+                }
+                let text = rumor.content.clone();
+                Some(dm_message(
+                    &rumor,
+                    &rumor.pubkey,
+                    text,
+                    NostrDmProtocol::Nip17,
+                ))
+            }
+            KIND_TEXT_NOTE if self.config.mentions.unwrap_or(true) => {
+                Some(mention_message(event, our_key))
+            }
+            _ => None,
+        }
+    }
+
+    /// Run the agent for an admitted message and reply in kind.
+    async fn handle_message(self: Arc<Self>, state: GatewayState, msg: NormalizedMessage) {
+        let reply = match dispatch_inbound(&state, &msg).await {
+            Ok(Some(reply)) => reply,
+            Ok(None) => return,
+            Err(e) => {
+                warn!(sender = %msg.sender.id, error = %e, "Nostr agent run failed");
+                return;
+            }
+        };
+
+        let outbound = match msg.chat_type {
+            ChatType::Dm => {
+                let protocol = match msg.raw.as_ref().and_then(|raw| raw["protocol"].as_str()) {
+                    Some("nip04") => "nip04",
+                    _ => "nip17",
+                };
+                NormalizedOutbound::text(format!("{protocol}:{}", msg.sender.id), reply)
+            }
+            ChatType::Group | ChatType::Thread => NormalizedOutbound {
+                reply_to_id: Some(msg.id.clone()),
+                thread_id: Some(msg.thread_id.clone().unwrap_or_else(|| msg.id.clone())),
+                ..NormalizedOutbound::text(msg.sender.id.clone(), reply)
+            },
+        };
+        if let Err(e) = state.channels.enqueue("nostr", outbound).await {
+            warn!(sender = %msg.sender.id, error = %e, "Nostr reply failed");
+        }
+    }
+
+    /// Queue a raw frame on every connected relay; returns the relay count.
+    fn broadcast(&self, frame: &str) -> usize {
+        self.connections
+            .lock()
+            .values()
+            .filter(|tx| tx.send(frame.to_string()).is_ok())
+            .count()
+    }
+
+    /// Publish a signed event to every connected relay.
+    fn publish(&self, event: &NostrEvent) -> Result<()> {
+        let frame = json!(["EVENT", event]).to_string();
+        if self.broadcast(&frame) == 0 {
+            bail!("Nostr: not connected to any relay — cannot publish event");
+        }
+        debug!(event_id = %event.id, kind = event.kind, "Nostr event published");
+        Ok(())
+    }
+
+    /// Send an encrypted direct message.
+    fn send_dm(&self, recipient: &str, text: &str, protocol: NostrDmProtocol) -> Result<()> {
+        let event = match protocol {
+            NostrDmProtocol::Nip04 => self.keys.sign(UnsignedEvent {
+                pubkey: self.keys.public_key.clone(),
+                created_at: chrono::Utc::now().timestamp(),
+                kind: KIND_ENCRYPTED_DM,
+                tags: vec![vec!["p".to_string(), recipient.to_string()]],
+                content: nip04::encrypt(&self.keys, recipient, text)?,
+            })?,
+            NostrDmProtocol::Nip17 => nip17::wrap(&self.keys, recipient, text)?,
+        };
+        self.publish(&event)
+    }
+
+    /// Publish a kind 1 reply in the thread rooted at `root`.
+    fn publish_reply(&self, text: &str, root: &str, parent: &str, author: &str) -> Result<()> {
+        let mut tags = vec![vec![
+            "e".to_string(),
+            root.to_string(),
+            String::new(),
+            "root".to_string(),
+        ]];
+        if parent != root {
+            tags.push(vec![
+                "e".to_string(),
+                parent.to_string(),
+                String::new(),
+                "reply".to_string(),
+            ]);
+        }
+        tags.push(vec!["p".to_string(), author.to_string()]);
+        let event = self.keys.sign(UnsignedEvent {
+            pubkey: self.keys.public_key.clone(),
+            created_at: chrono::Utc::now().timestamp(),
+            kind: KIND_TEXT_NOTE,
+            tags,
+            content: text.to_string(),
+        })?;
+        self.publish(&event)
+    }
+}
+
+/// Build a DM [`NormalizedMessage`] from a decrypted message.
+fn dm_message(
+    event: &NostrEvent,
+    sender: &str,
+    text: String,
+    protocol: NostrDmProtocol,
+) -> NormalizedMessage {
+    let protocol = match protocol {
+        NostrDmProtocol::Nip04 => "nip04",
+        NostrDmProtocol::Nip17 => "nip17",
+    };
+    NormalizedMessage {
+        id: event.id.clone(),
+        channel: "nostr".to_string(),
+        account_id: "default".to_string(),
+        chat_id: sender.to_string(),
+        chat_name: None,
+        chat_type: ChatType::Dm,
+        sender: NormalizedSender {
+            id: sender.to_string(),
+            name: npub(sender).unwrap_or_else(|| sender.to_string()),
+            is_bot: false,
+            roles: Vec::new(),
+        },
+        text,
+        attachments: Vec::new(),
+        reply_to_id: None,
+        thread_id: None,
+        mentioned: true,
+        timestamp: event_time(event.created_at),
+        raw: Some(json!({ "protocol": protocol, "event_id": event.id })),
+    }
+}
+
+/// Build a thread [`NormalizedMessage`] from a kind 1 note mentioning us.
+fn mention_message(event: &NostrEvent, our_key: &str) -> NormalizedMessage {
+    // NIP-10: the marked root, else the first `e` tag, else the note itself.
+    let e_tags: Vec<&Vec<String>> = event
+        .tags
+        .iter()
+        .filter(|t| t.first().map(String::as_str) == Some("e") && t.len() > 1)
+        .collect();
+    let root = e_tags
+        .iter()
+        .find(|t| t.get(3).map(String::as_str) == Some("root"))
+        .or(e_tags.first())
+        .map(|t| t[1].clone());
+    let reply_to_id = e_tags
+        .iter()
+        .find(|t| t.get(3).map(String::as_str) == Some("reply"))
+        .or(e_tags.last())
+        .map(|t| t[1].clone());
+
+    let mut text = event.content.clone();
+    if let Some(our_npub) = npub(our_key) {
+        text = text
+            .replace(&format!("nostr:{our_npub}"), "")
+            .replace(&our_npub, "");
+    }
+
+    let thread_root = root.unwrap_or_else(|| event.id.clone());
+    NormalizedMessage {
+        id: event.id.clone(),
+        channel: "nostr".to_string(),
+        account_id: "default".to_string(),
+        chat_id: thread_root.clone(),
+        chat_name: None,
+        chat_type: ChatType::Thread,
+        sender: NormalizedSender {
+            id: event.pubkey.clone(),
+            name: npub(&event.pubkey).unwrap_or_else(|| event.pubkey.clone()),
+            is_bot: false,
+            roles: Vec::new(),
+        },
+        text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+        attachments: Vec::new(),
+        reply_to_id,
+        thread_id: Some(thread_root),
+        mentioned: true,
+        timestamp: event_time(event.created_at),
+        raw: serde_json::to_value(event).ok(),
+    }
+}
+
+fn event_time(created_at: i64) -> String {
+    chrono::DateTime::from_timestamp(created_at, 0)
+        .unwrap_or_else(chrono::Utc::now)
+        .to_rfc3339()
+}
+
+// ============================================================================
+// Keys and Events (NIP-01, NIP-19)
+// ============================================================================
+
+/// Our key pair: BIP-340 signing key and x-only public key (hex).
+struct NostrKeys {
+    secret: SigningKey,
+    public_key: String,
+}
+
+impl NostrKeys {
+    /// Parse an `nsec1…` bech32 or 64-character hex private key.
+    fn parse(key: &str) -> Result<Self> {
+        let key = key.trim();
+        let bytes = if key.starts_with("nsec1") {
+            decode_bech32("nsec", key)?
+        } else {
+            decode_hex32(key)?
+        };
+        let secret = SigningKey::from_bytes(&bytes).context("invalid secp256k1 private key")?;
+        Ok(Self::from_secret(secret))
+    }
+
+    fn generate() -> Self {
+        Self::from_secret(SigningKey::random(&mut rand::rngs::OsRng))
+    }
+
+    fn from_secret(secret: SigningKey) -> Self {
+        let public_key = hex::encode(secret.verifying_key().to_bytes());
+        Self { secret, public_key }
+    }
+
+    /// Compute the id and BIP-340 signature of an event.
+    fn sign(&self, unsigned: UnsignedEvent) -> Result<NostrEvent> {
+        let mut event = unsigned.into_rumor();
+        let aux: [u8; 32] = rand::random();
+        let id = decode_hex32(&event.id)?;
+        let sig = self
+            .secret
+            .sign_raw(&id, &aux)
+            .context("Schnorr signing failed")?;
+        event.sig = hex::encode(sig.to_bytes());
+        Ok(event)
+    }
+
+    /// ECDH shared secret: the x coordinate of our key times `pubkey`.
+    fn shared_x(&self, pubkey: &str) -> Result<[u8; 32]> {
+        let mut sec1 = [0u8; 33];
+        sec1[0] = 0x02;
+        sec1[1..].copy_from_slice(&decode_hex32(pubkey)?);
+        let public = k256::PublicKey::from_sec1_bytes(&sec1).context("invalid public key")?;
+        let shared =
+            k256::ecdh::diffie_hellman(self.secret.as_nonzero_scalar(), public.as_affine());
+        let mut x = [0u8; 32];
+        x.copy_from_slice(shared.raw_secret_bytes());
+        Ok(x)
+    }
+}
+
+/// Event fields covered by the id.
+struct UnsignedEvent {
+    pubkey: String,
+    created_at: i64,
+    kind: u64,
+    tags: Vec<Vec<String>>,
+    content: String,
+}
+
+impl UnsignedEvent {
+    /// NIP-01 id: SHA-256 of `[0, pubkey, created_at, kind, tags, content]`.
+    fn id(&self) -> String {
+        let serialized = json!([
+            0,
+            self.pubkey,
+            self.created_at,
+            self.kind,
+            self.tags,
+            self.content
+        ])
+        .to_string();
+        hex::encode(Sha256::digest(serialized.as_bytes()))
+    }
+
+    /// An event with its id but no signature (a NIP-59 rumor).
+    fn into_rumor(self) -> NostrEvent {
+        NostrEvent {
+            id: self.id(),
+            pubkey: self.pubkey,
+            created_at: self.created_at,
+            kind: self.kind,
+            tags: self.tags,
+            content: self.content,
+            sig: String::new(),
+        }
+    }
+}
+
+/// A NIP-01 event as exchanged with relays.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct NostrEvent {
+    id: String,
+    pubkey: String,
+    created_at: i64,
+    kind: u64,
+    tags: Vec<Vec<String>>,
+    content: String,
+    /// Empty for unsigned rumors.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    sig: String,
+}
+
+impl NostrEvent {
+    fn unsigned(&self) -> UnsignedEvent {
+        UnsignedEvent {
+            pubkey: self.pubkey.clone(),
+            created_at: self.created_at,
+            kind: self.kind,
+            tags: self.tags.clone(),
+            content: self.content.clone(),
+        }
+    }
+
+    /// Whether the id matches the content and the signature is valid.
+    fn verify(&self) -> bool {
+        if self.unsigned().id() != self.id {
+            return false;
+        }
+        let (Ok(id), Ok(pubkey), Ok(sig)) = (
+            decode_hex32(&self.id),
+            decode_hex32(&self.pubkey),
+            hex::decode(&self.sig),
+        ) else {
+            return false;
+        };
+        let (Ok(key), Ok(sig)) = (
+            VerifyingKey::from_bytes(&pubkey),
+            Signature::try_from(sig.as_slice()),
+        ) else {
+            return false;
+        };
+        key.verify_raw(&id, &sig).is_ok()
+    }
+
+    /// Values of the event's `p` tags.
+    fn tags_p(&self) -> impl Iterator<Item = &str> {
+        self.tags
+            .iter()
+            .filter(|t| t.first().map(String::as_str) == Some("p"))
+            .filter_map(|t| t.get(1).map(String::as_str))
+    }
+}
+
+fn decode_hex32(s: &str) -> Result<[u8; 32]> {
+    let bytes = hex::decode(s.trim()).context("invalid hex key")?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("expected 32 bytes"))
+}
+
+fn decode_bech32(expected_hrp: &str, s: &str) -> Result<[u8; 32]> {
+    let (hrp, data) = bech32::decode(s).context("invalid bech32 key")?;
+    if hrp.as_str() != expected_hrp {
+        bail!("expected a {expected_hrp} key, got {}", hrp.as_str());
+    }
+    data.try_into()
+        .map_err(|_| anyhow::anyhow!("expected 32 bytes"))
+}
+
+/// Parse an `npub1…` or hex public key into hex.
+pub(super) fn parse_public_key(key: &str) -> Result<String> {
+    let key = key.trim().trim_start_matches("nostr:");
+    let bytes = if key.starts_with("npub1") {
+        decode_bech32("npub", key)?
+    } else {
+        decode_hex32(key)?
+    };
+    Ok(hex::encode(bytes))
+}
+
+/// Encode a hex public key as `npub1…`.
+fn npub(pubkey: &str) -> Option<String> {
+    let bytes = decode_hex32(pubkey).ok()?;
+    bech32::encode::<bech32::Bech32>(bech32::Hrp::parse("npub").ok()?, &bytes).ok()
+}
+
+// ============================================================================
+// NIP-04 Encryption
+// ============================================================================
+
+mod nip04 {
+    use super::*;
+
+    type Aes256CbcEnc = cbc::Encryptor<aes::Aes256>;
+    type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+
+    /// AES-256-CBC with the raw ECDH x coordinate; `<base64>?iv=<base64>`.
+    pub(super) fn encrypt(keys: &NostrKeys, pubkey: &str, text: &str) -> Result<String> {
+        let key = keys.shared_x(pubkey)?;
+        let iv: [u8; 16] = rand::random();
+        let ciphertext = Aes256CbcEnc::new(&key.into(), &iv.into())
+            .encrypt_padded_vec_mut::<Pkcs7>(text.as_bytes());
+        let b64 = base64::engine::general_purpose::STANDARD;
+        Ok(format!("{}?iv={}", b64.encode(ciphertext), b64.encode(iv)))
+    }
+
+    pub(super) fn decrypt(keys: &NostrKeys, pubkey: &str, content: &str) -> Result<String> {
+        let (ciphertext, iv) = content
+            .split_once("?iv=")
+            .context("missing ?iv= in NIP-04 content")?;
+        let b64 = base64::engine::general_purpose::STANDARD;
+        let ciphertext = b64.decode(ciphertext)?;
+        let iv: [u8; 16] = b64
+            .decode(iv)?
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("NIP-04 iv must be 16 bytes"))?;
+        let key = keys.shared_x(pubkey)?;
+        let plaintext = Aes256CbcDec::new(&key.into(), &iv.into())
+            .decrypt_padded_vec_mut::<Pkcs7>(&ciphertext)
+            .map_err(|_| anyhow::anyhow!("NIP-04 padding error"))?;
+        Ok(String::from_utf8(plaintext)?)
+    }
+}
+
+// ============================================================================
+// NIP-44 Encryption and NIP-17 Gift Wraps
+// ============================================================================
+
+mod nip44 {
+    use super::*;
+
+    const VERSION: u8 = 2;
+
+    /// `HKDF-extract(salt = "nip44-v2", ikm = shared_x)`.
+    pub(super) fn conversation_key(keys: &NostrKeys, pubkey: &str) -> Result<[u8; 32]> {
+        let shared_x = keys.shared_x(pubkey)?;
+        let (prk, _) = hkdf::Hkdf::<Sha256>::extract(Some(b"nip44-v2"), &shared_x);
+        Ok(prk.into())
+    }
+
+    /// Length of the padded plaintext for an unpadded length.
+    pub(super) fn padded_len(len: usize) -> usize {
+        if len <= 32 {
+            return 32;
+        }
+        let next_power = 1usize << (usize::BITS - (len - 1).leading_zeros());
+        let chunk = if next_power <= 256 {
+            32
+        } else {
+            next_power / 8
+        };
+        chunk * ((len - 1) / chunk + 1)
+    }
+
+    /// ChaCha20 key, ChaCha20 nonce and HMAC key for a message nonce.
+    fn message_keys(
+        conversation_key: &[u8; 32],
+        nonce: &[u8; 32],
+    ) -> ([u8; 32], [u8; 12], [u8; 32]) {
+        let hk = hkdf::Hkdf::<Sha256>::from_prk(conversation_key)
+            .expect("conversation key is a valid PRK length");
+        let mut okm = [0u8; 76];
+        hk.expand(nonce, &mut okm)
+            .expect("76 bytes is a valid HKDF output length");
+        let mut chacha_key = [0u8; 32];
+        let mut chacha_nonce = [0u8; 12];
+        let mut hmac_key = [0u8; 32];
+        chacha_key.copy_from_slice(&okm[..32]);
+        chacha_nonce.copy_from_slice(&okm[32..44]);
+        hmac_key.copy_from_slice(&okm[44..]);
+        (chacha_key, chacha_nonce, hmac_key)
+    }
+
+    fn mac(hmac_key: &[u8; 32], nonce: &[u8; 32], ciphertext: &[u8]) -> Hmac<Sha256> {
+        let mut mac =
+            <Hmac<Sha256> as Mac>::new_from_slice(hmac_key).expect("HMAC accepts any key length");
+        mac.update(nonce);
+        mac.update(ciphertext);
+        mac
+    }
+
+    pub(super) fn encrypt_with_nonce(
+        conversation_key: &[u8; 32],
+        text: &str,
+        nonce: &[u8; 32],
+    ) -> Result<String> {
+        let len = text.len();
+        if !(1..=65_535).contains(&len) {
+            bail!("NIP-44 plaintext must be 1..=65535 bytes");
+        }
+        let mut padded = Vec::with_capacity(2 + padded_len(len));
+        padded.extend_from_slice(&(len as u16).to_be_bytes());
+        padded.extend_from_slice(text.as_bytes());
+        padded.resize(2 + padded_len(len), 0);
+
+        let (chacha_key, chacha_nonce, hmac_key) = message_keys(conversation_key, nonce);
+        chacha20::ChaCha20::new(&chacha_key.into(), &chacha_nonce.into())
+            .apply_keystream(&mut padded);
+        let tag = mac(&hmac_key, nonce, &padded).finalize().into_bytes();
+
+        let mut payload = Vec::with_capacity(1 + 32 + padded.len() + 32);
+        payload.push(VERSION);
+        payload.extend_from_slice(nonce);
+        payload.extend_from_slice(&padded);
+        payload.extend_from_slice(&tag);
+        Ok(base64::engine::general_purpose::STANDARD.encode(payload))
+    }
+
+    pub(super) fn encrypt(conversation_key: &[u8; 32], text: &str) -> Result<String> {
+        encrypt_with_nonce(conversation_key, text, &rand::random())
+    }
+
+    pub(super) fn decrypt(conversation_key: &[u8; 32], payload: &str) -> Result<String> {
+        let data = base64::engine::general_purpose::STANDARD.decode(payload)?;
+        if data.len() < 99 || data[0] != VERSION {
+            bail!("unsupported NIP-44 payload");
+        }
+        let mut nonce = [0u8; 32];
+        nonce.copy_from_slice(&data[1..33]);
+        let (ciphertext, tag) = data[33..].split_at(data.len() - 33 - 32);
+
+        let (chacha_key, chacha_nonce, hmac_key) = message_keys(conversation_key, &nonce);
+        mac(&hmac_key, &nonce, ciphertext)
+            .verify_slice(tag)
+            .map_err(|_| anyhow::anyhow!("NIP-44 MAC mismatch"))?;
+
+        let mut padded = ciphertext.to_vec();
+        chacha20::ChaCha20::new(&chacha_key.into(), &chacha_nonce.into())
+            .apply_keystream(&mut padded);
+        let len = u16::from_be_bytes([padded[0], padded[1]]) as usize;
+        if len == 0 || padded.len() != 2 + padded_len(len) {
+            bail!("invalid NIP-44 padding");
+        }
+        Ok(String::from_utf8(padded[2..2 + len].to_vec())?)
+    }
+}
+
+mod nip17 {
+    use super::*;
+
+    /// A timestamp up to two days in the past, hiding the real send time.
+    fn tweaked_now() -> i64 {
+        chrono::Utc::now().timestamp()
+            - rand::random::<i64>().rem_euclid(GIFT_WRAP_TIME_WINDOW_SECS)
+    }
+
+    /// Rumor (kind 14) → seal (kind 13, signed by us) → gift wrap (kind
+    /// 1059, signed by a one-time key) addressed to `recipient`.
+    pub(super) fn wrap(keys: &NostrKeys, recipient: &str, text: &str) -> Result<NostrEvent> {
+        let rumor = UnsignedEvent {
+            pubkey: keys.public_key.clone(),
+            created_at: chrono::Utc::now().timestamp(),
+            kind: KIND_PRIVATE_DM,
+            tags: vec![vec!["p".to_string(), recipient.to_string()]],
+            content: text.to_string(),
+        }
+        .into_rumor();
+
+        let seal = keys.sign(UnsignedEvent {
+            pubkey: keys.public_key.clone(),
+            created_at: tweaked_now(),
+            kind: KIND_SEAL,
+            tags: Vec::new(),
+            content: nip44::encrypt(
+                &nip44::conversation_key(keys, recipient)?,
+                &serde_json::to_string(&rumor)?,
+            )?,
+        })?;
+
+        let ephemeral = NostrKeys::generate();
+        ephemeral.sign(UnsignedEvent {
+            pubkey: ephemeral.public_key.clone(),
+            created_at: tweaked_now(),
+            kind: KIND_GIFT_WRAP,
+            tags: vec![vec!["p".to_string(), recipient.to_string()]],
+            content: nip44::encrypt(
+                &nip44::conversation_key(&ephemeral, recipient)?,
+                &serde_json::to_string(&seal)?,
+            )?,
+        })
+    }
+
+    /// Open a gift wrap addressed to us, returning the rumor.
+    ///
+    /// The seal must be validly signed, and the rumor must come from the
+    /// seal's author (otherwise anyone could impersonate a sender).
+    pub(super) fn unwrap(keys: &NostrKeys, wrap: &NostrEvent) -> Result<NostrEvent> {
+        let seal_json =
+            nip44::decrypt(&nip44::conversation_key(keys, &wrap.pubkey)?, &wrap.content)?;
+        let seal: NostrEvent = serde_json::from_str(&seal_json)?;
+        if seal.kind != KIND_SEAL || !seal.verify() {
+            bail!("invalid seal");
+        }
+        let rumor_json =
+            nip44::decrypt(&nip44::conversation_key(keys, &seal.pubkey)?, &seal.content)?;
+        let rumor: NostrEvent = serde_json::from_str(&rumor_json)?;
+        if rumor.pubkey != seal.pubkey {
+            bail!("rumor author does not match seal");
+        }
+        if rumor.unsigned().id() != rumor.id {
+            bail!("rumor id mismatch");
+        }
+        Ok(rumor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::{Admission, Verdict};
+    use crate::config::DmPolicy;
+    use tokio::net::TcpListener;
+
+    fn client_with(relays: Vec<String>, configure: impl FnOnce(&mut NostrConfig)) -> NostrClient {
+        let mut config = NostrConfig {
+            relays: Some(relays),
+            ..Default::default()
+        };
+        configure(&mut config);
+        let mut client = NostrClient::new(config, NostrKeys::generate());
+        client.started_at = AtomicI64::new(chrono::Utc::now().timestamp() - 60);
+        client
+    }
+
+    fn note(keys: &NostrKeys, kind: u64, tags: Vec<Vec<String>>, content: &str) -> NostrEvent {
+        keys.sign(UnsignedEvent {
+            pubkey: keys.public_key.clone(),
+            created_at: chrono::Utc::now().timestamp(),
+            kind,
+            tags,
+            content: content.to_string(),
+        })
+        .unwrap()
+    }
+
+    fn p_tag(pubkey: &str) -> Vec<String> {
+        vec!["p".to_string(), pubkey.to_string()]
+    }
+
+    #[test]
+    fn parses_nip19_keys() {
+        let hex =
+            parse_public_key("npub10elfcs4fr0l0r8af98jlmgdh9c8tcxjvz9qkw038js35mp4dma8qzvjptg")
+                .unwrap();
+        assert_eq!(
+            hex,
+            "7e7e9c42a91bfef19fa929e5fda1b72e0ebc1a4c1141673e2794234d86addf4e"
+        );
+        assert_eq!(
+            npub(&hex).as_deref(),
+            Some("npub10elfcs4fr0l0r8af98jlmgdh9c8tcxjvz9qkw038js35mp4dma8qzvjptg")
+        );
+
+        let from_nsec =
+            NostrKeys::parse("nsec1vl029mgpspedva04g90vltkh6fvh240zqtv9k0t9af8935ke9laqsnlfe5")
+                .unwrap();
+        let from_hex =
+            NostrKeys::parse("67dea2ed018072d675f5415ecfaed7d2597555e202d85b3d65ea4e58d2d92ffa")
+                .unwrap();
+        assert_eq!(from_nsec.public_key, from_hex.public_key);
+        assert!(NostrKeys::parse(
+            "npub10elfcs4fr0l0r8af98jlmgdh9c8tcxjvz9qkw038js35mp4dma8qzvjptg"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn signed_events_verify_and_detect_tampering() {
+        let keys = NostrKeys::generate();
+        let event = note(&keys, KIND_TEXT_NOTE, vec![], "hello \"nostr\"\n");
+        assert!(event.verify());
+
+        let mut tampered = event.clone();
+        tampered.content = "goodbye".to_string();
+        assert!(!tampered.verify());
+
+        let mut forged = event.clone();
+        forged.pubkey = NostrKeys::generate().public_key;
+        forged.id = forged.unsigned().id();
+        assert!(!forged.verify());
+    }
+
+    #[test]
+    fn nip04_round_trip() {
+        let alice = NostrKeys::generate();
+        let bob = NostrKeys::generate();
+        let content = nip04::encrypt(&alice, &bob.public_key, "secret ✓").unwrap();
+        assert!(content.contains("?iv="));
+        assert_eq!(
+            nip04::decrypt(&bob, &alice.public_key, &content).unwrap(),
+            "secret ✓"
+        );
+        assert!(nip04::decrypt(&NostrKeys::generate(), &alice.public_key, &content).is_err());
+    }
+
+    #[test]
+    fn nip44_matches_reference_vector() {
+        let sec1 = NostrKeys::parse(&format!("{:064x}", 1)).unwrap();
+        let sec2 = NostrKeys::parse(&format!("{:064x}", 2)).unwrap();
+        let key = nip44::conversation_key(&sec1, &sec2.public_key).unwrap();
+        assert_eq!(
+            hex::encode(key),
+            "c41c775356fd92eadc63ff5a0dc1da211b268cbea22316767095b2871ea1412d"
+        );
+
+        let mut nonce = [0u8; 32];
+        nonce[31] = 1;
+        let payload = nip44::encrypt_with_nonce(&key, "a", &nonce).unwrap();
+        assert_eq!(
+            payload,
+            "AgAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABee0G5VSK0/9YypIObAtDKfYEAjD35uVkHyB0F4DwrcNaCXlCWZKaArsGrY6M9wnuTMxWfp1RTN9Xga8no+kF5Vsb"
+        );
+        assert_eq!(nip44::decrypt(&key, &payload).unwrap(), "a");
+    }
+
+    #[test]
+    fn nip44_padding_lengths() {
+        for (len, padded) in [
+            (1, 32),
+            (32, 32),
+            (33, 64),
+            (65, 96),
+            (100, 128),
+            (250, 256),
+            (320, 320),
+            (383, 384),
+            (400, 448),
+            (515, 640),
+            (900, 1024),
+            (65_535, 65_536),
+        ] {
+            assert_eq!(nip44::padded_len(len), padded, "len {len}");
+        }
+    }
+
+    #[test]
+    fn gift_wrap_round_trip_and_forgery() {
+        let alice = NostrKeys::generate();
+        let bob = NostrKeys::generate();
+        let wrap = nip17::wrap(&alice, &bob.public_key, "hi bob").unwrap();
+        assert!(wrap.verify());
+        assert_ne!(wrap.pubkey, alice.public_key);
+        assert_eq!(
+            wrap.tags_p().collect::<Vec<_>>(),
+            vec![bob.public_key.as_str()]
+        );
+
+        let rumor = nip17::unwrap(&bob, &wrap).unwrap();
+        assert_eq!(rumor.kind, KIND_PRIVATE_DM);
+        assert_eq!(rumor.pubkey, alice.public_key);
+        assert_eq!(rumor.content, "hi bob");
+        assert!(nip17::unwrap(&NostrKeys::generate(), &wrap).is_err());
+    }
+
+    #[test]
+    fn mentions_are_threaded_and_dms_admitted_by_npub() {
+        let client = client_with(vec![], |_| {});
+        let alice = NostrKeys::generate();
+        let our_npub = npub(&client.keys.public_key).unwrap();
+
+        let mention = note(
+            &alice,
+            KIND_TEXT_NOTE,
+            vec![
+                vec!["e".into(), "root1".into(), "".into(), "root".into()],
+                vec!["e".into(), "parent1".into(), "".into(), "reply".into()],
+                p_tag(&client.keys.public_key),
+            ],
+            &format!("nostr:{our_npub} what's up?"),
+        );
+        let msg = client.normalize(&mention).unwrap();
+        assert_eq!(msg.chat_type, ChatType::Thread);
+        assert_eq!(msg.thread_id.as_deref(), Some("root1"));
+        assert_eq!(msg.reply_to_id.as_deref(), Some("parent1"));
+        assert_eq!(msg.text, "what's up?");
+
+        let dm = note(
+            &alice,
+            KIND_ENCRYPTED_DM,
+            vec![p_tag(&client.keys.public_key)],
+            &nip04::encrypt(&alice, &client.keys.public_key, "psst").unwrap(),
+        );
+        let msg = client.normalize(&dm).unwrap();
+        assert_eq!(msg.text, "psst");
+
+        let admission = Admission::new();
+        let mut config = Config::default();
+        config.channels.nostr = Some(NostrConfig {
+            dm_policy: Some(DmPolicy::Allowlist),
+            ..Default::default()
+        });
+        assert_ne!(admission.check(&config, &msg), Verdict::Admit);
+        config.channels.nostr = Some(NostrConfig {
+            dm_policy: Some(DmPolicy::Allowlist),
+            allow_from: Some(vec![npub(&alice.public_key).unwrap()]),
+            ..Default::default()
+        });
+        assert_eq!(admission.check(&config, &msg), Verdict::Admit);
+    }
+
+    /// Accept one relay connection and return it with the client's `REQ`.
+    async fn accept_relay(
+        listener: &TcpListener,
+    ) -> (
+        tokio_tungstenite::WebSocketStream<tokio::net::TcpStream>,
+        Value,
+    ) {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        let req = ws.next().await.unwrap().unwrap();
+        (ws, serde_json::from_str(req.to_text().unwrap()).unwrap())
+    }
+
+    async fn send_event(
+        ws: &mut tokio_tungstenite::WebSocketStream<tokio::net::TcpStream>,
+        event: &NostrEvent,
+    ) {
+        ws.send(tungstenite::Message::Text(
+            json!(["EVENT", SUBSCRIPTION_ID, event]).to_string().into(),
+        ))
+        .await
+        .unwrap();
+    }
+
+    async fn recv_message(
+        rx: &mut mpsc::UnboundedReceiver<NormalizedMessage>,
+    ) -> NormalizedMessage {
+        tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn relays_deliver_deduplicated_dms_and_receive_replies() {
+        let relay_a = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let relay_b = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let urls = vec![
+            format!("ws://{}", relay_a.local_addr().unwrap()),
+            format!("ws://{}", relay_b.local_addr().unwrap()),
+        ];
+        let client = Arc::new(client_with(urls, |_| {}));
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let running = tokio::spawn(client.clone().run(tx));
+
+        let (mut ws_a, req_a) = accept_relay(&relay_a).await;
+        let (mut ws_b, req_b) = accept_relay(&relay_b).await;
+        for req in [&req_a, &req_b] {
+            assert_eq!(req[0], "REQ");
+            assert_eq!(req[2]["kinds"], json!([KIND_ENCRYPTED_DM]));
+            assert_eq!(req[2]["#p"], json!([client.keys.public_key]));
+            assert_eq!(req[3]["kinds"], json!([KIND_GIFT_WRAP]));
+        }
+
+        // The same NIP-04 DM arrives from both relays; a gift wrap follows.
+        let alice = NostrKeys::generate();
+        let dm = note(
+            &alice,
+            KIND_ENCRYPTED_DM,
+            vec![p_tag(&client.keys.public_key)],
+            &nip04::encrypt(&alice, &client.keys.public_key, "hello nip04").unwrap(),
+        );
+        send_event(&mut ws_a, &dm).await;
+        send_event(&mut ws_b, &dm).await;
+        let wrap = nip17::wrap(&alice, &client.keys.public_key, "hello nip17").unwrap();
+        send_event(&mut ws_b, &wrap).await;
+
+        let first = recv_message(&mut rx).await;
+        assert_eq!(first.text, "hello nip04");
+        assert_eq!(first.sender.id, alice.public_key);
+        let second = recv_message(&mut rx).await;
+        assert_eq!(second.text, "hello nip17");
+        assert_eq!(second.raw.as_ref().unwrap()["protocol"], "nip17");
+        assert!(rx.try_recv().is_err());
+
+        // Replies are published to every connected relay.
+        client
+            .send_dm(&alice.public_key, "hi alice", NostrDmProtocol::Nip17)
+            .unwrap();
+        for ws in [&mut ws_a, &mut ws_b] {
+            let frame: Value =
+                serde_json::from_str(ws.next().await.unwrap().unwrap().to_text().unwrap()).unwrap();
+            assert_eq!(frame[0], "EVENT");
+            let published: NostrEvent = serde_json::from_value(frame[1].clone()).unwrap();
+            assert!(published.verify());
+            let rumor = nip17::unwrap(&alice, &published).unwrap();
+            assert_eq!(rumor.content, "hi alice");
+            assert_eq!(rumor.pubkey, client.keys.public_key);
+        }
+
+        running.abort();
+    }
+
+    #[test]
+    fn publish_without_relays_fails() {
+        let client = client_with(vec![], |_| {});
+        let err = client
+            .send_dm(
+                &NostrKeys::generate().public_key,
+                "hi",
+                NostrDmProtocol::Nip04,
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("not connected"));
+    }
 }
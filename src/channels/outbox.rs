@@ -0,0 +1,976 @@
+//! Durable outbound delivery queue.
+//!
+//! Messages the gateway sends on a channel (agent replies, admission
+//! notices, `send` RPCs) are appended to the [`Outbox`] and written to
+//! `<stateDir>/channels/outbox.json` before delivery. A worker started with
+//! the channels delivers them through [`ChannelManager::send`] and removes
+//! each entry once the platform accepts it, so a crash between the agent
+//! reply and the platform call loses nothing: queued entries are recovered
+//! on the next start. One-off senders without a gateway (CLI, FFI, agent
+//! tools) queue into an in-memory outbox and wait in [`Outbox::deliver`].
+//!
+//! Failed sends are retried with jittered exponential backoff from the
+//! account's `retry` settings ([`OutboundRetryConfig`]). Messages for one
+//! target (channel, account and chat) go out in order; targets are delivered
+//! concurrently and each send is bounded by a timeout, so a failing or hung
+//! target never holds up the others, and one that keeps failing is suspended
+//! for a while. Telegram sends also honour each account's 401 backoff.
+//! Messages older than the stale threshold are dropped rather than delivered
+//! late.
+//!
+//! Delivery is at least once: a multi-part message that fails half way is
+//! retried from its first part.
+
+use super::admission::account;
+use super::normalize::NormalizedOutbound;
+use super::plugin::{UnsupportedCapability, DEFAULT_ACCOUNT_ID};
+use super::{configured_accounts, ChannelManager, WhatsAppApiError};
+use crate::config::{Config, OutboundRetryConfig};
+use crate::infra::delivery::{
+    is_stale_message, retry_delay_ms, DeliveryAttemptInfo, DeliveryRecoverySummary, DrainGuard,
+    TelegramBackoff,
+};
+
+use anyhow::Result;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+use teloxide::{ApiError, RequestError};
+use tokio::sync::{broadcast, oneshot, Notify};
+use tracing::{debug, info, warn};
+
+/// Consecutive failures after which a target is suspended.
+const SUSPEND_AFTER_FAILURES: u32 = 5;
+
+/// How long a suspended target is left alone before the next attempt.
+const TARGET_SUSPEND_MS: u64 = 60_000;
+
+/// Longest a single send may take before it counts as failed.
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest the worker sleeps without being woken by a new message.
+const IDLE_WAKEUP: Duration = Duration::from_secs(30);
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// A message waiting to be delivered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutboxEntry {
+    /// Queue-assigned id.
+    pub id: String,
+    /// Channel id to send through.
+    pub channel: String,
+    /// The message itself.
+    pub message: NormalizedOutbound,
+    /// When the message was queued (ms since epoch).
+    pub queued_at: u64,
+    /// Delivery attempts so far.
+    pub attempts: u32,
+    /// Earliest time of the next attempt (ms since epoch).
+    pub next_attempt_at: u64,
+    /// Error from the last failed attempt.
+    pub last_error: Option<String>,
+}
+
+/// Delivery counters for one channel since the gateway started.
+#[derive(Debug, Clone, Default)]
+struct ChannelStats {
+    delivered: u64,
+    /// Messages given up on after their last retry.
+    failed: u64,
+    /// Messages dropped as stale.
+    dropped: u64,
+    last_error: Option<String>,
+}
+
+/// Where a message goes: a chat reached through one channel account.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Target {
+    channel: String,
+    account_id: String,
+    chat_id: String,
+}
+
+impl Target {
+    fn of(entry: &OutboxEntry) -> Self {
+        Self {
+            channel: entry.channel.clone(),
+            account_id: entry.message.account().to_string(),
+            chat_id: entry.message.chat_id.clone(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct QueueState {
+    entries: Vec<OutboxEntry>,
+    /// Bumped on every change to `entries`.
+    generation: u64,
+    /// Attempt bookkeeping per target.
+    targets: HashMap<Target, DeliveryAttemptInfo>,
+    stats: HashMap<String, ChannelStats>,
+    /// `sendMessage` 401 backoff per Telegram account.
+    telegram: HashMap<String, TelegramBackoff>,
+    /// Callers waiting for an entry's outcome, keyed by entry id.
+    waiters: HashMap<String, oneshot::Sender<Result<(), String>>>,
+}
+
+/// Persistent queue of outbound channel messages.
+pub struct Outbox {
+    path: Option<PathBuf>,
+    /// Retry settings per `(channel, account id)`; accounts without one use
+    /// the default.
+    retry: HashMap<(String, String), OutboundRetryConfig>,
+    send_timeout: Duration,
+    state: Mutex<QueueState>,
+    /// Generation of the queue last written to disk; held while writing.
+    persisted: tokio::sync::Mutex<u64>,
+    wake: Notify,
+    /// Set while queued messages from the previous run are being recovered.
+    draining: Arc<AtomicBool>,
+    worker: Mutex<Option<tokio::task::JoinHandle<()>>>,
+}
+
+// ============================================================================
+// Queue
+// ============================================================================
+
+impl Outbox {
+    /// An outbox persisted at `path`, or kept in memory only when `None`.
+    ///
+    /// Entries already at `path` are picked up by [`start`](Self::start).
+    pub fn new(path: Option<PathBuf>, config: &Config) -> Self {
+        Self {
+            path,
+            retry: retry_settings(config),
+            send_timeout: SEND_TIMEOUT,
+            state: Mutex::new(QueueState::default()),
+            persisted: tokio::sync::Mutex::new(0),
+            wake: Notify::new(),
+            draining: Arc::new(AtomicBool::new(false)),
+            worker: Mutex::new(None),
+        }
+    }
+
+    /// Queue `message` for delivery on `channel`, returning its id.
+    ///
+    /// The entry is on disk when this returns.
+    pub async fn enqueue(&self, channel: &str, message: NormalizedOutbound) -> Result<String> {
+        let now = now_ms();
+        let entry = OutboxEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            channel: channel.to_string(),
+            message,
+            queued_at: now,
+            attempts: 0,
+            next_attempt_at: now,
+            last_error: None,
+        };
+        let id = entry.id.clone();
+        self.update(|state| state.entries.push(entry));
+        if let Err(e) = self.persist().await {
+            self.update(|state| state.entries.retain(|e| e.id != id));
+            return Err(e);
+        }
+        // While recovering, the drain loop picks new entries up itself.
+        if !DrainGuard::is_draining(&self.draining) {
+            self.wake.notify_one();
+        }
+        Ok(id)
+    }
+
+    /// Deliver the queued entry `id` and wait for the outcome.
+    ///
+    /// For callers without a running worker (CLI, FFI, agent tools): due
+    /// entries are flushed here, on the account's retry schedule, until `id`
+    /// is delivered or given up on.
+    pub async fn deliver(&self, channels: &ChannelManager, id: &str) -> Result<()> {
+        let mut outcome = {
+            let (tx, rx) = oneshot::channel();
+            let mut state = self.state.lock();
+            if state.entries.iter().any(|e| e.id == id) {
+                state.waiters.insert(id.to_string(), tx);
+            }
+            rx
+        };
+        loop {
+            self.flush(channels).await;
+            match outcome.try_recv() {
+                Ok(result) => return result.map_err(anyhow::Error::msg),
+                Err(oneshot::error::TryRecvError::Closed) => {
+                    anyhow::bail!("message {id} is not queued")
+                }
+                Err(oneshot::error::TryRecvError::Empty) => {}
+            }
+            tokio::time::sleep(self.next_wakeup(now_ms())).await;
+        }
+    }
+
+    /// Messages still waiting for delivery, oldest first.
+    pub fn pending(&self) -> Vec<OutboxEntry> {
+        self.state.lock().entries.clone()
+    }
+
+    /// Queue depth and delivery counters for `channel`.
+    pub fn status(&self, channel: &str) -> serde_json::Value {
+        let state = self.state.lock();
+        let queued = state
+            .entries
+            .iter()
+            .filter(|e| e.channel == channel)
+            .count();
+        let suspended = state
+            .targets
+            .iter()
+            .filter(|(target, info)| target.channel == channel && info.suspended)
+            .count();
+        let stats = state.stats.get(channel).cloned().unwrap_or_default();
+        let mut status = serde_json::json!({
+            "queued": queued,
+            "delivered": stats.delivered,
+            "failed": stats.failed,
+            "dropped": stats.dropped,
+            "suspendedTargets": suspended,
+            "lastError": stats.last_error,
+        });
+        if channel == "telegram" {
+            let now = now_ms();
+            let backoff = state.telegram.values().any(|b| b.should_skip(now));
+            status["backoff"] = serde_json::json!(backoff);
+        }
+        status
+    }
+
+    /// Load queued entries from disk and start the delivery worker.
+    ///
+    /// Recovered entries are delivered first, under a [`DrainGuard`]; the
+    /// worker then runs until `shutdown` fires or [`stop`](Self::stop).
+    pub fn start(
+        self: &Arc<Self>,
+        channels: Arc<ChannelManager>,
+        shutdown: broadcast::Receiver<()>,
+    ) {
+        let recovered = self.load();
+        let outbox = self.clone();
+        let worker = tokio::spawn(async move {
+            if recovered > 0 {
+                let _guard = DrainGuard::start(outbox.draining.clone());
+                let summary = outbox.flush(&channels).await;
+                info!(
+                    recovered,
+                    delivered = summary.succeeded,
+                    failed = summary.failed,
+                    skipped = summary.skipped,
+                    "Recovered queued channel messages"
+                );
+            }
+            outbox.run(&channels, shutdown).await;
+        });
+        if let Some(previous) = self.worker.lock().replace(worker) {
+            previous.abort();
+        }
+    }
+
+    /// Stop the delivery worker. Queued entries stay on disk.
+    pub fn stop(&self) {
+        if let Some(worker) = self.worker.lock().take() {
+            worker.abort();
+        }
+    }
+
+    async fn run(&self, channels: &ChannelManager, mut shutdown: broadcast::Receiver<()>) {
+        loop {
+            self.flush(channels).await;
+            let wait = self.next_wakeup(now_ms());
+            tokio::select! {
+                _ = self.wake.notified() => {}
+                _ = tokio::time::sleep(wait) => {}
+                _ = shutdown.recv() => break,
+            }
+        }
+    }
+
+    /// Attempt every due entry once.
+    pub async fn flush(&self, channels: &ChannelManager) -> DeliveryRecoverySummary {
+        self.flush_at(channels, now_ms()).await
+    }
+
+    async fn flush_at(&self, channels: &ChannelManager, now: u64) -> DeliveryRecoverySummary {
+        let queued = self.pending();
+        let mut summary = DeliveryRecoverySummary {
+            total_targets: queued.len(),
+            ..Default::default()
+        };
+        let mut by_target: HashMap<Target, Vec<OutboxEntry>> = HashMap::new();
+        for entry in queued {
+            by_target.entry(Target::of(&entry)).or_default().push(entry);
+        }
+
+        let deliveries = by_target
+            .into_iter()
+            .map(|(target, entries)| self.flush_target(channels, target, entries, now));
+        for part in futures::future::join_all(deliveries).await {
+            summary.succeeded += part.succeeded;
+            summary.failed += part.failed;
+            summary.skipped += part.skipped;
+        }
+        summary
+    }
+
+    /// Attempt one target's due entries in order, stopping at the first that
+    /// is not due or fails so the chat sees its messages in order.
+    async fn flush_target(
+        &self,
+        channels: &ChannelManager,
+        target: Target,
+        entries: Vec<OutboxEntry>,
+        now: u64,
+    ) -> DeliveryRecoverySummary {
+        let mut summary = DeliveryRecoverySummary::default();
+        let mut blocked = false;
+
+        for entry in entries {
+            if is_stale_message(entry.queued_at, now) {
+                warn!(channel = %entry.channel, chat = %entry.message.chat_id, "Dropping stale outbound message");
+                self.finish(
+                    &entry,
+                    Err("message went stale before delivery".to_string()),
+                    |stats| stats.dropped += 1,
+                )
+                .await;
+                summary.skipped += 1;
+                continue;
+            }
+            if blocked || entry.next_attempt_at > now || self.held(&target, now) {
+                blocked = true;
+                summary.skipped += 1;
+                continue;
+            }
+
+            let send = channels.send(&entry.channel, &entry.message);
+            let result = match tokio::time::timeout(self.send_timeout, send).await {
+                Ok(result) => result.map(|_| ()),
+                Err(_) => Err(anyhow::anyhow!(
+                    "send timed out after {:?}",
+                    self.send_timeout
+                )),
+            };
+            match result {
+                Ok(()) => {
+                    self.record_attempt(&target, now, None);
+                    self.finish(&entry, Ok(()), |stats| stats.delivered += 1)
+                        .await;
+                    summary.succeeded += 1;
+                }
+                Err(e) => {
+                    self.record_attempt(&target, now, Some(&e));
+                    self.reschedule(&entry, &e, now).await;
+                    blocked = true;
+                    summary.failed += 1;
+                }
+            }
+        }
+        summary
+    }
+
+    /// Whether `target` is suspended or its account is backing off.
+    fn held(&self, target: &Target, now: u64) -> bool {
+        let state = self.state.lock();
+        if target.channel == "telegram"
+            && state
+                .telegram
+                .get(&target.account_id)
+                .is_some_and(|b| b.should_skip(now))
+        {
+            return true;
+        }
+        state.targets.get(target).is_some_and(|info| {
+            info.suspended
+                && now
+                    < info
+                        .last_attempt_at
+                        .unwrap_or(0)
+                        .saturating_add(TARGET_SUSPEND_MS)
+        })
+    }
+
+    fn record_attempt(&self, target: &Target, now: u64, error: Option<&anyhow::Error>) {
+        let mut state = self.state.lock();
+        if target.channel == "telegram" {
+            let backoff = state.telegram.entry(target.account_id.clone()).or_default();
+            match error {
+                None => backoff.record_success(),
+                Some(e) if is_unauthorized(e) => backoff.record_401(now),
+                Some(_) => {}
+            }
+        }
+        let info = state.targets.entry(target.clone()).or_default();
+        info.last_attempt_at = Some(now);
+        match error {
+            None => {
+                info.consecutive_failures = 0;
+                info.suspended = false;
+            }
+            Some(_) => {
+                info.consecutive_failures += 1;
+                if info.consecutive_failures >= SUSPEND_AFTER_FAILURES && !info.suspended {
+                    info.suspended = true;
+                    warn!(
+                        channel = %target.channel,
+                        account = %target.account_id,
+                        chat = %target.chat_id,
+                        "Suspending outbound target after repeated failures"
+                    );
+                }
+            }
+        }
+    }
+
+    /// Schedule the next attempt for a failed entry, or give up on it.
+    async fn reschedule(&self, entry: &OutboxEntry, error: &anyhow::Error, now: u64) {
+        let retry = self
+            .retry
+            .get(&(entry.channel.clone(), entry.message.account().to_string()))
+            .cloned()
+            .unwrap_or_default();
+        let attempts = entry.attempts + 1;
+        if is_permanent(error) || attempts >= retry.attempts.max(1) {
+            warn!(
+                channel = %entry.channel,
+                chat = %entry.message.chat_id,
+                attempts,
+                error = %error,
+                "Giving up on outbound message"
+            );
+            let last_error = error.to_string();
+            self.finish(entry, Err(last_error.clone()), |stats| {
+                stats.failed += 1;
+                stats.last_error = Some(last_error);
+            })
+            .await;
+            return;
+        }
+
+        let delay = retry_delay_ms(&retry, attempts);
+        debug!(channel = %entry.channel, attempts, delay, error = %error, "Outbound send failed, retrying");
+        self.update(|state| {
+            if let Some(queued) = state.entries.iter_mut().find(|e| e.id == entry.id) {
+                queued.attempts = attempts;
+                queued.next_attempt_at = now + delay;
+                queued.last_error = Some(error.to_string());
+            }
+            state
+                .stats
+                .entry(entry.channel.clone())
+                .or_default()
+                .last_error = Some(error.to_string());
+        });
+        if let Err(e) = self.persist().await {
+            warn!(error = %e, "Failed to persist outbox");
+        }
+    }
+
+    /// Remove a delivered (or abandoned) entry, update its counters and
+    /// tell a caller waiting in [`deliver`](Self::deliver) how it went.
+    async fn finish(
+        &self,
+        entry: &OutboxEntry,
+        outcome: Result<(), String>,
+        update: impl FnOnce(&mut ChannelStats),
+    ) {
+        let waiter = self.update(|state| {
+            state.entries.retain(|e| e.id != entry.id);
+            update(state.stats.entry(entry.channel.clone()).or_default());
+            state.waiters.remove(&entry.id)
+        });
+        if let Some(waiter) = waiter {
+            let _ = waiter.send(outcome);
+        }
+        if let Err(e) = self.persist().await {
+            warn!(error = %e, "Failed to persist outbox");
+        }
+    }
+
+    /// Change the queue under the lock, marking it for the next write.
+    fn update<T>(&self, change: impl FnOnce(&mut QueueState) -> T) -> T {
+        let mut state = self.state.lock();
+        state.generation += 1;
+        change(&mut state)
+    }
+
+    /// How long the worker may sleep before something becomes due.
+    fn next_wakeup(&self, now: u64) -> Duration {
+        let state = self.state.lock();
+        let due = state
+            .entries
+            .iter()
+            .map(|e| e.next_attempt_at)
+            .chain(
+                state
+                    .targets
+                    .values()
+                    .filter(|info| info.suspended)
+                    .map(|info| {
+                        info.last_attempt_at
+                            .unwrap_or(0)
+                            .saturating_add(TARGET_SUSPEND_MS)
+                    }),
+            )
+            .chain(
+                state
+                    .telegram
+                    .values()
+                    .filter(|_| !state.entries.is_empty())
+                    .map(|b| b.backoff_until_ms),
+            )
+            .filter(|&at| at > now)
+            .min();
+        due.map_or(IDLE_WAKEUP, |at| {
+            Duration::from_millis(at - now).min(IDLE_WAKEUP)
+        })
+    }
+
+    // ------------------------------------------------------------------------
+    // Persistence
+    // ------------------------------------------------------------------------
+
+    /// Merge entries saved by a previous run; returns how many were loaded.
+    fn load(&self) -> usize {
+        let Some(path) = &self.path else {
+            return 0;
+        };
+        let saved: Vec<OutboxEntry> = match std::fs::read_to_string(path) {
+            Ok(raw) => serde_json::from_str::<serde_json::Value>(&raw)
+                .ok()
+                .and_then(|v| serde_json::from_value(v["entries"].clone()).ok())
+                .unwrap_or_else(|| {
+                    warn!(path = %path.display(), "Ignoring unreadable outbox file");
+                    Vec::new()
+                }),
+            Err(_) => Vec::new(),
+        };
+
+        let mut state = self.state.lock();
+        let known: HashSet<String> = state.entries.iter().map(|e| e.id.clone()).collect();
+        let now = now_ms();
+        let mut recovered: Vec<OutboxEntry> = saved
+            .into_iter()
+            .filter(|e| !known.contains(&e.id))
+            .map(|mut e| {
+                e.next_attempt_at = now;
+                e
+            })
+            .collect();
+        let count = recovered.len();
+        recovered.append(&mut state.entries);
+        state.entries = recovered;
+        state.generation += 1;
+        count
+    }
+
+    /// Write the queue to disk, replacing the previous file atomically.
+    ///
+    /// The queue is serialized under the lock and written outside it, one
+    /// writer at a time; a writer whose change is already on disk returns
+    /// straight away.
+    async fn persist(&self) -> Result<()> {
+        let Some(path) = self.path.clone() else {
+            return Ok(());
+        };
+        let mut persisted = self.persisted.lock().await;
+        let (generation, body) = {
+            let state = self.state.lock();
+            if state.generation == *persisted {
+                return Ok(());
+            }
+            let body = serde_json::json!({ "entries": state.entries });
+            (state.generation, serde_json::to_string(&body)?)
+        };
+        tokio::task::spawn_blocking(move || write_synced(&path, &body)).await??;
+        *persisted = generation;
+        Ok(())
+    }
+}
+
+/// Retry settings of every configured channel account, from
+/// `channels.<id>.retry` (or the account's own under `accounts`).
+fn retry_settings(config: &Config) -> HashMap<(String, String), OutboundRetryConfig> {
+    fn multi<T>(
+        retry: &mut HashMap<(String, String), OutboundRetryConfig>,
+        config: &Config,
+        channel: &str,
+        (accounts, default): (&Option<HashMap<String, T>>, &T),
+        settings: fn(&T) -> &Option<OutboundRetryConfig>,
+    ) {
+        for account_id in configured_accounts(config, channel) {
+            let account = account(accounts, default, &account_id);
+            if let Some(r) = settings(account).as_ref().or(settings(default).as_ref()) {
+                retry.insert((channel.to_string(), account_id), r.clone());
+            }
+        }
+    }
+
+    let mut retry = HashMap::new();
+    let c = &config.channels;
+    let telegram = (&c.telegram.accounts, &c.telegram.default_account);
+    multi(&mut retry, config, "telegram", telegram, |a| &a.retry);
+    let discord = (&c.discord.accounts, &c.discord.default_account);
+    multi(&mut retry, config, "discord", discord, |a| &a.retry);
+    let slack = (&c.slack.accounts, &c.slack.default_account);
+    multi(&mut retry, config, "slack", slack, |a| &a.retry);
+    let whatsapp = (&c.whatsapp.accounts, &c.whatsapp.default_account);
+    multi(&mut retry, config, "whatsapp", whatsapp, |a| &a.retry);
+    let matrix = (&c.matrix.accounts, &c.matrix.default_account);
+    multi(&mut retry, config, "matrix", matrix, |a| &a.retry);
+    let mattermost = (&c.mattermost.accounts, &c.mattermost.default_account);
+    multi(&mut retry, config, "mattermost", mattermost, |a| &a.retry);
+    if let Some(synology) = &c.synology_chat {
+        let synology = (&synology.accounts, &synology.default_account);
+        multi(&mut retry, config, "synology_chat", synology, |a| &a.retry);
+    }
+
+    let single = [
+        ("signal", c.signal.retry.as_ref()),
+        ("imessage", c.imessage.retry.as_ref()),
+        (
+            "googlechat",
+            c.googlechat.as_ref().and_then(|c| c.retry.as_ref()),
+        ),
+        ("teams", c.msteams.as_ref().and_then(|c| c.retry.as_ref())),
+        ("irc", c.irc.as_ref().and_then(|c| c.retry.as_ref())),
+        ("email", c.email.as_ref().and_then(|c| c.retry.as_ref())),
+        ("twitch", c.twitch.as_ref().and_then(|c| c.retry.as_ref())),
+        ("nostr", c.nostr.as_ref().and_then(|c| c.retry.as_ref())),
+        ("line", c.line.as_ref().and_then(|c| c.retry.as_ref())),
+        ("feishu", c.feishu.as_ref().and_then(|c| c.retry.as_ref())),
+        ("zalo", c.zalo.as_ref().and_then(|c| c.retry.as_ref())),
+        (
+            "zalouser",
+            c.zalouser.as_ref().and_then(|c| c.retry.as_ref()),
+        ),
+        (
+            "nextcloud",
+            c.nextcloud.as_ref().and_then(|c| c.retry.as_ref()),
+        ),
+        (
+            "bluebubbles",
+            c.bluebubbles.as_ref().and_then(|c| c.retry.as_ref()),
+        ),
+    ];
+    for (channel, r) in single {
+        if let Some(r) = r {
+            retry.insert(
+                (channel.to_string(), DEFAULT_ACCOUNT_ID.to_string()),
+                r.clone(),
+            );
+        }
+    }
+    // Extension channels carry their settings as raw JSON.
+    for (channel, section) in &c.extensions {
+        let r = section.get("retry").cloned().map(serde_json::from_value);
+        if let Some(Ok(r)) = r {
+            retry.insert((channel.clone(), DEFAULT_ACCOUNT_ID.to_string()), r);
+        }
+    }
+    retry
+}
+
+/// Write `body` to `path` through a temporary file, syncing the file and
+/// its directory so the replacement survives a crash.
+fn write_synced(path: &Path, body: &str) -> std::io::Result<()> {
+    let dir = path.parent();
+    if let Some(dir) = dir {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(body.as_bytes())?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    #[cfg(unix)]
+    if let Some(dir) = dir {
+        std::fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Whether retrying a failed send cannot help.
+fn is_permanent(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<UnsupportedCapability>().is_some()
+        || error
+            .downcast_ref::<WhatsAppApiError>()
+            .is_some_and(WhatsAppApiError::is_session_window_error)
+}
+
+/// Whether a send failed because the bot token was rejected.
+fn is_unauthorized(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<RequestError>(),
+        Some(RequestError::Api(ApiError::InvalidToken))
+    )
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::plugin::{ChannelCapability, ChannelMeta, ChannelPlugin};
+    use crate::gateway::GatewayState;
+    use async_trait::async_trait;
+
+    /// Records sends; chats listed in `failing` return an error and those in
+    /// `hanging` never answer.
+    #[derive(Default)]
+    struct FakeChannel {
+        failing: Mutex<HashSet<String>>,
+        hanging: Mutex<HashSet<String>>,
+        sent: Mutex<Vec<(String, String)>>,
+    }
+
+    #[async_trait]
+    impl ChannelPlugin for FakeChannel {
+        fn id(&self) -> &str {
+            "fake"
+        }
+
+        fn meta(&self) -> ChannelMeta {
+            ChannelMeta {
+                name: "Fake".to_string(),
+                description: "Test channel".to_string(),
+                enabled: true,
+                multi_account: false,
+            }
+        }
+
+        fn capabilities(&self) -> Vec<ChannelCapability> {
+            vec![ChannelCapability::SendText]
+        }
+
+        async fn start_account(&self, _state: &GatewayState) -> Result<()> {
+            Ok(())
+        }
+
+        async fn stop_account(&self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn send_message(&self, to: &str, message: &str) -> Result<()> {
+            if self.failing.lock().contains(to) {
+                anyhow::bail!("platform unavailable");
+            }
+            let hangs = self.hanging.lock().contains(to);
+            if hangs {
+                std::future::pending::<()>().await;
+            }
+            self.sent.lock().push((to.to_string(), message.to_string()));
+            Ok(())
+        }
+    }
+
+    async fn setup(path: Option<PathBuf>) -> (Outbox, Arc<FakeChannel>, ChannelManager) {
+        let config = Config::default();
+        let channels = ChannelManager::new(&config);
+        let fake = Arc::new(FakeChannel::default());
+        channels.register(fake.clone()).await;
+        (Outbox::new(path, &config), fake, channels)
+    }
+
+    fn text(chat: &str, body: &str) -> NormalizedOutbound {
+        NormalizedOutbound::text(chat, body)
+    }
+
+    #[tokio::test]
+    async fn failing_target_does_not_block_others() {
+        let (outbox, fake, channels) = setup(None).await;
+        fake.failing.lock().insert("bad".to_string());
+        outbox.enqueue("fake", text("bad", "one")).await.unwrap();
+        outbox.enqueue("fake", text("good", "two")).await.unwrap();
+
+        let now = now_ms();
+        let summary = outbox.flush_at(&channels, now).await;
+        assert_eq!(summary.succeeded, 1);
+        assert_eq!(summary.failed, 1);
+        assert_eq!(
+            *fake.sent.lock(),
+            vec![("good".to_string(), "two".to_string())]
+        );
+
+        let pending = outbox.pending();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].attempts, 1);
+        assert!(pending[0].next_attempt_at > now);
+        assert_eq!(
+            pending[0].last_error.as_deref(),
+            Some("platform unavailable")
+        );
+    }
+
+    #[tokio::test]
+    async fn hung_target_does_not_block_others() {
+        let (mut outbox, fake, channels) = setup(None).await;
+        outbox.send_timeout = Duration::from_millis(50);
+        fake.hanging.lock().insert("slow".to_string());
+        outbox.enqueue("fake", text("slow", "one")).await.unwrap();
+        outbox.enqueue("fake", text("good", "two")).await.unwrap();
+
+        let summary = outbox.flush_at(&channels, now_ms()).await;
+        assert_eq!((summary.succeeded, summary.failed), (1, 1));
+        assert_eq!(fake.sent.lock().len(), 1);
+        let pending = outbox.pending();
+        assert_eq!(pending[0].message.chat_id, "slow");
+        assert_eq!(
+            pending[0].last_error.as_deref(),
+            Some("send timed out after 50ms")
+        );
+    }
+
+    #[test]
+    fn retry_settings_cover_every_channel() {
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "channels": {
+                "slack": { "accounts": { "work": { "retry": { "attempts": 7 } } } },
+                "signal": { "retry": { "attempts": 2 } },
+                "tlon": { "retry": { "attempts": 4 } }
+            }
+        }))
+        .unwrap();
+        let retry = retry_settings(&config);
+        let attempts = |channel: &str, account: &str| {
+            retry
+                .get(&(channel.to_string(), account.to_string()))
+                .map(|r| r.attempts)
+        };
+        assert_eq!(attempts("slack", "work"), Some(7));
+        assert_eq!(attempts("slack", DEFAULT_ACCOUNT_ID), None);
+        assert_eq!(attempts("signal", DEFAULT_ACCOUNT_ID), Some(2));
+        assert_eq!(attempts("tlon", DEFAULT_ACCOUNT_ID), Some(4));
+    }
+
+    #[tokio::test]
+    async fn keeps_order_within_a_target() {
+        let (outbox, fake, channels) = setup(None).await;
+        fake.failing.lock().insert("chat".to_string());
+        outbox.enqueue("fake", text("chat", "first")).await.unwrap();
+        outbox
+            .enqueue("fake", text("chat", "second"))
+            .await
+            .unwrap();
+
+        let now = now_ms();
+        let summary = outbox.flush_at(&channels, now).await;
+        assert_eq!((summary.failed, summary.skipped), (1, 1));
+
+        fake.failing.lock().clear();
+        outbox.flush_at(&channels, now + 10_000).await;
+        let sent: Vec<String> = fake.sent.lock().iter().map(|(_, m)| m.clone()).collect();
+        assert_eq!(sent, vec!["first", "second"]);
+        assert!(outbox.pending().is_empty());
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_last_retry() {
+        let (outbox, fake, channels) = setup(None).await;
+        fake.failing.lock().insert("bad".to_string());
+        outbox.enqueue("fake", text("bad", "hello")).await.unwrap();
+
+        // Default settings: three attempts, at most 10s apart.
+        let now = now_ms();
+        for step in 0..3 {
+            outbox.flush_at(&channels, now + step * 30_000).await;
+        }
+        assert!(outbox.pending().is_empty());
+        let status = outbox.status("fake");
+        assert_eq!(status["failed"], 1);
+        assert_eq!(status["lastError"], "platform unavailable");
+    }
+
+    #[tokio::test]
+    async fn deliver_waits_for_the_entry() {
+        let (outbox, fake, channels) = setup(None).await;
+        let id = outbox.enqueue("fake", text("good", "hello")).await.unwrap();
+
+        outbox.deliver(&channels, &id).await.unwrap();
+        assert!(outbox.pending().is_empty());
+        assert_eq!(fake.sent.lock().len(), 1);
+        assert!(outbox.deliver(&channels, &id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn suspends_target_after_repeated_failures() {
+        let (mut outbox, fake, channels) = setup(None).await;
+        outbox.retry.insert(
+            ("fake".to_string(), DEFAULT_ACCOUNT_ID.to_string()),
+            OutboundRetryConfig {
+                attempts: 10,
+                min_delay_ms: 0,
+                max_delay_ms: 0,
+                jitter: Some(0.0),
+            },
+        );
+        fake.failing.lock().insert("bad".to_string());
+        outbox.enqueue("fake", text("bad", "hello")).await.unwrap();
+
+        let now = now_ms();
+        for _ in 0..SUSPEND_AFTER_FAILURES {
+            outbox.flush_at(&channels, now).await;
+        }
+        assert_eq!(outbox.status("fake")["suspendedTargets"], 1);
+        let summary = outbox.flush_at(&channels, now + 1).await;
+        assert_eq!((summary.failed, summary.skipped), (0, 1));
+
+        fake.failing.lock().clear();
+        outbox
+            .flush_at(&channels, now + TARGET_SUSPEND_MS + 1)
+            .await;
+        assert_eq!(fake.sent.lock().len(), 1);
+        assert_eq!(outbox.status("fake")["suspendedTargets"], 0);
+    }
+
+    #[tokio::test]
+    async fn drops_stale_messages() {
+        let (outbox, fake, channels) = setup(None).await;
+        outbox.enqueue("fake", text("chat", "late")).await.unwrap();
+
+        outbox.flush_at(&channels, now_ms() + 10 * 60 * 1000).await;
+        assert!(fake.sent.lock().is_empty());
+        assert!(outbox.pending().is_empty());
+        assert_eq!(outbox.status("fake")["dropped"], 1);
+    }
+
+    #[tokio::test]
+    async fn recovers_entries_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("channels").join("outbox.json");
+
+        let (outbox, _, _) = setup(Some(path.clone())).await;
+        let id = outbox
+            .enqueue("fake", text("chat", "survives"))
+            .await
+            .unwrap();
+        drop(outbox);
+
+        let (outbox, fake, channels) = setup(Some(path.clone())).await;
+        assert_eq!(outbox.load(), 1);
+        assert_eq!(outbox.pending()[0].id, id);
+
+        outbox.flush(&channels).await;
+        assert_eq!(fake.sent.lock().len(), 1);
+        let saved: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["entries"], serde_json::json!([]));
+    }
+}
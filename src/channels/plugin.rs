@@ -1,9 +1,17 @@
+use super::normalize::{NormalizedAttachment, NormalizedOutbound};
+use super::webhook::WebhookRoute;
 use crate::gateway::GatewayState;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use async_trait::async_trait;
+use axum::body::Bytes;
+use axum::http::{header, HeaderMap, Method, StatusCode};
+use axum::response::{IntoResponse, Response};
 use serde::{Deserialize, Serialize};
 
+/// Account id of a channel's top-level (unnamed) account.
+pub const DEFAULT_ACCOUNT_ID: &str = "default";
+
 /// Describes the capabilities a channel plugin supports.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ChannelCapability {
@@ -46,7 +54,7 @@ pub struct ChannelMeta {
     pub description: String,
     /// Whether the channel is currently enabled in configuration.
     pub enabled: bool,
-    /// Whether the channel supports multi-account mode.
+    /// Whether the channel runs an instance per entry of its `accounts` map.
     pub multi_account: bool,
 }
 
@@ -59,6 +67,13 @@ pub trait ChannelPlugin: Send + Sync + 'static {
     /// Unique identifier for this channel (e.g. "telegram").
     fn id(&self) -> &str;
 
+    /// Account this instance runs, for channels with `multi_account` set.
+    ///
+    /// The manager keeps one plugin instance per configured account.
+    fn account_id(&self) -> &str {
+        DEFAULT_ACCOUNT_ID
+    }
+
     /// Return static metadata about this channel.
     fn meta(&self) -> ChannelMeta;
 
@@ -74,9 +89,222 @@ pub trait ChannelPlugin: Send + Sync + 'static {
     /// Stop the channel account / connection gracefully.
     async fn stop_account(&self) -> Result<()>;
 
+    /// Check that a started account is still connected.
+    ///
+    /// The health supervisor calls this periodically while the account runs
+    /// (see [`super::health`]); an error marks it degraded, and an account
+    /// whose probes keep failing is restarted. The default reports healthy.
+    async fn probe(&self) -> Result<()> {
+        Ok(())
+    }
+
     /// Send a text message to the given recipient on this channel.
     ///
     /// The meaning of `to` is channel-specific: it may be a chat ID, a
     /// channel name, a phone number, etc.
     async fn send_message(&self, to: &str, message: &str) -> Result<()>;
+
+    /// Whether this channel advertises `capability`.
+    fn supports(&self, capability: ChannelCapability) -> bool {
+        self.capabilities().contains(&capability)
+    }
+
+    /// Send a structured message, returning the platform message id when
+    /// the channel reports one.
+    ///
+    /// The default sends the text with [`send_message`](Self::send_message),
+    /// dropping reply and thread ids and buttons; attachments fail with
+    /// [`UnsupportedCapability`]. Channels with `SendMedia` or `Threads`
+    /// override this.
+    async fn send(&self, message: &NormalizedOutbound) -> Result<Option<String>> {
+        if !message.attachments.is_empty() {
+            return Err(unsupported(self.id(), ChannelCapability::SendMedia));
+        }
+        self.send_message(&message.chat_id, &message.text).await?;
+        Ok(None)
+    }
+
+    /// Replace the text of a message previously sent by the bot.
+    async fn edit_message(&self, _chat_id: &str, _message_id: &str, _text: &str) -> Result<()> {
+        Err(unsupported(self.id(), ChannelCapability::EditMessage))
+    }
+
+    /// Delete a message.
+    async fn delete_message(&self, _chat_id: &str, _message_id: &str) -> Result<()> {
+        Err(unsupported(self.id(), ChannelCapability::DeleteMessage))
+    }
+
+    /// React to a message with `emoji`; an empty `emoji` removes the bot's
+    /// reaction.
+    async fn react(&self, _chat_id: &str, _message_id: &str, _emoji: &str) -> Result<()> {
+        Err(unsupported(self.id(), ChannelCapability::Reactions))
+    }
+
+    /// Show a typing indicator in a chat (and thread). Platforms clear it
+    /// on their own after a few seconds or when the bot sends a message.
+    async fn send_typing(&self, _chat_id: &str, _thread_id: Option<&str>) -> Result<()> {
+        Err(unsupported(self.id(), ChannelCapability::TypingIndicators))
+    }
+
+    /// Mark a message as read.
+    async fn mark_read(&self, _chat_id: &str, _message_id: &str) -> Result<()> {
+        Err(unsupported(self.id(), ChannelCapability::ReadReceipts))
+    }
+
+    /// Download an inbound attachment.
+    ///
+    /// The default returns the inline `data`, or fetches `url` without
+    /// credentials. Channels whose media URLs need authentication override
+    /// this.
+    async fn fetch_attachment(&self, attachment: &NormalizedAttachment) -> Result<Vec<u8>> {
+        if let Some(data) = &attachment.data {
+            return Ok(data.clone());
+        }
+        let Some(url) = attachment.url.as_deref() else {
+            bail!("{} attachment has neither data nor a URL", self.id());
+        };
+        let resp = reqwest::get(url).await?.error_for_status()?;
+        Ok(resp.bytes().await?.to_vec())
+    }
+
+    /// HTTP routes this channel serves below `/channels/<id>/`.
+    ///
+    /// The gateway answers requests matching none of them with 404 (or 405
+    /// for a known path) before calling [`handle_webhook`](Self::handle_webhook).
+    /// An empty list, the default, forwards every request.
+    fn webhook_routes(&self) -> Vec<WebhookRoute> {
+        Vec::new()
+    }
+
+    /// Handle an HTTP request delivered to this channel's webhook endpoint.
+    ///
+    /// The gateway forwards `/channels/<id>/<path>` to the plugin registered
+    /// under `<id>`. Channels that receive events over HTTP override this;
+    /// the default responds with 404.
+    async fn handle_webhook(
+        &self,
+        _state: &GatewayState,
+        _request: WebhookRequest,
+    ) -> Result<WebhookResponse> {
+        Ok(WebhookResponse::not_found())
+    }
+}
+
+/// Error returned for an operation a channel does not support.
+///
+/// Callers can `downcast_ref` the `anyhow::Error` to tell this apart from a
+/// failed platform request and fall back (e.g. send instead of edit).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedCapability {
+    /// Channel id.
+    pub channel: String,
+    /// Capability the operation needs.
+    pub capability: ChannelCapability,
+}
+
+impl std::fmt::Display for UnsupportedCapability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "channel {} does not support {:?}",
+            self.channel, self.capability
+        )
+    }
+}
+
+impl std::error::Error for UnsupportedCapability {}
+
+/// Build an [`UnsupportedCapability`] error.
+pub fn unsupported(channel: &str, capability: ChannelCapability) -> anyhow::Error {
+    UnsupportedCapability {
+        channel: channel.to_string(),
+        capability,
+    }
+    .into()
+}
+
+// ============================================================================
+// Webhook Ingress
+// ============================================================================
+
+/// An inbound HTTP request routed to a channel plugin.
+#[derive(Debug, Clone)]
+pub struct WebhookRequest {
+    /// HTTP method of the request.
+    pub method: Method,
+    /// Path below `/channels/<id>/`, without a leading slash.
+    pub path: String,
+    /// Raw query string, if any.
+    pub query: Option<String>,
+    /// Request headers.
+    pub headers: HeaderMap,
+    /// Raw request body (kept unparsed so signatures can be verified).
+    pub body: Bytes,
+}
+
+impl WebhookRequest {
+    /// Return a header value as a string, if present and valid UTF-8.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|v| v.to_str().ok())
+    }
+}
+
+/// Response returned by a channel webhook handler.
+#[derive(Debug, Clone)]
+pub struct WebhookResponse {
+    /// HTTP status code.
+    pub status: StatusCode,
+    /// Content type of `body`, if any.
+    pub content_type: Option<String>,
+    /// Response body.
+    pub body: Vec<u8>,
+}
+
+impl WebhookResponse {
+    /// Empty response with the given status.
+    pub fn status(status: StatusCode) -> Self {
+        Self {
+            status,
+            content_type: None,
+            body: Vec::new(),
+        }
+    }
+
+    /// Empty `200 OK` acknowledgement.
+    pub fn ok() -> Self {
+        Self::status(StatusCode::OK)
+    }
+
+    /// Empty `404 Not Found`.
+    pub fn not_found() -> Self {
+        Self::status(StatusCode::NOT_FOUND)
+    }
+
+    /// Plain-text response.
+    pub fn text(status: StatusCode, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type: Some("text/plain; charset=utf-8".to_string()),
+            body: body.into().into_bytes(),
+        }
+    }
+
+    /// JSON response.
+    pub fn json(status: StatusCode, body: &serde_json::Value) -> Self {
+        Self {
+            status,
+            content_type: Some("application/json".to_string()),
+            body: body.to_string().into_bytes(),
+        }
+    }
+}
+
+impl IntoResponse for WebhookResponse {
+    fn into_response(self) -> Response {
+        let mut response = (self.status, self.body).into_response();
+        if let Some(ct) = self.content_type.and_then(|ct| ct.parse().ok()) {
+            response.headers_mut().insert(header::CONTENT_TYPE, ct);
+        }
+        response
+    }
 }
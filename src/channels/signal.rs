@@ -1,26 +1,436 @@
-use crate::config::Config;
-use crate::gateway::GatewayState;
-
+use super::admission::group_allowlist;
+use super::format::render_signal_chunks;
+use super::group_history::{admissible, admit_group_message};
+use super::inbound::{dispatch_inbound, typing_interval_ms};
+use super::normalize::{
+    ChatType, NormalizedAttachment, NormalizedMessage, NormalizedOutbound, NormalizedSender,
+};
 use super::plugin::{ChannelCapability, ChannelMeta, ChannelPlugin};
+use super::voice;
+use super::TypingKeepaliveLoop;
+use crate::config::{Config, GroupPolicy, SignalConfig, SignalReceiveMode};
+use crate::gateway::GatewayState;
+use crate::infra::abort_signal::{monitor_with_abort_lifecycle, AbortHandle};
+use crate::infra::dm_policy::{self, check_dm_access};
 
-use anyhow::Result;
+use anyhow::{bail, Context as _, Result};
 use async_trait::async_trait;
-use tracing::info;
+use base64::Engine as _;
+use futures::StreamExt;
+use parking_lot::Mutex;
+use reqwest::{Client, Method};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio_tungstenite::{connect_async, tungstenite};
+use tracing::{debug, error, info, warn};
+
+// ============================================================================
+// Signal Channel Implementation
+// ============================================================================
+
+/// Signal itself has no hard limit, but very long messages are sent as
+/// attachments by clients; keep replies readable.
+const DEFAULT_TEXT_CHUNK_LIMIT: usize = 4_000;
+/// Typing indicators expire after ~15 s on Signal clients.
+const TYPING_INTERVAL_MS: u64 = 10_000;
+/// Server-side wait of one `GET /v1/receive` poll.
+const POLL_TIMEOUT_SECS: u64 = 10;
+/// Extra time on top of the poll timeout before a request is abandoned.
+const POLL_REQUEST_GRACE: Duration = Duration::from_secs(20);
+/// A connection that stayed up this long resets the reconnect backoff.
+const STABLE_CONNECTION: Duration = Duration::from_secs(60);
+/// Placeholder signal-cli puts in message bodies where a mention sits.
+const MENTION_PLACEHOLDER: char = '\u{FFFC}';
 
-/// Signal channel stub.
+/// State shared between the channel and its receive task.
+struct SignalClient {
+    account_id: String,
+    config: SignalConfig,
+    /// REST API base URL without a trailing slash.
+    api_url: Option<String>,
+    http: Client,
+}
+
+/// Signal channel backed by a signal-cli REST API daemon
+/// (`bbernhard/signal-cli-rest-api`).
 ///
-/// Signal integration requires a signal-cli REST API instance.  This stub
-/// registers the channel in the plugin system but does not yet implement the
-/// full protocol.
+/// Envelopes are received over the `/v1/receive/{number}` WebSocket in
+/// `json-rpc` mode, or by polling the same endpoint in `normal`/`native`
+/// mode (`receiveMode`). Messages, attachments, reactions, read receipts and
+/// typing indicators go through the REST API. DM and group admission is
+/// enforced through [`check_dm_access`].
 pub struct SignalChannel {
     enabled: bool,
+    client: Arc<SignalClient>,
+    /// Abort handle and task of the running receive loop, if any.
+    poller: Mutex<Option<(AbortHandle, tokio::task::JoinHandle<()>)>>,
 }
 
 impl SignalChannel {
     pub fn new(config: &Config) -> Self {
-        let enabled = config.channels.signal.enabled.unwrap_or(false);
+        let signal = &config.channels.signal;
+        let enabled = signal
+            .enabled
+            .unwrap_or(signal.api_url.is_some() && signal.phone_number.is_some());
+
+        Self {
+            enabled,
+            client: Arc::new(SignalClient {
+                account_id: "default".to_string(),
+                config: signal.clone(),
+                api_url: signal
+                    .api_url
+                    .as_deref()
+                    .map(|url| url.trim_end_matches('/').to_string()),
+                http: Client::new(),
+            }),
+            poller: Mutex::new(None),
+        }
+    }
 
-        Self { enabled }
+    /// Send an attachment, optionally captioned.
+    ///
+    /// `to` is a phone number, ACI/UUID or `group.<id>` recipient.
+    pub async fn send_attachment(
+        &self,
+        to: &str,
+        data: &[u8],
+        mime_type: &str,
+        filename: Option<&str>,
+        caption: Option<&str>,
+    ) -> Result<()> {
+        let encoded = base64::engine::general_purpose::STANDARD.encode(data);
+        let attachment = match filename {
+            Some(name) => format!("data:{mime_type};filename={name};base64,{encoded}"),
+            None => format!("data:{mime_type};base64,{encoded}"),
+        };
+        let mut body = self.client.send_body(to, caption.unwrap_or_default());
+        body["base64_attachments"] = json!([attachment]);
+        self.client
+            .api(Method::POST, "/v2/send", Some(&body))
+            .await?;
+        Ok(())
+    }
+
+    /// React to the message sent by `target_author` at `target_timestamp`.
+    pub async fn react(
+        &self,
+        to: &str,
+        target_author: &str,
+        target_timestamp: i64,
+        emoji: &str,
+    ) -> Result<()> {
+        let path = format!("/v1/reactions/{}", urlencoded(self.client.number()?));
+        let body = json!({
+            "reaction": emoji,
+            "recipient": to,
+            "target_author": target_author,
+            "timestamp": target_timestamp,
+        });
+        self.client.api(Method::POST, &path, Some(&body)).await?;
+        Ok(())
+    }
+}
+
+impl SignalClient {
+    fn api_url(&self) -> Result<&str> {
+        self.api_url
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Signal api_url not configured"))
+    }
+
+    fn number(&self) -> Result<&str> {
+        self.config
+            .phone_number
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Signal phone_number not configured"))
+    }
+
+    fn text_chunk_limit(&self) -> usize {
+        self.config
+            .text_chunk_limit
+            .unwrap_or(DEFAULT_TEXT_CHUNK_LIMIT)
+    }
+
+    /// Call a REST endpoint, returning the JSON body (`Null` when empty).
+    async fn api(&self, method: Method, path: &str, body: Option<&Value>) -> Result<Value> {
+        let mut req = self
+            .http
+            .request(method, format!("{}{}", self.api_url()?, path));
+        if let Some(body) = body {
+            req = req.json(body);
+        }
+        let resp = req.send().await?;
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        if !status.is_success() {
+            let error = serde_json::from_str::<Value>(&text)
+                .ok()
+                .and_then(|v| v["error"].as_str().map(str::to_string))
+                .unwrap_or(text);
+            bail!("Signal API {path} failed ({status}): {error}");
+        }
+        if text.trim().is_empty() {
+            return Ok(Value::Null);
+        }
+        serde_json::from_str(&text)
+            .with_context(|| format!("Signal API {path} returned invalid JSON"))
+    }
+
+    /// Base `/v2/send` body for one recipient.
+    fn send_body(&self, to: &str, message: &str) -> Value {
+        json!({
+            "number": self.config.phone_number,
+            "recipients": [to],
+            "message": message,
+        })
+    }
+
+    /// Receive WebSocket URL: the API URL with a `ws`/`wss` scheme.
+    fn websocket_url(&self) -> Result<String> {
+        let api = self.api_url()?;
+        let ws = if let Some(rest) = api.strip_prefix("https://") {
+            format!("wss://{rest}")
+        } else if let Some(rest) = api.strip_prefix("http://") {
+            format!("ws://{rest}")
+        } else {
+            bail!("Signal api_url must be http(s): {api}");
+        };
+        Ok(format!("{ws}/v1/receive/{}", urlencoded(self.number()?)))
+    }
+
+    /// Receive envelopes until the reconnect policy gives up.
+    async fn run(self: Arc<Self>, inbound: mpsc::UnboundedSender<NormalizedMessage>) {
+        let mode = self.config.receive_mode.unwrap_or_default();
+        let reconnect = self.config.reconnect.clone().unwrap_or_default();
+        let mut attempt = 0;
+        loop {
+            let started = Instant::now();
+            let result = match mode {
+                SignalReceiveMode::Websocket => self.stream(&inbound).await,
+                SignalReceiveMode::Poll => self.poll(&inbound).await,
+            };
+
+            if started.elapsed() >= STABLE_CONNECTION {
+                attempt = 0;
+            }
+            let Some(delay) = reconnect.delay(attempt) else {
+                error!(
+                    attempts = attempt,
+                    "Signal receive: giving up after repeated failures"
+                );
+                return;
+            };
+            attempt += 1;
+            match result {
+                Ok(()) => info!("Signal receive stream closed; reconnecting in {:?}", delay),
+                Err(e) => warn!(error = %e, "Signal receive failed; retrying in {:?}", delay),
+            }
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Read envelopes from the `json-rpc` mode WebSocket until it closes.
+    async fn stream(&self, inbound: &mpsc::UnboundedSender<NormalizedMessage>) -> Result<()> {
+        let (ws, _) = connect_async(self.websocket_url()?)
+            .await
+            .context("Signal receive WebSocket connect failed")?;
+        let (_sink, mut stream) = ws.split();
+        while let Some(frame) = stream.next().await {
+            match frame.context("Signal receive WebSocket error")? {
+                tungstenite::Message::Text(text) => match serde_json::from_str(text.as_str()) {
+                    Ok(envelope) => self.handle_envelope(&envelope, inbound),
+                    Err(e) => debug!(error = %e, "Ignoring non-JSON Signal frame"),
+                },
+                tungstenite::Message::Close(_) => break,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Poll `GET /v1/receive` until a request fails.
+    async fn poll(&self, inbound: &mpsc::UnboundedSender<NormalizedMessage>) -> Result<()> {
+        loop {
+            for envelope in self.receive_once().await? {
+                self.handle_envelope(&envelope, inbound);
+            }
+        }
+    }
+
+    async fn receive_once(&self) -> Result<Vec<Value>> {
+        let url = format!(
+            "{}/v1/receive/{}?timeout={POLL_TIMEOUT_SECS}",
+            self.api_url()?,
+            urlencoded(self.number()?)
+        );
+        let resp = self
+            .http
+            .get(url)
+            .timeout(Duration::from_secs(POLL_TIMEOUT_SECS) + POLL_REQUEST_GRACE)
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            bail!("Signal receive returned status {}", resp.status());
+        }
+        Ok(resp.json().await?)
+    }
+
+    /// Normalize one envelope and queue it if admitted.
+    fn handle_envelope(
+        &self,
+        envelope: &Value,
+        inbound: &mpsc::UnboundedSender<NormalizedMessage>,
+    ) {
+        let Some(msg) = normalize_envelope(
+            &self.account_id,
+            self.api_url.as_deref().unwrap_or_default(),
+            envelope,
+            self.config.phone_number.as_deref().unwrap_or_default(),
+        ) else {
+            return;
+        };
+        if admissible(&msg, |msg| self.admit(msg)) {
+            let _ = inbound.send(msg);
+        } else {
+            debug!(chat_id = %msg.chat_id, sender = %msg.sender.id, "Signal message not admitted");
+        }
+    }
+
+    /// Decide whether an inbound message should reach the agent.
+    ///
+    /// DMs follow `dmPolicy`/`allowFrom`; groups follow `groupPolicy` with
+    /// `groupAllowFrom` (which never inherits `allowFrom`) and then the
+    /// mention requirement.
+    fn admit(&self, msg: &NormalizedMessage) -> bool {
+        let senders = sender_addresses(msg);
+        match msg.chat_type {
+            // `dmPolicy` and `allowFrom` are applied by the admission stage.
+            ChatType::Dm => true,
+            ChatType::Group | ChatType::Thread => {
+                let admitted = match self.config.group_policy.unwrap_or_default() {
+                    GroupPolicy::Disabled => false,
+                    GroupPolicy::Open => true,
+                    GroupPolicy::Allowlist => {
+                        let entries = group_allowlist(
+                            self.config.group_allow_from.as_ref(),
+                            self.config.allow_from.as_ref(),
+                        );
+                        let allow =
+                            HashMap::from([(self.account_id.clone(), entries.unwrap_or_default())]);
+                        senders
+                            .iter()
+                            .chain(std::iter::once(&msg.chat_id.as_str()))
+                            .any(|source| {
+                                check_dm_access(
+                                    source,
+                                    dm_policy::DmPolicy::Allowlist,
+                                    &allow,
+                                    Some(&self.account_id),
+                                    true,
+                                )
+                                .allowed
+                            })
+                    }
+                };
+                admitted && (!self.config.require_mention.unwrap_or(true) || msg.mentioned)
+            }
+        }
+    }
+
+    /// Run the agent for an admitted message and reply.
+    async fn handle_message(self: Arc<Self>, state: GatewayState, mut msg: NormalizedMessage) {
+        if !admit_group_message(&state, &mut msg, |msg| self.admit(msg)).await {
+            return;
+        }
+        let timestamp = msg.id.parse::<i64>().unwrap_or_default();
+        if self.config.read_receipts.unwrap_or(true) {
+            if let Err(e) = self.send_read_receipt(&msg.sender.id, timestamp).await {
+                debug!(sender = %msg.sender.id, error = %e, "Signal read receipt failed");
+            }
+        }
+
+        let interval = typing_interval_ms(&*state.config.read().await, TYPING_INTERVAL_MS);
+        self.spawn_typing(&msg.chat_id, true);
+        let typing = TypingKeepaliveLoop::new(interval);
+        let typing_task = {
+            let client = self.clone();
+            let chat_id = msg.chat_id.clone();
+            typing.start(move || client.spawn_typing(&chat_id, true))
+        };
+
+        let result = dispatch_inbound(&state, &msg).await;
+        typing.stop();
+        typing_task.abort();
+        self.spawn_typing(&msg.chat_id, false);
+
+        // Group replies quote the triggering message.
+        let quote =
+            (msg.chat_type != ChatType::Dm).then(|| format!("{timestamp}:{}", msg.sender.id));
+        match result {
+            Ok(Some(reply)) => {
+                let outbound = NormalizedOutbound {
+                    reply_to_id: quote,
+                    ..NormalizedOutbound::text(msg.chat_id.clone(), reply)
+                };
+                let outbound = voice::voice_reply(&state, &msg, outbound).await;
+                if let Err(e) = state.channels.enqueue("signal", outbound).await {
+                    warn!(chat_id = %msg.chat_id, error = %e, "Signal reply failed");
+                }
+            }
+            Ok(None) => {}
+            Err(e) => warn!(chat_id = %msg.chat_id, error = %e, "Signal agent run failed"),
+        }
+    }
+
+    async fn send_read_receipt(&self, sender: &str, timestamp: i64) -> Result<()> {
+        let path = format!("/v1/receipts/{}", urlencoded(self.number()?));
+        let body = json!({
+            "receipt_type": "read",
+            "recipient": sender,
+            "timestamp": timestamp,
+        });
+        self.api(Method::POST, &path, Some(&body)).await.map(drop)
+    }
+
+    async fn set_typing(&self, recipient: &str, typing: bool) -> Result<()> {
+        let path = format!("/v1/typing-indicator/{}", urlencoded(self.number()?));
+        let method = if typing { Method::PUT } else { Method::DELETE };
+        self.api(method, &path, Some(&json!({ "recipient": recipient })))
+            .await
+            .map(drop)
+    }
+
+    fn spawn_typing(self: &Arc<Self>, recipient: &str, typing: bool) {
+        let client = self.clone();
+        let recipient = recipient.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = client.set_typing(&recipient, typing).await {
+                debug!(recipient = %recipient, error = %e, "Signal typing indicator failed");
+            }
+        });
+    }
+
+    /// Send text via `/v2/send`, quoting `(timestamp, author)` on the first chunk.
+    ///
+    /// Markdown is rendered to plain text: the REST API's `/v2/send` takes no
+    /// style ranges, so the ranges from [`render_signal_chunks`] are dropped.
+    async fn send_text(&self, to: &str, text: &str, quote: Option<(i64, &str)>) -> Result<()> {
+        for (i, chunk) in render_signal_chunks(text, self.text_chunk_limit())
+            .into_iter()
+            .enumerate()
+        {
+            let mut body = self.send_body(to, &chunk.text);
+            if let Some((timestamp, author)) = quote.filter(|_| i == 0) {
+                body["quote_timestamp"] = json!(timestamp);
+                body["quote_author"] = json!(author);
+            }
+            self.api(Method::POST, "/v2/send", Some(&body)).await?;
+        }
+        Ok(())
     }
 }
 
@@ -33,7 +443,7 @@ impl ChannelPlugin for SignalChannel {
     fn meta(&self) -> ChannelMeta {
         ChannelMeta {
             name: "Signal".to_string(),
-            description: "Signal Messenger channel (stub)".to_string(),
+            description: "Signal Messenger channel via signal-cli REST API".to_string(),
             enabled: self.enabled,
             multi_account: false,
         }
@@ -45,39 +455,601 @@ impl ChannelPlugin for SignalChannel {
             ChannelCapability::ReceiveText,
             ChannelCapability::SendMedia,
             ChannelCapability::ReceiveMedia,
+            ChannelCapability::Reactions,
             ChannelCapability::Groups,
             ChannelCapability::ReadReceipts,
             ChannelCapability::TypingIndicators,
+            ChannelCapability::Voice,
         ]
     }
 
-    async fn start_account(&self, _state: &GatewayState) -> Result<()> {
+    async fn start_account(&self, state: &GatewayState) -> Result<()> {
         if !self.enabled {
             return Ok(());
         }
 
-        info!("Signal channel starting (stub)");
-        // TODO: Connect to signal-cli REST API.
+        let Some(api_url) = &self.client.api_url else {
+            warn!("Signal channel enabled but no api_url configured");
+            return Ok(());
+        };
+
+        let Some(number) = &self.client.config.phone_number else {
+            warn!("Signal channel enabled but no phone_number configured");
+            return Ok(());
+        };
+
+        info!(
+            api_url = %api_url,
+            number = %number,
+            mode = ?self.client.config.receive_mode.unwrap_or_default(),
+            "Signal channel starting"
+        );
+
+        let abort = AbortHandle::new();
+        let task = {
+            let client = self.client.clone();
+            let state = state.clone();
+            let abort = abort.clone();
+            tokio::spawn(async move {
+                let (inbound_tx, mut inbound_rx) = mpsc::unbounded_channel();
+                let dispatch = {
+                    let client = client.clone();
+                    async move {
+                        while let Some(msg) = inbound_rx.recv().await {
+                            tokio::spawn(client.clone().handle_message(state.clone(), msg));
+                        }
+                    }
+                };
+                let receiving = async { tokio::join!(client.run(inbound_tx), dispatch) };
+                if monitor_with_abort_lifecycle(receiving, &abort)
+                    .await
+                    .is_err()
+                {
+                    debug!("Signal receive loop stopped");
+                }
+            })
+        };
+        if let Some((previous, _)) = self.poller.lock().replace((abort, task)) {
+            previous.abort();
+        }
 
         Ok(())
     }
 
     async fn stop_account(&self) -> Result<()> {
         if self.enabled {
-            info!("Signal channel stopping (stub)");
+            info!("Signal channel stopping");
+            let poller = self.poller.lock().take();
+            if let Some((abort, task)) = poller {
+                abort.abort();
+                let _ = task.await;
+            }
         }
         Ok(())
     }
 
-    async fn send_message(&self, to: &str, _message: &str) -> Result<()> {
-        info!(to = to, "Signal: sending message (stub -- not implemented)");
-        // TODO: Forward to signal-cli REST API /v2/send endpoint.
-        Ok(())
+    async fn send_message(&self, to: &str, message: &str) -> Result<()> {
+        // `to` is a phone number (E.164), an ACI/UUID, or a `group.<id>`
+        // recipient as listed by `GET /v1/groups/{number}`.
+        info!(to = %to, "Signal: sending message");
+        self.client.send_text(to, message, None).await
+    }
+
+    async fn send(&self, message: &NormalizedOutbound) -> Result<Option<String>> {
+        for attachment in &message.attachments {
+            let Some(data) = attachment.data.as_deref() else {
+                bail!("Signal attachments need inline data");
+            };
+            let mime_type = attachment
+                .mime_type
+                .as_deref()
+                .unwrap_or("application/octet-stream");
+            self.send_attachment(
+                &message.chat_id,
+                data,
+                mime_type,
+                attachment.filename.as_deref(),
+                None,
+            )
+            .await?;
+        }
+        if message.text.trim().is_empty() {
+            return Ok(None);
+        }
+        // A Signal message is identified by its timestamp and author, so
+        // quoted replies carry `reply_to_id` as `<timestamp>:<author>`.
+        let quote = message
+            .reply_to_id
+            .as_deref()
+            .and_then(|id| id.split_once(':'))
+            .and_then(|(timestamp, author)| Some((timestamp.parse().ok()?, author)));
+        self.client
+            .send_text(&message.chat_id, &message.text, quote)
+            .await?;
+        Ok(None)
+    }
+}
+
+// ============================================================================
+// Envelope Handling
+// ============================================================================
+
+/// Percent-encode a path segment (phone numbers carry a `+`).
+fn urlencoded(s: &str) -> String {
+    url::form_urlencoded::byte_serialize(s.as_bytes()).collect()
+}
+
+/// `/v2/send` recipient for a group, given the internal id from envelopes.
+fn group_recipient(internal_id: &str) -> String {
+    format!(
+        "group.{}",
+        base64::engine::general_purpose::STANDARD.encode(internal_id)
+    )
+}
+
+/// Addresses a sender can be matched by: number and ACI/UUID.
+fn sender_addresses(msg: &NormalizedMessage) -> Vec<&str> {
+    let mut addresses = vec![msg.sender.id.as_str()];
+    if let Some(uuid) = msg
+        .raw
+        .as_ref()
+        .and_then(|raw| raw["envelope"]["sourceUuid"].as_str())
+    {
+        if uuid != msg.sender.id {
+            addresses.push(uuid);
+        }
+    }
+    addresses
+}
+
+/// Replace mention placeholders in `text` with `@name`, dropping mentions
+/// of `own_number`. Returns whether the bot was mentioned.
+fn render_mentions(text: &str, mentions: &[Value], own_number: &str) -> (bool, String) {
+    let mut mentions: Vec<&Value> = mentions.iter().collect();
+    mentions.sort_by_key(|m| m["start"].as_u64().unwrap_or_default());
+    let mut mentions = mentions.into_iter();
+
+    let mut mentioned = false;
+    let mut rendered = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c != MENTION_PLACEHOLDER {
+            rendered.push(c);
+            continue;
+        }
+        let Some(mention) = mentions.next() else {
+            continue;
+        };
+        if !own_number.is_empty() && mention["number"].as_str() == Some(own_number) {
+            mentioned = true;
+            continue;
+        }
+        let name = ["name", "number", "uuid"]
+            .iter()
+            .find_map(|key| mention[*key].as_str().filter(|s| !s.is_empty()))
+            .unwrap_or("unknown");
+        rendered.push('@');
+        rendered.push_str(name);
     }
+    (
+        mentioned,
+        rendered.split_whitespace().collect::<Vec<_>>().join(" "),
+    )
 }
 
-/// Convenience function called by the top-level `send_message` dispatcher.
-pub(crate) async fn send_message(config: &Config, to: &str, message: &str) -> Result<()> {
-    let channel = SignalChannel::new(config);
-    channel.send_message(to, message).await
+/// Convert a signal-cli receive envelope into a [`NormalizedMessage`].
+///
+/// Only data messages with text or attachments are returned; receipts,
+/// typing notifications, sync messages (our own sends from linked devices)
+/// and reactions are skipped. Groups use their `group.<id>` recipient as
+/// `chat_id` so replies can be addressed directly.
+fn normalize_envelope(
+    account_id: &str,
+    api_url: &str,
+    wrapper: &Value,
+    own_number: &str,
+) -> Option<NormalizedMessage> {
+    let envelope = &wrapper["envelope"];
+    let data = envelope.get("dataMessage")?;
+    if data.get("reaction").is_some() || data.get("remoteDelete").is_some() {
+        return None;
+    }
+
+    let sender_id = ["sourceNumber", "source", "sourceUuid"]
+        .iter()
+        .find_map(|key| envelope[*key].as_str().filter(|s| !s.is_empty()))?;
+    if !own_number.is_empty() && sender_id == own_number {
+        return None;
+    }
+
+    let (mut mentioned, text) = render_mentions(
+        data["message"].as_str().unwrap_or_default(),
+        data["mentions"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default(),
+        own_number,
+    );
+
+    let attachments: Vec<NormalizedAttachment> = data["attachments"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|attachment| {
+            let id = attachment["id"].as_str()?;
+            Some(NormalizedAttachment {
+                mime_type: attachment["contentType"].as_str().map(str::to_string),
+                url: Some(format!("{api_url}/v1/attachments/{}", urlencoded(id))),
+                data: None,
+                filename: attachment["filename"].as_str().map(str::to_string),
+                size: attachment["size"].as_u64(),
+            })
+        })
+        .collect();
+    if text.is_empty() && attachments.is_empty() {
+        return None;
+    }
+
+    let quote = &data["quote"];
+    let reply_to_id = quote["id"].as_i64().map(|id| id.to_string());
+    if !own_number.is_empty()
+        && (quote["authorNumber"].as_str() == Some(own_number)
+            || quote["author"].as_str() == Some(own_number))
+    {
+        mentioned = true;
+    }
+
+    let group = &data["groupInfo"];
+    let (chat_id, chat_name, chat_type) = match group["groupId"].as_str() {
+        Some(group_id) => (
+            group_recipient(group_id),
+            group["groupName"].as_str().map(str::to_string),
+            ChatType::Group,
+        ),
+        None => {
+            mentioned = true;
+            (sender_id.to_string(), None, ChatType::Dm)
+        }
+    };
+
+    let timestamp = data["timestamp"]
+        .as_i64()
+        .or_else(|| envelope["timestamp"].as_i64())?;
+
+    Some(NormalizedMessage {
+        id: timestamp.to_string(),
+        channel: "signal".to_string(),
+        account_id: account_id.to_string(),
+        chat_id,
+        chat_name,
+        chat_type,
+        sender: NormalizedSender {
+            id: sender_id.to_string(),
+            name: envelope["sourceName"]
+                .as_str()
+                .filter(|name| !name.is_empty())
+                .unwrap_or(sender_id)
+                .to_string(),
+            is_bot: false,
+            roles: Vec::new(),
+        },
+        text,
+        attachments,
+        reply_to_id,
+        thread_id: None,
+        mentioned,
+        timestamp: chrono::DateTime::from_timestamp_millis(timestamp)
+            .unwrap_or_else(chrono::Utc::now)
+            .to_rfc3339(),
+        raw: Some(wrapper.clone()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::{Admission, Verdict};
+    use crate::config::DmPolicy;
+    use futures::SinkExt;
+    use tokio::net::TcpListener;
+    use wiremock::matchers::{body_partial_json, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const BOT: &str = "+15550000000";
+
+    fn envelope(data: Value) -> Value {
+        json!({
+            "account": BOT,
+            "envelope": {
+                "source": "+15551112222",
+                "sourceNumber": "+15551112222",
+                "sourceUuid": "aaaa-bbbb",
+                "sourceName": "Alice",
+                "sourceDevice": 1,
+                "timestamp": 1_700_000_000_000i64,
+                "dataMessage": data,
+            }
+        })
+    }
+
+    fn group_text(message: &str, mentions: Value) -> Value {
+        envelope(json!({
+            "timestamp": 1_700_000_000_000i64,
+            "message": message,
+            "mentions": mentions,
+            "groupInfo": { "groupId": "internal==", "groupName": "Crew", "type": "DELIVER" },
+        }))
+    }
+
+    fn normalize(wrapper: &Value) -> Option<NormalizedMessage> {
+        normalize_envelope("default", "http://signal", wrapper, BOT)
+    }
+
+    fn client_with(api_url: &str, configure: impl FnOnce(&mut SignalConfig)) -> SignalChannel {
+        let mut config = Config::default();
+        config.channels.signal.api_url = Some(api_url.to_string());
+        config.channels.signal.phone_number = Some(BOT.to_string());
+        configure(&mut config.channels.signal);
+        SignalChannel::new(&config)
+    }
+
+    #[test]
+    fn normalize_dm_with_attachment() {
+        let wrapper = envelope(json!({
+            "timestamp": 1_700_000_000_000i64,
+            "message": "look",
+            "attachments": [{ "id": "abc.jpg", "contentType": "image/jpeg", "filename": "cat.jpg", "size": 42 }],
+        }));
+        let msg = normalize(&wrapper).unwrap();
+        assert_eq!(msg.chat_type, ChatType::Dm);
+        assert_eq!(msg.chat_id, "+15551112222");
+        assert_eq!(msg.sender.name, "Alice");
+        assert_eq!(msg.id, "1700000000000");
+        assert!(msg.mentioned);
+        assert_eq!(
+            msg.attachments[0].url.as_deref(),
+            Some("http://signal/v1/attachments/abc.jpg")
+        );
+    }
+
+    #[test]
+    fn normalize_group_mentions_and_quotes() {
+        let wrapper = group_text(
+            "\u{FFFC} ask \u{FFFC}",
+            json!([
+                { "start": 0, "length": 1, "number": BOT, "uuid": "bot-uuid" },
+                { "start": 6, "length": 1, "name": "Bob", "number": "+15553334444" },
+            ]),
+        );
+        let msg = normalize(&wrapper).unwrap();
+        assert_eq!(msg.chat_type, ChatType::Group);
+        assert_eq!(msg.chat_id, group_recipient("internal=="));
+        assert_eq!(msg.chat_name.as_deref(), Some("Crew"));
+        assert!(msg.mentioned);
+        assert_eq!(msg.text, "ask @Bob");
+
+        let mut reply = group_text("and this?", json!([]));
+        reply["envelope"]["dataMessage"]["quote"] = json!({ "id": 1_699i64, "authorNumber": BOT });
+        let msg = normalize(&reply).unwrap();
+        assert!(msg.mentioned);
+        assert_eq!(msg.reply_to_id.as_deref(), Some("1699"));
+
+        assert!(
+            !normalize(&group_text("chatter", json!([])))
+                .unwrap()
+                .mentioned
+        );
+    }
+
+    #[test]
+    fn normalize_skips_non_messages() {
+        let mut receipt = envelope(json!({}));
+        receipt["envelope"]
+            .as_object_mut()
+            .unwrap()
+            .remove("dataMessage");
+        receipt["envelope"]["receiptMessage"] = json!({ "isRead": true });
+        assert!(normalize(&receipt).is_none());
+
+        let reaction = envelope(json!({ "reaction": { "emoji": "👍" }, "timestamp": 1 }));
+        assert!(normalize(&reaction).is_none());
+
+        let mut own = envelope(json!({ "message": "echo", "timestamp": 1 }));
+        own["envelope"]["sourceNumber"] = json!(BOT);
+        assert!(normalize(&own).is_none());
+    }
+
+    #[test]
+    fn group_recipient_encodes_internal_id() {
+        assert_eq!(group_recipient("abc"), "group.YWJj");
+    }
+
+    #[test]
+    fn websocket_url_encodes_number() {
+        let channel = client_with("https://signal.example.com/", |_| {});
+        assert_eq!(
+            channel.client.websocket_url().unwrap(),
+            "wss://signal.example.com/v1/receive/%2B15550000000"
+        );
+    }
+
+    #[test]
+    fn dm_admission_uses_policy_and_allowlist() {
+        let dm = normalize(&envelope(json!({ "message": "hi", "timestamp": 1 }))).unwrap();
+        assert!(client_with("http://s", |_| {}).client.admit(&dm));
+
+        let admission = Admission::new();
+        let check = |configure: fn(&mut SignalConfig)| {
+            let mut config = Config::default();
+            configure(&mut config.channels.signal);
+            admission.check(&config, &dm)
+        };
+        assert_eq!(check(|_| {}), Verdict::Admit);
+        assert_eq!(
+            check(|c| {
+                c.dm_policy = Some(DmPolicy::Allowlist);
+                c.allow_from = Some(vec!["aaaa-bbbb".to_string()]);
+            }),
+            Verdict::Admit
+        );
+        assert_ne!(
+            check(|c| c.allow_from = Some(vec!["+19999999999".to_string()])),
+            Verdict::Admit
+        );
+        assert_ne!(
+            check(|c| c.dm_policy = Some(DmPolicy::Disabled)),
+            Verdict::Admit
+        );
+    }
+
+    #[test]
+    fn group_admission_uses_group_policy_and_mentions() {
+        let mention = json!([{ "start": 0, "length": 1, "number": BOT }]);
+        let addressed = normalize(&group_text("\u{FFFC} hi", mention)).unwrap();
+        let chatter = normalize(&group_text("hi all", json!([]))).unwrap();
+
+        let open = client_with("http://s", |_| {});
+        assert!(open.client.admit(&addressed));
+        assert!(!open.client.admit(&chatter));
+
+        // Group allowlists never inherit the DM allowlist.
+        let inherited = client_with("http://s", |c| {
+            c.group_policy = Some(GroupPolicy::Allowlist);
+            c.allow_from = Some(vec!["+15551112222".to_string()]);
+        });
+        assert!(!inherited.client.admit(&addressed));
+        let by_group = client_with("http://s", |c| {
+            c.group_policy = Some(GroupPolicy::Allowlist);
+            c.group_allow_from = Some(vec![group_recipient("internal==")]);
+        });
+        assert!(by_group.client.admit(&addressed));
+        let disabled = client_with("http://s", |c| c.group_policy = Some(GroupPolicy::Disabled));
+        assert!(!disabled.client.admit(&addressed));
+    }
+
+    #[tokio::test]
+    async fn poll_receive_against_stub() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/receive/%2B15550000000"))
+            .and(query_param("timeout", "10"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                envelope(json!({ "message": "hello", "timestamp": 5 })),
+                group_text("ignored chatter", json!([])),
+            ])))
+            .mount(&server)
+            .await;
+
+        let channel = client_with(&server.uri(), |_| {});
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        for envelope in channel.client.receive_once().await.unwrap() {
+            channel.client.handle_envelope(&envelope, &tx);
+        }
+        drop(tx);
+        assert_eq!(rx.recv().await.unwrap().text, "hello");
+        // Group chatter is forwarded unmentioned, to be buffered as context.
+        let chatter = rx.recv().await.unwrap();
+        assert_eq!(chatter.text, "ignored chatter");
+        assert!(!chatter.mentioned);
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn websocket_receive_against_local_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let channel = client_with(&format!("http://{addr}"), |_| {});
+        let client = channel.client.clone();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let stream = tokio::spawn(async move { client.stream(&tx).await });
+
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(socket).await.unwrap();
+        let frame = envelope(json!({ "message": "over ws", "timestamp": 7 }));
+        ws.send(tungstenite::Message::Text(frame.to_string().into()))
+            .await
+            .unwrap();
+        let msg = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(msg.text, "over ws");
+
+        ws.close(None).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), stream)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn send_quote_attachment_and_reaction() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v2/send"))
+            .and(body_partial_json(json!({
+                "number": BOT,
+                "recipients": ["group.YWJj"],
+                "message": "reply",
+                "quote_timestamp": 42,
+                "quote_author": "+15551112222",
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "timestamp": "1" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v2/send"))
+            .and(body_partial_json(json!({
+                "base64_attachments": ["data:text/plain;filename=a.txt;base64,aGk="],
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "timestamp": "2" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/reactions/%2B15550000000"))
+            .and(body_partial_json(json!({
+                "reaction": "👍",
+                "recipient": "+15551112222",
+                "target_author": "+15551112222",
+                "timestamp": 42,
+            })))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let channel = client_with(&server.uri(), |_| {});
+        channel
+            .client
+            .send_text("group.YWJj", "reply", Some((42, "+15551112222")))
+            .await
+            .unwrap();
+        channel
+            .send_attachment("+15551112222", b"hi", "text/plain", Some("a.txt"), None)
+            .await
+            .unwrap();
+        channel
+            .react("+15551112222", "+15551112222", 42, "👍")
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn api_errors_surface_daemon_message() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v2/send"))
+            .respond_with(
+                ResponseTemplate::new(400).set_body_json(json!({ "error": "Unregistered user" })),
+            )
+            .mount(&server)
+            .await;
+
+        let channel = client_with(&server.uri(), |_| {});
+        let err = channel.send_message("+1", "hi").await.unwrap_err();
+        assert!(err.to_string().contains("Unregistered user"));
+    }
 }
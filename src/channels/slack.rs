@@ -1,10 +1,32 @@
-use crate::config::Config;
+use crate::config::{
+    Config, GroupPolicy, ReplyToMode, SlackAccountConfig, SlackChannelConfig, SlackDmConfig,
+};
 use crate::gateway::GatewayState;
+use crate::infra::dm_policy;
 
-use super::plugin::{ChannelCapability, ChannelMeta, ChannelPlugin};
+use super::admission::account;
+use super::commands;
+use super::format::{render_markdown_chunks, FormatTarget};
+use super::group_history::admit_group_message;
+use super::inbound::{dispatch_inbound, dispatch_inbound_to_session, resolve_session_key};
+use super::normalize::{
+    split_text, ChatType, NormalizedAttachment, NormalizedMessage, NormalizedOutbound,
+    NormalizedSender,
+};
+use super::plugin::{
+    unsupported, ChannelCapability, ChannelMeta, ChannelPlugin, WebhookRequest, WebhookResponse,
+    DEFAULT_ACCOUNT_ID,
+};
+use super::webhook::WebhookRoute;
 
-use anyhow::Result;
+use anyhow::{Context as _, Result};
 use async_trait::async_trait;
+use axum::http::{header, Method, StatusCode};
+use parking_lot::{Mutex, RwLock};
+use slack_morphism::prelude::*;
+use slack_morphism::signature_verifier::SlackEventSignatureVerifier;
+use std::collections::HashMap;
+use std::sync::Arc;
 use tracing::{debug, info, warn};
 
 // ============================================================================
@@ -28,34 +50,643 @@ pub fn should_suppress_message(message: &str) -> bool {
 // Slack Channel Implementation
 // ============================================================================
 
+/// Default Slack Web API base URL.
+const SLACK_API_URL: &str = "https://slack.com/api";
+/// Slack truncates message text beyond this many characters.
+const SLACK_MAX_MESSAGE_CHARS: usize = 40_000;
+/// Events API path below `/channels/slack/` when `webhookPath` is unset.
+const DEFAULT_WEBHOOK_PATH: &str = "events";
+/// Session key prefix for slash commands when `slashCommand.sessionPrefix` is unset.
+const DEFAULT_SLASH_SESSION_PREFIX: &str = "slack:slash";
+/// Header Slack sets when redelivering an Events API request.
+const RETRY_NUM_HEADER: &str = "x-slack-retry-num";
+
+/// Kind of Slack conversation, from the event's `channel_type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConversationKind {
+    /// 1:1 DM (`im`).
+    Direct,
+    /// Multi-person DM (`mpim`).
+    GroupDm,
+    /// Public or private channel.
+    Channel,
+}
+
+impl ConversationKind {
+    fn from_channel_type(channel_type: Option<&str>) -> Self {
+        match channel_type {
+            Some("im") => Self::Direct,
+            Some("mpim") => Self::GroupDm,
+            _ => Self::Channel,
+        }
+    }
+
+    /// Key used by `replyToModeByChatType`.
+    fn reply_mode_key(self) -> &'static str {
+        match self {
+            Self::Direct => "direct",
+            Self::GroupDm => "group",
+            Self::Channel => "channel",
+        }
+    }
+}
+
+/// Per-account state shared between the channel and its event handlers.
+struct SlackAccount {
+    account_id: String,
+    config: SlackAccountConfig,
+    bot_token: Option<String>,
+    app_token: Option<String>,
+    /// Bot user id, learned from `auth.test`.
+    bot_user_id: RwLock<Option<String>>,
+    /// Display names resolved via `users.info`, keyed by user id.
+    user_names: Mutex<HashMap<String, String>>,
+}
+
 /// Slack channel implementation using slack-morphism.
+///
+/// Events arrive over Socket Mode when an app token is configured (or
+/// `mode: "socket"`), otherwise over the HTTP Events API at
+/// `/channels/slack/<webhookPath>`, verified against `signingSecret`.
 pub struct SlackChannel {
     enabled: bool,
-    bot_token: Option<String>,
-    app_token: Option<String>,
+    account: Arc<SlackAccount>,
+    /// Running Socket Mode listener, if any.
+    socket: Mutex<Option<Arc<SlackClientSocketModeListener<SlackClientHyperHttpsConnector>>>>,
 }
 
 impl SlackChannel {
     pub fn new(config: &Config) -> Self {
+        Self::for_account(config, DEFAULT_ACCOUNT_ID)
+    }
+
+    /// The channel for one app account: an entry of `accounts`, or the
+    /// top-level settings for the default account.
+    ///
+    /// Named accounts without a `webhookPath` listen on `events/<id>`.
+    pub fn for_account(config: &Config, account_id: &str) -> Self {
         let sl = &config.channels.slack;
-        let bot_token = sl.default_account.bot_token.clone();
-        let app_token = sl.default_account.app_token.clone();
-        let enabled = sl.default_account.enabled.unwrap_or(bot_token.is_some());
+        let mut settings = account(&sl.accounts, &sl.default_account, account_id).clone();
+        if account_id != DEFAULT_ACCOUNT_ID && settings.webhook_path.is_none() {
+            settings.webhook_path = Some(format!("{DEFAULT_WEBHOOK_PATH}/{account_id}"));
+        }
+        let bot_token = settings.bot_token.clone();
+        let app_token = settings.app_token.clone();
+        let enabled = settings.enabled.unwrap_or(bot_token.is_some());
 
         Self {
             enabled,
-            bot_token,
-            app_token,
+            account: Arc::new(SlackAccount {
+                account_id: account_id.to_string(),
+                config: settings,
+                bot_token,
+                app_token,
+                bot_user_id: RwLock::new(None),
+                user_names: Mutex::new(HashMap::new()),
+            }),
+            socket: Mutex::new(None),
         }
     }
 }
 
+impl SlackAccount {
+    /// Build a Web API client, honouring `apiUrl`.
+    fn client(&self) -> Result<Arc<SlackHyperClient>> {
+        let api_url = self
+            .config
+            .api_url
+            .as_deref()
+            .unwrap_or(SLACK_API_URL)
+            .trim_end_matches('/');
+        // Both rustls backends are linked into the binary, so the provider
+        // has to be chosen explicitly.
+        let builder = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_provider_and_native_roots(rustls::crypto::ring::default_provider())
+            .context("failed to load TLS root certificates")?;
+        // Plain HTTP is only allowed for an explicitly configured local API.
+        let builder = if api_url.starts_with("http://") {
+            builder.https_or_http()
+        } else {
+            builder.https_only()
+        };
+        let connector = builder.enable_http1().enable_http2().build();
+        let connector =
+            SlackClientHyperConnector::with_connector(connector).with_slack_api_url(api_url);
+        Ok(Arc::new(SlackClient::new(connector)))
+    }
+
+    fn token(&self) -> Result<SlackApiToken> {
+        let token = self
+            .bot_token
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Slack bot token not configured"))?;
+        Ok(SlackApiToken::new(token.into()))
+    }
+
+    /// Whether events should be received over Socket Mode.
+    fn use_socket_mode(&self) -> bool {
+        match self.config.mode.as_deref() {
+            Some("socket") => true,
+            Some("http") => false,
+            _ => self.app_token.is_some(),
+        }
+    }
+
+    fn webhook_path(&self) -> &str {
+        self.config
+            .webhook_path
+            .as_deref()
+            .map(|p| p.trim_matches('/'))
+            .filter(|p| !p.is_empty())
+            .unwrap_or(DEFAULT_WEBHOOK_PATH)
+    }
+
+    /// Verify the `X-Slack-Signature` of an Events API request.
+    fn webhook_authorized(&self, request: &WebhookRequest) -> bool {
+        let Some(secret) = self.config.signing_secret.as_deref() else {
+            return false;
+        };
+        let (Some(signature), Some(timestamp)) = (
+            request.header(SlackEventSignatureVerifier::SLACK_SIGNED_HASH_HEADER),
+            request.header(SlackEventSignatureVerifier::SLACK_SIGNED_TIMESTAMP),
+        ) else {
+            return false;
+        };
+        let Ok(body) = std::str::from_utf8(&request.body) else {
+            return false;
+        };
+        SlackEventSignatureVerifier::new(&secret.to_string().into())
+            .verify(signature, body, timestamp)
+            .is_ok()
+    }
+
+    fn text_chunk_limit(&self) -> usize {
+        (self.config.text_chunk_limit as usize).clamp(1, SLACK_MAX_MESSAGE_CHARS)
+    }
+
+    fn dm_config(&self) -> Option<&SlackDmConfig> {
+        self.config.dm.as_ref().or(self.config.dms.as_ref())
+    }
+
+    /// Resolve the channel config, and whether it was listed explicitly.
+    fn channel_config(&self, channel_id: &str) -> (Option<&SlackChannelConfig>, bool) {
+        let channels = self.config.channels.as_ref();
+        let explicit = channels.and_then(|c| c.get(channel_id));
+        let channel = explicit.or_else(|| channels.and_then(|c| c.get("*")));
+        (channel, explicit.is_some())
+    }
+
+    fn reply_mode(&self, kind: ConversationKind) -> ReplyToMode {
+        self.config
+            .reply_to_mode_by_chat_type
+            .as_ref()
+            .and_then(|m| m.get(kind.reply_mode_key()).copied())
+            .or_else(|| {
+                (kind == ConversationKind::Direct)
+                    .then(|| self.dm_config().and_then(|d| d.reply_to_mode))
+                    .flatten()
+            })
+            .or(self.config.reply_to_mode)
+            .unwrap_or_default()
+    }
+
+    /// Decide whether an inbound message should reach the agent.
+    fn admit(&self, msg: &NormalizedMessage, kind: ConversationKind) -> bool {
+        let dm = self.dm_config();
+
+        if kind != ConversationKind::Channel {
+            if msg.sender.is_bot && self.config.allow_bots != Some(true) {
+                return false;
+            }
+            if dm.and_then(|d| d.enabled) == Some(false) {
+                return false;
+            }
+        }
+
+        match kind {
+            // `dm.policy` and `dm.allowFrom` are applied by the admission stage.
+            ConversationKind::Direct => true,
+            ConversationKind::GroupDm => {
+                if dm.and_then(|d| d.group_enabled) != Some(true) {
+                    return false;
+                }
+                let group_channels = dm
+                    .and_then(|d| d.group_channels.as_deref())
+                    .unwrap_or_default();
+                if !group_channels.is_empty() && !group_channels.contains(&msg.chat_id) {
+                    return false;
+                }
+                !self.config.require_mention.unwrap_or(true) || msg.mentioned
+            }
+            ConversationKind::Channel => {
+                let (channel, explicit) = self.channel_config(&msg.chat_id);
+
+                if channel.is_some_and(|c| c.enabled == Some(false) || c.allow == Some(false)) {
+                    return false;
+                }
+
+                let allow_bots = channel
+                    .and_then(|c| c.allow_bots)
+                    .or(self.config.allow_bots)
+                    .unwrap_or(false);
+                if msg.sender.is_bot && !allow_bots {
+                    return false;
+                }
+
+                match self.config.group_policy.unwrap_or_default() {
+                    GroupPolicy::Disabled => return false,
+                    GroupPolicy::Allowlist if !explicit => return false,
+                    GroupPolicy::Allowlist | GroupPolicy::Open => {}
+                }
+
+                if let Some(users) = channel
+                    .and_then(|c| c.users.as_deref())
+                    .filter(|u| !u.is_empty())
+                {
+                    if !sender_matches(users, &msg.sender) {
+                        return false;
+                    }
+                }
+
+                let require_mention = channel
+                    .and_then(|c| c.require_mention)
+                    .or(self.config.require_mention)
+                    .unwrap_or(true);
+                !require_mention || msg.mentioned
+            }
+        }
+    }
+
+    /// Display name for a user, resolved via `users.info` and cached.
+    async fn user_name(
+        &self,
+        session: &SlackClientSession<'_, SlackClientHyperHttpsConnector>,
+        user_id: &str,
+    ) -> Option<String> {
+        if let Some(name) = self.user_names.lock().get(user_id) {
+            return Some(name.clone());
+        }
+        let request = SlackApiUsersInfoRequest::new(user_id.to_string().into());
+        let user = match session.users_info(&request).await {
+            Ok(response) => response.user,
+            Err(e) => {
+                debug!(user = user_id, error = %e, "Slack users.info failed");
+                return None;
+            }
+        };
+        let name = user
+            .profile
+            .as_ref()
+            .and_then(|p| {
+                p.display_name
+                    .clone()
+                    .filter(|n| !n.is_empty())
+                    .or_else(|| p.real_name.clone())
+            })
+            .or(user.name)
+            .filter(|n| !n.is_empty())?;
+        self.user_names
+            .lock()
+            .insert(user_id.to_string(), name.clone());
+        Some(name)
+    }
+
+    /// Text of a thread's root message, for `thread.inheritParent`.
+    async fn thread_root_text(
+        &self,
+        session: &SlackClientSession<'_, SlackClientHyperHttpsConnector>,
+        channel: &str,
+        thread_ts: &str,
+    ) -> Option<String> {
+        let request =
+            SlackApiConversationsRepliesRequest::new(channel.to_string().into(), thread_ts.into())
+                .with_limit(1)
+                .with_inclusive(true);
+        match session.conversations_replies(&request).await {
+            Ok(response) => response
+                .messages
+                .into_iter()
+                .next()
+                .and_then(|m| m.content.text)
+                .map(|t| unescape_slack(&t))
+                .filter(|t| !t.trim().is_empty()),
+            Err(e) => {
+                debug!(channel, error = %e, "Slack conversations.replies failed");
+                None
+            }
+        }
+    }
+
+    /// Process one Events API / Socket Mode event callback.
+    async fn handle_event(
+        &self,
+        state: &GatewayState,
+        client: &SlackHyperClient,
+        event: SlackPushEventCallback,
+    ) {
+        match event.event {
+            SlackEventCallbackBody::Message(message) => {
+                self.handle_message(state, client, message).await;
+            }
+            // Mentions also arrive as `message` events, which carry the
+            // thread and channel type; handling both would reply twice.
+            SlackEventCallbackBody::AppMention(_) => {}
+            other => debug!(event = ?other, "Ignoring Slack event"),
+        }
+    }
+
+    /// Normalise, admit, run the agent and reply to one message event.
+    async fn handle_message(
+        &self,
+        state: &GatewayState,
+        client: &SlackHyperClient,
+        event: SlackMessageEvent,
+    ) {
+        let kind = ConversationKind::from_channel_type(
+            event.origin.channel_type.as_ref().map(|t| t.0.as_str()),
+        );
+        let bot_user_id = self.bot_user_id.read().clone();
+        let Some(mut msg) = normalize_message(&self.account_id, &event, bot_user_id.as_deref())
+        else {
+            return;
+        };
+
+        let token = match self.token() {
+            Ok(token) => token,
+            Err(e) => {
+                warn!(error = %e, "Slack reply skipped");
+                return;
+            }
+        };
+        let session = client.open_session(&token);
+        // Resolved before admission so buffered group context is labeled
+        // with display names too.
+        if !msg.sender.is_bot {
+            if let Some(name) = self.user_name(&session, &msg.sender.id).await {
+                msg.sender.name = name;
+            }
+        }
+
+        if !admit_group_message(state, &mut msg, |msg| self.admit(msg, kind)).await {
+            debug!(
+                channel = %msg.chat_id,
+                sender = %msg.sender.id,
+                "Slack message not admitted"
+            );
+            return;
+        }
+
+        if let Some(thread_ts) = msg.thread_id.clone() {
+            let thread = self.config.thread.as_ref();
+            if thread.and_then(|t| t.history_scope.as_deref()) == Some("channel") {
+                // Threads share the channel session; replies stay in the thread.
+                msg.chat_type = ChatType::Group;
+            } else if thread.and_then(|t| t.inherit_parent) == Some(true) {
+                let session_key = resolve_session_key(&*state.config.read().await, &msg);
+                if state.sessions.get_session(&session_key).is_none() {
+                    if let Some(root) = self
+                        .thread_root_text(&session, &msg.chat_id, &thread_ts)
+                        .await
+                    {
+                        msg.text = format!("[Thread started with: {root}]\n\n{}", msg.text);
+                    }
+                }
+            }
+        }
+
+        let reply_thread = match (&msg.thread_id, self.reply_mode(kind)) {
+            (Some(thread_ts), _) => Some(thread_ts.clone()),
+            (None, ReplyToMode::Off) => None,
+            (None, ReplyToMode::First | ReplyToMode::All) => Some(msg.id.clone()),
+        };
+
+        match dispatch_inbound(state, &msg).await {
+            Ok(Some(reply)) if !should_suppress_message(&reply) => {
+                let outbound = NormalizedOutbound {
+                    thread_id: reply_thread,
+                    account_id: Some(self.account_id.clone()),
+                    ..NormalizedOutbound::text(msg.chat_id.clone(), reply)
+                };
+                if let Err(e) = state.channels.enqueue("slack", outbound).await {
+                    warn!(channel = %msg.chat_id, error = %e, "Slack reply failed");
+                }
+            }
+            Ok(_) => {}
+            Err(e) => warn!(channel = %msg.chat_id, error = %e, "Slack agent run failed"),
+        }
+    }
+
+    /// Whether a slash command is the configured one or a built-in command.
+    fn accepts_command(&self, command: &str) -> bool {
+        self.configured_command(command)
+            || (self.config.commands != Some(false) && commands::parse(command).is_some())
+    }
+
+    /// Whether a slash command is enabled and matches `slashCommand.name`.
+    fn configured_command(&self, command: &str) -> bool {
+        let Some(config) = self.config.slash_command.as_ref() else {
+            return false;
+        };
+        if config.enabled != Some(true) {
+            return false;
+        }
+        config.name.as_deref().map_or(true, |name| {
+            name.trim_start_matches('/') == command.trim_start_matches('/')
+        })
+    }
+
+    /// Run a slash command and answer through its `response_url`.
+    ///
+    /// The configured command is admitted like a DM and runs in a per-user
+    /// session under `slashCommand.sessionPrefix`; built-in commands act on
+    /// the session of the conversation they were typed in.
+    async fn handle_command(
+        &self,
+        state: &GatewayState,
+        client: &SlackHyperClient,
+        event: SlackCommandEvent,
+    ) {
+        let slash = self.config.slash_command.clone().unwrap_or_default();
+        let user_id = event.user_id.to_string();
+        let mut msg = NormalizedMessage {
+            id: event.trigger_id.to_string(),
+            channel: "slack".to_string(),
+            account_id: self.account_id.clone(),
+            chat_id: event.channel_id.to_string(),
+            chat_name: event.channel_name.clone(),
+            chat_type: ChatType::Dm,
+            sender: NormalizedSender {
+                id: user_id.clone(),
+                name: user_id.clone(),
+                is_bot: false,
+                roles: Vec::new(),
+            },
+            text: event.text.clone().unwrap_or_default().trim().to_string(),
+            attachments: Vec::new(),
+            reply_to_id: None,
+            thread_id: None,
+            mentioned: true,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            raw: serde_json::to_value(&event).ok(),
+        };
+
+        if let Ok(token) = self.token() {
+            let session = client.open_session(&token);
+            if let Some(name) = self.user_name(&session, &user_id).await {
+                msg.sender.name = name;
+            }
+        }
+
+        let reply = if !self.configured_command(&event.command.0) {
+            let kind = if msg.chat_id.starts_with('D') {
+                ConversationKind::Direct
+            } else {
+                ConversationKind::Channel
+            };
+            if kind == ConversationKind::Channel {
+                msg.chat_type = ChatType::Group;
+            }
+            msg.text = format!("{} {}", event.command, msg.text)
+                .trim_end()
+                .to_string();
+            let native =
+                commands::native_enabled(&*state.config.read().await, self.config.commands);
+            let reply = if native && self.admit(&msg, kind) {
+                commands::dispatch(state, &msg).await
+            } else {
+                None
+            };
+            reply.unwrap_or_else(|| "Commands are not available here.".to_string())
+        } else if !self.admit(&msg, ConversationKind::Direct) {
+            "You are not allowed to use this command.".to_string()
+        } else if msg.text.is_empty() {
+            format!("Usage: {} <message>", event.command)
+        } else {
+            let prefix = slash
+                .session_prefix
+                .as_deref()
+                .unwrap_or(DEFAULT_SLASH_SESSION_PREFIX);
+            let session_key = format!("{prefix}:{user_id}");
+            match dispatch_inbound_to_session(state, session_key, &msg).await {
+                Ok(Some(reply)) if !should_suppress_message(&reply) => reply,
+                Ok(_) => return,
+                Err(e) => {
+                    warn!(command = %event.command, error = %e, "Slack slash command failed");
+                    "Sorry, something went wrong running that command.".to_string()
+                }
+            }
+        };
+
+        let response_type = if slash.ephemeral.unwrap_or(true) {
+            "ephemeral"
+        } else {
+            "in_channel"
+        };
+        let text = self.render(&reply).join("\n");
+        let result = reqwest::Client::new()
+            .post(event.response_url.0.clone())
+            .json(&serde_json::json!({ "response_type": response_type, "text": text }))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        if let Err(e) = result {
+            warn!(command = %event.command, error = %e, "Slack slash command response failed");
+        }
+    }
+
+    /// Render markdown into mrkdwn chunks, or escaped plain text when
+    /// `markdown` is disabled.
+    fn render(&self, text: &str) -> Vec<String> {
+        let limit = self.text_chunk_limit();
+        if self.config.markdown == Some(false) {
+            return split_text(text, limit)
+                .iter()
+                .map(|chunk| escape_mrkdwn(chunk))
+                .collect();
+        }
+        render_markdown_chunks(text, FormatTarget::Slack, limit)
+    }
+
+    /// Post text as one or more messages, optionally inside a thread.
+    async fn send_text(
+        &self,
+        session: &SlackClientSession<'_, SlackClientHyperHttpsConnector>,
+        channel: &str,
+        thread_ts: Option<&str>,
+        text: &str,
+    ) -> Result<()> {
+        for chunk in self.render(text) {
+            let request = SlackApiChatPostMessageRequest::new(
+                channel.to_string().into(),
+                SlackMessageContent::new().with_text(chunk),
+            )
+            .opt_thread_ts(thread_ts.map(SlackTs::from))
+            .with_unfurl_links(false);
+            session
+                .chat_post_message(&request)
+                .await
+                .context("chat.postMessage failed")?;
+        }
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Socket Mode Callbacks
+// ============================================================================
+
+/// User state attached to the Socket Mode listener environment.
+struct SocketModeState {
+    account: Arc<SlackAccount>,
+    state: GatewayState,
+}
+
+async fn socket_mode_handler(
+    states: &SlackClientEventsUserState,
+) -> Option<(Arc<SlackAccount>, GatewayState)> {
+    states
+        .read()
+        .await
+        .get_user_state::<SocketModeState>()
+        .map(|s| (s.account.clone(), s.state.clone()))
+}
+
+async fn on_push_event(
+    event: SlackPushEventCallback,
+    client: Arc<SlackHyperClient>,
+    states: SlackClientEventsUserState,
+) -> UserCallbackResult<()> {
+    if let Some((account, state)) = socket_mode_handler(&states).await {
+        // Acknowledge immediately; the agent run can take far longer than
+        // Slack's acknowledgement deadline.
+        tokio::spawn(async move { account.handle_event(&state, &client, event).await });
+    }
+    Ok(())
+}
+
+async fn on_command_event(
+    event: SlackCommandEvent,
+    client: Arc<SlackHyperClient>,
+    states: SlackClientEventsUserState,
+) -> UserCallbackResult<SlackCommandEventResponse> {
+    if let Some((account, state)) = socket_mode_handler(&states).await {
+        if account.accepts_command(&event.command.0) {
+            tokio::spawn(async move { account.handle_command(&state, &client, event).await });
+        }
+    }
+    Ok(SlackCommandEventResponse::new(SlackMessageContent::new()))
+}
+
 #[async_trait]
 impl ChannelPlugin for SlackChannel {
     fn id(&self) -> &str {
         "slack"
     }
 
+    fn account_id(&self) -> &str {
+        &self.account.account_id
+    }
+
     fn meta(&self) -> ChannelMeta {
         ChannelMeta {
             name: "Slack".to_string(),
@@ -79,24 +710,76 @@ impl ChannelPlugin for SlackChannel {
         ]
     }
 
-    async fn start_account(&self, _state: &GatewayState) -> Result<()> {
+    fn webhook_routes(&self) -> Vec<WebhookRoute> {
+        if !self.enabled || self.account.use_socket_mode() {
+            return Vec::new();
+        }
+        vec![WebhookRoute::post(self.account.webhook_path())]
+    }
+
+    async fn start_account(&self, state: &GatewayState) -> Result<()> {
         if !self.enabled {
             return Ok(());
         }
 
-        let _bot_token = match &self.bot_token {
-            Some(t) => t,
-            None => {
-                warn!("Slack channel enabled but no bot token configured");
-                return Ok(());
-            }
-        };
+        if self.account.bot_token.is_none() {
+            warn!("Slack channel enabled but no bot token configured");
+            return Ok(());
+        }
 
         info!("Slack channel starting");
 
-        // TODO: Initialise slack-morphism client.
-        // If app_token is present, use Socket Mode for real-time events.
-        // Otherwise, register an Events API webhook endpoint on the gateway.
+        let client = self.account.client()?;
+        let token = self.account.token()?;
+        match client.open_session(&token).auth_test().await {
+            Ok(auth) => {
+                info!(user = %auth.user_id, team = %auth.team, "Slack bot authenticated");
+                *self.account.bot_user_id.write() = Some(auth.user_id.to_string());
+            }
+            Err(e) => warn!(error = %e, "Slack auth.test failed; mentions will not be detected"),
+        }
+
+        if !self.account.use_socket_mode() {
+            if self.account.config.signing_secret.is_none() {
+                warn!("Slack Events API mode without signingSecret; requests will be rejected");
+            }
+            info!(
+                path = %format!("/channels/slack/{}", self.account.webhook_path()),
+                "Slack Events API webhook ready"
+            );
+            return Ok(());
+        }
+
+        let app_token = self
+            .account
+            .app_token
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Slack Socket Mode requires an app token"))?;
+
+        let callbacks = SlackSocketModeListenerCallbacks::new()
+            .with_push_events(on_push_event)
+            .with_command_events(on_command_event);
+        let environment = Arc::new(
+            SlackClientEventsListenerEnvironment::new(client).with_user_state(SocketModeState {
+                account: self.account.clone(),
+                state: state.clone(),
+            }),
+        );
+        let listener = Arc::new(SlackClientSocketModeListener::new(
+            &SlackClientSocketModeConfig::new(),
+            environment,
+            callbacks,
+        ));
+        listener
+            .listen_for(&SlackApiToken::new(app_token.into()))
+            .await
+            .context("failed to open Slack Socket Mode connection")?;
+        listener.start().await;
+
+        let previous = self.socket.lock().replace(listener);
+        if let Some(previous) = previous {
+            previous.shutdown().await;
+        }
 
         Ok(())
     }
@@ -104,40 +787,440 @@ impl ChannelPlugin for SlackChannel {
     async fn stop_account(&self) -> Result<()> {
         if self.enabled {
             info!("Slack channel stopping");
-            // TODO: Close Socket Mode connection or deregister webhook.
+            let listener = self.socket.lock().take();
+            if let Some(listener) = listener {
+                listener.shutdown().await;
+            }
         }
         Ok(())
     }
 
+    async fn probe(&self) -> Result<()> {
+        let client = self.account.client()?;
+        let token = self.account.token()?;
+        client
+            .open_session(&token)
+            .auth_test()
+            .await
+            .context("Slack auth.test failed")?;
+        Ok(())
+    }
+
     async fn send_message(&self, to: &str, message: &str) -> Result<()> {
+        self.send(&NormalizedOutbound::text(to, message)).await?;
+        Ok(())
+    }
+
+    async fn send(&self, message: &NormalizedOutbound) -> Result<Option<String>> {
+        if !message.attachments.is_empty() {
+            return Err(unsupported(self.id(), ChannelCapability::SendMedia));
+        }
+        let to = message.chat_id.as_str();
         // v2026.2.26: Suppress NO_REPLY before making API call.
-        if should_suppress_message(message) {
+        if should_suppress_message(&message.text) {
             debug!(channel = to, "Slack: suppressing NO_REPLY message");
-            return Ok(());
+            return Ok(None);
         }
 
-        let _bot_token = self
-            .bot_token
-            .as_deref()
-            .ok_or_else(|| anyhow::anyhow!("Slack bot token not configured"))?;
+        let token = self.account.token()?;
+        let client = self.account.client()?;
 
         info!(channel = to, "Slack: sending message");
 
-        // TODO: Use slack-morphism to call chat.postMessage with (to, message).
+        let (channel, thread_ts) = parse_target(to)?;
+        let thread_ts = message.thread_id.as_deref().or(thread_ts);
+        self.account
+            .send_text(
+                &client.open_session(&token),
+                channel,
+                thread_ts,
+                &message.text,
+            )
+            .await?;
+        Ok(None)
+    }
+
+    async fn react(&self, chat_id: &str, message_id: &str, emoji: &str) -> Result<()> {
+        let token = self.account.token()?;
+        let client = self.account.client()?;
+        let session = client.open_session(&token);
+        let (channel, _) = parse_target(chat_id)?;
+        let channel = SlackChannelId::new(channel.to_string());
+        let ts = SlackTs::new(message_id.to_string());
 
+        if !emoji.is_empty() {
+            let name = reaction_name(emoji)
+                .with_context(|| format!("no Slack reaction name for {emoji}"))?;
+            let request = SlackApiReactionsAddRequest::new(channel, name, ts);
+            match session.reactions_add(&request).await {
+                Ok(_) => {}
+                Err(e) if e.to_string().contains("already_reacted") => {}
+                Err(e) => return Err(e).context("Slack reactions.add failed"),
+            }
+            return Ok(());
+        }
+
+        // Removing: drop every reaction the bot left on the message.
+        let Some(bot) = self.account.bot_user_id.read().clone() else {
+            return Ok(());
+        };
+        let request = SlackApiReactionsGetRequest::new()
+            .with_channel(channel.clone())
+            .with_timestamp(ts.clone());
+        let reactions = match session
+            .reactions_get(&request)
+            .await
+            .context("Slack reactions.get failed")?
+        {
+            SlackApiReactionsGetResponse::Message(m) => m.message.content.reactions,
+            SlackApiReactionsGetResponse::File(_) => None,
+        };
+        for reaction in reactions.unwrap_or_default() {
+            if !reaction.users.iter().any(|u| u.0 == bot) {
+                continue;
+            }
+            let request = SlackApiReactionsRemoveRequest::new(reaction.name)
+                .with_channel(channel.clone())
+                .with_timestamp(ts.clone());
+            session
+                .reactions_remove(&request)
+                .await
+                .context("Slack reactions.remove failed")?;
+        }
         Ok(())
     }
+
+    async fn handle_webhook(
+        &self,
+        state: &GatewayState,
+        request: WebhookRequest,
+    ) -> Result<WebhookResponse> {
+        if !self.enabled
+            || self.account.use_socket_mode()
+            || request.path.trim_matches('/') != self.account.webhook_path()
+        {
+            return Ok(WebhookResponse::not_found());
+        }
+        if request.method != Method::POST {
+            return Ok(WebhookResponse::status(StatusCode::METHOD_NOT_ALLOWED));
+        }
+        if !self.account.webhook_authorized(&request) {
+            warn!("Slack Events API request with invalid signature");
+            return Ok(WebhookResponse::status(StatusCode::UNAUTHORIZED));
+        }
+        // Redeliveries are for requests we already acknowledged.
+        if request.header(RETRY_NUM_HEADER).is_some() {
+            return Ok(WebhookResponse::ok());
+        }
+
+        let is_form = request
+            .header(header::CONTENT_TYPE.as_str())
+            .is_some_and(|ct| ct.starts_with("application/x-www-form-urlencoded"));
+        if is_form {
+            let fields: serde_json::Map<String, serde_json::Value> =
+                url::form_urlencoded::parse(&request.body)
+                    .map(|(k, v)| (k.into_owned(), v.into_owned().into()))
+                    .collect();
+            // Only slash commands are handled; interactivity payloads are acknowledged.
+            if !fields.contains_key("command") {
+                return Ok(WebhookResponse::ok());
+            }
+            let event: SlackCommandEvent = match serde_json::from_value(fields.into()) {
+                Ok(event) => event,
+                Err(e) => {
+                    return Ok(WebhookResponse::text(
+                        StatusCode::BAD_REQUEST,
+                        format!("invalid command: {e}"),
+                    ));
+                }
+            };
+            if self.account.accepts_command(&event.command.0) {
+                let account = self.account.clone();
+                let client = account.client()?;
+                let state = state.clone();
+                tokio::spawn(async move { account.handle_command(&state, &client, event).await });
+            }
+            return Ok(WebhookResponse::ok());
+        }
+
+        let event: SlackPushEvent = match serde_json::from_slice(&request.body) {
+            Ok(event) => event,
+            Err(e) => {
+                return Ok(WebhookResponse::text(
+                    StatusCode::BAD_REQUEST,
+                    format!("invalid event: {e}"),
+                ));
+            }
+        };
+
+        match event {
+            SlackPushEvent::UrlVerification(verification) => Ok(WebhookResponse::text(
+                StatusCode::OK,
+                verification.challenge,
+            )),
+            SlackPushEvent::EventCallback(callback) => {
+                // Acknowledge immediately; Slack retries after three seconds.
+                let account = self.account.clone();
+                let client = account.client()?;
+                let state = state.clone();
+                tokio::spawn(async move { account.handle_event(&state, &client, callback).await });
+                Ok(WebhookResponse::ok())
+            }
+            SlackPushEvent::AppRateLimited(limited) => {
+                warn!(team = %limited.team_id, "Slack Events API rate limited");
+                Ok(WebhookResponse::ok())
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Message Normalization
+// ============================================================================
+
+/// Convert a Slack message event into a [`NormalizedMessage`].
+///
+/// Thread replies get `ChatType::Thread` with the thread's root `ts` in
+/// `thread_id`. Returns `None` for the bot's own messages, edits, deletions
+/// and other service subtypes, and empty messages.
+fn normalize_message(
+    account_id: &str,
+    event: &SlackMessageEvent,
+    bot_user_id: Option<&str>,
+) -> Option<NormalizedMessage> {
+    match event.subtype {
+        None
+        | Some(SlackMessageEventType::FileShare)
+        | Some(SlackMessageEventType::ThreadBroadcast)
+        | Some(SlackMessageEventType::MeMessage)
+        | Some(SlackMessageEventType::BotMessage) => {}
+        Some(_) => return None,
+    }
+
+    let sender = &event.sender;
+    let sender_id = sender
+        .user
+        .as_ref()
+        .map(|u| u.to_string())
+        .or_else(|| sender.bot_id.as_ref().map(|b| b.to_string()))?;
+    if bot_user_id == Some(sender_id.as_str()) {
+        return None;
+    }
+    let channel = event.origin.channel.as_ref()?.to_string();
+
+    let content = event.content.as_ref();
+    let raw_text = content.and_then(|c| c.text.as_deref()).unwrap_or_default();
+    let mentioned = bot_user_id.is_some_and(|bot| raw_text.contains(&format!("<@{bot}>")));
+    let text = match bot_user_id {
+        Some(bot) => strip_user_mention(raw_text, bot),
+        None => raw_text.trim().to_string(),
+    };
+    let text = unescape_slack(&text);
+
+    let attachments: Vec<NormalizedAttachment> = content
+        .and_then(|c| c.files.as_ref())
+        .map(|files| {
+            files
+                .iter()
+                .map(|f| NormalizedAttachment {
+                    mime_type: f.mimetype.as_ref().map(|m| m.to_string()),
+                    url: f.url_private.as_ref().map(|u| u.to_string()),
+                    data: None,
+                    filename: f.name.clone(),
+                    size: None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    if text.is_empty() && attachments.is_empty() {
+        return None;
+    }
+
+    let ts = event.origin.ts.to_string();
+    let thread_id = event
+        .origin
+        .thread_ts
+        .as_ref()
+        .map(|t| t.to_string())
+        .filter(|t| *t != ts);
+    let kind = ConversationKind::from_channel_type(
+        event.origin.channel_type.as_ref().map(|t| t.0.as_str()),
+    );
+    let chat_type = match (kind, &thread_id) {
+        (ConversationKind::Direct, _) => ChatType::Dm,
+        (_, Some(_)) => ChatType::Thread,
+        (_, None) => ChatType::Group,
+    };
+
+    let name = sender
+        .user_profile
+        .as_ref()
+        .and_then(|p| {
+            p.display_name
+                .clone()
+                .filter(|n| !n.is_empty())
+                .or(p.real_name.clone())
+        })
+        .or_else(|| sender.username.clone())
+        .unwrap_or_else(|| sender_id.clone());
+
+    Some(NormalizedMessage {
+        id: ts,
+        channel: "slack".to_string(),
+        account_id: account_id.to_string(),
+        chat_id: channel,
+        chat_name: None,
+        chat_type,
+        sender: NormalizedSender {
+            id: sender_id,
+            name,
+            is_bot: sender.bot_id.is_some()
+                || event.subtype == Some(SlackMessageEventType::BotMessage),
+            roles: Vec::new(),
+        },
+        text,
+        attachments,
+        reply_to_id: None,
+        thread_id,
+        mentioned,
+        timestamp: event
+            .origin
+            .ts
+            .to_date_time_opt()
+            .map(|t| t.to_rfc3339())
+            .unwrap_or_default(),
+        raw: serde_json::to_value(event).ok(),
+    })
+}
+
+/// Remove `<@id>` mentions of the bot from message text.
+fn strip_user_mention(text: &str, bot_user_id: &str) -> String {
+    text.replace(&format!("<@{bot_user_id}>"), "")
+        .split(' ')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim()
+        .to_string()
+}
+
+/// Whether the sender's id or name appears in an allowlist.
+fn sender_matches(allow_from: &[String], sender: &NormalizedSender) -> bool {
+    dm_policy::is_source_allowed(allow_from, &sender.id)
+        || dm_policy::is_source_allowed(allow_from, &sender.name)
+}
+
+/// Parse a send target: `"<channel>"` or `"<channel>:<thread_ts>"`.
+fn parse_target(to: &str) -> Result<(&str, Option<&str>)> {
+    let (channel, thread_ts) = match to.split_once(':') {
+        Some((channel, ts)) => (channel, Some(ts)),
+        None => (to, None),
+    };
+    if channel.is_empty() || thread_ts.is_some_and(|ts| ts.parse::<f64>().is_err()) {
+        anyhow::bail!("invalid Slack target: {to}");
+    }
+    Ok((channel, thread_ts))
+}
+
+/// Slack reaction name for an emoji: `:eyes:` or `eyes` as given, or the
+/// shortcode of a common unicode emoji.
+fn reaction_name(emoji: &str) -> Option<SlackReactionName> {
+    let emoji = emoji.trim();
+    let shortcode = emoji.trim_matches(':');
+    if !shortcode.is_empty()
+        && shortcode
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+'))
+    {
+        return Some(SlackReactionName(shortcode.to_string()));
+    }
+    let name = match emoji.trim_end_matches('\u{fe0f}') {
+        "👀" => "eyes",
+        "👍" => "+1",
+        "👎" => "-1",
+        "✅" => "white_check_mark",
+        "✔" => "heavy_check_mark",
+        "👌" => "ok_hand",
+        "🤔" => "thinking_face",
+        "⏳" => "hourglass_flowing_sand",
+        "⌛" => "hourglass",
+        "🔥" => "fire",
+        "❤" => "heart",
+        "🎉" => "tada",
+        "🙏" => "pray",
+        "🚀" => "rocket",
+        "💯" => "100",
+        "🤖" => "robot_face",
+        "🦞" => "lobster",
+        "✍" => "writing_hand",
+        "⚡" => "zap",
+        _ => return None,
+    };
+    Some(SlackReactionName(name.to_string()))
+}
+
+// ============================================================================
+// mrkdwn Escaping
+// ============================================================================
+
+/// Escape the characters Slack treats as control sequences.
+fn escape_mrkdwn(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
 }
 
-/// Convenience function called by the top-level `send_message` dispatcher.
-pub(crate) async fn send_message(config: &Config, to: &str, message: &str) -> Result<()> {
-    let channel = SlackChannel::new(config);
-    channel.send_message(to, message).await
+/// Undo Slack's escaping of inbound message text.
+fn unescape_slack(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::channels::{Admission, Verdict};
+    use crate::config::{DmPolicy, SlackSlashCommandConfig, SlackThreadConfig};
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const BOT: &str = "UBOT";
+
+    fn message_event(json: serde_json::Value) -> SlackMessageEvent {
+        serde_json::from_value(json).expect("valid Slack message event")
+    }
+
+    fn channel_message(text: &str) -> SlackMessageEvent {
+        message_event(serde_json::json!({
+            "type": "message",
+            "channel": "C1",
+            "channel_type": "channel",
+            "user": "U42",
+            "text": text,
+            "ts": "1700000000.000100"
+        }))
+    }
+
+    fn dm_message(text: &str) -> SlackMessageEvent {
+        message_event(serde_json::json!({
+            "type": "message",
+            "channel": "D1",
+            "channel_type": "im",
+            "user": "U42",
+            "text": text,
+            "ts": "1700000000.000200"
+        }))
+    }
+
+    fn channel_with(configure: impl FnOnce(&mut SlackAccountConfig)) -> SlackChannel {
+        let mut config = Config::default();
+        config.channels.slack.default_account.bot_token = Some("xoxb-test".to_string());
+        configure(&mut config.channels.slack.default_account);
+        SlackChannel::new(&config)
+    }
 
     #[test]
     fn no_reply_sentinel_suppressed() {
@@ -159,4 +1242,245 @@ mod tests {
         assert!(!should_suppress_message("NO_REPLY extra text"));
         assert!(!should_suppress_message("no_reply")); // case-sensitive
     }
+
+    #[test]
+    fn normalize_channel_mention_and_thread() {
+        let msg = normalize_message("default", &channel_message("<@UBOT> a &lt; b"), Some(BOT))
+            .expect("normalized");
+        assert_eq!(msg.chat_type, ChatType::Group);
+        assert_eq!(msg.chat_id, "C1");
+        assert_eq!(msg.sender.id, "U42");
+        assert!(msg.mentioned);
+        assert_eq!(msg.text, "a < b");
+        assert!(msg.thread_id.is_none());
+
+        let reply = message_event(serde_json::json!({
+            "type": "message",
+            "channel": "C1",
+            "channel_type": "channel",
+            "user": "U42",
+            "text": "in thread",
+            "ts": "1700000001.000100",
+            "thread_ts": "1700000000.000100"
+        }));
+        let msg = normalize_message("default", &reply, Some(BOT)).unwrap();
+        assert_eq!(msg.chat_type, ChatType::Thread);
+        assert_eq!(msg.thread_id.as_deref(), Some("1700000000.000100"));
+        assert!(!msg.mentioned);
+    }
+
+    #[test]
+    fn normalize_skips_own_and_service_messages() {
+        let own = message_event(serde_json::json!({
+            "type": "message", "channel": "C1", "user": "UBOT", "text": "hi", "ts": "1.0"
+        }));
+        assert!(normalize_message("default", &own, Some(BOT)).is_none());
+
+        let edited = message_event(serde_json::json!({
+            "type": "message", "subtype": "message_changed", "channel": "C1", "ts": "1.0"
+        }));
+        assert!(normalize_message("default", &edited, Some(BOT)).is_none());
+
+        let dm = normalize_message("default", &dm_message("hello"), Some(BOT)).unwrap();
+        assert_eq!(dm.chat_type, ChatType::Dm);
+    }
+
+    #[test]
+    fn channel_messages_require_mention_by_default() {
+        let channel = channel_with(|_| {});
+        let mut msg = normalize_message("default", &channel_message("hi"), Some(BOT)).unwrap();
+        assert!(!channel.account.admit(&msg, ConversationKind::Channel));
+        msg.mentioned = true;
+        assert!(channel.account.admit(&msg, ConversationKind::Channel));
+    }
+
+    #[test]
+    fn channel_allowlist_users_and_bots() {
+        let open = SlackChannelConfig {
+            require_mention: Some(false),
+            users: Some(vec!["U42".to_string()]),
+            ..Default::default()
+        };
+        let channel = channel_with(|account| {
+            account.group_policy = Some(GroupPolicy::Allowlist);
+            account.channels = Some([("C1".to_string(), open)].into_iter().collect());
+        });
+        let mut msg = normalize_message("default", &channel_message("hi"), Some(BOT)).unwrap();
+        assert!(channel.account.admit(&msg, ConversationKind::Channel));
+
+        msg.sender.is_bot = true;
+        assert!(!channel.account.admit(&msg, ConversationKind::Channel));
+        msg.sender.is_bot = false;
+
+        msg.sender.id = "U7".to_string();
+        msg.sender.name = "bob".to_string();
+        assert!(!channel.account.admit(&msg, ConversationKind::Channel));
+
+        let mut other = normalize_message("default", &channel_message("hi"), Some(BOT)).unwrap();
+        other.chat_id = "C2".to_string();
+        other.mentioned = true;
+        assert!(!channel.account.admit(&other, ConversationKind::Channel));
+    }
+
+    #[test]
+    fn dm_policy_and_group_dms() {
+        let channel = channel_with(|account| {
+            account.dm = Some(SlackDmConfig {
+                policy: Some(DmPolicy::Allowlist),
+                allow_from: Some(vec!["U42".to_string()]),
+                ..Default::default()
+            });
+        });
+        let mut config = Config::default();
+        config.channels.slack.default_account.dm = channel.account.config.dm.clone();
+        let admission = Admission::new();
+        let mut msg = normalize_message("default", &dm_message("hi"), Some(BOT)).unwrap();
+        assert!(channel.account.admit(&msg, ConversationKind::Direct));
+        assert_eq!(admission.check(&config, &msg), Verdict::Admit);
+        msg.sender.id = "U7".to_string();
+        msg.sender.name = "bob".to_string();
+        assert_ne!(admission.check(&config, &msg), Verdict::Admit);
+
+        // Group DMs are off unless `dm.groupEnabled` is set.
+        let mut group = normalize_message("default", &channel_message("hi"), Some(BOT)).unwrap();
+        group.mentioned = true;
+        assert!(!channel.account.admit(&group, ConversationKind::GroupDm));
+    }
+
+    #[test]
+    fn reply_mode_resolution() {
+        let channel = channel_with(|account| {
+            account.reply_to_mode = Some(ReplyToMode::All);
+            account.reply_to_mode_by_chat_type = Some(
+                [("direct".to_string(), ReplyToMode::Off)]
+                    .into_iter()
+                    .collect(),
+            );
+        });
+        assert_eq!(
+            channel.account.reply_mode(ConversationKind::Direct),
+            ReplyToMode::Off
+        );
+        assert_eq!(
+            channel.account.reply_mode(ConversationKind::Channel),
+            ReplyToMode::All
+        );
+    }
+
+    #[test]
+    fn slash_command_gating() {
+        let channel = channel_with(|_| {});
+        assert!(!channel.account.accepts_command("/lobster"));
+
+        let channel = channel_with(|account| {
+            account.slash_command = Some(SlackSlashCommandConfig {
+                enabled: Some(true),
+                name: Some("lobster".to_string()),
+                ..Default::default()
+            });
+            account.thread = Some(SlackThreadConfig::default());
+        });
+        assert!(channel.account.accepts_command("/lobster"));
+        assert!(!channel.account.accepts_command("/other"));
+
+        // Built-in commands are accepted unless the account turns them off.
+        assert!(channel.account.accepts_command("/status"));
+        let channel = channel_with(|account| account.commands = Some(false));
+        assert!(!channel.account.accepts_command("/status"));
+    }
+
+    #[test]
+    fn mrkdwn_chunks_respect_limit() {
+        let chunks = render_markdown_chunks(&"a&b ".repeat(100), FormatTarget::Slack, 50);
+        assert!(chunks.iter().all(|c| c.chars().count() <= 50));
+        assert_eq!(unescape_slack(&chunks.join(" ")), "a&b ".repeat(100).trim());
+    }
+
+    #[test]
+    fn parse_target_variants() {
+        assert_eq!(parse_target("C1").unwrap(), ("C1", None));
+        assert_eq!(
+            parse_target("C1:1700000000.000100").unwrap(),
+            ("C1", Some("1700000000.000100"))
+        );
+        assert!(parse_target("C1:thread").is_err());
+        assert!(parse_target("").is_err());
+    }
+
+    #[test]
+    fn reaction_names() {
+        let name = |emoji| reaction_name(emoji).map(|n| n.0);
+        assert_eq!(name("👀").as_deref(), Some("eyes"));
+        assert_eq!(name("❤️").as_deref(), Some("heart"));
+        assert_eq!(
+            name(":white_check_mark:").as_deref(),
+            Some("white_check_mark")
+        );
+        assert_eq!(name("+1").as_deref(), Some("+1"));
+        assert_eq!(name("🫠"), None);
+    }
+
+    fn signed_request(secret: &str, body: &str, timestamp: i64) -> WebhookRequest {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(format!("v0:{timestamp}:{body}").as_bytes());
+        let signature = format!("v0={}", hex::encode(mac.finalize().into_bytes()));
+        let mut headers = axum::http::HeaderMap::new();
+        headers.insert("x-slack-signature", signature.parse().unwrap());
+        headers.insert(
+            "x-slack-request-timestamp",
+            timestamp.to_string().parse().unwrap(),
+        );
+        headers.insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
+        WebhookRequest {
+            method: Method::POST,
+            path: "events".to_string(),
+            query: None,
+            headers,
+            body: body.to_string().into(),
+        }
+    }
+
+    #[test]
+    fn webhook_signature_is_checked() {
+        let channel = channel_with(|account| {
+            account.signing_secret = Some("s3cret".to_string());
+        });
+        let now = chrono::Utc::now().timestamp();
+        assert!(channel
+            .account
+            .webhook_authorized(&signed_request("s3cret", "{}", now)));
+        assert!(!channel
+            .account
+            .webhook_authorized(&signed_request("wrong", "{}", now)));
+        assert!(!channel
+            .account
+            .webhook_authorized(&signed_request("s3cret", "{}", now - 3600)));
+    }
+
+    #[tokio::test]
+    async fn send_message_posts_mrkdwn_in_thread() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat.postMessage"))
+            .and(body_partial_json(serde_json::json!({
+                "channel": "C1",
+                "text": "*hi*",
+                "thread_ts": "1700000000.000100"
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "ok": true,
+                "channel": "C1",
+                "ts": "1700000001.000100",
+                "message": {"ts": "1700000001.000100", "text": "*hi*"}
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let channel = channel_with(|account| account.api_url = Some(server.uri()));
+        channel
+            .send_message("C1:1700000000.000100", "**hi**")
+            .await
+            .unwrap();
+    }
 }
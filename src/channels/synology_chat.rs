@@ -1,10 +1,20 @@
-use super::plugin::{ChannelCapability, ChannelMeta, ChannelPlugin};
+use super::admission;
+use super::normalize::{ChatType, NormalizedMessage, NormalizedOutbound, NormalizedSender};
+use super::plugin::{
+    ChannelCapability, ChannelMeta, ChannelPlugin, WebhookRequest, WebhookResponse,
+    DEFAULT_ACCOUNT_ID,
+};
+use super::webhook::{self, ReplayGuard, WebhookRoute};
 use crate::config::Config;
 use crate::gateway::GatewayState;
 
 use anyhow::Result;
 use async_trait::async_trait;
+use axum::http::StatusCode;
+use parking_lot::Mutex;
 use reqwest::Client;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use subtle::ConstantTimeEq;
 use tracing::{debug, info, warn};
 
@@ -12,45 +22,60 @@ use tracing::{debug, info, warn};
 ///
 /// Communicates with Synology Chat via incoming/outgoing webhooks.
 /// Outbound messages are sent as `POST` with `payload={"text":"..."}`.
-/// Inbound messages are received as form-urlencoded webhook POSTs.
+/// Inbound messages are received as form-urlencoded webhook POSTs on
+/// `/channels/synology_chat/<webhookPath>` and checked against `token`.
 pub struct SynologyChatChannel {
+    account_id: String,
     enabled: bool,
     token: Option<String>,
     incoming_url: Option<String>,
+    webhook_path: String,
     bot_name: String,
     dm_policy: crate::config::DmPolicy,
-    allowed_user_ids: Vec<String>,
     rate_limit_per_minute: u32,
+    /// Recent message times per user, for `rate_limit_per_minute`.
+    recent: Mutex<HashMap<String, Vec<Instant>>>,
+    /// Post ids already handled.
+    replay: ReplayGuard,
     client: Client,
 }
 
 impl SynologyChatChannel {
     pub fn new(config: &Config) -> Self {
+        Self::for_account(config, DEFAULT_ACCOUNT_ID)
+    }
+
+    /// The channel for one bot account: an entry of `accounts`, or the
+    /// top-level settings for the default account.
+    ///
+    /// Named accounts without a `webhookPath` listen on `webhook/<id>`.
+    pub fn for_account(config: &Config, account_id: &str) -> Self {
         let chat_config = config.channels.synology_chat.as_ref();
-        let account = chat_config.map(|c| &c.default_account);
+        let account =
+            chat_config.map(|c| admission::account(&c.accounts, &c.default_account, account_id));
+        let default_path = match account_id {
+            DEFAULT_ACCOUNT_ID => "webhook".to_string(),
+            named => format!("webhook/{named}"),
+        };
 
-        let enabled = account
-            .and_then(|a| a.enabled)
-            .unwrap_or(false);
+        let enabled = account.and_then(|a| a.enabled).unwrap_or(false);
 
         let token = account.and_then(|a| a.token.clone());
         let incoming_url = account.and_then(|a| a.incoming_url.clone());
+        let webhook_path = webhook::webhook_path(
+            account.and_then(|a| a.webhook_path.as_deref()),
+            &default_path,
+        )
+        .to_string();
         let bot_name = account
             .and_then(|a| a.bot_name.clone())
             .unwrap_or_else(|| "MyLobster".to_string());
         let dm_policy = account
             .and_then(|a| a.dm_policy)
             .unwrap_or(crate::config::DmPolicy::Open);
-        let allowed_user_ids = account
-            .and_then(|a| a.allowed_user_ids.clone())
-            .unwrap_or_default();
-        let rate_limit_per_minute = account
-            .and_then(|a| a.rate_limit_per_minute)
-            .unwrap_or(30);
+        let rate_limit_per_minute = account.and_then(|a| a.rate_limit_per_minute).unwrap_or(30);
 
-        let allow_insecure = account
-            .and_then(|a| a.allow_insecure_ssl)
-            .unwrap_or(false);
+        let allow_insecure = account.and_then(|a| a.allow_insecure_ssl).unwrap_or(false);
 
         let client = Client::builder()
             .danger_accept_invalid_certs(allow_insecure)
@@ -59,13 +84,16 @@ impl SynologyChatChannel {
             .unwrap_or_else(|_| Client::new());
 
         Self {
+            account_id: account_id.to_string(),
             enabled,
             token,
             incoming_url,
+            webhook_path,
             bot_name,
             dm_policy,
-            allowed_user_ids,
             rate_limit_per_minute,
+            recent: Mutex::new(HashMap::new()),
+            replay: ReplayGuard::default(),
             client,
         }
     }
@@ -86,13 +114,26 @@ impl SynologyChatChannel {
     /// Sanitize inbound message text by stripping dangerous patterns.
     fn sanitize_input(text: &str) -> String {
         // Strip potential injection patterns
-        text.replace('\0', "")
-            .replace('\r', "")
+        text.replace(['\0', '\r'], "")
             // Limit length to prevent abuse
             .chars()
             .take(4096)
             .collect()
     }
+
+    /// Record a message from `user_id`; false once the user exceeds
+    /// `rate_limit_per_minute` within the last minute.
+    fn within_rate_limit(&self, user_id: &str) -> bool {
+        let now = Instant::now();
+        let mut recent = self.recent.lock();
+        let times = recent.entry(user_id.to_string()).or_default();
+        times.retain(|t| now.duration_since(*t) < Duration::from_secs(60));
+        if times.len() >= self.rate_limit_per_minute as usize {
+            return false;
+        }
+        times.push(now);
+        true
+    }
 }
 
 #[async_trait]
@@ -101,6 +142,10 @@ impl ChannelPlugin for SynologyChatChannel {
         "synology_chat"
     }
 
+    fn account_id(&self) -> &str {
+        &self.account_id
+    }
+
     fn meta(&self) -> ChannelMeta {
         ChannelMeta {
             name: "Synology Chat".to_string(),
@@ -111,10 +156,14 @@ impl ChannelPlugin for SynologyChatChannel {
     }
 
     fn capabilities(&self) -> Vec<ChannelCapability> {
-        vec![
-            ChannelCapability::SendText,
-            ChannelCapability::ReceiveText,
-        ]
+        vec![ChannelCapability::SendText, ChannelCapability::ReceiveText]
+    }
+
+    fn webhook_routes(&self) -> Vec<WebhookRoute> {
+        if !self.enabled {
+            return Vec::new();
+        }
+        vec![WebhookRoute::post(&self.webhook_path)]
     }
 
     async fn start_account(&self, _state: &GatewayState) -> Result<()> {
@@ -123,7 +172,9 @@ impl ChannelPlugin for SynologyChatChannel {
         }
 
         if self.token.is_none() {
-            warn!("Synology Chat: no token configured, webhook validation will reject all messages");
+            warn!(
+                "Synology Chat: no token configured, webhook validation will reject all messages"
+            );
         }
         if self.incoming_url.is_none() {
             warn!("Synology Chat: no incoming_url configured, outbound messages disabled");
@@ -132,6 +183,7 @@ impl ChannelPlugin for SynologyChatChannel {
         info!(
             bot_name = %self.bot_name,
             dm_policy = ?self.dm_policy,
+            path = %self.webhook_path,
             "Synology Chat channel started"
         );
         Ok(())
@@ -143,51 +195,194 @@ impl ChannelPlugin for SynologyChatChannel {
     }
 
     async fn send_message(&self, to: &str, message: &str) -> Result<()> {
-        let incoming_url = self.incoming_url.as_ref().ok_or_else(|| {
-            anyhow::anyhow!("Synology Chat: no incoming_url configured for outbound messages")
-        })?;
+        send_incoming(&self.client, self.incoming_url.as_deref(), to, message).await
+    }
 
-        // Build payload per Synology Chat incoming webhook format
-        let user_ids: Vec<serde_json::Value> = if to.is_empty() {
-            vec![]
-        } else {
-            to.split(',')
-                .map(|id| serde_json::Value::Number(id.trim().parse::<i64>().unwrap_or(0).into()))
-                .collect()
+    async fn handle_webhook(
+        &self,
+        state: &GatewayState,
+        request: WebhookRequest,
+    ) -> Result<WebhookResponse> {
+        if !self.enabled {
+            return Ok(WebhookResponse::not_found());
+        }
+        if let Some(response) = webhook::route_check(&self.webhook_routes(), &request) {
+            return Ok(response);
+        }
+        let form: HashMap<String, String> = url::form_urlencoded::parse(&request.body)
+            .into_owned()
+            .collect();
+        if !self.validate_token(form.get("token").map(String::as_str).unwrap_or_default()) {
+            warn!("Synology Chat webhook request with invalid token");
+            return Ok(WebhookResponse::status(StatusCode::UNAUTHORIZED));
+        }
+
+        let Some(mut msg) = normalize_form(&form) else {
+            return Ok(WebhookResponse::ok());
         };
+        msg.account_id = self.account_id.clone();
+        if !self.replay.first_delivery(&msg.id) {
+            debug!(post_id = %msg.id, "Skipping redelivered Synology Chat post");
+            return Ok(WebhookResponse::ok());
+        }
+        msg.text = Self::sanitize_input(&msg.text);
+        if !self.within_rate_limit(&msg.sender.id) {
+            warn!(user_id = %msg.sender.id, "Synology Chat: rate limit exceeded");
+            return Ok(WebhookResponse::ok());
+        }
 
-        let payload = serde_json::json!({
-            "text": message,
-            "user_ids": user_ids,
+        webhook::spawn_dispatch(state, msg, |msg, text| {
+            // Bot DMs are addressed to the user; channel webhooks post to
+            // their own channel.
+            let to = match msg.chat_type {
+                ChatType::Dm => msg.sender.id.as_str(),
+                _ => "",
+            };
+            NormalizedOutbound::text(to, text)
         });
+        Ok(WebhookResponse::ok())
+    }
+}
 
-        let payload_str = serde_json::to_string(&payload)?;
+/// Post a message through the incoming webhook, addressed to `to` (comma
+/// separated user ids) or, when empty, to the webhook's channel.
+async fn send_incoming(
+    client: &Client,
+    incoming_url: Option<&str>,
+    to: &str,
+    message: &str,
+) -> Result<()> {
+    let incoming_url = incoming_url.ok_or_else(|| {
+        anyhow::anyhow!("Synology Chat: no incoming_url configured for outbound messages")
+    })?;
 
-        debug!(url = %incoming_url, "Sending Synology Chat message");
+    // Build payload per Synology Chat incoming webhook format
+    let user_ids: Vec<serde_json::Value> = if to.is_empty() {
+        vec![]
+    } else {
+        to.split(',')
+            .map(|id| serde_json::Value::Number(id.trim().parse::<i64>().unwrap_or(0).into()))
+            .collect()
+    };
 
-        let resp = self
-            .client
-            .post(incoming_url)
-            .form(&[("payload", &payload_str)])
-            .send()
-            .await?;
+    let payload = serde_json::json!({
+        "text": message,
+        "user_ids": user_ids,
+    });
 
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let body = resp.text().await.unwrap_or_default();
-            anyhow::bail!(
-                "Synology Chat: send failed with status {}: {}",
-                status,
-                body
-            );
-        }
+    let payload_str = serde_json::to_string(&payload)?;
 
-        Ok(())
+    debug!(url = %incoming_url, "Sending Synology Chat message");
+
+    let resp = client
+        .post(incoming_url)
+        .form(&[("payload", &payload_str)])
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let body = resp.text().await.unwrap_or_default();
+        anyhow::bail!(
+            "Synology Chat: send failed with status {}: {}",
+            status,
+            body
+        );
     }
+
+    Ok(())
 }
 
-/// Send a standalone message via Synology Chat (used by the channel dispatch in mod.rs).
-pub async fn send_message(config: &Config, to: &str, message: &str) -> Result<()> {
-    let channel = SynologyChatChannel::new(config);
-    channel.send_message(to, message).await
+/// Convert an outgoing-webhook form post into a [`NormalizedMessage`].
+///
+/// Chatbot posts carry only the user; outgoing webhooks from a channel also
+/// carry `channel_id` and are triggered by their trigger word.
+fn normalize_form(form: &HashMap<String, String>) -> Option<NormalizedMessage> {
+    let user_id = form.get("user_id")?.clone();
+    let text = form.get("text")?.trim().to_string();
+    if text.is_empty() {
+        return None;
+    }
+    let channel_id = form.get("channel_id").filter(|id| !id.is_empty());
+
+    Some(NormalizedMessage {
+        id: form.get("post_id")?.clone(),
+        channel: "synology_chat".to_string(),
+        account_id: DEFAULT_ACCOUNT_ID.to_string(),
+        chat_id: channel_id.unwrap_or(&user_id).clone(),
+        chat_name: form.get("channel_name").cloned(),
+        chat_type: if channel_id.is_some() {
+            ChatType::Group
+        } else {
+            ChatType::Dm
+        },
+        sender: NormalizedSender {
+            id: user_id.clone(),
+            name: form.get("username").unwrap_or(&user_id).clone(),
+            is_bot: false,
+            roles: Vec::new(),
+        },
+        text,
+        attachments: Vec::new(),
+        reply_to_id: None,
+        thread_id: None,
+        mentioned: true,
+        timestamp: webhook::timestamp_from_millis(
+            form.get("timestamp")
+                .and_then(|ts| ts.parse().ok())
+                .unwrap_or_default(),
+        ),
+        raw: serde_json::to_value(form).ok(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{SynologyChatAccountConfig, SynologyChatConfig};
+
+    fn channel(rate_limit: u32) -> SynologyChatChannel {
+        let mut config = Config::default();
+        config.channels.synology_chat = Some(SynologyChatConfig {
+            accounts: None,
+            default_account: SynologyChatAccountConfig {
+                enabled: Some(true),
+                token: Some("secret".to_string()),
+                rate_limit_per_minute: Some(rate_limit),
+                ..Default::default()
+            },
+        });
+        SynologyChatChannel::new(&config)
+    }
+
+    #[test]
+    fn chatbot_and_channel_posts_normalize() {
+        let form: HashMap<String, String> = url::form_urlencoded::parse(
+            b"token=secret&user_id=7&username=ada&post_id=99&timestamp=1700000000000&text=hello",
+        )
+        .into_owned()
+        .collect();
+        let msg = normalize_form(&form).unwrap();
+        assert_eq!(msg.chat_type, ChatType::Dm);
+        assert_eq!(msg.chat_id, "7");
+        assert_eq!(msg.sender.name, "ada");
+
+        let mut in_channel = form.clone();
+        in_channel.insert("channel_id".to_string(), "3".to_string());
+        let msg = normalize_form(&in_channel).unwrap();
+        assert_eq!(msg.chat_type, ChatType::Group);
+        assert_eq!(msg.chat_id, "3");
+    }
+
+    #[test]
+    fn tokens_and_rate_limits() {
+        let channel = channel(2);
+        assert!(channel.validate_token("secret"));
+        assert!(!channel.validate_token("secreT"));
+
+        assert!(channel.within_rate_limit("7"));
+        assert!(channel.within_rate_limit("7"));
+        assert!(!channel.within_rate_limit("7"));
+        assert!(channel.within_rate_limit("8"));
+    }
 }
@@ -1,10 +1,27 @@
-use super::plugin::{ChannelCapability, ChannelMeta, ChannelPlugin};
+use super::admission::Verdict;
+use super::group_history::admit_group_message;
+use super::normalize::{
+    ChatType, NormalizedMessage, NormalizedOutbound, NormalizedSender, OutboundButton,
+};
+use super::plugin::{
+    unsupported, ChannelCapability, ChannelMeta, ChannelPlugin, WebhookRequest, WebhookResponse,
+};
+use super::webhook::{self, JwtVerifier, ReplayGuard, WebhookRoute, BOT_FRAMEWORK_OPENID_METADATA};
+use crate::config::{Config, MsTeamsConfig};
 use crate::gateway::GatewayState;
+use crate::infra::dm_policy;
 
 use anyhow::Result;
 use async_trait::async_trait;
+use axum::http::StatusCode;
+use parking_lot::{Mutex, RwLock};
 use reqwest::Client;
-use tracing::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
 
 // ============================================================================
 // Microsoft Teams Channel Implementation
@@ -18,50 +35,229 @@ use tracing::{info, warn};
 /// activities).
 ///
 /// Configuration requires an Azure Bot registration with app ID and password.
+/// The bot's messaging endpoint is `POST /channels/teams/<webhookPath>`;
+/// every activity carries a Bot Framework JWT that is validated before the
+/// activity is processed.
+///
+/// The `serviceUrl` of every conversation the bot hears from is persisted
+/// under `state_dir/teams/`, so proactive messages (cron deliveries, the
+/// `message` tool) reach it after a restart.
 pub struct TeamsChannel {
-    /// Azure Bot app ID (client ID from Azure AD app registration).
-    app_id: Option<String>,
-    /// Azure Bot app password (client secret).
-    app_password: Option<String>,
-    /// Bot Framework service URL (set from incoming activity `serviceUrl`).
-    service_url: Option<String>,
-    /// Whether this channel is enabled.
-    enabled: Option<bool>,
-    /// HTTP client for Bot Framework API calls.
-    client: Client,
+    enabled: bool,
+    config: MsTeamsConfig,
+    /// Bot Connector client used for replies and outbound messages.
+    connector: BotConnector,
+    /// Validates inbound Bot Framework tokens; `None` without an app id.
+    verifier: Option<JwtVerifier>,
+    /// Conversation id → reference learned from incoming activities.
+    conversations: RwLock<HashMap<String, ConversationRef>>,
+    /// Where `conversations` is persisted.
+    conversations_path: PathBuf,
+    /// Activity ids already handled.
+    replay: ReplayGuard,
+}
+
+const DEFAULT_WEBHOOK_PATH: &str = "messages";
+const DEFAULT_SERVICE_URL: &str = "https://smba.trafficmanager.net/amer/";
+
+/// Prefix of send targets naming a user rather than a conversation.
+const USER_TARGET_PREFIX: &str = "user:";
+
+const ADAPTIVE_CARD_CONTENT_TYPE: &str = "application/vnd.microsoft.card.adaptive";
+
+/// Longest tool arguments shown on an approval card.
+const MAX_APPROVAL_ARGS_CHARS: usize = 1500;
+
+/// Where to reach a conversation proactively.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConversationRef {
+    service_url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tenant_id: Option<String>,
 }
 
 impl TeamsChannel {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
+        let teams = config.channels.msteams.clone().unwrap_or_default();
+        let tenant = teams
+            .tenant_id
+            .clone()
+            .unwrap_or_else(|| "botframework.com".to_string());
+        let conversations_path = config.state_dir.join("teams").join("conversations.json");
         Self {
-            app_id: None,
-            app_password: None,
-            service_url: None,
-            enabled: None,
-            client: Client::new(),
+            enabled: teams.enabled.unwrap_or(false),
+            connector: BotConnector {
+                client: Client::new(),
+                app_id: teams.app_id.clone(),
+                app_password: teams.app_password.clone(),
+                token_url: teams.token_url.clone().unwrap_or_else(|| {
+                    format!("https://login.microsoftonline.com/{tenant}/oauth2/v2.0/token")
+                }),
+                token: Mutex::new(None),
+            },
+            verifier: teams.app_id.as_deref().map(|app_id| {
+                JwtVerifier::bot_framework(
+                    app_id,
+                    teams
+                        .open_id_metadata_url
+                        .as_deref()
+                        .unwrap_or(BOT_FRAMEWORK_OPENID_METADATA),
+                )
+            }),
+            config: teams,
+            conversations: RwLock::new(load_conversations(&conversations_path)),
+            conversations_path,
+            replay: ReplayGuard::default(),
         }
     }
 
-    /// Create a configured Teams channel.
-    pub fn with_config(app_id: String, app_password: String) -> Self {
-        Self {
-            app_id: Some(app_id),
-            app_password: Some(app_password),
-            service_url: None,
-            enabled: Some(true),
-            client: Client::new(),
+    fn webhook_path(&self) -> &str {
+        webhook::webhook_path(self.config.webhook_path.as_deref(), DEFAULT_WEBHOOK_PATH)
+    }
+
+    /// Validate the bearer token and return the verified `serviceurl` claim.
+    async fn authenticate(&self, request: &WebhookRequest) -> Option<String> {
+        let verifier = self.verifier.as_ref()?;
+        let token = webhook::bearer_token(request)?;
+        match verifier.verify(token).await {
+            Ok(claims) => claims["serviceurl"].as_str().map(str::to_string),
+            Err(e) => {
+                warn!(error = %e, "Teams: rejected activity token");
+                None
+            }
         }
     }
 
-    fn is_enabled(&self) -> bool {
-        self.enabled.unwrap_or(false)
+    fn default_service_url(&self) -> &str {
+        self.config
+            .service_url
+            .as_deref()
+            .unwrap_or(DEFAULT_SERVICE_URL)
+    }
+
+    /// Remember where a conversation lives, persisting it when it changed.
+    fn remember_conversation(&self, conversation_id: &str, reference: ConversationRef) {
+        let mut conversations = self.conversations.write();
+        if conversations.get(conversation_id) == Some(&reference) {
+            return;
+        }
+        conversations.insert(conversation_id.to_string(), reference);
+        if let Err(e) = save_conversations(&self.conversations_path, &conversations) {
+            warn!(error = %e, "Failed to persist Teams conversation references");
+        }
     }
 
+    /// The conversation and `serviceUrl` for a send target.
+    ///
+    /// `user:<id>` opens (or reuses) the bot's 1:1 conversation with a
+    /// user; anything else is a conversation id.
+    async fn resolve_target(&self, to: &str) -> Result<(String, String)> {
+        if let Some(user_id) = to.strip_prefix(USER_TARGET_PREFIX) {
+            let service_url = self.default_service_url().to_string();
+            let conversation_id = self
+                .connector
+                .create_conversation(&service_url, user_id, self.config.tenant_id.as_deref())
+                .await?;
+            self.remember_conversation(
+                &conversation_id,
+                ConversationRef {
+                    service_url: service_url.clone(),
+                    tenant_id: self.config.tenant_id.clone(),
+                },
+            );
+            return Ok((conversation_id, service_url));
+        }
+        let service_url = self
+            .conversations
+            .read()
+            .get(to)
+            .map(|reference| reference.service_url.clone())
+            .unwrap_or_else(|| self.default_service_url().to_string());
+        Ok((to.to_string(), service_url))
+    }
+
+    /// Post an adaptive card asking to approve or deny an exec approval
+    /// request (as stored by `exec.approval.request`).
+    pub(crate) async fn send_approval(&self, to: &str, request: &Value) -> Result<()> {
+        let (conversation_id, service_url) = self.resolve_target(to).await?;
+        let activity = json!({
+            "type": "message",
+            "attachments": [card_attachment(approval_card(request))],
+        });
+        self.connector
+            .post_activity(&service_url, &conversation_id, None, &activity)
+            .await?;
+        Ok(())
+    }
+
+    /// Apply an approval card submission, returning the text to answer
+    /// with.
+    ///
+    /// With `allowFrom` set, only those senders may decide.
+    fn handle_approval(
+        &self,
+        state: &GatewayState,
+        msg: &NormalizedMessage,
+        submission: &ApprovalSubmission,
+    ) -> String {
+        let allow_from = self.config.allow_from.as_deref().unwrap_or_default();
+        if !dm_policy::is_source_allowed(allow_from, &msg.sender.id) {
+            return "You are not allowed to answer approval requests.".to_string();
+        }
+        let mut requests = state.rpc.exec_requests.write();
+        resolve_approval(&mut requests, submission, &msg.sender.id)
+    }
+}
+
+/// Read persisted conversation references; a missing or corrupt file
+/// starts empty.
+fn load_conversations(path: &PathBuf) -> HashMap<String, ConversationRef> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+/// Persist conversation references, replacing the previous file atomically.
+fn save_conversations(
+    path: &PathBuf,
+    conversations: &HashMap<String, ConversationRef>,
+) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_string_pretty(conversations)?)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Bot Connector REST client: token acquisition and activity posting.
+struct BotConnector {
+    client: Client,
+    app_id: Option<String>,
+    app_password: Option<String>,
+    /// OAuth2 client-credentials endpoint that issues bot tokens.
+    token_url: String,
+    /// Cached access token and when to stop using it.
+    token: Mutex<Option<(String, Instant)>>,
+}
+
+impl BotConnector {
     /// Acquire an OAuth2 token from Azure AD for the Bot Framework.
     ///
-    /// Calls `https://login.microsoftonline.com/botframework.com/oauth2/v2.0/token`
-    /// with client credentials grant.
+    /// Calls `token_url` (by default
+    /// `https://login.microsoftonline.com/<tenant>/oauth2/v2.0/token`) with
+    /// the client credentials grant. Tokens are reused until five minutes
+    /// before they expire.
     async fn acquire_token(&self) -> Result<String> {
+        if let Some((token, until)) = self.token.lock().as_ref() {
+            if Instant::now() < *until {
+                return Ok(token.clone());
+            }
+        }
+
         let app_id = self
             .app_id
             .as_deref()
@@ -71,20 +267,14 @@ impl TeamsChannel {
             .as_deref()
             .ok_or_else(|| anyhow::anyhow!("Teams app_password not configured"))?;
 
-        let token_url =
-            "https://login.microsoftonline.com/botframework.com/oauth2/v2.0/token";
-
         let resp = self
             .client
-            .post(token_url)
+            .post(&self.token_url)
             .form(&[
                 ("grant_type", "client_credentials"),
                 ("client_id", app_id),
                 ("client_secret", app_password),
-                (
-                    "scope",
-                    "https://api.botframework.com/.default",
-                ),
+                ("scope", "https://api.botframework.com/.default"),
             ])
             .send()
             .await?;
@@ -100,9 +290,102 @@ impl TeamsChannel {
             .as_str()
             .ok_or_else(|| anyhow::anyhow!("Teams: no access_token in OAuth2 response"))?
             .to_string();
+        let lifetime = body["expires_in"].as_u64().unwrap_or(3600);
+        let until = Instant::now() + Duration::from_secs(lifetime.saturating_sub(300));
+        *self.token.lock() = Some((token.clone(), until));
 
         Ok(token)
     }
+
+    /// Post an activity to a conversation, optionally as a reply, and
+    /// return the new activity id.
+    ///
+    /// `POST {serviceUrl}/v3/conversations/{id}/activities[/{replyToId}]`
+    async fn post_activity(
+        &self,
+        service_url: &str,
+        conversation_id: &str,
+        reply_to: Option<&str>,
+        activity: &Value,
+    ) -> Result<Option<String>> {
+        let token = self.acquire_token().await?;
+        let mut url = format!(
+            "{}/v3/conversations/{}/activities",
+            service_url.trim_end_matches('/'),
+            conversation_id,
+        );
+        let mut activity = activity.clone();
+        if let Some(reply_to) = reply_to {
+            url = format!("{}/{}", url, reply_to);
+            activity["replyToId"] = Value::String(reply_to.to_string());
+        }
+
+        let resp = self
+            .client
+            .post(&url)
+            .header("Authorization", format!("Bearer {}", token))
+            .json(&activity)
+            .send()
+            .await?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            anyhow::bail!("Teams send failed ({}): {}", status, text);
+        }
+
+        let body: Value = resp.json().await.unwrap_or_default();
+        Ok(body["id"].as_str().map(str::to_string))
+    }
+
+    /// Open the bot's 1:1 conversation with a user, returning its id.
+    ///
+    /// `POST {serviceUrl}/v3/conversations`; Teams returns the existing
+    /// conversation when there is one.
+    async fn create_conversation(
+        &self,
+        service_url: &str,
+        user_id: &str,
+        tenant_id: Option<&str>,
+    ) -> Result<String> {
+        let app_id = self
+            .app_id
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Teams app_id not configured"))?;
+        let token = self.acquire_token().await?;
+        let mut body = json!({
+            "bot": { "id": format!("28:{app_id}") },
+            "members": [{ "id": user_id }],
+            "isGroup": false,
+        });
+        if let Some(tenant_id) = tenant_id {
+            body["tenantId"] = json!(tenant_id);
+            body["channelData"] = json!({ "tenant": { "id": tenant_id } });
+        }
+
+        let resp = self
+            .client
+            .post(format!(
+                "{}/v3/conversations",
+                service_url.trim_end_matches('/')
+            ))
+            .header("Authorization", format!("Bearer {}", token))
+            .json(&body)
+            .send()
+            .await?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            anyhow::bail!("Teams conversation create failed ({}): {}", status, text);
+        }
+
+        let body: Value = resp.json().await?;
+        body["id"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow::anyhow!("Teams: no conversation id in response"))
+    }
 }
 
 #[async_trait]
@@ -115,7 +398,7 @@ impl ChannelPlugin for TeamsChannel {
         ChannelMeta {
             name: "Microsoft Teams".to_string(),
             description: "Microsoft Teams channel via Bot Framework REST API".to_string(),
-            enabled: self.is_enabled(),
+            enabled: self.enabled,
             multi_account: false,
         }
     }
@@ -133,74 +416,630 @@ impl ChannelPlugin for TeamsChannel {
         ]
     }
 
+    fn webhook_routes(&self) -> Vec<WebhookRoute> {
+        if !self.enabled {
+            return Vec::new();
+        }
+        vec![WebhookRoute::post(self.webhook_path())]
+    }
+
     async fn start_account(&self, _state: &GatewayState) -> Result<()> {
-        if !self.is_enabled() {
+        if !self.enabled {
             return Ok(());
         }
 
-        if self.app_id.is_none() || self.app_password.is_none() {
+        if self.config.app_id.is_none() || self.config.app_password.is_none() {
             warn!("Teams channel enabled but app_id or app_password not configured");
             return Ok(());
         }
 
-        info!("Microsoft Teams channel starting");
+        info!(path = %self.webhook_path(), "Microsoft Teams channel starting");
 
         // Validate credentials by acquiring an initial token.
-        match self.acquire_token().await {
+        match self.connector.acquire_token().await {
             Ok(_) => info!("Teams: OAuth2 token acquired successfully"),
             Err(e) => warn!("Teams: failed to acquire initial OAuth2 token: {}", e),
         }
 
-        // TODO: Set up an HTTP endpoint to receive incoming Bot Framework activities.
-        // The Bot Framework sends POST requests to the bot's messaging endpoint.
-
         Ok(())
     }
 
     async fn stop_account(&self) -> Result<()> {
-        if self.is_enabled() {
+        if self.enabled {
             info!("Microsoft Teams channel stopping");
         }
         Ok(())
     }
 
     async fn send_message(&self, to: &str, message: &str) -> Result<()> {
-        let service_url = self
-            .service_url
-            .as_deref()
-            .unwrap_or("https://smba.trafficmanager.net/amer/");
+        self.send(&NormalizedOutbound::text(to, message)).await?;
+        Ok(())
+    }
 
-        let token = self.acquire_token().await?;
+    /// Send text, or an adaptive card when the message has buttons.
+    async fn send(&self, message: &NormalizedOutbound) -> Result<Option<String>> {
+        if !message.attachments.is_empty() {
+            return Err(unsupported(self.id(), ChannelCapability::SendMedia));
+        }
+        let (conversation_id, service_url) = self.resolve_target(&message.chat_id).await?;
+        let activity = if message.buttons.is_empty() {
+            json!({ "type": "message", "text": message.text })
+        } else {
+            json!({
+                "type": "message",
+                "attachments": [card_attachment(buttons_card(&message.text, &message.buttons))],
+            })
+        };
+
+        info!(conversation_id = %conversation_id, "Teams: sending message");
+        self.connector
+            .post_activity(
+                &service_url,
+                &conversation_id,
+                message.reply_to_id.as_deref(),
+                &activity,
+            )
+            .await
+    }
+
+    async fn handle_webhook(
+        &self,
+        state: &GatewayState,
+        request: WebhookRequest,
+    ) -> Result<WebhookResponse> {
+        if !self.enabled {
+            return Ok(WebhookResponse::not_found());
+        }
+        if let Some(response) = webhook::route_check(&self.webhook_routes(), &request) {
+            return Ok(response);
+        }
+        let Some(token_service_url) = self.authenticate(&request).await else {
+            return Ok(WebhookResponse::status(StatusCode::UNAUTHORIZED));
+        };
+        let Ok(activity) = serde_json::from_slice::<Value>(&request.body) else {
+            return Ok(WebhookResponse::status(StatusCode::BAD_REQUEST));
+        };
+
+        // The token is bound to the service URL it was issued for.
+        let Some(service_url) = activity["serviceUrl"].as_str() else {
+            return Ok(WebhookResponse::status(StatusCode::BAD_REQUEST));
+        };
+        if service_url.trim_end_matches('/') != token_service_url.trim_end_matches('/') {
+            warn!(
+                service_url,
+                "Teams: activity serviceUrl does not match its token"
+            );
+            return Ok(WebhookResponse::status(StatusCode::UNAUTHORIZED));
+        }
+        if let Some(conversation_id) = activity["conversation"]["id"].as_str() {
+            self.remember_conversation(
+                conversation_id,
+                ConversationRef {
+                    service_url: service_url.to_string(),
+                    tenant_id: activity["channelData"]["tenant"]["id"]
+                        .as_str()
+                        .or_else(|| activity["conversation"]["tenantId"].as_str())
+                        .map(str::to_string),
+                },
+            );
+        }
+
+        if let Some(submission) = approval_submission(&activity) {
+            let Some(msg) = activity_message(&activity, String::new()) else {
+                return Ok(WebhookResponse::ok());
+            };
+            if !self.replay.first_delivery(&msg.id) {
+                return Ok(WebhookResponse::ok());
+            }
+            let config = state.config.read().await.clone();
+            let reply = match state.admission.check(&config, &msg) {
+                Verdict::Admit => self.handle_approval(state, &msg, &submission),
+                Verdict::Reply(notice) => notice,
+                Verdict::Drop => return Ok(WebhookResponse::ok()),
+            };
+            let outbound = NormalizedOutbound {
+                reply_to_id: Some(msg.id.clone()),
+                ..NormalizedOutbound::text(msg.chat_id.clone(), reply)
+            };
+            if let Err(e) = state.channels.enqueue(self.id(), outbound).await {
+                warn!(error = %e, "Teams: failed to queue approval reply");
+            }
+            return Ok(WebhookResponse::ok());
+        }
+
+        let Some(mut msg) = normalize_activity(&activity) else {
+            return Ok(WebhookResponse::ok());
+        };
+        if !self.replay.first_delivery(&msg.id) {
+            debug!(activity_id = %msg.id, "Skipping redelivered Teams activity");
+            return Ok(WebhookResponse::ok());
+        }
+
+        let admitted = admit_group_message(state, &mut msg, |msg| webhook::admit(true, msg)).await;
+        if !admitted {
+            debug!(sender = %msg.sender.id, "Teams message not admitted");
+            return Ok(WebhookResponse::ok());
+        }
+
+        webhook::spawn_dispatch(state, msg, |msg, text| NormalizedOutbound {
+            reply_to_id: Some(msg.id.clone()),
+            ..NormalizedOutbound::text(msg.chat_id.clone(), text)
+        });
+        Ok(WebhookResponse::ok())
+    }
+}
+
+/// Post an approval card for an exec approval request to `to`.
+pub(crate) async fn send_approval(config: &Config, to: &str, request: &Value) -> Result<()> {
+    TeamsChannel::new(config).send_approval(to, request).await
+}
+
+/// Convert a Bot Framework `message` activity into a [`NormalizedMessage`].
+///
+/// The bot's own `<at>` mention is removed from the text and recorded as
+/// `mentioned`.
+fn normalize_activity(activity: &Value) -> Option<NormalizedMessage> {
+    let bot_id = activity["recipient"]["id"].as_str();
+
+    let mut text = activity["text"].as_str().unwrap_or_default().to_string();
+    let mut mentioned = false;
+    for entity in activity["entities"].as_array().into_iter().flatten() {
+        if entity["type"] == "mention" && entity["mentioned"]["id"].as_str() == bot_id {
+            mentioned = true;
+            if let Some(tag) = entity["text"].as_str() {
+                text = text.replace(tag, "");
+            }
+        }
+    }
+    let text = text.trim().to_string();
+    if text.is_empty() {
+        return None;
+    }
+
+    let mut msg = activity_message(activity, text)?;
+    msg.mentioned = mentioned || msg.chat_type == ChatType::Dm;
+    Some(msg)
+}
+
+/// The conversation, sender and ids of a `message` activity, with `text`.
+fn activity_message(activity: &Value, text: String) -> Option<NormalizedMessage> {
+    if activity["type"] != "message" {
+        return None;
+    }
+    let conversation = &activity["conversation"];
+    let from = &activity["from"];
+
+    let chat_type = match conversation["conversationType"].as_str() {
+        Some("personal") => ChatType::Dm,
+        Some("channel") => ChatType::Thread,
+        _ => ChatType::Group,
+    };
+    let sender_id = from["aadObjectId"]
+        .as_str()
+        .or_else(|| from["id"].as_str())?
+        .to_string();
+
+    Some(NormalizedMessage {
+        id: activity["id"].as_str()?.to_string(),
+        channel: "teams".to_string(),
+        account_id: "default".to_string(),
+        chat_id: conversation["id"].as_str()?.to_string(),
+        chat_name: conversation["name"].as_str().map(str::to_string),
+        chat_type,
+        sender: NormalizedSender {
+            id: sender_id.clone(),
+            name: from["name"].as_str().unwrap_or(&sender_id).to_string(),
+            is_bot: false,
+            roles: Vec::new(),
+        },
+        text,
+        attachments: Vec::new(),
+        reply_to_id: activity["replyToId"].as_str().map(str::to_string),
+        thread_id: None,
+        mentioned: chat_type == ChatType::Dm,
+        timestamp: activity["timestamp"]
+            .as_str()
+            .map(str::to_string)
+            .unwrap_or_else(|| chrono::Utc::now().to_rfc3339()),
+        raw: Some(activity.clone()),
+    })
+}
+
+// ============================================================================
+// Adaptive Cards
+// ============================================================================
+
+/// Wrap an adaptive card as an activity attachment.
+fn card_attachment(card: Value) -> Value {
+    json!({ "contentType": ADAPTIVE_CARD_CONTENT_TYPE, "content": card })
+}
+
+fn adaptive_card(body: Vec<Value>, actions: Vec<Value>) -> Value {
+    json!({
+        "type": "AdaptiveCard",
+        "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+        "version": "1.4",
+        "body": body,
+        "actions": actions,
+    })
+}
+
+/// A card showing `text` with one button per quick reply.
+///
+/// Buttons are `messageBack` submits, so pressing one posts its value as
+/// the user's message.
+fn buttons_card(text: &str, buttons: &[OutboundButton]) -> Value {
+    adaptive_card(
+        vec![json!({ "type": "TextBlock", "text": text, "wrap": true })],
+        buttons
+            .iter()
+            .map(|button| {
+                json!({
+                    "type": "Action.Submit",
+                    "title": button.label,
+                    "data": {
+                        "msteams": {
+                            "type": "messageBack",
+                            "text": button.value,
+                            "displayText": button.label,
+                        }
+                    },
+                })
+            })
+            .collect(),
+    )
+}
+
+/// A card for an exec approval request with Approve and Deny buttons,
+/// which submit `{approvalId, decision}`.
+pub(crate) fn approval_card(request: &Value) -> Value {
+    let id = request["id"].as_str().unwrap_or_default();
+    let tool = request["tool"].as_str().unwrap_or("unknown");
+    let mut body = vec![
+        json!({
+            "type": "TextBlock",
+            "text": "Approval needed",
+            "weight": "Bolder",
+            "size": "Medium",
+        }),
+        json!({
+            "type": "FactSet",
+            "facts": [{ "title": "Tool", "value": tool }],
+        }),
+    ];
+    if !request["args"].is_null() {
+        let args = serde_json::to_string_pretty(&request["args"]).unwrap_or_default();
+        let args: String = if args.chars().count() > MAX_APPROVAL_ARGS_CHARS {
+            let mut cut: String = args.chars().take(MAX_APPROVAL_ARGS_CHARS).collect();
+            cut.push('…');
+            cut
+        } else {
+            args
+        };
+        body.push(json!({
+            "type": "TextBlock",
+            "text": args,
+            "wrap": true,
+            "fontType": "Monospace",
+        }));
+    }
+    let action = |title: &str, decision: &str, style: &str| {
+        json!({
+            "type": "Action.Submit",
+            "title": title,
+            "style": style,
+            "data": { "approvalId": id, "decision": decision },
+        })
+    };
+    adaptive_card(
+        body,
+        vec![
+            action("Approve", "approved", "positive"),
+            action("Deny", "denied", "destructive"),
+        ],
+    )
+}
 
-        // `to` is a conversation ID from the Bot Framework activity.
-        // Format: `POST {serviceUrl}/v3/conversations/{conversationId}/activities`
-        let url = format!(
-            "{}v3/conversations/{}/activities",
-            service_url.trim_end_matches('/').to_string() + "/",
-            to,
+/// A press of an approval card button.
+#[derive(Debug, PartialEq, Eq)]
+struct ApprovalSubmission {
+    approval_id: String,
+    approved: bool,
+}
+
+/// The approval decision carried by a card submission (an activity with
+/// a `value` and no text), if any.
+fn approval_submission(activity: &Value) -> Option<ApprovalSubmission> {
+    if activity["type"] != "message" {
+        return None;
+    }
+    let value = &activity["value"];
+    let approval_id = value["approvalId"].as_str()?.to_string();
+    let approved = match value["decision"].as_str()? {
+        "approved" => true,
+        "denied" => false,
+        _ => return None,
+    };
+    Some(ApprovalSubmission {
+        approval_id,
+        approved,
+    })
+}
+
+/// Record a decision on a pending exec approval request, returning the
+/// text to answer with.
+fn resolve_approval(
+    requests: &mut HashMap<String, Value>,
+    submission: &ApprovalSubmission,
+    resolved_by: &str,
+) -> String {
+    let Some(request) = requests.get_mut(&submission.approval_id) else {
+        return "That approval request no longer exists.".to_string();
+    };
+    if request["status"] != "pending" {
+        return format!(
+            "That request was already {}.",
+            request["status"].as_str().unwrap_or("resolved")
         );
+    }
+    let decision = if submission.approved {
+        "approved"
+    } else {
+        "denied"
+    };
+    request["status"] = json!(decision);
+    request["resolvedAt"] = json!(chrono::Utc::now().to_rfc3339());
+    request["resolvedBy"] = json!(format!("teams:{resolved_by}"));
+    info!(id = %submission.approval_id, decision, "Teams: exec approval resolved");
+    let tool = request["tool"].as_str().unwrap_or("unknown");
+    format!(
+        "{} `{tool}`.",
+        if submission.approved {
+            "Approved"
+        } else {
+            "Denied"
+        }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::webhook::tests::{request, sign_test_jwt, test_jwks};
+    use axum::http::Method;
+    use wiremock::matchers::{body_partial_json, body_string_contains, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
 
-        let body = serde_json::json!({
+    fn activity(conversation_type: &str) -> Value {
+        serde_json::json!({
             "type": "message",
-            "text": message,
+            "id": "1700000000000",
+            "timestamp": "2024-01-01T00:00:00.000Z",
+            "serviceUrl": "https://smba.trafficmanager.net/emea/",
+            "from": { "id": "29:abc", "name": "Ada", "aadObjectId": "aad-1" },
+            "recipient": { "id": "28:bot", "name": "Lobster" },
+            "conversation": { "id": "19:room@thread.tacv2", "conversationType": conversation_type },
+            "text": "<at>Lobster</at> status please",
+            "entities": [
+                { "type": "mention", "text": "<at>Lobster</at>", "mentioned": { "id": "28:bot" } }
+            ]
+        })
+    }
+
+    #[test]
+    fn bot_mentions_are_stripped() {
+        let msg = normalize_activity(&activity("channel")).unwrap();
+        assert_eq!(msg.text, "status please");
+        assert!(msg.mentioned);
+        assert_eq!(msg.chat_type, ChatType::Thread);
+        assert_eq!(msg.sender.id, "aad-1");
+
+        let mut other = activity("groupChat");
+        other["entities"][0]["mentioned"]["id"] = "28:someone-else".into();
+        let msg = normalize_activity(&other).unwrap();
+        assert!(!msg.mentioned);
+        assert!(msg.text.contains("<at>Lobster</at>"));
+
+        assert_eq!(
+            normalize_activity(&activity("personal")).unwrap().chat_type,
+            ChatType::Dm
+        );
+        let mut typing = activity("personal");
+        typing["type"] = "typing".into();
+        assert!(normalize_activity(&typing).is_none());
+    }
+
+    #[tokio::test]
+    async fn activities_require_a_token_bound_to_their_service_url() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/keys"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(test_jwks()))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/openid"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({ "jwks_uri": format!("{}/keys", server.uri()) })),
+            )
+            .mount(&server)
+            .await;
+
+        let mut config = Config::default();
+        config.channels.msteams = Some(MsTeamsConfig {
+            enabled: Some(true),
+            app_id: Some("app-id".to_string()),
+            open_id_metadata_url: Some(format!("{}/openid", server.uri())),
+            ..Default::default()
         });
+        let channel = TeamsChannel::new(&config);
 
-        info!(conversation_id = %to, "Teams: sending message");
+        let exp = chrono::Utc::now().timestamp() + 600;
+        let token = |service_url: &str| {
+            sign_test_jwt(&serde_json::json!({
+                "iss": "https://api.botframework.com",
+                "aud": "app-id",
+                "exp": exp,
+                "serviceurl": service_url,
+            }))
+        };
+        let body = activity("personal").to_string();
+        let with_token = |token: &str| {
+            let auth = format!("Bearer {}", token);
+            request(
+                Method::POST,
+                "messages",
+                &[("authorization", &auth)],
+                body.as_bytes(),
+            )
+        };
 
-        let resp = self
-            .client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", token))
-            .json(&body)
-            .send()
-            .await?;
+        let good = with_token(&token("https://smba.trafficmanager.net/emea/"));
+        assert_eq!(
+            channel.authenticate(&good).await.as_deref(),
+            Some("https://smba.trafficmanager.net/emea/")
+        );
+        let unsigned = request(Method::POST, "messages", &[], body.as_bytes());
+        assert!(channel.authenticate(&unsigned).await.is_none());
+        let tampered = with_token(&format!(
+            "{}x",
+            token("https://smba.trafficmanager.net/emea/")
+        ));
+        assert!(channel.authenticate(&tampered).await.is_none());
+    }
 
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let text = resp.text().await.unwrap_or_default();
-            anyhow::bail!("Teams send failed ({}): {}", status, text);
-        }
+    #[tokio::test]
+    async fn proactive_messages_open_and_remember_conversations() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .and(body_string_contains("client_id=app-id"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({ "access_token": "bot-token", "expires_in": 3600 })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v3/conversations"))
+            .and(header("authorization", "Bearer bot-token"))
+            .and(body_partial_json(json!({
+                "bot": { "id": "28:app-id" },
+                "members": [{ "id": "aad-1" }],
+                "tenantId": "tenant-1",
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "a:dm" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v3/conversations/a:dm/activities"))
+            .and(body_partial_json(
+                json!({ "type": "message", "text": "Daily report" }),
+            ))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "id": "act-1" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v3/conversations/a:dm/activities"))
+            .and(body_partial_json(json!({
+                "attachments": [{
+                    "contentType": ADAPTIVE_CARD_CONTENT_TYPE,
+                    "content": { "actions": [{
+                        "type": "Action.Submit",
+                        "title": "Yes",
+                        "data": { "msteams": { "type": "messageBack", "text": "yes" } },
+                    }] },
+                }],
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "id": "act-2" })))
+            .expect(1)
+            .mount(&server)
+            .await;
 
-        Ok(())
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config {
+            state_dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        config.channels.msteams = Some(MsTeamsConfig {
+            enabled: Some(true),
+            app_id: Some("app-id".to_string()),
+            app_password: Some("secret".to_string()),
+            tenant_id: Some("tenant-1".to_string()),
+            token_url: Some(format!("{}/token", server.uri())),
+            service_url: Some(server.uri()),
+            ..Default::default()
+        });
+
+        let channel = TeamsChannel::new(&config);
+        channel
+            .send_message("user:aad-1", "Daily report")
+            .await
+            .unwrap();
+
+        // The token is reused for the next send.
+        let mut question = NormalizedOutbound::text("a:dm", "Ship it?");
+        question.buttons.push(OutboundButton {
+            label: "Yes".to_string(),
+            value: "yes".to_string(),
+        });
+        let id = ChannelPlugin::send(&channel, &question).await.unwrap();
+        assert_eq!(id.as_deref(), Some("act-2"));
+
+        // A fresh instance (as used by cron delivery) knows the conversation.
+        let restarted = TeamsChannel::new(&config);
+        assert_eq!(
+            restarted.conversations.read()["a:dm"].service_url,
+            server.uri()
+        );
+    }
+
+    #[test]
+    fn approval_cards_resolve_pending_requests() {
+        let request = json!({
+            "id": "req-1",
+            "tool": "bash",
+            "args": { "command": "rm -rf build" },
+            "status": "pending",
+        });
+        let card = approval_card(&request);
+        assert_eq!(card["type"], "AdaptiveCard");
+        assert!(card["body"][2]["text"]
+            .as_str()
+            .unwrap()
+            .contains("rm -rf build"));
+        assert_eq!(
+            card["actions"][1]["data"],
+            json!({ "approvalId": "req-1", "decision": "denied" })
+        );
+
+        let mut submit = activity("personal");
+        submit["text"] = Value::Null;
+        submit["value"] = card["actions"][0]["data"].clone();
+        let submission = approval_submission(&submit).unwrap();
+        assert_eq!(
+            submission,
+            ApprovalSubmission {
+                approval_id: "req-1".to_string(),
+                approved: true,
+            }
+        );
+        assert!(approval_submission(&activity("personal")).is_none());
+
+        let mut requests = HashMap::from([("req-1".to_string(), request)]);
+        assert_eq!(
+            resolve_approval(&mut requests, &submission, "aad-1"),
+            "Approved `bash`."
+        );
+        assert_eq!(requests["req-1"]["status"], "approved");
+        assert_eq!(requests["req-1"]["resolvedBy"], "teams:aad-1");
+        assert_eq!(
+            resolve_approval(&mut requests, &submission, "aad-1"),
+            "That request was already approved."
+        );
     }
 }
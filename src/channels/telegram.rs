@@ -1,13 +1,46 @@
-use crate::config::Config;
+use crate::config::{Config, GroupPolicy, ReplyToMode, TelegramAccountConfig};
 use crate::gateway::GatewayState;
+use crate::infra::abort_signal::{monitor_with_abort_lifecycle, AbortHandle};
+use crate::infra::delivery::TelegramBackoff;
 use crate::infra::dm_policy;
 
-use super::plugin::{ChannelCapability, ChannelMeta, ChannelPlugin};
+use super::admission::{account, group_allowlist};
+use super::commands::{self, COMMANDS};
+use super::format::{render_markdown_chunks, FormatTarget};
+use super::group_history::admit_group_message;
+use super::inbound::{dispatch_inbound, typing_interval_ms};
+use super::normalize::{
+    split_text, ChatType, NormalizedAttachment, NormalizedMessage, NormalizedOutbound,
+    NormalizedSender,
+};
+use super::plugin::{
+    unsupported, ChannelCapability, ChannelMeta, ChannelPlugin, WebhookRequest, WebhookResponse,
+    DEFAULT_ACCOUNT_ID,
+};
+use super::voice;
+use super::webhook::WebhookRoute;
+use super::TypingKeepaliveLoop;
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
+use axum::http::{Method, StatusCode};
+use parking_lot::{Mutex, RwLock};
 use serde::{Deserialize, Serialize};
-use tracing::{info, warn};
+use std::sync::Arc;
+use std::time::Duration;
+use subtle::ConstantTimeEq;
+use teloxide::net::Download;
+use teloxide::payloads::{
+    GetUpdatesSetters, SendChatActionSetters, SendMessageSetters, SendVoiceSetters,
+    SetMessageReactionSetters, SetWebhookSetters,
+};
+use teloxide::requests::Requester;
+use teloxide::types::{
+    ChatAction, ChatId, InputFile, LinkPreviewOptions, Message, MessageEntityKind, MessageId,
+    ParseMode, ReactionType, Recipient, ReplyParameters, ThreadId, Update, UpdateKind,
+};
+use teloxide::{ApiError, Bot, RequestError};
+use tracing::{debug, info, warn};
 
 // ============================================================================
 // v2026.2.26: Inline Keyboard Support
@@ -62,14 +95,6 @@ pub struct BotCommand {
     pub description: String,
 }
 
-/// Result of a command registration attempt.
-#[derive(Debug)]
-pub struct CommandRegistrationResult {
-    pub success: bool,
-    pub registered_count: usize,
-    pub error: Option<String>,
-}
-
 // ============================================================================
 // v2026.2.26: Streaming Preview Finalization
 // ============================================================================
@@ -112,106 +137,165 @@ impl StreamingPreview {
 // Telegram Channel Implementation
 // ============================================================================
 
+/// Default Bot API base URL.
+const TELEGRAM_API_URL: &str = "https://api.telegram.org";
+/// Maximum message length accepted by `sendMessage`.
+const TELEGRAM_MAX_MESSAGE_CHARS: usize = 4096;
+/// Long-polling timeout passed to `getUpdates`, in seconds.
+const POLL_TIMEOUT_SECS: u32 = 10;
+/// Initial delay before retrying a failed `getUpdates` call.
+const POLL_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// Maximum delay between `getUpdates` retries.
+const POLL_MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// Webhook path below `/channels/telegram/` when `webhookPath` is unset.
+const DEFAULT_WEBHOOK_PATH: &str = "webhook";
+/// Header carrying the secret token registered with `setWebhook`.
+const SECRET_TOKEN_HEADER: &str = "x-telegram-bot-api-secret-token";
+/// Clients show "typing…" for ~5 s, so refresh slightly sooner.
+const TYPING_INTERVAL_MS: u64 = 4_000;
+/// Forum "General" topic; sends to it must omit `message_thread_id`.
+const GENERAL_TOPIC_ID: i32 = 1;
+
+/// Bot identity resolved via `getMe`, used for mention detection.
+#[derive(Debug, Clone)]
+struct BotIdentity {
+    id: u64,
+    username: Option<String>,
+}
+
+/// Per-account state shared between the channel, its polling task and the
+/// webhook handler.
+struct TelegramAccount {
+    account_id: String,
+    config: TelegramAccountConfig,
+    bot_token: Option<String>,
+    /// Bot API base URL without a trailing slash.
+    api_url: String,
+    identity: RwLock<Option<BotIdentity>>,
+    /// Backoff for `sendChatAction` 401s.
+    typing_backoff: Mutex<TelegramBackoff>,
+}
+
 /// Telegram channel implementation using the Bot API via teloxide.
+///
+/// Receives updates by long-polling `getUpdates`, or — when `webhookUrl` is
+/// configured — via `setWebhook` pointed at `/channels/telegram/<webhookPath>`
+/// on the gateway.
 pub struct TelegramChannel {
     enabled: bool,
-    bot_token: Option<String>,
-    /// v2026.2.26: DM allowlist from config.
-    dm_allow_from: Vec<String>,
+    account: Arc<TelegramAccount>,
+    /// Abort handle and task of the running polling loop, if any.
+    poller: Mutex<Option<(AbortHandle, tokio::task::JoinHandle<()>)>>,
 }
 
 impl TelegramChannel {
     pub fn new(config: &Config) -> Self {
+        Self::for_account(config, DEFAULT_ACCOUNT_ID)
+    }
+
+    /// The channel for one bot account: an entry of `accounts`, or the
+    /// top-level settings for the default account.
+    ///
+    /// Named accounts without a `webhookPath` listen on `webhook/<id>`.
+    pub fn for_account(config: &Config, account_id: &str) -> Self {
         let tg = &config.channels.telegram;
-        let bot_token = tg.default_account.bot_token.clone();
-        let enabled = tg.default_account.enabled.unwrap_or(bot_token.is_some());
+        let mut settings = account(&tg.accounts, &tg.default_account, account_id).clone();
+        if account_id != DEFAULT_ACCOUNT_ID && settings.webhook_path.is_none() {
+            settings.webhook_path = Some(format!("{DEFAULT_WEBHOOK_PATH}/{account_id}"));
+        }
+        let bot_token = settings.bot_token.clone();
+        let enabled = settings.enabled.unwrap_or(bot_token.is_some());
 
-        // v2026.2.26: Load DM allowlist (does NOT inherit from parent)
-        let dm_allow_from = tg
-            .default_account
-            .allow_from
-            .clone()
-            .unwrap_or_default();
+        let api_url = settings
+            .api_url
+            .as_deref()
+            .unwrap_or(TELEGRAM_API_URL)
+            .trim_end_matches('/')
+            .to_string();
 
         Self {
             enabled,
-            bot_token,
-            dm_allow_from,
+            account: Arc::new(TelegramAccount {
+                account_id: account_id.to_string(),
+                config: settings,
+                bot_token,
+                api_url,
+                identity: RwLock::new(None),
+                typing_backoff: Mutex::new(TelegramBackoff::default()),
+            }),
+            poller: Mutex::new(None),
         }
     }
 
     /// v2026.2.26: Check if a Telegram user is allowed to DM the bot.
     pub fn is_dm_allowed(&self, user_id: &str) -> bool {
-        if self.dm_allow_from.is_empty() {
+        // DM allowlist from config (does NOT inherit from parent).
+        let allow_from = self
+            .account
+            .config
+            .allow_from
+            .as_deref()
+            .unwrap_or_default();
+        if allow_from.is_empty() {
             // No allowlist = allow all DMs
             return true;
         }
-        dm_policy::is_source_allowed(&self.dm_allow_from, user_id)
+        dm_policy::is_source_allowed(allow_from, user_id)
     }
 
     /// v2026.2.26: Register bot commands with Telegram API.
     ///
-    /// Gracefully degrades if the API call fails (e.g., due to rate limits
-    /// or permissions). Commands are still usable even without registration.
-    pub async fn register_commands(
-        &self,
-        commands: &[BotCommand],
-    ) -> CommandRegistrationResult {
-        let token = match &self.bot_token {
-            Some(t) => t,
-            None => {
-                return CommandRegistrationResult {
-                    success: false,
-                    registered_count: 0,
-                    error: Some("No bot token configured".to_string()),
-                };
-            }
-        };
-
-        let url = format!(
-            "https://api.telegram.org/bot{}/setMyCommands",
-            token
-        );
-
+    /// Returns how many commands were registered. Callers should degrade
+    /// gracefully on failure (e.g., due to rate limits or permissions):
+    /// commands are still usable as text even without registration.
+    pub async fn register_commands(&self, commands: &[BotCommand]) -> Result<usize> {
+        let url = self.account.method_url("setMyCommands")?;
         let body = serde_json::json!({
             "commands": commands
         });
 
         let client = reqwest::Client::new();
-        match client.post(&url).json(&body).send().await {
-            Ok(resp) => {
-                if resp.status().is_success() {
-                    info!("Registered {} Telegram bot commands", commands.len());
-                    CommandRegistrationResult {
-                        success: true,
-                        registered_count: commands.len(),
-                        error: None,
-                    }
-                } else {
-                    // v2026.2.26: Graceful degradation — log warning but don't fail
-                    let status = resp.status();
-                    let text = resp.text().await.unwrap_or_default();
-                    warn!(
-                        "Telegram command registration failed ({}): {}",
-                        status, text
-                    );
-                    CommandRegistrationResult {
-                        success: false,
-                        registered_count: 0,
-                        error: Some(format!("API error {}: {}", status, text)),
-                    }
-                }
-            }
-            Err(e) => {
-                // v2026.2.26: Graceful degradation — network error
-                warn!("Telegram command registration error: {}", e);
-                CommandRegistrationResult {
-                    success: false,
-                    registered_count: 0,
-                    error: Some(format!("Network error: {}", e)),
-                }
+        let resp = client
+            .post(&url)
+            .json(&body)
+            .send()
+            .await
+            .context("network error")?;
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().await.unwrap_or_default();
+            bail!("API error {}: {}", status, text);
+        }
+        Ok(commands.len())
+    }
+
+    /// The command menu: built-in commands, then the account's
+    /// `customCommands`. Invalid or duplicate custom commands are skipped.
+    fn command_menu(&self) -> Vec<BotCommand> {
+        let mut menu: Vec<BotCommand> = COMMANDS
+            .iter()
+            .map(|c| BotCommand {
+                command: c.name.to_string(),
+                description: c.description.to_string(),
+            })
+            .collect();
+        for custom in self.account.config.custom_commands.iter().flatten() {
+            let command = custom.command.trim_start_matches('/').to_ascii_lowercase();
+            let valid = (1..=32).contains(&command.len())
+                && command
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+                && (1..=256).contains(&custom.description.chars().count());
+            if !valid || menu.iter().any(|c| c.command == command) {
+                warn!(command = %custom.command, "Skipping invalid Telegram custom command");
+                continue;
             }
+            menu.push(BotCommand {
+                command,
+                description: custom.description.clone(),
+            });
         }
+        menu
     }
 
     /// v2026.2.26: Send a message with inline keyboard buttons (for groups).
@@ -221,12 +305,7 @@ impl TelegramChannel {
         message: &str,
         keyboard: &InlineKeyboardMarkup,
     ) -> Result<()> {
-        let token = self
-            .bot_token
-            .as_deref()
-            .ok_or_else(|| anyhow::anyhow!("Telegram bot token not configured"))?;
-
-        let url = format!("https://api.telegram.org/bot{}/sendMessage", token);
+        let url = self.account.method_url("sendMessage")?;
 
         let body = serde_json::json!({
             "chat_id": chat_id,
@@ -247,12 +326,349 @@ impl TelegramChannel {
     }
 }
 
+impl TelegramAccount {
+    fn token(&self) -> Result<&str> {
+        self.bot_token
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Telegram bot token not configured"))
+    }
+
+    /// Build a teloxide bot pointed at the configured API base URL.
+    fn bot(&self) -> Result<Bot> {
+        let api_url = url::Url::parse(&self.api_url)
+            .with_context(|| format!("invalid Telegram apiUrl: {}", self.api_url))?;
+        Ok(Bot::new(self.token()?).set_api_url(api_url))
+    }
+
+    /// Full URL of a Bot API method, for calls made without teloxide.
+    fn method_url(&self, method: &str) -> Result<String> {
+        Ok(format!("{}/bot{}/{}", self.api_url, self.token()?, method))
+    }
+
+    fn webhook_path(&self) -> &str {
+        self.config
+            .webhook_path
+            .as_deref()
+            .map(|p| p.trim_matches('/'))
+            .filter(|p| !p.is_empty())
+            .unwrap_or(DEFAULT_WEBHOOK_PATH)
+    }
+
+    /// Whether a webhook request carries the configured secret token.
+    /// Without a secret every request is refused.
+    fn webhook_authorized(&self, request: &WebhookRequest) -> bool {
+        match &self.config.webhook_secret {
+            Some(secret) => {
+                let provided = request.header(SECRET_TOKEN_HEADER).unwrap_or("");
+                provided.as_bytes().ct_eq(secret.as_bytes()).into()
+            }
+            None => false,
+        }
+    }
+
+    fn text_chunk_limit(&self) -> usize {
+        (self.config.text_chunk_limit as usize).clamp(1, TELEGRAM_MAX_MESSAGE_CHARS)
+    }
+
+    /// Decide whether an inbound message should reach the agent.
+    fn admit(&self, msg: &NormalizedMessage) -> bool {
+        // `dmPolicy` and `allowFrom` are applied by the admission stage.
+        if msg.chat_type == ChatType::Dm {
+            return true;
+        }
+
+        let groups = self.config.groups.as_ref();
+        let explicit = groups.and_then(|g| g.get(&msg.chat_id));
+        let group = explicit.or_else(|| groups.and_then(|g| g.get("*")));
+        let topic = msg
+            .thread_id
+            .as_ref()
+            .and_then(|t| group?.topics.as_ref()?.get(t));
+
+        if group.and_then(|g| g.enabled) == Some(false)
+            || topic.and_then(|t| t.enabled) == Some(false)
+        {
+            return false;
+        }
+
+        let sender_allowlist = group_allowlist(
+            topic
+                .and_then(|t| t.allow_from.as_ref())
+                .or_else(|| group.and_then(|g| g.allow_from.as_ref()))
+                .or(self.config.group_allow_from.as_ref()),
+            self.config.allow_from.as_ref(),
+        );
+        if let Some(list) = &sender_allowlist {
+            if !dm_policy::is_source_allowed(list, &msg.sender.id) {
+                return false;
+            }
+        }
+
+        let policy = topic
+            .and_then(|t| t.group_policy)
+            .or_else(|| group.and_then(|g| g.group_policy))
+            .or(self.config.group_policy)
+            .unwrap_or_default();
+        match policy {
+            GroupPolicy::Disabled => return false,
+            GroupPolicy::Allowlist if explicit.is_none() && sender_allowlist.is_none() => {
+                return false;
+            }
+            GroupPolicy::Open | GroupPolicy::Allowlist => {}
+        }
+
+        let require_mention = topic
+            .and_then(|t| t.require_mention)
+            .or_else(|| group.and_then(|g| g.require_mention))
+            .unwrap_or(true);
+        !require_mention || msg.mentioned
+    }
+
+    /// Process one update: normalise, admit, run the agent and reply.
+    async fn handle_update(self: Arc<Self>, state: GatewayState, update: Update) {
+        let UpdateKind::Message(message) = update.kind else {
+            return;
+        };
+
+        let identity = self.identity.read().clone();
+        let Some(mut normalized) = normalize_message(&self.account_id, &message, identity.as_ref())
+        else {
+            return;
+        };
+
+        let admitted = admit_group_message(&state, &mut normalized, |msg| self.admit(msg)).await;
+        if !admitted {
+            debug!(
+                chat_id = %normalized.chat_id,
+                sender = %normalized.sender.id,
+                "Telegram message not admitted"
+            );
+            return;
+        }
+
+        let bot = match self.bot() {
+            Ok(bot) => bot,
+            Err(e) => {
+                warn!(error = %e, "Telegram bot unavailable");
+                return;
+            }
+        };
+        // Voice notes are fetched up front: attachments carry no file id.
+        if let Some(file_id) = voice_file_id(&message) {
+            match download_file(&bot, file_id).await {
+                Ok(data) => {
+                    if let Some(audio) = normalized
+                        .attachments
+                        .iter_mut()
+                        .find(|a| voice::is_audio(a))
+                    {
+                        audio.data = Some(data);
+                    }
+                }
+                Err(e) => debug!(error = %e, "Telegram voice download failed"),
+            }
+        }
+        let thread = outbound_thread(&message);
+
+        let interval = typing_interval_ms(&*state.config.read().await, TYPING_INTERVAL_MS);
+        self.send_typing(&bot, message.chat.id, thread);
+        let typing = TypingKeepaliveLoop::new(interval);
+        let typing_task = {
+            let account = self.clone();
+            let bot = bot.clone();
+            let chat_id = message.chat.id;
+            typing.start(move || account.send_typing(&bot, chat_id, thread))
+        };
+
+        let result = dispatch_inbound(&state, &normalized).await;
+        typing.stop();
+        typing_task.abort();
+
+        match result {
+            Ok(Some(reply)) => {
+                let reply_to = match self.config.reply_to_mode.unwrap_or_default() {
+                    ReplyToMode::Off => None,
+                    ReplyToMode::First | ReplyToMode::All => Some(message.id.0.to_string()),
+                };
+                let outbound = NormalizedOutbound {
+                    reply_to_id: reply_to,
+                    thread_id: thread.map(|t| t.0 .0.to_string()),
+                    account_id: Some(self.account_id.clone()),
+                    ..NormalizedOutbound::text(message.chat.id.to_string(), reply)
+                };
+                let outbound = voice::voice_reply(&state, &normalized, outbound).await;
+                if let Err(e) = state.channels.enqueue("telegram", outbound).await {
+                    warn!(chat_id = %message.chat.id, error = %e, "Telegram reply failed");
+                }
+            }
+            Ok(None) => {}
+            Err(e) => warn!(chat_id = %message.chat.id, error = %e, "Telegram agent run failed"),
+        }
+    }
+
+    /// Fire a `sendChatAction(typing)` without waiting for the result.
+    fn send_typing(self: &Arc<Self>, bot: &Bot, chat_id: ChatId, thread: Option<ThreadId>) {
+        if self.typing_backoff.lock().should_skip(now_ms()) {
+            return;
+        }
+        let account = self.clone();
+        let bot = bot.clone();
+        tokio::spawn(async move {
+            let mut request = bot.send_chat_action(chat_id, ChatAction::Typing);
+            if let Some(thread) = thread {
+                request = request.message_thread_id(thread);
+            }
+            match request.await {
+                Ok(_) => account.typing_backoff.lock().record_success(),
+                Err(RequestError::Api(ApiError::InvalidToken)) => {
+                    account.typing_backoff.lock().record_401(now_ms());
+                }
+                Err(e) => debug!(error = %e, "Telegram sendChatAction failed"),
+            }
+        });
+    }
+
+    /// Send markdown text as one or more HTML-formatted messages.
+    ///
+    /// Falls back to plain text for a chunk Telegram refuses to parse.
+    async fn send_text(
+        &self,
+        bot: &Bot,
+        chat: Recipient,
+        thread: Option<ThreadId>,
+        reply_to: Option<MessageId>,
+        text: &str,
+    ) -> Result<()> {
+        let html = self.config.markdown != Some(false);
+        let limit = self.text_chunk_limit();
+        let chunks = if html {
+            render_markdown_chunks(text, FormatTarget::TelegramHtml, limit)
+        } else {
+            split_text(text, limit)
+        };
+        let reply_all = self.config.reply_to_mode == Some(ReplyToMode::All);
+
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            let reply = reply_to.filter(|_| i == 0 || reply_all);
+            let result = self
+                .send_chunk(bot, chat.clone(), thread, reply, chunk.clone(), html)
+                .await;
+            match result {
+                Ok(()) => {}
+                Err(RequestError::Api(ApiError::CantParseEntities(e))) if html => {
+                    warn!(error = %e, "Telegram rejected HTML, resending as plain text");
+                    self.send_chunk(
+                        bot,
+                        chat.clone(),
+                        thread,
+                        reply,
+                        html_to_plain(&chunk),
+                        false,
+                    )
+                    .await?;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    async fn send_chunk(
+        &self,
+        bot: &Bot,
+        chat: Recipient,
+        thread: Option<ThreadId>,
+        reply_to: Option<MessageId>,
+        text: String,
+        html: bool,
+    ) -> Result<(), RequestError> {
+        let mut request = bot.send_message(chat, text);
+        if html {
+            request = request.parse_mode(ParseMode::Html);
+        }
+        if let Some(thread) = thread {
+            request = request.message_thread_id(thread);
+        }
+        if let Some(id) = reply_to {
+            request =
+                request.reply_parameters(ReplyParameters::new(id).allow_sending_without_reply());
+        }
+        if self.config.link_preview == Some(false) {
+            request = request.link_preview_options(LinkPreviewOptions {
+                is_disabled: true,
+                url: None,
+                prefer_small_media: false,
+                prefer_large_media: false,
+                show_above_text: false,
+            });
+        }
+        request.await.map(|_| ())
+    }
+
+    /// Send OGG/Opus audio as a voice note.
+    async fn send_voice(
+        &self,
+        bot: &Bot,
+        chat: Recipient,
+        thread: Option<ThreadId>,
+        reply_to: Option<MessageId>,
+        audio: &[u8],
+    ) -> Result<()> {
+        let file = InputFile::memory(audio.to_vec()).file_name("voice.ogg");
+        let mut request = bot.send_voice(chat, file);
+        if let Some(thread) = thread {
+            request = request.message_thread_id(thread);
+        }
+        if let Some(id) = reply_to {
+            request =
+                request.reply_parameters(ReplyParameters::new(id).allow_sending_without_reply());
+        }
+        request.await.context("Telegram sendVoice failed")?;
+        Ok(())
+    }
+
+    /// Long-poll `getUpdates` until aborted, backing off on errors.
+    async fn poll_updates(self: Arc<Self>, bot: Bot, state: GatewayState) {
+        let mut offset: Option<i32> = None;
+        let mut backoff = POLL_INITIAL_BACKOFF;
+
+        loop {
+            let mut request = bot.get_updates().timeout(POLL_TIMEOUT_SECS);
+            if let Some(offset) = offset {
+                request = request.offset(offset);
+            }
+
+            match request.await {
+                Ok(updates) => {
+                    backoff = POLL_INITIAL_BACKOFF;
+                    for update in updates {
+                        offset = Some(update.id.0 as i32 + 1);
+                        tokio::spawn(self.clone().handle_update(state.clone(), update));
+                    }
+                }
+                Err(RequestError::RetryAfter(after)) => {
+                    tokio::time::sleep(after.duration()).await;
+                }
+                Err(e) => {
+                    warn!(error = %e, "Telegram getUpdates failed; retrying in {:?}", backoff);
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(POLL_MAX_BACKOFF);
+                }
+            }
+        }
+    }
+}
+
 #[async_trait]
 impl ChannelPlugin for TelegramChannel {
     fn id(&self) -> &str {
         "telegram"
     }
 
+    fn account_id(&self) -> &str {
+        &self.account.account_id
+    }
+
     fn meta(&self) -> ChannelMeta {
         ChannelMeta {
             name: "Telegram".to_string(),
@@ -271,6 +687,7 @@ impl ChannelPlugin for TelegramChannel {
             ChannelCapability::Reactions,
             ChannelCapability::Groups,
             ChannelCapability::Threads,
+            ChannelCapability::TypingIndicators,
             ChannelCapability::EditMessage,
             ChannelCapability::DeleteMessage,
             ChannelCapability::Stickers,
@@ -279,12 +696,20 @@ impl ChannelPlugin for TelegramChannel {
         ]
     }
 
-    async fn start_account(&self, _state: &GatewayState) -> Result<()> {
+    /// The webhook route exists only in webhook mode (`webhookUrl` set).
+    fn webhook_routes(&self) -> Vec<WebhookRoute> {
+        if !self.enabled || self.account.config.webhook_url.is_none() {
+            return Vec::new();
+        }
+        vec![WebhookRoute::post(self.account.webhook_path())]
+    }
+
+    async fn start_account(&self, state: &GatewayState) -> Result<()> {
         if !self.enabled {
             return Ok(());
         }
 
-        let token = match &self.bot_token {
+        let token = match &self.account.bot_token {
             Some(t) => t,
             None => {
                 warn!("Telegram channel enabled but no bot token configured");
@@ -297,7 +722,65 @@ impl ChannelPlugin for TelegramChannel {
             &token[token.len().saturating_sub(4)..]
         );
 
-        // TODO: Initialise teloxide bot dispatcher and start polling / webhook.
+        let bot = self.account.bot()?;
+        let me = bot.get_me().await.context("Telegram getMe failed")?;
+        info!(
+            username = me.user.username.as_deref().unwrap_or(""),
+            "Telegram bot authenticated"
+        );
+        *self.account.identity.write() = Some(BotIdentity {
+            id: me.user.id.0,
+            username: me.user.username.clone(),
+        });
+
+        let native =
+            commands::native_enabled(&*state.config.read().await, self.account.config.commands);
+        if native {
+            // v2026.2.26: Graceful degradation — log warning but don't fail
+            match self.register_commands(&self.command_menu()).await {
+                Ok(count) => info!("Registered {} Telegram bot commands", count),
+                Err(e) => warn!("Telegram command registration failed: {:#}", e),
+            }
+        }
+
+        if let Some(webhook_url) = &self.account.config.webhook_url {
+            let url = url::Url::parse(webhook_url)
+                .with_context(|| format!("invalid Telegram webhookUrl: {webhook_url}"))?;
+            let Some(secret) = &self.account.config.webhook_secret else {
+                bail!("Telegram webhook mode requires webhookSecret");
+            };
+            bot.set_webhook(url)
+                .secret_token(secret.clone())
+                .await
+                .context("Telegram setWebhook failed")?;
+            info!(
+                "Telegram webhook registered (gateway path /channels/telegram/{})",
+                self.account.webhook_path()
+            );
+            return Ok(());
+        }
+
+        // Polling and webhooks are mutually exclusive on the Bot API side.
+        bot.delete_webhook()
+            .await
+            .context("Telegram deleteWebhook failed")?;
+
+        let abort = AbortHandle::new();
+        let task = {
+            let account = self.account.clone();
+            let state = state.clone();
+            let abort = abort.clone();
+            tokio::spawn(async move {
+                let polling = account.poll_updates(bot, state);
+                if monitor_with_abort_lifecycle(polling, &abort).await.is_err() {
+                    debug!("Telegram polling stopped");
+                }
+            })
+        };
+        if let Some((previous, _)) = self.poller.lock().replace((abort, task)) {
+            previous.abort();
+        }
+        info!("Telegram long-polling started");
 
         Ok(())
     }
@@ -305,38 +788,391 @@ impl ChannelPlugin for TelegramChannel {
     async fn stop_account(&self) -> Result<()> {
         if self.enabled {
             info!("Telegram channel stopping");
-            // TODO: Signal the teloxide dispatcher to shut down.
+            let poller = self.poller.lock().take();
+            if let Some((abort, task)) = poller {
+                abort.abort();
+                let _ = task.await;
+            }
         }
         Ok(())
     }
 
-    async fn send_message(&self, to: &str, _message: &str) -> Result<()> {
-        let _token = self
-            .bot_token
-            .as_deref()
-            .ok_or_else(|| anyhow::anyhow!("Telegram bot token not configured"))?;
+    async fn probe(&self) -> Result<()> {
+        if self
+            .poller
+            .lock()
+            .as_ref()
+            .is_some_and(|(_, task)| task.is_finished())
+        {
+            anyhow::bail!("Telegram polling stopped");
+        }
+        self.account
+            .bot()?
+            .get_me()
+            .await
+            .context("Telegram getMe failed")?;
+        Ok(())
+    }
 
-        let _chat_id: i64 = to
-            .parse()
-            .map_err(|_| anyhow::anyhow!("invalid Telegram chat_id: {to}"))?;
+    async fn send_message(&self, to: &str, message: &str) -> Result<()> {
+        let bot = self.account.bot()?;
+        let (chat, thread) = parse_target(to)?;
 
         info!(chat_id = to, "Telegram: sending message");
 
-        // TODO: Use teloxide to call sendMessage with (_token, _chat_id, _message).
+        self.account
+            .send_text(&bot, chat, thread, None, message)
+            .await
+    }
 
+    async fn send(&self, message: &NormalizedOutbound) -> Result<Option<String>> {
+        // Only voice notes with inline audio can be sent so far.
+        let voice_notes = message
+            .attachments
+            .iter()
+            .map(|a| a.data.as_deref().filter(|_| voice::is_audio(a)))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| unsupported(self.id(), ChannelCapability::SendMedia))?;
+        let bot = self.account.bot()?;
+        let target = match &message.thread_id {
+            Some(thread) => format!("{}:{thread}", message.chat_id),
+            None => message.chat_id.clone(),
+        };
+        let (chat, thread) = parse_target(&target)?;
+        let reply_to = message
+            .reply_to_id
+            .as_deref()
+            .and_then(|id| id.parse().ok())
+            .map(MessageId);
+
+        for audio in voice_notes {
+            self.account
+                .send_voice(&bot, chat.clone(), thread, reply_to, audio)
+                .await?;
+        }
+        if !message.text.trim().is_empty() {
+            self.account
+                .send_text(&bot, chat, thread, reply_to, &message.text)
+                .await?;
+        }
+        Ok(None)
+    }
+
+    async fn react(&self, chat_id: &str, message_id: &str, emoji: &str) -> Result<()> {
+        let bot = self.account.bot()?;
+        let (chat, _) = parse_target(chat_id)?;
+        let message_id = message_id
+            .parse()
+            .map(MessageId)
+            .with_context(|| format!("invalid Telegram message id: {message_id}"))?;
+        // Bots get one reaction per message; an empty list clears it.
+        let reaction = (!emoji.is_empty()).then(|| ReactionType::Emoji {
+            emoji: emoji.to_string(),
+        });
+        bot.set_message_reaction(chat, message_id)
+            .reaction(reaction)
+            .await
+            .context("Telegram setMessageReaction failed")?;
         Ok(())
     }
+
+    async fn handle_webhook(
+        &self,
+        state: &GatewayState,
+        request: WebhookRequest,
+    ) -> Result<WebhookResponse> {
+        if self.webhook_routes().is_empty()
+            || request.path.trim_matches('/') != self.account.webhook_path()
+        {
+            return Ok(WebhookResponse::not_found());
+        }
+        if request.method != Method::POST {
+            return Ok(WebhookResponse::status(StatusCode::METHOD_NOT_ALLOWED));
+        }
+        if !self.account.webhook_authorized(&request) {
+            warn!("Telegram webhook request with invalid secret token");
+            return Ok(WebhookResponse::status(StatusCode::UNAUTHORIZED));
+        }
+
+        let update: Update = match serde_json::from_slice(&request.body) {
+            Ok(update) => update,
+            Err(e) => {
+                return Ok(WebhookResponse::text(
+                    StatusCode::BAD_REQUEST,
+                    format!("invalid update: {e}"),
+                ));
+            }
+        };
+
+        // Acknowledge immediately; Telegram retries slow webhooks.
+        tokio::spawn(self.account.clone().handle_update(state.clone(), update));
+        Ok(WebhookResponse::ok())
+    }
 }
 
-/// Convenience function called by the top-level `send_message` dispatcher.
-pub(crate) async fn send_message(config: &Config, to: &str, message: &str) -> Result<()> {
-    let channel = TelegramChannel::new(config);
-    channel.send_message(to, message).await
+// ============================================================================
+// Update Normalization
+// ============================================================================
+
+/// Convert a Telegram message into a [`NormalizedMessage`].
+///
+/// Returns `None` for messages without a user sender (channel posts), the
+/// bot's own messages, and service messages with no text or media.
+fn normalize_message(
+    account_id: &str,
+    message: &Message,
+    identity: Option<&BotIdentity>,
+) -> Option<NormalizedMessage> {
+    let from = message.from.as_ref()?;
+    if identity.is_some_and(|me| me.id == from.id.0) {
+        return None;
+    }
+
+    let (text, mentioned) = extract_text(message, identity);
+    let attachments = extract_attachments(message);
+    if text.is_empty() && attachments.is_empty() {
+        return None;
+    }
+
+    let (chat_type, thread_id) = if message.chat.is_private() {
+        (ChatType::Dm, None)
+    } else if message.is_topic_message {
+        (
+            ChatType::Thread,
+            message.thread_id.map(|t| t.0 .0.to_string()),
+        )
+    } else {
+        (ChatType::Group, None)
+    };
+
+    Some(NormalizedMessage {
+        id: message.id.0.to_string(),
+        channel: "telegram".to_string(),
+        account_id: account_id.to_string(),
+        chat_id: message.chat.id.0.to_string(),
+        chat_name: message
+            .chat
+            .title()
+            .or_else(|| message.chat.username())
+            .map(str::to_string),
+        chat_type,
+        sender: NormalizedSender {
+            id: from.id.0.to_string(),
+            name: from.full_name(),
+            is_bot: from.is_bot,
+            roles: Vec::new(),
+        },
+        text,
+        attachments,
+        reply_to_id: message.reply_to_message().map(|m| m.id.0.to_string()),
+        thread_id,
+        mentioned,
+        timestamp: message.date.to_rfc3339(),
+        raw: serde_json::to_value(message).ok(),
+    })
+}
+
+/// Extract message text (or caption), stripping `@bot` mentions, and report
+/// whether the bot was addressed.
+fn extract_text(message: &Message, identity: Option<&BotIdentity>) -> (String, bool) {
+    let text = message.text().or_else(|| message.caption()).unwrap_or("");
+    let Some(me) = identity else {
+        return (text.trim().to_string(), false);
+    };
+
+    let handle = me.username.as_deref().map(|u| format!("@{u}"));
+    let is_handle = |s: &str| handle.as_deref().is_some_and(|h| s.eq_ignore_ascii_case(h));
+
+    let mut mentioned = message
+        .reply_to_message()
+        .and_then(|m| m.from.as_ref())
+        .is_some_and(|u| u.id.0 == me.id);
+
+    let entities = message
+        .parse_entities()
+        .or_else(|| message.parse_caption_entities())
+        .unwrap_or_default();
+    let mut strip = Vec::new();
+    for entity in &entities {
+        match entity.kind() {
+            MessageEntityKind::Mention if is_handle(entity.text()) => {
+                mentioned = true;
+                strip.push(entity.start()..entity.end());
+            }
+            MessageEntityKind::TextMention { user } if user.id.0 == me.id => {
+                mentioned = true;
+            }
+            MessageEntityKind::BotCommand => {
+                if let Some((_, target)) = entity.text().split_once('@') {
+                    mentioned |= is_handle(&format!("@{target}"));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if strip.is_empty() {
+        return (text.trim().to_string(), mentioned);
+    }
+
+    let mut cleaned = String::with_capacity(text.len());
+    let mut pos = 0;
+    for range in strip {
+        cleaned.push_str(&text[pos..range.start]);
+        pos = range.end;
+    }
+    cleaned.push_str(&text[pos..]);
+    let cleaned = cleaned
+        .split(' ')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    (cleaned.trim().to_string(), mentioned)
+}
+
+fn extract_attachments(message: &Message) -> Vec<NormalizedAttachment> {
+    let attachment =
+        |mime: Option<String>, filename: Option<String>, size: u32| NormalizedAttachment {
+            mime_type: mime,
+            url: None,
+            data: None,
+            filename,
+            // teloxide reports u32::MAX when the Bot API omits the size.
+            size: (size != u32::MAX).then_some(size as u64),
+        };
+
+    let mut attachments = Vec::new();
+    if let Some(photo) = message
+        .photo()
+        .and_then(|sizes| sizes.iter().max_by_key(|p| p.width * p.height))
+    {
+        attachments.push(attachment(
+            Some("image/jpeg".to_string()),
+            None,
+            photo.file.size,
+        ));
+    }
+    if let Some(doc) = message.document() {
+        attachments.push(attachment(
+            doc.mime_type.as_ref().map(|m| m.to_string()),
+            doc.file_name.clone(),
+            doc.file.size,
+        ));
+    }
+    if let Some(voice) = message.voice() {
+        let mime = voice.mime_type.as_ref().map(|m| m.to_string());
+        attachments.push(attachment(
+            mime.or_else(|| Some("audio/ogg".to_string())),
+            None,
+            voice.file.size,
+        ));
+    }
+    if let Some(audio) = message.audio() {
+        attachments.push(attachment(
+            audio.mime_type.as_ref().map(|m| m.to_string()),
+            audio.file_name.clone(),
+            audio.file.size,
+        ));
+    }
+    if let Some(video) = message.video() {
+        attachments.push(attachment(
+            video.mime_type.as_ref().map(|m| m.to_string()),
+            video.file_name.clone(),
+            video.file.size,
+        ));
+    }
+    attachments
+}
+
+/// File id of a voice note or audio file.
+fn voice_file_id(message: &Message) -> Option<&str> {
+    message
+        .voice()
+        .map(|voice| &voice.file)
+        .or_else(|| message.audio().map(|audio| &audio.file))
+        .map(|file| file.id.as_str())
+}
+
+/// Download a file by id through `getFile`.
+async fn download_file(bot: &Bot, file_id: &str) -> Result<Vec<u8>> {
+    let file = bot.get_file(file_id).await?;
+    let mut data = Vec::new();
+    bot.download_file(&file.path, &mut data).await?;
+    Ok(data)
+}
+
+/// Thread to reply into: the message's forum topic, except the General topic.
+fn outbound_thread(message: &Message) -> Option<ThreadId> {
+    message
+        .thread_id
+        .filter(|t| message.is_topic_message && t.0 .0 != GENERAL_TOPIC_ID)
+}
+
+/// Parse an outbound target: `<chat_id>`, `@channel`, or `<chat_id>:<thread_id>`.
+fn parse_target(to: &str) -> Result<(Recipient, Option<ThreadId>)> {
+    let (chat, thread) = match to.rsplit_once(':') {
+        Some((chat, thread)) => (chat, Some(thread)),
+        None => (to, None),
+    };
+
+    let recipient = if chat.starts_with('@') {
+        Recipient::ChannelUsername(chat.to_string())
+    } else {
+        let id: i64 = chat
+            .parse()
+            .map_err(|_| anyhow::anyhow!("invalid Telegram chat_id: {to}"))?;
+        Recipient::Id(ChatId(id))
+    };
+
+    let thread = match thread {
+        Some(t) => {
+            let id: i32 = t
+                .parse()
+                .map_err(|_| anyhow::anyhow!("invalid Telegram thread id: {to}"))?;
+            (id != GENERAL_TOPIC_ID).then_some(ThreadId(MessageId(id)))
+        }
+        None => None,
+    };
+
+    Ok((recipient, thread))
+}
+
+fn now_ms() -> u64 {
+    chrono::Utc::now().timestamp_millis() as u64
+}
+
+// ============================================================================
+// HTML Formatting
+// ============================================================================
+
+/// Strip tags and entities from rendered HTML for the plain-text fallback.
+fn html_to_plain(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for ch in html.chars() {
+        match ch {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => out.push(ch),
+            _ => {}
+        }
+    }
+    out.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::channels::{Admission, Verdict};
+    use crate::config::{
+        DmPolicy, TelegramCustomCommand, TelegramGroupConfig, TelegramTopicConfig,
+    };
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
 
     #[test]
     fn inline_keyboard_single_row() {
@@ -422,4 +1258,383 @@ mod tests {
         assert_eq!(json["command"], "help");
         assert_eq!(json["description"], "Show help message");
     }
+
+    #[test]
+    fn command_menu_appends_valid_custom_commands() {
+        let mut config = Config::default();
+        config.channels.telegram.default_account.custom_commands = Some(vec![
+            TelegramCustomCommand {
+                command: "/Weather".to_string(),
+                description: "Today's forecast".to_string(),
+            },
+            TelegramCustomCommand {
+                command: "bad-name".to_string(),
+                description: "Dashes are not allowed".to_string(),
+            },
+            TelegramCustomCommand {
+                command: "new".to_string(),
+                description: "Shadows a built-in".to_string(),
+            },
+        ]);
+        let menu = TelegramChannel::new(&config).command_menu();
+        let names: Vec<&str> = menu.iter().map(|c| c.command.as_str()).collect();
+        assert_eq!(
+            names,
+            ["new", "model", "think", "status", "stop", "compact", "help", "weather"]
+        );
+    }
+
+    fn identity() -> BotIdentity {
+        BotIdentity {
+            id: 999,
+            username: Some("lobster_bot".to_string()),
+        }
+    }
+
+    fn parse_message(value: serde_json::Value) -> Message {
+        serde_json::from_value(value).expect("valid Telegram message")
+    }
+
+    fn dm_message(text: &str) -> Message {
+        parse_message(serde_json::json!({
+            "message_id": 10,
+            "date": 1_700_000_000,
+            "chat": {"id": 42, "type": "private", "first_name": "Ann"},
+            "from": {"id": 42, "is_bot": false, "first_name": "Ann", "username": "ann"},
+            "text": text
+        }))
+    }
+
+    fn topic_message(text: &str, entities: serde_json::Value) -> Message {
+        parse_message(serde_json::json!({
+            "message_id": 11,
+            "date": 1_700_000_000,
+            "chat": {"id": -1001, "type": "supergroup", "title": "Dev", "is_forum": true},
+            "from": {"id": 42, "is_bot": false, "first_name": "Ann", "last_name": "Lee"},
+            "message_thread_id": 7,
+            "is_topic_message": true,
+            "text": text,
+            "entities": entities
+        }))
+    }
+
+    fn channel_with(
+        api_url: &str,
+        configure: impl FnOnce(&mut TelegramAccountConfig),
+    ) -> TelegramChannel {
+        let mut config = Config::default();
+        config.channels.telegram.default_account.bot_token = Some("123:abc".to_string());
+        config.channels.telegram.default_account.api_url = Some(api_url.to_string());
+        configure(&mut config.channels.telegram.default_account);
+        TelegramChannel::new(&config)
+    }
+
+    #[test]
+    fn normalize_dm_message() {
+        let msg = normalize_message("default", &dm_message("hello there"), Some(&identity()))
+            .expect("normalized");
+        assert_eq!(msg.channel, "telegram");
+        assert_eq!(msg.chat_type, ChatType::Dm);
+        assert_eq!(msg.chat_id, "42");
+        assert_eq!(msg.sender.id, "42");
+        assert_eq!(msg.sender.name, "Ann");
+        assert_eq!(msg.text, "hello there");
+        assert!(msg.thread_id.is_none());
+        assert!(msg.raw.is_some());
+    }
+
+    #[test]
+    fn normalize_topic_message_strips_mention() {
+        let message = topic_message(
+            "@lobster_bot what's up?",
+            serde_json::json!([{"type": "mention", "offset": 0, "length": 12}]),
+        );
+        let msg = normalize_message("default", &message, Some(&identity())).expect("normalized");
+        assert_eq!(msg.chat_type, ChatType::Thread);
+        assert_eq!(msg.thread_id.as_deref(), Some("7"));
+        assert_eq!(msg.chat_name.as_deref(), Some("Dev"));
+        assert_eq!(msg.sender.name, "Ann Lee");
+        assert!(msg.mentioned);
+        assert_eq!(msg.text, "what's up?");
+        assert_eq!(outbound_thread(&message), Some(ThreadId(MessageId(7))));
+    }
+
+    #[test]
+    fn normalize_ignores_other_mentions_and_own_messages() {
+        let message = topic_message(
+            "@someone_else hi",
+            serde_json::json!([{"type": "mention", "offset": 0, "length": 13}]),
+        );
+        let msg = normalize_message("default", &message, Some(&identity())).expect("normalized");
+        assert!(!msg.mentioned);
+        assert_eq!(msg.text, "@someone_else hi");
+
+        let own = BotIdentity {
+            id: 42,
+            username: None,
+        };
+        assert!(normalize_message("default", &dm_message("echo"), Some(&own)).is_none());
+    }
+
+    #[test]
+    fn group_requires_mention_by_default() {
+        let channel = channel_with("http://localhost", |_| {});
+        let message = topic_message("hello", serde_json::json!([]));
+        let msg = normalize_message("default", &message, Some(&identity())).unwrap();
+        assert!(!channel.account.admit(&msg));
+
+        let mut mentioned = msg.clone();
+        mentioned.mentioned = true;
+        assert!(channel.account.admit(&mentioned));
+    }
+
+    #[test]
+    fn group_topic_config_overrides_group() {
+        let channel = channel_with("http://localhost", |account| {
+            let topic = TelegramTopicConfig {
+                require_mention: Some(false),
+                ..Default::default()
+            };
+            let group = TelegramGroupConfig {
+                topics: Some([("7".to_string(), topic)].into_iter().collect()),
+                ..Default::default()
+            };
+            account.groups = Some([("-1001".to_string(), group)].into_iter().collect());
+            account.group_policy = Some(GroupPolicy::Allowlist);
+        });
+        let message = topic_message("hello", serde_json::json!([]));
+        let msg = normalize_message("default", &message, Some(&identity())).unwrap();
+        assert!(channel.account.admit(&msg));
+
+        let mut other_group = msg.clone();
+        other_group.chat_id = "-2002".to_string();
+        other_group.mentioned = true;
+        assert!(!channel.account.admit(&other_group));
+    }
+
+    #[test]
+    fn group_allowlist_does_not_inherit_allow_from() {
+        let channel = channel_with("http://localhost", |account| {
+            account.allow_from = Some(vec!["42".to_string()]);
+            account.group_allow_from = Some(vec!["7".to_string()]);
+        });
+        let message = topic_message("hello", serde_json::json!([]));
+        let mut msg = normalize_message("default", &message, Some(&identity())).unwrap();
+        msg.mentioned = true;
+        assert!(!channel.account.admit(&msg));
+
+        msg.sender.id = "7".to_string();
+        assert!(channel.account.admit(&msg));
+    }
+
+    #[test]
+    fn dm_policy_and_allowlist() {
+        let mut msg = normalize_message("default", &dm_message("hi"), None).unwrap();
+        let admission = Admission::new();
+        let mut config = Config::default();
+        config.channels.telegram.default_account.allow_from = Some(vec![msg.sender.id.clone()]);
+
+        assert_eq!(admission.check(&config, &msg), Verdict::Admit);
+        msg.sender.id = "43".to_string();
+        assert_ne!(admission.check(&config, &msg), Verdict::Admit);
+
+        config.channels.telegram.default_account.allow_from = Some(vec!["43".to_string()]);
+        config.channels.telegram.default_account.dm_policy = Some(DmPolicy::Disabled);
+        assert_ne!(admission.check(&config, &msg), Verdict::Admit);
+    }
+
+    #[test]
+    fn html_chunks_respect_limit() {
+        let paragraph = "word ".repeat(200);
+        let text = [paragraph.trim(); 6].join("\n\n");
+        let chunks = render_markdown_chunks(
+            &text,
+            FormatTarget::TelegramHtml,
+            TELEGRAM_MAX_MESSAGE_CHARS,
+        );
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks
+            .iter()
+            .all(|c| c.chars().count() <= TELEGRAM_MAX_MESSAGE_CHARS));
+
+        // Escaping grows the text; chunks are measured after rendering.
+        let chunks = render_markdown_chunks(&"a&b ".repeat(50), FormatTarget::TelegramHtml, 60);
+        assert!(chunks.len() > 4);
+        assert!(chunks.iter().all(|c| c.chars().count() <= 60));
+        assert_eq!(
+            html_to_plain(&chunks.concat()).replace(' ', ""),
+            "a&b".repeat(50)
+        );
+    }
+
+    #[test]
+    fn parse_target_variants() {
+        let (chat, thread) = parse_target("-1001").unwrap();
+        assert_eq!(chat, Recipient::Id(ChatId(-1001)));
+        assert!(thread.is_none());
+
+        let (_, thread) = parse_target("-1001:7").unwrap();
+        assert_eq!(thread, Some(ThreadId(MessageId(7))));
+
+        let (chat, _) = parse_target("@news").unwrap();
+        assert_eq!(chat, Recipient::ChannelUsername("@news".to_string()));
+
+        assert!(parse_target("not-a-chat").is_err());
+    }
+
+    #[test]
+    fn webhook_secret_is_checked() {
+        let channel = channel_with("http://localhost", |account| {
+            account.webhook_secret = Some("s3cret".to_string());
+        });
+        let mut request = WebhookRequest {
+            method: Method::POST,
+            path: "webhook".to_string(),
+            query: None,
+            headers: axum::http::HeaderMap::new(),
+            body: Default::default(),
+        };
+        assert!(!channel.account.webhook_authorized(&request));
+
+        request
+            .headers
+            .insert(SECRET_TOKEN_HEADER, "s3cret".parse().unwrap());
+        assert!(channel.account.webhook_authorized(&request));
+        assert_eq!(channel.account.webhook_path(), "webhook");
+    }
+
+    #[tokio::test]
+    async fn unsigned_webhook_updates_are_refused() {
+        let update = serde_json::json!({ "update_id": 1, "message": dm_message("hi") });
+        let request = || WebhookRequest {
+            method: Method::POST,
+            path: "webhook".to_string(),
+            query: None,
+            headers: axum::http::HeaderMap::new(),
+            body: update.to_string().into_bytes().into(),
+        };
+        let state = GatewayState::for_test(Config::default());
+
+        // Polling mode mounts no route at all.
+        let polling = channel_with("http://localhost", |_| {});
+        assert!(polling.webhook_routes().is_empty());
+        let response = polling.handle_webhook(&state, request()).await.unwrap();
+        assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+        // Webhook mode without a secret refuses everything.
+        let unsecured = channel_with("http://localhost", |account| {
+            account.webhook_url = Some("https://bot.example.com/channels/telegram/webhook".into());
+        });
+        assert!(!unsecured.account.webhook_authorized(&request()));
+        let response = unsecured.handle_webhook(&state, request()).await.unwrap();
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    }
+
+    fn sent_message_response() -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "ok": true,
+            "result": {
+                "message_id": 100,
+                "date": 1_700_000_000,
+                "chat": {"id": 42, "type": "private", "first_name": "Ann"},
+                "text": "ok"
+            }
+        }))
+    }
+
+    #[tokio::test]
+    async fn send_message_uses_html_against_mock_api() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/bot123:abc/SendMessage"))
+            .and(body_partial_json(serde_json::json!({
+                "chat_id": 42,
+                "text": "<b>hi</b>",
+                "parse_mode": "HTML"
+            })))
+            .respond_with(sent_message_response())
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let channel = channel_with(&server.uri(), |_| {});
+        channel.send_message("42", "**hi**").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn long_message_is_split_against_mock_api() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/bot123:abc/SendMessage"))
+            .respond_with(sent_message_response())
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let channel = channel_with(&server.uri(), |_| {});
+        let text = format!("{}\n\n{}", "a".repeat(3000), "b".repeat(3000));
+        channel.send_message("42:7", &text).await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body["message_thread_id"], 7);
+    }
+
+    #[tokio::test]
+    async fn voice_notes_use_send_voice_against_mock_api() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/bot123:abc/SendVoice"))
+            .respond_with(sent_message_response())
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let channel = channel_with(&server.uri(), |_| {});
+        let mut voice = NormalizedOutbound::text("42", "");
+        voice.reply_to_id = Some("5".to_string());
+        voice.attachments.push(NormalizedAttachment {
+            mime_type: Some("audio/ogg".to_string()),
+            url: None,
+            data: Some(b"OggS".to_vec()),
+            filename: Some("voice.ogg".to_string()),
+            size: Some(4),
+        });
+        ChannelPlugin::send(&channel, &voice).await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 1);
+        let body = String::from_utf8_lossy(&requests[0].body);
+        assert!(body.contains("filename=\"voice.ogg\""));
+
+        voice.attachments[0].mime_type = Some("image/png".to_string());
+        assert!(ChannelPlugin::send(&channel, &voice).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn react_sets_and_clears_the_reaction_against_mock_api() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/bot123:abc/SetMessageReaction"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "ok": true,
+                "result": true
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let channel = channel_with(&server.uri(), |_| {});
+        channel.react("42:7", "5", "👀").await.unwrap();
+        channel.react("42", "5", "").await.unwrap();
+        assert!(channel.react("42", "nope", "👀").await.is_err());
+
+        let requests = server.received_requests().await.unwrap();
+        let set: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(set["chat_id"], 42);
+        assert_eq!(set["message_id"], 5);
+        assert_eq!(set["reaction"][0]["emoji"], "👀");
+        let cleared: serde_json::Value = serde_json::from_slice(&requests[1].body).unwrap();
+        assert_eq!(cleared["reaction"], serde_json::json!([]));
+    }
 }
@@ -1,70 +1,421 @@
-use super::plugin::{ChannelCapability, ChannelMeta, ChannelPlugin};
+use super::group_history::{admissible, admit_group_message};
+use super::inbound::dispatch_inbound;
+use super::irc::{
+    connect_stream, read_line, split_for_irc, strip_nick_mention, IrcMessage, IrcStream,
+    TokenBucket, QUIT_TIMEOUT, READ_TIMEOUT, RECONNECT_INITIAL_BACKOFF, RECONNECT_MAX_BACKOFF,
+    RECONNECT_RESET_AFTER,
+};
+use super::normalize::{ChatType, NormalizedMessage, NormalizedOutbound, NormalizedSender};
+use super::plugin::{unsupported, ChannelCapability, ChannelMeta, ChannelPlugin};
+use crate::config::{Config, TwitchConfig};
 use crate::gateway::GatewayState;
+use crate::infra::abort_signal::{monitor_with_abort_lifecycle, AbortHandle};
+use crate::infra::dm_policy;
 
-use anyhow::Result;
+use anyhow::{bail, Context as _, Result};
 use async_trait::async_trait;
+use parking_lot::{Mutex, RwLock};
+use std::collections::HashSet;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tracing::{info, warn};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
 
 // ============================================================================
 // Twitch Channel Implementation
 // ============================================================================
 
+/// Twitch IRC (TMI) server address.
+const TWITCH_IRC_HOST: &str = "irc.chat.twitch.tv";
+/// Twitch IRC TLS port.
+const TWITCH_IRC_PORT: u16 = 6697;
+/// Twitch IRC plaintext port.
+const TWITCH_IRC_PLAIN_PORT: u16 = 6667;
+const DEFAULT_NICK: &str = "mylobster";
+/// Twitch rejects chat messages longer than 500 characters; splitting by
+/// bytes keeps every chunk under that.
+const TWITCH_MAX_MESSAGE_BYTES: usize = 500;
+/// Capabilities requested on connect.
+const TWITCH_CAPABILITIES: &str = "twitch.tv/tags twitch.tv/commands";
+/// Regular accounts may send 20 messages per 30 seconds.
+const REGULAR_BURST: u32 = 20;
+const REGULAR_INTERVAL: Duration = Duration::from_millis(1500);
+/// Moderators and broadcasters may send 100 messages per 30 seconds.
+const MODERATOR_BURST: u32 = 100;
+const MODERATOR_INTERVAL: Duration = Duration::from_millis(300);
+
+/// Rate limit bucket an outbound line is charged against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RateClass {
+    Regular,
+    Moderator,
+}
+
+/// A line queued for the writer; `rate` is `None` for protocol traffic.
+struct OutboundLine {
+    line: String,
+    rate: Option<RateClass>,
+}
+
+impl OutboundLine {
+    fn protocol(line: impl Into<String>) -> Self {
+        Self {
+            line: line.into(),
+            rate: None,
+        }
+    }
+}
+
+/// Connection state for the Twitch chat account.
+struct TwitchClient {
+    config: TwitchConfig,
+    /// Bot login name (lowercase).
+    nick: String,
+    /// Queue into the writer of the active connection.
+    outbound: Mutex<Option<mpsc::UnboundedSender<OutboundLine>>>,
+    /// Channels (without `#`) where the bot is a moderator or broadcaster.
+    moderator_in: RwLock<HashSet<String>>,
+    /// Whether the connection is registered (RPL_WELCOME received).
+    connected: AtomicBool,
+    /// Set by `stop_account` so the reconnect loop exits.
+    stopping: AtomicBool,
+}
+
 /// Twitch chat channel integration via IRC (TMI).
 ///
 /// Twitch chat uses an IRC-compatible protocol at `irc.chat.twitch.tv:6697`
 /// (TLS). Authentication is via OAuth token. Messages are standard
-/// PRIVMSG commands to Twitch channels (prefixed with `#`).
+/// PRIVMSG commands to Twitch channels (prefixed with `#`), with IRCv3 tags
+/// carrying user metadata (display name, badges, moderator status) and
+/// reply threading.
 ///
 /// This is a non-REST channel — it requires a persistent IRC/TMI connection.
 /// `send_message` will return an error if the connection is not active.
 pub struct TwitchChannel {
-    /// OAuth token for Twitch IRC (format: `oauth:xxxxx`).
-    oauth_token: Option<String>,
-    /// Bot nickname (Twitch username, lowercase).
-    nick: Option<String>,
-    /// List of Twitch channels to join (without `#` prefix).
-    channels: Option<Vec<String>>,
-    /// Whether this channel is enabled.
-    enabled: Option<bool>,
-    /// Whether the Twitch IRC connection is currently active.
-    connected: Arc<AtomicBool>,
+    enabled: bool,
+    client: Arc<TwitchClient>,
+    /// Abort handle and task of the connection loop, if running.
+    task: Mutex<Option<(AbortHandle, tokio::task::JoinHandle<()>)>>,
 }
 
-/// Twitch IRC (TMI) server address.
-const TWITCH_IRC_HOST: &str = "irc.chat.twitch.tv";
-/// Twitch IRC TLS port.
-const TWITCH_IRC_PORT: u16 = 6697;
-
 impl TwitchChannel {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
+        let config = config.channels.twitch.clone().unwrap_or_default();
+        let enabled = config.enabled.unwrap_or(false);
+        let nick = config
+            .nickname
+            .as_deref()
+            .unwrap_or(DEFAULT_NICK)
+            .to_ascii_lowercase();
         Self {
-            oauth_token: None,
-            nick: None,
-            channels: None,
-            enabled: None,
-            connected: Arc::new(AtomicBool::new(false)),
+            enabled,
+            client: Arc::new(TwitchClient {
+                config,
+                nick,
+                outbound: Mutex::new(None),
+                moderator_in: RwLock::new(HashSet::new()),
+                connected: AtomicBool::new(false),
+                stopping: AtomicBool::new(false),
+            }),
+            task: Mutex::new(None),
         }
     }
+}
 
-    /// Create a configured Twitch channel.
-    pub fn with_config(
-        oauth_token: String,
-        nick: String,
-        channels: Vec<String>,
-    ) -> Self {
-        Self {
-            oauth_token: Some(oauth_token),
-            nick: Some(nick),
-            channels: Some(channels),
-            enabled: Some(true),
-            connected: Arc::new(AtomicBool::new(false)),
+impl TwitchClient {
+    fn host(&self) -> &str {
+        self.config.server.as_deref().unwrap_or(TWITCH_IRC_HOST)
+    }
+
+    fn use_tls(&self) -> bool {
+        self.config.tls.unwrap_or(true)
+    }
+
+    fn port(&self) -> u16 {
+        self.config.port.unwrap_or(if self.use_tls() {
+            TWITCH_IRC_PORT
+        } else {
+            TWITCH_IRC_PLAIN_PORT
+        })
+    }
+
+    /// The `PASS` value: the configured token with an `oauth:` prefix.
+    fn oauth_password(&self) -> Option<String> {
+        let token = self.config.oauth_token.as_deref()?.trim();
+        if token.is_empty() {
+            return None;
+        }
+        Some(match token.strip_prefix("oauth:") {
+            Some(_) => token.to_string(),
+            None => format!("oauth:{token}"),
+        })
+    }
+
+    fn channels(&self) -> Vec<String> {
+        self.config
+            .channels
+            .iter()
+            .flatten()
+            .map(|c| channel_login(c))
+            .filter(|c| !c.is_empty())
+            .collect()
+    }
+
+    async fn connect(&self) -> Result<Box<dyn IrcStream>> {
+        connect_stream(self.host(), self.port(), self.use_tls()).await
+    }
+
+    /// Connect and run sessions until stopped, reconnecting with backoff.
+    async fn run(self: Arc<Self>, inbound: mpsc::UnboundedSender<NormalizedMessage>) {
+        let mut backoff = RECONNECT_INITIAL_BACKOFF;
+        loop {
+            let started = Instant::now();
+            let result = match self.connect().await {
+                Ok(stream) => self.session(stream, &inbound).await,
+                Err(e) => Err(e),
+            };
+            self.connected.store(false, Ordering::Relaxed);
+            self.outbound.lock().take();
+            self.moderator_in.write().clear();
+
+            if self.stopping.load(Ordering::Relaxed) {
+                return;
+            }
+            if started.elapsed() >= RECONNECT_RESET_AFTER {
+                backoff = RECONNECT_INITIAL_BACKOFF;
+            }
+            match result {
+                Ok(()) => info!("Twitch connection closed; reconnecting in {:?}", backoff),
+                Err(e) => {
+                    warn!(error = %e, "Twitch connection failed; reconnecting in {:?}", backoff)
+                }
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(RECONNECT_MAX_BACKOFF);
+        }
+    }
+
+    /// Authenticate and process one connection until it closes.
+    async fn session(
+        &self,
+        stream: impl IrcStream,
+        inbound: &mpsc::UnboundedSender<NormalizedMessage>,
+    ) -> Result<()> {
+        let Some(password) = self.oauth_password() else {
+            bail!("Twitch oauth token not configured");
+        };
+
+        let (reader, mut writer) = tokio::io::split(stream);
+        let (tx, mut rx) = mpsc::unbounded_channel::<OutboundLine>();
+        *self.outbound.lock() = Some(tx.clone());
+
+        let write_loop = async move {
+            let now = Instant::now();
+            let mut regular = TokenBucket::new(REGULAR_BURST, REGULAR_INTERVAL, now);
+            let mut moderator = TokenBucket::new(MODERATOR_BURST, MODERATOR_INTERVAL, now);
+            while let Some(OutboundLine { line, rate }) = rx.recv().await {
+                let wait = match rate {
+                    Some(RateClass::Regular) => regular.take(Instant::now()),
+                    Some(RateClass::Moderator) => moderator.take(Instant::now()),
+                    None => Duration::ZERO,
+                };
+                if !wait.is_zero() {
+                    tokio::time::sleep(wait).await;
+                }
+                writer.write_all(line.as_bytes()).await?;
+                writer.write_all(b"\r\n").await?;
+                writer.flush().await?;
+            }
+            Ok::<_, std::io::Error>(())
+        };
+
+        let _ = tx.send(OutboundLine::protocol(format!(
+            "CAP REQ :{TWITCH_CAPABILITIES}"
+        )));
+        let _ = tx.send(OutboundLine::protocol(format!("PASS {password}")));
+        let _ = tx.send(OutboundLine::protocol(format!("NICK {}", self.nick)));
+        let read_loop = self.read_loop(reader, &tx, inbound);
+
+        tokio::select! {
+            result = write_loop => result.context("Twitch write failed"),
+            result = read_loop => result,
+        }
+    }
+
+    async fn read_loop(
+        &self,
+        reader: impl AsyncRead + Unpin,
+        tx: &mpsc::UnboundedSender<OutboundLine>,
+        inbound: &mpsc::UnboundedSender<NormalizedMessage>,
+    ) -> Result<()> {
+        let mut reader = BufReader::new(reader);
+        let mut buf = Vec::new();
+        loop {
+            let read = tokio::time::timeout(READ_TIMEOUT, read_line(&mut reader, &mut buf))
+                .await
+                .context("Twitch ping timeout")??;
+            if read == 0 {
+                return Ok(());
+            }
+            let line = String::from_utf8_lossy(&buf);
+            let Some(message) = IrcMessage::parse(&line) else {
+                continue;
+            };
+            if !self.handle_line(&message, tx, inbound)? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// React to one server line; returns `false` when the server asks us
+    /// to reconnect.
+    fn handle_line(
+        &self,
+        message: &IrcMessage,
+        tx: &mpsc::UnboundedSender<OutboundLine>,
+        inbound: &mpsc::UnboundedSender<NormalizedMessage>,
+    ) -> Result<bool> {
+        match message.command.as_str() {
+            "PING" => {
+                let _ = tx.send(OutboundLine::protocol(format!(
+                    "PONG :{}",
+                    message.param(0).unwrap_or_default()
+                )));
+            }
+            "CAP" if message.param(1) == Some("NAK") => {
+                warn!("Twitch rejected tags/commands capabilities; sender metadata unavailable");
+            }
+            // RPL_WELCOME
+            "001" => {
+                self.connected.store(true, Ordering::Relaxed);
+                info!(nick = %self.nick, "Twitch chat connected");
+                let channels = self.channels();
+                if !channels.is_empty() {
+                    let joined: Vec<String> = channels.iter().map(|c| format!("#{c}")).collect();
+                    let _ = tx.send(OutboundLine::protocol(format!("JOIN {}", joined.join(","))));
+                }
+            }
+            "USERSTATE" => {
+                if let Some(channel) = message.param(0) {
+                    let channel = channel_login(channel);
+                    let is_moderator = is_moderator(message);
+                    let mut moderator_in = self.moderator_in.write();
+                    if is_moderator {
+                        moderator_in.insert(channel);
+                    } else {
+                        moderator_in.remove(&channel);
+                    }
+                }
+            }
+            "NOTICE" => {
+                let text = message.param(1).unwrap_or_default();
+                if message.param(0) == Some("*")
+                    && (text.contains("Login authentication failed")
+                        || text.contains("Improperly formatted auth"))
+                {
+                    bail!("Twitch authentication failed: {text}");
+                }
+                match message.tag("msg-id") {
+                    Some(id @ ("msg_ratelimit" | "msg_duplicate" | "msg_banned")) => {
+                        warn!(msg_id = id, channel = ?message.param(0), "Twitch rejected message: {text}");
+                    }
+                    id => debug!(msg_id = ?id, "Twitch notice: {text}"),
+                }
+            }
+            "RECONNECT" => {
+                info!("Twitch requested reconnect");
+                return Ok(false);
+            }
+            "PRIVMSG" => {
+                if let Some(msg) = normalize_privmsg(message, &self.nick) {
+                    if admissible(&msg, |msg| self.admit(msg)) {
+                        let _ = inbound.send(msg);
+                    } else {
+                        debug!(chat = %msg.chat_id, sender = %msg.sender.id, "Twitch message not admitted");
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    /// Decide whether a chat message should reach the agent.
+    fn admit(&self, msg: &NormalizedMessage) -> bool {
+        let allow_from = self.config.allow_from.as_deref().unwrap_or_default();
+        let login = msg
+            .raw
+            .as_ref()
+            .and_then(|raw| raw["login"].as_str())
+            .unwrap_or_default();
+        if !dm_policy::is_source_allowed(allow_from, &msg.sender.id)
+            && !dm_policy::is_source_allowed(allow_from, login)
+        {
+            return false;
+        }
+        !self.config.require_mention.unwrap_or(true) || msg.mentioned
+    }
+
+    /// Queue `text` as PRIVMSG lines to `channel`, optionally as replies.
+    fn send_text(&self, channel: &str, text: &str, reply_to: Option<&str>) -> Result<()> {
+        let channel = channel_login(channel);
+        if channel.is_empty() || channel.contains([' ', '\r', '\n', ',']) {
+            bail!("invalid Twitch channel: {channel:?}");
+        }
+        if reply_to.is_some_and(|id| id.contains([' ', ';', '\r', '\n'])) {
+            bail!("invalid Twitch message id: {reply_to:?}");
+        }
+        let tx = self
+            .outbound
+            .lock()
+            .clone()
+            .filter(|_| self.connected.load(Ordering::Relaxed));
+        let Some(tx) = tx else {
+            bail!(
+                "Twitch: not connected — cannot send message to #{}",
+                channel
+            );
+        };
+
+        let rate = if self.moderator_in.read().contains(&channel) {
+            RateClass::Moderator
+        } else {
+            RateClass::Regular
+        };
+        for chunk in split_for_irc(text, TWITCH_MAX_MESSAGE_BYTES) {
+            let line = match reply_to {
+                Some(id) => format!("@reply-parent-msg-id={id} PRIVMSG #{channel} :{chunk}"),
+                None => format!("PRIVMSG #{channel} :{chunk}"),
+            };
+            tx.send(OutboundLine {
+                line,
+                rate: Some(rate),
+            })
+            .map_err(|_| anyhow::anyhow!("Twitch connection closed"))?;
         }
+        Ok(())
     }
 
-    fn is_enabled(&self) -> bool {
-        self.enabled.unwrap_or(false)
+    /// Run the agent for an admitted message and reply in-thread.
+    async fn reply(&self, state: &GatewayState, mut msg: NormalizedMessage) {
+        if !admit_group_message(state, &mut msg, |msg| self.admit(msg)).await {
+            return;
+        }
+        match dispatch_inbound(state, &msg).await {
+            Ok(Some(reply)) => {
+                let outbound = NormalizedOutbound {
+                    reply_to_id: Some(msg.id.clone()),
+                    ..NormalizedOutbound::text(msg.chat_id.clone(), reply)
+                };
+                if let Err(e) = state.channels.enqueue("twitch", outbound).await {
+                    warn!(channel = %msg.chat_id, error = %e, "Twitch reply failed");
+                }
+            }
+            Ok(None) => {}
+            Err(e) => warn!(channel = %msg.chat_id, error = %e, "Twitch agent run failed"),
+        }
     }
 }
 
@@ -78,7 +429,7 @@ impl ChannelPlugin for TwitchChannel {
         ChannelMeta {
             name: "Twitch".to_string(),
             description: "Twitch chat channel via IRC/TMI protocol".to_string(),
-            enabled: self.is_enabled(),
+            enabled: self.enabled,
             multi_account: false,
         }
     }
@@ -91,70 +442,409 @@ impl ChannelPlugin for TwitchChannel {
         ]
     }
 
-    async fn start_account(&self, _state: &GatewayState) -> Result<()> {
-        if !self.is_enabled() {
+    async fn start_account(&self, state: &GatewayState) -> Result<()> {
+        if !self.enabled {
             return Ok(());
         }
 
-        let oauth_token = match &self.oauth_token {
-            Some(t) => t,
-            None => {
-                warn!("Twitch channel enabled but no oauth_token configured");
-                return Ok(());
-            }
-        };
-
-        let nick = self.nick.as_deref().unwrap_or("mylobster");
-        let channels = self.channels.as_deref().unwrap_or(&[]);
+        if self.client.oauth_password().is_none() {
+            warn!("Twitch channel enabled but no oauth_token configured");
+            return Ok(());
+        }
 
         info!(
-            host = %TWITCH_IRC_HOST,
-            port = %TWITCH_IRC_PORT,
-            nick = %nick,
-            channels = ?channels,
-            token_suffix = %&oauth_token[oauth_token.len().saturating_sub(4)..],
-            "Twitch channel starting — would connect to TMI"
+            host = %self.client.host(),
+            port = %self.client.port(),
+            nick = %self.client.nick,
+            channels = ?self.client.channels(),
+            "Twitch channel starting"
         );
 
-        // TODO: Establish a TLS connection to irc.chat.twitch.tv:6697.
-        // 1. Send: PASS oauth:<token>
-        // 2. Send: NICK <nick>
-        // 3. Send: CAP REQ :twitch.tv/membership twitch.tv/tags twitch.tv/commands
-        // 4. JOIN each configured channel (prefixed with #).
-        // 5. Start a read loop to parse incoming IRC messages and PING/PONG.
-        //
-        // The connection lifecycle would be managed in a spawned task,
-        // setting `self.connected` to true once the welcome message (001) is received.
+        self.client.stopping.store(false, Ordering::Relaxed);
+        let abort = AbortHandle::new();
+        let task = {
+            let client = self.client.clone();
+            let state = state.clone();
+            let abort = abort.clone();
+            tokio::spawn(async move {
+                let (inbound_tx, mut inbound_rx) = mpsc::unbounded_channel();
+                let dispatch = {
+                    let client = client.clone();
+                    async move {
+                        while let Some(msg) = inbound_rx.recv().await {
+                            let client = client.clone();
+                            let state = state.clone();
+                            tokio::spawn(async move { client.reply(&state, msg).await });
+                        }
+                    }
+                };
+                let connection = async { tokio::join!(client.run(inbound_tx), dispatch) };
+                if monitor_with_abort_lifecycle(connection, &abort)
+                    .await
+                    .is_err()
+                {
+                    debug!("Twitch connection loop stopped");
+                }
+            })
+        };
+        if let Some((previous, _)) = self.task.lock().replace((abort, task)) {
+            previous.abort();
+        }
 
         Ok(())
     }
 
     async fn stop_account(&self) -> Result<()> {
-        if self.is_enabled() {
+        if self.enabled {
             info!("Twitch channel stopping");
-            self.connected.store(false, Ordering::Relaxed);
-            // TODO: Send QUIT and close the TLS connection.
+            self.client.stopping.store(true, Ordering::Relaxed);
+            if let Some(tx) = self.client.outbound.lock().clone() {
+                let _ = tx.send(OutboundLine::protocol("QUIT"));
+            }
+            let task = self.task.lock().take();
+            if let Some((abort, mut task)) = task {
+                if tokio::time::timeout(QUIT_TIMEOUT, &mut task).await.is_err() {
+                    abort.abort();
+                    let _ = task.await;
+                }
+            }
+            self.client.connected.store(false, Ordering::Relaxed);
         }
         Ok(())
     }
 
     async fn send_message(&self, to: &str, message: &str) -> Result<()> {
-        if !self.connected.load(Ordering::Relaxed) {
-            anyhow::bail!(
-                "Twitch: not connected — cannot send message to #{}",
-                to
-            );
-        }
+        self.send(&NormalizedOutbound::text(to, message)).await?;
+        Ok(())
+    }
 
+    async fn send(&self, message: &NormalizedOutbound) -> Result<Option<String>> {
+        if !message.attachments.is_empty() {
+            return Err(unsupported(self.id(), ChannelCapability::SendMedia));
+        }
+        let to = message.chat_id.as_str();
         info!(channel = %to, "Twitch: sending PRIVMSG");
 
-        // `to` is a Twitch channel name (without `#` prefix).
-        // The message would be sent as: PRIVMSG #<to> :<message>
-        //
-        // TODO: Write the PRIVMSG line to the active TLS stream.
-        // Twitch IRC has a 500-char message limit; split if needed.
-        let _ = message;
+        // `chat_id` is a Twitch channel name, optionally `channel:<message-id>`
+        // to reply to a specific chat message.
+        let (channel, reply_to) = match to.split_once(':') {
+            Some((channel, id)) if !id.is_empty() => (channel, Some(id)),
+            _ => (to, None),
+        };
+        let reply_to = message.reply_to_id.as_deref().or(reply_to);
+        self.client.send_text(channel, &message.text, reply_to)?;
+        Ok(None)
+    }
+}
 
-        Ok(())
+// ============================================================================
+// Message Handling
+// ============================================================================
+
+/// Channel login without the `#` prefix, lowercased.
+fn channel_login(channel: &str) -> String {
+    channel.trim().trim_start_matches('#').to_ascii_lowercase()
+}
+
+/// Whether the tags mark the user as a moderator or broadcaster.
+fn is_moderator(message: &IrcMessage) -> bool {
+    message.tag("mod") == Some("1")
+        || message.tag("badges").is_some_and(|badges| {
+            badges
+                .split(',')
+                .any(|b| b.starts_with("broadcaster/") || b.starts_with("moderator/"))
+        })
+}
+
+/// Sender roles from the `badges`/`badge-info`/`mod` tags.
+///
+/// Each badge becomes its name (`"vip"`); badges with extra detail in
+/// `badge-info` carry it after a slash (`"subscriber/14"` for months).
+fn sender_roles(message: &IrcMessage) -> Vec<String> {
+    let info: Vec<(&str, &str)> = message
+        .tag("badge-info")
+        .into_iter()
+        .flat_map(|tag| tag.split(','))
+        .filter_map(|entry| entry.split_once('/'))
+        .collect();
+    let mut roles: Vec<String> = message
+        .tag("badges")
+        .into_iter()
+        .flat_map(|tag| tag.split(','))
+        .filter_map(|badge| {
+            let name = badge.split('/').next().filter(|n| !n.is_empty())?;
+            Some(match info.iter().find(|(key, _)| *key == name) {
+                Some((_, detail)) => format!("{name}/{detail}"),
+                None => name.to_string(),
+            })
+        })
+        .collect();
+    if message.tag("mod") == Some("1") && !roles.iter().any(|r| r == "moderator") {
+        roles.push("moderator".to_string());
+    }
+    roles
+}
+
+/// Convert a chat PRIVMSG into a [`NormalizedMessage`].
+///
+/// Messages are reported against the channel login. A message counts as a
+/// mention when it addresses the bot (`@nick`) or replies to one of its
+/// messages. Returns `None` for our own messages.
+fn normalize_privmsg(message: &IrcMessage, own_nick: &str) -> Option<NormalizedMessage> {
+    if message.command != "PRIVMSG" {
+        return None;
+    }
+    let login = message.nick()?;
+    if login.eq_ignore_ascii_case(own_nick) {
+        return None;
+    }
+    let channel = channel_login(message.param(0)?.strip_prefix('#')?);
+    let body = message.param(1)?;
+
+    let display_name = message.tag("display-name").unwrap_or(login);
+    let body = match body.strip_prefix('\x01') {
+        Some(ctcp) => {
+            let action = ctcp.trim_end_matches('\x01').strip_prefix("ACTION ")?;
+            format!("* {display_name} {action}")
+        }
+        None => body.to_string(),
+    };
+
+    let replies_to_bot = message
+        .tag("reply-parent-user-login")
+        .is_some_and(|parent| parent.eq_ignore_ascii_case(own_nick));
+    let (mentioned, text) = strip_nick_mention(&body, own_nick);
+    if text.is_empty() {
+        return None;
+    }
+
+    let timestamp = message
+        .tag("tmi-sent-ts")
+        .and_then(|ts| ts.parse::<i64>().ok())
+        .and_then(chrono::DateTime::from_timestamp_millis)
+        .unwrap_or_else(chrono::Utc::now)
+        .to_rfc3339();
+
+    Some(NormalizedMessage {
+        id: message
+            .tag("id")
+            .map(str::to_string)
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+        channel: "twitch".to_string(),
+        account_id: "default".to_string(),
+        chat_id: channel.clone(),
+        chat_name: Some(format!("#{channel}")),
+        chat_type: ChatType::Group,
+        sender: NormalizedSender {
+            id: message.tag("user-id").unwrap_or(login).to_string(),
+            name: display_name.to_string(),
+            is_bot: false,
+            roles: sender_roles(message),
+        },
+        text,
+        attachments: Vec::new(),
+        reply_to_id: message.tag("reply-parent-msg-id").map(str::to_string),
+        thread_id: None,
+        mentioned: mentioned || replies_to_bot,
+        timestamp,
+        raw: Some(serde_json::json!({
+            "login": login,
+            "channel": channel,
+            "tags": message.tags,
+        })),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    const CHAT_LINE: &str = "@badge-info=subscriber/14;badges=moderator/1,subscriber/12;\
+        display-name=Alice_B;id=msg-1;mod=1;tmi-sent-ts=1700000000000;user-id=1234 \
+        :alice_b!alice_b@alice_b.tmi.twitch.tv PRIVMSG #streamer :@Lobster how's it going?";
+
+    fn client_with(configure: impl FnOnce(&mut TwitchConfig)) -> Arc<TwitchClient> {
+        let mut config = Config::default();
+        let mut twitch = TwitchConfig {
+            enabled: Some(true),
+            oauth_token: Some("abc123".to_string()),
+            nickname: Some("Lobster".to_string()),
+            channels: Some(vec!["#Streamer".to_string()]),
+            ..Default::default()
+        };
+        configure(&mut twitch);
+        config.channels.twitch = Some(twitch);
+        TwitchChannel::new(&config).client
+    }
+
+    #[test]
+    fn normalize_parses_tags_into_sender() {
+        let message = IrcMessage::parse(CHAT_LINE).unwrap();
+        let msg = normalize_privmsg(&message, "lobster").unwrap();
+        assert_eq!(msg.id, "msg-1");
+        assert_eq!(msg.chat_id, "streamer");
+        assert_eq!(msg.chat_type, ChatType::Group);
+        assert_eq!(msg.sender.id, "1234");
+        assert_eq!(msg.sender.name, "Alice_B");
+        assert_eq!(msg.sender.roles, vec!["moderator", "subscriber/14"]);
+        assert!(msg.mentioned);
+        assert_eq!(msg.text, "how's it going?");
+        assert_eq!(msg.timestamp, "2023-11-14T22:13:20+00:00");
+        assert!(is_moderator(&message));
+    }
+
+    #[test]
+    fn normalize_replies_and_own_messages() {
+        let reply = IrcMessage::parse(
+            "@display-name=Bob;reply-parent-msg-id=parent-9;reply-parent-user-login=lobster \
+             :bob!bob@bob.tmi.twitch.tv PRIVMSG #streamer :thanks!",
+        )
+        .unwrap();
+        let msg = normalize_privmsg(&reply, "lobster").unwrap();
+        assert!(msg.mentioned);
+        assert_eq!(msg.reply_to_id.as_deref(), Some("parent-9"));
+        assert!(msg.sender.roles.is_empty());
+
+        let chatter =
+            IrcMessage::parse(":bob!bob@bob.tmi.twitch.tv PRIVMSG #streamer :hi chat").unwrap();
+        assert!(!normalize_privmsg(&chatter, "lobster").unwrap().mentioned);
+
+        let own = IrcMessage::parse(":lobster!lobster@lobster.tmi.twitch.tv PRIVMSG #streamer :hi")
+            .unwrap();
+        assert!(normalize_privmsg(&own, "lobster").is_none());
+    }
+
+    #[test]
+    fn admission_and_token_handling() {
+        let client = client_with(|twitch| {
+            twitch.allow_from = Some(vec!["alice_b".to_string()]);
+        });
+        assert_eq!(client.oauth_password().as_deref(), Some("oauth:abc123"));
+        assert_eq!(client.channels(), vec!["streamer"]);
+        assert_eq!(client.port(), TWITCH_IRC_PORT);
+
+        let msg = normalize_privmsg(&IrcMessage::parse(CHAT_LINE).unwrap(), "lobster").unwrap();
+        assert!(client.admit(&msg));
+        let mut unaddressed = msg.clone();
+        unaddressed.mentioned = false;
+        assert!(!client.admit(&unaddressed));
+        let mut stranger = msg.clone();
+        stranger.sender.id = "999".to_string();
+        stranger.raw = Some(serde_json::json!({"login": "mallory"}));
+        assert!(!client.admit(&stranger));
+
+        let client = client_with(|twitch| {
+            twitch.oauth_token = Some("oauth:xyz".to_string());
+        });
+        assert_eq!(client.oauth_password().as_deref(), Some("oauth:xyz"));
+    }
+
+    #[test]
+    fn userstate_tracks_moderator_rate_class() {
+        let client = client_with(|_| {});
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let (inbound, _inbound_rx) = mpsc::unbounded_channel();
+        let userstate =
+            IrcMessage::parse("@badges=moderator/1;mod=1 :tmi.twitch.tv USERSTATE #streamer")
+                .unwrap();
+        assert!(client.handle_line(&userstate, &tx, &inbound).unwrap());
+        assert!(client.moderator_in.read().contains("streamer"));
+
+        let demoted =
+            IrcMessage::parse("@badges=;mod=0 :tmi.twitch.tv USERSTATE #streamer").unwrap();
+        client.handle_line(&demoted, &tx, &inbound).unwrap();
+        assert!(client.moderator_in.read().is_empty());
+
+        let reconnect = IrcMessage::parse(":tmi.twitch.tv RECONNECT").unwrap();
+        assert!(!client.handle_line(&reconnect, &tx, &inbound).unwrap());
+    }
+
+    async fn expect_line(
+        reader: &mut tokio::io::Lines<BufReader<tokio::net::tcp::OwnedReadHalf>>,
+        prefix: &str,
+    ) -> String {
+        loop {
+            let line = tokio::time::timeout(Duration::from_secs(5), reader.next_line())
+                .await
+                .expect("timed out waiting for client line")
+                .unwrap()
+                .expect("client closed");
+            if line.starts_with(prefix) {
+                return line;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn session_against_local_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let client = client_with(|twitch| {
+            twitch.server = Some("127.0.0.1".to_string());
+            twitch.port = Some(port);
+            twitch.tls = Some(false);
+        });
+
+        let (inbound_tx, mut inbound) = mpsc::unbounded_channel();
+        let session = {
+            let client = client.clone();
+            tokio::spawn(async move {
+                let stream = client.connect().await.unwrap();
+                client.session(stream, &inbound_tx).await
+            })
+        };
+        let (socket, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = socket.into_split();
+        let mut reader = BufReader::new(reader).lines();
+
+        assert_eq!(
+            expect_line(&mut reader, "CAP REQ").await,
+            "CAP REQ :twitch.tv/tags twitch.tv/commands"
+        );
+        assert_eq!(expect_line(&mut reader, "PASS").await, "PASS oauth:abc123");
+        assert_eq!(expect_line(&mut reader, "NICK").await, "NICK lobster");
+        writer
+            .write_all(b":tmi.twitch.tv 001 lobster :Welcome, GLHF!\r\n")
+            .await
+            .unwrap();
+        assert_eq!(expect_line(&mut reader, "JOIN").await, "JOIN #streamer");
+
+        writer
+            .write_all(format!("{CHAT_LINE}\r\nPING :tmi.twitch.tv\r\n").as_bytes())
+            .await
+            .unwrap();
+        assert_eq!(
+            expect_line(&mut reader, "PONG").await,
+            "PONG :tmi.twitch.tv"
+        );
+        let msg = inbound.recv().await.unwrap();
+        assert_eq!(msg.sender.name, "Alice_B");
+
+        client
+            .send_text("streamer", "hello there", Some(&msg.id))
+            .unwrap();
+        assert_eq!(
+            expect_line(&mut reader, "@reply").await,
+            "@reply-parent-msg-id=msg-1 PRIVMSG #streamer :hello there"
+        );
+
+        writer
+            .write_all(b":tmi.twitch.tv RECONNECT\r\n")
+            .await
+            .unwrap();
+        let result = tokio::time::timeout(Duration::from_secs(5), session)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn send_fails_when_not_connected() {
+        let client = client_with(|_| {});
+        let err = client.send_text("streamer", "hi", None).unwrap_err();
+        assert!(err.to_string().contains("not connected"));
     }
 }
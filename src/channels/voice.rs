@@ -0,0 +1,412 @@
+//! Voice notes: transcription of inbound audio and spoken replies.
+//!
+//! Audio attachments on channels that advertise [`ChannelCapability::Voice`]
+//! (Telegram, WhatsApp, Signal, Discord) are downloaded with
+//! [`ChannelPlugin::fetch_attachment`] and transcribed before the turn runs.
+//! `messages.audio.transcription.command` picks the transcriber: an argv
+//! whose `{{MediaPath}}` argument is replaced by the audio file (appended
+//! when absent) and whose stdout is the transcript. Without one, the OpenAI
+//! Whisper API is used when an OpenAI key is configured, else the local
+//! `whisper` CLI (see [`AudioProcessor::transcribe`]). The agent sees the
+//! transcript labeled as such, after the caption if there was one.
+//!
+//! `tts.auto` decides which replies are spoken:
+//!
+//! - `always`: every reply.
+//! - `inbound`: replies to voice notes.
+//! - `tagged`: replies containing a `[[tts]]` tag.
+//! - `off` (default): none.
+//!
+//! `[[tts]]` tags are always stripped from the reply. A spoken reply is
+//! synthesized with [`TtsManager`], converted to OGG/Opus with
+//! [`AudioProcessor::convert_audio`] and sent in place of the text as an
+//! `audio/ogg` attachment, which the channel delivers as a native voice
+//! note. When synthesis fails the text goes out instead.
+
+use super::format::{render_markdown, FormatTarget};
+use super::normalize::{NormalizedAttachment, NormalizedMessage, NormalizedOutbound};
+use super::plugin::{ChannelCapability, ChannelPlugin};
+use crate::config::{Config, TtsAutoMode, TtsConfig};
+use crate::gateway::GatewayState;
+use crate::media::AudioProcessor;
+use crate::tts::{ElevenLabsTtsProvider, TtsManager};
+
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Tag that asks for a spoken reply under `tts.auto: tagged`.
+const TTS_TAG: &str = "[[tts]]";
+
+/// Placeholder for the audio file in a transcription command.
+const MEDIA_PATH_PLACEHOLDER: &str = "{{MediaPath}}";
+
+/// Label put in front of a transcript in the user turn.
+const TRANSCRIPT_LABEL: &str = "[Voice message transcript]";
+
+/// User-turn text for a voice note that could not be transcribed.
+const UNTRANSCRIBED_NOTICE: &str = "[Voice message: transcription unavailable]";
+
+/// Largest audio file sent for transcription (the Whisper API limit).
+const MAX_TRANSCRIBE_BYTES: u64 = 25 * 1024 * 1024;
+
+const DEFAULT_TRANSCRIBE_TIMEOUT_SECS: u64 = 60;
+const DEFAULT_TTS_TIMEOUT_MS: u64 = 30_000;
+const DEFAULT_TTS_MAX_TEXT_LENGTH: u64 = 4_000;
+
+/// ElevenLabs voice used when `tts.elevenlabs.voiceId` is not set (Rachel).
+const DEFAULT_VOICE_ID: &str = "21m00Tcm4TlvDq8ikWAM";
+
+/// MIME type and file name of outbound voice notes.
+const VOICE_NOTE_MIME: &str = "audio/ogg";
+const VOICE_NOTE_FILENAME: &str = "voice.ogg";
+
+/// Whether `attachment` is audio.
+pub fn is_audio(attachment: &NormalizedAttachment) -> bool {
+    attachment
+        .mime_type
+        .as_deref()
+        .is_some_and(|mime| mime.starts_with("audio/"))
+}
+
+/// Whether `msg` carries a voice note (or other audio).
+fn has_voice(msg: &NormalizedMessage) -> bool {
+    msg.attachments.iter().any(is_audio)
+}
+
+// ============================================================================
+// Inbound Transcription
+// ============================================================================
+
+/// `msg` with its voice note transcribed into the text, or `None` when it
+/// has no audio or the channel does not handle voice.
+pub async fn transcribe_inbound(
+    state: &GatewayState,
+    config: &Config,
+    msg: &NormalizedMessage,
+) -> Option<NormalizedMessage> {
+    let attachment = msg.attachments.iter().find(|a| is_audio(a))?;
+    let plugin = state
+        .channels
+        .get_account(&msg.channel, &msg.account_id)
+        .await
+        .filter(|p| p.supports(ChannelCapability::Voice))?;
+
+    let transcript = match transcribe_attachment(config, plugin.as_ref(), attachment).await {
+        Ok(transcript) => Some(transcript),
+        Err(e) => {
+            warn!(channel = %msg.channel, message_id = %msg.id, error = %e, "Voice transcription failed");
+            None
+        }
+    };
+    Some(NormalizedMessage {
+        text: with_transcript(&msg.text, transcript.as_deref()),
+        ..msg.clone()
+    })
+}
+
+/// User-turn text for a voice note: the caption, then the labeled
+/// transcript.
+fn with_transcript(caption: &str, transcript: Option<&str>) -> String {
+    let voice = match transcript.map(str::trim) {
+        Some(transcript) if !transcript.is_empty() => format!("{TRANSCRIPT_LABEL} {transcript}"),
+        _ => UNTRANSCRIBED_NOTICE.to_string(),
+    };
+    match caption.trim() {
+        "" => voice,
+        caption => format!("{caption}\n\n{voice}"),
+    }
+}
+
+async fn transcribe_attachment(
+    config: &Config,
+    plugin: &dyn ChannelPlugin,
+    attachment: &NormalizedAttachment,
+) -> Result<String> {
+    if attachment
+        .size
+        .is_some_and(|size| size > MAX_TRANSCRIBE_BYTES)
+    {
+        bail!("voice note too large to transcribe");
+    }
+    let audio = plugin.fetch_attachment(attachment).await?;
+    transcribe_audio(config, &audio, attachment.mime_type.as_deref()).await
+}
+
+/// Transcribe audio bytes with the configured command or Whisper.
+async fn transcribe_audio(
+    config: &Config,
+    audio: &[u8],
+    mime_type: Option<&str>,
+) -> Result<String> {
+    let transcription = config
+        .messages
+        .audio
+        .as_ref()
+        .and_then(|a| a.transcription.as_ref());
+    let timeout = Duration::from_secs(
+        transcription
+            .and_then(|t| t.timeout_seconds)
+            .unwrap_or(DEFAULT_TRANSCRIBE_TIMEOUT_SECS),
+    );
+
+    let dir = tempfile::tempdir()?;
+    let path = dir
+        .path()
+        .join(format!("voice.{}", audio_extension(mime_type)));
+    tokio::fs::write(&path, audio).await?;
+
+    let command = transcription
+        .and_then(|t| t.command.as_deref())
+        .filter(|argv| !argv.is_empty());
+    let transcribe = async {
+        match command {
+            Some(argv) => run_command(argv, &path).await,
+            None => AudioProcessor::transcribe(&path, openai_api_key(config).as_deref()).await,
+        }
+    };
+    let transcript = tokio::time::timeout(timeout, transcribe)
+        .await
+        .context("voice transcription timed out")??;
+    debug!(chars = transcript.len(), "Voice note transcribed");
+    Ok(transcript.trim().to_string())
+}
+
+/// Run a transcription command, returning its stdout.
+async fn run_command(argv: &[String], path: &Path) -> Result<String> {
+    let path = path.to_string_lossy();
+    let mut args: Vec<String> = argv[1..]
+        .iter()
+        .map(|arg| arg.replace(MEDIA_PATH_PLACEHOLDER, &path))
+        .collect();
+    if !argv.iter().any(|arg| arg.contains(MEDIA_PATH_PLACEHOLDER)) {
+        args.push(path.to_string());
+    }
+    let output = tokio::process::Command::new(&argv[0])
+        .args(&args)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .with_context(|| format!("failed to run transcription command {}", argv[0]))?;
+    if !output.status.success() {
+        bail!(
+            "transcription command exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// OpenAI key for the Whisper API: the `openai` provider's, else
+/// `OPENAI_API_KEY`.
+fn openai_api_key(config: &Config) -> Option<String> {
+    config
+        .models
+        .providers
+        .get("openai")
+        .and_then(|p| p.api_key.clone())
+        .or_else(|| std::env::var("OPENAI_API_KEY").ok())
+        .filter(|key| !key.is_empty())
+}
+
+/// File extension for an audio MIME type; transcribers go by the name.
+fn audio_extension(mime_type: Option<&str>) -> &'static str {
+    let essence = mime_type
+        .and_then(|m| m.split(';').next())
+        .map(|m| m.trim().to_ascii_lowercase());
+    match essence.as_deref() {
+        Some("audio/mpeg" | "audio/mp3") => "mp3",
+        Some("audio/mp4" | "audio/m4a" | "audio/x-m4a" | "audio/aac") => "m4a",
+        Some("audio/wav" | "audio/x-wav" | "audio/wave") => "wav",
+        Some("audio/webm") => "webm",
+        Some("audio/flac") => "flac",
+        _ => "ogg",
+    }
+}
+
+// ============================================================================
+// Spoken Replies
+// ============================================================================
+
+/// Apply `tts.auto` to a reply to `msg`: strip `[[tts]]` tags and, when
+/// the reply should be spoken, replace its text with a voice note.
+pub async fn voice_reply(
+    state: &GatewayState,
+    msg: &NormalizedMessage,
+    outbound: NormalizedOutbound,
+) -> NormalizedOutbound {
+    let tts = state.config.read().await.tts.clone();
+    let (speak, text) = plan_reply(&tts, has_voice(msg), &outbound.text);
+    let outbound = NormalizedOutbound { text, ..outbound };
+    if !speak {
+        return outbound;
+    }
+    let voice_capable = state
+        .channels
+        .get_account(&msg.channel, &msg.account_id)
+        .await
+        .is_some_and(|p| p.supports(ChannelCapability::Voice));
+    if !voice_capable {
+        return outbound;
+    }
+
+    match synthesize_voice_note(&tts, &outbound.text).await {
+        Ok(audio) => NormalizedOutbound {
+            text: String::new(),
+            attachments: vec![NormalizedAttachment {
+                mime_type: Some(VOICE_NOTE_MIME.to_string()),
+                url: None,
+                size: Some(audio.len() as u64),
+                data: Some(audio),
+                filename: Some(VOICE_NOTE_FILENAME.to_string()),
+            }],
+            ..outbound
+        },
+        Err(e) => {
+            warn!(channel = %msg.channel, chat_id = %msg.chat_id, error = %e, "Voice reply failed, sending text");
+            outbound
+        }
+    }
+}
+
+/// Whether a reply should be spoken, and its text without `[[tts]]` tags.
+fn plan_reply(tts: &TtsConfig, inbound_voice: bool, reply: &str) -> (bool, String) {
+    let tagged = reply.contains(TTS_TAG);
+    let text = if tagged {
+        reply.replace(TTS_TAG, "").trim().to_string()
+    } else {
+        reply.to_string()
+    };
+    let speak = tts.enabled != Some(false)
+        && !text.is_empty()
+        && text.chars().count() as u64
+            <= tts.max_text_length.unwrap_or(DEFAULT_TTS_MAX_TEXT_LENGTH)
+        && match tts.auto.unwrap_or_default() {
+            TtsAutoMode::Always => true,
+            TtsAutoMode::Inbound => inbound_voice,
+            TtsAutoMode::Tagged => tagged,
+            TtsAutoMode::Off => false,
+        };
+    (speak, text)
+}
+
+/// Speak `text` and encode it as OGG/Opus.
+async fn synthesize_voice_note(tts: &TtsConfig, text: &str) -> Result<Vec<u8>> {
+    let elevenlabs = tts.elevenlabs.as_ref();
+    let manager = match elevenlabs
+        .and_then(|e| e.api_key.clone())
+        .filter(|key| !key.is_empty())
+    {
+        Some(key) => TtsManager::new(Box::new(ElevenLabsTtsProvider::new(key))),
+        None => TtsManager::from_env().await?,
+    };
+    let voice = elevenlabs
+        .and_then(|e| e.voice_id.as_deref())
+        .unwrap_or(DEFAULT_VOICE_ID);
+    let timeout = Duration::from_millis(tts.timeout_ms.unwrap_or(DEFAULT_TTS_TIMEOUT_MS));
+
+    let speech = render_markdown(text, FormatTarget::Plain);
+    let audio = tokio::time::timeout(timeout, manager.generate(&speech, voice))
+        .await
+        .context("speech synthesis timed out")??;
+
+    let dir = tempfile::tempdir()?;
+    let input = dir.path().join("speech");
+    let output = dir.path().join(VOICE_NOTE_FILENAME);
+    tokio::fs::write(&input, &audio).await?;
+    // The `opus` muxer writes Opus in an Ogg container.
+    AudioProcessor::convert_audio(&input, &output, "opus").await?;
+    Ok(tokio::fs::read(&output).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{AudioConfig, AudioTranscriptionConfig};
+
+    fn tts(auto: TtsAutoMode) -> TtsConfig {
+        TtsConfig {
+            auto: Some(auto),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn auto_mode_selects_spoken_replies() {
+        assert_eq!(
+            plan_reply(&tts(TtsAutoMode::Off), true, "hi"),
+            (false, "hi".into())
+        );
+        assert_eq!(
+            plan_reply(&tts(TtsAutoMode::Always), false, "hi"),
+            (true, "hi".into())
+        );
+        assert!(plan_reply(&tts(TtsAutoMode::Inbound), true, "hi").0);
+        assert!(!plan_reply(&tts(TtsAutoMode::Inbound), false, "hi").0);
+        assert!(!plan_reply(&tts(TtsAutoMode::Tagged), true, "hi").0);
+        assert_eq!(
+            plan_reply(&tts(TtsAutoMode::Tagged), false, "[[tts]] Hello there"),
+            (true, "Hello there".into())
+        );
+        // Tags are stripped even when the reply stays text.
+        assert_eq!(
+            plan_reply(&tts(TtsAutoMode::Off), false, "Hello [[tts]]"),
+            (false, "Hello".into())
+        );
+
+        let mut limited = tts(TtsAutoMode::Always);
+        limited.max_text_length = Some(3);
+        assert!(!plan_reply(&limited, false, "too long").0);
+        limited.enabled = Some(false);
+        assert!(!plan_reply(&limited, false, "hi").0);
+    }
+
+    #[test]
+    fn transcripts_are_labeled_after_the_caption() {
+        assert_eq!(
+            with_transcript("", Some(" turn on the lights ")),
+            "[Voice message transcript] turn on the lights"
+        );
+        assert_eq!(
+            with_transcript("for you", Some("hello")),
+            "for you\n\n[Voice message transcript] hello"
+        );
+        assert_eq!(with_transcript("", None), UNTRANSCRIBED_NOTICE);
+        assert_eq!(with_transcript("", Some("  ")), UNTRANSCRIBED_NOTICE);
+    }
+
+    #[test]
+    fn audio_extensions_follow_the_mime_type() {
+        assert_eq!(audio_extension(Some("audio/ogg; codecs=opus")), "ogg");
+        assert_eq!(audio_extension(Some("audio/mpeg")), "mp3");
+        assert_eq!(audio_extension(Some("audio/x-m4a")), "m4a");
+        assert_eq!(audio_extension(None), "ogg");
+    }
+
+    #[tokio::test]
+    async fn transcription_command_receives_the_media_path() {
+        let mut config = Config::default();
+        config.messages.audio = Some(AudioConfig {
+            transcription: Some(AudioTranscriptionConfig {
+                command: Some(vec!["cat".into(), MEDIA_PATH_PLACEHOLDER.into()]),
+                timeout_seconds: Some(5),
+            }),
+        });
+        let transcript = transcribe_audio(&config, b" hello world\n", Some("audio/ogg"))
+            .await
+            .unwrap();
+        assert_eq!(transcript, "hello world");
+
+        // Without the placeholder the path is appended.
+        config.messages.audio = Some(AudioConfig {
+            transcription: Some(AudioTranscriptionConfig {
+                command: Some(vec!["cat".into()]),
+                timeout_seconds: Some(5),
+            }),
+        });
+        let transcript = transcribe_audio(&config, b"appended", None).await.unwrap();
+        assert_eq!(transcript, "appended");
+    }
+}
@@ -1,12 +1,22 @@
+use super::dispatch_inbound;
+use super::normalize::{ChatType, NormalizedMessage, NormalizedSender};
 use super::plugin::{ChannelCapability, ChannelMeta, ChannelPlugin};
+use crate::config::{Config, WebChatConfig};
 use crate::gateway::GatewayState;
 
 use anyhow::Result;
 use async_trait::async_trait;
-use std::collections::HashMap;
+use axum::extract::ws::{Message, WebSocket};
+use axum::http::StatusCode;
+use futures::{SinkExt, StreamExt};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 
 // ============================================================================
 // WebChat Channel Implementation
@@ -18,89 +28,204 @@ use tracing::{info, warn};
 /// Unlike REST-based channels, WebChat runs entirely within the gateway
 /// process using axum's WebSocket support.
 ///
-/// Connected clients are tracked in memory. Messages can be sent to a
-/// specific client by their session ID, or broadcast to all connected
-/// clients.
+/// Browsers connect to `GET /ws/webchat?token=<visitor token>&since=<id>`.
+/// A visitor token is an HS256 JWT (`sub`, optional `name`, `exp`) signed by
+/// the embedding site with `visitorSecret`; without one, and unless
+/// `allowAnonymous` is false, the gateway issues an anonymous token that the
+/// widget keeps for reconnects. The widget itself is served at
+/// `/webchat/widget.js` (and a standalone page at `/webchat`).
+///
+/// Frames are JSON objects tagged by `type`:
+///
+/// - server: `session` (visitor, token, title, greeting), `history`
+///   (messages after `since`), `message`, `ack`, `typing`, `read`, `error`,
+///   `pong`
+/// - client: `message` (`text`, `clientId`), `typing`, `read` (`id`), `ping`
+///
+/// Messages are kept per visitor (`historyLimit`) so that a visitor with
+/// several tabs, or one that reconnects, sees the whole conversation.
 ///
 /// This channel does not require any external service or API keys.
 pub struct WebChatChannel {
-    /// Whether this channel is enabled.
-    enabled: Option<bool>,
-    /// Bind address for the WebChat endpoint (if separate from main gateway).
-    bind_address: Option<String>,
-    /// Port for the WebChat endpoint (if separate from main gateway).
-    port: Option<u16>,
-    /// Maximum number of concurrent WebSocket connections.
-    max_connections: Option<u32>,
-    /// Connected clients keyed by session ID.
+    enabled: bool,
+    config: WebChatConfig,
+    /// Connected clients keyed by connection ID.
     clients: Arc<RwLock<HashMap<String, WebChatClient>>>,
+    /// Conversation state keyed by visitor ID.
+    visitors: parking_lot::Mutex<HashMap<String, VisitorState>>,
+    /// Signs anonymous visitor tokens; regenerated on every start.
+    anonymous_key: [u8; 32],
 }
 
 /// Represents a connected WebChat client.
 struct WebChatClient {
-    /// Unique session identifier.
-    session_id: String,
+    /// Visitor the connection belongs to.
+    visitor_id: String,
     /// Sender half of the WebSocket channel for pushing messages.
     tx: tokio::sync::mpsc::UnboundedSender<String>,
 }
 
+/// Per-visitor history, read marker and rate-limit window.
+#[derive(Default)]
+struct VisitorState {
+    next_id: u64,
+    history: VecDeque<HistoryEntry>,
+    /// Highest message id the visitor has reported as read.
+    last_read: u64,
+    /// Send times within the last minute.
+    recent: Vec<Instant>,
+    updated: Option<Instant>,
+}
+
+/// A message in a visitor's conversation.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub id: u64,
+    /// `visitor` or `assistant`.
+    pub role: &'static str,
+    pub text: String,
+    pub timestamp: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+}
+
+/// An authenticated WebChat visitor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Visitor {
+    pub id: String,
+    pub name: Option<String>,
+    /// Set when the gateway issued the token, so the widget can store it.
+    pub issued_token: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VisitorClaims {
+    sub: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    exp: i64,
+}
+
+/// Frames sent by the widget.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ClientFrame {
+    Message {
+        text: String,
+        #[serde(rename = "clientId")]
+        client_id: Option<String>,
+    },
+    Typing {
+        #[serde(default)]
+        state: bool,
+    },
+    Read {
+        id: u64,
+    },
+    Ping,
+}
+
+/// Lifetime of gateway-issued anonymous tokens.
+const ANONYMOUS_TOKEN_TTL_SECS: i64 = 30 * 24 * 60 * 60;
+/// Visitors without activity for this long are forgotten.
+const VISITOR_IDLE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Longest message a visitor may send, in characters.
+const MAX_VISITOR_MESSAGE_CHARS: usize = 4000;
+
 impl WebChatChannel {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
+        let webchat = config.channels.webchat.clone().unwrap_or_default();
+        let mut anonymous_key = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut anonymous_key);
         Self {
-            enabled: None,
-            bind_address: None,
-            port: None,
-            max_connections: None,
+            enabled: webchat.enabled.unwrap_or(false),
+            config: webchat,
             clients: Arc::new(RwLock::new(HashMap::new())),
+            visitors: parking_lot::Mutex::new(HashMap::new()),
+            anonymous_key,
         }
     }
 
-    /// Create a configured WebChat channel.
-    pub fn with_config(bind_address: String, port: u16) -> Self {
-        Self {
-            enabled: Some(true),
-            bind_address: Some(bind_address),
-            port: Some(port),
-            max_connections: Some(100),
-            clients: Arc::new(RwLock::new(HashMap::new())),
-        }
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
     }
 
-    fn is_enabled(&self) -> bool {
-        self.enabled.unwrap_or(false)
+    fn max_connections(&self) -> usize {
+        self.config.max_connections.unwrap_or(100) as usize
+    }
+
+    /// Resolve the visitor for a connection attempt.
+    ///
+    /// Tokens signed with `visitorSecret` identify the visitor; tokens this
+    /// gateway issued resume an anonymous visitor. Anything else starts a
+    /// new anonymous visitor, or is rejected when anonymous access is off.
+    pub fn authenticate(&self, token: Option<&str>) -> Result<Visitor, StatusCode> {
+        if let Some(token) = token.filter(|t| !t.is_empty()) {
+            if let Some(secret) = &self.config.visitor_secret {
+                if let Some(claims) = verify_token(token, secret.as_bytes()) {
+                    return Ok(Visitor {
+                        id: claims.sub,
+                        name: claims.name,
+                        issued_token: None,
+                    });
+                }
+            }
+            if let Some(claims) = verify_token(token, &self.anonymous_key) {
+                return Ok(Visitor {
+                    id: claims.sub,
+                    name: None,
+                    issued_token: None,
+                });
+            }
+            debug!("WebChat: ignoring invalid or expired visitor token");
+        }
+
+        if !self.config.allow_anonymous.unwrap_or(true) {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        let claims = VisitorClaims {
+            sub: format!("anon-{}", uuid::Uuid::new_v4()),
+            name: None,
+            exp: chrono::Utc::now().timestamp() + ANONYMOUS_TOKEN_TTL_SECS,
+        };
+        let token = sign_token(&claims, &self.anonymous_key).map_err(|e| {
+            warn!(error = %e, "WebChat: failed to sign anonymous token");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        Ok(Visitor {
+            id: claims.sub,
+            name: None,
+            issued_token: Some(token),
+        })
     }
 
     /// Register a new WebChat client connection.
     pub async fn register_client(
         &self,
-        session_id: String,
+        connection_id: String,
+        visitor_id: String,
         tx: tokio::sync::mpsc::UnboundedSender<String>,
     ) -> Result<()> {
-        let max = self.max_connections.unwrap_or(100) as usize;
+        let max = self.max_connections();
         let mut clients = self.clients.write().await;
 
         if clients.len() >= max {
             anyhow::bail!(
                 "WebChat: max connections ({}) reached, rejecting client {}",
                 max,
-                session_id
+                connection_id
             );
         }
 
-        clients.insert(
-            session_id.clone(),
-            WebChatClient {
-                session_id,
-                tx,
-            },
-        );
+        clients.insert(connection_id, WebChatClient { visitor_id, tx });
 
         Ok(())
     }
 
     /// Remove a disconnected client.
-    pub async fn unregister_client(&self, session_id: &str) {
-        self.clients.write().await.remove(session_id);
+    pub async fn unregister_client(&self, connection_id: &str) {
+        self.clients.write().await.remove(connection_id);
     }
 
     /// Get the number of currently connected clients.
@@ -113,12 +238,303 @@ impl WebChatChannel {
         let clients = self.clients.read().await;
         for (id, client) in clients.iter() {
             if client.tx.send(message.to_string()).is_err() {
-                warn!(session_id = %id, "WebChat: failed to send to client (disconnected?)");
+                warn!(connection_id = %id, "WebChat: failed to send to client (disconnected?)");
             }
         }
     }
+
+    /// Send a frame to every connection of a visitor; returns how many
+    /// connections received it.
+    async fn send_to_visitor(&self, visitor_id: &str, frame: &Value) -> usize {
+        let payload = frame.to_string();
+        let clients = self.clients.read().await;
+        clients
+            .values()
+            .filter(|c| c.visitor_id == visitor_id)
+            .filter(|c| c.tx.send(payload.clone()).is_ok())
+            .count()
+    }
+
+    /// Append a message to a visitor's history.
+    fn record(
+        &self,
+        visitor_id: &str,
+        role: &'static str,
+        text: &str,
+        client_id: Option<String>,
+    ) -> HistoryEntry {
+        let limit = self.config.history_limit.unwrap_or(50).max(1);
+        let mut visitors = self.visitors.lock();
+        let state = self.visitor_state(&mut visitors, visitor_id);
+        state.next_id += 1;
+        let entry = HistoryEntry {
+            id: state.next_id,
+            role,
+            text: text.to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            client_id,
+        };
+        state.history.push_back(entry.clone());
+        while state.history.len() > limit {
+            state.history.pop_front();
+        }
+        entry
+    }
+
+    /// Messages after `since` (all retained messages when `None`).
+    pub fn history(&self, visitor_id: &str, since: Option<u64>) -> Vec<HistoryEntry> {
+        let visitors = self.visitors.lock();
+        visitors
+            .get(visitor_id)
+            .map(|state| {
+                state
+                    .history
+                    .iter()
+                    .filter(|e| since.map_or(true, |since| e.id > since))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Count a message against the visitor's per-minute budget.
+    fn within_rate_limit(&self, visitor_id: &str) -> bool {
+        let limit = self.config.rate_limit_per_minute.unwrap_or(10) as usize;
+        let now = Instant::now();
+        let mut visitors = self.visitors.lock();
+        let state = self.visitor_state(&mut visitors, visitor_id);
+        state
+            .recent
+            .retain(|t| now.duration_since(*t) < Duration::from_secs(60));
+        if state.recent.len() >= limit {
+            return false;
+        }
+        state.recent.push(now);
+        true
+    }
+
+    /// Get or create a visitor's state, forgetting idle visitors on creation.
+    fn visitor_state<'a>(
+        &self,
+        visitors: &'a mut HashMap<String, VisitorState>,
+        visitor_id: &str,
+    ) -> &'a mut VisitorState {
+        if !visitors.contains_key(visitor_id) {
+            visitors.retain(|_, s| s.updated.is_some_and(|t| t.elapsed() < VISITOR_IDLE_TTL));
+        }
+        let state = visitors.entry(visitor_id.to_string()).or_default();
+        state.updated = Some(Instant::now());
+        state
+    }
+
+    /// Run a WebSocket session for an authenticated visitor.
+    ///
+    /// Replays history after `since`, then relays visitor messages to the
+    /// agent until the socket closes.
+    pub async fn serve_socket(
+        self: Arc<Self>,
+        socket: WebSocket,
+        state: GatewayState,
+        visitor: Visitor,
+        since: Option<u64>,
+    ) {
+        let connection_id = uuid::Uuid::new_v4().to_string();
+        let (mut ws_tx, mut ws_rx) = socket.split();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+
+        if let Err(e) = self
+            .register_client(connection_id.clone(), visitor.id.clone(), tx.clone())
+            .await
+        {
+            warn!(error = %e, "WebChat: rejecting connection");
+            let frame = serde_json::json!({ "type": "error", "code": "capacity" });
+            let _ = ws_tx.send(Message::Text(frame.to_string().into())).await;
+            let _ = ws_tx.close().await;
+            return;
+        }
+        info!(visitor = %visitor.id, connection_id = %connection_id, "WebChat client connected");
+
+        let last_read = self
+            .visitors
+            .lock()
+            .get(&visitor.id)
+            .map_or(0, |s| s.last_read);
+        let session = serde_json::json!({
+            "type": "session",
+            "visitorId": visitor.id,
+            "name": visitor.name,
+            "token": visitor.issued_token,
+            "title": self.config.title.as_deref().unwrap_or("Chat"),
+            "greeting": self.config.greeting,
+            "lastRead": last_read,
+        });
+        let history = serde_json::json!({
+            "type": "history",
+            "messages": self.history(&visitor.id, since),
+        });
+        let _ = tx.send(session.to_string());
+        let _ = tx.send(history.to_string());
+
+        let writer = tokio::spawn(async move {
+            while let Some(payload) = rx.recv().await {
+                if ws_tx.send(Message::Text(payload.into())).await.is_err() {
+                    break;
+                }
+            }
+            let _ = ws_tx.close().await;
+        });
+
+        while let Some(Ok(message)) = ws_rx.next().await {
+            let text = match message {
+                Message::Text(text) => text,
+                Message::Close(_) => break,
+                _ => continue,
+            };
+            match serde_json::from_str::<ClientFrame>(&text) {
+                Ok(frame) => self.handle_frame(&state, &visitor, &tx, frame).await,
+                Err(e) => {
+                    debug!(error = %e, "WebChat: ignoring malformed frame");
+                    let frame = serde_json::json!({ "type": "error", "code": "bad_frame" });
+                    let _ = tx.send(frame.to_string());
+                }
+            }
+        }
+
+        self.unregister_client(&connection_id).await;
+        drop(tx);
+        let _ = writer.await;
+        info!(visitor = %visitor.id, connection_id = %connection_id, "WebChat client disconnected");
+    }
+
+    async fn handle_frame(
+        self: &Arc<Self>,
+        state: &GatewayState,
+        visitor: &Visitor,
+        tx: &tokio::sync::mpsc::UnboundedSender<String>,
+        frame: ClientFrame,
+    ) {
+        match frame {
+            ClientFrame::Message { text, client_id } => {
+                let text = text.trim();
+                if text.is_empty() || text.chars().count() > MAX_VISITOR_MESSAGE_CHARS {
+                    let frame = serde_json::json!({
+                        "type": "error",
+                        "code": "invalid_message",
+                        "clientId": client_id,
+                    });
+                    let _ = tx.send(frame.to_string());
+                    return;
+                }
+                if !self.within_rate_limit(&visitor.id) {
+                    let frame = serde_json::json!({
+                        "type": "error",
+                        "code": "rate_limited",
+                        "clientId": client_id,
+                    });
+                    let _ = tx.send(frame.to_string());
+                    return;
+                }
+
+                let entry = self.record(&visitor.id, "visitor", text, client_id.clone());
+                let ack =
+                    serde_json::json!({ "type": "ack", "clientId": client_id, "id": entry.id });
+                let _ = tx.send(ack.to_string());
+                // Other tabs of the same visitor show the message too.
+                let echo = serde_json::json!({ "type": "message", "message": entry });
+                self.send_to_visitor(&visitor.id, &echo).await;
+
+                let channel = Arc::clone(self);
+                let state = state.clone();
+                let visitor = visitor.clone();
+                tokio::spawn(async move { channel.run_agent(&state, &visitor, entry).await });
+            }
+            ClientFrame::Typing { state } => {
+                debug!(visitor = %visitor.id, typing = state, "WebChat visitor typing");
+            }
+            ClientFrame::Read { id } => {
+                let mut visitors = self.visitors.lock();
+                if let Some(s) = visitors.get_mut(&visitor.id) {
+                    s.last_read = s.last_read.max(id.min(s.next_id));
+                }
+            }
+            ClientFrame::Ping => {
+                let _ = tx.send(serde_json::json!({ "type": "pong" }).to_string());
+            }
+        }
+    }
+
+    /// Answer a visitor message, with read and typing events around the run.
+    async fn run_agent(&self, state: &GatewayState, visitor: &Visitor, entry: HistoryEntry) {
+        let read = serde_json::json!({ "type": "read", "id": entry.id });
+        self.send_to_visitor(&visitor.id, &read).await;
+        let typing = |on: bool| serde_json::json!({ "type": "typing", "state": on });
+        self.send_to_visitor(&visitor.id, &typing(true)).await;
+
+        let msg = NormalizedMessage {
+            id: format!("{}:{}", visitor.id, entry.id),
+            channel: "webchat".to_string(),
+            account_id: "default".to_string(),
+            chat_id: visitor.id.clone(),
+            chat_name: None,
+            chat_type: ChatType::Dm,
+            sender: NormalizedSender {
+                id: visitor.id.clone(),
+                name: visitor.name.clone().unwrap_or_else(|| visitor.id.clone()),
+                is_bot: false,
+                roles: Vec::new(),
+            },
+            text: entry.text,
+            attachments: Vec::new(),
+            reply_to_id: None,
+            thread_id: None,
+            mentioned: true,
+            timestamp: entry.timestamp,
+            raw: None,
+        };
+        let result = dispatch_inbound(state, &msg).await;
+        self.send_to_visitor(&visitor.id, &typing(false)).await;
+
+        match result {
+            Ok(Some(reply)) => {
+                let entry = self.record(&visitor.id, "assistant", &reply, None);
+                let frame = serde_json::json!({ "type": "message", "message": entry });
+                self.send_to_visitor(&visitor.id, &frame).await;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                warn!(visitor = %visitor.id, error = %e, "WebChat: agent run failed");
+                let frame = serde_json::json!({ "type": "error", "code": "agent_failed" });
+                self.send_to_visitor(&visitor.id, &frame).await;
+            }
+        }
+    }
+}
+
+fn sign_token(claims: &VisitorClaims, key: &[u8]) -> Result<String> {
+    Ok(jsonwebtoken::encode(
+        &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::HS256),
+        claims,
+        &jsonwebtoken::EncodingKey::from_secret(key),
+    )?)
 }
 
+fn verify_token(token: &str, key: &[u8]) -> Option<VisitorClaims> {
+    let validation = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::HS256);
+    jsonwebtoken::decode::<VisitorClaims>(
+        token,
+        &jsonwebtoken::DecodingKey::from_secret(key),
+        &validation,
+    )
+    .ok()
+    .map(|data| data.claims)
+    .filter(|claims| !claims.sub.is_empty())
+}
+
+/// The embeddable widget script served at `/webchat/widget.js`.
+pub const WIDGET_JS: &str = include_str!("../../assets/webchat/widget.js");
+/// A standalone page hosting the widget, served at `/webchat`.
+pub const WIDGET_HTML: &str = include_str!("../../assets/webchat/index.html");
+
 #[async_trait]
 impl ChannelPlugin for WebChatChannel {
     fn id(&self) -> &str {
@@ -129,7 +545,7 @@ impl ChannelPlugin for WebChatChannel {
         ChannelMeta {
             name: "WebChat".to_string(),
             description: "Built-in WebChat channel using axum WebSocket".to_string(),
-            enabled: self.is_enabled(),
+            enabled: self.enabled,
             multi_account: false,
         }
     }
@@ -146,40 +562,22 @@ impl ChannelPlugin for WebChatChannel {
     }
 
     async fn start_account(&self, _state: &GatewayState) -> Result<()> {
-        if !self.is_enabled() {
+        if !self.enabled {
             return Ok(());
         }
 
-        let bind = self.bind_address.as_deref().unwrap_or("0.0.0.0");
-        let port = self.port.unwrap_or(0);
-        let max_conn = self.max_connections.unwrap_or(100);
-
         info!(
-            bind = %bind,
-            port = %port,
-            max_connections = %max_conn,
-            "WebChat channel starting"
+            max_connections = %self.max_connections(),
+            anonymous = %self.config.allow_anonymous.unwrap_or(true),
+            signed_visitors = %self.config.visitor_secret.is_some(),
+            "WebChat channel starting on /ws/webchat"
         );
 
-        // The WebChat WebSocket endpoint is typically registered on the main
-        // gateway router (e.g. at `/ws/webchat`). If a separate bind address
-        // or port is configured, a dedicated axum listener would be spawned here.
-        //
-        // TODO: Register the WebSocket upgrade handler on the gateway router:
-        //   router.route("/ws/webchat", get(webchat_ws_handler))
-        //
-        // The handler would:
-        // 1. Upgrade the HTTP connection to WebSocket.
-        // 2. Create an mpsc channel for outbound messages.
-        // 3. Register the client via `register_client()`.
-        // 4. Spawn a read loop for incoming messages.
-        // 5. Spawn a write loop that forwards messages from the mpsc channel.
-
         Ok(())
     }
 
     async fn stop_account(&self) -> Result<()> {
-        if self.is_enabled() {
+        if self.enabled {
             let count = self.connected_count().await;
             info!(
                 connected_clients = %count,
@@ -194,40 +592,170 @@ impl ChannelPlugin for WebChatChannel {
     }
 
     async fn send_message(&self, to: &str, message: &str) -> Result<()> {
-        let clients = self.clients.read().await;
-
-        // `to` is a session ID. If `to` is "*", broadcast to all clients.
+        // `to` is a visitor ID. If `to` is "*", broadcast to all clients.
         if to == "*" {
-            drop(clients);
-            self.broadcast(message).await;
+            let frame = serde_json::json!({
+                "type": "message",
+                "message": {
+                    "id": 0,
+                    "role": "assistant",
+                    "text": message,
+                    "timestamp": chrono::Utc::now().to_rfc3339(),
+                },
+            });
+            self.broadcast(&frame.to_string()).await;
             return Ok(());
         }
 
-        match clients.get(to) {
-            Some(client) => {
-                let payload = serde_json::json!({
-                    "type": "message",
-                    "session_id": to,
-                    "content": message,
-                    "timestamp": chrono::Utc::now().to_rfc3339(),
-                });
+        if !self.visitors.lock().contains_key(to) {
+            anyhow::bail!("WebChat: no visitor with id '{}'", to);
+        }
 
-                client
-                    .tx
-                    .send(payload.to_string())
-                    .map_err(|_| {
-                        anyhow::anyhow!(
-                            "WebChat: client {} disconnected, cannot send message",
-                            to
-                        )
-                    })?;
-
-                info!(session_id = %to, "WebChat: message sent to client");
-                Ok(())
-            }
-            None => {
-                anyhow::bail!("WebChat: no client with session_id '{}'", to);
-            }
+        // Visitors that are offline see the message when they reconnect.
+        let entry = self.record(to, "assistant", message, None);
+        let frame = serde_json::json!({ "type": "message", "message": entry });
+        let delivered = self.send_to_visitor(to, &frame).await;
+        info!(visitor = %to, connections = delivered, "WebChat: message sent to visitor");
+        Ok(())
+    }
+
+    async fn send_typing(&self, chat_id: &str, _thread_id: Option<&str>) -> Result<()> {
+        let frame = serde_json::json!({ "type": "typing", "state": true });
+        self.send_to_visitor(chat_id, &frame).await;
+        Ok(())
+    }
+
+    async fn mark_read(&self, chat_id: &str, message_id: &str) -> Result<()> {
+        // Inbound ids are `<visitor>:<id>`; the widget knows only the number.
+        let id = message_id.rsplit(':').next().unwrap_or(message_id);
+        let Ok(id) = id.parse::<u64>() else {
+            anyhow::bail!("WebChat: invalid message id '{}'", message_id);
+        };
+        let frame = serde_json::json!({ "type": "read", "id": id });
+        self.send_to_visitor(chat_id, &frame).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(configure: impl FnOnce(&mut WebChatConfig)) -> WebChatChannel {
+        let mut webchat = WebChatConfig {
+            enabled: Some(true),
+            ..Default::default()
+        };
+        configure(&mut webchat);
+        let mut config = Config::default();
+        config.channels.webchat = Some(webchat);
+        WebChatChannel::new(&config)
+    }
+
+    #[test]
+    fn anonymous_visitors_get_a_resumable_token() {
+        let channel = channel(|_| {});
+        let visitor = channel.authenticate(None).unwrap();
+        assert!(visitor.id.starts_with("anon-"));
+        let token = visitor.issued_token.clone().unwrap();
+
+        let resumed = channel.authenticate(Some(&token)).unwrap();
+        assert_eq!(resumed.id, visitor.id);
+        assert!(resumed.issued_token.is_none());
+
+        // Tokens from another gateway instance start a fresh visitor.
+        let other = self::channel(|_| {}).authenticate(Some(&token)).unwrap();
+        assert_ne!(other.id, visitor.id);
+    }
+
+    #[test]
+    fn signed_visitors_and_anonymous_opt_out() {
+        let channel = channel(|c| {
+            c.visitor_secret = Some("site-secret".to_string());
+            c.allow_anonymous = Some(false);
+        });
+        let claims = VisitorClaims {
+            sub: "user-42".to_string(),
+            name: Some("Ada".to_string()),
+            exp: chrono::Utc::now().timestamp() + 60,
+        };
+        let token = sign_token(&claims, b"site-secret").unwrap();
+        let visitor = channel.authenticate(Some(&token)).unwrap();
+        assert_eq!(visitor.id, "user-42");
+        assert_eq!(visitor.name.as_deref(), Some("Ada"));
+
+        let forged = sign_token(&claims, b"guess").unwrap();
+        assert_eq!(
+            channel.authenticate(Some(&forged)),
+            Err(StatusCode::UNAUTHORIZED)
+        );
+        let expired = VisitorClaims {
+            exp: chrono::Utc::now().timestamp() - 3600,
+            ..claims
+        };
+        let expired = sign_token(&expired, b"site-secret").unwrap();
+        assert_eq!(
+            channel.authenticate(Some(&expired)),
+            Err(StatusCode::UNAUTHORIZED)
+        );
+        assert_eq!(channel.authenticate(None), Err(StatusCode::UNAUTHORIZED));
+    }
+
+    #[test]
+    fn history_is_bounded_and_replayed_after_since() {
+        let channel = channel(|c| c.history_limit = Some(3));
+        for i in 1..=5 {
+            channel.record("v1", "visitor", &format!("m{i}"), None);
+        }
+        let all: Vec<u64> = channel.history("v1", None).iter().map(|e| e.id).collect();
+        assert_eq!(all, vec![3, 4, 5]);
+        let since: Vec<u64> = channel
+            .history("v1", Some(4))
+            .iter()
+            .map(|e| e.id)
+            .collect();
+        assert_eq!(since, vec![5]);
+        assert!(channel.history("v2", None).is_empty());
+    }
+
+    #[test]
+    fn rate_limit_is_per_visitor() {
+        let channel = channel(|c| c.rate_limit_per_minute = Some(2));
+        assert!(channel.within_rate_limit("v1"));
+        assert!(channel.within_rate_limit("v1"));
+        assert!(!channel.within_rate_limit("v1"));
+        assert!(channel.within_rate_limit("v2"));
+    }
+
+    #[tokio::test]
+    async fn outbound_messages_reach_every_tab_and_history() {
+        let channel = channel(|_| {});
+        let (tx1, mut rx1) = tokio::sync::mpsc::unbounded_channel();
+        let (tx2, mut rx2) = tokio::sync::mpsc::unbounded_channel();
+        let (tx3, mut rx3) = tokio::sync::mpsc::unbounded_channel();
+        channel
+            .register_client("c1".into(), "v1".into(), tx1)
+            .await
+            .unwrap();
+        channel
+            .register_client("c2".into(), "v1".into(), tx2)
+            .await
+            .unwrap();
+        channel
+            .register_client("c3".into(), "v2".into(), tx3)
+            .await
+            .unwrap();
+
+        assert!(channel.send_message("v1", "hello").await.is_err());
+        channel.record("v1", "visitor", "hi", None);
+        channel.send_message("v1", "hello").await.unwrap();
+
+        for rx in [&mut rx1, &mut rx2] {
+            let frame: Value = serde_json::from_str(&rx.recv().await.unwrap()).unwrap();
+            assert_eq!(frame["message"]["text"], "hello");
+            assert_eq!(frame["message"]["role"], "assistant");
         }
+        assert!(rx3.try_recv().is_err());
+        assert_eq!(channel.history("v1", Some(1))[0].text, "hello");
     }
 }
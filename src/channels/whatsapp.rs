@@ -1,26 +1,556 @@
-use crate::config::Config;
+use crate::config::{Config, WhatsAppAccountConfig};
 use crate::gateway::GatewayState;
 
-use super::plugin::{ChannelCapability, ChannelMeta, ChannelPlugin};
+use super::admission;
+use super::format::{render_markdown_chunks, FormatTarget};
+use super::inbound::{dispatch_inbound, InboundEvent};
+use super::normalize::{
+    split_text, ChatType, NormalizedAttachment, NormalizedMessage, NormalizedOutbound,
+    NormalizedSender, OutboundButton,
+};
+use super::plugin::{
+    ChannelCapability, ChannelMeta, ChannelPlugin, WebhookRequest, WebhookResponse,
+    DEFAULT_ACCOUNT_ID,
+};
+use super::voice;
+use super::webhook::WebhookRoute;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use async_trait::async_trait;
-use tracing::info;
+use axum::http::{Method, StatusCode};
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+// ============================================================================
+// WhatsApp Cloud API Channel
+// ============================================================================
+
+const DEFAULT_API_URL: &str = "https://graph.facebook.com";
+const DEFAULT_API_VERSION: &str = "v21.0";
+const DEFAULT_WEBHOOK_PATH: &str = "webhook";
+const SIGNATURE_HEADER: &str = "x-hub-signature-256";
+/// Graph API error: free-form message outside the 24-hour customer service
+/// window ("Re-engagement message").
+const SESSION_WINDOW_ERROR_CODE: i64 = 131_047;
+/// Longest media caption the Cloud API accepts.
+const MAX_CAPTION_CHARS: usize = 1024;
+/// Longest body text of an interactive (button or list) message.
+const MAX_INTERACTIVE_BODY_CHARS: usize = 1024;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Error returned by the Graph API.
+#[derive(Debug, Clone)]
+pub struct WhatsAppApiError {
+    pub status: u16,
+    pub code: Option<i64>,
+    pub message: String,
+}
+
+impl WhatsAppApiError {
+    /// Whether the message was rejected because the customer has not written
+    /// in the last 24 hours; only template messages can be sent then.
+    pub fn is_session_window_error(&self) -> bool {
+        self.code == Some(SESSION_WINDOW_ERROR_CODE)
+    }
+}
+
+impl std::fmt::Display for WhatsAppApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_session_window_error() {
+            return write!(
+                f,
+                "WhatsApp recipient is outside the 24-hour session window; send a template message instead ({})",
+                self.message
+            );
+        }
+        match self.code {
+            Some(code) => write!(
+                f,
+                "WhatsApp API error {code} ({}): {}",
+                self.status, self.message
+            ),
+            None => write!(f, "WhatsApp API error ({}): {}", self.status, self.message),
+        }
+    }
+}
+
+impl std::error::Error for WhatsAppApiError {}
+
+/// Media message kinds supported by the Cloud API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WhatsAppMediaKind {
+    Image,
+    Video,
+    Audio,
+    Document,
+    Sticker,
+}
+
+impl WhatsAppMediaKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Image => "image",
+            Self::Video => "video",
+            Self::Audio => "audio",
+            Self::Document => "document",
+            Self::Sticker => "sticker",
+        }
+    }
+}
+
+/// An event parsed from a webhook delivery.
+#[derive(Debug, Clone)]
+enum WebhookEvent {
+    /// Text, media, or interactive/button reply.
+    Message(Box<NormalizedMessage>),
+    /// A reaction to one of the conversation's messages; an empty emoji
+    /// removes the reaction.
+    Reaction {
+        from: String,
+        message_id: String,
+        emoji: String,
+    },
+    /// Delivery status of one of our messages.
+    Status {
+        message_id: String,
+        recipient: String,
+        status: String,
+        errors: Vec<(Option<i64>, String)>,
+    },
+}
+
+/// Per-account state shared between the channel and webhook handlers.
+struct WhatsAppAccount {
+    account_id: String,
+    config: WhatsAppAccountConfig,
+    http: Client,
+}
 
 /// WhatsApp channel implementation.
 ///
-/// Connects to the WhatsApp Business API (or a compatible bridge) to send and
-/// receive messages.
+/// Connects to the WhatsApp Business Cloud API. Inbound messages arrive on
+/// the gateway webhook at `/channels/whatsapp/<webhookPath>`, which answers
+/// the `hub.challenge` verification and validates `X-Hub-Signature-256`
+/// against the app secret. Messages, media, templates, reactions and read
+/// receipts are sent via `POST /{phone-number-id}/messages`.
 pub struct WhatsAppChannel {
     enabled: bool,
+    account: Arc<WhatsAppAccount>,
 }
 
 impl WhatsAppChannel {
     pub fn new(config: &Config) -> Self {
+        Self::for_account(config, DEFAULT_ACCOUNT_ID)
+    }
+
+    /// The channel for one business number: an entry of `accounts`, or the
+    /// top-level settings for the default account.
+    ///
+    /// Named accounts without a `webhookPath` listen on `webhook/<id>`.
+    pub fn for_account(config: &Config, account_id: &str) -> Self {
         let wa = &config.channels.whatsapp;
-        let enabled = wa.default_account.enabled.unwrap_or(false);
+        let mut account = admission::account(&wa.accounts, &wa.default_account, account_id).clone();
+        if account_id != DEFAULT_ACCOUNT_ID && account.webhook_path.is_none() {
+            account.webhook_path = Some(format!("{DEFAULT_WEBHOOK_PATH}/{account_id}"));
+        }
+        let enabled = account
+            .enabled
+            .unwrap_or(account.access_token.is_some() && account.phone_number_id.is_some());
+
+        Self {
+            enabled,
+            account: Arc::new(WhatsAppAccount {
+                account_id: account_id.to_string(),
+                config: account,
+                http: Client::new(),
+            }),
+        }
+    }
+
+    /// Send media by public `link` or uploaded media id, with an optional
+    /// caption (not supported for audio and stickers).
+    pub async fn send_media(
+        &self,
+        to: &str,
+        kind: WhatsAppMediaKind,
+        link_or_id: &str,
+        caption: Option<&str>,
+        filename: Option<&str>,
+    ) -> Result<String> {
+        let mut media = if link_or_id.starts_with("http://") || link_or_id.starts_with("https://") {
+            json!({ "link": link_or_id })
+        } else {
+            json!({ "id": link_or_id })
+        };
+        if let Some(caption) = caption {
+            media["caption"] = json!(caption);
+        }
+        if let (WhatsAppMediaKind::Document, Some(filename)) = (kind, filename) {
+            media["filename"] = json!(filename);
+        }
+        let mut body = message_body(to, kind.as_str());
+        body[kind.as_str()] = media;
+        self.account.post_message(&body).await
+    }
+
+    /// Send an approved template message, which is allowed outside the
+    /// 24-hour session window.
+    pub async fn send_template(
+        &self,
+        to: &str,
+        name: &str,
+        language: &str,
+        components: Option<Value>,
+    ) -> Result<String> {
+        let mut template = json!({ "name": name, "language": { "code": language } });
+        if let Some(components) = components {
+            template["components"] = components;
+        }
+        let mut body = message_body(to, "template");
+        body["template"] = template;
+        self.account.post_message(&body).await
+    }
 
-        Self { enabled }
+    /// React to a message; an empty `emoji` removes the reaction.
+    pub async fn react(&self, to: &str, message_id: &str, emoji: &str) -> Result<()> {
+        self.account.react(to, message_id, emoji).await
+    }
+}
+
+impl WhatsAppAccount {
+    fn api_base(&self) -> String {
+        format!(
+            "{}/{}",
+            self.config
+                .api_url
+                .as_deref()
+                .unwrap_or(DEFAULT_API_URL)
+                .trim_end_matches('/'),
+            self.config
+                .api_version
+                .as_deref()
+                .unwrap_or(DEFAULT_API_VERSION)
+        )
+    }
+
+    fn webhook_path(&self) -> &str {
+        self.config
+            .webhook_path
+            .as_deref()
+            .unwrap_or(DEFAULT_WEBHOOK_PATH)
+            .trim_matches('/')
+    }
+
+    /// Answer the `hub.mode=subscribe` verification handshake.
+    fn verify_subscription(&self, query: Option<&str>) -> WebhookResponse {
+        let params: std::collections::HashMap<String, String> =
+            url::form_urlencoded::parse(query.unwrap_or_default().as_bytes())
+                .into_owned()
+                .collect();
+        let expected = self.config.verify_token.as_deref();
+        let verified = params.get("hub.mode").map(String::as_str) == Some("subscribe")
+            && expected.is_some()
+            && params.get("hub.verify_token").map(String::as_str) == expected;
+        match params.get("hub.challenge") {
+            Some(challenge) if verified => WebhookResponse::text(StatusCode::OK, challenge.clone()),
+            _ => WebhookResponse::status(StatusCode::FORBIDDEN),
+        }
+    }
+
+    /// Validate `X-Hub-Signature-256` (HMAC-SHA256 of the body with the app secret).
+    fn signature_valid(&self, request: &WebhookRequest) -> bool {
+        let Some(secret) = self.config.app_secret.as_deref() else {
+            return false;
+        };
+        let Some(signature) = request
+            .header(SIGNATURE_HEADER)
+            .and_then(|value| value.strip_prefix("sha256="))
+            .and_then(|hex_sig| hex::decode(hex_sig).ok())
+        else {
+            return false;
+        };
+        let Ok(mut mac) = HmacSha256::new_from_slice(secret.as_bytes()) else {
+            return false;
+        };
+        mac.update(&request.body);
+        mac.verify_slice(&signature).is_ok()
+    }
+
+    async fn handle_event(self: Arc<Self>, state: GatewayState, event: WebhookEvent) {
+        match event {
+            // Every WhatsApp conversation is a DM; `dmPolicy` and
+            // `allowFrom` are applied by the admission stage.
+            WebhookEvent::Message(msg) => self.handle_message(state, *msg).await,
+            WebhookEvent::Reaction {
+                from,
+                message_id,
+                emoji,
+            } => {
+                debug!(from = %from, message_id = %message_id, emoji = %emoji, "WhatsApp reaction received");
+                state.inbound.push(InboundEvent::Reaction {
+                    channel: "whatsapp".to_string(),
+                    chat_id: from.clone(),
+                    message_id,
+                    sender: NormalizedSender {
+                        id: from.clone(),
+                        name: from,
+                        is_bot: false,
+                        roles: Vec::new(),
+                    },
+                    emoji,
+                });
+            }
+            WebhookEvent::Status {
+                message_id,
+                recipient,
+                status,
+                errors,
+            } => {
+                for (code, title) in errors {
+                    if code == Some(SESSION_WINDOW_ERROR_CODE) {
+                        warn!(
+                            recipient = %recipient,
+                            message_id = %message_id,
+                            "WhatsApp message not delivered: outside the 24-hour session window"
+                        );
+                    } else {
+                        warn!(
+                            recipient = %recipient,
+                            message_id = %message_id,
+                            code = ?code,
+                            error = %title,
+                            "WhatsApp message delivery failed"
+                        );
+                    }
+                }
+                debug!(message_id = %message_id, status = %status, "WhatsApp status update");
+            }
+        }
+    }
+
+    /// Acknowledge, run the agent and reply to an admitted message.
+    async fn handle_message(&self, state: GatewayState, msg: NormalizedMessage) {
+        if self.config.send_read_receipts.unwrap_or(true) {
+            if let Err(e) = self.mark_read(&msg.id).await {
+                debug!(message_id = %msg.id, error = %e, "WhatsApp read receipt failed");
+            }
+        }
+        let ack = self
+            .config
+            .ack_reaction
+            .as_ref()
+            .filter(|ack| ack.direct.unwrap_or(true))
+            .and_then(|ack| ack.emoji.as_deref())
+            .filter(|emoji| !emoji.is_empty());
+        if let Some(emoji) = ack {
+            if let Err(e) = self.react(&msg.chat_id, &msg.id, emoji).await {
+                debug!(message_id = %msg.id, error = %e, "WhatsApp ack reaction failed");
+            }
+        }
+
+        match dispatch_inbound(&state, &msg).await {
+            Ok(Some(reply)) => {
+                let outbound = NormalizedOutbound {
+                    reply_to_id: Some(msg.id.clone()),
+                    account_id: Some(msg.account_id.clone()),
+                    ..NormalizedOutbound::text(msg.chat_id.clone(), reply)
+                };
+                let outbound = voice::voice_reply(&state, &msg, outbound).await;
+                if let Err(e) = state.channels.enqueue("whatsapp", outbound).await {
+                    warn!(to = %msg.chat_id, error = %e, "WhatsApp reply failed");
+                }
+            }
+            Ok(None) => {}
+            Err(e) => warn!(to = %msg.chat_id, error = %e, "WhatsApp agent run failed"),
+        }
+    }
+
+    fn access_token(&self) -> Result<&str> {
+        match self.config.access_token.as_deref() {
+            Some(token) => Ok(token),
+            None => bail!("WhatsApp access_token not configured"),
+        }
+    }
+
+    fn phone_number_id(&self) -> Result<&str> {
+        match self.config.phone_number_id.as_deref() {
+            Some(id) => Ok(id),
+            None => bail!("WhatsApp phone_number_id not configured"),
+        }
+    }
+
+    /// `POST /{phone-number-id}/messages`, returning the sent message id.
+    async fn post_message(&self, body: &Value) -> Result<String> {
+        let token = self.access_token()?;
+        let phone_number_id = self.phone_number_id()?;
+
+        let resp = self
+            .http
+            .post(format!("{}/{}/messages", self.api_base(), phone_number_id))
+            .bearer_auth(token)
+            .json(body)
+            .send()
+            .await?;
+        let status = resp.status();
+        let payload: Value = resp.json().await.unwrap_or(Value::Null);
+        if !status.is_success() {
+            return Err(api_error(status.as_u16(), &payload));
+        }
+        Ok(payload["messages"][0]["id"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string())
+    }
+
+    /// `POST /{phone-number-id}/media`, returning the id to send the upload
+    /// by.
+    async fn upload_media(&self, data: &[u8], mime_type: &str, filename: &str) -> Result<String> {
+        let token = self.access_token()?;
+        let phone_number_id = self.phone_number_id()?;
+
+        let file = reqwest::multipart::Part::bytes(data.to_vec())
+            .file_name(filename.to_string())
+            .mime_str(mime_type)?;
+        let form = reqwest::multipart::Form::new()
+            .text("messaging_product", "whatsapp")
+            .text("type", mime_type.to_string())
+            .part("file", file);
+        let resp = self
+            .http
+            .post(format!("{}/{}/media", self.api_base(), phone_number_id))
+            .bearer_auth(token)
+            .multipart(form)
+            .send()
+            .await?;
+        let status = resp.status();
+        let payload: Value = resp.json().await.unwrap_or(Value::Null);
+        if !status.is_success() {
+            return Err(api_error(status.as_u16(), &payload));
+        }
+        match payload["id"].as_str() {
+            Some(id) => Ok(id.to_string()),
+            None => bail!("WhatsApp media upload returned no id"),
+        }
+    }
+
+    /// Download inbound media. Its Graph URL answers with a short-lived
+    /// download URL; both requests need the access token.
+    async fn download_media(&self, media_url: &str) -> Result<Vec<u8>> {
+        let token = self.access_token()?;
+        let media: Value = self
+            .http
+            .get(media_url)
+            .bearer_auth(token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let Some(url) = media["url"].as_str() else {
+            bail!("WhatsApp media lookup returned no url");
+        };
+        let bytes = self
+            .http
+            .get(url)
+            .bearer_auth(token)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        Ok(bytes.to_vec())
+    }
+
+    /// Send text, replying to `reply_to` with the first chunk. Returns the
+    /// id of the last message sent.
+    async fn send_text(
+        &self,
+        to: &str,
+        text: &str,
+        reply_to: Option<&str>,
+    ) -> Result<Option<String>> {
+        let limit = (self.config.text_chunk_limit as usize).max(1);
+        let chunks = if self.config.markdown != Some(false) {
+            render_markdown_chunks(text, FormatTarget::WhatsApp, limit)
+        } else {
+            split_text(text, limit)
+        };
+        let mut last = None;
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            let mut body = message_body(to, "text");
+            body["text"] = json!({ "body": chunk, "preview_url": false });
+            if let Some(reply_to) = reply_to.filter(|_| i == 0) {
+                body["context"] = json!({ "message_id": reply_to });
+            }
+            last = Some(self.post_message(&body).await?);
+        }
+        Ok(last)
+    }
+
+    /// Send text with reply buttons (up to 3) or a list (up to 10 options).
+    async fn send_buttons(
+        &self,
+        to: &str,
+        text: &str,
+        buttons: &[OutboundButton],
+        reply_to: Option<&str>,
+    ) -> Result<String> {
+        if text.chars().count() > MAX_INTERACTIVE_BODY_CHARS {
+            bail!("WhatsApp interactive messages are limited to {MAX_INTERACTIVE_BODY_CHARS} characters");
+        }
+        let action = match buttons.len() {
+            0..=3 => json!({
+                "buttons": buttons
+                    .iter()
+                    .map(|b| json!({
+                        "type": "reply",
+                        "reply": { "id": b.value, "title": truncate(&b.label, 20) },
+                    }))
+                    .collect::<Vec<_>>(),
+            }),
+            4..=10 => json!({
+                "button": "Options",
+                "sections": [{
+                    "rows": buttons
+                        .iter()
+                        .map(|b| json!({ "id": b.value, "title": truncate(&b.label, 24) }))
+                        .collect::<Vec<_>>(),
+                }],
+            }),
+            n => bail!("WhatsApp supports at most 10 options per message, got {n}"),
+        };
+        let mut body = message_body(to, "interactive");
+        body["interactive"] = json!({
+            "type": if buttons.len() <= 3 { "button" } else { "list" },
+            "body": { "text": text },
+            "action": action,
+        });
+        if let Some(reply_to) = reply_to {
+            body["context"] = json!({ "message_id": reply_to });
+        }
+        self.post_message(&body).await
+    }
+
+    async fn react(&self, to: &str, message_id: &str, emoji: &str) -> Result<()> {
+        let mut body = message_body(to, "reaction");
+        body["reaction"] = json!({ "message_id": message_id, "emoji": emoji });
+        self.post_message(&body).await.map(drop)
+    }
+
+    /// Mark a message read, showing a typing indicator until we reply.
+    async fn mark_read(&self, message_id: &str) -> Result<()> {
+        let body = json!({
+            "messaging_product": "whatsapp",
+            "status": "read",
+            "message_id": message_id,
+            "typing_indicator": { "type": "text" },
+        });
+        self.post_message(&body).await.map(drop)
     }
 }
 
@@ -30,10 +560,14 @@ impl ChannelPlugin for WhatsAppChannel {
         "whatsapp"
     }
 
+    fn account_id(&self) -> &str {
+        &self.account.account_id
+    }
+
     fn meta(&self) -> ChannelMeta {
         ChannelMeta {
             name: "WhatsApp".to_string(),
-            description: "WhatsApp Business API channel".to_string(),
+            description: "WhatsApp Business Cloud API channel".to_string(),
             enabled: self.enabled,
             multi_account: true,
         }
@@ -54,18 +588,33 @@ impl ChannelPlugin for WhatsAppChannel {
         ]
     }
 
+    fn webhook_routes(&self) -> Vec<WebhookRoute> {
+        if !self.enabled {
+            return Vec::new();
+        }
+        let path = self.account.webhook_path();
+        vec![WebhookRoute::get(path), WebhookRoute::post(path)]
+    }
+
     async fn start_account(&self, _state: &GatewayState) -> Result<()> {
         if !self.enabled {
             return Ok(());
         }
 
-        info!("WhatsApp channel starting");
+        if self.account.config.access_token.is_none()
+            || self.account.config.phone_number_id.is_none()
+        {
+            warn!("WhatsApp channel enabled but access_token or phone_number_id is not configured");
+            return Ok(());
+        }
+        if self.account.config.app_secret.is_none() {
+            warn!("WhatsApp app_secret not configured; webhook deliveries will be rejected");
+        }
 
-        // TODO: Connect to WhatsApp Business API / bridge.
-        // The implementation will:
-        // 1. Authenticate via the configured auth_dir / session.
-        // 2. Set up a message listener to forward incoming messages.
-        // 3. Optionally send read receipts.
+        info!(
+            webhook = %format!("/channels/whatsapp/{}", self.account.webhook_path()),
+            "WhatsApp channel starting"
+        );
 
         Ok(())
     }
@@ -73,23 +622,704 @@ impl ChannelPlugin for WhatsAppChannel {
     async fn stop_account(&self) -> Result<()> {
         if self.enabled {
             info!("WhatsApp channel stopping");
-            // TODO: Disconnect from WhatsApp.
         }
         Ok(())
     }
 
-    async fn send_message(&self, to: &str, _message: &str) -> Result<()> {
+    async fn send_message(&self, to: &str, message: &str) -> Result<()> {
+        // `to` is a phone number in international format (e.g. "+1234567890").
         info!(to = to, "WhatsApp: sending message");
+        self.account
+            .send_text(to.trim_start_matches('+'), message, None)
+            .await
+            .map(drop)
+    }
 
-        // TODO: Use the WhatsApp API client to send _message.
-        // `to` is expected to be a phone number in international format (e.g. "+1234567890").
+    async fn send(&self, message: &NormalizedOutbound) -> Result<Option<String>> {
+        let to = message.chat_id.trim_start_matches('+');
+        let reply_to = message.reply_to_id.as_deref();
+        let mut last = None;
+        let mut captioned = false;
+        for attachment in &message.attachments {
+            let mime_type = attachment.mime_type.as_deref();
+            // Inline bytes (voice replies) are uploaded first.
+            let link = match (&attachment.url, &attachment.data) {
+                (Some(url), _) => url.clone(),
+                (None, Some(data)) => {
+                    let mime_type = mime_type.unwrap_or("application/octet-stream");
+                    let filename = attachment.filename.as_deref().unwrap_or("file");
+                    self.account.upload_media(data, mime_type, filename).await?
+                }
+                (None, None) => {
+                    bail!("WhatsApp attachments need a public URL, uploaded media id or data")
+                }
+            };
+            let kind = media_kind(mime_type);
+            // A lone attachment carries the text as its caption.
+            let caption = (message.attachments.len() == 1
+                && message.buttons.is_empty()
+                && kind != WhatsAppMediaKind::Audio
+                && !message.text.trim().is_empty()
+                && message.text.chars().count() <= MAX_CAPTION_CHARS)
+                .then_some(message.text.as_str());
+            captioned |= caption.is_some();
+            last = Some(
+                self.send_media(to, kind, &link, caption, attachment.filename.as_deref())
+                    .await?,
+            );
+        }
+        if captioned {
+            return Ok(last);
+        }
+        if !message.buttons.is_empty() {
+            return self
+                .account
+                .send_buttons(to, &message.text, &message.buttons, reply_to)
+                .await
+                .map(Some);
+        }
+        if message.text.trim().is_empty() {
+            return Ok(last);
+        }
+        Ok(self
+            .account
+            .send_text(to, &message.text, reply_to)
+            .await?
+            .or(last))
+    }
 
-        Ok(())
+    async fn react(&self, chat_id: &str, message_id: &str, emoji: &str) -> Result<()> {
+        self.account
+            .react(chat_id.trim_start_matches('+'), message_id, emoji)
+            .await
+    }
+
+    async fn mark_read(&self, _chat_id: &str, message_id: &str) -> Result<()> {
+        self.account.mark_read(message_id).await
+    }
+
+    async fn fetch_attachment(&self, attachment: &NormalizedAttachment) -> Result<Vec<u8>> {
+        match (&attachment.data, attachment.url.as_deref()) {
+            (Some(data), _) => Ok(data.clone()),
+            (None, Some(url)) => self.account.download_media(url).await,
+            (None, None) => bail!("WhatsApp attachment has no media id"),
+        }
+    }
+
+    async fn handle_webhook(
+        &self,
+        state: &GatewayState,
+        request: WebhookRequest,
+    ) -> Result<WebhookResponse> {
+        if !self.enabled || request.path.trim_matches('/') != self.account.webhook_path() {
+            return Ok(WebhookResponse::not_found());
+        }
+        if request.method == Method::GET {
+            return Ok(self.account.verify_subscription(request.query.as_deref()));
+        }
+        if request.method != Method::POST {
+            return Ok(WebhookResponse::status(StatusCode::METHOD_NOT_ALLOWED));
+        }
+        if !self.account.signature_valid(&request) {
+            warn!("WhatsApp webhook request with invalid signature");
+            return Ok(WebhookResponse::status(StatusCode::UNAUTHORIZED));
+        }
+
+        let payload: Value = match serde_json::from_slice(&request.body) {
+            Ok(payload) => payload,
+            Err(e) => {
+                return Ok(WebhookResponse::text(
+                    StatusCode::BAD_REQUEST,
+                    format!("invalid payload: {e}"),
+                ));
+            }
+        };
+
+        // Acknowledge immediately; Meta retries slow webhooks.
+        let events = parse_webhook(
+            &self.account.account_id,
+            &self.account.api_base(),
+            self.account.config.phone_number_id.as_deref(),
+            &payload,
+        );
+        for event in events {
+            tokio::spawn(self.account.clone().handle_event(state.clone(), event));
+        }
+        Ok(WebhookResponse::ok())
+    }
+}
+
+// ============================================================================
+// Webhook Parsing
+// ============================================================================
+
+/// Error from a failed Graph API response.
+fn api_error(status: u16, payload: &Value) -> anyhow::Error {
+    let error = &payload["error"];
+    WhatsAppApiError {
+        status,
+        code: error["code"].as_i64(),
+        message: error["error_data"]["details"]
+            .as_str()
+            .or_else(|| error["message"].as_str())
+            .unwrap_or("unknown error")
+            .to_string(),
     }
+    .into()
+}
+
+/// Media message kind for an attachment's MIME type.
+fn media_kind(mime_type: Option<&str>) -> WhatsAppMediaKind {
+    match mime_type.and_then(|m| m.split('/').next()) {
+        Some("image") => WhatsAppMediaKind::Image,
+        Some("video") => WhatsAppMediaKind::Video,
+        Some("audio") => WhatsAppMediaKind::Audio,
+        _ => WhatsAppMediaKind::Document,
+    }
+}
+
+/// Truncate to the first `max` characters (button titles have hard limits).
+fn truncate(text: &str, max: usize) -> String {
+    text.chars().take(max).collect()
+}
+
+/// Skeleton of a `/messages` request body.
+fn message_body(to: &str, kind: &str) -> Value {
+    json!({
+        "messaging_product": "whatsapp",
+        "recipient_type": "individual",
+        "to": to,
+        "type": kind,
+    })
+}
+
+/// Parse a `whatsapp_business_account` webhook delivery.
+///
+/// Changes for other phone numbers than `phone_number_id` (when set) are
+/// ignored, as are message types without text or media (locations,
+/// contacts, system messages).
+fn parse_webhook(
+    account_id: &str,
+    api_base: &str,
+    phone_number_id: Option<&str>,
+    payload: &Value,
+) -> Vec<WebhookEvent> {
+    let mut events = Vec::new();
+    let changes = payload["entry"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|entry| entry["changes"].as_array().into_iter().flatten())
+        .filter(|change| change["field"] == "messages");
+
+    for change in changes {
+        let value = &change["value"];
+        let ours = phone_number_id.map_or(true, |id| {
+            value["metadata"]["phone_number_id"].as_str() == Some(id)
+        });
+        if !ours {
+            continue;
+        }
+
+        for message in value["messages"].as_array().into_iter().flatten() {
+            let Some(from) = message["from"].as_str() else {
+                continue;
+            };
+            if message["type"] == "reaction" {
+                events.push(WebhookEvent::Reaction {
+                    from: from.to_string(),
+                    message_id: message["reaction"]["message_id"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                    emoji: message["reaction"]["emoji"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                });
+                continue;
+            }
+            let contact_name = value["contacts"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .find(|contact| contact["wa_id"].as_str() == Some(from))
+                .and_then(|contact| contact["profile"]["name"].as_str());
+            if let Some(msg) = normalize_message(account_id, api_base, message, contact_name) {
+                events.push(WebhookEvent::Message(Box::new(msg)));
+            }
+        }
+
+        for status in value["statuses"].as_array().into_iter().flatten() {
+            events.push(WebhookEvent::Status {
+                message_id: status["id"].as_str().unwrap_or_default().to_string(),
+                recipient: status["recipient_id"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                status: status["status"].as_str().unwrap_or_default().to_string(),
+                errors: status["errors"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|error| {
+                        (
+                            error["code"].as_i64(),
+                            error["title"]
+                                .as_str()
+                                .or_else(|| error["message"].as_str())
+                                .unwrap_or_default()
+                                .to_string(),
+                        )
+                    })
+                    .collect(),
+            });
+        }
+    }
+    events
+}
+
+/// Convert one inbound Cloud API message into a [`NormalizedMessage`].
+///
+/// Interactive (button/list) and template button replies become their
+/// visible title; media is referenced by its Graph media id URL.
+fn normalize_message(
+    account_id: &str,
+    api_base: &str,
+    message: &Value,
+    contact_name: Option<&str>,
+) -> Option<NormalizedMessage> {
+    let from = message["from"].as_str()?;
+    let id = message["id"].as_str()?;
+    let kind = message["type"].as_str()?;
+
+    let mut attachments = Vec::new();
+    let text = match kind {
+        "text" => message["text"]["body"].as_str()?.to_string(),
+        "interactive" => {
+            let interactive = &message["interactive"];
+            let reply = interactive
+                .get("button_reply")
+                .or_else(|| interactive.get("list_reply"))?;
+            reply["title"].as_str()?.to_string()
+        }
+        "button" => message["button"]["text"].as_str()?.to_string(),
+        "image" | "video" | "audio" | "document" | "sticker" => {
+            let media = &message[kind];
+            let media_id = media["id"].as_str()?;
+            attachments.push(NormalizedAttachment {
+                mime_type: media["mime_type"].as_str().map(str::to_string),
+                url: Some(format!("{api_base}/{media_id}")),
+                data: None,
+                filename: media["filename"].as_str().map(str::to_string),
+                size: None,
+            });
+            media["caption"].as_str().unwrap_or_default().to_string()
+        }
+        other => {
+            debug!(kind = %other, "Ignoring unsupported WhatsApp message type");
+            return None;
+        }
+    };
+
+    let timestamp = message["timestamp"]
+        .as_str()
+        .and_then(|ts| ts.parse::<i64>().ok())
+        .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+        .unwrap_or_else(chrono::Utc::now)
+        .to_rfc3339();
+
+    Some(NormalizedMessage {
+        id: id.to_string(),
+        channel: "whatsapp".to_string(),
+        account_id: account_id.to_string(),
+        chat_id: from.to_string(),
+        chat_name: contact_name.map(str::to_string),
+        chat_type: ChatType::Dm,
+        sender: NormalizedSender {
+            id: from.to_string(),
+            name: contact_name.unwrap_or(from).to_string(),
+            is_bot: false,
+            roles: Vec::new(),
+        },
+        text,
+        attachments,
+        reply_to_id: message["context"]["id"].as_str().map(str::to_string),
+        thread_id: None,
+        mentioned: true,
+        timestamp,
+        raw: Some(message.clone()),
+    })
 }
 
-/// Convenience function called by the top-level `send_message` dispatcher.
-pub(crate) async fn send_message(config: &Config, to: &str, message: &str) -> Result<()> {
-    let channel = WhatsAppChannel::new(config);
-    channel.send_message(to, message).await
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::{Admission, Verdict};
+    use crate::config::DmPolicy;
+    use axum::body::Bytes;
+    use axum::http::HeaderMap;
+    use wiremock::matchers::{body_partial_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn delivery(messages: Value, statuses: Value) -> Value {
+        json!({
+            "object": "whatsapp_business_account",
+            "entry": [{
+                "id": "WABA",
+                "changes": [{
+                    "field": "messages",
+                    "value": {
+                        "messaging_product": "whatsapp",
+                        "metadata": { "display_phone_number": "15550000000", "phone_number_id": "PNID" },
+                        "contacts": [{ "profile": { "name": "Alice" }, "wa_id": "15551112222" }],
+                        "messages": messages,
+                        "statuses": statuses,
+                    }
+                }]
+            }]
+        })
+    }
+
+    fn parse(payload: &Value) -> Vec<WebhookEvent> {
+        parse_webhook("default", "http://graph/v21.0", Some("PNID"), payload)
+    }
+
+    fn channel_with(
+        api_url: &str,
+        configure: impl FnOnce(&mut WhatsAppAccountConfig),
+    ) -> WhatsAppChannel {
+        let mut config = Config::default();
+        let account = &mut config.channels.whatsapp.default_account;
+        account.access_token = Some("tok".to_string());
+        account.phone_number_id = Some("PNID".to_string());
+        account.api_url = Some(api_url.to_string());
+        account.app_secret = Some("secret".to_string());
+        account.verify_token = Some("verify-me".to_string());
+        configure(account);
+        WhatsAppChannel::new(&config)
+    }
+
+    fn request(
+        method: Method,
+        query: Option<&str>,
+        headers: HeaderMap,
+        body: &[u8],
+    ) -> WebhookRequest {
+        WebhookRequest {
+            method,
+            path: "webhook".to_string(),
+            query: query.map(str::to_string),
+            headers,
+            body: Bytes::copy_from_slice(body),
+        }
+    }
+
+    fn signed(body: &[u8], secret: &str) -> HeaderMap {
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body);
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            SIGNATURE_HEADER,
+            format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+                .parse()
+                .unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn parse_text_media_and_interactive_messages() {
+        let payload = delivery(
+            json!([
+                { "from": "15551112222", "id": "wamid.1", "timestamp": "1700000000", "type": "text",
+                  "text": { "body": "hello" }, "context": { "from": "15550000000", "id": "wamid.0" } },
+                { "from": "15551112222", "id": "wamid.2", "timestamp": "1700000001", "type": "image",
+                  "image": { "id": "MEDIA1", "mime_type": "image/jpeg", "caption": "look" } },
+                { "from": "15551112222", "id": "wamid.3", "timestamp": "1700000002", "type": "interactive",
+                  "interactive": { "type": "button_reply", "button_reply": { "id": "yes", "title": "Yes please" } } },
+                { "from": "15551112222", "id": "wamid.4", "timestamp": "1700000003", "type": "location",
+                  "location": { "latitude": 1.0, "longitude": 2.0 } },
+            ]),
+            json!([]),
+        );
+        let messages: Vec<NormalizedMessage> = parse(&payload)
+            .into_iter()
+            .filter_map(|event| match event {
+                WebhookEvent::Message(msg) => Some(*msg),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(messages.len(), 3);
+
+        assert_eq!(messages[0].text, "hello");
+        assert_eq!(messages[0].sender.name, "Alice");
+        assert_eq!(messages[0].chat_type, ChatType::Dm);
+        assert_eq!(messages[0].reply_to_id.as_deref(), Some("wamid.0"));
+
+        assert_eq!(messages[1].text, "look");
+        assert_eq!(
+            messages[1].attachments[0].url.as_deref(),
+            Some("http://graph/v21.0/MEDIA1")
+        );
+
+        assert_eq!(messages[2].text, "Yes please");
+    }
+
+    #[test]
+    fn parse_reactions_statuses_and_other_numbers() {
+        let payload = delivery(
+            json!([{ "from": "15551112222", "id": "wamid.9", "type": "reaction",
+                     "reaction": { "message_id": "wamid.1", "emoji": "👍" } }]),
+            json!([{ "id": "wamid.out", "recipient_id": "15551112222", "status": "failed",
+                     "errors": [{ "code": 131047, "title": "Re-engagement message" }] }]),
+        );
+        let events = parse(&payload);
+        assert!(matches!(
+            &events[0],
+            WebhookEvent::Reaction { emoji, message_id, .. } if emoji == "👍" && message_id == "wamid.1"
+        ));
+        assert!(matches!(
+            &events[1],
+            WebhookEvent::Status { errors, .. } if errors[0].0 == Some(SESSION_WINDOW_ERROR_CODE)
+        ));
+
+        assert!(parse_webhook("default", "http://graph", Some("OTHER"), &payload).is_empty());
+    }
+
+    #[test]
+    fn verification_handshake() {
+        let channel = channel_with("http://graph", |_| {});
+        let ok = channel.account.verify_subscription(Some(
+            "hub.mode=subscribe&hub.verify_token=verify-me&hub.challenge=1158201444",
+        ));
+        assert_eq!(ok.status, StatusCode::OK);
+        assert_eq!(ok.body, b"1158201444");
+
+        let bad = channel.account.verify_subscription(Some(
+            "hub.mode=subscribe&hub.verify_token=wrong&hub.challenge=1",
+        ));
+        assert_eq!(bad.status, StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn webhook_rejects_bad_signatures() {
+        let channel = channel_with("http://graph", |_| {});
+        let body = br#"{"object":"whatsapp_business_account","entry":[]}"#;
+
+        assert!(channel.account.signature_valid(&request(
+            Method::POST,
+            None,
+            signed(body, "secret"),
+            body
+        )));
+        assert!(!channel.account.signature_valid(&request(
+            Method::POST,
+            None,
+            signed(body, "other"),
+            body
+        )));
+        assert!(!channel.account.signature_valid(&request(
+            Method::POST,
+            None,
+            HeaderMap::new(),
+            body
+        )));
+    }
+
+    #[test]
+    fn admission_accepts_e164_allowlist() {
+        let mut config = Config::default();
+        config.channels.whatsapp.default_account.dm_policy = Some(DmPolicy::Allowlist);
+        config.channels.whatsapp.default_account.allow_from =
+            Some(vec!["+15551112222".to_string()]);
+        let admission = Admission::new();
+        let payload = delivery(
+            json!([{ "from": "15551112222", "id": "wamid.1", "type": "text", "text": { "body": "hi" } }]),
+            json!([]),
+        );
+        let Some(WebhookEvent::Message(msg)) = parse(&payload).pop() else {
+            panic!("expected a message");
+        };
+        assert_eq!(admission.check(&config, &msg), Verdict::Admit);
+
+        let mut stranger = msg.clone();
+        stranger.sender.id = "15559999999".to_string();
+        assert_ne!(admission.check(&config, &stranger), Verdict::Admit);
+    }
+
+    #[tokio::test]
+    async fn send_text_template_media_and_reaction() {
+        let server = MockServer::start().await;
+        let ok = ResponseTemplate::new(200)
+            .set_body_json(json!({ "messages": [{ "id": "wamid.out" }] }));
+        for body in [
+            json!({ "type": "text", "to": "15551112222", "text": { "body": "hi" } }),
+            json!({ "type": "template", "template": { "name": "hello_world", "language": { "code": "en_US" } } }),
+            json!({ "type": "document", "document": { "link": "https://x/y.pdf", "filename": "y.pdf" } }),
+            json!({ "type": "reaction", "reaction": { "message_id": "wamid.1", "emoji": "✅" } }),
+        ] {
+            Mock::given(method("POST"))
+                .and(path("/v21.0/PNID/messages"))
+                .and(header("authorization", "Bearer tok"))
+                .and(body_partial_json(body))
+                .respond_with(ok.clone())
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let channel = channel_with(&server.uri(), |_| {});
+        channel.send_message("+15551112222", "hi").await.unwrap();
+        let id = channel
+            .send_template("15551112222", "hello_world", "en_US", None)
+            .await
+            .unwrap();
+        assert_eq!(id, "wamid.out");
+        channel
+            .send_media(
+                "15551112222",
+                WhatsAppMediaKind::Document,
+                "https://x/y.pdf",
+                None,
+                Some("y.pdf"),
+            )
+            .await
+            .unwrap();
+        channel.react("15551112222", "wamid.1", "✅").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn structured_sends_use_captions_and_buttons() {
+        let server = MockServer::start().await;
+        let ok = ResponseTemplate::new(200)
+            .set_body_json(json!({ "messages": [{ "id": "wamid.out" }] }));
+        for body in [
+            json!({ "type": "image", "image": { "link": "https://x/a.png", "caption": "look" } }),
+            json!({ "type": "interactive", "context": { "message_id": "wamid.1" }, "interactive": {
+                "type": "button", "body": { "text": "Approve?" },
+                "action": { "buttons": [{ "type": "reply", "reply": { "id": "yes", "title": "Yes" } }] } } }),
+        ] {
+            Mock::given(method("POST"))
+                .and(path("/v21.0/PNID/messages"))
+                .and(body_partial_json(body))
+                .respond_with(ok.clone())
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let channel = channel_with(&server.uri(), |_| {});
+        let mut photo = NormalizedOutbound::text("+15551112222", "look");
+        photo.attachments.push(NormalizedAttachment {
+            mime_type: Some("image/png".to_string()),
+            url: Some("https://x/a.png".to_string()),
+            data: None,
+            filename: None,
+            size: None,
+        });
+        let id = ChannelPlugin::send(&channel, &photo).await.unwrap();
+        assert_eq!(id.as_deref(), Some("wamid.out"));
+
+        let mut question = NormalizedOutbound::text("15551112222", "Approve?");
+        question.reply_to_id = Some("wamid.1".to_string());
+        question.buttons.push(OutboundButton {
+            label: "Yes".to_string(),
+            value: "yes".to_string(),
+        });
+        ChannelPlugin::send(&channel, &question).await.unwrap();
+
+        assert!(channel
+            .edit_message("15551112222", "wamid.out", "edited")
+            .await
+            .unwrap_err()
+            .downcast_ref::<crate::channels::UnsupportedCapability>()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn voice_notes_are_uploaded_and_media_downloaded() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v21.0/PNID/media"))
+            .and(header("authorization", "Bearer tok"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "media.1" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v21.0/PNID/messages"))
+            .and(body_partial_json(
+                json!({ "type": "audio", "audio": { "id": "media.1" } }),
+            ))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({ "messages": [{ "id": "wamid.out" }] })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v21.0/media.2"))
+            .and(header("authorization", "Bearer tok"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({ "url": format!("{}/download/media.2", server.uri()) })),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/download/media.2"))
+            .and(header("authorization", "Bearer tok"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(b"OggS".to_vec()))
+            .mount(&server)
+            .await;
+
+        let channel = channel_with(&server.uri(), |_| {});
+        let mut voice = NormalizedOutbound::text("15551112222", "");
+        voice.attachments.push(NormalizedAttachment {
+            mime_type: Some("audio/ogg".to_string()),
+            url: None,
+            data: Some(b"OggS".to_vec()),
+            filename: Some("voice.ogg".to_string()),
+            size: Some(4),
+        });
+        let id = ChannelPlugin::send(&channel, &voice).await.unwrap();
+        assert_eq!(id.as_deref(), Some("wamid.out"));
+
+        let inbound = NormalizedAttachment {
+            mime_type: Some("audio/ogg; codecs=opus".to_string()),
+            url: Some(format!("{}/v21.0/media.2", server.uri())),
+            data: None,
+            filename: None,
+            size: None,
+        };
+        let audio = channel.fetch_attachment(&inbound).await.unwrap();
+        assert_eq!(audio, b"OggS");
+    }
+
+    #[tokio::test]
+    async fn session_window_errors_are_typed() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v21.0/PNID/messages"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+                "error": {
+                    "message": "(#131047) Re-engagement message",
+                    "code": 131047,
+                    "error_data": { "details": "Message failed to send because more than 24 hours have passed" }
+                }
+            })))
+            .mount(&server)
+            .await;
+
+        let channel = channel_with(&server.uri(), |_| {});
+        let err = channel
+            .send_message("15551112222", "late")
+            .await
+            .unwrap_err();
+        let api = err.downcast_ref::<WhatsAppApiError>().unwrap();
+        assert!(api.is_session_window_error());
+        assert!(err.to_string().contains("24-hour session window"));
+    }
 }
@@ -1,10 +1,20 @@
-use super::plugin::{ChannelCapability, ChannelMeta, ChannelPlugin};
+use super::normalize::{
+    ChatType, NormalizedAttachment, NormalizedMessage, NormalizedOutbound, NormalizedSender,
+};
+use super::plugin::{
+    ChannelCapability, ChannelMeta, ChannelPlugin, WebhookRequest, WebhookResponse,
+};
+use super::split_text;
+use super::webhook::{self, ReplayGuard, WebhookRoute};
+use crate::config::{Config, ZaloConfig};
 use crate::gateway::GatewayState;
 
 use anyhow::Result;
 use async_trait::async_trait;
+use axum::http::StatusCode;
 use reqwest::Client;
-use tracing::{info, warn};
+use serde_json::Value;
+use tracing::{debug, info, warn};
 
 // ============================================================================
 // Zalo OA Channel Implementation
@@ -19,38 +29,58 @@ use tracing::{info, warn};
 /// (customer service messages) or `/v3.0/oa/message` (transactional).
 ///
 /// API docs: <https://developers.zalo.me/docs/official-account>
+///
+/// Incoming events arrive on `POST /channels/zalo/<webhookPath>` (the
+/// webhook URL set in the Zalo Developer portal) and are verified with
+/// `X-ZEvent-Signature`.
 pub struct ZaloChannel {
-    /// Zalo OA access token (obtained via OAuth2 flow).
-    oa_access_token: Option<String>,
-    /// Whether this channel is enabled.
-    enabled: Option<bool>,
+    enabled: bool,
+    config: ZaloConfig,
     /// HTTP client for API calls.
     client: Client,
+    /// Message ids already handled, and timestamp freshness checks.
+    replay: ReplayGuard,
 }
 
 /// Zalo OA API base URL.
 const ZALO_OA_API_BASE: &str = "https://openapi.zalo.me/v3.0/oa";
+const DEFAULT_WEBHOOK_PATH: &str = "webhook";
+const SIGNATURE_HEADER: &str = "x-zevent-signature";
+/// Maximum characters in one customer service message.
+const ZALO_MAX_TEXT_CHARS: usize = 2000;
 
 impl ZaloChannel {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
+        let zalo = config.channels.zalo.clone().unwrap_or_default();
         Self {
-            oa_access_token: None,
-            enabled: None,
+            enabled: zalo.enabled.unwrap_or(false),
+            config: zalo,
             client: Client::new(),
+            replay: ReplayGuard::default(),
         }
     }
 
-    /// Create a configured Zalo OA channel.
-    pub fn with_config(oa_access_token: String) -> Self {
-        Self {
-            oa_access_token: Some(oa_access_token),
-            enabled: Some(true),
-            client: Client::new(),
-        }
+    fn webhook_path(&self) -> &str {
+        webhook::webhook_path(self.config.webhook_path.as_deref(), DEFAULT_WEBHOOK_PATH)
     }
 
-    fn is_enabled(&self) -> bool {
-        self.enabled.unwrap_or(false)
+    /// Validate `X-ZEvent-Signature`:
+    /// `mac=` + hex `sha256(app_id + body + timestamp + oa_secret_key)`.
+    fn signature_valid(&self, request: &WebhookRequest, timestamp: &str) -> bool {
+        let (Some(app_id), Some(secret), Some(signature)) = (
+            self.config.app_id.as_deref(),
+            self.config.oa_secret_key.as_deref(),
+            request.header(SIGNATURE_HEADER),
+        ) else {
+            return false;
+        };
+        let digest = webhook::sha256(&[
+            app_id.as_bytes(),
+            &request.body,
+            timestamp.as_bytes(),
+            secret.as_bytes(),
+        ]);
+        webhook::digest_matches_hex(&digest, signature.trim_start_matches("mac="))
     }
 }
 
@@ -64,7 +94,7 @@ impl ChannelPlugin for ZaloChannel {
         ChannelMeta {
             name: "Zalo".to_string(),
             description: "Zalo Official Account messaging channel".to_string(),
-            enabled: self.is_enabled(),
+            enabled: self.enabled,
             multi_account: false,
         }
     }
@@ -78,20 +108,31 @@ impl ChannelPlugin for ZaloChannel {
         ]
     }
 
+    fn webhook_routes(&self) -> Vec<WebhookRoute> {
+        if !self.enabled {
+            return Vec::new();
+        }
+        vec![WebhookRoute::post(self.webhook_path())]
+    }
+
     async fn start_account(&self, _state: &GatewayState) -> Result<()> {
-        if !self.is_enabled() {
+        if !self.enabled {
             return Ok(());
         }
 
-        if self.oa_access_token.is_none() {
+        if self.config.oa_access_token.is_none() {
             warn!("Zalo OA channel enabled but no oa_access_token configured");
             return Ok(());
         }
 
-        info!("Zalo OA channel starting");
+        if self.config.app_id.is_none() || self.config.oa_secret_key.is_none() {
+            warn!("Zalo OA: app_id or oa_secret_key not configured; webhooks will be rejected");
+        }
+
+        info!(path = %self.webhook_path(), "Zalo OA channel starting");
 
         // Verify the token by calling the OA info endpoint.
-        if let Some(token) = &self.oa_access_token {
+        if let Some(token) = &self.config.oa_access_token {
             let url = format!("{}/getoa", ZALO_OA_API_BASE);
             match self
                 .client
@@ -119,42 +160,88 @@ impl ChannelPlugin for ZaloChannel {
             }
         }
 
-        // TODO: Register a webhook endpoint for incoming Zalo messages.
-        // Zalo sends events to the webhook URL configured in the Zalo Developer portal.
-
         Ok(())
     }
 
     async fn stop_account(&self) -> Result<()> {
-        if self.is_enabled() {
+        if self.enabled {
             info!("Zalo OA channel stopping");
         }
         Ok(())
     }
 
     async fn send_message(&self, to: &str, message: &str) -> Result<()> {
-        let token = self
-            .oa_access_token
-            .as_deref()
-            .ok_or_else(|| anyhow::anyhow!("Zalo OA access_token not configured"))?;
-
         // `to` is a Zalo user ID (follower of the OA).
-        // Use the customer service message endpoint for conversational replies.
-        let url = format!("{}/message/cs", ZALO_OA_API_BASE);
+        info!(user_id = %to, "Zalo OA: sending message");
+        send_text(
+            &self.client,
+            self.config.oa_access_token.as_deref(),
+            to,
+            message,
+        )
+        .await
+    }
+
+    async fn handle_webhook(
+        &self,
+        state: &GatewayState,
+        request: WebhookRequest,
+    ) -> Result<WebhookResponse> {
+        if !self.enabled {
+            return Ok(WebhookResponse::not_found());
+        }
+        if let Some(response) = webhook::route_check(&self.webhook_routes(), &request) {
+            return Ok(response);
+        }
+        let Ok(event) = serde_json::from_slice::<Value>(&request.body) else {
+            return Ok(WebhookResponse::status(StatusCode::BAD_REQUEST));
+        };
+        let timestamp = event["timestamp"].as_str().unwrap_or_default();
+        if !self.signature_valid(&request, timestamp) {
+            warn!("Zalo OA webhook request with invalid signature");
+            return Ok(WebhookResponse::status(StatusCode::UNAUTHORIZED));
+        }
+        let fresh = timestamp
+            .parse::<i64>()
+            .is_ok_and(|ms| self.replay.timestamp_fresh(ms / 1000));
+        if !fresh {
+            warn!("Zalo OA webhook request with stale timestamp");
+            return Ok(WebhookResponse::status(StatusCode::UNAUTHORIZED));
+        }
+
+        let Some(msg) = normalize_event(&event) else {
+            return Ok(WebhookResponse::ok());
+        };
+        if !self.replay.first_delivery(&msg.id) {
+            debug!(msg_id = %msg.id, "Skipping redelivered Zalo OA event");
+            return Ok(WebhookResponse::ok());
+        }
+
+        webhook::spawn_dispatch(state, msg, |msg, text| {
+            NormalizedOutbound::text(msg.chat_id.clone(), text)
+        });
+        Ok(WebhookResponse::ok())
+    }
+}
+
+/// Send a customer service text message, split to Zalo's length limit.
+async fn send_text(client: &Client, token: Option<&str>, to: &str, message: &str) -> Result<()> {
+    let token = token.ok_or_else(|| anyhow::anyhow!("Zalo OA access_token not configured"))?;
 
+    // Use the customer service message endpoint for conversational replies.
+    let url = format!("{}/message/cs", ZALO_OA_API_BASE);
+
+    for chunk in split_text(message, ZALO_MAX_TEXT_CHARS) {
         let body = serde_json::json!({
             "recipient": {
                 "user_id": to,
             },
             "message": {
-                "text": message,
+                "text": chunk,
             },
         });
 
-        info!(user_id = %to, "Zalo OA: sending message");
-
-        let resp = self
-            .client
+        let resp = client
             .post(&url)
             .header("access_token", token)
             .json(&body)
@@ -174,7 +261,128 @@ impl ChannelPlugin for ZaloChannel {
             let msg = result["message"].as_str().unwrap_or("unknown error");
             anyhow::bail!("Zalo OA send error (code {}): {}", error, msg);
         }
+    }
 
-        Ok(())
+    Ok(())
+}
+
+// ============================================================================
+// Event Parsing
+// ============================================================================
+
+/// Convert a `user_send_*` event into a [`NormalizedMessage`].
+fn normalize_event(event: &Value) -> Option<NormalizedMessage> {
+    if !event["event_name"].as_str()?.starts_with("user_send_") {
+        return None;
+    }
+    let message = &event["message"];
+    let sender_id = event["sender"]["id"].as_str()?.to_string();
+
+    let attachments = message["attachments"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|a| {
+            let url = a["payload"]["url"].as_str()?;
+            let mime_type = match a["type"].as_str() {
+                Some("image") | Some("gif") => Some("image/*".to_string()),
+                Some("audio") => Some("audio/*".to_string()),
+                Some("video") => Some("video/*".to_string()),
+                _ => None,
+            };
+            Some(NormalizedAttachment {
+                mime_type,
+                url: Some(url.to_string()),
+                data: None,
+                filename: a["payload"]["name"].as_str().map(str::to_string),
+                size: a["payload"]["size"].as_str().and_then(|s| s.parse().ok()),
+            })
+        })
+        .collect();
+
+    Some(NormalizedMessage {
+        id: message["msg_id"].as_str()?.to_string(),
+        channel: "zalo".to_string(),
+        account_id: "default".to_string(),
+        chat_id: sender_id.clone(),
+        chat_name: None,
+        chat_type: ChatType::Dm,
+        sender: NormalizedSender {
+            id: sender_id.clone(),
+            name: sender_id,
+            is_bot: false,
+            roles: Vec::new(),
+        },
+        text: message["text"].as_str().unwrap_or_default().to_string(),
+        attachments,
+        reply_to_id: None,
+        thread_id: None,
+        mentioned: true,
+        timestamp: webhook::timestamp_from_millis(
+            event["timestamp"]
+                .as_str()
+                .and_then(|t| t.parse().ok())
+                .unwrap_or_default(),
+        ),
+        raw: Some(event.clone()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::webhook::tests::request;
+    use axum::http::Method;
+
+    fn channel() -> ZaloChannel {
+        let mut config = Config::default();
+        config.channels.zalo = Some(ZaloConfig {
+            enabled: Some(true),
+            app_id: Some("360846524940903967".to_string()),
+            oa_secret_key: Some("oa-secret".to_string()),
+            ..Default::default()
+        });
+        ZaloChannel::new(&config)
+    }
+
+    #[test]
+    fn signature_covers_app_id_body_timestamp_and_secret() {
+        let channel = channel();
+        let timestamp = chrono::Utc::now().timestamp_millis().to_string();
+        let body = serde_json::json!({
+            "app_id": "360846524940903967",
+            "event_name": "user_send_text",
+            "sender": { "id": "246845883529197922" },
+            "recipient": { "id": "388613280878808645" },
+            "message": { "text": "xin chào", "msg_id": "96d3cdf3af150460909" },
+            "timestamp": timestamp,
+        })
+        .to_string();
+        let mac = hex::encode(webhook::sha256(&[
+            b"360846524940903967",
+            body.as_bytes(),
+            timestamp.as_bytes(),
+            b"oa-secret",
+        ]));
+
+        let signed = request(
+            Method::POST,
+            "webhook",
+            &[(SIGNATURE_HEADER, &format!("mac={mac}"))],
+            body.as_bytes(),
+        );
+        assert!(channel.signature_valid(&signed, &timestamp));
+        assert!(!channel.signature_valid(&signed, "0"));
+
+        let msg = normalize_event(&serde_json::from_str(&body).unwrap()).unwrap();
+        assert_eq!(msg.id, "96d3cdf3af150460909");
+        assert_eq!(msg.chat_id, "246845883529197922");
+        assert_eq!(msg.text, "xin chào");
+    }
+
+    #[test]
+    fn ignores_non_message_events() {
+        let follow = serde_json::json!({ "event_name": "follow", "follower": { "id": "1" } });
+        assert!(normalize_event(&follow).is_none());
     }
 }
@@ -1,10 +1,17 @@
-use super::plugin::{ChannelCapability, ChannelMeta, ChannelPlugin};
+use super::normalize::{ChatType, NormalizedMessage, NormalizedOutbound, NormalizedSender};
+use super::plugin::{
+    ChannelCapability, ChannelMeta, ChannelPlugin, WebhookRequest, WebhookResponse,
+};
+use super::webhook::{self, ReplayGuard, WebhookRoute};
+use crate::config::{Config, ZaloUserConfig};
 use crate::gateway::GatewayState;
 
 use anyhow::Result;
 use async_trait::async_trait;
+use axum::http::StatusCode;
 use reqwest::Client;
-use tracing::{info, warn};
+use serde_json::Value;
+use tracing::{debug, info, warn};
 
 // ============================================================================
 // Zalo Personal (User) Channel Implementation
@@ -22,38 +29,65 @@ use tracing::{info, warn};
 ///
 /// Note: The Zalo Social API has stricter rate limits and usage policies
 /// compared to the OA API. User-level messaging may require explicit consent.
+///
+/// The Social API has no inbound webhooks, so incoming messages are
+/// forwarded by a bridge (a client logged in to the account) to
+/// `POST /channels/zalouser/<webhookPath>`, signed with the shared
+/// `webhookSecret`: `X-Zalouser-Signature` is `sha256=` + hex
+/// `HMAC-SHA256(secret, "<X-Zalouser-Timestamp>." + body)`. The body is
+/// `{"msgId", "threadId", "threadType": "user"|"group", "senderId",
+/// "senderName", "text", "timestamp"}`; only direct (`user`) threads are
+/// answered, since the Social API cannot post to groups.
 pub struct ZaloUserChannel {
-    /// Zalo user access token (obtained via Zalo Login OAuth2 flow).
-    user_access_token: Option<String>,
-    /// Whether this channel is enabled.
-    enabled: Option<bool>,
+    enabled: bool,
+    config: ZaloUserConfig,
     /// HTTP client for API calls.
     client: Client,
+    /// Message ids already handled, and timestamp freshness checks.
+    replay: ReplayGuard,
 }
 
 /// Zalo Social API base URL.
 const ZALO_SOCIAL_API_BASE: &str = "https://openapi.zalo.me/v2.0";
+const DEFAULT_WEBHOOK_PATH: &str = "webhook";
+const SIGNATURE_HEADER: &str = "x-zalouser-signature";
+const TIMESTAMP_HEADER: &str = "x-zalouser-timestamp";
 
 impl ZaloUserChannel {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
+        let zalouser = config.channels.zalouser.clone().unwrap_or_default();
         Self {
-            user_access_token: None,
-            enabled: None,
+            enabled: zalouser.enabled.unwrap_or(false),
+            config: zalouser,
             client: Client::new(),
+            replay: ReplayGuard::default(),
         }
     }
 
-    /// Create a configured Zalo Personal channel.
-    pub fn with_config(user_access_token: String) -> Self {
-        Self {
-            user_access_token: Some(user_access_token),
-            enabled: Some(true),
-            client: Client::new(),
-        }
+    fn webhook_path(&self) -> &str {
+        webhook::webhook_path(self.config.webhook_path.as_deref(), DEFAULT_WEBHOOK_PATH)
     }
 
-    fn is_enabled(&self) -> bool {
-        self.enabled.unwrap_or(false)
+    /// Validate the bridge signature and timestamp.
+    fn signature_valid(&self, request: &WebhookRequest) -> bool {
+        let (Some(secret), Some(signature), Some(timestamp)) = (
+            self.config.webhook_secret.as_deref(),
+            request.header(SIGNATURE_HEADER),
+            request.header(TIMESTAMP_HEADER),
+        ) else {
+            return false;
+        };
+        let Some(signature) = signature.strip_prefix("sha256=") else {
+            return false;
+        };
+        let digest = webhook::hmac_sha256(
+            secret.as_bytes(),
+            &[timestamp.as_bytes(), b".", &request.body],
+        );
+        webhook::digest_matches_hex(&digest, signature)
+            && timestamp
+                .parse()
+                .is_ok_and(|ts| self.replay.timestamp_fresh(ts))
     }
 }
 
@@ -67,7 +101,7 @@ impl ChannelPlugin for ZaloUserChannel {
         ChannelMeta {
             name: "Zalo Personal".to_string(),
             description: "Zalo personal messaging channel via Social API".to_string(),
-            enabled: self.is_enabled(),
+            enabled: self.enabled,
             multi_account: false,
         }
     }
@@ -80,20 +114,30 @@ impl ChannelPlugin for ZaloUserChannel {
         ]
     }
 
+    fn webhook_routes(&self) -> Vec<WebhookRoute> {
+        if !self.enabled {
+            return Vec::new();
+        }
+        vec![WebhookRoute::post(self.webhook_path())]
+    }
+
     async fn start_account(&self, _state: &GatewayState) -> Result<()> {
-        if !self.is_enabled() {
+        if !self.enabled {
             return Ok(());
         }
 
-        if self.user_access_token.is_none() {
+        if self.config.user_access_token.is_none() {
             warn!("Zalo Personal channel enabled but no user_access_token configured");
             return Ok(());
         }
+        if self.config.webhook_secret.is_none() {
+            warn!("Zalo Personal: no webhook_secret configured; bridge events will be rejected");
+        }
 
-        info!("Zalo Personal channel starting");
+        info!(path = %self.webhook_path(), "Zalo Personal channel starting");
 
         // Verify the token by calling the user profile endpoint.
-        if let Some(token) = &self.user_access_token {
+        if let Some(token) = &self.config.user_access_token {
             let url = format!("{}/me?fields=id,name", ZALO_SOCIAL_API_BASE);
             match self
                 .client
@@ -128,50 +172,193 @@ impl ChannelPlugin for ZaloUserChannel {
     }
 
     async fn stop_account(&self) -> Result<()> {
-        if self.is_enabled() {
+        if self.enabled {
             info!("Zalo Personal channel stopping");
         }
         Ok(())
     }
 
     async fn send_message(&self, to: &str, message: &str) -> Result<()> {
-        let token = self
-            .user_access_token
-            .as_deref()
-            .ok_or_else(|| anyhow::anyhow!("Zalo Personal user_access_token not configured"))?;
-
         // `to` is a Zalo friend's user ID.
-        let url = format!("{}/apigraph/v2/me/message", ZALO_SOCIAL_API_BASE);
-
-        let body = serde_json::json!({
-            "to": to,
-            "message": message,
-        });
-
         info!(to = %to, "Zalo Personal: sending message");
+        send_text(
+            &self.client,
+            self.config.user_access_token.as_deref(),
+            to,
+            message,
+        )
+        .await
+    }
 
-        let resp = self
-            .client
-            .post(&url)
-            .header("access_token", token)
-            .json(&body)
-            .send()
-            .await?;
-
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let text = resp.text().await.unwrap_or_default();
-            anyhow::bail!("Zalo Personal send message failed ({}): {}", status, text);
+    async fn handle_webhook(
+        &self,
+        state: &GatewayState,
+        request: WebhookRequest,
+    ) -> Result<WebhookResponse> {
+        if !self.enabled {
+            return Ok(WebhookResponse::not_found());
+        }
+        if let Some(response) = webhook::route_check(&self.webhook_routes(), &request) {
+            return Ok(response);
         }
+        if !self.signature_valid(&request) {
+            warn!("Zalo Personal webhook request with invalid signature");
+            return Ok(WebhookResponse::status(StatusCode::UNAUTHORIZED));
+        }
+        let Ok(event) = serde_json::from_slice::<Value>(&request.body) else {
+            return Ok(WebhookResponse::status(StatusCode::BAD_REQUEST));
+        };
 
-        // Check the Zalo API-level error code.
-        let result: serde_json::Value = resp.json().await?;
-        let error = result["error"].as_i64().unwrap_or(-1);
-        if error != 0 {
-            let msg = result["message"].as_str().unwrap_or("unknown error");
-            anyhow::bail!("Zalo Personal send error (code {}): {}", error, msg);
+        let Some(msg) = normalize_event(&event) else {
+            return Ok(WebhookResponse::ok());
+        };
+        if !self.replay.first_delivery(&msg.id) {
+            debug!(msg_id = %msg.id, "Skipping redelivered Zalo Personal message");
+            return Ok(WebhookResponse::ok());
         }
 
-        Ok(())
+        webhook::spawn_dispatch(state, msg, |msg, text| {
+            NormalizedOutbound::text(msg.chat_id.clone(), text)
+        });
+        Ok(WebhookResponse::ok())
+    }
+}
+
+/// Send a text message to a friend via the Social API.
+async fn send_text(client: &Client, token: Option<&str>, to: &str, message: &str) -> Result<()> {
+    let token =
+        token.ok_or_else(|| anyhow::anyhow!("Zalo Personal user_access_token not configured"))?;
+
+    let url = format!("{}/apigraph/v2/me/message", ZALO_SOCIAL_API_BASE);
+
+    let body = serde_json::json!({
+        "to": to,
+        "message": message,
+    });
+
+    let resp = client
+        .post(&url)
+        .header("access_token", token)
+        .json(&body)
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        anyhow::bail!("Zalo Personal send message failed ({}): {}", status, text);
+    }
+
+    // Check the Zalo API-level error code.
+    let result: serde_json::Value = resp.json().await?;
+    let error = result["error"].as_i64().unwrap_or(-1);
+    if error != 0 {
+        let msg = result["message"].as_str().unwrap_or("unknown error");
+        anyhow::bail!("Zalo Personal send error (code {}): {}", error, msg);
+    }
+
+    Ok(())
+}
+
+/// Convert a bridge event for a direct thread into a [`NormalizedMessage`].
+fn normalize_event(event: &Value) -> Option<NormalizedMessage> {
+    if event["threadType"] != "user" {
+        debug!(thread = ?event["threadId"].as_str(), "Ignoring Zalo Personal group message");
+        return None;
+    }
+    let sender_id = event["senderId"].as_str()?.to_string();
+    let text = event["text"].as_str().unwrap_or_default();
+    if text.trim().is_empty() {
+        return None;
+    }
+
+    Some(NormalizedMessage {
+        id: event["msgId"].as_str()?.to_string(),
+        channel: "zalouser".to_string(),
+        account_id: "default".to_string(),
+        chat_id: event["threadId"].as_str().unwrap_or(&sender_id).to_string(),
+        chat_name: None,
+        chat_type: ChatType::Dm,
+        sender: NormalizedSender {
+            id: sender_id.clone(),
+            name: event["senderName"]
+                .as_str()
+                .unwrap_or(&sender_id)
+                .to_string(),
+            is_bot: false,
+            roles: Vec::new(),
+        },
+        text: text.to_string(),
+        attachments: Vec::new(),
+        reply_to_id: None,
+        thread_id: None,
+        mentioned: true,
+        timestamp: webhook::timestamp_from_millis(event["timestamp"].as_i64().unwrap_or_default()),
+        raw: Some(event.clone()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::webhook::tests::request;
+    use axum::http::Method;
+
+    fn channel() -> ZaloUserChannel {
+        let mut config = Config::default();
+        config.channels.zalouser = Some(ZaloUserConfig {
+            enabled: Some(true),
+            webhook_secret: Some("bridge-secret".to_string()),
+            ..Default::default()
+        });
+        ZaloUserChannel::new(&config)
+    }
+
+    fn signed(body: &str, timestamp: i64, secret: &[u8]) -> WebhookRequest {
+        let timestamp = timestamp.to_string();
+        let signature = format!(
+            "sha256={}",
+            hex::encode(webhook::hmac_sha256(
+                secret,
+                &[timestamp.as_bytes(), b".", body.as_bytes()]
+            ))
+        );
+        request(
+            Method::POST,
+            "webhook",
+            &[
+                (SIGNATURE_HEADER, &signature),
+                (TIMESTAMP_HEADER, &timestamp),
+            ],
+            body.as_bytes(),
+        )
+    }
+
+    #[test]
+    fn bridge_requests_are_signed_and_fresh() {
+        let channel = channel();
+        let now = chrono::Utc::now().timestamp();
+        let body =
+            r#"{"msgId":"1","threadId":"42","threadType":"user","senderId":"42","text":"hi"}"#;
+        assert!(channel.signature_valid(&signed(body, now, b"bridge-secret")));
+        assert!(!channel.signature_valid(&signed(body, now, b"wrong")));
+        assert!(!channel.signature_valid(&signed(body, now - 3600, b"bridge-secret")));
+    }
+
+    #[test]
+    fn only_direct_threads_are_normalized() {
+        let dm: Value = serde_json::from_str(
+            r#"{"msgId":"1","threadId":"42","threadType":"user","senderId":"42","senderName":"Lan","text":"hi","timestamp":1700000000000}"#,
+        )
+        .unwrap();
+        let msg = normalize_event(&dm).unwrap();
+        assert_eq!(msg.chat_id, "42");
+        assert_eq!(msg.sender.name, "Lan");
+
+        let group: Value = serde_json::from_str(
+            r#"{"msgId":"2","threadId":"g1","threadType":"group","senderId":"42","text":"hi"}"#,
+        )
+        .unwrap();
+        assert!(normalize_event(&group).is_none());
     }
 }
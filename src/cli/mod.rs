@@ -43,6 +43,9 @@ pub struct SendOpts {
     pub channel: String,
     pub to: String,
     pub message: String,
+    /// Channel account to send from
+    #[arg(long)]
+    pub account: Option<String>,
 }
 
 #[derive(clap::Args)]
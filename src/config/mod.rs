@@ -40,6 +40,8 @@ pub struct Config {
     #[serde(default)]
     pub session: SessionConfig,
     #[serde(default)]
+    pub commands: CommandsConfig,
+    #[serde(default)]
     pub logging: LoggingConfig,
     #[serde(default)]
     pub diagnostics: DiagnosticsConfig,
@@ -139,6 +141,36 @@ impl Config {
         if let Ok(token) = std::env::var("SLACK_APP_TOKEN") {
             self.channels.slack.apply_app_token(&token);
         }
+
+        if let Ok(token) = std::env::var("WHATSAPP_API_TOKEN") {
+            self.channels.whatsapp.apply_token(&token);
+        }
+
+        if let Ok(id) = std::env::var("WHATSAPP_PHONE_NUMBER_ID") {
+            self.channels.whatsapp.apply_phone_number_id(&id);
+        }
+
+        if let Ok(token) = std::env::var("MATRIX_ACCESS_TOKEN") {
+            self.channels.matrix.apply_token(&token);
+        }
+
+        if let Ok(token) = std::env::var("MATTERMOST_BOT_TOKEN") {
+            self.channels.mattermost.apply_token(&token);
+        }
+
+        if let Ok(key) = std::env::var("NOSTR_PRIVATE_KEY") {
+            self.channels
+                .nostr
+                .get_or_insert_with(Default::default)
+                .apply_private_key(&key);
+        }
+
+        if let Ok(token) = std::env::var("TWITCH_OAUTH_TOKEN") {
+            self.channels
+                .twitch
+                .get_or_insert_with(Default::default)
+                .apply_token(&token);
+        }
     }
 }
 
@@ -156,6 +188,7 @@ impl Default for Config {
             hooks: HooksConfig::default(),
             messages: MessagesConfig::default(),
             session: SessionConfig::default(),
+            commands: CommandsConfig::default(),
             logging: LoggingConfig::default(),
             diagnostics: DiagnosticsConfig::default(),
             sandbox: SandboxConfig::default(),
@@ -364,6 +364,47 @@ pub enum ThinkingLevel {
     Xhigh,
 }
 
+impl ThinkingLevel {
+    /// Extended-thinking token budget, or `None` when thinking is off.
+    pub fn budget_tokens(self) -> Option<u64> {
+        match self {
+            Self::Off => None,
+            Self::Minimal => Some(1024),
+            Self::Low => Some(4096),
+            Self::Medium => Some(10_000),
+            Self::High => Some(16_384),
+            Self::Xhigh => Some(32_000),
+        }
+    }
+
+    /// The level's config name.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Minimal => "minimal",
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Xhigh => "xhigh",
+        }
+    }
+}
+
+impl std::str::FromStr for ThinkingLevel {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "minimal" => Ok(Self::Minimal),
+            "low" => Ok(Self::Low),
+            "medium" => Ok(Self::Medium),
+            "high" => Ok(Self::High),
+            "xhigh" => Ok(Self::Xhigh),
+            _ => Err(format!("invalid thinking level: {s}")),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum VerboseLevel {
@@ -961,6 +1002,9 @@ pub enum ReplyToMode {
 #[serde(rename_all = "camelCase")]
 pub struct ChannelDefaultsConfig {
     pub group_policy: Option<GroupPolicy>,
+    /// Reply once to senders denied by `dmPolicy` (default true); pairing
+    /// codes are always sent.
+    pub deny_reply: Option<bool>,
     pub heartbeat: Option<HeartbeatConfig>,
 }
 
@@ -983,7 +1027,21 @@ pub struct ChannelsConfig {
     pub googlechat: Option<GoogleChatConfig>,
     pub msteams: Option<MsTeamsConfig>,
     pub irc: Option<IrcConfig>,
+    pub email: Option<EmailConfig>,
+    pub twitch: Option<TwitchConfig>,
+    #[serde(default)]
+    pub matrix: MatrixConfig,
+    #[serde(default)]
+    pub mattermost: MattermostConfig,
+    pub nostr: Option<NostrConfig>,
     pub synology_chat: Option<SynologyChatConfig>,
+    pub line: Option<LineConfig>,
+    pub feishu: Option<FeishuConfig>,
+    pub zalo: Option<ZaloConfig>,
+    pub zalouser: Option<ZaloUserConfig>,
+    pub nextcloud: Option<NextcloudTalkConfig>,
+    pub bluebubbles: Option<BlueBubblesConfig>,
+    pub webchat: Option<WebChatConfig>,
     /// Extension channels loaded via plugins.
     #[serde(flatten)]
     pub extensions: HashMap<String, serde_json::Value>,
@@ -1087,6 +1145,8 @@ pub struct TelegramAccountConfig {
     pub retry: Option<OutboundRetryConfig>,
     pub network: Option<TelegramNetworkConfig>,
     pub proxy: Option<String>,
+    /// Bot API base URL (defaults to `https://api.telegram.org`).
+    pub api_url: Option<String>,
     pub webhook_url: Option<String>,
     pub webhook_secret: Option<String>,
     pub webhook_path: Option<String>,
@@ -1130,6 +1190,7 @@ impl Default for TelegramAccountConfig {
             retry: None,
             network: None,
             proxy: None,
+            api_url: None,
             webhook_url: None,
             webhook_secret: None,
             webhook_path: None,
@@ -1189,6 +1250,8 @@ pub struct DiscordGuildChannelConfig {
     pub users: Option<Vec<String>>,
     pub system_prompt: Option<String>,
     pub include_thread_starter: Option<bool>,
+    /// Reply to top-level messages in a new thread.
+    pub auto_thread: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -1254,6 +1317,8 @@ pub struct DiscordAccountConfig {
     pub config_writes: Option<bool>,
     pub enabled: Option<bool>,
     pub token: Option<String>,
+    /// Discord API origin (defaults to `https://discord.com`), e.g. an HTTP proxy.
+    pub api_url: Option<String>,
     pub allow_bots: Option<bool>,
     pub group_policy: Option<GroupPolicy>,
     #[serde(default = "default_discord_text_chunk_limit")]
@@ -1289,6 +1354,7 @@ impl Default for DiscordAccountConfig {
             config_writes: None,
             enabled: None,
             token: None,
+            api_url: None,
             allow_bots: None,
             group_policy: None,
             text_chunk_limit: 2000,
@@ -1403,6 +1469,8 @@ pub struct SlackAccountConfig {
     pub app_token: Option<String>,
     pub user_token: Option<String>,
     pub user_token_read_only: Option<bool>,
+    /// Web API base URL (defaults to `https://slack.com/api`).
+    pub api_url: Option<String>,
     pub allow_bots: Option<bool>,
     pub require_mention: Option<bool>,
     pub group_policy: Option<GroupPolicy>,
@@ -1426,6 +1494,7 @@ pub struct SlackAccountConfig {
     pub channels: Option<HashMap<String, SlackChannelConfig>>,
     pub heartbeat: Option<HeartbeatConfig>,
     pub response_prefix: Option<String>,
+    pub retry: Option<OutboundRetryConfig>,
 }
 
 impl Default for SlackAccountConfig {
@@ -1444,6 +1513,7 @@ impl Default for SlackAccountConfig {
             app_token: None,
             user_token: None,
             user_token_read_only: None,
+            api_url: None,
             allow_bots: None,
             require_mention: None,
             group_policy: None,
@@ -1466,6 +1536,7 @@ impl Default for SlackAccountConfig {
             channels: None,
             heartbeat: None,
             response_prefix: None,
+            retry: None,
         }
     }
 }
@@ -1516,6 +1587,20 @@ pub struct WhatsAppAccountConfig {
     pub markdown: Option<bool>,
     pub config_writes: Option<bool>,
     pub enabled: Option<bool>,
+    /// Cloud API access token (system user or permanent token).
+    pub access_token: Option<String>,
+    /// Business phone number id that sends and receives messages.
+    pub phone_number_id: Option<String>,
+    /// Graph API base URL (defaults to `https://graph.facebook.com`).
+    pub api_url: Option<String>,
+    /// Graph API version (defaults to `v21.0`).
+    pub api_version: Option<String>,
+    /// Token echoed back during the `hub.challenge` webhook verification.
+    pub verify_token: Option<String>,
+    /// App secret used to validate `X-Hub-Signature-256`.
+    pub app_secret: Option<String>,
+    /// Path below `/channels/whatsapp/` for the webhook (default `webhook`).
+    pub webhook_path: Option<String>,
     pub send_read_receipts: Option<bool>,
     pub message_prefix: Option<String>,
     pub response_prefix: Option<String>,
@@ -1539,6 +1624,7 @@ pub struct WhatsAppAccountConfig {
     pub debounce_ms: Option<u64>,
     pub heartbeat: Option<HeartbeatConfig>,
     pub actions: Option<WhatsAppActionConfig>,
+    pub retry: Option<OutboundRetryConfig>,
 }
 
 impl Default for WhatsAppAccountConfig {
@@ -1549,6 +1635,13 @@ impl Default for WhatsAppAccountConfig {
             markdown: None,
             config_writes: None,
             enabled: None,
+            access_token: None,
+            phone_number_id: None,
+            api_url: None,
+            api_version: None,
+            verify_token: None,
+            app_secret: None,
+            webhook_path: None,
             send_read_receipts: Some(true),
             message_prefix: None,
             response_prefix: None,
@@ -1571,6 +1664,7 @@ impl Default for WhatsAppAccountConfig {
             debounce_ms: None,
             heartbeat: None,
             actions: None,
+            retry: None,
         }
     }
 }
@@ -1583,6 +1677,55 @@ pub struct WhatsAppConfig {
     pub default_account: WhatsAppAccountConfig,
 }
 
+impl WhatsAppConfig {
+    pub fn apply_token(&mut self, token: &str) {
+        self.default_account.access_token = Some(token.to_string());
+    }
+
+    pub fn apply_phone_number_id(&mut self, phone_number_id: &str) {
+        self.default_account.phone_number_id = Some(phone_number_id.to_string());
+    }
+}
+
+// ============================================================================
+// Nostr Configuration
+// ============================================================================
+
+/// Encryption scheme for outgoing Nostr direct messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NostrDmProtocol {
+    /// Kind 4 events with NIP-04 (AES-256-CBC) encryption.
+    Nip04,
+    /// NIP-17 gift-wrapped kind 14 messages with NIP-44 encryption.
+    #[default]
+    Nip17,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct NostrConfig {
+    pub enabled: Option<bool>,
+    /// Private key as `nsec1…` or 64 hex characters.
+    pub private_key: Option<String>,
+    /// Relay WebSocket URLs (e.g. `wss://relay.damus.io`).
+    pub relays: Option<Vec<String>>,
+    /// Protocol for DMs we initiate; replies use the sender's protocol.
+    pub dm_protocol: Option<NostrDmProtocol>,
+    pub dm_policy: Option<DmPolicy>,
+    /// Allowed DM senders as `npub1…` or hex public keys.
+    pub allow_from: Option<Vec<String>>,
+    /// Answer public notes that mention our public key (default true).
+    pub mentions: Option<bool>,
+    pub retry: Option<OutboundRetryConfig>,
+}
+
+impl NostrConfig {
+    pub fn apply_private_key(&mut self, key: &str) {
+        self.private_key = Some(key.to_string());
+    }
+}
+
 // ============================================================================
 // Signal Configuration
 // ============================================================================
@@ -1593,9 +1736,30 @@ pub struct SignalConfig {
     pub enabled: Option<bool>,
     pub api_url: Option<String>,
     pub phone_number: Option<String>,
+    /// How to receive from the signal-cli REST API (default `websocket`).
+    pub receive_mode: Option<SignalReceiveMode>,
     pub allow_from: Option<Vec<String>>,
     pub group_policy: Option<GroupPolicy>,
+    /// Senders (or `group.<id>` groups) admitted when `groupPolicy` is `allowlist`.
+    pub group_allow_from: Option<Vec<String>>,
     pub dm_policy: Option<DmPolicy>,
+    pub require_mention: Option<bool>,
+    /// Send read receipts for handled messages (default true).
+    pub read_receipts: Option<bool>,
+    pub reconnect: Option<WebReconnectConfig>,
+    pub text_chunk_limit: Option<usize>,
+    pub retry: Option<OutboundRetryConfig>,
+}
+
+/// Receive transport of the signal-cli REST API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SignalReceiveMode {
+    /// `json-rpc` daemon mode: envelopes stream over a WebSocket.
+    #[default]
+    Websocket,
+    /// `normal`/`native` mode: `GET /v1/receive` is polled.
+    Poll,
 }
 
 // ============================================================================
@@ -1612,6 +1776,7 @@ pub struct IMessageConfig {
     pub allow_from: Option<Vec<String>>,
     pub group_policy: Option<GroupPolicy>,
     pub dm_policy: Option<DmPolicy>,
+    pub retry: Option<OutboundRetryConfig>,
 }
 
 // ============================================================================
@@ -1622,7 +1787,18 @@ pub struct IMessageConfig {
 #[serde(rename_all = "camelCase")]
 pub struct GoogleChatConfig {
     pub enabled: Option<bool>,
+    /// Service account key JSON, inline or as a file path; used to reply
+    /// through the Chat API.
     pub service_account_key: Option<String>,
+    /// Incoming webhook URL for outbound-only use.
+    pub webhook_url: Option<String>,
+    /// Expected `aud` of inbound request tokens: the Cloud project number.
+    pub audience: Option<String>,
+    /// Path below `/channels/googlechat/` for events (default `webhook`).
+    pub webhook_path: Option<String>,
+    pub dm_policy: Option<DmPolicy>,
+    pub allow_from: Option<Vec<String>>,
+    pub retry: Option<OutboundRetryConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -1630,6 +1806,26 @@ pub struct GoogleChatConfig {
 pub struct MsTeamsConfig {
     pub enabled: Option<bool>,
     pub webhook_url: Option<String>,
+    /// Azure bot app (client) id; also the audience of inbound tokens.
+    pub app_id: Option<String>,
+    /// Azure bot client secret.
+    pub app_password: Option<String>,
+    /// Tenant for single-tenant bots (default `botframework.com`).
+    pub tenant_id: Option<String>,
+    /// Path below `/channels/teams/` for activities (default `messages`).
+    pub webhook_path: Option<String>,
+    /// OpenID metadata document listing the keys of inbound tokens
+    /// (default Bot Framework's).
+    pub open_id_metadata_url: Option<String>,
+    /// OAuth2 token endpoint for outbound calls (default
+    /// `https://login.microsoftonline.com/<tenantId>/oauth2/v2.0/token`).
+    pub token_url: Option<String>,
+    /// Connector `serviceUrl` for proactive messages to conversations the
+    /// bot has not heard from yet (default `https://smba.trafficmanager.net/amer/`).
+    pub service_url: Option<String>,
+    pub dm_policy: Option<DmPolicy>,
+    pub allow_from: Option<Vec<String>>,
+    pub retry: Option<OutboundRetryConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -1641,6 +1837,215 @@ pub struct IrcConfig {
     pub nickname: Option<String>,
     pub channels: Option<Vec<String>>,
     pub tls: Option<bool>,
+    /// Server password sent with `PASS` before registration.
+    pub password: Option<String>,
+    pub username: Option<String>,
+    pub realname: Option<String>,
+    pub sasl: Option<IrcSaslConfig>,
+    /// Password sent to NickServ with `IDENTIFY` after registration.
+    pub nickserv_password: Option<String>,
+    pub dm_policy: Option<DmPolicy>,
+    pub allow_from: Option<Vec<String>>,
+    pub require_mention: Option<bool>,
+    /// Messages that may be sent back-to-back before throttling (default 4).
+    pub flood_burst: Option<u32>,
+    /// Milliseconds per message once the burst is spent (default 1000).
+    pub flood_interval_ms: Option<u64>,
+    pub retry: Option<OutboundRetryConfig>,
+}
+
+/// SASL PLAIN credentials for IRC.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct IrcSaslConfig {
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+// ============================================================================
+// Email Configuration
+// ============================================================================
+
+/// Transport security for a mail server connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailSecurity {
+    /// TLS from the first byte (IMAP 993, SMTP 465).
+    Tls,
+    /// Plain connection upgraded with `STARTTLS` (SMTP 587).
+    Starttls,
+    /// No encryption; for local relays and tests only.
+    None,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailConfig {
+    pub enabled: Option<bool>,
+    /// Address replies are sent from; also filters our own mail.
+    pub address: Option<String>,
+    /// Display name in the `From` header.
+    pub display_name: Option<String>,
+    pub imap: Option<EmailImapConfig>,
+    pub smtp: Option<EmailSmtpConfig>,
+    pub dm_policy: Option<DmPolicy>,
+    /// Sender addresses allowed to reach the agent (`*@example.com` works).
+    pub allow_from: Option<Vec<String>>,
+    /// Attachments larger than this are listed but not kept (default 10 MiB).
+    pub max_attachment_bytes: Option<u64>,
+    pub retry: Option<OutboundRetryConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailImapConfig {
+    pub host: Option<String>,
+    /// Defaults to 993 with `tls`, 143 otherwise.
+    pub port: Option<u16>,
+    /// Default `tls`.
+    pub security: Option<EmailSecurity>,
+    /// Login name; defaults to `address`.
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Mailboxes to watch (default `["INBOX"]`).
+    pub mailboxes: Option<Vec<String>>,
+    /// Use IMAP IDLE when the server supports it (default true).
+    pub idle: Option<bool>,
+    /// Seconds between checks without IDLE (default 60).
+    pub poll_interval_seconds: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailSmtpConfig {
+    pub host: Option<String>,
+    /// Defaults to 465 with `tls`, 587 with `starttls`, 25 otherwise.
+    pub port: Option<u16>,
+    /// Default `starttls`.
+    pub security: Option<EmailSecurity>,
+    /// Login name; defaults to the IMAP username, then `address`.
+    pub username: Option<String>,
+    /// Defaults to the IMAP password.
+    pub password: Option<String>,
+}
+
+// ============================================================================
+// Matrix Configuration
+// ============================================================================
+
+/// How the bot responds to room invites.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MatrixAutoJoin {
+    /// Join every invite.
+    Always,
+    /// Join invites from users in `autoJoinAllowFrom`.
+    Allowlist,
+    /// Ignore invites.
+    #[default]
+    Off,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MatrixAccountConfig {
+    pub enabled: Option<bool>,
+    /// Homeserver base URL (e.g. `https://matrix.org`).
+    pub homeserver_url: Option<String>,
+    pub access_token: Option<String>,
+    /// Bot user id (e.g. `@bot:matrix.org`); resolved via `whoami` if unset.
+    pub user_id: Option<String>,
+    pub auto_join: Option<MatrixAutoJoin>,
+    pub auto_join_allow_from: Option<Vec<String>>,
+    pub dm_policy: Option<DmPolicy>,
+    pub allow_from: Option<Vec<String>>,
+    pub require_mention: Option<bool>,
+    /// Send read receipts for handled messages (default true).
+    pub read_receipts: Option<bool>,
+    pub text_chunk_limit: Option<usize>,
+    pub retry: Option<OutboundRetryConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MatrixConfig {
+    pub accounts: Option<HashMap<String, MatrixAccountConfig>>,
+    #[serde(flatten)]
+    pub default_account: MatrixAccountConfig,
+}
+
+impl MatrixConfig {
+    pub fn apply_token(&mut self, token: &str) {
+        self.default_account.access_token = Some(token.to_string());
+    }
+}
+
+// ============================================================================
+// Mattermost Configuration
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MattermostAccountConfig {
+    pub enabled: Option<bool>,
+    /// Server base URL (e.g. `https://mattermost.example.com`).
+    pub server_url: Option<String>,
+    /// Bot access token or personal access token.
+    pub token: Option<String>,
+    pub dm_policy: Option<DmPolicy>,
+    pub allow_from: Option<Vec<String>>,
+    pub require_mention: Option<bool>,
+    /// Reply in a thread under channel posts (default true).
+    pub thread_replies: Option<bool>,
+    /// WebSocket reconnect backoff.
+    pub reconnect: Option<WebReconnectConfig>,
+    pub text_chunk_limit: Option<usize>,
+    pub retry: Option<OutboundRetryConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MattermostConfig {
+    pub accounts: Option<HashMap<String, MattermostAccountConfig>>,
+    #[serde(flatten)]
+    pub default_account: MattermostAccountConfig,
+}
+
+impl MattermostConfig {
+    pub fn apply_token(&mut self, token: &str) {
+        self.default_account.token = Some(token.to_string());
+    }
+}
+
+// ============================================================================
+// Twitch Configuration
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TwitchConfig {
+    pub enabled: Option<bool>,
+    /// Chat OAuth token; the `oauth:` prefix is optional.
+    pub oauth_token: Option<String>,
+    /// Bot account login name.
+    pub nickname: Option<String>,
+    /// Channels to join, with or without the leading `#`.
+    pub channels: Option<Vec<String>>,
+    /// Chat server host (defaults to `irc.chat.twitch.tv`).
+    pub server: Option<String>,
+    pub port: Option<u16>,
+    /// Connect over TLS (default true).
+    pub tls: Option<bool>,
+    pub require_mention: Option<bool>,
+    /// Logins or user ids allowed to talk to the bot; empty allows everyone.
+    pub allow_from: Option<Vec<String>>,
+    pub retry: Option<OutboundRetryConfig>,
+}
+
+impl TwitchConfig {
+    pub fn apply_token(&mut self, token: &str) {
+        self.oauth_token = Some(token.to_string());
+    }
 }
 
 // ============================================================================
@@ -1660,6 +2065,7 @@ pub struct SynologyChatAccountConfig {
     pub rate_limit_per_minute: Option<u32>,
     pub bot_name: Option<String>,
     pub allow_insecure_ssl: Option<bool>,
+    pub retry: Option<OutboundRetryConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -1670,6 +2076,132 @@ pub struct SynologyChatConfig {
     pub default_account: SynologyChatAccountConfig,
 }
 
+// ============================================================================
+// LINE / Feishu / Zalo / Nextcloud Talk / BlueBubbles Configuration
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LineConfig {
+    pub enabled: Option<bool>,
+    pub channel_access_token: Option<String>,
+    /// Channel secret used to verify `X-Line-Signature`.
+    pub channel_secret: Option<String>,
+    /// Path below `/channels/line/` for events (default `webhook`).
+    pub webhook_path: Option<String>,
+    pub dm_policy: Option<DmPolicy>,
+    pub allow_from: Option<Vec<String>>,
+    /// Only answer group messages that mention the bot (default true).
+    pub require_mention: Option<bool>,
+    pub retry: Option<OutboundRetryConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FeishuConfig {
+    pub enabled: Option<bool>,
+    pub app_id: Option<String>,
+    pub app_secret: Option<String>,
+    /// Verification token checked on unencrypted events.
+    pub verification_token: Option<String>,
+    /// Encrypt key; when set, events are encrypted and signed with it.
+    pub encrypt_key: Option<String>,
+    /// Open API base URL (default `https://open.feishu.cn/open-apis`; use
+    /// `https://open.larksuite.com/open-apis` for Lark).
+    pub api_url: Option<String>,
+    /// Path below `/channels/feishu/` for events (default `webhook`).
+    pub webhook_path: Option<String>,
+    pub dm_policy: Option<DmPolicy>,
+    pub allow_from: Option<Vec<String>>,
+    /// Only answer group messages that mention the bot (default true).
+    pub require_mention: Option<bool>,
+    pub retry: Option<OutboundRetryConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ZaloConfig {
+    pub enabled: Option<bool>,
+    pub app_id: Option<String>,
+    /// OA secret key used to verify `X-ZEvent-Signature`.
+    pub oa_secret_key: Option<String>,
+    pub oa_access_token: Option<String>,
+    /// Path below `/channels/zalo/` for events (default `webhook`).
+    pub webhook_path: Option<String>,
+    pub dm_policy: Option<DmPolicy>,
+    pub allow_from: Option<Vec<String>>,
+    pub retry: Option<OutboundRetryConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ZaloUserConfig {
+    pub enabled: Option<bool>,
+    pub user_access_token: Option<String>,
+    /// Shared secret the bridge signs forwarded messages with.
+    pub webhook_secret: Option<String>,
+    /// Path below `/channels/zalouser/` for events (default `webhook`).
+    pub webhook_path: Option<String>,
+    pub dm_policy: Option<DmPolicy>,
+    pub allow_from: Option<Vec<String>>,
+    pub retry: Option<OutboundRetryConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct NextcloudTalkConfig {
+    pub enabled: Option<bool>,
+    /// Nextcloud server URL (e.g. `https://cloud.example.com`).
+    pub server_url: Option<String>,
+    /// Shared secret from `occ talk:bot:install`.
+    pub bot_secret: Option<String>,
+    /// Path below `/channels/nextcloud/` for events (default `webhook`).
+    pub webhook_path: Option<String>,
+    /// Nextcloud actors (e.g. `users/alice`) allowed to talk to the bot.
+    pub allow_from: Option<Vec<String>>,
+    pub retry: Option<OutboundRetryConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BlueBubblesConfig {
+    pub enabled: Option<bool>,
+    /// BlueBubbles server URL (e.g. `http://192.168.1.100:1234`).
+    pub server_url: Option<String>,
+    /// Server password; webhooks must pass it as `?password=`.
+    pub password: Option<String>,
+    /// Path below `/channels/bluebubbles/` for events (default `webhook`).
+    pub webhook_path: Option<String>,
+    pub dm_policy: Option<DmPolicy>,
+    pub allow_from: Option<Vec<String>>,
+    pub group_policy: Option<GroupPolicy>,
+    pub retry: Option<OutboundRetryConfig>,
+}
+
+// ============================================================================
+// WebChat Configuration
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct WebChatConfig {
+    pub enabled: Option<bool>,
+    /// Maximum concurrent WebSocket connections (default 100).
+    pub max_connections: Option<u32>,
+    /// Accept visitors without a signed token (default true).
+    pub allow_anonymous: Option<bool>,
+    /// HS256 secret the embedding site signs visitor tokens with.
+    pub visitor_secret: Option<String>,
+    /// Messages kept per visitor and replayed on reconnect (default 50).
+    pub history_limit: Option<usize>,
+    /// Messages a visitor may send per minute (default 10).
+    pub rate_limit_per_minute: Option<u32>,
+    /// Widget header title (default "Chat").
+    pub title: Option<String>,
+    /// Assistant greeting shown in an empty conversation.
+    pub greeting: Option<String>,
+}
+
 // ============================================================================
 // Tools Configuration
 // ============================================================================
@@ -2181,10 +2713,45 @@ pub struct HooksConfig {
 #[serde(rename_all = "camelCase")]
 pub struct GroupChatConfig {
     #[serde(default)]
-    pub mention_patterns: Vec<String>,
+    pub mention_patterns: Vec<MentionPattern>,
     pub history_limit: Option<u32>,
 }
 
+/// A `mentionPatterns` entry: a case-insensitive regular expression.
+///
+/// Compiled when the config is loaded, so an invalid pattern fails the load
+/// instead of being skipped on every message.
+#[derive(Debug, Clone)]
+pub struct MentionPattern(regex::Regex);
+
+impl MentionPattern {
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        regex::RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map(Self)
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.0.is_match(text)
+    }
+}
+
+impl Serialize for MentionPattern {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for MentionPattern {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Self::new(&pattern).map_err(|e| {
+            serde::de::Error::custom(format!("invalid mention pattern {pattern:?}: {e}"))
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct DmChatConfig {
@@ -2245,6 +2812,7 @@ pub struct MessagesConfig {
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct CommandsConfig {
+    /// Register commands with the platforms: `true`/`"auto"` (default) or `false`.
     pub native: Option<serde_json::Value>,
     pub native_skills: Option<serde_json::Value>,
     pub text: Option<bool>,
@@ -2254,10 +2822,29 @@ pub struct CommandsConfig {
     pub debug: Option<bool>,
     pub restart: Option<bool>,
     pub use_access_groups: Option<bool>,
+    /// Senders allowed to run every command, as `<channel>:<id>`.
     pub owner_allow_from: Option<Vec<String>>,
+    /// Senders allowed to run commands, per channel id (`*` for all channels,
+    /// whose entries are `<channel>:<id>`).
     pub allow_from: Option<HashMap<String, Vec<serde_json::Value>>>,
 }
 
+impl CommandsConfig {
+    /// Whether `/command` messages are recognised in chat text.
+    pub fn text_enabled(&self) -> bool {
+        self.text != Some(false)
+    }
+
+    /// Whether commands are registered natively with the platforms.
+    pub fn native_enabled(&self) -> bool {
+        match &self.native {
+            Some(serde_json::Value::Bool(enabled)) => *enabled,
+            Some(serde_json::Value::String(mode)) => mode != "off",
+            _ => true,
+        }
+    }
+}
+
 // ============================================================================
 // Session Configuration
 // ============================================================================
@@ -2689,6 +3276,37 @@ pub struct WebReconnectConfig {
     pub max_attempts: Option<u32>,
 }
 
+impl WebReconnectConfig {
+    /// Delay before reconnect attempt `attempt` (0-based), or `None` once
+    /// `maxAttempts` is exhausted. `jitter` spreads the delay by up to that
+    /// fraction in either direction.
+    pub fn delay(&self, attempt: u32) -> Option<std::time::Duration> {
+        if self.max_attempts.is_some_and(|max| attempt >= max) {
+            return None;
+        }
+        let initial = self
+            .initial_ms
+            .unwrap_or(super::defaults::DEFAULT_RECONNECT_INITIAL_MS) as f64;
+        let max = self
+            .max_ms
+            .unwrap_or(super::defaults::DEFAULT_RECONNECT_MAX_MS) as f64;
+        let factor = self
+            .factor
+            .unwrap_or(super::defaults::DEFAULT_RECONNECT_FACTOR)
+            .max(1.0);
+        let base = (initial * factor.powi(attempt.min(64) as i32)).min(max);
+        let jitter = self.jitter.unwrap_or(0.0).clamp(0.0, 1.0);
+        let spread = if jitter > 0.0 {
+            (rand::random::<f64>() * 2.0 - 1.0) * jitter
+        } else {
+            0.0
+        };
+        Some(std::time::Duration::from_millis(
+            (base * (1.0 + spread)).max(0.0) as u64,
+        ))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct WebConfig {
@@ -3189,4 +3807,32 @@ mod tests {
         assert!(config.alternative_providers.is_none());
         assert!(config.cooldown_probe_cap.is_none());
     }
+
+    // ====================================================================
+    // WebReconnectConfig backoff
+    // ====================================================================
+
+    #[test]
+    fn web_reconnect_delay_backs_off_and_caps() {
+        let config = WebReconnectConfig {
+            initial_ms: Some(100),
+            max_ms: Some(500),
+            factor: Some(2.0),
+            max_attempts: Some(5),
+            ..Default::default()
+        };
+        let delays: Vec<u64> = (0..6)
+            .filter_map(|attempt| config.delay(attempt))
+            .map(|d| d.as_millis() as u64)
+            .collect();
+        assert_eq!(delays, vec![100, 200, 400, 500, 500]);
+
+        let jittered = WebReconnectConfig {
+            jitter: Some(0.5),
+            ..Default::default()
+        };
+        let delay = jittered.delay(0).unwrap().as_millis();
+        assert!((500..=1500).contains(&delay));
+        assert!(WebReconnectConfig::default().delay(1000).is_some());
+    }
 }
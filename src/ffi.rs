@@ -526,10 +526,11 @@ pub unsafe extern "C" fn mylobster_channel_send(
     match rt.block_on(crate::channels::send_message(
         cfg,
         channel_str,
+        None,
         to_str,
         msg_str,
     )) {
-        Ok(()) => MyLobsterStatus::Ok,
+        Ok(_) => MyLobsterStatus::Ok,
         Err(e) => {
             set_last_error(&format!("channel send error: {e}"));
             MyLobsterStatus::ChannelError
@@ -1,4 +1,4 @@
-use crate::config::Config;
+use crate::config::{Config, ThinkingLevel};
 use crate::gateway::protocol::*;
 use crate::hooks::{HookEvent, HookResult, SharedHookRegistry};
 use crate::providers::{ProviderMessage, ProviderRequest, StreamEvent, ThinkingConfig};
@@ -70,6 +70,9 @@ pub async fn process_chat_with_hooks(
 
     // Build messages from session history + new user message
     let mut messages = session.get_history();
+    if let Some(limit) = params.history_limit {
+        trim_to_user_turns(&mut messages, limit as usize);
+    }
 
     // v2026.2.26: Inject message timestamp context for time-aware responses.
     let timestamp = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC").to_string();
@@ -87,12 +90,20 @@ pub async fn process_chat_with_hooks(
         tool_calls: None,
     });
 
-    // Resolve model provider
-    let mut model = config
-        .agent
-        .model
-        .primary_model()
+    // Resolve model provider; a session override (`sessions.patch`, `/model`)
+    // wins over the configured primary model.
+    let info = sessions.get_session(session_key);
+    let mut model = info
+        .as_ref()
+        .and_then(|info| info.model.clone())
+        .or_else(|| config.agent.model.primary_model())
         .unwrap_or_else(|| "claude-sonnet-4-6".to_string());
+    let thinking_level = info
+        .as_ref()
+        .and_then(|info| info.thinking.as_deref())
+        .and_then(|level| level.parse::<ThinkingLevel>().ok())
+        .or(config.agent.thinking_default)
+        .unwrap_or_default();
 
     // Fire BeforeModelResolve hook (modifying — can override model)
     if let Some(ref h) = hooks {
@@ -156,7 +167,9 @@ pub async fn process_chat_with_hooks(
 
         // Enable extended thinking for Claude models (makes reasoning visible)
         let thinking = if model.contains("claude") {
-            Some(ThinkingConfig { budget_tokens: 10000 })
+            thinking_level
+                .budget_tokens()
+                .map(|budget_tokens| ThinkingConfig { budget_tokens })
         } else {
             None
         };
@@ -481,6 +494,24 @@ fn build_tool_definitions(config: &Config) -> Vec<serde_json::Value> {
         .collect()
 }
 
+/// Drop history before the last `limit` user turns; `0` drops it all.
+fn trim_to_user_turns(messages: &mut Vec<ProviderMessage>, limit: usize) {
+    let start = messages
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, m)| m.role == "user")
+        .nth(limit.saturating_sub(1))
+        .map(|(i, _)| i);
+    match (limit, start) {
+        (0, _) => messages.clear(),
+        (_, Some(start)) => {
+            messages.drain(..start);
+        }
+        (_, None) => {}
+    }
+}
+
 /// Execute a tool by name and return the result.
 async fn execute_tool(
     config: &Config,
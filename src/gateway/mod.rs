@@ -8,6 +8,7 @@ mod server;
 mod websocket;
 
 pub use auth::*;
+pub use chat::{process_chat, process_chat_with_hooks};
 pub use client::*;
 pub use protocol::*;
 pub use server::*;
@@ -351,6 +351,9 @@ pub struct ChatSendParams {
     /// Resume an existing session instead of creating new (v2026.3.11 ACPX).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub resume_session_id: Option<String>,
+    /// Send only the last N user turns of session history to the model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history_limit: Option<u32>,
 }
 
 // ============================================================================
@@ -1138,6 +1141,7 @@ mod tests {
             idempotency_key: None,
             best_effort_deliver: Some(true),
             resume_session_id: None,
+            history_limit: None,
         };
         let v = serde_json::to_value(&params).unwrap();
         assert_eq!(v["bestEffortDeliver"], true);
@@ -1158,6 +1162,7 @@ mod tests {
             idempotency_key: None,
             best_effort_deliver: None,
             resume_session_id: None,
+            history_limit: None,
         };
         let v = serde_json::to_value(&params).unwrap();
         assert!(v.get("bestEffortDeliver").is_none());
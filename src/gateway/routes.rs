@@ -4,15 +4,15 @@ use crate::gateway::websocket;
 use crate::infra::security_path;
 
 use axum::{
-    body::Body,
+    body::{Body, Bytes},
     extract::{
         ws::WebSocketUpgrade,
-        ConnectInfo, Json, Query, State,
+        ConnectInfo, Json, Query, RawQuery, State,
     },
-    http::{header, Request, StatusCode},
+    http::{header, HeaderMap, Method, Request, StatusCode},
     middleware::{self, Next},
     response::{IntoResponse, Response},
-    routing::{get, post},
+    routing::{any, get, post},
     Router,
 };
 use serde::{Deserialize, Serialize};
@@ -127,6 +127,10 @@ pub fn build_routes(state: GatewayState) -> Router {
         // WebSocket
         .route("/ws", get(ws_handler))
         .route("/api/chat", get(ws_handler))
+        // WebChat (visitor sessions, authenticated by visitor token)
+        .route("/ws/webchat", get(webchat_ws_handler))
+        .route("/webchat", get(webchat_page_handler))
+        .route("/webchat/widget.js", get(webchat_widget_handler))
         // Sessions
         .route("/api/sessions", get(sessions_list_handler))
         .route("/api/sessions/{id}", get(session_get_handler))
@@ -136,6 +140,8 @@ pub fn build_routes(state: GatewayState) -> Router {
         .route("/api/memory/search", post(memory_search_handler))
         // Channels
         .route("/api/channels/status", get(channels_status_handler))
+        // Channel webhooks (authenticated by each channel, not the gateway token)
+        .route("/channels/{id}/{*path}", any(channel_webhook_handler))
         // Gateway info
         .route("/api/gateway/info", get(gateway_info_handler))
         // Models
@@ -205,6 +211,64 @@ async fn ws_handler(
         .on_upgrade(move |socket| websocket::handle_websocket(socket, state, addr, query.token))
 }
 
+// ============================================================================
+// WebChat
+// ============================================================================
+
+/// Largest frame a WebChat visitor may send.
+const MAX_WEBCHAT_PAYLOAD: usize = 64 * 1024;
+
+#[derive(Debug, Deserialize)]
+struct WebChatQuery {
+    token: Option<String>,
+    since: Option<u64>,
+}
+
+async fn webchat_ws_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<GatewayState>,
+    Query(query): Query<WebChatQuery>,
+) -> Response {
+    let webchat = state.channels.webchat();
+    if !webchat.is_enabled() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let visitor = match webchat.authenticate(query.token.as_deref()) {
+        Ok(visitor) => visitor,
+        Err(status) => return status.into_response(),
+    };
+    ws.max_message_size(MAX_WEBCHAT_PAYLOAD)
+        .on_upgrade(move |socket| webchat.serve_socket(socket, state, visitor, query.since))
+}
+
+async fn webchat_widget_handler(State(state): State<GatewayState>) -> Response {
+    if !state.channels.webchat().is_enabled() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    (
+        [
+            (
+                header::CONTENT_TYPE,
+                "application/javascript; charset=utf-8",
+            ),
+            (header::CACHE_CONTROL, "public, max-age=300"),
+        ],
+        crate::channels::WEBCHAT_WIDGET_JS,
+    )
+        .into_response()
+}
+
+async fn webchat_page_handler(State(state): State<GatewayState>) -> Response {
+    if !state.channels.webchat().is_enabled() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    (
+        [(header::CONTENT_TYPE, "text/html; charset=utf-8")],
+        crate::channels::WEBCHAT_WIDGET_HTML,
+    )
+        .into_response()
+}
+
 // ============================================================================
 // Sessions
 // ============================================================================
@@ -296,6 +360,41 @@ async fn channels_status_handler(State(state): State<GatewayState>) -> Json<serd
     Json(status)
 }
 
+/// Forward `/channels/{id}/{*path}` to the channel plugin's webhook handler.
+async fn channel_webhook_handler(
+    State(state): State<GatewayState>,
+    axum::extract::Path((id, path)): axum::extract::Path<(String, String)>,
+    method: Method,
+    RawQuery(query): RawQuery,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let Some(plugin) = state.channels.webhook_plugin(&id, &path).await else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let request = crate::channels::WebhookRequest {
+        method,
+        path,
+        query,
+        headers,
+        body,
+    };
+    let routes = plugin.webhook_routes();
+    if !routes.is_empty() {
+        if let Some(response) = crate::channels::webhook::route_check(&routes, &request) {
+            return response.into_response();
+        }
+    }
+    match plugin.handle_webhook(&state, request).await {
+        Ok(response) => response.into_response(),
+        Err(e) => {
+            warn!(channel = %id, error = %e, "Channel webhook failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
 // ============================================================================
 // Gateway Info
 // ============================================================================
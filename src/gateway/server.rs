@@ -1,5 +1,5 @@
 use crate::agents::acp::AcpAgentManager;
-use crate::channels::ChannelManager;
+use crate::channels::{ActiveRuns, Admission, ChannelManager, GroupHistory, InboundSink};
 use crate::cli::GatewayOpts;
 use crate::config::Config;
 use crate::gateway::auth::{resolve_gateway_auth, ResolvedGatewayAuth};
@@ -119,6 +119,14 @@ pub struct GatewayState {
     pub auth: Arc<ResolvedGatewayAuth>,
     pub sessions: Arc<SessionStore>,
     pub channels: Arc<ChannelManager>,
+    /// Events pushed by channels (messages, edits, reactions).
+    pub inbound: Arc<InboundSink>,
+    /// DM admission: pairing approvals and denial notices.
+    pub admission: Arc<Admission>,
+    /// Agent runs started by channel messages, cancelled by `/stop`.
+    pub runs: Arc<ActiveRuns>,
+    /// Unaddressed group messages kept as context for the next turn.
+    pub group_history: Arc<GroupHistory>,
     pub plugins: Arc<PluginRegistry>,
     pub rpc: Arc<RpcState>,
     pub shutdown_tx: broadcast::Sender<()>,
@@ -126,6 +134,29 @@ pub struct GatewayState {
     pub version: String,
 }
 
+#[cfg(test)]
+impl GatewayState {
+    /// State for unit tests: nothing started, approvals kept in memory.
+    pub(crate) fn for_test(config: Config) -> Self {
+        let (shutdown_tx, _) = broadcast::channel(1);
+        Self {
+            auth: Arc::new(resolve_gateway_auth(None, None)),
+            sessions: Arc::new(SessionStore::new(&config)),
+            channels: Arc::new(ChannelManager::new(&config)),
+            inbound: Arc::new(InboundSink::new()),
+            admission: Arc::new(Admission::new()),
+            runs: Arc::new(ActiveRuns::default()),
+            group_history: Arc::new(GroupHistory::new()),
+            plugins: Arc::new(PluginRegistry::new(&config)),
+            rpc: Arc::new(RpcState::new()),
+            config: Arc::new(RwLock::new(config)),
+            shutdown_tx,
+            start_time: std::time::Instant::now(),
+            version: "test".to_string(),
+        }
+    }
+}
+
 /// The gateway server.
 pub struct GatewayServer {
     state: GatewayState,
@@ -148,6 +179,7 @@ impl GatewayServer {
         let sessions = SessionStore::new(&config);
         let channels = ChannelManager::new(&config);
         let plugins = PluginRegistry::new(&config);
+        let admission = Admission::load(config.state_dir.join("channels").join("pairing.json"));
 
         let rpc = RpcState::new();
 
@@ -156,6 +188,10 @@ impl GatewayServer {
             auth: Arc::new(auth),
             sessions: Arc::new(sessions),
             channels: Arc::new(channels),
+            inbound: Arc::new(InboundSink::new()),
+            admission: Arc::new(admission),
+            runs: Arc::new(ActiveRuns::default()),
+            group_history: Arc::new(GroupHistory::new()),
             plugins: Arc::new(plugins),
             rpc: Arc::new(rpc),
             shutdown_tx,
@@ -4,6 +4,7 @@ use crate::gateway::auth::{
 use crate::gateway::chat;
 use crate::gateway::protocol::*;
 use crate::gateway::server::GatewayState;
+use crate::infra::exec_approval::TurnOrigin;
 
 use axum::extract::ws::{Message, WebSocket};
 use futures::{SinkExt, StreamExt};
@@ -11,7 +12,7 @@ use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::atomic::AtomicU64;
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{broadcast, mpsc, RwLock};
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
@@ -295,6 +296,24 @@ pub async fn handle_websocket(
         }
     });
 
+    // Forward channel health changes to operators
+    let health_forwarder = (!conn_state.scopes.is_empty()).then(|| {
+        let mut health = state.channels.health().subscribe();
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            loop {
+                match health.recv().await {
+                    Ok(event) => {
+                        let payload = serde_json::to_value(&event).unwrap();
+                        send_oc_event(&tx, OcEventFrame::new("channels.health", payload)).await;
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    });
+
     // Process incoming messages
     while let Some(msg) = ws_rx.next().await {
         match msg {
@@ -344,6 +363,10 @@ pub async fn handle_websocket(
         }
     }
 
+    if let Some(forwarder) = health_forwarder {
+        forwarder.abort();
+    }
+
     // Cancel any active runs on disconnect
     {
         let runs = active_runs.read().await;
@@ -594,6 +617,35 @@ async fn handle_request(
             let response = handle_channels_logout(state, &request).await;
             send_oc_response(tx, response).await;
         }
+        "channels.restart" => {
+            let response = handle_channels_restart(state, &request).await;
+            send_oc_response(tx, response).await;
+        }
+        "channels.pairing.list" => {
+            send_oc_response(
+                tx,
+                OcResponseFrame::success(
+                    request_id,
+                    serde_json::json!({
+                        "pending": state.admission.pending(),
+                        "paired": state.admission.paired(),
+                    }),
+                ),
+            )
+            .await;
+        }
+        "channels.pairing.approve" => {
+            let response = handle_channels_pairing_code(state, &request, true);
+            send_oc_response(tx, response).await;
+        }
+        "channels.pairing.reject" => {
+            let response = handle_channels_pairing_code(state, &request, false);
+            send_oc_response(tx, response).await;
+        }
+        "channels.pairing.revoke" => {
+            let response = handle_channels_pairing_revoke(state, &request);
+            send_oc_response(tx, response).await;
+        }
 
         // ================================================================
         // Skills extensions
@@ -1500,7 +1552,6 @@ async fn send_oc_response(tx: &mpsc::Sender<String>, response: OcResponseFrame)
 }
 
 /// Send an OC-format event over the writer channel.
-#[allow(dead_code)]
 async fn send_oc_event(tx: &mpsc::Sender<String>, event: OcEventFrame) {
     let json = serde_json::to_string(&event).unwrap();
     let _ = tx.send(json).await;
@@ -2554,25 +2605,167 @@ async fn handle_channels_logout(
     state: &GatewayState,
     request: &RequestFrame,
 ) -> OcResponseFrame {
-    let channel = request
+    let accounts = match channel_accounts_param(state, request).await {
+        Ok(accounts) => accounts,
+        Err(response) => return response,
+    };
+    for (channel, account_id) in &accounts {
+        if let Err(e) = state.channels.stop_account(channel, account_id).await {
+            warn!(
+                "Channel '{}' account '{}' logout failed: {}",
+                channel, account_id, e
+            );
+        }
+        info!("Channel '{}' account '{}' logged out", channel, account_id);
+    }
+    OcResponseFrame::success(
+        request.id.clone(),
+        serde_json::json!({ "ok": true, "accounts": account_ids(&accounts) }),
+    )
+}
+
+/// Restart one account of a channel (`{ channel, accountId? }`), or all of
+/// its enabled accounts without `accountId`.
+async fn handle_channels_restart(state: &GatewayState, request: &RequestFrame) -> OcResponseFrame {
+    let mut accounts = match channel_accounts_param(state, request).await {
+        Ok(accounts) => accounts,
+        Err(response) => return response,
+    };
+    let explicit = request
         .params
         .as_ref()
-        .and_then(|p| p.get("channel"))
-        .and_then(|v| v.as_str());
-
-    match channel {
-        Some(ch) => {
-            if let Some(plugin) = state.channels.get_plugin(ch).await {
-                let _ = plugin.stop_account().await;
-                info!("Channel '{}' logged out", ch);
+        .is_some_and(|p| p.get("accountId").is_some());
+    if !explicit {
+        let mut enabled = Vec::new();
+        for (channel, account_id) in accounts {
+            if let Some(plugin) = state.channels.get_account(&channel, &account_id).await {
+                if plugin.meta().enabled {
+                    enabled.push((channel, account_id));
+                }
             }
-            OcResponseFrame::success(request.id.clone(), serde_json::json!({ "ok": true }))
         }
-        None => OcResponseFrame::error(
+        accounts = enabled;
+    }
+    for (channel, account_id) in &accounts {
+        if let Err(e) = state
+            .channels
+            .restart_account(state, channel, account_id)
+            .await
+        {
+            return OcResponseFrame::error(
+                request.id.clone(),
+                format!("Restart failed: {}", e),
+                Some(-32603),
+            );
+        }
+    }
+    OcResponseFrame::success(
+        request.id.clone(),
+        serde_json::json!({ "ok": true, "accounts": account_ids(&accounts) }),
+    )
+}
+
+/// The `(channel, accountId)` pairs a `{ channel, accountId? }` request
+/// names: the given account, or every account of the channel.
+async fn channel_accounts_param(
+    state: &GatewayState,
+    request: &RequestFrame,
+) -> Result<Vec<(String, String)>, OcResponseFrame> {
+    let param = |name: &str| {
+        request
+            .params
+            .as_ref()
+            .and_then(|p| p.get(name))
+            .and_then(|v| v.as_str())
+    };
+    let Some(channel) = param("channel") else {
+        return Err(OcResponseFrame::error(
             request.id.clone(),
             "Missing channel param".to_string(),
             Some(-32602),
+        ));
+    };
+    let plugins = match param("accountId") {
+        Some(account_id) => state
+            .channels
+            .get_account(channel, account_id)
+            .await
+            .into_iter()
+            .collect(),
+        None => state.channels.accounts(channel).await,
+    };
+    if plugins.is_empty() {
+        return Err(OcResponseFrame::error(
+            request.id.clone(),
+            format!("Unknown channel account: {}", channel),
+            Some(-32602),
+        ));
+    }
+    Ok(plugins
+        .iter()
+        .map(|p| (p.id().to_string(), p.account_id().to_string()))
+        .collect())
+}
+
+fn account_ids(accounts: &[(String, String)]) -> Vec<&str> {
+    accounts.iter().map(|(_, id)| id.as_str()).collect()
+}
+
+/// Approve or reject a channel pairing code (`{ code }`).
+fn handle_channels_pairing_code(
+    state: &GatewayState,
+    request: &RequestFrame,
+    approve: bool,
+) -> OcResponseFrame {
+    let Some(code) = request
+        .params
+        .as_ref()
+        .and_then(|p| p.get("code"))
+        .and_then(|v| v.as_str())
+    else {
+        return OcResponseFrame::error(
+            request.id.clone(),
+            "Missing code param".to_string(),
+            Some(-32602),
+        );
+    };
+
+    let result = if approve {
+        state
+            .admission
+            .approve(code)
+            .map(|sender| serde_json::json!({ "ok": true, "sender": sender }))
+    } else {
+        state
+            .admission
+            .reject(code)
+            .map(|pending| serde_json::json!({ "ok": true, "request": pending }))
+    };
+    match result {
+        Ok(body) => OcResponseFrame::success(request.id.clone(), body),
+        Err(e) => OcResponseFrame::error(request.id.clone(), e.to_string(), Some(-32600)),
+    }
+}
+
+/// Remove a paired sender (`{ channel, senderId, accountId? }`).
+fn handle_channels_pairing_revoke(state: &GatewayState, request: &RequestFrame) -> OcResponseFrame {
+    let params = request.params.as_ref();
+    let param = |key: &str| params.and_then(|p| p.get(key)).and_then(|v| v.as_str());
+    let (Some(channel), Some(sender_id)) = (param("channel"), param("senderId")) else {
+        return OcResponseFrame::error(
+            request.id.clone(),
+            "Missing channel or senderId param".to_string(),
+            Some(-32602),
+        );
+    };
+    let account_id = param("accountId").unwrap_or("default");
+
+    match state.admission.revoke(channel, account_id, sender_id) {
+        Ok(removed) => OcResponseFrame::success(
+            request.id.clone(),
+            serde_json::json!({ "ok": true, "removed": removed }),
         ),
+        Err(e) => OcResponseFrame::error(request.id.clone(), e.to_string(), Some(-32603)),
     }
 }
 
@@ -3124,11 +3317,19 @@ async fn handle_send(state: &GatewayState, request: &RequestFrame) -> OcResponse
         .and_then(|v| v.as_str())
         .unwrap_or("");
 
-    let config = state.config.read().await;
-    match crate::channels::send_message(&config, channel, to, message).await {
-        Ok(()) => {
-            OcResponseFrame::success(request.id.clone(), serde_json::json!({ "ok": true }))
-        }
+    // Delivery happens in the background; the id identifies the queued message.
+    let outbound = crate::channels::NormalizedOutbound {
+        account_id: params
+            .get("accountId")
+            .and_then(|v| v.as_str())
+            .map(str::to_string),
+        ..crate::channels::NormalizedOutbound::text(to, message)
+    };
+    match state.channels.enqueue(channel, outbound).await {
+        Ok(id) => OcResponseFrame::success(
+            request.id.clone(),
+            serde_json::json!({ "ok": true, "queued": true, "id": id }),
+        ),
         Err(e) => OcResponseFrame::error(
             request.id.clone(),
             format!("Send failed: {}", e),
@@ -3189,6 +3390,28 @@ fn handle_exec_approval_request(
         .write()
         .insert(id.clone(), approval_req.clone());
 
+    // Ask in the chat the turn came from, where the channel can render it.
+    let origin = params
+        .and_then(|p| p.get("turnOrigin"))
+        .and_then(|v| serde_json::from_value::<TurnOrigin>(v.clone()).ok());
+    if let Some(TurnOrigin {
+        source_channel: Some(channel),
+        source_to: Some(to),
+        ..
+    }) = origin
+    {
+        let state = state.clone();
+        let approval_req = approval_req.clone();
+        tokio::spawn(async move {
+            let config = state.config.read().await.clone();
+            if let Err(e) =
+                crate::channels::send_approval_request(&config, &channel, &to, &approval_req).await
+            {
+                debug!(channel = %channel, error = %e, "Approval prompt not sent to chat");
+            }
+        });
+    }
+
     OcResponseFrame::success(request.id.clone(), approval_req)
 }
 
@@ -1,8 +1,13 @@
 //! Outbound delivery types and chat-type classification (v2026.2.26).
 //!
-//! Types and helpers for the delivery pipeline: queue recovery,
-//! session context, drain reliability, and stale message detection.
+//! Types and helpers for the delivery pipeline: retry backoff, queue
+//! recovery, session context, drain reliability, and stale message
+//! detection. The queue itself lives in `channels::outbox`.
 
+use crate::channels::ChannelManager;
+use crate::config::OutboundRetryConfig;
+
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -176,6 +181,15 @@ pub struct DeliveryRecoverySummary {
     pub skipped: usize,
 }
 
+/// Process delivery queue with head-of-line blocking fix.
+///
+/// Attempts every due entry of the channels' outbox once. A failed target
+/// is rescheduled and the remaining targets are still attempted, unlike the
+/// pre-v2026.2.26 implementation that stopped at the first failure.
+pub async fn process_delivery_queue(channels: &ChannelManager) -> DeliveryRecoverySummary {
+    channels.outbox().flush(channels).await
+}
+
 /// Maximum age for a queued message before it's considered stale (5 min).
 const STALE_MESSAGE_THRESHOLD_MS: u64 = 5 * 60 * 1000;
 
@@ -184,58 +198,23 @@ pub fn is_stale_message(queued_at_ms: u64, now_ms: u64) -> bool {
     now_ms.saturating_sub(queued_at_ms) > STALE_MESSAGE_THRESHOLD_MS
 }
 
-/// Process delivery queue with head-of-line blocking fix.
+/// Delay before retry number `attempt` (1-based) of a failed send.
 ///
-/// Unlike the pre-v2026.2.26 implementation that used `break` on failure
-/// (blocking all subsequent targets), this uses `continue` to skip failed
-/// targets and attempt delivery to remaining targets.
-pub fn process_delivery_queue(
-    targets: &[OutboundTarget],
-    attempt_info: &mut Vec<DeliveryAttemptInfo>,
-    now_ms: u64,
-) -> DeliveryRecoverySummary {
-    let mut summary = DeliveryRecoverySummary {
-        total_targets: targets.len(),
-        ..Default::default()
-    };
-
-    // Ensure attempt_info is the same length as targets.
-    attempt_info.resize_with(targets.len(), Default::default);
-
-    for (i, target) in targets.iter().enumerate() {
-        let info = &mut attempt_info[i];
-
-        // Skip suspended targets.
-        if info.suspended {
-            summary.skipped += 1;
-            continue; // v2026.2.26: continue instead of break
-        }
-
-        // Skip stale messages.
-        if let Some(last) = info.last_attempt_at {
-            if is_stale_message(last, now_ms) {
-                summary.skipped += 1;
-                continue; // v2026.2.26: continue instead of break
-            }
-        }
-
-        // Record attempt timestamp.
-        info.last_attempt_at = Some(now_ms);
-
-        // Check if target is valid (non-empty channel, not "none").
-        if target.channel.is_empty() || target.channel == "none" {
-            info.consecutive_failures += 1;
-            summary.failed += 1;
-            continue; // v2026.2.26: continue instead of break
-        }
-
-        // Delivery would happen here in full implementation.
-        // For now, mark as succeeded.
-        info.consecutive_failures = 0;
-        summary.succeeded += 1;
+/// Doubles from `minDelayMs` up to `maxDelayMs`, then spreads the result by
+/// `±jitter` (a fraction, default 0.1) so targets that failed together do
+/// not retry in lockstep.
+pub fn retry_delay_ms(retry: &OutboundRetryConfig, attempt: u32) -> u64 {
+    let exponent = attempt.saturating_sub(1).min(20);
+    let base = retry
+        .min_delay_ms
+        .saturating_mul(1 << exponent)
+        .min(retry.max_delay_ms.max(retry.min_delay_ms));
+    let jitter = retry.jitter.unwrap_or(0.1).clamp(0.0, 1.0);
+    if jitter == 0.0 {
+        return base;
     }
-
-    summary
+    let factor = 1.0 + rand::thread_rng().gen_range(-jitter..=jitter);
+    (base as f64 * factor).round() as u64
 }
 
 // ============================================================================
@@ -362,6 +341,27 @@ mod tests {
         assert_eq!(ctx.account_id.as_deref(), Some("acct-1"));
     }
 
+    #[tokio::test]
+    async fn delivery_queue_continues_past_failures() {
+        let mut config = crate::config::Config::default();
+        config.channels.webchat = Some(crate::config::WebChatConfig {
+            enabled: Some(true),
+            ..Default::default()
+        });
+        let channels = ChannelManager::detached(&config);
+        let outbox = channels.outbox();
+        let fail = crate::channels::NormalizedOutbound::text("123", "one");
+        outbox.enqueue("none", fail).await.unwrap(); // will fail
+        let ok = crate::channels::NormalizedOutbound::text("*", "two");
+        outbox.enqueue("webchat", ok).await.unwrap(); // should succeed
+
+        let summary = process_delivery_queue(&channels).await;
+        assert_eq!(summary.total_targets, 2);
+        assert_eq!(summary.failed, 1);
+        assert_eq!(summary.succeeded, 1);
+        // Key assertion: second target was attempted (no head-of-line blocking)
+    }
+
     #[test]
     fn stale_message_detection() {
         let now = 1_000_000;
@@ -374,26 +374,24 @@ mod tests {
     }
 
     #[test]
-    fn delivery_queue_continues_past_failures() {
-        let targets = vec![
-            OutboundTarget {
-                channel: "none".into(), // will fail
-                ..Default::default()
-            },
-            OutboundTarget {
-                channel: "telegram".into(), // should succeed
-                to: Some("123".into()),
-                ..Default::default()
-            },
-        ];
-        let mut info = vec![];
-        let now = 1_000_000;
-
-        let summary = process_delivery_queue(&targets, &mut info, now);
-        assert_eq!(summary.total_targets, 2);
-        assert_eq!(summary.failed, 1);
-        assert_eq!(summary.succeeded, 1);
-        // Key assertion: second target was attempted (no head-of-line blocking)
+    fn retry_delay_backs_off_with_jitter() {
+        let retry = OutboundRetryConfig {
+            attempts: 5,
+            min_delay_ms: 1000,
+            max_delay_ms: 5000,
+            jitter: Some(0.0),
+        };
+        let delays: Vec<u64> = (1..=5).map(|n| retry_delay_ms(&retry, n)).collect();
+        assert_eq!(delays, vec![1000, 2000, 4000, 5000, 5000]);
+
+        let jittered = OutboundRetryConfig {
+            jitter: Some(0.5),
+            ..retry
+        };
+        for _ in 0..50 {
+            let delay = retry_delay_ms(&jittered, 2);
+            assert!((1000..=3000).contains(&delay), "{delay}");
+        }
     }
 
     #[test]
@@ -32,8 +32,15 @@ async fn main() -> anyhow::Result<()> {
         Commands::Send(opts) => {
             info!("Sending message via channel");
             let config = Config::load(opts.config.as_deref())?;
-            mylobster::channels::send_message(&config, &opts.channel, &opts.to, &opts.message)
-                .await?;
+            let id = mylobster::channels::send_message(
+                &config,
+                &opts.channel,
+                opts.account.as_deref(),
+                &opts.to,
+                &opts.message,
+            )
+            .await?;
+            info!(id = %id, "Message delivered");
         }
         Commands::Config(opts) => {
             let config = Config::load(opts.config.as_deref())?;
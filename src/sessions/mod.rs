@@ -6,6 +6,9 @@ use dashmap::DashMap;
 use std::sync::Arc;
 use uuid::Uuid;
 
+/// Messages kept at most when `/compact` trims a session's history.
+pub const COMPACT_KEEP_MESSAGES: usize = 20;
+
 // ============================================================================
 // Turn-Source Binding (v2026.2.24)
 // ============================================================================
@@ -247,6 +250,24 @@ impl SessionStore {
         self.sessions.contains_key(key)
     }
 
+    /// Drop older history messages of a session, keeping at most the last
+    /// `keep` and starting at a user turn, so the kept history never opens
+    /// with an assistant reply or a tool result.
+    ///
+    /// Returns the message counts before and after, or `None` if the
+    /// session does not exist.
+    pub fn compact_history(&self, key: &str, keep: usize) -> Option<(usize, usize)> {
+        let entry = self.sessions.get(key)?;
+        let mut history = entry.value().inner.history.write();
+        let before = history.len();
+        let cut = (before.saturating_sub(keep)..before)
+            .find(|&i| starts_user_turn(&history[i]))
+            .unwrap_or(before);
+        history.drain(..cut);
+        entry.value().inner.info.write().updated_at = chrono::Utc::now().to_rfc3339();
+        Some((before, history.len()))
+    }
+
     /// Get an existing session or create a new one for the given key.
     pub fn get_or_create_session(&self, key: &str, config: &Config) -> SessionHandle {
         if let Some(entry) = self.sessions.get(key) {
@@ -277,6 +298,16 @@ impl SessionStore {
     }
 }
 
+/// Whether a history message opens a user turn: a user message that is
+/// not a tool result.
+fn starts_user_turn(msg: &ProviderMessage) -> bool {
+    msg.role == "user"
+        && msg.tool_call_id.is_none()
+        && !msg.content.as_array().is_some_and(|blocks| {
+            blocks.iter().any(|block| block["type"] == "tool_result")
+        })
+}
+
 // ============================================================================
 // Session Alias Canonicalization (v2026.3.11)
 // ============================================================================
@@ -428,4 +459,42 @@ mod tests {
         store.get_or_create_session("my-key", &config);
         assert_eq!(store.resolve_session("my-key"), Some("my-key".to_string()));
     }
+
+    // ====================================================================
+    // History compaction
+    // ====================================================================
+
+    #[test]
+    fn compaction_cuts_at_a_user_turn() {
+        let store = SessionStore::new(&Config::default());
+        let session = store.get_or_create_session("s", &Config::default());
+        let message = |role: &str, content: serde_json::Value| ProviderMessage {
+            role: role.to_string(),
+            content,
+            name: None,
+            tool_call_id: None,
+            tool_calls: None,
+        };
+        for turn in 0..3 {
+            session.add_message(message("user", format!("question {turn}").into()));
+            session.add_message(message("assistant", "calling a tool".into()));
+            session.add_message(message(
+                "user",
+                serde_json::json!([{ "type": "tool_result", "tool_use_id": "t1", "content": "ok" }]),
+            ));
+            session.add_message(message("assistant", format!("answer {turn}").into()));
+        }
+
+        // The last 6 messages start mid-turn; the cut moves to the next
+        // user question.
+        assert_eq!(store.compact_history("s", 6), Some((12, 4)));
+        let history = session.get_history();
+        assert_eq!(history[0].content, "question 2");
+        assert!(starts_user_turn(&history[0]));
+
+        // The RPC leaves history alone.
+        assert!(store.compact_session("s"));
+        assert_eq!(session.get_history().len(), 4);
+        assert_eq!(store.compact_history("missing", 6), None);
+    }
 }
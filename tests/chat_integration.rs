@@ -16,8 +16,8 @@ use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, MockServer, ResponseTemplate};
 
-use mylobster::channels::ChannelManager;
-use mylobster::config::{Config, ModelProviderConfig};
+use mylobster::channels::{ActiveRuns, Admission, ChannelManager, GroupHistory, InboundSink};
+use mylobster::config::{Config, ModelProviderConfig, WebChatConfig};
 use mylobster::gateway::{GatewayState, ResolvedGatewayAuth, RpcState};
 use mylobster::plugins::PluginRegistry;
 use mylobster::sessions::SessionStore;
@@ -104,7 +104,16 @@ fn build_sse_response(chunks: &[&str], input_tokens: u64, output_tokens: u64) ->
 
 /// Start a gateway with config pointing Anthropic provider at the given mock URL.
 async fn start_chat_gateway(mock_url: &str) -> (String, broadcast::Sender<()>) {
+    start_chat_gateway_with(mock_url, |_| {}).await
+}
+
+/// Like [`start_chat_gateway`], with extra configuration applied first.
+async fn start_chat_gateway_with(
+    mock_url: &str,
+    configure: impl FnOnce(&mut Config),
+) -> (String, broadcast::Sender<()>) {
     let mut config = Config::default();
+    configure(&mut config);
 
     // Point Anthropic provider at mock server
     config.models.providers.insert(
@@ -132,6 +141,10 @@ async fn start_chat_gateway(mock_url: &str) -> (String, broadcast::Sender<()>) {
         }),
         sessions: Arc::new(SessionStore::new(&config)),
         channels: Arc::new(ChannelManager::new(&config)),
+        inbound: Arc::new(InboundSink::new()),
+        admission: Arc::new(Admission::new()),
+        runs: Arc::new(ActiveRuns::default()),
+        group_history: Arc::new(GroupHistory::new()),
         plugins: Arc::new(PluginRegistry::new(&config)),
         rpc: Arc::new(RpcState::new()),
         shutdown_tx: shutdown_tx.clone(),
@@ -816,3 +829,81 @@ async fn chat_send_with_idempotency_key() {
 
     let _ = shutdown.send(());
 }
+
+// ============================================================================
+// WebChat
+// ============================================================================
+
+/// Read WebChat frames until one of type `kind` arrives.
+async fn recv_webchat_frame(stream: &mut WsRx, kind: &str) -> serde_json::Value {
+    loop {
+        let frame = recv_msg(stream).await;
+        if frame["type"] == kind {
+            return frame;
+        }
+    }
+}
+
+#[tokio::test]
+async fn webchat_visitor_round_trip_and_history_replay() {
+    let mock_server = MockServer::start().await;
+    mock_streaming_response(&mock_server, &["Hi ", "there!"]).await;
+
+    let (url, shutdown) = start_chat_gateway_with(&mock_server.uri(), |config| {
+        config.channels.webchat = Some(WebChatConfig {
+            enabled: Some(true),
+            title: Some("Support".to_string()),
+            ..Default::default()
+        });
+    })
+    .await;
+    let webchat_url = format!("{}/webchat", url);
+
+    // First visit: the gateway issues an anonymous token.
+    let (ws, _) = connect_async(webchat_url.as_str()).await.unwrap();
+    let (mut tx, mut rx) = ws.split();
+    let session = recv_msg(&mut rx).await;
+    assert_eq!(session["type"], "session");
+    assert_eq!(session["title"], "Support");
+    let token = session["token"].as_str().unwrap().to_string();
+    let visitor_id = session["visitorId"].clone();
+    let history = recv_msg(&mut rx).await;
+    assert_eq!(history["messages"], json!([]));
+
+    let frame = json!({ "type": "message", "text": "Hello", "clientId": "c1" });
+    tx.send(Message::Text(frame.to_string().into()))
+        .await
+        .unwrap();
+
+    let ack = recv_webchat_frame(&mut rx, "ack").await;
+    assert_eq!(ack["clientId"], "c1");
+    assert_eq!(ack["id"], 1);
+    let read = recv_webchat_frame(&mut rx, "read").await;
+    assert_eq!(read["id"], 1);
+    let typing = recv_webchat_frame(&mut rx, "typing").await;
+    assert_eq!(typing["state"], true);
+    let reply = loop {
+        let frame = recv_webchat_frame(&mut rx, "message").await;
+        if frame["message"]["role"] == "assistant" {
+            break frame;
+        }
+    };
+    assert_eq!(reply["message"]["text"], "Hi there!");
+    assert_eq!(reply["message"]["id"], 2);
+    drop(tx);
+
+    // Reconnecting with the token resumes the same visitor and replays
+    // everything after `since`.
+    let resume_url = format!("{}?token={}&since=1", webchat_url, token);
+    let (ws, _) = connect_async(resume_url.as_str()).await.unwrap();
+    let (_tx, mut rx) = ws.split();
+    let session = recv_msg(&mut rx).await;
+    assert_eq!(session["visitorId"], visitor_id);
+    assert!(session["token"].is_null());
+    let history = recv_msg(&mut rx).await;
+    let messages = history["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["text"], "Hi there!");
+
+    let _ = shutdown.send(());
+}
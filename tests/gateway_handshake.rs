@@ -14,7 +14,7 @@ use std::sync::Arc;
 
 use mylobster::config::Config;
 use mylobster::gateway::{ResolvedGatewayAuth, GatewayState, RpcState};
-use mylobster::channels::ChannelManager;
+use mylobster::channels::{ActiveRuns, Admission, ChannelManager, GroupHistory, InboundSink};
 use mylobster::plugins::PluginRegistry;
 use mylobster::sessions::SessionStore;
 
@@ -28,6 +28,10 @@ async fn start_test_gateway(auth: ResolvedGatewayAuth) -> (String, broadcast::Se
         auth: Arc::new(auth),
         sessions: Arc::new(SessionStore::new(&config)),
         channels: Arc::new(ChannelManager::new(&config)),
+        inbound: Arc::new(InboundSink::new()),
+        admission: Arc::new(Admission::new()),
+        runs: Arc::new(ActiveRuns::default()),
+        group_history: Arc::new(GroupHistory::new()),
         plugins: Arc::new(PluginRegistry::new(&config)),
         rpc: Arc::new(RpcState::new()),
         shutdown_tx: shutdown_tx.clone(),